@@ -0,0 +1,140 @@
+//! Invocation latency: per-call linking vs cached `InstancePre`, on-demand vs pooled.
+//!
+//! Run with `cargo bench -p tempo-x402-cartridge --bench invoke`.
+//! Besides criterion's estimates, each case prints p50/p99 over a fixed sample.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasmtime::{Engine, Linker, Module, Store};
+use x402_cartridge::abi;
+use x402_cartridge::engine::CartridgeState;
+use x402_cartridge::{CartridgeEngine, CartridgeRequest, LimitPolicy};
+
+/// Shaped like a compiled Rust cartridge: many host imports, an allocator,
+/// an init hook and a handler that sets a JSON response.
+const CARTRIDGE_WAT: &str = r#"(module
+  (import "x402" "log" (func (param i32 i32 i32)))
+  (import "x402" "kv_get" (func (param i32 i32) (result i64)))
+  (import "x402" "kv_set" (func (param i32 i32 i32 i32) (result i32)))
+  (import "x402" "header" (func (param i32 i32 i32 i32) (result i32)))
+  (import "x402" "call" (func (param i32 i32 i32 i32) (result i64)))
+  (import "x402" "response" (func $response (param i32 i32 i32 i32 i32)))
+  (memory (export "memory") 17)
+  (global $heap (mut i32) (i32.const 65536))
+  (data (i32.const 1024) "{\"ok\":true}")
+  (data (i32.const 1040) "application/json")
+  (func (export "x402_init") (result i32) (i32.const 0))
+  (func (export "x402_alloc") (param $n i32) (result i32)
+    (global.get $heap))
+  (func (export "x402_handle") (param i32 i32)
+    (call $response (i32.const 200) (i32.const 1024) (i32.const 11)
+                    (i32.const 1040) (i32.const 16))))"#;
+
+const SAMPLES: usize = 2000;
+
+fn request() -> CartridgeRequest {
+    CartridgeRequest {
+        method: "GET".to_string(),
+        path: "/".to_string(),
+        query: String::new(),
+        body: String::new(),
+        headers: HashMap::new(),
+        payment: None,
+        raw_body: None,
+    }
+}
+
+fn engine(pool_instances: u32) -> Arc<CartridgeEngine> {
+    let dir = std::env::temp_dir().join(format!("x402-cartridge-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("bench.wat");
+    std::fs::write(&path, CARTRIDGE_WAT).unwrap();
+    let policy = LimitPolicy {
+        pool_instances,
+        ..Default::default()
+    };
+    let engine = CartridgeEngine::with_policy(&dir, policy).unwrap();
+    engine.load_module("bench", &path).unwrap();
+    Arc::new(engine)
+}
+
+/// The pre-caching path: build a linker, register every host function and
+/// instantiate from scratch on each call.
+fn relink_and_call(engine: &Engine, module: &Module) {
+    let mut store = Store::new(engine, CartridgeState::default());
+    store.limiter(|s| &mut s.limiter);
+    let mut linker = Linker::new(engine);
+    abi::register_host_functions(&mut linker).unwrap();
+    let instance = linker.instantiate(&mut store, module).unwrap();
+    let handle = instance
+        .get_typed_func::<(i32, i32), ()>(&mut store, "x402_handle")
+        .unwrap();
+    handle.call(&mut store, (0, 0)).unwrap();
+}
+
+fn percentiles(mut samples: Vec<Duration>) -> (Duration, Duration) {
+    samples.sort();
+    let at = |q: f64| samples[((samples.len() as f64 * q) as usize).min(samples.len() - 1)];
+    (at(0.50), at(0.99))
+}
+
+fn report(name: &str, mut f: impl FnMut()) {
+    let samples = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    let (p50, p99) = percentiles(samples);
+    println!("{name:<28} p50 {p50:>10.2?}  p99 {p99:>10.2?}");
+}
+
+fn bench_instantiate(c: &mut Criterion) {
+    let raw = Engine::default();
+    let module = Module::new(&raw, CARTRIDGE_WAT).unwrap();
+    let mut linker = Linker::new(&raw);
+    abi::register_host_functions(&mut linker).unwrap();
+    let pre = linker.instantiate_pre(&module).unwrap();
+    let call_pre = || {
+        let mut store = Store::new(&raw, CartridgeState::default());
+        store.limiter(|s| &mut s.limiter);
+        let instance = pre.instantiate(&mut store).unwrap();
+        let handle = instance
+            .get_typed_func::<(i32, i32), ()>(&mut store, "x402_handle")
+            .unwrap();
+        handle.call(&mut store, (0, 0)).unwrap();
+    };
+
+    report("instantiate/relink", || relink_and_call(&raw, &module));
+    report("instantiate/instance_pre", call_pre);
+
+    let mut group = c.benchmark_group("instantiate");
+    group.bench_function("relink", |b| b.iter(|| relink_and_call(&raw, &module)));
+    group.bench_function("instance_pre", |b| b.iter(call_pre));
+    group.finish();
+}
+
+fn bench_execute(c: &mut Criterion) {
+    let req = request();
+    let on_demand = engine(0);
+    let pooled = engine(16);
+    let run = |e: &Arc<CartridgeEngine>| {
+        e.execute_with_composition("bench", &req, HashMap::new(), 5)
+            .unwrap();
+    };
+
+    report("execute/on_demand", || run(&on_demand));
+    report("execute/pooled", || run(&pooled));
+
+    let mut group = c.benchmark_group("execute");
+    group.bench_function("on_demand", |b| b.iter(|| run(&on_demand)));
+    group.bench_function("pooled", |b| b.iter(|| run(&pooled)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_instantiate, bench_execute);
+criterion_main!(benches);
@@ -2,23 +2,25 @@
 //!
 //! Cartridges communicate with the host via JSON strings passed through
 //! linear memory. This is deliberately simple so Flash Lite can generate
-//! correct cartridge code.
+//! correct cartridge code. Response bodies are raw bytes, so cartridges can
+//! also return images and other binary content.
 
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use wasmtime::{Caller, Linker};
+use wasmtime::{Caller, IntoFunc, Linker};
 
-use crate::engine::{CartridgeEngine, CartridgeState};
+use crate::engine::{CartridgeState, StreamEvent};
 use crate::error::CartridgeError;
+use crate::limits::ExecutionUsage;
 use crate::manifest::CartridgeRequest;
 
 /// Register all host functions on the linker.
-/// If `engine` is provided, x402_call is registered enabling cartridge-calls-cartridge.
-pub fn register_host_functions(
-    linker: &mut Linker<CartridgeState>,
-    engine: Option<Arc<CartridgeEngine>>,
-) -> Result<(), CartridgeError> {
+///
+/// Host functions only touch per-store [`CartridgeState`], so one linker is
+/// built per engine and shared by every invocation. x402_call reaches the
+/// engine through `CartridgeState::engine`.
+pub fn register_host_functions(linker: &mut Linker<CartridgeState>) -> Result<(), CartridgeError> {
     // x402_log(level: i32, msg_ptr: i32, msg_len: i32)
     linker
         .func_wrap(
@@ -108,132 +110,80 @@ pub fn register_host_functions(
              body_len: i32,
              ct_ptr: i32,
              ct_len: i32| {
-                let body = read_string(&mut caller, body_ptr, body_len).unwrap_or_default();
+                let body = read_bytes(&mut caller, body_ptr, body_len).unwrap_or_default();
                 let content_type = read_string(&mut caller, ct_ptr, ct_len).unwrap_or_default();
-                let state = caller.data_mut();
-                state.response_status = status as u16;
-                state.response_body = body;
-                state.response_content_type = if content_type.is_empty() {
-                    "application/json".to_string()
-                } else {
-                    content_type
-                };
+                set_response(&mut caller, status, body, content_type);
             },
         )
         .map_err(|e| CartridgeError::Abi(format!("failed to register response: {e}")))?;
 
     // ── x402_call: cartridge-calls-cartridge (composition primitive) ──
-    // Only registered when engine Arc is available (top-level execute_with_composition).
-    if let Some(engine_arc) = engine {
-        let engine_for_x402 = Arc::clone(&engine_arc);
-        linker
-            .func_wrap(
-                "x402",
-                "call",
-                move |mut caller: Caller<'_, CartridgeState>,
-                      slug_ptr: i32,
-                      slug_len: i32,
-                      req_ptr: i32,
-                      req_len: i32|
-                      -> i64 {
-                    let slug = match read_string(&mut caller, slug_ptr, slug_len) {
-                        Some(s) => s,
-                        None => return 0,
-                    };
-                    let req_json = match read_string(&mut caller, req_ptr, req_len) {
-                        Some(s) => s,
-                        None => return 0,
-                    };
-
-                    let depth = caller.data().call_depth;
-
-                    // Parse request JSON or construct a simple GET
-                    let request = serde_json::from_str::<CartridgeRequest>(&req_json)
-                        .unwrap_or_else(|_| CartridgeRequest {
-                            method: "GET".to_string(),
-                            path: "/".to_string(),
-                            body: req_json,
-                            headers: HashMap::new(),
-                            payment: None,
-                        });
-
-                    // Execute child cartridge with isolated KV, incremented depth
-                    let child_timeout = 10u64; // max 10s per child call
-                    match engine_for_x402.execute_with_depth(
-                        &slug,
-                        &request,
-                        HashMap::new(),
-                        child_timeout,
-                        depth + 1,
-                        Some(Arc::clone(&engine_for_x402)),
-                    ) {
-                        Ok((result, _kv)) => {
-                            let response_json =
-                                serde_json::to_string(&result).unwrap_or_default();
-                            write_bytes_to_guest(&mut caller, response_json.as_bytes())
-                        }
-                        Err(e) => {
-                            tracing::warn!(
-                                slug = slug,
-                                depth = depth + 1,
-                                error = %e,
-                                "x402_call failed"
-                            );
-                            0
-                        }
-                    }
-                },
-            )
-            .map_err(|e| CartridgeError::Abi(format!("failed to register call: {e}")))?;
-
-        // env namespace alias
-        let engine_for_env = engine_arc;
-        linker
-            .func_wrap(
-                "env",
-                "x402_call",
-                move |mut caller: Caller<'_, CartridgeState>,
-                      slug_ptr: i32,
-                      slug_len: i32,
-                      req_ptr: i32,
-                      req_len: i32|
-                      -> i64 {
-                    let slug = match read_string(&mut caller, slug_ptr, slug_len) {
-                        Some(s) => s,
-                        None => return 0,
-                    };
-                    let req_json = match read_string(&mut caller, req_ptr, req_len) {
-                        Some(s) => s,
-                        None => return 0,
-                    };
-                    let depth = caller.data().call_depth;
-                    let request = serde_json::from_str::<CartridgeRequest>(&req_json)
-                        .unwrap_or_else(|_| CartridgeRequest {
-                            method: "GET".to_string(),
-                            path: "/".to_string(),
-                            body: req_json,
-                            headers: HashMap::new(),
-                            payment: None,
-                        });
-                    match engine_for_env.execute_with_depth(
-                        &slug,
-                        &request,
-                        HashMap::new(),
-                        10,
-                        depth + 1,
-                        Some(Arc::clone(&engine_for_env)),
-                    ) {
-                        Ok((result, _kv)) => {
-                            let response_json =
-                                serde_json::to_string(&result).unwrap_or_default();
-                            write_bytes_to_guest(&mut caller, response_json.as_bytes())
-                        }
-                        Err(_) => 0,
+    // Always linked; returns 0 unless the invocation carries an engine handle
+    // (top-level execute_with_composition and its children).
+    wrap_both(
+        linker,
+        "call",
+        |mut caller: Caller<'_, CartridgeState>,
+         slug_ptr: i32,
+         slug_len: i32,
+         req_ptr: i32,
+         req_len: i32|
+         -> i64 {
+            let Some(engine) = caller.data().engine.clone() else {
+                return 0;
+            };
+            let slug = match read_string(&mut caller, slug_ptr, slug_len) {
+                Some(s) => s,
+                None => return 0,
+            };
+            let req_json = match read_string(&mut caller, req_ptr, req_len) {
+                Some(s) => s,
+                None => return 0,
+            };
+
+            let depth = caller.data().call_depth;
+
+            // Parse request JSON or construct a simple GET
+            let request =
+                serde_json::from_str::<CartridgeRequest>(&req_json).unwrap_or_else(|_| {
+                    CartridgeRequest {
+                        method: "GET".to_string(),
+                        path: "/".to_string(),
+                        query: String::new(),
+                        body: req_json,
+                        headers: HashMap::new(),
+                        payment: None,
+                        raw_body: None,
                     }
-                },
-            )
-            .map_err(|e| CartridgeError::Abi(format!("failed to register env::x402_call: {e}")))?;
-    }
+                });
+
+            // Execute child cartridge with isolated KV, incremented depth.
+            // The child runs under its own manifest limits.
+            let child_timeout = engine.child_timeout_secs(&slug);
+            match engine.execute_with_depth(
+                &slug,
+                &request,
+                HashMap::new(),
+                child_timeout,
+                depth + 1,
+                Some(Arc::clone(&engine)),
+            ) {
+                Ok((result, _kv)) => {
+                    let response_json = serde_json::to_string(&result).unwrap_or_default();
+                    write_bytes_to_guest(&mut caller, response_json.as_bytes())
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        slug = slug,
+                        depth = depth + 1,
+                        error = %e,
+                        "x402_call failed"
+                    );
+                    0
+                }
+            }
+        },
+    )?;
 
     // ── Backward-compat aliases: "env" namespace with x402_ prefix ──
     // Cartridges compiled without #[link(wasm_import_module = "x402")]
@@ -265,16 +215,9 @@ pub fn register_host_functions(
              body_len: i32,
              ct_ptr: i32,
              ct_len: i32| {
-                let body = read_string(&mut caller, body_ptr, body_len).unwrap_or_default();
+                let body = read_bytes(&mut caller, body_ptr, body_len).unwrap_or_default();
                 let content_type = read_string(&mut caller, ct_ptr, ct_len).unwrap_or_default();
-                let state = caller.data_mut();
-                state.response_status = status as u16;
-                state.response_body = body;
-                state.response_content_type = if content_type.is_empty() {
-                    "application/json".to_string()
-                } else {
-                    content_type
-                };
+                set_response(&mut caller, status, body, content_type);
             },
         )
         .map_err(|e| CartridgeError::Abi(format!("failed to register env::x402_response: {e}")))?;
@@ -335,6 +278,84 @@ pub fn register_host_functions(
             CartridgeError::Abi(format!("failed to register env::x402_payment_info: {e}"))
         })?;
 
+    // ── ABI v3: binary bodies, response headers, query, streaming ──
+    // Registered under both "x402" and the legacy "env" x402_-prefixed names.
+
+    // x402_header(name_ptr: i32, name_len: i32, val_ptr: i32, val_len: i32) -> i32
+    // Add a response header. Returns 0 on success, -1 if denied or invalid.
+    wrap_both(
+        linker,
+        "header",
+        |mut caller: Caller<'_, CartridgeState>,
+         name_ptr: i32,
+         name_len: i32,
+         val_ptr: i32,
+         val_len: i32|
+         -> i32 {
+            let name = match read_string(&mut caller, name_ptr, name_len) {
+                Some(n) => n,
+                None => return -1,
+            };
+            let value = match read_string(&mut caller, val_ptr, val_len) {
+                Some(v) => v,
+                None => return -1,
+            };
+            let state = caller.data_mut();
+            if state.stream_started
+                || state.response_headers.len() >= MAX_RESPONSE_HEADERS
+                || !is_allowed_response_header(&name, &value)
+            {
+                return -1;
+            }
+            state.response_headers.push((name, value));
+            0
+        },
+    )?;
+
+    // x402_request_body() -> i64
+    // Returns packed (ptr << 32 | len) with the raw request body, or 0 if empty.
+    wrap_both(
+        linker,
+        "request_body",
+        |mut caller: Caller<'_, CartridgeState>| -> i64 {
+            let body = caller.data().request_body.clone();
+            if body.is_empty() {
+                return 0;
+            }
+            write_bytes_to_guest(&mut caller, &body)
+        },
+    )?;
+
+    // x402_query() -> i64
+    // Returns packed (ptr << 32 | len) with the raw query string, or 0 if empty.
+    wrap_both(
+        linker,
+        "query",
+        |mut caller: Caller<'_, CartridgeState>| -> i64 {
+            let query = caller.data().request_query.clone();
+            if query.is_empty() {
+                return 0;
+            }
+            write_bytes_to_guest(&mut caller, query.as_bytes())
+        },
+    )?;
+
+    // x402_write(ptr: i32, len: i32) -> i32
+    // Stream a chunk of the response body. The first write commits status and
+    // headers. Without a streaming sink the chunk is appended to the buffered
+    // body. Returns 0 on success, -1 if the client has gone away.
+    wrap_both(
+        linker,
+        "write",
+        |mut caller: Caller<'_, CartridgeState>, ptr: i32, len: i32| -> i32 {
+            let chunk = match read_bytes(&mut caller, ptr, len) {
+                Some(c) => c,
+                None => return -1,
+            };
+            stream_chunk(&mut caller, chunk)
+        },
+    )?;
+
     // ── env::malloc / env::free — safety net for cartridges that use std::alloc ──
     // wasm32-unknown-unknown + no_std emits env::malloc/env::free imports when
     // code calls std::alloc::alloc(). Provide a bump allocator so old cartridges
@@ -375,16 +396,133 @@ pub fn register_host_functions(
     Ok(())
 }
 
-/// Read a UTF-8 string from guest linear memory at (ptr, len).
-fn read_string(caller: &mut Caller<'_, CartridgeState>, ptr: i32, len: i32) -> Option<String> {
+/// Maximum number of extra response headers a cartridge may set.
+const MAX_RESPONSE_HEADERS: usize = 32;
+
+/// Response headers owned by the node: framing, hop-by-hop, CORS and payment.
+const DENIED_RESPONSE_HEADERS: &[&str] = &[
+    "connection",
+    "content-length",
+    "content-type",
+    "host",
+    "keep-alive",
+    "payment-response",
+    "payment-required",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Header name prefixes owned by the node.
+const DENIED_RESPONSE_HEADER_PREFIXES: &[&str] = &["access-control-", "proxy-", "x-cartridge-"];
+
+/// True if a cartridge may set this response header.
+/// Rejects denylisted names and anything that could split the response.
+pub fn is_allowed_response_header(name: &str, value: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    !name.is_empty()
+        && name.len() <= 128
+        && value.len() <= 4096
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        && !value.bytes().any(|b| b == b'\r' || b == b'\n' || b == 0)
+        && !DENIED_RESPONSE_HEADERS.contains(&lower.as_str())
+        && !DENIED_RESPONSE_HEADER_PREFIXES
+            .iter()
+            .any(|p| lower.starts_with(p))
+}
+
+/// Register a host function as `x402::{name}` and `env::x402_{name}`.
+fn wrap_both<Params, Args>(
+    linker: &mut Linker<CartridgeState>,
+    name: &str,
+    func: impl IntoFunc<CartridgeState, Params, Args> + Clone,
+) -> Result<(), CartridgeError> {
+    linker
+        .func_wrap("x402", name, func.clone())
+        .map_err(|e| CartridgeError::Abi(format!("failed to register {name}: {e}")))?;
+    linker
+        .func_wrap("env", &format!("x402_{name}"), func)
+        .map_err(|e| CartridgeError::Abi(format!("failed to register env::x402_{name}: {e}")))?;
+    Ok(())
+}
+
+/// Apply an x402_response call. Once streaming has started the status is
+/// already committed, so the body is sent as a final chunk instead.
+fn set_response(
+    caller: &mut Caller<'_, CartridgeState>,
+    status: i32,
+    body: Vec<u8>,
+    content_type: String,
+) {
+    if caller.data().stream_started {
+        if !body.is_empty() {
+            stream_chunk(caller, body);
+        }
+        return;
+    }
+    let state = caller.data_mut();
+    state.response_status = status as u16;
+    state.response_body = body;
+    state.response_content_type = if content_type.is_empty() {
+        "application/json".to_string()
+    } else {
+        content_type
+    };
+}
+
+/// Send a body chunk on the streaming sink, committing the head first.
+/// Falls back to buffering when the invocation isn't streamed.
+fn stream_chunk(caller: &mut Caller<'_, CartridgeState>, chunk: Vec<u8>) -> i32 {
+    let fuel_remaining = caller.get_fuel().unwrap_or(0);
+    let state = caller.data_mut();
+    let Some(sink) = state.stream.clone() else {
+        state.response_body.extend_from_slice(&chunk);
+        return 0;
+    };
+    if !state.stream_started {
+        state.stream_started = true;
+        let head = StreamEvent::Head {
+            status: state.response_status,
+            content_type: state.response_content_type.clone(),
+            headers: state.response_headers.clone(),
+            usage: ExecutionUsage {
+                fuel_consumed: state.fuel_limit.saturating_sub(fuel_remaining),
+                peak_memory_bytes: state.limiter.peak_bytes(),
+            },
+        };
+        if sink.blocking_send(head).is_err() {
+            return -1;
+        }
+        // Anything buffered before the first write goes out first
+        let buffered = std::mem::take(&mut state.response_body);
+        if !buffered.is_empty() && sink.blocking_send(StreamEvent::Chunk(buffered)).is_err() {
+            return -1;
+        }
+    }
+    match sink.blocking_send(StreamEvent::Chunk(chunk)) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Read raw bytes from guest linear memory at (ptr, len).
+fn read_bytes(caller: &mut Caller<'_, CartridgeState>, ptr: i32, len: i32) -> Option<Vec<u8>> {
     let memory = caller.get_export("memory")?.into_memory()?;
     let data = memory.data(caller);
     let start = ptr as usize;
-    let end = start + len as usize;
+    let end = start.checked_add(len as usize)?;
     if end > data.len() {
         return None;
     }
-    String::from_utf8(data[start..end].to_vec()).ok()
+    Some(data[start..end].to_vec())
+}
+
+/// Read a UTF-8 string from guest linear memory at (ptr, len).
+fn read_string(caller: &mut Caller<'_, CartridgeState>, ptr: i32, len: i32) -> Option<String> {
+    String::from_utf8(read_bytes(caller, ptr, len)?).ok()
 }
 
 /// Write bytes into guest memory and return packed (ptr << 32 | len).
@@ -428,3 +566,40 @@ fn write_bytes_to_guest(caller: &mut Caller<'_, CartridgeState>, bytes: &[u8]) -
         0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_cache_cookie_and_redirect_headers() {
+        assert!(is_allowed_response_header("Cache-Control", "max-age=60"));
+        assert!(is_allowed_response_header(
+            "Set-Cookie",
+            "sid=abc; HttpOnly"
+        ));
+        assert!(is_allowed_response_header("Location", "/next"));
+    }
+
+    #[test]
+    fn denies_node_owned_headers() {
+        assert!(!is_allowed_response_header("Content-Length", "10"));
+        assert!(!is_allowed_response_header("transfer-encoding", "chunked"));
+        assert!(!is_allowed_response_header(
+            "Access-Control-Allow-Origin",
+            "*"
+        ));
+        assert!(!is_allowed_response_header("X-Cartridge-Cost", "0"));
+        assert!(!is_allowed_response_header("PAYMENT-RESPONSE", "x"));
+    }
+
+    #[test]
+    fn denies_response_splitting() {
+        assert!(!is_allowed_response_header(
+            "X-Evil",
+            "a\r\nSet-Cookie: x=1"
+        ));
+        assert!(!is_allowed_response_header("X Evil", "a"));
+        assert!(!is_allowed_response_header("", "a"));
+    }
+}
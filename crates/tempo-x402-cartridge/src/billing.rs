@@ -0,0 +1,131 @@
+//! Metered billing — price an invocation by the resources it actually used.
+//!
+//! A metered cartridge charges `base_amount` plus `per_megafuel_amount` for
+//! every million units of fuel and `per_mb_amount` for every megabyte of peak
+//! linear memory. All amounts are in token base units (same as `price_amount`).
+//!
+//! Payers pre-authorize a maximum. Before running, the node converts that
+//! maximum into a fuel budget so the settled cost can never exceed it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::limits::{EffectiveLimits, ExecutionUsage};
+
+const FUEL_PER_MEGAFUEL: u64 = 1_000_000;
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Metered price schedule declared in the cartridge manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeteredPricing {
+    /// Flat charge per invocation.
+    pub base_amount: u64,
+    /// Charge per 1M fuel consumed (rounded up).
+    #[serde(default)]
+    pub per_megafuel_amount: u64,
+    /// Charge per MB of peak linear memory (rounded up).
+    #[serde(default)]
+    pub per_mb_amount: u64,
+}
+
+impl MeteredPricing {
+    /// Cost of an invocation given its measured usage.
+    pub fn cost(&self, usage: &ExecutionUsage) -> u64 {
+        self.cost_for(usage.fuel_consumed, usage.peak_memory_bytes)
+    }
+
+    /// Worst-case cost of an invocation that exhausts its limits.
+    /// This is the amount a payer must pre-authorize to never be cut short.
+    pub fn max_cost(&self, limits: &EffectiveLimits) -> u64 {
+        self.cost_for(limits.fuel, limits.memory_bytes)
+    }
+
+    /// Fuel budget that keeps the cost within `max_amount`.
+    ///
+    /// Memory is charged at the worst case for the given limits, so the
+    /// remaining authorization is spent on fuel. Returns `None` if the
+    /// authorization doesn't cover the base and memory charges.
+    pub fn fuel_budget(&self, max_amount: u64, limits: &EffectiveLimits) -> Option<u64> {
+        let fixed = self
+            .base_amount
+            .saturating_add(self.memory_charge(limits.memory_bytes));
+        let remaining = max_amount.checked_sub(fixed)?;
+        if self.per_megafuel_amount == 0 {
+            return Some(limits.fuel);
+        }
+        let megafuel = remaining / self.per_megafuel_amount;
+        Some(megafuel.saturating_mul(FUEL_PER_MEGAFUEL).min(limits.fuel))
+    }
+
+    fn cost_for(&self, fuel: u64, memory_bytes: u64) -> u64 {
+        let megafuel = fuel.div_ceil(FUEL_PER_MEGAFUEL);
+        self.base_amount
+            .saturating_add(megafuel.saturating_mul(self.per_megafuel_amount))
+            .saturating_add(self.memory_charge(memory_bytes))
+    }
+
+    fn memory_charge(&self, memory_bytes: u64) -> u64 {
+        memory_bytes
+            .div_ceil(BYTES_PER_MB)
+            .saturating_mul(self.per_mb_amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pricing() -> MeteredPricing {
+        MeteredPricing {
+            base_amount: 100,
+            per_megafuel_amount: 10,
+            per_mb_amount: 1,
+        }
+    }
+
+    fn limits() -> EffectiveLimits {
+        EffectiveLimits {
+            fuel: 50_000_000,
+            memory_bytes: 4 * BYTES_PER_MB,
+            timeout_secs: 10,
+        }
+    }
+
+    #[test]
+    fn cost_rounds_up_partial_units() {
+        let usage = ExecutionUsage {
+            fuel_consumed: 1_500_000,
+            peak_memory_bytes: BYTES_PER_MB + 1,
+        };
+        // base 100 + 2 megafuel * 10 + 2 MB * 1
+        assert_eq!(pricing().cost(&usage), 122);
+    }
+
+    #[test]
+    fn max_cost_uses_limits() {
+        // base 100 + 50 megafuel * 10 + 4 MB * 1
+        assert_eq!(pricing().max_cost(&limits()), 604);
+    }
+
+    #[test]
+    fn fuel_budget_never_exceeds_authorization() {
+        let p = pricing();
+        let l = limits();
+        let budget = p.fuel_budget(250, &l).unwrap();
+        assert_eq!(budget, 14_000_000);
+        let usage = ExecutionUsage {
+            fuel_consumed: budget,
+            peak_memory_bytes: l.memory_bytes,
+        };
+        assert!(p.cost(&usage) <= 250);
+    }
+
+    #[test]
+    fn fuel_budget_rejects_insufficient_authorization() {
+        assert_eq!(pricing().fuel_budget(50, &limits()), None);
+    }
+
+    #[test]
+    fn fuel_budget_capped_by_limits() {
+        assert_eq!(pricing().fuel_budget(u64::MAX, &limits()), Some(50_000_000));
+    }
+}
@@ -1,18 +1,23 @@
 //! Cartridge compiler — wraps `cargo build` for wasip1 (backend/interactive) and
 //! wasm32-unknown-unknown + wasm-bindgen (frontend Leptos apps).
+//! The build itself runs offline in the [`sandbox`](crate::sandbox).
 
 use std::path::{Path, PathBuf};
 
 use crate::error::CartridgeError;
+use crate::sandbox::{self, SandboxCommand, SandboxError, SandboxLimits};
 
 /// Maximum compilation time (first compile downloads deps, needs more time).
 const COMPILE_TIMEOUT_SECS: u64 = 600;
 
-/// Shared target directory for backend cartridges — caches compiled deps across cartridges.
-const BACKEND_TARGET_DIR: &str = "/tmp/cartridge-build-shared";
+/// Parent of the per-cartridge target directories for backend cartridges.
+const BACKEND_TARGET_ROOT: &str = "/tmp/cartridge-build";
 
-/// Shared target directory for frontend cartridges — caches compiled deps across cartridges.
-const FRONTEND_TARGET_DIR: &str = "/tmp/cartridge-frontend-build-shared";
+/// Parent of the per-cartridge target directories for frontend cartridges.
+const FRONTEND_TARGET_ROOT: &str = "/tmp/cartridge-frontend-build";
+
+/// Where the target directory is mounted inside the build sandbox.
+const SANDBOX_TARGET_DIR: &str = "/target";
 
 /// Compile a cartridge from its source directory.
 ///
@@ -52,34 +57,12 @@ pub async fn compile_cartridge(
             .await;
     }
 
-    // Shared target dir — caches compiled deps (leptos, serde, etc.) across all cartridges.
-    // First compilation downloads + builds deps (~2-5 min), subsequent ones reuse cached artifacts.
-    let target_dir = BACKEND_TARGET_DIR.to_string();
+    // Per-cartridge target dir: rebuilds reuse compiled deps, but a cartridge's
+    // build scripts can never touch another cartridge's artifacts.
+    let target_dir = cartridge_target_dir(BACKEND_TARGET_ROOT, source_dir);
 
     // Build with wasm32-unknown-unknown target
-    let output = tokio::time::timeout(
-        std::time::Duration::from_secs(COMPILE_TIMEOUT_SECS),
-        tokio::process::Command::new("cargo")
-            .args([
-                "build",
-                "--target",
-                "wasm32-unknown-unknown",
-                "--release",
-                "--manifest-path",
-            ])
-            .arg(cargo_toml.to_string_lossy().as_ref())
-            .env("CARGO_TARGET_DIR", &target_dir)
-            .env("RUSTUP_HOME", "/usr/local/rustup")
-            .env("CARGO_HOME", "/usr/local/cargo")
-            .output(),
-    )
-    .await
-    .map_err(|_| {
-        CartridgeError::CompilationFailed(format!(
-            "compilation timed out after {COMPILE_TIMEOUT_SECS}s"
-        ))
-    })?
-    .map_err(|e| CartridgeError::CompilationFailed(format!("cargo failed to start: {e}")))?;
+    let output = build_wasm(source_dir, &target_dir).await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -131,13 +114,64 @@ pub async fn compile_cartridge(
             }
         }
     }
-    // NOTE: Do NOT delete shared target dir — it caches compiled deps for future cartridges.
+    // NOTE: Do NOT delete the target dir — it caches compiled deps for rebuilds.
 
     wasm_path.ok_or_else(|| {
         CartridgeError::CompilationFailed(format!("no .wasm binary found in {}/wasm32-unknown-unknown/release", target_dir))
     })
 }
 
+/// Target directory for the cartridge at `source_dir`: `{root}/{dir name}`.
+/// Each cartridge gets its own, since anything writable by one build's
+/// scripts and proc-macros is writable by that untrusted code.
+fn cartridge_target_dir(root: &str, source_dir: &Path) -> String {
+    let name = source_dir.file_name().unwrap_or_default().to_string_lossy();
+    format!("{root}/{name}")
+}
+
+/// Run `cargo build --target wasm32-unknown-unknown --release` for `source_dir`
+/// inside the sandbox.
+///
+/// Build scripts and proc-macros are untrusted code, so only the dependency
+/// download runs on the host (`cargo fetch`, which executes nothing). The
+/// build itself runs offline on a copy of the sources, and only the
+/// cartridge's own target dir is writable.
+async fn build_wasm(
+    source_dir: &Path,
+    target_dir: &str,
+) -> Result<std::process::Output, CartridgeError> {
+    sandbox::fetch_dependencies(&source_dir.join("Cargo.toml"))
+        .await
+        .map_err(|e| CartridgeError::CompilationFailed(e.to_string()))?;
+
+    SandboxCommand::new("cargo")
+        .args([
+            "build",
+            "--offline",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--release",
+        ])
+        .env("CARGO_TARGET_DIR", SANDBOX_TARGET_DIR)
+        .dir(source_dir, "")?
+        .writable(target_dir, SANDBOX_TARGET_DIR)
+        .limits(SandboxLimits {
+            cpu_secs: COMPILE_TIMEOUT_SECS,
+            memory_bytes: 8 << 30,
+            max_file_bytes: 2 << 30,
+            timeout_secs: COMPILE_TIMEOUT_SECS,
+            ..SandboxLimits::default()
+        })
+        .output()
+        .await
+        .map_err(|e| match e {
+            SandboxError::Timeout(secs) => {
+                CartridgeError::CompilationFailed(format!("compilation timed out after {secs}s"))
+            }
+            e => CartridgeError::CompilationFailed(e.to_string()),
+        })
+}
+
 /// Generate the default Cargo.toml for a new cartridge.
 /// NOTE: No dependencies needed — the host ABI uses raw extern "C" FFI.
 pub fn default_cargo_toml(slug: &str) -> String {
@@ -191,6 +225,14 @@ extern "C" {
     fn payment_info() -> i64;
     /// Call another cartridge by slug. Returns packed (ptr << 32 | len) with JSON response, or 0 on error.
     fn call(slug_ptr: *const u8, slug_len: i32, req_ptr: *const u8, req_len: i32) -> i64;
+    /// Add a response header (e.g. Cache-Control, Set-Cookie, Location). Returns 0 or -1 if denied.
+    fn header(name_ptr: *const u8, name_len: i32, val_ptr: *const u8, val_len: i32) -> i32;
+    /// Raw request body bytes. Returns packed (ptr << 32 | len), or 0 if empty.
+    fn request_body() -> i64;
+    /// Raw query string (no leading '?'). Returns packed (ptr << 32 | len), or 0 if empty.
+    fn query() -> i64;
+    /// Stream a chunk of the response body. The first write sends status + headers.
+    fn write(ptr: *const u8, len: i32) -> i32;
 }
 
 /// Helper: send a response back to the host.
@@ -214,7 +256,7 @@ fn host_log(level: i32, msg: &str) {
 /// Entry point: handle an HTTP request.
 ///
 /// `request_ptr` points to a JSON string in memory:
-/// {"method": "GET", "path": "/", "body": "", "headers": {}}
+/// {"method": "GET", "path": "/", "query": "", "body": "", "headers": {}}
 #[no_mangle]
 pub extern "C" fn x402_handle(request_ptr: *const u8, request_len: i32) {
     host_log(1, "__SLUG__ cartridge invoked");
@@ -453,34 +495,12 @@ pub async fn compile_frontend_cartridge(
             .await;
     }
 
-    // Shared target dir — caches compiled deps across all frontend cartridges.
-    // First compilation downloads + builds ~100 crates (~3-8 min), subsequent ones are fast.
-    let target_dir = FRONTEND_TARGET_DIR.to_string();
+    // Per-cartridge target dir, as for backend cartridges. The first compilation
+    // builds ~100 crates (~3-8 min); rebuilds of the same cartridge are fast.
+    let target_dir = cartridge_target_dir(FRONTEND_TARGET_ROOT, source_dir);
 
     // Step 1: cargo build
-    let output = tokio::time::timeout(
-        std::time::Duration::from_secs(COMPILE_TIMEOUT_SECS),
-        tokio::process::Command::new("cargo")
-            .args([
-                "build",
-                "--target",
-                "wasm32-unknown-unknown",
-                "--release",
-                "--manifest-path",
-            ])
-            .arg(cargo_toml.to_string_lossy().as_ref())
-            .env("CARGO_TARGET_DIR", &target_dir)
-            .env("RUSTUP_HOME", "/usr/local/rustup")
-            .env("CARGO_HOME", "/usr/local/cargo")
-            .output(),
-    )
-    .await
-    .map_err(|_| {
-        CartridgeError::CompilationFailed(format!(
-            "frontend compilation timed out after {COMPILE_TIMEOUT_SECS}s"
-        ))
-    })?
-    .map_err(|e| CartridgeError::CompilationFailed(format!("cargo failed to start: {e}")))?;
+    let output = build_wasm(source_dir, &target_dir).await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -492,7 +512,7 @@ pub async fn compile_frontend_cartridge(
         return Err(CartridgeError::CompilationFailed(truncated));
     }
 
-    // Find the .wasm binary by crate name (shared target dir may have multiple)
+    // Find the .wasm binary by crate name
     let release_dir = format!("{}/wasm32-unknown-unknown/release", target_dir);
     let slug = source_dir
         .file_name()
@@ -582,7 +602,7 @@ pub async fn compile_frontend_cartridge(
     }
     tracing::info!(files = pkg_files, dir = %pkg_dir.display(), "Frontend: wasm-bindgen output verified");
 
-    // NOTE: Do NOT delete shared target dir — it caches compiled deps for future cartridges.
+    // NOTE: Do NOT delete the target dir — it caches compiled deps for rebuilds.
 
     Ok(pkg_dir)
 }
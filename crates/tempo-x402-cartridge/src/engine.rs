@@ -1,7 +1,11 @@
 //! CartridgeEngine — WASM module loading, caching, and execution.
 //!
-//! Pre-compiles .wasm files at load time and caches them.
+//! Pre-compiles .wasm files at load time and caches them together with an
+//! [`InstancePre`] — imports resolved against a linker built once per engine —
+//! so a request only pays for instantiation, not linking.
 //! Each request creates a fresh Store with its own KV state and limits.
+//! With `pool_instances` set, instances come from wasmtime's pooling allocator.
+//! Limits come from the cartridge manifest, clamped to the node's [`LimitPolicy`].
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -9,27 +13,66 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use dashmap::DashMap;
-use wasmtime::{Engine, Linker, Module, Store};
+use wasmtime::{
+    Engine, Instance, InstanceAllocationStrategy, InstancePre, Linker, Module,
+    PoolingAllocationConfig, Store,
+};
 
 use crate::abi;
-use crate::error::CartridgeError;
+use crate::error::{CartridgeError, ExecutionFailure};
+use crate::limits::{EffectiveLimits, ExecutionUsage, LimitPolicy, MemoryLimiter, ResourceLimits};
 use crate::manifest::{CartridgeRequest, CartridgeResult, PaymentContext};
 
 /// Maximum nesting depth for cartridge-calls-cartridge.
 const MAX_CALL_DEPTH: u32 = 3;
 
+/// Timeout for nested x402_call invocations when the child declares none.
+const DEFAULT_CHILD_TIMEOUT_SECS: u64 = 10;
+
+/// Epoch tick interval. Wall-clock timeouts are enforced in whole ticks.
+const EPOCH_TICK_MS: u64 = 10;
+
+/// Output emitted by a streaming cartridge via x402_write.
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// Status and headers, committed by the first x402_write.
+    Head {
+        status: u16,
+        content_type: String,
+        headers: Vec<(String, String)>,
+        /// Resources consumed before the head was committed.
+        usage: ExecutionUsage,
+    },
+    /// A chunk of response body.
+    Chunk(Vec<u8>),
+}
+
 /// Per-request state passed into WASM host functions.
 pub struct CartridgeState {
     /// Cartridge-scoped key-value store (in-memory per request; persisted externally).
     pub kv_store: HashMap<String, String>,
     /// Payment context for the current request.
     pub payment: Option<PaymentContext>,
-    /// Response set by the cartridge via x402_response.
+    /// Raw request body and query string (served by x402_request_body / x402_query).
+    pub request_body: Vec<u8>,
+    pub request_query: String,
+    /// Response set by the cartridge via x402_response / x402_header.
     pub response_status: u16,
-    pub response_body: String,
+    pub response_body: Vec<u8>,
     pub response_content_type: String,
+    pub response_headers: Vec<(String, String)>,
+    /// Sink for x402_write. When None, writes are buffered into `response_body`.
+    pub stream: Option<tokio::sync::mpsc::Sender<StreamEvent>>,
+    /// True once the head has been sent on `stream`; later header changes are ignored.
+    pub stream_started: bool,
+    /// Fuel the store started with, to report usage mid-invocation.
+    pub fuel_limit: u64,
     /// Current nesting depth for x402_call (0 = top-level request).
     pub call_depth: u32,
+    /// Enforces the memory limit and records peak usage.
+    pub limiter: MemoryLimiter,
+    /// Engine handle for nested x402_call. None disables composition.
+    pub engine: Option<Arc<CartridgeEngine>>,
 }
 
 impl Default for CartridgeState {
@@ -37,42 +80,108 @@ impl Default for CartridgeState {
         Self {
             kv_store: HashMap::new(),
             payment: None,
+            request_body: Vec::new(),
+            request_query: String::new(),
             response_status: 200,
-            response_body: String::new(),
+            response_body: Vec::new(),
             response_content_type: "application/json".to_string(),
+            response_headers: Vec::new(),
+            stream: None,
+            stream_started: false,
+            fuel_limit: 0,
             call_depth: 0,
+            limiter: MemoryLimiter::default(),
+            engine: None,
         }
     }
 }
 
-/// Maximum WASM memory per cartridge (64MB).
-const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+/// Per-invocation knobs that top-level callers may set.
+#[derive(Default)]
+struct RunOptions {
+    /// Fuel cap below the cartridge's limit (metered billing).
+    fuel_budget: Option<u64>,
+    /// Streaming sink for x402_write.
+    stream: Option<tokio::sync::mpsc::Sender<StreamEvent>>,
+    /// Engine handle that enables nested x402_call.
+    engine_arc: Option<Arc<CartridgeEngine>>,
+}
 
-/// Maximum fuel (instruction count) per invocation.
-const MAX_FUEL: u64 = 100_000_000;
+/// A compiled module and its pre-linked instantiation template.
+#[derive(Clone)]
+struct LoadedModule {
+    module: Module,
+    /// None if the module imports something the host doesn't provide;
+    /// instantiation then goes through the linker to report the error.
+    pre: Option<InstancePre<CartridgeState>>,
+}
 
 /// The cartridge runtime engine.
 pub struct CartridgeEngine {
     engine: Engine,
-    /// Pre-compiled module cache: slug → Module.
-    modules: DashMap<String, Module>,
+    /// Host functions, registered once and shared by all modules.
+    linker: Linker<CartridgeState>,
+    /// Pre-compiled module cache: slug → module + InstancePre.
+    modules: DashMap<String, LoadedModule>,
+    /// Manifest-declared resource limits: slug → limits.
+    limits: DashMap<String, ResourceLimits>,
+    /// Node-wide ceilings applied on top of manifest limits.
+    policy: LimitPolicy,
     /// Base directory for cartridge storage.
     pub cartridge_dir: PathBuf,
 }
 
 impl CartridgeEngine {
-    /// Create a new cartridge engine.
+    /// Create a new cartridge engine with the default limit policy.
     pub fn new(cartridge_dir: impl Into<PathBuf>) -> Result<Self, CartridgeError> {
+        Self::with_policy(cartridge_dir, LimitPolicy::default())
+    }
+
+    /// Create a new cartridge engine with explicit node-wide limit ceilings.
+    pub fn with_policy(
+        cartridge_dir: impl Into<PathBuf>,
+        policy: LimitPolicy,
+    ) -> Result<Self, CartridgeError> {
         let mut config = wasmtime::Config::new();
         config.consume_fuel(true);
+        config.epoch_interruption(true);
         config.wasm_memory64(false);
+        if policy.pool_instances > 0 {
+            // Slots are sized to the policy ceiling; the per-store limiter
+            // still enforces each cartridge's own (smaller) memory limit.
+            let mut pool = PoolingAllocationConfig::default();
+            pool.total_core_instances(policy.pool_instances)
+                .total_memories(policy.pool_instances)
+                .total_tables(policy.pool_instances)
+                .max_memory_size(usize::try_from(policy.max_memory_bytes).unwrap_or(usize::MAX));
+            config.allocation_strategy(InstanceAllocationStrategy::Pooling(pool));
+        }
 
         let engine = Engine::new(&config)
             .map_err(|e| CartridgeError::ModuleLoadFailed(format!("engine init: {e}")))?;
+        let mut linker = Linker::new(&engine);
+        abi::register_host_functions(&mut linker)?;
+
+        // Advance the epoch so stores past their deadline trap. The ticker
+        // exits once the engine is dropped.
+        let weak = engine.weak();
+        std::thread::Builder::new()
+            .name("cartridge-epoch".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(std::time::Duration::from_millis(EPOCH_TICK_MS));
+                match weak.upgrade() {
+                    Some(engine) => engine.increment_epoch(),
+                    None => break,
+                }
+            })
+            .map_err(|e| CartridgeError::ModuleLoadFailed(format!("epoch ticker: {e}")))?;
 
         Ok(Self {
             engine,
+            linker,
             modules: DashMap::new(),
+            limits: DashMap::new(),
+            policy,
             cartridge_dir: cartridge_dir.into(),
         })
     }
@@ -82,7 +191,15 @@ impl CartridgeEngine {
         let wasm_bytes = std::fs::read(wasm_path)?;
         let module = Module::new(&self.engine, &wasm_bytes)
             .map_err(|e| CartridgeError::ModuleLoadFailed(format!("{slug}: {e}")))?;
-        self.modules.insert(slug.to_string(), module);
+        let pre = match self.linker.instantiate_pre(&module) {
+            Ok(pre) => Some(pre),
+            Err(e) => {
+                tracing::warn!(slug, error = %e, "Cartridge imports unresolved; not pre-linked");
+                None
+            }
+        };
+        self.modules
+            .insert(slug.to_string(), LoadedModule { module, pre });
         tracing::info!(slug, path = %wasm_path.display(), "Cartridge module loaded");
         Ok(())
     }
@@ -90,18 +207,55 @@ impl CartridgeEngine {
     /// Unload a cached module.
     pub fn unload_module(&self, slug: &str) {
         self.modules.remove(slug);
+        self.limits.remove(slug);
+    }
+
+    /// Set the manifest-declared resource limits for a cartridge.
+    pub fn set_limits(&self, slug: &str, limits: ResourceLimits) {
+        self.limits.insert(slug.to_string(), limits);
+    }
+
+    /// Node-wide limit ceilings.
+    pub fn policy(&self) -> &LimitPolicy {
+        &self.policy
+    }
+
+    /// Effective limits for a cartridge: its manifest limits clamped to the policy.
+    pub fn limits_for(&self, slug: &str) -> EffectiveLimits {
+        self.limits
+            .get(slug)
+            .map(|l| l.resolve(&self.policy))
+            .unwrap_or_else(|| ResourceLimits::default().resolve(&self.policy))
+    }
+
+    /// Timeout for a nested x402_call into `slug`: the child's declared
+    /// timeout, or a short default so a child can't hold its parent for long.
+    pub fn child_timeout_secs(&self, slug: &str) -> u64 {
+        self.limits
+            .get(slug)
+            .and_then(|l| l.timeout_secs)
+            .unwrap_or(DEFAULT_CHILD_TIMEOUT_SECS)
     }
 
     /// Atomic hot-swap: unload old module and load new one.
     /// If loading fails, the old module is already gone (no rollback).
+    /// Declared limits are kept across the swap.
     pub fn replace_module(&self, slug: &str, wasm_path: &Path) -> Result<(), CartridgeError> {
-        self.unload_module(slug);
+        self.modules.remove(slug);
         self.load_module(slug, wasm_path)
     }
 
     /// Unload all cached modules.
     pub fn unload_all(&self) {
         self.modules.clear();
+        self.limits.clear();
+    }
+
+    /// Export names of a loaded module.
+    pub fn module_exports(&self, slug: &str) -> Option<Vec<String>> {
+        self.modules
+            .get(slug)
+            .map(|m| m.module.exports().map(|e| e.name().to_string()).collect())
     }
 
     /// List loaded module slugs.
@@ -132,6 +286,50 @@ impl CartridgeEngine {
         self.execute_with_depth(slug, request, kv_preload, timeout_secs, 0, Some(Arc::clone(self)))
     }
 
+    /// Execute a metered invocation with composition support.
+    /// `fuel_budget` further caps fuel below the cartridge's limit, so the
+    /// cost never exceeds what the payer pre-authorized. A failed invocation
+    /// reports what it consumed (the whole budget if fuel ran out) so it can
+    /// still be billed.
+    pub fn execute_metered(
+        self: &Arc<Self>,
+        slug: &str,
+        request: &CartridgeRequest,
+        kv_preload: HashMap<String, String>,
+        timeout_secs: u64,
+        fuel_budget: u64,
+    ) -> Result<(CartridgeResult, HashMap<String, String>), ExecutionFailure> {
+        let opts = RunOptions {
+            fuel_budget: Some(fuel_budget),
+            engine_arc: Some(Arc::clone(self)),
+            ..Default::default()
+        };
+        self.run(slug, request, kv_preload, timeout_secs, 0, opts)
+    }
+
+    /// Execute with composition support, streaming x402_write output to `sink`.
+    ///
+    /// The first x402_write sends a [`StreamEvent::Head`] with the status and
+    /// headers set so far, followed by body chunks. If the cartridge never
+    /// writes, nothing is sent and the returned result carries the full body.
+    /// The sink is dropped when execution finishes.
+    pub fn execute_streaming(
+        self: &Arc<Self>,
+        slug: &str,
+        request: &CartridgeRequest,
+        kv_preload: HashMap<String, String>,
+        timeout_secs: u64,
+        sink: tokio::sync::mpsc::Sender<StreamEvent>,
+    ) -> Result<(CartridgeResult, HashMap<String, String>), CartridgeError> {
+        let opts = RunOptions {
+            stream: Some(sink),
+            engine_arc: Some(Arc::clone(self)),
+            ..Default::default()
+        };
+        self.run(slug, request, kv_preload, timeout_secs, 0, opts)
+            .map_err(|f| f.error)
+    }
+
     /// Execute with call depth tracking and optional engine for nested x402_call.
     pub fn execute_with_depth(
         &self,
@@ -142,147 +340,116 @@ impl CartridgeEngine {
         call_depth: u32,
         engine_arc: Option<Arc<CartridgeEngine>>,
     ) -> Result<(CartridgeResult, HashMap<String, String>), CartridgeError> {
+        let opts = RunOptions {
+            engine_arc,
+            ..Default::default()
+        };
+        self.run(slug, request, kv_preload, timeout_secs, call_depth, opts)
+            .map_err(|f| f.error)
+    }
+
+    fn run(
+        &self,
+        slug: &str,
+        request: &CartridgeRequest,
+        kv_preload: HashMap<String, String>,
+        timeout_secs: u64,
+        call_depth: u32,
+        opts: RunOptions,
+    ) -> Result<(CartridgeResult, HashMap<String, String>), ExecutionFailure> {
         if call_depth > MAX_CALL_DEPTH {
             return Err(CartridgeError::ExecutionFailed(format!(
                 "max nesting depth ({MAX_CALL_DEPTH}) exceeded"
-            )));
+            ))
+            .into());
         }
 
-        let module = self
-            .modules
-            .get(slug)
-            .ok_or_else(|| CartridgeError::NotFound(slug.to_string()))?;
-
         let start = Instant::now();
 
+        let limits = self.limits_for(slug);
+        let fuel_limit = opts.fuel_budget.map_or(limits.fuel, |b| b.min(limits.fuel));
+        let timeout_secs = timeout_secs.min(limits.timeout_secs);
+
         // Create per-request store with limits
         let state = CartridgeState {
             kv_store: kv_preload,
             payment: request.payment.clone(),
+            request_body: request
+                .raw_body
+                .clone()
+                .unwrap_or_else(|| request.body.as_bytes().to_vec()),
+            request_query: request.query.clone(),
             call_depth,
+            limiter: MemoryLimiter::new(limits.memory_bytes),
+            stream: opts.stream,
+            fuel_limit,
+            engine: opts.engine_arc,
             ..Default::default()
         };
 
-        let mut store = Store::new(&self.engine, state);
-        store
-            .set_fuel(MAX_FUEL)
-            .map_err(|e| CartridgeError::ExecutionFailed(format!("fuel setup: {e}")))?;
-
-        // Create linker and register host functions (including x402_call if engine available)
-        let mut linker = Linker::new(&self.engine);
-        abi::register_host_functions(&mut linker, engine_arc)?;
-
-        // Instantiate
-        let instance = linker
-            .instantiate(&mut store, &module)
-            .map_err(|e| CartridgeError::ExecutionFailed(format!("instantiate: {e}")))?;
-
-        // Call x402_init if exported
-        if let Ok(init_fn) = instance.get_typed_func::<(), i32>(&mut store, "x402_init") {
-            let result = init_fn
-                .call(&mut store, ())
-                .map_err(|e| CartridgeError::ExecutionFailed(format!("x402_init: {e}")))?;
-            if result != 0 {
-                return Err(CartridgeError::ExecutionFailed(format!(
-                    "x402_init returned {result}"
-                )));
-            }
-        }
-
-        // Prepare request data in guest memory
-        let memory = instance
-            .get_memory(&mut store, "memory")
-            .ok_or_else(|| CartridgeError::Abi("no memory export".to_string()))?;
-
-        // Check memory size limit
-        if memory.data_size(&store) > MAX_MEMORY_BYTES {
-            return Err(CartridgeError::ResourceLimit(
-                "memory exceeds 64MB".to_string(),
-            ));
-        }
+        let (mut store, instance) = self.instantiate(slug, state, fuel_limit, timeout_secs)?;
+        let handled = handle(&mut store, &instance, request, &limits, timeout_secs);
 
-        // Serialize request as JSON and write to guest memory
-        let request_json = serde_json::to_string(request)?;
-        let request_bytes = request_json.as_bytes();
-
-        // Try to get the guest allocator
-        let alloc_fn = instance
-            .get_typed_func::<i32, i32>(&mut store, "x402_alloc")
-            .ok();
-
-        let req_ptr = if let Some(ref alloc) = alloc_fn {
-            let ptr = alloc
-                .call(&mut store, request_bytes.len() as i32)
-                .map_err(|e| CartridgeError::Abi(format!("alloc: {e}")))?;
-            ptr as usize
-        } else {
-            // Write at beginning of memory (simple cartridges)
-            0
+        // Measured whether or not the call succeeded: a guest that traps or
+        // runs out of fuel has still consumed it.
+        let duration_ms = start.elapsed().as_millis() as u64;
+        let fuel_remaining = store.get_fuel().unwrap_or(0);
+        let state = store.data();
+        let usage = ExecutionUsage {
+            fuel_consumed: fuel_limit.saturating_sub(fuel_remaining),
+            peak_memory_bytes: state.limiter.peak_bytes(),
         };
-
-        // Write request JSON to guest memory
-        let mem_data = memory.data_mut(&mut store);
-        if req_ptr + request_bytes.len() > mem_data.len() {
-            return Err(CartridgeError::ResourceLimit(
-                "request too large for guest memory".to_string(),
-            ));
+        if let Err(error) = handled {
+            return Err(ExecutionFailure { error, usage });
         }
-        mem_data[req_ptr..req_ptr + request_bytes.len()].copy_from_slice(request_bytes);
-
-        // Call x402_handle(request_ptr: i32, request_len: i32)
-        let handle_fn = instance
-            .get_typed_func::<(i32, i32), ()>(&mut store, "x402_handle")
-            .map_err(|e| CartridgeError::Abi(format!("no x402_handle export: {e}")))?;
-
-        // Execute with timeout
-        let result = std::thread::scope(|s| {
-            let handle = s
-                .spawn(|| handle_fn.call(&mut store, (req_ptr as i32, request_bytes.len() as i32)));
-
-            // Wait with timeout
-            let deadline = std::time::Duration::from_secs(timeout_secs);
-            let start_wait = Instant::now();
-            loop {
-                if handle.is_finished() {
-                    return handle.join().unwrap();
-                }
-                if start_wait.elapsed() > deadline {
-                    return Err(wasmtime::Error::msg("execution timed out"));
-                }
-                std::thread::sleep(std::time::Duration::from_millis(10));
-            }
-        });
-
-        match result {
-            Ok(()) => {}
-            Err(e) => {
-                let msg = e.to_string();
-                if msg.contains("timed out") {
-                    return Err(CartridgeError::Timeout(timeout_secs));
-                }
-                if msg.contains("fuel") {
-                    return Err(CartridgeError::ResourceLimit(
-                        "CPU fuel exhausted".to_string(),
-                    ));
-                }
-                return Err(CartridgeError::ExecutionFailed(msg));
-            }
-        }
-
-        let duration_ms = start.elapsed().as_millis() as u64;
 
         // Read response and KV from store state
-        let state = store.data();
         let result = CartridgeResult {
             status: state.response_status,
             body: state.response_body.clone(),
             content_type: state.response_content_type.clone(),
+            headers: state.response_headers.clone(),
             duration_ms,
+            usage,
+            streamed: state.stream_started,
         };
         let kv_out = state.kv_store.clone();
         Ok((result, kv_out))
     }
 
+    /// Instantiate a loaded module in a fresh store with the given fuel and
+    /// wall-clock deadline. Fails if the module's initial memory exceeds the
+    /// limiter's ceiling. Does not call any exports.
+    pub(crate) fn instantiate(
+        &self,
+        slug: &str,
+        state: CartridgeState,
+        fuel: u64,
+        timeout_secs: u64,
+    ) -> Result<(Store<CartridgeState>, Instance), CartridgeError> {
+        // Clone out of the map so a concurrent hot-swap never waits on this call.
+        let loaded = self
+            .modules
+            .get(slug)
+            .map(|m| m.clone())
+            .ok_or_else(|| CartridgeError::NotFound(slug.to_string()))?;
+
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|s| &mut s.limiter);
+        store
+            .set_fuel(fuel)
+            .map_err(|e| CartridgeError::ExecutionFailed(format!("fuel setup: {e}")))?;
+        // Covers x402_init as well as x402_handle.
+        store.set_epoch_deadline((timeout_secs * 1000).div_ceil(EPOCH_TICK_MS).max(1));
+
+        let instance = match loaded.pre {
+            Some(ref pre) => pre.instantiate(&mut store),
+            None => self.linker.instantiate(&mut store, &loaded.module),
+        }
+        .map_err(|e| CartridgeError::ExecutionFailed(format!("instantiate: {e}")))?;
+        Ok((store, instance))
+    }
+
     /// Compute SHA-256 hash of a WASM binary file.
     pub fn hash_wasm(path: &Path) -> Result<String, CartridgeError> {
         use sha2::{Digest, Sha256};
@@ -291,3 +458,302 @@ impl CartridgeEngine {
         Ok(format!("{:x}", hash))
     }
 }
+
+/// Write `request` into guest memory and call `x402_init` (if exported) and
+/// `x402_handle`. The response is left in the store's state.
+fn handle(
+    store: &mut Store<CartridgeState>,
+    instance: &Instance,
+    request: &CartridgeRequest,
+    limits: &EffectiveLimits,
+    timeout_secs: u64,
+) -> Result<(), CartridgeError> {
+    // Call x402_init if exported
+    if let Ok(init_fn) = instance.get_typed_func::<(), i32>(&mut *store, "x402_init") {
+        let result = init_fn
+            .call(&mut *store, ())
+            .map_err(|e| CartridgeError::ExecutionFailed(format!("x402_init: {e}")))?;
+        if result != 0 {
+            return Err(CartridgeError::ExecutionFailed(format!(
+                "x402_init returned {result}"
+            )));
+        }
+    }
+
+    // Prepare request data in guest memory
+    let memory = instance
+        .get_memory(&mut *store, "memory")
+        .ok_or_else(|| CartridgeError::Abi("no memory export".to_string()))?;
+
+    // Check memory size limit
+    if memory.data_size(&*store) as u64 > limits.memory_bytes {
+        return Err(CartridgeError::ResourceLimit(format!(
+            "memory exceeds {}MB",
+            limits.memory_bytes / (1024 * 1024)
+        )));
+    }
+
+    // Serialize request as JSON and write to guest memory
+    let request_json = serde_json::to_string(request)?;
+    let request_bytes = request_json.as_bytes();
+
+    // Try to get the guest allocator
+    let alloc_fn = instance
+        .get_typed_func::<i32, i32>(&mut *store, "x402_alloc")
+        .ok();
+
+    let req_ptr = if let Some(ref alloc) = alloc_fn {
+        let ptr = alloc
+            .call(&mut *store, request_bytes.len() as i32)
+            .map_err(|e| CartridgeError::Abi(format!("alloc: {e}")))?;
+        ptr as usize
+    } else {
+        // Write at beginning of memory (simple cartridges)
+        0
+    };
+
+    // Write request JSON to guest memory
+    let mem_data = memory.data_mut(&mut *store);
+    if req_ptr + request_bytes.len() > mem_data.len() {
+        return Err(CartridgeError::ResourceLimit(
+            "request too large for guest memory".to_string(),
+        ));
+    }
+    mem_data[req_ptr..req_ptr + request_bytes.len()].copy_from_slice(request_bytes);
+
+    // Call x402_handle(request_ptr: i32, request_len: i32)
+    let handle_fn = instance
+        .get_typed_func::<(i32, i32), ()>(&mut *store, "x402_handle")
+        .map_err(|e| CartridgeError::Abi(format!("no x402_handle export: {e}")))?;
+
+    // Execute; the store's epoch deadline enforces the timeout.
+    let result = handle_fn.call(&mut *store, (req_ptr as i32, request_bytes.len() as i32));
+
+    result.map_err(|e| trap_error(e, timeout_secs))
+}
+
+/// Map a guest call failure to a cartridge error: epoch interrupts are
+/// timeouts and fuel exhaustion is a resource limit.
+pub(crate) fn trap_error(e: wasmtime::Error, timeout_secs: u64) -> CartridgeError {
+    match e.downcast_ref::<wasmtime::Trap>() {
+        Some(wasmtime::Trap::Interrupt) => CartridgeError::Timeout(timeout_secs),
+        Some(wasmtime::Trap::OutOfFuel) => {
+            CartridgeError::ResourceLimit("CPU fuel exhausted".to_string())
+        }
+        _ => CartridgeError::ExecutionFailed(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Responds with a binary PNG-ish body and a Cache-Control header.
+    const BINARY_WAT: &str = r#"(module
+      (import "x402" "header" (func $header (param i32 i32 i32 i32) (result i32)))
+      (import "x402" "response" (func $response (param i32 i32 i32 i32 i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 1024) "Cache-Control")
+      (data (i32.const 1040) "max-age=60")
+      (data (i32.const 1056) "\89PNG\00\ff")
+      (data (i32.const 1072) "image/png")
+      (func (export "x402_handle") (param i32 i32)
+        (drop (call $header (i32.const 1024) (i32.const 13) (i32.const 1040) (i32.const 10)))
+        (call $response (i32.const 200) (i32.const 1056) (i32.const 6) (i32.const 1072) (i32.const 9))))"#;
+
+    /// Streams "ab" then "cd" via x402_write.
+    const STREAM_WAT: &str = r#"(module
+      (import "x402" "write" (func $write (param i32 i32) (result i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 1024) "abcd")
+      (func (export "x402_handle") (param i32 i32)
+        (drop (call $write (i32.const 1024) (i32.const 2)))
+        (drop (call $write (i32.const 1026) (i32.const 2)))))"#;
+
+    /// Spins forever — only fuel exhaustion stops it.
+    const LOOP_WAT: &str = r#"(module
+      (memory (export "memory") 1)
+      (func (export "x402_handle") (param i32 i32)
+        (loop $l (br $l))))"#;
+
+    fn engine_with(slug: &str, wat: &str) -> Arc<CartridgeEngine> {
+        let dir =
+            std::env::temp_dir().join(format!("x402-cartridge-test-{}-{slug}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{slug}.wat"));
+        std::fs::write(&path, wat).unwrap();
+        let engine = CartridgeEngine::new(&dir).unwrap();
+        engine.load_module(slug, &path).unwrap();
+        Arc::new(engine)
+    }
+
+    fn get() -> CartridgeRequest {
+        CartridgeRequest {
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            query: "size=2".to_string(),
+            body: String::new(),
+            headers: HashMap::new(),
+            payment: None,
+            raw_body: None,
+        }
+    }
+
+    #[test]
+    fn binary_body_headers_and_usage() {
+        let engine = engine_with("binary", BINARY_WAT);
+        let (result, _) = engine.execute("binary", &get(), HashMap::new(), 5).unwrap();
+        assert_eq!(result.body, b"\x89PNG\x00\xff");
+        assert_eq!(result.content_type, "image/png");
+        assert_eq!(
+            result.headers,
+            vec![("Cache-Control".to_string(), "max-age=60".to_string())]
+        );
+        assert!(result.usage.fuel_consumed > 0);
+        assert_eq!(result.usage.peak_memory_bytes, 65536);
+        assert!(!result.streamed);
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["body"], "");
+        assert!(json["body_base64"].is_string());
+    }
+
+    #[test]
+    fn streaming_sends_head_then_chunks() {
+        let engine = engine_with("stream", STREAM_WAT);
+        let (sink, mut events) = tokio::sync::mpsc::channel(16);
+        let (result, _) = engine
+            .execute_streaming("stream", &get(), HashMap::new(), 5, sink)
+            .unwrap();
+        assert!(result.streamed);
+        assert!(result.body.is_empty());
+        let StreamEvent::Head { status, usage, .. } = events.try_recv().unwrap() else {
+            panic!("first event must be the head");
+        };
+        assert_eq!(status, 200);
+        assert!(usage.fuel_consumed > 0);
+        let mut body = Vec::new();
+        while let Ok(StreamEvent::Chunk(bytes)) = events.try_recv() {
+            body.extend(bytes);
+        }
+        assert_eq!(body, b"abcd");
+    }
+
+    #[test]
+    fn writes_are_buffered_without_a_sink() {
+        let engine = engine_with("buffered", STREAM_WAT);
+        let (result, _) = engine
+            .execute("buffered", &get(), HashMap::new(), 5)
+            .unwrap();
+        assert_eq!(result.body, b"abcd");
+        assert!(!result.streamed);
+    }
+
+    #[test]
+    fn manifest_fuel_limit_is_enforced() {
+        let engine = engine_with("spin", LOOP_WAT);
+        engine.set_limits(
+            "spin",
+            ResourceLimits {
+                max_fuel: Some(10_000),
+                ..Default::default()
+            },
+        );
+        let err = engine
+            .execute("spin", &get(), HashMap::new(), 5)
+            .unwrap_err();
+        assert!(matches!(err, CartridgeError::ResourceLimit(_)), "{err}");
+    }
+
+    #[test]
+    fn metered_run_out_of_fuel_is_billed_for_its_budget() {
+        let engine = engine_with("metered-spin", LOOP_WAT);
+        let pricing = crate::MeteredPricing {
+            base_amount: 100,
+            per_megafuel_amount: 10,
+            per_mb_amount: 1,
+        };
+        let budget = 3_000_000;
+        let failure = engine
+            .execute_metered("metered-spin", &get(), HashMap::new(), 5, budget)
+            .unwrap_err();
+        assert!(
+            matches!(failure.error, CartridgeError::ResourceLimit(_)),
+            "{failure}"
+        );
+        assert_eq!(failure.usage.fuel_consumed, budget);
+        // base 100 + 3 megafuel * 10 + 1 MB * 1
+        assert_eq!(pricing.cost(&failure.usage), 131);
+    }
+
+    #[test]
+    fn manifest_memory_limit_is_enforced() {
+        let engine = engine_with("tiny", BINARY_WAT);
+        engine.set_limits(
+            "tiny",
+            ResourceLimits {
+                max_memory_mb: Some(0),
+                ..Default::default()
+            },
+        );
+        assert!(engine.execute("tiny", &get(), HashMap::new(), 5).is_err());
+    }
+
+    #[test]
+    fn pooled_engine_reuses_instance_slots() {
+        let dir =
+            std::env::temp_dir().join(format!("x402-cartridge-test-{}-pool", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pool.wat");
+        std::fs::write(&path, BINARY_WAT).unwrap();
+        let policy = LimitPolicy {
+            pool_instances: 2,
+            ..Default::default()
+        };
+        let engine = CartridgeEngine::with_policy(&dir, policy).unwrap();
+        engine.load_module("pool", &path).unwrap();
+        // More sequential calls than slots: each store returns its slot on drop.
+        for _ in 0..5 {
+            let (result, _) = engine.execute("pool", &get(), HashMap::new(), 5).unwrap();
+            assert_eq!(result.status, 200);
+        }
+    }
+
+    #[test]
+    fn unresolved_imports_fail_at_instantiation() {
+        let wat = r#"(module
+          (import "x402" "no_such_function" (func))
+          (memory (export "memory") 1)
+          (func (export "x402_handle") (param i32 i32)))"#;
+        let engine = engine_with("unlinked", wat);
+        assert!(engine.loaded_slugs().contains(&"unlinked".to_string()));
+        let err = engine
+            .execute("unlinked", &get(), HashMap::new(), 5)
+            .unwrap_err();
+        assert!(err.to_string().contains("instantiate"), "{err}");
+    }
+
+    #[test]
+    fn wall_clock_timeout_interrupts_guest() {
+        let dir = std::env::temp_dir().join(format!(
+            "x402-cartridge-test-{}-timeout",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("spin.wat");
+        std::fs::write(&path, LOOP_WAT).unwrap();
+        let policy = LimitPolicy {
+            max_fuel: u64::MAX / 2,
+            max_timeout_secs: 1,
+            ..Default::default()
+        };
+        let engine = CartridgeEngine::with_policy(&dir, policy).unwrap();
+        engine.load_module("spin", &path).unwrap();
+        let start = Instant::now();
+        let err = engine
+            .execute("spin", &get(), HashMap::new(), 30)
+            .unwrap_err();
+        assert!(matches!(err, CartridgeError::Timeout(1)), "{err}");
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+}
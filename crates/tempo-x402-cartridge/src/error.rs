@@ -1,5 +1,7 @@
 //! Cartridge error types.
 
+use crate::limits::ExecutionUsage;
+
 /// Errors from cartridge operations.
 #[derive(Debug, thiserror::Error)]
 pub enum CartridgeError {
@@ -27,9 +29,31 @@ pub enum CartridgeError {
     #[error("resource limit exceeded: {0}")]
     ResourceLimit(String),
 
+    #[error("invalid trigger: {0}")]
+    Trigger(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 }
+
+/// A failed invocation and the resources it consumed before failing, so
+/// metered callers can bill for the work done.
+#[derive(Debug, thiserror::Error)]
+#[error("{error}")]
+pub struct ExecutionFailure {
+    pub error: CartridgeError,
+    pub usage: ExecutionUsage,
+}
+
+impl From<CartridgeError> for ExecutionFailure {
+    /// A failure before the guest ran, so nothing was consumed.
+    fn from(error: CartridgeError) -> Self {
+        Self {
+            error,
+            usage: ExecutionUsage::default(),
+        }
+    }
+}
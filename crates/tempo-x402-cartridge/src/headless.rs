@@ -0,0 +1,499 @@
+//! Headless runner for interactive cartridges — tick frames without a browser.
+//!
+//! Mirrors the Studio canvas loop: `x402_init(width, height)` once, then for
+//! each frame deliver scripted key events, call `x402_tick` and read the RGBA
+//! framebuffer at `x402_get_framebuffer()`. Captured frames can be encoded as
+//! PNGs or reduced to 64-bit perceptual hashes for golden-image tests.
+//!
+//! Each tick gets the cartridge's full fuel allowance; the wall-clock timeout
+//! covers the whole run.
+
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{trap_error, CartridgeEngine, CartridgeState};
+use crate::error::CartridgeError;
+use crate::limits::MemoryLimiter;
+
+/// Default framebuffer size (matches the Studio canvas).
+pub const DEFAULT_WIDTH: u32 = 320;
+pub const DEFAULT_HEIGHT: u32 = 240;
+
+/// Upper bound on frames per run.
+pub const MAX_FRAMES: u32 = 10_000;
+
+/// Upper bound on either framebuffer dimension.
+const MAX_DIMENSION: u32 = 4096;
+
+/// Browser `keyCode`s for the keys the interactive template handles.
+pub const KEY_LEFT: i32 = 37;
+pub const KEY_UP: i32 = 38;
+pub const KEY_RIGHT: i32 = 39;
+pub const KEY_DOWN: i32 = 40;
+pub const KEY_SPACE: i32 = 32;
+
+/// Whether a key is pressed or released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    Down,
+    Up,
+}
+
+/// A scripted key event, delivered before `x402_tick` of `frame`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputEvent {
+    pub frame: u32,
+    pub action: KeyAction,
+    /// Browser `keyCode` (37-40 for arrows).
+    pub code: i32,
+}
+
+impl InputEvent {
+    /// Press `code` at `frame` and release it `hold` frames later.
+    pub fn tap(code: i32, frame: u32, hold: u32) -> [InputEvent; 2] {
+        [
+            InputEvent {
+                frame,
+                action: KeyAction::Down,
+                code,
+            },
+            InputEvent {
+                frame: frame + hold.max(1),
+                action: KeyAction::Up,
+                code,
+            },
+        ]
+    }
+}
+
+/// Which frames to capture.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capture {
+    /// Only the final frame.
+    #[default]
+    Last,
+    /// Every n-th frame (0, n, 2n, ...) plus the final frame.
+    Every(u32),
+    /// Exactly these frame indices.
+    Frames(Vec<u32>),
+}
+
+impl Capture {
+    fn wants(&self, frame: u32, total: u32) -> bool {
+        let last = frame + 1 == total;
+        match self {
+            Self::Last => last,
+            Self::Every(n) => last || (*n > 0 && frame.is_multiple_of(*n)),
+            Self::Frames(list) => list.contains(&frame),
+        }
+    }
+}
+
+/// Options for a headless run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadlessOptions {
+    #[serde(default = "default_frames")]
+    pub frames: u32,
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default)]
+    pub inputs: Vec<InputEvent>,
+    #[serde(default)]
+    pub capture: Capture,
+}
+
+fn default_frames() -> u32 {
+    60
+}
+fn default_width() -> u32 {
+    DEFAULT_WIDTH
+}
+fn default_height() -> u32 {
+    DEFAULT_HEIGHT
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            frames: default_frames(),
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            inputs: Vec::new(),
+            capture: Capture::default(),
+        }
+    }
+}
+
+/// One captured RGBA frame. The buffer always holds exactly
+/// `width * height` pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    index: u32,
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+impl Frame {
+    /// Fails unless `rgba` is `width * height * 4` bytes.
+    pub fn new(index: u32, width: u32, height: u32, rgba: Vec<u8>) -> Result<Self, CartridgeError> {
+        let expected = width as usize * height as usize * 4;
+        if rgba.len() != expected {
+            return Err(CartridgeError::Abi(format!(
+                "{width}x{height} frame needs {expected} RGBA bytes, got {}",
+                rgba.len()
+            )));
+        }
+        Ok(Self {
+            index,
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    /// Frame number within the run (0-based).
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Row-major RGBA pixels.
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// Encode as an 8-bit RGBA PNG.
+    pub fn to_png(&self) -> Result<Vec<u8>, CartridgeError> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let err = |e: png::EncodingError| CartridgeError::ExecutionFailed(format!("png: {e}"));
+        let mut writer = encoder.write_header().map_err(err)?;
+        writer.write_image_data(&self.rgba).map_err(err)?;
+        writer.finish().map_err(err)?;
+        Ok(out)
+    }
+
+    /// 64-bit difference hash: luminance downscaled to 9x8, one bit per
+    /// horizontal neighbour comparison. Near-identical images differ in few
+    /// bits — compare with [`hash_distance`].
+    pub fn phash(&self) -> u64 {
+        let (w, h) = (self.width as usize, self.height as usize);
+        if w == 0 || h == 0 {
+            return 0;
+        }
+        let luma = |x: usize, y: usize| {
+            let i = (y * w + x) * 4;
+            let px = &self.rgba[i..i + 3];
+            299 * px[0] as u64 + 587 * px[1] as u64 + 114 * px[2] as u64
+        };
+        let mut cells = [[0u64; 9]; 8];
+        for (cy, row) in cells.iter_mut().enumerate() {
+            let (y0, y1) = (cy * h / 8, ((cy + 1) * h / 8).max(cy * h / 8 + 1).min(h));
+            for (cx, cell) in row.iter_mut().enumerate() {
+                let (x0, x1) = (cx * w / 9, ((cx + 1) * w / 9).max(cx * w / 9 + 1).min(w));
+                let mut sum = 0;
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        sum += luma(x, y);
+                    }
+                }
+                *cell = sum / ((y1 - y0) * (x1 - x0)) as u64;
+            }
+        }
+        let mut hash = 0u64;
+        for row in &cells {
+            for pair in row.windows(2) {
+                hash = (hash << 1) | u64::from(pair[0] < pair[1]);
+            }
+        }
+        hash
+    }
+
+    /// True if every pixel has the same color (e.g. a blank or crashed render).
+    pub fn is_uniform(&self) -> bool {
+        self.rgba
+            .chunks_exact(4)
+            .all(|px| px == &self.rgba[..4.min(self.rgba.len())])
+    }
+}
+
+/// Number of differing bits between two perceptual hashes.
+pub fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Result of a headless run.
+#[derive(Debug, Clone)]
+pub struct HeadlessRun {
+    pub width: u32,
+    pub height: u32,
+    pub frames_run: u32,
+    pub captured: Vec<Frame>,
+    /// Fuel consumed by init and all ticks.
+    pub fuel_consumed: u64,
+    pub duration_ms: u64,
+}
+
+impl CartridgeEngine {
+    /// True if the loaded module exports `x402_tick` (an interactive cartridge).
+    pub fn is_interactive(&self, slug: &str) -> bool {
+        self.module_exports(slug)
+            .is_some_and(|exports| exports.iter().any(|e| e == "x402_tick"))
+    }
+
+    /// Tick an interactive cartridge for `opts.frames` frames, replaying
+    /// `opts.inputs`, and return the captured frames.
+    pub fn run_headless(
+        &self,
+        slug: &str,
+        opts: &HeadlessOptions,
+    ) -> Result<HeadlessRun, CartridgeError> {
+        if opts.frames == 0 || opts.frames > MAX_FRAMES {
+            return Err(CartridgeError::ResourceLimit(format!(
+                "frames must be 1-{MAX_FRAMES}"
+            )));
+        }
+        let start = Instant::now();
+        let limits = self.limits_for(slug);
+        let state = CartridgeState {
+            limiter: MemoryLimiter::new(limits.memory_bytes),
+            ..Default::default()
+        };
+        let (mut store, instance) =
+            self.instantiate(slug, state, limits.fuel, limits.timeout_secs)?;
+
+        let tick = instance
+            .get_typed_func::<(), ()>(&mut store, "x402_tick")
+            .map_err(|e| CartridgeError::Abi(format!("no x402_tick export: {e}")))?;
+        let framebuffer = instance
+            .get_typed_func::<(), i32>(&mut store, "x402_get_framebuffer")
+            .map_err(|e| CartridgeError::Abi(format!("no x402_get_framebuffer export: {e}")))?;
+        let key_down = instance
+            .get_typed_func::<i32, ()>(&mut store, "x402_key_down")
+            .ok();
+        let key_up = instance
+            .get_typed_func::<i32, ()>(&mut store, "x402_key_up")
+            .ok();
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| CartridgeError::Abi("no memory export".to_string()))?;
+
+        let fail = |e| trap_error(e, limits.timeout_secs);
+        if let Ok(init) = instance.get_typed_func::<(i32, i32), ()>(&mut store, "x402_init") {
+            init.call(&mut store, (opts.width as i32, opts.height as i32))
+                .map_err(fail)?;
+        }
+
+        // The cartridge may clamp or ignore the requested size.
+        let mut dimension = |name: &str, requested: u32| -> Result<u32, CartridgeError> {
+            match instance.get_typed_func::<(), i32>(&mut store, name) {
+                Ok(f) => Ok(f.call(&mut store, ()).map_err(fail)?.max(0) as u32),
+                Err(_) => Ok(requested),
+            }
+        };
+        let width = dimension("x402_get_width", opts.width)?;
+        let height = dimension("x402_get_height", opts.height)?;
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(CartridgeError::Abi(format!(
+                "invalid framebuffer size {width}x{height}"
+            )));
+        }
+        let fb_len = (width * height * 4) as usize;
+
+        let mut inputs = opts.inputs.clone();
+        inputs.sort_by_key(|e| e.frame);
+        let mut next_input = 0;
+        let mut fuel_consumed = limits.fuel - store.get_fuel().unwrap_or(0);
+        let mut captured = Vec::new();
+
+        for frame in 0..opts.frames {
+            store
+                .set_fuel(limits.fuel)
+                .map_err(|e| CartridgeError::ExecutionFailed(format!("fuel setup: {e}")))?;
+            while let Some(event) = inputs.get(next_input).filter(|e| e.frame <= frame) {
+                let handler = match event.action {
+                    KeyAction::Down => &key_down,
+                    KeyAction::Up => &key_up,
+                };
+                if let Some(f) = handler {
+                    f.call(&mut store, event.code).map_err(fail)?;
+                }
+                next_input += 1;
+            }
+            tick.call(&mut store, ()).map_err(fail)?;
+
+            if opts.capture.wants(frame, opts.frames) {
+                let ptr = framebuffer.call(&mut store, ()).map_err(fail)? as u32 as usize;
+                let rgba = memory
+                    .data(&store)
+                    .get(ptr..ptr + fb_len)
+                    .ok_or_else(|| CartridgeError::Abi("framebuffer out of bounds".to_string()))?
+                    .to_vec();
+                captured.push(Frame::new(frame, width, height, rgba)?);
+            }
+            fuel_consumed += limits.fuel - store.get_fuel().unwrap_or(0);
+        }
+
+        Ok(HeadlessRun {
+            width,
+            height,
+            frames_run: opts.frames,
+            captured,
+            fuel_consumed,
+            duration_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x2 framebuffer at 1024. Each tick fills it with red = 10 * frame,
+    /// and green = 255 while any key is held.
+    const GAME_WAT: &str = r#"(module
+      (memory (export "memory") 1)
+      (global $frame (mut i32) (i32.const 0))
+      (global $key (mut i32) (i32.const 0))
+      (func (export "x402_init") (param i32 i32))
+      (func (export "x402_get_width") (result i32) (i32.const 4))
+      (func (export "x402_get_height") (result i32) (i32.const 2))
+      (func (export "x402_get_framebuffer") (result i32) (i32.const 1024))
+      (func (export "x402_key_down") (param i32) (global.set $key (local.get 0)))
+      (func (export "x402_key_up") (param i32) (global.set $key (i32.const 0)))
+      (func (export "x402_tick")
+        (local $i i32)
+        (global.set $frame (i32.add (global.get $frame) (i32.const 1)))
+        (loop $px
+          (i32.store8 (i32.add (i32.const 1024) (local.get $i))
+                      (i32.mul (global.get $frame) (i32.const 10)))
+          (i32.store8 (i32.add (i32.const 1025) (local.get $i))
+                      (select (i32.const 255) (i32.const 0) (global.get $key)))
+          (i32.store8 (i32.add (i32.const 1027) (local.get $i)) (i32.const 255))
+          (local.set $i (i32.add (local.get $i) (i32.const 4)))
+          (br_if $px (i32.lt_u (local.get $i) (i32.const 32))))))"#;
+
+    fn engine() -> CartridgeEngine {
+        let dir = std::env::temp_dir().join(format!(
+            "x402-cartridge-test-{}-headless",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.wat");
+        std::fs::write(&path, GAME_WAT).unwrap();
+        let engine = CartridgeEngine::new(&dir).unwrap();
+        engine.load_module("game", &path).unwrap();
+        engine
+    }
+
+    #[test]
+    fn ticks_frames_and_captures() {
+        let engine = engine();
+        assert!(engine.is_interactive("game"));
+        let opts = HeadlessOptions {
+            frames: 5,
+            capture: Capture::Every(2),
+            ..Default::default()
+        };
+        let run = engine.run_headless("game", &opts).unwrap();
+        assert_eq!((run.width, run.height), (4, 2));
+        let indices: Vec<u32> = run.captured.iter().map(|f| f.index()).collect();
+        assert_eq!(indices, vec![0, 2, 4]);
+        // Frame 4 is the fifth tick: red = 50
+        assert_eq!(&run.captured[2].rgba()[..4], &[50, 0, 0, 255]);
+        assert!(run.captured[2].is_uniform());
+        assert!(run.fuel_consumed > 0);
+    }
+
+    #[test]
+    fn replays_scripted_input() {
+        let engine = engine();
+        let mut inputs = InputEvent::tap(KEY_RIGHT, 2, 2).to_vec();
+        inputs.reverse(); // order in the script doesn't matter
+        let opts = HeadlessOptions {
+            frames: 6,
+            inputs,
+            capture: Capture::Frames(vec![1, 2, 3, 4]),
+            ..Default::default()
+        };
+        let run = engine.run_headless("game", &opts).unwrap();
+        let green: Vec<u8> = run.captured.iter().map(|f| f.rgba()[1]).collect();
+        assert_eq!(green, vec![0, 255, 255, 0]);
+    }
+
+    #[test]
+    fn png_and_hash_are_deterministic() {
+        let engine = engine();
+        let opts = HeadlessOptions {
+            frames: 3,
+            ..Default::default()
+        };
+        let a = engine.run_headless("game", &opts).unwrap();
+        let b = engine.run_headless("game", &opts).unwrap();
+        let (fa, fb) = (&a.captured[0], &b.captured[0]);
+        assert_eq!(fa.phash(), fb.phash());
+        assert_eq!(hash_distance(fa.phash(), fb.phash()), 0);
+        let png = fa.to_png().unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn phash_separates_different_images() {
+        let gradient = |flip: bool| {
+            let rgba = (0..8)
+                .flat_map(|_| {
+                    (0..18u8).flat_map(move |x| {
+                        let v = if flip { 255 - x * 14 } else { x * 14 };
+                        [v, v, v, 255]
+                    })
+                })
+                .collect();
+            Frame::new(0, 18, 8, rgba).unwrap()
+        };
+        assert!(hash_distance(gradient(false).phash(), gradient(true).phash()) > 32);
+    }
+
+    #[test]
+    fn frames_must_match_their_dimensions() {
+        assert!(Frame::new(0, 4, 2, vec![0; 4 * 2 * 4]).is_ok());
+        assert!(Frame::new(0, 4, 2, vec![0; 4 * 2 * 4 - 1]).is_err());
+        assert!(Frame::new(0, 1000, 1000, vec![0; 12]).is_err());
+        assert_eq!(Frame::new(0, 0, 0, Vec::new()).unwrap().phash(), 0);
+    }
+
+    #[test]
+    fn rejects_non_interactive_modules() {
+        let dir = std::env::temp_dir().join(format!(
+            "x402-cartridge-test-{}-notgame",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("backend.wat");
+        std::fs::write(
+            &path,
+            r#"(module (memory (export "memory") 1) (func (export "x402_handle") (param i32 i32)))"#,
+        )
+        .unwrap();
+        let engine = CartridgeEngine::new(&dir).unwrap();
+        engine.load_module("backend", &path).unwrap();
+        assert!(!engine.is_interactive("backend"));
+        assert!(engine
+            .run_headless("backend", &HeadlessOptions::default())
+            .is_err());
+    }
+}
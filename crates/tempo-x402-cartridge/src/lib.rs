@@ -18,24 +18,35 @@
 //! let request = CartridgeRequest {
 //!     method: "GET".into(),
 //!     path: "/".into(),
+//!     query: String::new(),
 //!     body: String::new(),
 //!     headers: Default::default(),
 //!     payment: None,
+//!     raw_body: None,
 //! };
 //!
 //! let (result, _kv) = engine.execute("hello", &request, Default::default(), 30)?;
-//! println!("Status: {}, Body: {}", result.status, result.body);
+//! println!("Status: {}, Body: {}", result.status, result.body_text());
 //! ```
 
 pub mod abi;
+pub mod billing;
 pub mod compiler;
 pub mod engine;
 pub mod error;
+pub mod headless;
+pub mod limits;
 pub mod manifest;
+pub mod sandbox;
+pub mod triggers;
 
-pub use engine::CartridgeEngine;
-pub use error::CartridgeError;
+pub use billing::MeteredPricing;
+pub use engine::{CartridgeEngine, StreamEvent};
+pub use error::{CartridgeError, ExecutionFailure};
+pub use headless::{Capture, Frame, HeadlessOptions, HeadlessRun, InputEvent, KeyAction};
+pub use limits::{EffectiveLimits, ExecutionUsage, LimitPolicy, ResourceLimits};
 pub use manifest::{
     CartridgeKind, CartridgeManifest, CartridgeRequest, CartridgeResult, PaymentContext,
     ABI_VERSION,
 };
+pub use triggers::{CronSchedule, Trigger, TriggerEvent};
@@ -0,0 +1,214 @@
+//! Resource limits — per-cartridge declarations capped by node policy.
+//!
+//! A cartridge declares what it needs in its manifest (`limits`). The node
+//! operator sets a [`LimitPolicy`] with hard ceilings. The engine resolves the
+//! two into [`EffectiveLimits`] before every invocation, so a manifest can ask
+//! for less than the node allows but never more.
+
+use serde::{Deserialize, Serialize};
+
+/// Default fuel ceiling (instruction count) per invocation.
+pub const DEFAULT_MAX_FUEL: u64 = 100_000_000;
+
+/// Default linear memory ceiling per cartridge (64MB).
+pub const DEFAULT_MAX_MEMORY_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Default wall-clock ceiling per invocation.
+pub const DEFAULT_MAX_TIMEOUT_SECS: u64 = 30;
+
+/// Default pooled instance slots (0 = allocate instances on demand).
+pub const DEFAULT_POOL_INSTANCES: u32 = 0;
+
+/// Limits a cartridge declares in its manifest. Unset fields fall back to the node policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Fuel (instruction count) per invocation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fuel: Option<u64>,
+    /// Linear memory ceiling in megabytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_mb: Option<u64>,
+    /// Wall-clock timeout per invocation in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Node-wide ceilings. Manifest limits are clamped to these.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LimitPolicy {
+    pub max_fuel: u64,
+    pub max_memory_bytes: u64,
+    pub max_timeout_secs: u64,
+    /// Pooling allocator slots — the most cartridge instances (including nested
+    /// x402_call children) that may run at once. 0 disables pooling.
+    #[serde(default)]
+    pub pool_instances: u32,
+}
+
+impl Default for LimitPolicy {
+    fn default() -> Self {
+        Self {
+            max_fuel: DEFAULT_MAX_FUEL,
+            max_memory_bytes: DEFAULT_MAX_MEMORY_BYTES,
+            max_timeout_secs: DEFAULT_MAX_TIMEOUT_SECS,
+            pool_instances: DEFAULT_POOL_INSTANCES,
+        }
+    }
+}
+
+impl LimitPolicy {
+    /// Read policy overrides from `CARTRIDGE_MAX_FUEL`, `CARTRIDGE_MAX_MEMORY_MB`,
+    /// `CARTRIDGE_MAX_TIMEOUT_SECS` and `CARTRIDGE_POOL_INSTANCES`.
+    /// Missing or invalid values keep the defaults.
+    pub fn from_env() -> Self {
+        let read = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+        };
+        let defaults = Self::default();
+        Self {
+            max_fuel: read("CARTRIDGE_MAX_FUEL").unwrap_or(defaults.max_fuel),
+            max_memory_bytes: read("CARTRIDGE_MAX_MEMORY_MB")
+                .map(|mb| mb * 1024 * 1024)
+                .unwrap_or(defaults.max_memory_bytes),
+            max_timeout_secs: read("CARTRIDGE_MAX_TIMEOUT_SECS")
+                .unwrap_or(defaults.max_timeout_secs),
+            pool_instances: read("CARTRIDGE_POOL_INSTANCES")
+                .and_then(|n| u32::try_from(n).ok())
+                .unwrap_or(defaults.pool_instances),
+        }
+    }
+}
+
+/// Concrete limits applied to a single invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EffectiveLimits {
+    pub fuel: u64,
+    pub memory_bytes: u64,
+    pub timeout_secs: u64,
+}
+
+impl ResourceLimits {
+    /// Resolve declared limits against the node policy.
+    /// Undeclared limits get the policy ceiling; declared ones are clamped to it.
+    pub fn resolve(&self, policy: &LimitPolicy) -> EffectiveLimits {
+        EffectiveLimits {
+            fuel: self
+                .max_fuel
+                .unwrap_or(policy.max_fuel)
+                .min(policy.max_fuel),
+            memory_bytes: self
+                .max_memory_mb
+                .map(|mb| mb.saturating_mul(1024 * 1024))
+                .unwrap_or(policy.max_memory_bytes)
+                .min(policy.max_memory_bytes),
+            timeout_secs: self
+                .timeout_secs
+                .unwrap_or(policy.max_timeout_secs)
+                .min(policy.max_timeout_secs),
+        }
+    }
+}
+
+/// Resources actually consumed by one invocation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionUsage {
+    pub fuel_consumed: u64,
+    pub peak_memory_bytes: u64,
+}
+
+/// Wasmtime resource limiter that enforces the memory ceiling and records the peak.
+#[derive(Debug, Clone)]
+pub struct MemoryLimiter {
+    max_bytes: usize,
+    peak_bytes: usize,
+}
+
+impl MemoryLimiter {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes: usize::try_from(max_bytes).unwrap_or(usize::MAX),
+            peak_bytes: 0,
+        }
+    }
+
+    /// Highest linear memory size observed so far.
+    pub fn peak_bytes(&self) -> u64 {
+        self.peak_bytes as u64
+    }
+}
+
+impl Default for MemoryLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_MEMORY_BYTES)
+    }
+}
+
+impl wasmtime::ResourceLimiter for MemoryLimiter {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        if desired > self.max_bytes || maximum.is_some_and(|m| desired > m) {
+            return Ok(false);
+        }
+        self.peak_bytes = self.peak_bytes.max(desired);
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        Ok(maximum.is_none_or(|m| desired <= m))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undeclared_limits_use_policy() {
+        let policy = LimitPolicy::default();
+        let eff = ResourceLimits::default().resolve(&policy);
+        assert_eq!(eff.fuel, DEFAULT_MAX_FUEL);
+        assert_eq!(eff.memory_bytes, DEFAULT_MAX_MEMORY_BYTES);
+        assert_eq!(eff.timeout_secs, DEFAULT_MAX_TIMEOUT_SECS);
+    }
+
+    #[test]
+    fn declared_limits_are_capped_by_policy() {
+        let policy = LimitPolicy {
+            max_fuel: 1_000,
+            max_memory_bytes: 2 * 1024 * 1024,
+            max_timeout_secs: 5,
+            pool_instances: 0,
+        };
+        let limits = ResourceLimits {
+            max_fuel: Some(500),
+            max_memory_mb: Some(16),
+            timeout_secs: Some(60),
+        };
+        let eff = limits.resolve(&policy);
+        assert_eq!(eff.fuel, 500);
+        assert_eq!(eff.memory_bytes, 2 * 1024 * 1024);
+        assert_eq!(eff.timeout_secs, 5);
+    }
+
+    #[test]
+    fn limiter_tracks_peak_and_rejects_growth() {
+        use wasmtime::ResourceLimiter;
+        let mut limiter = MemoryLimiter::new(128 * 1024);
+        assert!(limiter.memory_growing(0, 65536, None).unwrap());
+        assert!(limiter.memory_growing(65536, 131072, None).unwrap());
+        assert!(!limiter.memory_growing(131072, 196608, None).unwrap());
+        assert_eq!(limiter.peak_bytes(), 131072);
+    }
+}
@@ -1,10 +1,16 @@
 //! Cartridge manifest — metadata for a deployed WASM cartridge.
 
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::billing::MeteredPricing;
+use crate::limits::{ExecutionUsage, ResourceLimits};
+use crate::triggers::Trigger;
 
 /// ABI version. Increment when host function signatures change.
 /// v2: added x402_call for cartridge-calls-cartridge composition.
-pub const ABI_VERSION: u32 = 2;
+/// v3: binary bodies, x402_header, x402_request_body, x402_query, x402_write.
+pub const ABI_VERSION: u32 = 3;
 
 /// The kind of cartridge — determines compilation target and runtime.
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
@@ -77,6 +83,16 @@ pub struct CartridgeManifest {
     pub updated_at: i64,
     #[serde(default = "default_active")]
     pub active: bool,
+    /// Requested fuel/memory/timeout limits (capped by node policy).
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    /// Metered pricing. When set, payers pre-authorize a maximum and are
+    /// charged for actual usage instead of the flat `price_amount`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<MeteredPricing>,
+    /// Cron schedules and node events that invoke the cartridge without an HTTP request.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<Trigger>,
 }
 
 fn default_version() -> String {
@@ -93,12 +109,53 @@ fn default_active() -> bool {
 }
 
 /// Result of executing a cartridge.
-#[derive(Debug, Clone, Serialize)]
+///
+/// Serializes `body` as a UTF-8 string when possible; binary bodies are
+/// emitted as `body_base64` instead (what nested x402_call callers see).
+#[derive(Debug, Clone)]
 pub struct CartridgeResult {
     pub status: u16,
-    pub body: String,
+    /// Raw response body. Empty if the body was streamed via x402_write.
+    pub body: Vec<u8>,
     pub content_type: String,
+    /// Extra response headers set via x402_header (already denylist-filtered).
+    pub headers: Vec<(String, String)>,
     pub duration_ms: u64,
+    /// Fuel and peak memory consumed by this invocation.
+    pub usage: ExecutionUsage,
+    /// True if the cartridge streamed its output via x402_write.
+    pub streamed: bool,
+}
+
+impl CartridgeResult {
+    /// Body as text, replacing invalid UTF-8 sequences.
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+impl Serialize for CartridgeResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut st = serializer.serialize_struct("CartridgeResult", 7)?;
+        st.serialize_field("status", &self.status)?;
+        match std::str::from_utf8(&self.body) {
+            Ok(text) => st.serialize_field("body", text)?,
+            Err(_) => {
+                use base64::Engine;
+                st.serialize_field("body", "")?;
+                st.serialize_field(
+                    "body_base64",
+                    &base64::engine::general_purpose::STANDARD.encode(&self.body),
+                )?;
+            }
+        }
+        st.serialize_field("content_type", &self.content_type)?;
+        st.serialize_field("headers", &self.headers)?;
+        st.serialize_field("duration_ms", &self.duration_ms)?;
+        st.serialize_field("usage", &self.usage)?;
+        st.serialize_field("streamed", &self.streamed)?;
+        st.end()
+    }
 }
 
 /// Request context passed to a cartridge invocation.
@@ -106,6 +163,10 @@ pub struct CartridgeResult {
 pub struct CartridgeRequest {
     pub method: String,
     pub path: String,
+    /// Raw query string without the leading `?` (e.g. `page=2&q=rust`).
+    #[serde(default)]
+    pub query: String,
+    /// Body as text (lossy for binary uploads — use x402_request_body for raw bytes).
     #[serde(default)]
     pub body: String,
     #[serde(default)]
@@ -113,6 +174,9 @@ pub struct CartridgeRequest {
     /// Payment info (if request was paid).
     #[serde(default)]
     pub payment: Option<PaymentContext>,
+    /// Raw request body bytes, served by x402_request_body. Not part of the JSON.
+    #[serde(skip)]
+    pub raw_body: Option<Vec<u8>>,
 }
 
 /// Payment context from a settled x402 request.
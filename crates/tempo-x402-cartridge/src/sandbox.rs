@@ -0,0 +1,1391 @@
+//! Sandboxed execution of untrusted builds.
+//!
+//! Benchmark solutions and cartridge sources are built with `cargo`, which
+//! runs build scripts and proc-macros as native code. [`SandboxCommand`] runs
+//! such a process bubblewrap-style, inside fresh Linux namespaces:
+//!
+//! - **user** — the child is uid 1000 with no capabilities on the host
+//! - **mount** — the root is a private, size-capped tmpfs. System directories
+//!   and the Rust toolchain are bind-mounted read-only, and only host
+//!   directories passed to [`SandboxCommand::writable`] can be written.
+//!   [`SandboxCommand::overlay`] shares a host directory copy-on-write
+//! - **network** — a loopback interface and nothing else
+//! - **pid / ipc / uts** — host processes are invisible and unsignalable
+//!
+//! rlimits cap CPU time, address space, process count and file size. A
+//! seccomp filter denies the syscalls that could undo the sandbox (mount,
+//! namespaces, ptrace, kernel modules, bpf, ...). The environment is cleared,
+//! so API keys and wallet secrets never reach the child.
+//!
+//! With no network, dependencies must already be in the registry. Call
+//! [`fetch_dependencies`] first: it runs `cargo fetch` on the host, which
+//! downloads and unpacks crates without running any of their code.
+//!
+//! If the kernel refuses unprivileged user namespaces (see [`check`]),
+//! commands fail with [`SandboxError::Unavailable`]. Operators who cannot
+//! change the host can opt into a degraded mode with
+//! `SANDBOX_ALLOW_UNCONFINED=1`: the program then runs as the node's own
+//! user in a scratch directory, with a cleared environment, the rlimits
+//! (except `RLIMIT_NPROC`) and the seccomp filter, but no filesystem,
+//! network or process isolation. Every such run is logged as an error.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Working directory of the sandboxed process; [`SandboxCommand::file`]
+/// paths are relative to it.
+pub const WORKDIR: &str = "/work";
+
+/// Where the toolchain homes are mounted inside the sandbox.
+const SANDBOX_CARGO_HOME: &str = "/toolchain/cargo";
+const SANDBOX_RUSTUP_HOME: &str = "/toolchain/rustup";
+
+/// Host directories mounted read-only (when they exist).
+const SYSTEM_DIRS: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc"];
+
+/// Device nodes bound into the sandbox's `/dev`.
+const DEVICES: &[&str] = &["null", "zero", "full", "random", "urandom"];
+
+/// Subdirectories of `CARGO_HOME` the sandbox may read. Everything else in
+/// there (`credentials.toml`, `config.toml`, ...) stays hidden.
+const CARGO_HOME_DIRS: &[&str] = &["bin", "registry", "git"];
+
+/// Errors from sandboxed execution.
+#[derive(Debug, thiserror::Error)]
+pub enum SandboxError {
+    #[error("sandbox unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("sandboxed process timed out after {0}s")]
+    Timeout(u64),
+
+    #[error("dependency fetch failed: {0}")]
+    Fetch(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Resource caps for one sandboxed command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SandboxLimits {
+    /// CPU seconds per process (`RLIMIT_CPU`).
+    pub cpu_secs: u64,
+    /// Address space per process (`RLIMIT_AS`).
+    pub memory_bytes: u64,
+    /// Processes and threads across the whole sandbox (`RLIMIT_NPROC`).
+    pub max_processes: u64,
+    /// Largest file any process may write (`RLIMIT_FSIZE`).
+    pub max_file_bytes: u64,
+    /// Size of the tmpfs holding the root, `/work` and `/tmp`.
+    pub tmpfs_bytes: u64,
+    /// Wall-clock limit; the whole sandbox is killed when it expires.
+    pub timeout_secs: u64,
+}
+
+impl Default for SandboxLimits {
+    /// Sized for `cargo test` on a small crate with no heavy dependencies.
+    fn default() -> Self {
+        Self {
+            cpu_secs: 120,
+            memory_bytes: 4 << 30,
+            max_processes: 256,
+            max_file_bytes: 512 << 20,
+            tmpfs_bytes: 1 << 30,
+            timeout_secs: 90,
+        }
+    }
+}
+
+/// A command to run inside the sandbox, built like [`std::process::Command`].
+///
+/// ```ignore
+/// use x402_cartridge::sandbox::SandboxCommand;
+///
+/// let output = SandboxCommand::new("cargo")
+///     .args(["test", "--offline"])
+///     .file("Cargo.toml", manifest)
+///     .file("src/lib.rs", solution)
+///     .output()
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct SandboxCommand {
+    program: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    files: Vec<(PathBuf, Vec<u8>)>,
+    writable: Vec<(PathBuf, PathBuf)>,
+    overlays: Vec<(PathBuf, PathBuf)>,
+    limits: SandboxLimits,
+}
+
+impl SandboxCommand {
+    /// `program` is looked up on the sandbox's `PATH`, which starts with the
+    /// toolchain's `bin` directory.
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            files: Vec::new(),
+            writable: Vec::new(),
+            overlays: Vec::new(),
+            limits: SandboxLimits::default(),
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set an environment variable. The host environment is never inherited.
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Write `contents` to `path` (relative to [`WORKDIR`]) in the sandbox's
+    /// tmpfs before the program starts. Nothing is written on the host.
+    pub fn file(mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) -> Self {
+        self.files.push((path.into(), contents.into()));
+        self
+    }
+
+    /// Copy a host directory tree into `dest` (relative to [`WORKDIR`]),
+    /// skipping `target/` and `.git/`.
+    pub fn dir(mut self, host: &Path, dest: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dest = dest.into();
+        let mut stack = vec![(host.to_path_buf(), dest)];
+        while let Some((from, to)) = stack.pop() {
+            for entry in std::fs::read_dir(&from)? {
+                let entry = entry?;
+                let name = entry.file_name();
+                let kind = entry.file_type()?;
+                if kind.is_dir() {
+                    if name != "target" && name != ".git" {
+                        stack.push((entry.path(), to.join(&name)));
+                    }
+                } else if kind.is_file() {
+                    self.files.push((to.join(&name), std::fs::read(entry.path())?));
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// Bind a host directory read-write at an absolute sandbox path, e.g. a
+    /// shared `CARGO_TARGET_DIR` whose artifacts the caller needs afterwards.
+    pub fn writable(mut self, host: impl Into<PathBuf>, inside: impl Into<PathBuf>) -> Self {
+        self.writable.push((host.into(), inside.into()));
+        self
+    }
+
+    /// Mount a host directory at an absolute sandbox path as the lower layer
+    /// of an overlay. The process can read and modify it, but every write
+    /// lands in the sandbox's tmpfs; the host copy is never touched. Any
+    /// number of sandboxes can share one lower directory, e.g. a prebuilt
+    /// target dir.
+    pub fn overlay(mut self, host: impl Into<PathBuf>, inside: impl Into<PathBuf>) -> Self {
+        self.overlays.push((host.into(), inside.into()));
+        self
+    }
+
+    pub fn limits(mut self, limits: SandboxLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Run to completion and collect stdout/stderr. On timeout the sandbox
+    /// and everything in it is killed.
+    pub async fn output(self) -> Result<Output, SandboxError> {
+        match mode()? {
+            Mode::Confined => self.confined().await,
+            Mode::Unconfined => self.unconfined().await,
+        }
+    }
+
+    async fn confined(self) -> Result<Output, SandboxError> {
+        let timeout = self.limits.timeout_secs;
+        let mut cmd = tokio::process::Command::from(self.prepare()?);
+        cmd.stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+        match tokio::time::timeout(Duration::from_secs(timeout), cmd.output()).await {
+            Ok(output) => Ok(output?),
+            Err(_) => Err(SandboxError::Timeout(timeout)),
+        }
+    }
+
+    /// Degraded mode: the layout is recreated under a host scratch directory
+    /// that stands in for the sandbox root, and sandbox paths in arguments
+    /// and environment values are rewritten to point into it. Writable mounts
+    /// become symlinks to the host directory; overlays are copied.
+    async fn unconfined(self) -> Result<Output, SandboxError> {
+        static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let root = std::env::temp_dir().join(format!("x402-unconfined-{}-{n}", std::process::id()));
+        tracing::error!(
+            program = %self.program,
+            root = %root.display(),
+            "Running untrusted build WITHOUT sandbox isolation (SANDBOX_ALLOW_UNCONFINED)"
+        );
+        let result = self.run_unconfined(&root).await;
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        result
+    }
+
+    async fn run_unconfined(self, root: &Path) -> Result<Output, SandboxError> {
+        let layout = self.layout()?;
+        let rehome = |inside: &Path| root.join(inside.strip_prefix("/").unwrap_or(inside));
+        for dir in ["/tmp", WORKDIR] {
+            std::fs::create_dir_all(rehome(Path::new(dir)))?;
+        }
+        for (path, contents) in &layout.files {
+            let path = rehome(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, contents)?;
+        }
+        let mut mounts = vec![PathBuf::from(WORKDIR), PathBuf::from("/tmp")];
+        for (host, inside) in &layout.writable {
+            let at = rehome(inside);
+            if let Some(parent) = at.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            linux::symlink(&host.canonicalize()?, &at)?;
+            mounts.push(inside.clone());
+        }
+        for (host, inside) in &layout.overlays {
+            copy_tree(host, &rehome(inside))?;
+            mounts.push(inside.clone());
+        }
+        let rewrite = |value: &str| -> String {
+            let path = Path::new(value);
+            if mounts.iter().any(|m| path.starts_with(m)) {
+                rehome(path).to_string_lossy().into_owned()
+            } else {
+                value.to_string()
+            }
+        };
+
+        let (cargo_home, rustup_home) = toolchain_homes();
+        let mut cmd = std::process::Command::new(&self.program);
+        cmd.args(self.args.iter().map(|a| rewrite(a))).env_clear();
+        cmd.env(
+            "PATH",
+            format!("{}:/usr/local/bin:/usr/bin:/bin", cargo_home.join("bin").display()),
+        )
+        .env("HOME", rehome(Path::new(WORKDIR)))
+        .env("TMPDIR", rehome(Path::new("/tmp")))
+        .env("CARGO_HOME", &cargo_home)
+        .env("RUSTUP_HOME", &rustup_home)
+        .env("CARGO_NET_OFFLINE", "true")
+        .env("CARGO_TERM_COLOR", "never");
+        if let Ok(toolchain) = std::env::var("RUSTUP_TOOLCHAIN") {
+            cmd.env("RUSTUP_TOOLCHAIN", toolchain);
+        }
+        for (key, value) in &self.env {
+            cmd.env(key, rewrite(value));
+        }
+        cmd.current_dir(rehome(Path::new(WORKDIR)));
+        linux::install_unconfined(&mut cmd, self.limits)?;
+
+        let timeout = self.limits.timeout_secs;
+        let mut cmd = tokio::process::Command::from(cmd);
+        let child = cmd
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let group = child.id();
+        match tokio::time::timeout(Duration::from_secs(timeout), child.wait_with_output()).await {
+            Ok(output) => Ok(output?),
+            Err(_) => {
+                if let Some(pgid) = group {
+                    linux::kill_group(pgid);
+                }
+                Err(SandboxError::Timeout(timeout))
+            }
+        }
+    }
+
+    /// Build the host-side `Command`. Its child sets up the namespaces in a
+    /// `pre_exec` hook and then execs the program.
+    fn prepare(self) -> Result<std::process::Command, SandboxError> {
+        let mut cmd = std::process::Command::new(&self.program);
+        cmd.args(&self.args).env_clear();
+        cmd.env(
+            "PATH",
+            format!("{SANDBOX_CARGO_HOME}/bin:/usr/local/bin:/usr/bin:/bin"),
+        )
+        .env("HOME", WORKDIR)
+        .env("TMPDIR", "/tmp")
+        .env("CARGO_HOME", SANDBOX_CARGO_HOME)
+        .env("RUSTUP_HOME", SANDBOX_RUSTUP_HOME)
+        .env("CARGO_NET_OFFLINE", "true")
+        .env("CARGO_TERM_COLOR", "never");
+        if let Ok(toolchain) = std::env::var("RUSTUP_TOOLCHAIN") {
+            cmd.env("RUSTUP_TOOLCHAIN", toolchain);
+        }
+        for (key, value) in &self.env {
+            cmd.env(key, value);
+        }
+        cmd.current_dir("/");
+        linux::install(&mut cmd, self.layout()?, self.limits)?;
+        Ok(cmd)
+    }
+
+    /// What the sandbox's filesystem looks like.
+    fn layout(&self) -> Result<Layout, SandboxError> {
+        let mut layout = Layout::default();
+        for dir in SYSTEM_DIRS {
+            if Path::new(dir).exists() {
+                layout.read_only.push((PathBuf::from(dir), PathBuf::from(dir)));
+            }
+        }
+        let (cargo_home, rustup_home) = toolchain_homes();
+        for sub in CARGO_HOME_DIRS {
+            let host = cargo_home.join(sub);
+            if host.is_dir() {
+                layout
+                    .read_only
+                    .push((host, Path::new(SANDBOX_CARGO_HOME).join(sub)));
+            }
+        }
+        if rustup_home.is_dir() {
+            layout
+                .read_only
+                .push((rustup_home, PathBuf::from(SANDBOX_RUSTUP_HOME)));
+        }
+        for (_, inside) in self.writable.iter().chain(&self.overlays) {
+            if !inside.is_absolute() {
+                return Err(SandboxError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("sandbox mount point must be absolute: {}", inside.display()),
+                )));
+            }
+        }
+        for (host, inside) in &self.writable {
+            std::fs::create_dir_all(host)?;
+            layout.writable.push((host.clone(), inside.clone()));
+        }
+        layout.overlays = self.overlays.clone();
+        for (path, contents) in &self.files {
+            let relative = path.components().all(|c| {
+                matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir)
+            });
+            if !relative {
+                return Err(SandboxError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("sandbox file path must be relative: {}", path.display()),
+                )));
+            }
+            layout
+                .files
+                .push((Path::new(WORKDIR).join(path), contents.clone()));
+        }
+        Ok(layout)
+    }
+}
+
+/// Host paths and files the sandbox is assembled from.
+#[derive(Debug, Default)]
+struct Layout {
+    read_only: Vec<(PathBuf, PathBuf)>,
+    writable: Vec<(PathBuf, PathBuf)>,
+    overlays: Vec<(PathBuf, PathBuf)>,
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl Layout {
+    /// Every directory that must exist in the new root, parents first.
+    fn dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = BTreeSet::new();
+        let mut add = |path: &Path| {
+            for ancestor in path.ancestors() {
+                if ancestor != Path::new("/") {
+                    dirs.insert(ancestor.to_path_buf());
+                }
+            }
+        };
+        for fixed in ["/dev", "/proc", "/tmp", WORKDIR] {
+            add(Path::new(fixed));
+        }
+        for (_, inside) in self.read_only.iter().chain(&self.writable) {
+            add(inside);
+        }
+        for (i, (_, inside)) in self.overlays.iter().enumerate() {
+            add(inside);
+            add(&overlay_scratch(i).join("upper"));
+            add(&overlay_scratch(i).join("work"));
+        }
+        for (path, _) in &self.files {
+            if let Some(parent) = path.parent() {
+                add(parent);
+            }
+        }
+        dirs.into_iter().collect()
+    }
+}
+
+/// Upper and work directories of the `i`th overlay, on the root tmpfs.
+fn overlay_scratch(i: usize) -> PathBuf {
+    PathBuf::from(format!("/.overlay/{i}"))
+}
+
+/// `CARGO_HOME` and `RUSTUP_HOME` as the node sees them.
+fn toolchain_homes() -> (PathBuf, PathBuf) {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".to_string());
+    let cargo = std::env::var("CARGO_HOME").unwrap_or_else(|_| format!("{home}/.cargo"));
+    let rustup = std::env::var("RUSTUP_HOME").unwrap_or_else(|_| format!("{home}/.rustup"));
+    (PathBuf::from(cargo), PathBuf::from(rustup))
+}
+
+/// Recursively copy `from` to `to`, preserving symlinks.
+fn copy_tree(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let kind = entry.file_type()?;
+        let dest = to.join(entry.file_name());
+        if kind.is_dir() {
+            copy_tree(&entry.path(), &dest)?;
+        } else if kind.is_symlink() {
+            linux::symlink(&std::fs::read_link(entry.path())?, &dest)?;
+        } else {
+            std::fs::copy(entry.path(), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// How [`SandboxCommand::output`] runs its program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Inside fresh namespaces.
+    Confined,
+    /// Degraded, opted into with `SANDBOX_ALLOW_UNCONFINED` because the
+    /// kernel refuses namespaces. See the [module docs](self).
+    Unconfined,
+}
+
+/// Whether the operator opted into [`Mode::Unconfined`].
+fn unconfined_allowed() -> bool {
+    matches!(
+        std::env::var("SANDBOX_ALLOW_UNCONFINED").as_deref(),
+        Ok("1") | Ok("true")
+    )
+}
+
+/// How untrusted builds run here: [`Mode::Confined`] when [`check`] passes,
+/// [`Mode::Unconfined`] when it fails and `SANDBOX_ALLOW_UNCONFINED` is set,
+/// otherwise the [`check`] error.
+pub fn mode() -> Result<Mode, SandboxError> {
+    match check() {
+        Ok(()) => Ok(Mode::Confined),
+        Err(_) if unconfined_allowed() && cfg!(target_os = "linux") => Ok(Mode::Unconfined),
+        Err(e) => Err(e),
+    }
+}
+
+/// Check once per process that sandboxes can be created here, by running
+/// `true` inside one.
+pub fn check() -> Result<(), SandboxError> {
+    static PROBE: OnceLock<Result<(), String>> = OnceLock::new();
+    PROBE
+        .get_or_init(|| {
+            let mut cmd = SandboxCommand::new("true")
+                .prepare()
+                .map_err(|e| e.to_string())?;
+            let output = cmd
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::piped())
+                .output()
+                .map_err(|e| e.to_string())?;
+            if output.status.success() {
+                Ok(())
+            } else {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(format!("probe exited with {}: {}", output.status, stderr.trim()))
+            }
+        })
+        .clone()
+        .map_err(SandboxError::Unavailable)
+}
+
+/// Download the dependencies of the crate at `manifest_path` into the
+/// registry so an offline build in the sandbox can find them.
+///
+/// Runs `cargo fetch` on the host, which resolves and unpacks crates but
+/// runs none of their build scripts.
+pub async fn fetch_dependencies(manifest_path: &Path) -> Result<(), SandboxError> {
+    let output = tokio::time::timeout(
+        Duration::from_secs(300),
+        tokio::process::Command::new("cargo")
+            .arg("fetch")
+            .arg("--manifest-path")
+            .arg(manifest_path)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| SandboxError::Fetch("cargo fetch timed out after 300s".to_string()))??;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(SandboxError::Fetch(stderr.chars().take(2000).collect()))
+    }
+}
+
+/// Like [`fetch_dependencies`], for a manifest that only exists as text.
+/// Returns the resulting `Cargo.lock`, so the sandboxed build resolves to the
+/// exact versions that were fetched.
+pub async fn fetch_manifest(manifest: &str) -> Result<String, SandboxError> {
+    static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("x402-fetch-{}-{n}", std::process::id()));
+    let result = async {
+        tokio::fs::create_dir_all(dir.join("src")).await?;
+        tokio::fs::write(dir.join("Cargo.toml"), manifest).await?;
+        tokio::fs::write(dir.join("src/lib.rs"), "").await?;
+        fetch_dependencies(&dir.join("Cargo.toml")).await?;
+        Ok(tokio::fs::read_to_string(dir.join("Cargo.lock")).await?)
+    }
+    .await;
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    result
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    //! The namespace setup itself. Everything in [`Plan`] is prepared before
+    //! `fork`; the `pre_exec` hook only makes raw syscalls, since allocating
+    //! in the child of a multi-threaded process can deadlock.
+
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+    use std::path::Path;
+
+    use super::{overlay_scratch, Layout, SandboxError, SandboxLimits, DEVICES};
+
+    /// Uid and gid of the sandboxed process inside its user namespace.
+    const SANDBOX_ID: u32 = 1000;
+
+    const AUDIT_ARCH_X86_64: u32 = 0xC000_003E;
+    const AUDIT_ARCH_AARCH64: u32 = 0xC000_00B7;
+
+    /// Syscalls that fail with `EPERM` inside the sandbox.
+    const DENIED: &[libc::c_long] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_mount_setattr,
+        libc::SYS_move_mount,
+        libc::SYS_open_tree,
+        libc::SYS_fsopen,
+        libc::SYS_fsconfig,
+        libc::SYS_fsmount,
+        libc::SYS_fspick,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_kexec_load,
+        libc::SYS_kexec_file_load,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_userfaultfd,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_reboot,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_acct,
+        libc::SYS_settimeofday,
+        libc::SYS_clock_settime,
+        libc::SYS_clock_adjtime,
+        libc::SYS_adjtimex,
+        libc::SYS_sethostname,
+        libc::SYS_setdomainname,
+        libc::SYS_syslog,
+        libc::SYS_open_by_handle_at,
+        libc::SYS_name_to_handle_at,
+        libc::SYS_io_uring_setup,
+        libc::SYS_io_uring_enter,
+        libc::SYS_io_uring_register,
+        libc::SYS_quotactl,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_ioperm,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_iopl,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_uselib,
+    ];
+
+    /// `clone` flags that would create new namespaces.
+    const NAMESPACE_FLAGS: libc::c_int = libc::CLONE_NEWNS
+        | libc::CLONE_NEWUSER
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWNET
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWCGROUP;
+
+    struct Bind {
+        /// Source path under `/oldroot`.
+        source: CString,
+        target: CString,
+        read_only: bool,
+        /// Mount over a file (device node) rather than a directory.
+        file: bool,
+    }
+
+    struct Plan {
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+        tmpfs_options: CString,
+        dirs: Vec<CString>,
+        binds: Vec<Bind>,
+        /// Mount point and `lowerdir=...,upperdir=...,workdir=...` options.
+        overlays: Vec<(CString, CString)>,
+        files: Vec<(CString, Vec<u8>)>,
+        limits: SandboxLimits,
+        filter: Vec<libc::sock_filter>,
+    }
+
+    fn cstring(path: &Path) -> Result<CString, SandboxError> {
+        CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+            SandboxError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("path contains a NUL byte: {}", path.display()),
+            ))
+        })
+    }
+
+    fn under_oldroot(host: &Path) -> Result<CString, SandboxError> {
+        let host = host.canonicalize()?;
+        cstring(&Path::new("/oldroot").join(host.strip_prefix("/").unwrap_or(&host)))
+    }
+
+    /// Attach the sandbox setup to `cmd` as a `pre_exec` hook.
+    pub(super) fn install(
+        cmd: &mut std::process::Command,
+        layout: Layout,
+        limits: SandboxLimits,
+    ) -> Result<(), SandboxError> {
+        // SAFETY: getuid/getgid cannot fail and have no preconditions.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let mut binds = Vec::new();
+        for (host, inside) in &layout.read_only {
+            binds.push(Bind {
+                source: under_oldroot(host)?,
+                target: cstring(inside)?,
+                read_only: true,
+                file: false,
+            });
+        }
+        for (host, inside) in &layout.writable {
+            binds.push(Bind {
+                source: under_oldroot(host)?,
+                target: cstring(inside)?,
+                read_only: false,
+                file: false,
+            });
+        }
+        for device in DEVICES {
+            binds.push(Bind {
+                source: cstring(&Path::new("/oldroot/dev").join(device))?,
+                target: cstring(&Path::new("/dev").join(device))?,
+                read_only: false,
+                file: true,
+            });
+        }
+        let mut overlays = Vec::new();
+        for (i, (host, inside)) in layout.overlays.iter().enumerate() {
+            let lower = under_oldroot(host)?;
+            let lower = lower.to_string_lossy();
+            if lower.contains([',', ':']) {
+                return Err(SandboxError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("overlay source cannot contain ',' or ':': {}", host.display()),
+                )));
+            }
+            let scratch = overlay_scratch(i);
+            let options = format!(
+                "lowerdir={lower},upperdir={},workdir={}",
+                scratch.join("upper").display(),
+                scratch.join("work").display()
+            );
+            overlays.push((
+                cstring(inside)?,
+                CString::new(options).expect("no NUL in overlay options"),
+            ));
+        }
+        let plan = Plan {
+            uid_map: format!("{SANDBOX_ID} {uid} 1\n").into_bytes(),
+            gid_map: format!("{SANDBOX_ID} {gid} 1\n").into_bytes(),
+            tmpfs_options: CString::new(format!("size={},mode=0755", limits.tmpfs_bytes))
+                .expect("no NUL in tmpfs options"),
+            dirs: layout
+                .dirs()
+                .iter()
+                .map(|d| cstring(d))
+                .collect::<Result<_, _>>()?,
+            binds,
+            overlays,
+            files: layout
+                .files
+                .iter()
+                .map(|(path, contents)| Ok((cstring(path)?, contents.clone())))
+                .collect::<Result<_, SandboxError>>()?,
+            limits,
+            filter: seccomp_filter(),
+        };
+        // SAFETY: the hook runs between fork and exec and only makes raw
+        // syscalls on data prepared above; see `enter`.
+        unsafe {
+            cmd.pre_exec(move || enter(&plan));
+        }
+        Ok(())
+    }
+
+    /// Report which setup step failed on the child's stderr; `pre_exec` can
+    /// only pass an errno back to the parent.
+    fn fail(step: &str) -> std::io::Error {
+        let err = std::io::Error::last_os_error();
+        let mut msg = [0u8; 128];
+        let prefix = b"sandbox: ";
+        let len = (prefix.len() + step.len()).min(msg.len() - 1);
+        msg[..prefix.len()].copy_from_slice(prefix);
+        msg[prefix.len()..len].copy_from_slice(&step.as_bytes()[..len - prefix.len()]);
+        msg[len] = b'\n';
+        // SAFETY: writing a stack buffer to stderr.
+        unsafe {
+            libc::write(2, msg.as_ptr().cast(), len + 1);
+        }
+        err
+    }
+
+    fn check(ret: libc::c_int, step: &str) -> std::io::Result<()> {
+        if ret < 0 {
+            Err(fail(step))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn write_file(path: &CStr, data: &[u8]) -> libc::c_int {
+        // SAFETY: plain open/write/close on a valid C string and buffer.
+        unsafe {
+            let fd = libc::open(
+                path.as_ptr(),
+                libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+                0o644,
+            );
+            if fd < 0 {
+                return -1;
+            }
+            let mut written = 0;
+            while written < data.len() {
+                let n = libc::write(fd, data[written..].as_ptr().cast(), data.len() - written);
+                if n <= 0 {
+                    libc::close(fd);
+                    return -1;
+                }
+                written += n as usize;
+            }
+            libc::close(fd)
+        }
+    }
+
+    /// Runs in the forked child. Enters the namespaces, then forks again so
+    /// the program runs as pid 1 of the new pid namespace. The first child
+    /// stays behind to relay the exit status.
+    fn enter(plan: &Plan) -> std::io::Result<()> {
+        let flags = libc::CLONE_NEWUSER
+            | libc::CLONE_NEWNS
+            | libc::CLONE_NEWPID
+            | libc::CLONE_NEWNET
+            | libc::CLONE_NEWIPC
+            | libc::CLONE_NEWUTS;
+        // SAFETY: unshare has no memory-safety preconditions.
+        check(unsafe { libc::unshare(flags) }, "unshare")?;
+        check(write_file(c"/proc/self/setgroups", b"deny"), "setgroups")?;
+        check(write_file(c"/proc/self/uid_map", &plan.uid_map), "uid_map")?;
+        check(write_file(c"/proc/self/gid_map", &plan.gid_map), "gid_map")?;
+
+        // SAFETY: the process is single-threaded after the first fork.
+        let pid = unsafe { libc::fork() };
+        if pid < 0 {
+            return Err(fail("fork"));
+        }
+        if pid > 0 {
+            relay(pid);
+        }
+        // SAFETY: prctl with integer arguments.
+        check(
+            unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) },
+            "pdeathsig",
+        )?;
+        build_root(plan)?;
+        restrict(plan)
+    }
+
+    /// Wait for the sandboxed pid 1 and exit with its status. Killing this
+    /// process (timeouts, `kill_on_drop`) kills pid 1 via `PR_SET_PDEATHSIG`,
+    /// and with it the whole pid namespace.
+    fn relay(pid: libc::pid_t) -> ! {
+        // SAFETY: raw syscalls only; `_exit` never returns.
+        unsafe {
+            // Drop every inherited descriptor but stdio, in particular the
+            // pipe std uses to detect a successful exec.
+            libc::syscall(libc::SYS_close_range, 3u32, u32::MAX, 0u32);
+            let mut status = 0;
+            while libc::waitpid(pid, &mut status, 0) < 0 {
+                if *libc::__errno_location() != libc::EINTR {
+                    libc::_exit(127);
+                }
+            }
+            if libc::WIFEXITED(status) {
+                libc::_exit(libc::WEXITSTATUS(status));
+            }
+            libc::_exit(128 + libc::WTERMSIG(status));
+        }
+    }
+
+    /// Mount a tmpfs root, pivot into it and assemble the filesystem from the
+    /// old root, which is then detached.
+    fn build_root(plan: &Plan) -> std::io::Result<()> {
+        // SAFETY: mount/pivot_root/mkdir on valid C strings, in our own
+        // mount namespace.
+        unsafe {
+            let null = std::ptr::null();
+            let no_data = std::ptr::null();
+            check(
+                libc::mount(null, c"/".as_ptr(), null, libc::MS_REC | libc::MS_PRIVATE, no_data),
+                "make / private",
+            )?;
+            check(
+                libc::mount(
+                    c"tmpfs".as_ptr(),
+                    c"/tmp".as_ptr(),
+                    c"tmpfs".as_ptr(),
+                    libc::MS_NOSUID | libc::MS_NODEV,
+                    plan.tmpfs_options.as_ptr().cast(),
+                ),
+                "mount tmpfs",
+            )?;
+            check(libc::chdir(c"/tmp".as_ptr()), "chdir tmpfs")?;
+            check(libc::mkdir(c"oldroot".as_ptr(), 0o755), "mkdir oldroot")?;
+            check(
+                libc::syscall(libc::SYS_pivot_root, c".".as_ptr(), c"oldroot".as_ptr()) as _,
+                "pivot_root",
+            )?;
+            check(libc::chdir(c"/".as_ptr()), "chdir /")?;
+
+            for dir in &plan.dirs {
+                if libc::mkdir(dir.as_ptr(), 0o755) < 0
+                    && *libc::__errno_location() != libc::EEXIST
+                {
+                    return Err(fail("mkdir"));
+                }
+            }
+            for bind in &plan.binds {
+                if bind.file {
+                    check(write_file(&bind.target, b""), "create mount point")?;
+                }
+                check(
+                    libc::mount(
+                        bind.source.as_ptr(),
+                        bind.target.as_ptr(),
+                        null,
+                        libc::MS_BIND | libc::MS_REC,
+                        no_data,
+                    ),
+                    "bind mount",
+                )?;
+                if bind.read_only {
+                    remount_read_only(&bind.target)?;
+                }
+            }
+            for (target, options) in &plan.overlays {
+                check(
+                    libc::mount(
+                        c"overlay".as_ptr(),
+                        target.as_ptr(),
+                        c"overlay".as_ptr(),
+                        0,
+                        options.as_ptr().cast(),
+                    ),
+                    "mount overlay",
+                )?;
+            }
+            check(
+                libc::mount(
+                    c"proc".as_ptr(),
+                    c"/proc".as_ptr(),
+                    c"proc".as_ptr(),
+                    libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                    no_data,
+                ),
+                "mount /proc",
+            )?;
+            check(
+                libc::umount2(c"/oldroot".as_ptr(), libc::MNT_DETACH),
+                "detach old root",
+            )?;
+            check(libc::rmdir(c"/oldroot".as_ptr()), "remove old root")?;
+
+            for (path, contents) in &plan.files {
+                check(write_file(path, contents), "write workspace file")?;
+            }
+            check(libc::chdir(c"/work".as_ptr()), "chdir /work")?;
+        }
+        Ok(())
+    }
+
+    /// Remount a bind read-only. Flags the host already locked (nosuid,
+    /// nodev, ...) must be repeated or the kernel refuses the remount.
+    unsafe fn remount_read_only(target: &CStr) -> std::io::Result<()> {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        check(libc::statvfs(target.as_ptr(), &mut stat), "statvfs")?;
+        let mut flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY;
+        for (st, ms) in [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ] {
+            if stat.f_flag & st != 0 {
+                flags |= ms;
+            }
+        }
+        let null = std::ptr::null();
+        check(
+            libc::mount(null, target.as_ptr(), null, flags, std::ptr::null()),
+            "remount read-only",
+        )
+    }
+
+    /// Last steps before exec: loopback, hostname, rlimits, then seccomp.
+    fn restrict(plan: &Plan) -> std::io::Result<()> {
+        // SAFETY: raw syscalls on stack data and the prepared filter.
+        unsafe {
+            let name = b"sandbox";
+            check(libc::sethostname(name.as_ptr().cast(), name.len()), "sethostname")?;
+            loopback_up()?;
+
+            set_limits(&plan.limits, true)?;
+        }
+        seal(&plan.filter)
+    }
+
+    /// Apply the rlimits. `RLIMIT_NPROC` counts every process of the uid, so
+    /// it is only meaningful for the sandbox's own user namespace.
+    fn set_limits(limits: &SandboxLimits, nproc: bool) -> std::io::Result<()> {
+        let values = [
+            (libc::RLIMIT_CPU, limits.cpu_secs),
+            (libc::RLIMIT_AS, limits.memory_bytes),
+            (libc::RLIMIT_NPROC, limits.max_processes),
+            (libc::RLIMIT_FSIZE, limits.max_file_bytes),
+            (libc::RLIMIT_CORE, 0),
+        ];
+        for (resource, value) in values {
+            if resource == libc::RLIMIT_NPROC && !nproc {
+                continue;
+            }
+            let limit = libc::rlimit {
+                rlim_cur: value,
+                rlim_max: value,
+            };
+            // SAFETY: setrlimit on a stack value.
+            check(unsafe { libc::setrlimit(resource, &limit) }, "setrlimit")?;
+        }
+        Ok(())
+    }
+
+    /// `no_new_privs`, then the seccomp filter.
+    fn seal(filter: &[libc::sock_filter]) -> std::io::Result<()> {
+        // SAFETY: prctl on integer arguments and the prepared filter.
+        unsafe {
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0), "no_new_privs")?;
+            let program = libc::sock_fprog {
+                len: filter.len() as u16,
+                filter: filter.as_ptr() as *mut _,
+            };
+            check(
+                libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &program as *const libc::sock_fprog,
+                ),
+                "seccomp",
+            )?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    /// SIGKILL every process in the group led by `pgid`.
+    pub(super) fn kill_group(pgid: u32) {
+        // SAFETY: kill with integer arguments.
+        unsafe {
+            libc::kill(-(pgid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+
+    /// Unconfined mode: no namespaces, but the child still gets its own
+    /// process group (so a timeout kills the whole tree), the rlimits other
+    /// than `RLIMIT_NPROC`, and the seccomp filter.
+    pub(super) fn install_unconfined(
+        cmd: &mut std::process::Command,
+        limits: SandboxLimits,
+    ) -> Result<(), SandboxError> {
+        let filter = seccomp_filter();
+        cmd.process_group(0);
+        // SAFETY: the hook only makes raw syscalls on data prepared above.
+        unsafe {
+            cmd.pre_exec(move || {
+                set_limits(&limits, false)?;
+                seal(&filter)
+            });
+        }
+        Ok(())
+    }
+
+    /// A fresh network namespace has `lo`, but down. Tests that bind to
+    /// localhost expect it up.
+    unsafe fn loopback_up() -> std::io::Result<()> {
+        let sock = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        check(sock, "socket")?;
+        let mut req: libc::ifreq = std::mem::zeroed();
+        for (dst, src) in req.ifr_name.iter_mut().zip(b"lo") {
+            *dst = *src as libc::c_char;
+        }
+        let mut ret = libc::ioctl(sock, libc::SIOCGIFFLAGS, &mut req);
+        if ret >= 0 {
+            req.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+            ret = libc::ioctl(sock, libc::SIOCSIFFLAGS, &req);
+        }
+        libc::close(sock);
+        check(ret, "loopback up")
+    }
+
+    fn stmt(code: u32, k: u32) -> libc::sock_filter {
+        libc::sock_filter {
+            code: code as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+        libc::sock_filter {
+            code: code as u16,
+            jt,
+            jf,
+            k,
+        }
+    }
+
+    /// Offsets into `struct seccomp_data`.
+    const NR: u32 = 0;
+    const ARCH: u32 = 4;
+    const ARG0: u32 = 16;
+
+    /// A denylist filter: foreign syscall ABIs kill the process, [`DENIED`]
+    /// syscalls and namespace-creating `clone`s fail with `EPERM`, and
+    /// `clone3` (whose flags a filter cannot inspect) reports `ENOSYS` so libc
+    /// falls back to `clone`.
+    fn seccomp_filter() -> Vec<libc::sock_filter> {
+        use libc::{
+            BPF_ABS, BPF_JEQ, BPF_JGE, BPF_JMP, BPF_JSET, BPF_K, BPF_LD, BPF_RET, BPF_W,
+            SECCOMP_RET_ALLOW, SECCOMP_RET_ERRNO, SECCOMP_RET_KILL_PROCESS,
+        };
+        let arch = if cfg!(target_arch = "x86_64") {
+            AUDIT_ARCH_X86_64
+        } else {
+            AUDIT_ARCH_AARCH64
+        };
+        let eperm = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+        let mut filter = vec![
+            stmt(BPF_LD | BPF_W | BPF_ABS, ARCH),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, arch, 1, 0),
+            stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD | BPF_W | BPF_ABS, NR),
+        ];
+        if cfg!(target_arch = "x86_64") {
+            // x32 syscalls share the arch value but set bit 30.
+            filter.push(jump(BPF_JMP | BPF_JGE | BPF_K, 0x4000_0000, 0, 1));
+            filter.push(stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS));
+        }
+        for &nr in DENIED {
+            filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1));
+            filter.push(stmt(BPF_RET | BPF_K, eperm));
+        }
+        filter.extend([
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_clone3 as u32, 0, 1),
+            stmt(BPF_RET | BPF_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_clone as u32, 0, 3),
+            stmt(BPF_LD | BPF_W | BPF_ABS, ARG0),
+            jump(BPF_JMP | BPF_JSET | BPF_K, NAMESPACE_FLAGS as u32, 0, 1),
+            stmt(BPF_RET | BPF_K, eperm),
+            stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        ]);
+        filter
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod linux {
+    use std::path::Path;
+
+    use super::{Layout, SandboxError, SandboxLimits};
+
+    pub(super) fn install(
+        _cmd: &mut std::process::Command,
+        _layout: Layout,
+        _limits: SandboxLimits,
+    ) -> Result<(), SandboxError> {
+        Err(SandboxError::Unavailable(
+            "sandboxing requires Linux namespaces".to_string(),
+        ))
+    }
+
+    pub(super) fn install_unconfined(
+        _cmd: &mut std::process::Command,
+        _limits: SandboxLimits,
+    ) -> Result<(), SandboxError> {
+        Err(SandboxError::Unavailable(
+            "sandboxing requires Linux namespaces".to_string(),
+        ))
+    }
+
+    pub(super) fn symlink(_target: &Path, _link: &Path) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn kill_group(_pgid: u32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Skip (rather than fail) on kernels without unprivileged user
+    /// namespaces, e.g. inside a default Docker seccomp profile.
+    fn sandbox_available() -> bool {
+        match check() {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("skipping: {e}");
+                false
+            }
+        }
+    }
+
+    async fn sh(script: &str) -> Output {
+        SandboxCommand::new("sh")
+            .args(["-c", script])
+            .output()
+            .await
+            .expect("sandboxed sh runs")
+    }
+
+    #[test]
+    fn layout_creates_parents_before_children() {
+        let layout = SandboxCommand::new("true")
+            .file("src/nested/lib.rs", "")
+            .layout()
+            .unwrap();
+        let dirs = layout.dirs();
+        let work = dirs.iter().position(|d| d == Path::new("/work")).unwrap();
+        let nested = dirs
+            .iter()
+            .position(|d| d == Path::new("/work/src/nested"))
+            .unwrap();
+        assert!(work < nested);
+        assert!(SandboxCommand::new("true")
+            .file("../escape", "")
+            .layout()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn workspace_is_private_and_toolchain_read_only() {
+        if !sandbox_available() {
+            return;
+        }
+        let out = SandboxCommand::new("sh")
+            .args(["-c", "cat hello.txt; id -u; touch /usr/x 2>/dev/null || echo ro"])
+            .file("hello.txt", "hi\n")
+            .output()
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "hi\n1000\nro\n");
+        assert!(!Path::new("/work/hello.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn host_secrets_and_processes_are_hidden() {
+        if !sandbox_available() {
+            return;
+        }
+        std::env::set_var("X402_SANDBOX_TEST_SECRET", "leak");
+        let out = sh("echo \"[$X402_SANDBOX_TEST_SECRET]\"; ls /proc | grep -c '^[0-9]'").await;
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let mut lines = stdout.lines();
+        assert_eq!(lines.next(), Some("[]"));
+        let pids: u32 = lines.next().unwrap().trim().parse().unwrap();
+        assert!(pids <= 3, "host processes visible: {stdout}");
+    }
+
+    #[tokio::test]
+    async fn no_network_and_no_remount() {
+        if !sandbox_available() {
+            return;
+        }
+        let out = sh("ls /sys/class/net 2>/dev/null; cat /proc/net/dev | tail -n +3 | cut -d: -f1").await;
+        let ifaces: Vec<_> = String::from_utf8_lossy(&out.stdout)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        assert!(ifaces.iter().all(|i| i == "lo"), "{ifaces:?}");
+        let out = sh("unshare -r true 2>/dev/null && echo escaped || echo denied").await;
+        assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "denied");
+    }
+
+    #[tokio::test]
+    async fn cargo_test_runs_offline() {
+        if !sandbox_available() {
+            return;
+        }
+        let out = SandboxCommand::new("cargo")
+            .args(["test", "--offline"])
+            .env("CARGO_TARGET_DIR", "/work/target")
+            .file(
+                "Cargo.toml",
+                "[package]\nname = \"probe\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+            )
+            .file("src/lib.rs", "pub fn two() -> u32 { 2 }")
+            .file("tests/probe.rs", "#[test]\nfn two() { assert_eq!(probe::two(), 2); }")
+            .output()
+            .await
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(out.status.success(), "{stderr}");
+        assert!(String::from_utf8_lossy(&out.stdout).contains("1 passed"));
+    }
+
+    #[tokio::test]
+    async fn overlay_writes_stay_in_the_sandbox() {
+        if !sandbox_available() {
+            return;
+        }
+        let lower = std::env::temp_dir().join(format!("x402-overlay-test-{}", std::process::id()));
+        std::fs::create_dir_all(&lower).unwrap();
+        std::fs::write(lower.join("shared"), "cached\n").unwrap();
+        let out = SandboxCommand::new("sh")
+            .args(["-c", "cat /cache/shared && echo new > /cache/shared && cat /cache/shared"])
+            .overlay(&lower, "/cache")
+            .output()
+            .await
+            .unwrap();
+        let shared = std::fs::read_to_string(lower.join("shared")).unwrap();
+        std::fs::remove_dir_all(&lower).unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "cached\nnew\n");
+        assert_eq!(shared, "cached\n");
+    }
+
+    #[test]
+    fn unconfined_mode_is_opt_in() {
+        match check() {
+            Ok(()) => assert_eq!(mode().unwrap(), Mode::Confined),
+            Err(e) if !unconfined_allowed() => {
+                eprintln!("skipping: {e}");
+                assert!(mode().is_err());
+            }
+            Err(_) => assert_eq!(mode().unwrap(), Mode::Unconfined),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn unconfined_runs_in_a_scratch_root() {
+        let host = std::env::temp_dir().join(format!("x402-unconfined-test-{}", std::process::id()));
+        let lower = host.join("lower");
+        std::fs::create_dir_all(&lower).unwrap();
+        std::fs::write(lower.join("shared"), "cached\n").unwrap();
+        std::env::set_var("X402_SANDBOX_TEST_SECRET", "leak");
+        let out = SandboxCommand::new("sh")
+            .args([
+                "-c",
+                "cat hello.txt; echo \"[$X402_SANDBOX_TEST_SECRET]\"; echo out > \"$OUT/built\"; \
+                 cat \"$CACHE/shared\"; echo new > \"$CACHE/shared\"",
+            ])
+            .env("OUT", "/target")
+            .env("CACHE", "/cache")
+            .file("hello.txt", "hi\n")
+            .writable(host.join("target"), "/target")
+            .overlay(&lower, "/cache")
+            .unconfined()
+            .await
+            .unwrap();
+        let built = std::fs::read_to_string(host.join("target/built")).unwrap();
+        let shared = std::fs::read_to_string(lower.join("shared")).unwrap();
+        std::fs::remove_dir_all(&host).unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "hi\n[]\ncached\n", "{out:?}");
+        assert_eq!(built, "out\n");
+        assert_eq!(shared, "cached\n");
+    }
+
+    #[tokio::test]
+    async fn timeout_kills_the_sandbox() {
+        if !sandbox_available() {
+            return;
+        }
+        let started = std::time::Instant::now();
+        let err = SandboxCommand::new("sleep")
+            .arg("30")
+            .limits(SandboxLimits {
+                timeout_secs: 1,
+                ..SandboxLimits::default()
+            })
+            .output()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SandboxError::Timeout(1)));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,336 @@
+//! Triggers — run a cartridge on a schedule or in response to node events.
+//!
+//! A cartridge declares triggers in its manifest. The node's scheduler invokes
+//! `x402_handle` with a synthetic [`CartridgeRequest`] whenever one fires:
+//!
+//! ```json
+//! "triggers": [
+//!   { "type": "cron", "schedule": "*/5 * * * *" },
+//!   { "type": "event", "event": "payment.settled" }
+//! ]
+//! ```
+//!
+//! Triggered requests are `POST /__trigger` with an `x-x402-trigger` header
+//! naming the trigger, and a JSON body describing what fired.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::CartridgeError;
+use crate::manifest::CartridgeRequest;
+
+/// Path of synthetic trigger requests.
+pub const TRIGGER_PATH: &str = "/__trigger";
+
+/// Header carrying the trigger label (`cron:<schedule>` or `event:<name>`).
+pub const TRIGGER_HEADER: &str = "x-x402-trigger";
+
+/// Built-in event: an x402 payment to this node was settled.
+pub const EVENT_PAYMENT_SETTLED: &str = "payment.settled";
+/// Built-in event: a new peer joined the colony.
+pub const EVENT_PEER_JOINED: &str = "peer.joined";
+/// Built-in event: a soul goal was completed.
+pub const EVENT_GOAL_COMPLETED: &str = "goal.completed";
+
+/// Prefix for internal (webhook-style) events posted by the operator or other cartridges.
+pub const CUSTOM_EVENT_PREFIX: &str = "custom.";
+
+/// A manifest-declared trigger.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Trigger {
+    /// Fire on a 5-field cron schedule (UTC): minute hour day-of-month month day-of-week.
+    Cron { schedule: String },
+    /// Fire when a node event with this name is emitted.
+    Event { event: String },
+}
+
+impl Trigger {
+    /// Check the trigger is well-formed. Cron schedules must parse; event
+    /// names must be a built-in event or start with `custom.`.
+    pub fn validate(&self) -> Result<(), CartridgeError> {
+        match self {
+            Self::Cron { schedule } => CronSchedule::parse(schedule).map(|_| ()),
+            Self::Event { event } => {
+                let known = [
+                    EVENT_PAYMENT_SETTLED,
+                    EVENT_PEER_JOINED,
+                    EVENT_GOAL_COMPLETED,
+                ];
+                let custom = event
+                    .strip_prefix(CUSTOM_EVENT_PREFIX)
+                    .is_some_and(is_valid_event_suffix);
+                if known.contains(&event.as_str()) || custom {
+                    Ok(())
+                } else {
+                    Err(CartridgeError::Trigger(format!("unknown event '{event}'")))
+                }
+            }
+        }
+    }
+
+    /// Short label used in the trigger header and run history.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Cron { schedule } => format!("cron:{schedule}"),
+            Self::Event { event } => format!("event:{event}"),
+        }
+    }
+
+    /// True if this is an event trigger listening for `event`.
+    pub fn listens_for(&self, event: &str) -> bool {
+        matches!(self, Self::Event { event: e } if e == event)
+    }
+}
+
+/// Custom event suffixes: non-empty, alphanumeric plus `-`, `_`, `.`.
+fn is_valid_event_suffix(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 64
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// A node event delivered to event triggers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerEvent {
+    /// Event name, e.g. `payment.settled` or `custom.deploy`.
+    pub name: String,
+    /// Event-specific data passed through to the cartridge.
+    #[serde(default)]
+    pub payload: serde_json::Value,
+}
+
+impl TriggerEvent {
+    pub fn new(name: impl Into<String>, payload: serde_json::Value) -> Self {
+        Self {
+            name: name.into(),
+            payload,
+        }
+    }
+}
+
+/// Build the synthetic request sent to `x402_handle` when `trigger` fires.
+/// `event` is set for event triggers and carried in the body.
+pub fn trigger_request(
+    trigger: &Trigger,
+    event: Option<&TriggerEvent>,
+    fired_at: DateTime<Utc>,
+) -> CartridgeRequest {
+    let body = serde_json::json!({
+        "trigger": trigger,
+        "event": event,
+        "fired_at": fired_at.timestamp(),
+    })
+    .to_string();
+    let mut headers = HashMap::new();
+    headers.insert(TRIGGER_HEADER.to_string(), trigger.label());
+    headers.insert("content-type".to_string(), "application/json".to_string());
+    CartridgeRequest {
+        method: "POST".to_string(),
+        path: TRIGGER_PATH.to_string(),
+        query: String::new(),
+        raw_body: Some(body.clone().into_bytes()),
+        body,
+        headers,
+        payment: None,
+    }
+}
+
+/// A parsed 5-field cron expression.
+///
+/// Supports `*`, single values, ranges (`1-5`), steps (`*/15`, `0-30/10`) and
+/// comma lists. Day-of-week is 0-6 with Sunday = 0 (7 is also accepted).
+/// When both day-of-month and day-of-week are restricted, either may match
+/// (standard cron semantics).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self, CartridgeError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(CartridgeError::Trigger(format!(
+                "cron '{expr}' must have 5 fields, got {}",
+                fields.len()
+            )));
+        }
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        // 7 is an alias for Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            dom_restricted: fields[2] != "*",
+            dow_restricted: fields[4] != "*",
+        })
+    }
+
+    /// True if the schedule fires in the minute containing `t`.
+    pub fn matches(&self, t: &DateTime<Utc>) -> bool {
+        let bit = |set: u64, v: u32| set & (1 << v) != 0;
+        if !bit(self.minutes, t.minute())
+            || !bit(self.hours, t.hour())
+            || !bit(self.months, t.month())
+        {
+            return false;
+        }
+        let dom = bit(self.days_of_month, t.day());
+        let dow = bit(self.days_of_week, t.weekday().num_days_from_sunday());
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+
+    /// The first matching minute strictly after `t`, searching up to ~4 years ahead.
+    pub fn next_after(&self, t: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut candidate = t.with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1);
+        for _ in 0..(4 * 366 * 24 * 60) {
+            if self.matches(&candidate) {
+                return Some(candidate);
+            }
+            candidate += chrono::Duration::minutes(1);
+        }
+        None
+    }
+}
+
+/// Parse one cron field into a bitset of allowed values in `min..=max`.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, CartridgeError> {
+    let err = |msg: &str| CartridgeError::Trigger(format!("cron field '{field}': {msg}"));
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (
+                r,
+                s.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| err("invalid step"))?,
+            ),
+            None => (part, 1),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            let a = a.parse::<u32>().map_err(|_| err("invalid range"))?;
+            let b = b.parse::<u32>().map_err(|_| err("invalid range"))?;
+            (a, b)
+        } else {
+            let v = range.parse::<u32>().map_err(|_| err("invalid value"))?;
+            // `5/10` means "from 5 to max, every 10"
+            if step > 1 {
+                (v, max)
+            } else {
+                (v, v)
+            }
+        };
+        if lo < min || hi > max || lo > hi {
+            return Err(err(&format!("out of range {min}-{max}")));
+        }
+        let mut v = lo;
+        while v <= hi {
+            set |= 1 << v;
+            v += step;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn every_five_minutes() {
+        let s = CronSchedule::parse("*/5 * * * *").unwrap();
+        assert!(s.matches(&at(2026, 1, 1, 12, 0)));
+        assert!(s.matches(&at(2026, 1, 1, 12, 35)));
+        assert!(!s.matches(&at(2026, 1, 1, 12, 36)));
+    }
+
+    #[test]
+    fn lists_ranges_and_weekdays() {
+        // 09:30 on weekdays
+        let s = CronSchedule::parse("30 9 * * 1-5").unwrap();
+        // 2026-01-05 is a Monday, 2026-01-04 a Sunday
+        assert!(s.matches(&at(2026, 1, 5, 9, 30)));
+        assert!(!s.matches(&at(2026, 1, 4, 9, 30)));
+        let s = CronSchedule::parse("0 0,12 1 * *").unwrap();
+        assert!(s.matches(&at(2026, 3, 1, 12, 0)));
+        assert!(!s.matches(&at(2026, 3, 2, 12, 0)));
+    }
+
+    #[test]
+    fn dom_or_dow_when_both_restricted() {
+        // 1st of the month OR any Sunday (7 = Sunday)
+        let s = CronSchedule::parse("0 0 1 * 7").unwrap();
+        assert!(s.matches(&at(2026, 1, 1, 0, 0)));
+        assert!(s.matches(&at(2026, 1, 4, 0, 0)));
+        assert!(!s.matches(&at(2026, 1, 5, 0, 0)));
+    }
+
+    #[test]
+    fn next_after_skips_to_following_match() {
+        let s = CronSchedule::parse("15 * * * *").unwrap();
+        let next = s.next_after(&at(2026, 1, 1, 10, 15)).unwrap();
+        assert_eq!(next, at(2026, 1, 1, 11, 15));
+    }
+
+    #[test]
+    fn rejects_malformed_schedules() {
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("5-1 * * * *").is_err());
+    }
+
+    #[test]
+    fn validates_event_names() {
+        let ok = |e: &str| {
+            Trigger::Event {
+                event: e.to_string(),
+            }
+            .validate()
+            .is_ok()
+        };
+        assert!(ok(EVENT_PAYMENT_SETTLED));
+        assert!(ok("custom.deploy-finished"));
+        assert!(!ok("custom."));
+        assert!(!ok("something.else"));
+    }
+
+    #[test]
+    fn trigger_serde_and_request() {
+        let t: Trigger = serde_json::from_str(r#"{"type":"cron","schedule":"0 * * * *"}"#).unwrap();
+        assert_eq!(t.label(), "cron:0 * * * *");
+        let req = trigger_request(&t, None, at(2026, 1, 1, 0, 0));
+        assert_eq!(req.path, TRIGGER_PATH);
+        assert_eq!(req.headers[TRIGGER_HEADER], "cron:0 * * * *");
+        let body: serde_json::Value = serde_json::from_str(&req.body).unwrap();
+        assert_eq!(body["trigger"]["schedule"], "0 * * * *");
+    }
+}
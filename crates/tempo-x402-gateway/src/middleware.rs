@@ -29,6 +29,7 @@ pub fn platform_requirements(
         description: Some("Platform registration fee".to_string()),
         mime_type: Some("application/json".to_string()),
         facilitator_address,
+        metered: false,
     }
 }
 
@@ -51,6 +52,7 @@ pub fn endpoint_requirements(
         description: description.map(String::from),
         mime_type: Some("application/json".to_string()),
         facilitator_address,
+        metered: false,
     }
 }
 
@@ -0,0 +1,135 @@
+//! Encode/decode throughput of the from-scratch models, in tokens/sec.
+//!
+//! Run with `cargo bench -p tempo-x402-model --bench tokens`. To compare
+//! kernels, save a baseline on one tree (`-- --save-baseline before`) and
+//! rerun the other against it (`-- --baseline before`). Besides criterion's
+//! estimates, each case prints mean tokens/sec over a fixed sample.
+
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use x402_model::codegen::CodeGenModel;
+use x402_model::transformer::PlanTransformer;
+use x402_model::unified::UnifiedModel;
+use x402_model::IncrementalDecoder;
+
+/// Context (encoder) and target (decoder) lengths.
+const CONTEXT: usize = 64;
+const TARGET: usize = 32;
+
+const SAMPLES: usize = 10;
+
+fn tokens(n: usize, seed: u32) -> Vec<u32> {
+    (0..n as u32).map(|i| (i.wrapping_mul(2_654_435_761) ^ seed) % 8000 + 4).collect()
+}
+
+fn report(name: &str, n_tokens: usize, mut f: impl FnMut()) {
+    f(); // warm up rayon and the caches
+    let mut total = Duration::ZERO;
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+        f();
+        total += start.elapsed();
+    }
+    let per_call = total / SAMPLES as u32;
+    let rate = n_tokens as f64 / per_call.as_secs_f64();
+    println!("{name:<28} {per_call:>10.2?}/call  {rate:>10.1} tokens/sec");
+}
+
+/// Greedy generation of `TARGET` tokens through the KV cache.
+fn generate<M: IncrementalDecoder>(model: &M, enc: &[f32]) {
+    let mut cache = model.start(enc, CONTEXT);
+    let mut token = 1;
+    for _ in 0..TARGET {
+        let logits = model.step(&mut cache, token);
+        token = argmax(&logits);
+    }
+}
+
+fn argmax(v: &[f32]) -> u32 {
+    v.iter()
+        .enumerate()
+        .fold((0, f32::NEG_INFINITY), |best, (i, &x)| {
+            if x > best.1 {
+                (i, x)
+            } else {
+                best
+            }
+        })
+        .0 as u32
+}
+
+fn bench_codegen(c: &mut Criterion) {
+    let model = CodeGenModel::new();
+    let context = tokens(CONTEXT, 7);
+    let target = tokens(TARGET, 11);
+    let enc = model.encode(&context);
+
+    report("codegen/encode", CONTEXT, || {
+        model.encode(&context);
+    });
+    report("codegen/decode", TARGET, || {
+        model.decode(&target, &enc, CONTEXT);
+    });
+    report("codegen/generate_cached", TARGET, || generate(&model, &enc));
+
+    let mut group = c.benchmark_group("codegen");
+    group.sample_size(SAMPLES);
+    group.throughput(Throughput::Elements(CONTEXT as u64));
+    group.bench_function("encode", |b| b.iter(|| model.encode(&context)));
+    group.throughput(Throughput::Elements(TARGET as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| model.decode(&target, &enc, CONTEXT))
+    });
+    group.bench_function("generate_cached", |b| b.iter(|| generate(&model, &enc)));
+    group.finish();
+}
+
+fn bench_unified(c: &mut Criterion) {
+    let model = UnifiedModel::new();
+    let context = tokens(CONTEXT, 13);
+    let target = tokens(TARGET, 17);
+    let enc = model.encode(&context);
+
+    report("unified/encode", CONTEXT, || {
+        model.encode(&context);
+    });
+    report("unified/fast_predict", CONTEXT, || {
+        model.fast_predict(&context);
+    });
+    report("unified/decode", TARGET, || {
+        model.decode(&target, &enc, CONTEXT);
+    });
+    report("unified/generate_cached", TARGET, || generate(&model, &enc));
+
+    let mut group = c.benchmark_group("unified");
+    group.sample_size(SAMPLES);
+    group.throughput(Throughput::Elements(CONTEXT as u64));
+    group.bench_function("encode", |b| b.iter(|| model.encode(&context)));
+    group.bench_function("fast_predict", |b| {
+        b.iter(|| model.fast_predict(&context))
+    });
+    group.throughput(Throughput::Elements(TARGET as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| model.decode(&target, &enc, CONTEXT))
+    });
+    group.bench_function("generate_cached", |b| b.iter(|| generate(&model, &enc)));
+    group.finish();
+}
+
+fn bench_plan(c: &mut Criterion) {
+    let model = PlanTransformer::new();
+    let plan: Vec<u32> = (0..16).map(|i| 4 + i % 24).collect();
+
+    report("plan/forward", plan.len(), || {
+        model.forward(&plan);
+    });
+
+    let mut group = c.benchmark_group("plan");
+    group.throughput(Throughput::Elements(plan.len() as u64));
+    group.bench_function("forward", |b| b.iter(|| model.forward(&plan)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_codegen, bench_unified, bench_plan);
+criterion_main!(benches);
@@ -0,0 +1,1192 @@
+//! Reverse-mode automatic differentiation over a flat tape.
+//!
+//! Every op appends a node holding its output value; [`Tape::backward`] walks
+//! the tape in reverse and accumulates gradients into every node that depends
+//! on a parameter. All tensors are row-major `[rows x cols]` f32 matrices.
+//!
+//! Ops: `linear` (`x · Wᵀ`, the layout every model here stores), `matmul`,
+//! `add`, `add_row`, `relu`, `gelu`, `softmax`, `layer_norm` (scale only, as
+//! in the models), multi-head `attention`, `gather` (embedding lookup),
+//! `rows`, `mean_rows`, `cross_entropy` and `mse`.
+//!
+//! The transformer blocks at the bottom build the shared encoder/decoder
+//! layers on a tape, so a model's training step is forward + `backward` +
+//! [`Tape::sgd`] instead of hand-written gradients.
+
+use rayon::prelude::*;
+
+use crate::linalg::{axpy, dot, matmul_nn, matmul_nt, matmul_tn};
+use crate::optim::Gradients;
+use crate::quantize::Dims;
+
+/// Handle to a node on a [`Tape`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Var(usize);
+
+#[derive(Debug)]
+enum Op {
+    Leaf,
+    Linear {
+        x: Var,
+        w: Var,
+    },
+    MatMul {
+        a: Var,
+        b: Var,
+    },
+    Add(Var, Var),
+    AddRow {
+        x: Var,
+        b: Var,
+    },
+    Relu(Var),
+    Gelu(Var),
+    Softmax(Var),
+    LayerNorm {
+        x: Var,
+        scale: Var,
+        xhat: Vec<f32>,
+        inv_std: Vec<f32>,
+    },
+    Attention {
+        q: Var,
+        k: Var,
+        v: Var,
+        n_heads: usize,
+        /// `[n_heads x q_len x kv_len]`
+        probs: Vec<f32>,
+    },
+    Gather {
+        table: Var,
+        ids: Vec<usize>,
+    },
+    Rows {
+        x: Var,
+        start: usize,
+    },
+    MeanRows(Var),
+    CrossEntropy {
+        logits: Var,
+        labels: Vec<usize>,
+        probs: Vec<f32>,
+    },
+    Mse {
+        x: Var,
+        target: Vec<f32>,
+    },
+}
+
+#[derive(Debug)]
+struct Node {
+    value: Vec<f32>,
+    rows: usize,
+    cols: usize,
+    /// Whether any parameter flows into this node.
+    needs_grad: bool,
+    op: Op,
+}
+
+/// A recorded computation. Build the forward pass with the op methods, call
+/// [`backward`](Self::backward) on a scalar loss, then read [`grad`](Self::grad)
+/// or apply [`sgd`](Self::sgd) to each parameter.
+#[derive(Debug, Default)]
+pub struct Tape {
+    nodes: Vec<Node>,
+    grads: Vec<Option<Vec<f32>>>,
+}
+
+impl Tape {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constant input — no gradient flows into it.
+    pub fn input(&mut self, value: Vec<f32>, rows: usize, cols: usize) -> Var {
+        assert_eq!(value.len(), rows * cols, "input shape mismatch");
+        self.leaf(value, rows, cols, false)
+    }
+
+    /// Trainable parameter; its gradient is kept after `backward`.
+    pub fn param(&mut self, value: &[f32], rows: usize, cols: usize) -> Var {
+        assert_eq!(value.len(), rows * cols, "param shape mismatch");
+        self.leaf(value.to_vec(), rows, cols, true)
+    }
+
+    fn leaf(&mut self, value: Vec<f32>, rows: usize, cols: usize, needs_grad: bool) -> Var {
+        self.nodes.push(Node {
+            value,
+            rows,
+            cols,
+            needs_grad,
+            op: Op::Leaf,
+        });
+        Var(self.nodes.len() - 1)
+    }
+
+    fn push(&mut self, value: Vec<f32>, rows: usize, cols: usize, deps: &[Var], op: Op) -> Var {
+        debug_assert_eq!(value.len(), rows * cols);
+        let needs_grad = deps.iter().any(|v| self.nodes[v.0].needs_grad);
+        self.nodes.push(Node {
+            value,
+            rows,
+            cols,
+            needs_grad,
+            op,
+        });
+        Var(self.nodes.len() - 1)
+    }
+
+    pub fn value(&self, v: Var) -> &[f32] {
+        &self.nodes[v.0].value
+    }
+
+    /// `(rows, cols)` of a node.
+    pub fn shape(&self, v: Var) -> (usize, usize) {
+        (self.nodes[v.0].rows, self.nodes[v.0].cols)
+    }
+
+    /// Value of a `[1 x 1]` node.
+    pub fn scalar(&self, v: Var) -> f32 {
+        self.nodes[v.0].value[0]
+    }
+
+    /// Gradient of the last `backward` loss w.r.t. a parameter.
+    pub fn grad(&self, v: Var) -> Option<&[f32]> {
+        self.grads.get(v.0).and_then(|g| g.as_deref())
+    }
+
+    /// Plain SGD with per-element gradient clipping to [-1, 1]:
+    /// `param -= lr * clip(grad)`.
+    pub fn sgd(&self, param: &mut [f32], v: Var, lr: f32) {
+        if let Some(g) = self.grad(v) {
+            param
+                .par_iter_mut()
+                .zip(g.par_iter())
+                .for_each(|(p, &g)| *p -= lr * g.clamp(-1.0, 1.0));
+        }
+    }
+
+    // ── Ops ──────────────────────────────────────────────────────
+
+    /// `x · Wᵀ`: `x` is `[n x in]`, `w` is `[out x in]`; returns `[n x out]`.
+    pub fn linear(&mut self, x: Var, w: Var) -> Var {
+        let (n, k) = self.shape(x);
+        let (m, wk) = self.shape(w);
+        assert_eq!(k, wk, "linear: input width {k} vs weight width {wk}");
+        let value = matmul_nt(self.value(x), self.value(w), n, k, m);
+        self.push(value, n, m, &[x, w], Op::Linear { x, w })
+    }
+
+    /// `a · b`: `[n x k] · [k x m]`.
+    pub fn matmul(&mut self, a: Var, b: Var) -> Var {
+        let (n, k) = self.shape(a);
+        let (bk, m) = self.shape(b);
+        assert_eq!(k, bk, "matmul: inner dims {k} vs {bk}");
+        let value = matmul_nn(self.value(a), self.value(b), n, k, m);
+        self.push(value, n, m, &[a, b], Op::MatMul { a, b })
+    }
+
+    pub fn add(&mut self, a: Var, b: Var) -> Var {
+        assert_eq!(self.shape(a), self.shape(b), "add: shape mismatch");
+        let (rows, cols) = self.shape(a);
+        let value = self
+            .value(a)
+            .iter()
+            .zip(self.value(b))
+            .map(|(x, y)| x + y)
+            .collect();
+        self.push(value, rows, cols, &[a, b], Op::Add(a, b))
+    }
+
+    /// Add a `[1 x cols]` row (bias) to every row of `x`.
+    pub fn add_row(&mut self, x: Var, b: Var) -> Var {
+        let (rows, cols) = self.shape(x);
+        assert_eq!(self.shape(b), (1, cols), "add_row: bias shape");
+        let bias = self.value(b);
+        let value = self
+            .value(x)
+            .chunks(cols)
+            .flat_map(|row| row.iter().zip(bias).map(|(x, b)| x + b))
+            .collect();
+        self.push(value, rows, cols, &[x, b], Op::AddRow { x, b })
+    }
+
+    pub fn relu(&mut self, x: Var) -> Var {
+        let (rows, cols) = self.shape(x);
+        let value = self.value(x).iter().map(|v| v.max(0.0)).collect();
+        self.push(value, rows, cols, &[x], Op::Relu(x))
+    }
+
+    /// GELU, tanh approximation.
+    pub fn gelu(&mut self, x: Var) -> Var {
+        let (rows, cols) = self.shape(x);
+        let value = self.value(x).iter().map(|&v| gelu(v).0).collect();
+        self.push(value, rows, cols, &[x], Op::Gelu(x))
+    }
+
+    /// Row-wise softmax.
+    pub fn softmax(&mut self, x: Var) -> Var {
+        let (rows, cols) = self.shape(x);
+        let mut value = self.value(x).to_vec();
+        for row in value.chunks_mut(cols) {
+            softmax_in_place(row);
+        }
+        self.push(value, rows, cols, &[x], Op::Softmax(x))
+    }
+
+    /// Row-wise layer norm with a learned `[1 x cols]` scale and no bias,
+    /// matching the models' `layer_norm`.
+    pub fn layer_norm(&mut self, x: Var, scale: Var) -> Var {
+        let (rows, cols) = self.shape(x);
+        assert_eq!(self.shape(scale), (1, cols), "layer_norm: scale shape");
+        let s = self.value(scale);
+        let mut xhat = Vec::with_capacity(rows * cols);
+        let mut inv_std = Vec::with_capacity(rows);
+        for row in self.value(x).chunks(cols) {
+            let mean = row.iter().sum::<f32>() / cols as f32;
+            let var = row.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / cols as f32;
+            let inv = 1.0 / (var + 1e-5).sqrt();
+            inv_std.push(inv);
+            xhat.extend(row.iter().map(|v| (v - mean) * inv));
+        }
+        let value = xhat
+            .chunks(cols)
+            .flat_map(|row| row.iter().zip(s).map(|(x, s)| x * s))
+            .collect();
+        self.push(
+            value,
+            rows,
+            cols,
+            &[x, scale],
+            Op::LayerNorm {
+                x,
+                scale,
+                xhat,
+                inv_std,
+            },
+        )
+    }
+
+    /// Multi-head scaled dot-product attention over pre-projected Q/K/V.
+    /// `q` is `[q_len x d]`, `k`/`v` are `[kv_len x d]`; heads split `d`
+    /// evenly. With `causal`, query `i` only sees keys `0..=i`.
+    pub fn attention(&mut self, q: Var, k: Var, v: Var, n_heads: usize, causal: bool) -> Var {
+        let (n, d) = self.shape(q);
+        let (m, kd) = self.shape(k);
+        assert_eq!(kd, d, "attention: key width");
+        assert_eq!(self.shape(v), (m, d), "attention: value shape");
+        assert_eq!(d % n_heads, 0, "attention: d not divisible by heads");
+        let dh = d / n_heads;
+        let inv_sqrt = 1.0 / (dh as f32).sqrt();
+        let (qv, kv, vv) = (self.value(q), self.value(k), self.value(v));
+
+        let heads: Vec<(Vec<f32>, Vec<f32>)> = (0..n_heads)
+            .into_par_iter()
+            .map(|h| {
+                let off = h * dh;
+                let mut probs = vec![0.0f32; n * m];
+                let mut out = vec![0.0f32; n * dh];
+                for i in 0..n {
+                    let span = if causal { (i + 1).min(m) } else { m };
+                    let qi = &qv[i * d + off..i * d + off + dh];
+                    let p = &mut probs[i * m..i * m + span];
+                    for (t, s) in p.iter_mut().enumerate() {
+                        let kt = &kv[t * d + off..t * d + off + dh];
+                        *s = dot(qi, kt) * inv_sqrt;
+                    }
+                    softmax_in_place(p);
+                    let o = &mut out[i * dh..(i + 1) * dh];
+                    for (t, &w) in p.iter().enumerate() {
+                        axpy(o, w, &vv[t * d + off..t * d + off + dh]);
+                    }
+                }
+                (probs, out)
+            })
+            .collect();
+
+        let mut value = vec![0.0f32; n * d];
+        let mut probs = Vec::with_capacity(n_heads * n * m);
+        for (h, (p, out)) in heads.into_iter().enumerate() {
+            for i in 0..n {
+                value[i * d + h * dh..i * d + (h + 1) * dh]
+                    .copy_from_slice(&out[i * dh..(i + 1) * dh]);
+            }
+            probs.extend(p);
+        }
+        self.push(
+            value,
+            n,
+            d,
+            &[q, k, v],
+            Op::Attention {
+                q,
+                k,
+                v,
+                n_heads,
+                probs,
+            },
+        )
+    }
+
+    /// Embedding lookup: rows `ids` of `table`.
+    pub fn gather(&mut self, table: Var, ids: &[usize]) -> Var {
+        let (rows, cols) = self.shape(table);
+        let t = self.value(table);
+        let value = ids
+            .iter()
+            .flat_map(|&i| {
+                assert!(i < rows, "gather: id {i} out of range {rows}");
+                t[i * cols..(i + 1) * cols].iter().copied()
+            })
+            .collect();
+        let ids = ids.to_vec();
+        self.push(value, ids.len(), cols, &[table], Op::Gather { table, ids })
+    }
+
+    /// Rows `start..start + len` of `x`.
+    pub fn rows(&mut self, x: Var, start: usize, len: usize) -> Var {
+        let (rows, cols) = self.shape(x);
+        assert!(start + len <= rows, "rows: {start}+{len} out of {rows}");
+        let value = self.value(x)[start * cols..(start + len) * cols].to_vec();
+        self.push(value, len, cols, &[x], Op::Rows { x, start })
+    }
+
+    /// Column means: `[rows x cols]` -> `[1 x cols]`.
+    pub fn mean_rows(&mut self, x: Var) -> Var {
+        let (rows, cols) = self.shape(x);
+        let mut value = vec![0.0f32; cols];
+        for row in self.value(x).chunks(cols) {
+            axpy(&mut value, 1.0 / rows as f32, row);
+        }
+        self.push(value, 1, cols, &[x], Op::MeanRows(x))
+    }
+
+    /// Mean softmax cross-entropy of each logits row against its label.
+    pub fn cross_entropy(&mut self, logits: Var, labels: &[usize]) -> Var {
+        let (rows, cols) = self.shape(logits);
+        assert_eq!(labels.len(), rows, "cross_entropy: one label per row");
+        let mut probs = self.value(logits).to_vec();
+        let mut loss = 0.0f32;
+        for (row, &label) in probs.chunks_mut(cols).zip(labels) {
+            let max = row.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            let log_sum = row.iter().map(|l| (l - max).exp()).sum::<f32>().ln() + max;
+            loss += log_sum - row[label];
+            for p in row.iter_mut() {
+                *p = (*p - log_sum).exp();
+            }
+        }
+        let labels = labels.to_vec();
+        self.push(
+            vec![loss / rows as f32],
+            1,
+            1,
+            &[logits],
+            Op::CrossEntropy {
+                logits,
+                labels,
+                probs,
+            },
+        )
+    }
+
+    /// Mean squared error against a constant target.
+    pub fn mse(&mut self, x: Var, target: &[f32]) -> Var {
+        assert_eq!(self.value(x).len(), target.len(), "mse: length mismatch");
+        let n = target.len() as f32;
+        let loss = self
+            .value(x)
+            .iter()
+            .zip(target)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f32>()
+            / n;
+        let target = target.to_vec();
+        self.push(vec![loss], 1, 1, &[x], Op::Mse { x, target })
+    }
+
+    // ── Backward ─────────────────────────────────────────────────
+
+    /// Backpropagate from `loss` (seeded with ones). Afterwards only
+    /// parameter gradients are retained.
+    pub fn backward(&mut self, loss: Var) {
+        let nodes = &self.nodes;
+        let mut grads: Vec<Option<Vec<f32>>> = (0..nodes.len()).map(|_| None).collect();
+        grads[loss.0] = Some(vec![1.0; nodes[loss.0].value.len()]);
+
+        for i in (0..=loss.0).rev() {
+            let node = &nodes[i];
+            if !node.needs_grad {
+                continue;
+            }
+            let Some(g) = grads[i].take() else {
+                continue;
+            };
+            let (rows, cols) = (node.rows, node.cols);
+            match &node.op {
+                Op::Leaf => {
+                    grads[i] = Some(g);
+                }
+                Op::Linear { x, w } => {
+                    let (k, m) = (nodes[x.0].cols, nodes[w.0].rows);
+                    if let Some(dx) = slot(&mut grads, nodes, *x) {
+                        add_into(dx, &matmul_nn(&g, &nodes[w.0].value, rows, m, k));
+                    }
+                    if let Some(dw) = slot(&mut grads, nodes, *w) {
+                        add_into(dw, &matmul_tn(&g, &nodes[x.0].value, rows, m, k));
+                    }
+                }
+                Op::MatMul { a, b } => {
+                    let k = nodes[a.0].cols;
+                    if let Some(da) = slot(&mut grads, nodes, *a) {
+                        add_into(da, &matmul_nt(&g, &nodes[b.0].value, rows, cols, k));
+                    }
+                    if let Some(db) = slot(&mut grads, nodes, *b) {
+                        add_into(db, &matmul_tn(&nodes[a.0].value, &g, rows, k, cols));
+                    }
+                }
+                Op::Add(a, b) => {
+                    if let Some(da) = slot(&mut grads, nodes, *a) {
+                        add_into(da, &g);
+                    }
+                    if let Some(db) = slot(&mut grads, nodes, *b) {
+                        add_into(db, &g);
+                    }
+                }
+                Op::AddRow { x, b } => {
+                    if let Some(dx) = slot(&mut grads, nodes, *x) {
+                        add_into(dx, &g);
+                    }
+                    if let Some(db) = slot(&mut grads, nodes, *b) {
+                        for row in g.chunks(cols) {
+                            add_into(db, row);
+                        }
+                    }
+                }
+                Op::Relu(x) => {
+                    if let Some(dx) = slot(&mut grads, nodes, *x) {
+                        for ((d, &g), &y) in dx.iter_mut().zip(&g).zip(&node.value) {
+                            if y > 0.0 {
+                                *d += g;
+                            }
+                        }
+                    }
+                }
+                Op::Gelu(x) => {
+                    let xv = &nodes[x.0].value;
+                    if let Some(dx) = slot(&mut grads, nodes, *x) {
+                        for ((d, &g), &v) in dx.iter_mut().zip(&g).zip(xv) {
+                            *d += g * gelu(v).1;
+                        }
+                    }
+                }
+                Op::Softmax(x) => {
+                    if let Some(dx) = slot(&mut grads, nodes, *x) {
+                        for ((d, g), y) in dx
+                            .chunks_mut(cols)
+                            .zip(g.chunks(cols))
+                            .zip(node.value.chunks(cols))
+                        {
+                            let s = dot(g, y);
+                            for j in 0..cols {
+                                d[j] += y[j] * (g[j] - s);
+                            }
+                        }
+                    }
+                }
+                Op::LayerNorm {
+                    x,
+                    scale,
+                    xhat,
+                    inv_std,
+                } => {
+                    let s = &nodes[scale.0].value;
+                    if let Some(ds) = slot(&mut grads, nodes, *scale) {
+                        for (g, xh) in g.chunks(cols).zip(xhat.chunks(cols)) {
+                            for j in 0..cols {
+                                ds[j] += g[j] * xh[j];
+                            }
+                        }
+                    }
+                    if let Some(dx) = slot(&mut grads, nodes, *x) {
+                        for r in 0..rows {
+                            let g = &g[r * cols..(r + 1) * cols];
+                            let xh = &xhat[r * cols..(r + 1) * cols];
+                            let dxhat: Vec<f32> = g.iter().zip(s).map(|(g, s)| g * s).collect();
+                            let mean_d = dxhat.iter().sum::<f32>() / cols as f32;
+                            let mean_dx = dot(&dxhat, xh) / cols as f32;
+                            for j in 0..cols {
+                                dx[r * cols + j] +=
+                                    inv_std[r] * (dxhat[j] - mean_d - xh[j] * mean_dx);
+                            }
+                        }
+                    }
+                }
+                Op::Attention {
+                    q,
+                    k,
+                    v,
+                    n_heads,
+                    probs,
+                } => {
+                    let (n, d, m) = (rows, cols, nodes[k.0].rows);
+                    let (dq, dk, dv) = attention_backward(
+                        &g,
+                        probs,
+                        &nodes[q.0].value,
+                        &nodes[k.0].value,
+                        &nodes[v.0].value,
+                        (n, m, d, *n_heads),
+                    );
+                    for (var, delta) in [(*q, dq), (*k, dk), (*v, dv)] {
+                        if let Some(dst) = slot(&mut grads, nodes, var) {
+                            add_into(dst, &delta);
+                        }
+                    }
+                }
+                Op::Gather { table, ids } => {
+                    if let Some(dt) = slot(&mut grads, nodes, *table) {
+                        for (r, &id) in ids.iter().enumerate() {
+                            add_into(
+                                &mut dt[id * cols..(id + 1) * cols],
+                                &g[r * cols..(r + 1) * cols],
+                            );
+                        }
+                    }
+                }
+                Op::Rows { x, start } => {
+                    if let Some(dx) = slot(&mut grads, nodes, *x) {
+                        add_into(&mut dx[start * cols..(start + rows) * cols], &g);
+                    }
+                }
+                Op::MeanRows(x) => {
+                    let n = nodes[x.0].rows;
+                    if let Some(dx) = slot(&mut grads, nodes, *x) {
+                        for row in dx.chunks_mut(cols) {
+                            axpy(row, 1.0 / n as f32, &g);
+                        }
+                    }
+                }
+                Op::CrossEntropy {
+                    logits,
+                    labels,
+                    probs,
+                } => {
+                    let n = labels.len();
+                    let c = nodes[logits.0].cols;
+                    let scale = g[0] / n as f32;
+                    if let Some(dl) = slot(&mut grads, nodes, *logits) {
+                        for (r, &label) in labels.iter().enumerate() {
+                            let row = &mut dl[r * c..(r + 1) * c];
+                            axpy(row, scale, &probs[r * c..(r + 1) * c]);
+                            row[label] -= scale;
+                        }
+                    }
+                }
+                Op::Mse { x, target } => {
+                    let scale = 2.0 * g[0] / target.len() as f32;
+                    let xv = &nodes[x.0].value;
+                    if let Some(dx) = slot(&mut grads, nodes, *x) {
+                        for ((d, &a), &b) in dx.iter_mut().zip(xv).zip(target) {
+                            *d += scale * (a - b);
+                        }
+                    }
+                }
+            }
+        }
+        self.grads = grads;
+    }
+}
+
+/// Gradient buffer for `v`, zero-initialised on first use; `None` when no
+/// parameter flows into `v`.
+fn slot<'g>(grads: &'g mut [Option<Vec<f32>>], nodes: &[Node], v: Var) -> Option<&'g mut Vec<f32>> {
+    let node = &nodes[v.0];
+    if !node.needs_grad {
+        return None;
+    }
+    Some(grads[v.0].get_or_insert_with(|| vec![0.0; node.value.len()]))
+}
+
+fn attention_backward(
+    g: &[f32],
+    probs: &[f32],
+    q: &[f32],
+    k: &[f32],
+    v: &[f32],
+    (n, m, d, n_heads): (usize, usize, usize, usize),
+) -> (Vec<f32>, Vec<f32>, Vec<f32>) {
+    let dh = d / n_heads;
+    let inv_sqrt = 1.0 / (dh as f32).sqrt();
+    let heads: Vec<_> = (0..n_heads)
+        .into_par_iter()
+        .map(|h| {
+            let off = h * dh;
+            let p = &probs[h * n * m..(h + 1) * n * m];
+            let (mut dq, mut dk, mut dv) = (
+                vec![0.0f32; n * dh],
+                vec![0.0f32; m * dh],
+                vec![0.0f32; m * dh],
+            );
+            let mut ds = vec![0.0f32; m];
+            for i in 0..n {
+                let gi = &g[i * d + off..i * d + off + dh];
+                let pi = &p[i * m..(i + 1) * m];
+                // dP = g · vᵀ, dS = P ⊙ (dP - Σ P ⊙ dP)
+                for t in 0..m {
+                    ds[t] = if pi[t] == 0.0 {
+                        0.0
+                    } else {
+                        dot(gi, &v[t * d + off..t * d + off + dh])
+                    };
+                }
+                let s = dot(pi, &ds);
+                for t in 0..m {
+                    if pi[t] == 0.0 {
+                        continue;
+                    }
+                    axpy(&mut dv[t * dh..(t + 1) * dh], pi[t], gi);
+                    let dst = pi[t] * (ds[t] - s) * inv_sqrt;
+                    axpy(
+                        &mut dq[i * dh..(i + 1) * dh],
+                        dst,
+                        &k[t * d + off..t * d + off + dh],
+                    );
+                    axpy(
+                        &mut dk[t * dh..(t + 1) * dh],
+                        dst,
+                        &q[i * d + off..i * d + off + dh],
+                    );
+                }
+            }
+            (dq, dk, dv)
+        })
+        .collect();
+
+    let (mut dq, mut dk, mut dv) = (
+        vec![0.0f32; n * d],
+        vec![0.0f32; m * d],
+        vec![0.0f32; m * d],
+    );
+    for (h, (hq, hk, hv)) in heads.into_iter().enumerate() {
+        let off = h * dh;
+        for i in 0..n {
+            dq[i * d + off..i * d + off + dh].copy_from_slice(&hq[i * dh..(i + 1) * dh]);
+        }
+        for t in 0..m {
+            dk[t * d + off..t * d + off + dh].copy_from_slice(&hk[t * dh..(t + 1) * dh]);
+            dv[t * d + off..t * d + off + dh].copy_from_slice(&hv[t * dh..(t + 1) * dh]);
+        }
+    }
+    (dq, dk, dv)
+}
+
+// ── Kernels ──────────────────────────────────────────────────────
+
+fn add_into(dst: &mut [f32], src: &[f32]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d += s;
+    }
+}
+
+fn softmax_in_place(row: &mut [f32]) {
+    let max = row.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let mut sum = 0.0;
+    for v in row.iter_mut() {
+        *v = (*v - max).exp();
+        sum += *v;
+    }
+    for v in row.iter_mut() {
+        *v /= sum;
+    }
+}
+
+/// GELU (tanh approximation) and its derivative.
+fn gelu(x: f32) -> (f32, f32) {
+    const C: f32 = 0.797_884_6; // sqrt(2/pi)
+    let inner = C * (x + 0.044715 * x * x * x);
+    let t = inner.tanh();
+    let y = 0.5 * x * (1.0 + t);
+    let dy = 0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * C * (1.0 + 3.0 * 0.044715 * x * x);
+    (y, dy)
+}
+
+// ── Transformer blocks ───────────────────────────────────────────
+
+/// Weights of an encoder layer, in tape order:
+/// `wq, wk, wv, wo, ff_w1, ff_w2, ln1_scale, ln2_scale`.
+pub(crate) trait EncoderParams {
+    fn tensors(&self) -> [&[f32]; 8];
+    fn tensors_mut(&mut self) -> [&mut [f32]; 8];
+}
+
+/// Weights of a decoder layer, in tape order: `wq, wk, wv, wo, cross_wq,
+/// cross_wk, cross_wv, cross_wo, ff_w1, ff_w2, ln1_scale, ln2_scale, ln3_scale`.
+pub(crate) trait DecoderParams {
+    fn tensors(&self) -> [&[f32]; 13];
+    fn tensors_mut(&mut self) -> [&mut [f32]; 13];
+}
+
+/// Checkpoint names of [`EncoderParams::tensors`], in order.
+pub(crate) const ENCODER_TENSORS: [&str; 8] = [
+    "wq",
+    "wk",
+    "wv",
+    "wo",
+    "ff_w1",
+    "ff_w2",
+    "ln1_scale",
+    "ln2_scale",
+];
+
+/// Checkpoint names of [`DecoderParams::tensors`], in order.
+pub(crate) const DECODER_TENSORS: [&str; 13] = [
+    "wq",
+    "wk",
+    "wv",
+    "wo",
+    "cross_wq",
+    "cross_wk",
+    "cross_wv",
+    "cross_wo",
+    "ff_w1",
+    "ff_w2",
+    "ln1_scale",
+    "ln2_scale",
+    "ln3_scale",
+];
+
+/// Pair layer tensors with their checkpoint names under `prefix`
+/// (e.g. `enc.0.wq`), for [`crate::optim::Optimizer::step`].
+pub(crate) fn named<'a, const N: usize>(
+    prefix: &str,
+    tensors: [&'a mut [f32]; N],
+    names: [&str; N],
+) -> Vec<(String, &'a mut [f32])> {
+    tensors
+        .into_iter()
+        .zip(names)
+        .map(|(w, n)| (format!("{prefix}.{n}"), w))
+        .collect()
+}
+
+/// Add the gradient of `v` (if any reached it) to `out` under `name`.
+pub(crate) fn collect(t: &Tape, v: Var, name: &str, out: &mut Gradients) {
+    if let Some(g) = t.grad(v) {
+        out.add(name, g);
+    }
+}
+
+fn encoder_shapes(Dims { d, ff, .. }: Dims) -> [(usize, usize); 8] {
+    let sq = (d, d);
+    [sq, sq, sq, sq, (ff, d), (d, ff), (1, d), (1, d)]
+}
+
+fn decoder_shapes(Dims { d, ff, .. }: Dims) -> [(usize, usize); 13] {
+    let sq = (d, d);
+    [
+        sq,
+        sq,
+        sq,
+        sq,
+        sq,
+        sq,
+        sq,
+        sq,
+        (ff, d),
+        (d, ff),
+        (1, d),
+        (1, d),
+        (1, d),
+    ]
+}
+
+fn register<const N: usize>(
+    t: &mut Tape,
+    tensors: [&[f32]; N],
+    shapes: [(usize, usize); N],
+) -> [Var; N] {
+    let mut i = 0;
+    tensors.map(|w| {
+        let (r, c) = shapes[i];
+        i += 1;
+        t.param(w, r, c)
+    })
+}
+
+/// Encoder layer parameters registered on a tape.
+pub(crate) struct EncoderVars([Var; 8]);
+
+/// Decoder layer parameters registered on a tape.
+pub(crate) struct DecoderVars([Var; 13]);
+
+impl EncoderVars {
+    pub(crate) fn register(t: &mut Tape, layer: &impl EncoderParams, dims: Dims) -> Self {
+        Self(register(t, layer.tensors(), encoder_shapes(dims)))
+    }
+
+    fn collect(&self, t: &Tape, prefix: &str, out: &mut Gradients) {
+        for (&v, n) in self.0.iter().zip(ENCODER_TENSORS) {
+            collect(t, v, &format!("{prefix}.{n}"), out);
+        }
+    }
+}
+
+impl DecoderVars {
+    pub(crate) fn register(t: &mut Tape, layer: &impl DecoderParams, dims: Dims) -> Self {
+        Self(register(t, layer.tensors(), decoder_shapes(dims)))
+    }
+
+    fn collect(&self, t: &Tape, prefix: &str, out: &mut Gradients) {
+        for (&v, n) in self.0.iter().zip(DECODER_TENSORS) {
+            collect(t, v, &format!("{prefix}.{n}"), out);
+        }
+    }
+}
+
+/// Token embeddings plus positional encodings for `ids`.
+pub(crate) fn embed(t: &mut Tape, table: Var, pos: Var, ids: &[usize]) -> Var {
+    let tok = t.gather(table, ids);
+    let pos = t.rows(pos, 0, ids.len());
+    t.add(tok, pos)
+}
+
+/// Pre-norm feed-forward sublayer with residual.
+fn ffn_block(t: &mut Tape, x: Var, ln: Var, w1: Var, w2: Var) -> Var {
+    let h = t.layer_norm(x, ln);
+    let h = t.linear(h, w1);
+    let h = t.relu(h);
+    let h = t.linear(h, w2);
+    t.add(x, h)
+}
+
+/// Pre-norm attention sublayer with residual. Keys/values come from `kv`
+/// (already normalized) or, for self-attention, from the normed input.
+#[allow(clippy::too_many_arguments)]
+fn attention_block(
+    t: &mut Tape,
+    x: Var,
+    ln: Var,
+    [wq, wk, wv, wo]: [Var; 4],
+    kv: Option<Var>,
+    n_heads: usize,
+    causal: bool,
+) -> Var {
+    let h = t.layer_norm(x, ln);
+    let src = kv.unwrap_or(h);
+    let q = t.linear(h, wq);
+    let k = t.linear(src, wk);
+    let v = t.linear(src, wv);
+    let a = t.attention(q, k, v, n_heads, causal);
+    let a = t.linear(a, wo);
+    t.add(x, a)
+}
+
+/// Bidirectional encoder layer: same computation as the models' inference
+/// `apply_encoder_layer`.
+pub(crate) fn encoder_layer(t: &mut Tape, x: Var, p: &EncoderVars, dims: Dims) -> Var {
+    let [wq, wk, wv, wo, ff_w1, ff_w2, ln1, ln2] = p.0;
+    let x = attention_block(t, x, ln1, [wq, wk, wv, wo], None, dims.n_heads, false);
+    ffn_block(t, x, ln2, ff_w1, ff_w2)
+}
+
+/// Decoder layer: causal self-attention, cross-attention over `enc` (skipped
+/// when there is no encoder output), FFN.
+pub(crate) fn decoder_layer(
+    t: &mut Tape,
+    x: Var,
+    enc: Option<Var>,
+    p: &DecoderVars,
+    dims: Dims,
+) -> Var {
+    let [wq, wk, wv, wo, cwq, cwk, cwv, cwo, ff_w1, ff_w2, ln1, ln2, ln3] = p.0;
+    let mut x = attention_block(t, x, ln1, [wq, wk, wv, wo], None, dims.n_heads, true);
+    if let Some(enc) = enc {
+        x = attention_block(
+            t,
+            x,
+            ln2,
+            [cwq, cwk, cwv, cwo],
+            Some(enc),
+            dims.n_heads,
+            false,
+        );
+    }
+    ffn_block(t, x, ln3, ff_w1, ff_w2)
+}
+
+/// An encoder stack on a tape: positional table, layer parameters, output.
+pub(crate) struct EncoderGraph {
+    pub(crate) output: Var,
+    pos: Var,
+    layers: Vec<EncoderVars>,
+}
+
+impl EncoderGraph {
+    /// Embed `ids` with the shared `table` and run every encoder layer.
+    pub(crate) fn build<L: EncoderParams>(
+        t: &mut Tape,
+        table: Var,
+        enc_pos: &[f32],
+        layers: &[L],
+        ids: &[usize],
+        dims: Dims,
+    ) -> Self {
+        let pos = t.param(enc_pos, enc_pos.len() / dims.d, dims.d);
+        let layers: Vec<EncoderVars> = layers
+            .iter()
+            .map(|l| EncoderVars::register(t, l, dims))
+            .collect();
+        let mut output = embed(t, table, pos, ids);
+        for p in &layers {
+            output = encoder_layer(t, output, p, dims);
+        }
+        Self {
+            output,
+            pos,
+            layers,
+        }
+    }
+
+    /// Gradients of the positional table and layers, under their checkpoint
+    /// names (`enc_pos`, `enc.{i}.wq`, ...).
+    pub(crate) fn collect(&self, t: &Tape, out: &mut Gradients) {
+        collect(t, self.pos, "enc_pos", out);
+        for (i, p) in self.layers.iter().enumerate() {
+            p.collect(t, &format!("enc.{i}"), out);
+        }
+    }
+}
+
+/// A decoder stack on a tape, conditioned on an optional encoder output.
+pub(crate) struct DecoderGraph {
+    pub(crate) output: Var,
+    pos: Var,
+    layers: Vec<DecoderVars>,
+}
+
+impl DecoderGraph {
+    pub(crate) fn build<L: DecoderParams>(
+        t: &mut Tape,
+        table: Var,
+        dec_pos: &[f32],
+        layers: &[L],
+        ids: &[usize],
+        enc: Option<Var>,
+        dims: Dims,
+    ) -> Self {
+        let pos = t.param(dec_pos, dec_pos.len() / dims.d, dims.d);
+        let layers: Vec<DecoderVars> = layers
+            .iter()
+            .map(|l| DecoderVars::register(t, l, dims))
+            .collect();
+        let mut output = embed(t, table, pos, ids);
+        for p in &layers {
+            output = decoder_layer(t, output, enc, p, dims);
+        }
+        Self {
+            output,
+            pos,
+            layers,
+        }
+    }
+
+    /// Gradients of the positional table and layers, under their checkpoint
+    /// names (`dec_pos`, `dec.{i}.wq`, ...).
+    pub(crate) fn collect(&self, t: &Tape, out: &mut Gradients) {
+        collect(t, self.pos, "dec_pos", out);
+        for (i, p) in self.layers.iter().enumerate() {
+            p.collect(t, &format!("dec.{i}"), out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic values in [-1, 1].
+    fn values(seed: u64, n: usize) -> Vec<f32> {
+        let mut s = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        (0..n)
+            .map(|_| {
+                s ^= s << 13;
+                s ^= s >> 7;
+                s ^= s << 17;
+                (s >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
+    /// Compare `backward` against central finite differences for every
+    /// element of every parameter. `build` maps parameter vars to a scalar.
+    fn grad_check(params: &[(usize, usize)], build: impl Fn(&mut Tape, &[Var]) -> Var) {
+        let init: Vec<Vec<f32>> = params
+            .iter()
+            .enumerate()
+            .map(|(i, &(r, c))| values(i as u64 + 1, r * c))
+            .collect();
+        let loss_at = |vals: &[Vec<f32>]| -> f64 {
+            let mut t = Tape::new();
+            let vars: Vec<Var> = vals
+                .iter()
+                .zip(params)
+                .map(|(v, &(r, c))| t.param(v, r, c))
+                .collect();
+            let l = build(&mut t, &vars);
+            t.scalar(l) as f64
+        };
+
+        let mut t = Tape::new();
+        let vars: Vec<Var> = init
+            .iter()
+            .zip(params)
+            .map(|(v, &(r, c))| t.param(v, r, c))
+            .collect();
+        let l = build(&mut t, &vars);
+        t.backward(l);
+
+        let eps = 1e-2f32;
+        for (p, var) in vars.iter().enumerate() {
+            let analytic = t.grad(*var).expect("param gradient").to_vec();
+            for j in 0..analytic.len() {
+                let mut plus = init.clone();
+                plus[p][j] += eps;
+                let mut minus = init.clone();
+                minus[p][j] -= eps;
+                let numeric = ((loss_at(&plus) - loss_at(&minus)) / (2.0 * eps as f64)) as f32;
+                let tol = 2e-3 + 2e-2 * numeric.abs().max(analytic[j].abs());
+                assert!(
+                    (numeric - analytic[j]).abs() < tol,
+                    "param {p}[{j}]: analytic {} vs numeric {numeric}",
+                    analytic[j]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn grad_check_elementwise_ops() {
+        let target = values(99, 12);
+        grad_check(&[(3, 4), (3, 4), (1, 4)], |t, v| {
+            let a = t.add(v[0], v[1]);
+            let a = t.add_row(a, v[2]);
+            let r = t.relu(a);
+            let g = t.gelu(v[0]);
+            let s = t.add(r, g);
+            let s = t.softmax(s);
+            t.mse(s, &target)
+        });
+    }
+
+    #[test]
+    fn grad_check_linear_and_matmul() {
+        let target = values(98, 6);
+        grad_check(&[(3, 4), (2, 4), (2, 2)], |t, v| {
+            let y = t.linear(v[0], v[1]);
+            let y = t.matmul(y, v[2]);
+            t.mse(y, &target)
+        });
+    }
+
+    #[test]
+    fn grad_check_layer_norm_and_pooling() {
+        let target = values(97, 5);
+        grad_check(&[(3, 5), (1, 5)], |t, v| {
+            let y = t.layer_norm(v[0], v[1]);
+            let y = t.rows(y, 1, 2);
+            let y = t.mean_rows(y);
+            t.mse(y, &target)
+        });
+    }
+
+    #[test]
+    fn grad_check_attention() {
+        for causal in [false, true] {
+            let target = values(96, 12);
+            grad_check(&[(3, 4), (3, 4), (3, 4)], |t, v| {
+                let y = t.attention(v[0], v[1], v[2], 2, causal);
+                t.mse(y, &target)
+            });
+        }
+        // Cross-attention: different query and key/value lengths.
+        let target = values(95, 8);
+        grad_check(&[(2, 4), (3, 4), (3, 4)], |t, v| {
+            let y = t.attention(v[0], v[1], v[2], 2, false);
+            t.mse(y, &target)
+        });
+    }
+
+    #[test]
+    fn grad_check_embedding_and_cross_entropy() {
+        grad_check(&[(5, 4), (3, 4), (5, 4)], |t, v| {
+            let x = embed(t, v[0], v[1], &[2, 0, 2]);
+            let logits = t.linear(x, v[2]);
+            t.cross_entropy(logits, &[1, 4, 0])
+        });
+    }
+
+    #[test]
+    fn grad_check_transformer_layers() {
+        let dims = Dims {
+            d: 4,
+            n_heads: 2,
+            d_head: 2,
+            ff: 6,
+        };
+        let mut shapes = vec![(3, 4), (2, 4)];
+        shapes.extend(encoder_shapes(dims));
+        shapes.extend(decoder_shapes(dims));
+        grad_check(&shapes, |t, v| {
+            let enc_p = EncoderVars(v[2..10].try_into().unwrap());
+            let dec_p = DecoderVars(v[10..23].try_into().unwrap());
+            let enc = encoder_layer(t, v[0], &enc_p, dims);
+            let dec = decoder_layer(t, v[1], Some(enc), &dec_p, dims);
+            let logits = t.linear(dec, v[0]);
+            t.cross_entropy(logits, &[2, 1])
+        });
+    }
+
+    #[test]
+    fn models_learn_on_tape() {
+        let context = [1u32, 40, 41, 42, 2];
+        let target = [1u32, 100, 200, 300, 2];
+
+        let mut codegen = crate::codegen::CodeGenModel::new();
+        let first = codegen.train_enc_dec(&context, &target, 0.001);
+        let mut last = first;
+        for _ in 0..3 {
+            last = codegen.train_enc_dec(&context, &target, 0.001);
+        }
+        assert!(last < first, "codegen loss {first} -> {last}");
+
+        let mut unified = crate::unified::UnifiedModel::new();
+        let first = unified.train_slow(&context, &target, 0.001);
+        let mut last = first;
+        for _ in 0..3 {
+            last = unified.train_slow(&context, &target, 0.001);
+        }
+        assert!(last < first, "slow head loss {first} -> {last}");
+
+        let goal = vec![0.5; crate::unified::FAST_OUTPUT];
+        let first = unified.train_fast(&context, &goal, 0.001);
+        let mut last = first;
+        for _ in 0..3 {
+            last = unified.train_fast(&context, &goal, 0.001);
+        }
+        assert!(last < first, "fast head loss {first} -> {last}");
+    }
+
+    #[test]
+    fn inputs_get_no_gradient() {
+        let mut t = Tape::new();
+        let x = t.input(vec![1.0, 2.0], 1, 2);
+        let w = t.param(&[0.5, -0.5, 1.0, 1.0], 2, 2);
+        let y = t.linear(x, w);
+        let l = t.mse(y, &[0.0, 0.0]);
+        t.backward(l);
+        assert!(t.grad(x).is_none());
+        let mut w_vals = vec![0.5, -0.5, 1.0, 1.0];
+        t.sgd(&mut w_vals, w, 0.1);
+        assert!(w_vals[2] < 1.0, "loss pulls weights toward zero output");
+    }
+}
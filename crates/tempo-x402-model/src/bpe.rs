@@ -7,12 +7,18 @@
 //!
 //! - Vocab size: configurable (default 8192 for Phase 3 code gen model)
 //! - Base vocab: 256 byte values + special tokens
-//! - Merge rules: learned from training corpus via frequency counting
-//! - Serializable: merge rules + vocab stored as JSON for persistence
+//! - Pre-tokenization: text is split on Rust lexical classes (identifiers,
+//!   numbers, punctuation runs, whitespace) so merges never cross a boundary
+//! - Training: incremental pair counts — a max-heap of pair frequencies plus
+//!   linked-list symbol updates, so each merge only touches its occurrences
+//! - Serializable: merge rules + vocab stored as JSON for persistence, and
+//!   import/export of the Hugging Face `tokenizer.json` format
 //!
 //! ## Usage
 //!
 //! ```rust
+//! use x402_model::bpe::BpeTokenizer;
+//!
 //! let mut tokenizer = BpeTokenizer::new(8192);
 //! tokenizer.train("fn main() { println!(\"hello\"); }");
 //! let tokens = tokenizer.encode("fn main()");
@@ -20,7 +26,8 @@
 //! ```
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 
 /// Special token IDs.
 pub const PAD_TOKEN: u32 = 0;
@@ -35,6 +42,32 @@ const BYTE_VOCAB_SIZE: u32 = 256;
 /// First merge token ID.
 const MERGE_START: u32 = BASE_VOCAB_START + BYTE_VOCAB_SIZE; // 260
 
+/// Names of the special tokens in `tokenizer.json` exports.
+const SPECIAL_NAMES: [&str; 4] = ["<|pad|>", "<|bos|>", "<|eos|>", "<|unk|>"];
+
+/// The pre-tokenizer as a regex, for `tokenizer.json` `Split` pre-tokenizers.
+/// Matches exactly the pieces produced by [`pretokenize`].
+pub const PRETOKENIZE_PATTERN: &str = r" ?(?:[A-Za-z_]|[^\x00-\x7F])(?:[A-Za-z0-9_]|[^\x00-\x7F])*| ?[0-9](?:[A-Za-z0-9_]|[^\x00-\x7F]|\.[0-9])*| ?[\x00-\x08\x0E-\x1F!-/:-@\[-^`{-\x7F]+|[\t\n\x0B\x0C\r ]+(?![^\t\n\x0B\x0C\r ])|[\t\n\x0B\x0C\r ]+";
+
+/// Errors from importing or exporting `tokenizer.json`.
+#[derive(Debug, thiserror::Error)]
+pub enum BpeError {
+    #[error("invalid tokenizer.json: {0}")]
+    Format(String),
+
+    #[error("token {0:?} is not in the byte-level alphabet")]
+    NotByteLevel(String),
+
+    #[error("merge references unknown token {0:?}")]
+    UnknownToken(String),
+
+    #[error("token {0:?} appears twice; tokenizer.json needs unique token strings")]
+    Duplicate(String),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
 /// A learned BPE tokenizer.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BpeTokenizer {
@@ -44,9 +77,17 @@ pub struct BpeTokenizer {
     pub merges: Vec<(u32, u32)>,
     /// Token ID → byte sequence mapping (for decoding).
     pub vocab: Vec<Vec<u8>>,
+    /// Split text with [`pretokenize`] before merging. Tokenizers saved before
+    /// pre-tokenization existed load with `false` and keep merging across the
+    /// whole byte sequence, so the token IDs models were trained on stay put.
+    #[serde(default)]
+    pub pretokenize: bool,
     /// Byte sequence → token ID (for encoding, built from vocab).
     #[serde(skip)]
     encode_cache: HashMap<Vec<u8>, u32>,
+    /// Merge pair → rank (index into `merges`), built from merges.
+    #[serde(skip)]
+    ranks: HashMap<(u32, u32), u32>,
 }
 
 impl BpeTokenizer {
@@ -69,40 +110,44 @@ impl BpeTokenizer {
             vocab_size,
             merges: Vec::new(),
             vocab,
+            pretokenize: true,
             encode_cache: HashMap::new(),
+            ranks: HashMap::new(),
         };
         tok.rebuild_encode_cache();
         tok
     }
 
     /// Train the tokenizer on a corpus of text.
-    /// Learns merge rules until vocab_size is reached.
+    /// Learns merge rules until vocab_size is reached. Training again
+    /// continues from the merges already learned.
     pub fn train(&mut self, text: &str) {
-        let bytes = text.as_bytes();
-        if bytes.is_empty() {
+        if text.is_empty() {
             return;
         }
 
-        // Start with byte-level tokens
-        let mut sequence: Vec<u32> = bytes.iter().map(|&b| BASE_VOCAB_START + b as u32).collect();
-
-        let target_merges = self.vocab_size.saturating_sub(MERGE_START);
-
-        for _ in 0..target_merges {
-            // Count all adjacent pairs
-            let mut pair_counts: HashMap<(u32, u32), usize> = HashMap::new();
-            for window in sequence.windows(2) {
-                let pair = (window[0], window[1]);
-                *pair_counts.entry(pair).or_insert(0) += 1;
-            }
+        // Each distinct piece is tokenized once and weighted by its frequency.
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for piece in self.pieces(text) {
+            *counts.entry(piece).or_insert(0) += 1;
+        }
+        let mut words: Vec<(&str, u64)> = counts.into_iter().collect();
+        words.sort_unstable();
 
-            // Find most frequent pair
-            let best = pair_counts
+        let mut pairs = PairCounts::new(
+            words
                 .iter()
-                .max_by_key(|(_, &count)| count)
-                .map(|(&pair, &count)| (pair, count));
+                .map(|&(word, count)| (self.encode_piece(word.as_bytes()), count)),
+        );
 
-            let Some(((a, b), count)) = best else {
+        while (self.vocab.len() as u32) < self.vocab_size {
+            // A pair whose bytes are already a token would give the same
+            // string two IDs; skip it so the vocab stays a bijection.
+            let Some(((a, b), count)) = pairs.best(|a, b| {
+                let mut bytes = self.vocab[a as usize].clone();
+                bytes.extend_from_slice(&self.vocab[b as usize]);
+                self.encode_cache.contains_key(&bytes)
+            }) else {
                 break; // No more pairs
             };
 
@@ -114,26 +159,12 @@ impl BpeTokenizer {
             let new_id = self.vocab.len() as u32;
             let mut new_bytes = self.vocab[a as usize].clone();
             new_bytes.extend_from_slice(&self.vocab[b as usize]);
+            self.encode_cache.insert(new_bytes.clone(), new_id);
+            self.ranks.entry((a, b)).or_insert(self.merges.len() as u32);
             self.vocab.push(new_bytes);
             self.merges.push((a, b));
 
-            // Apply merge to sequence
-            let mut new_seq = Vec::with_capacity(sequence.len());
-            let mut i = 0;
-            while i < sequence.len() {
-                if i + 1 < sequence.len() && sequence[i] == a && sequence[i + 1] == b {
-                    new_seq.push(new_id);
-                    i += 2;
-                } else {
-                    new_seq.push(sequence[i]);
-                    i += 1;
-                }
-            }
-            sequence = new_seq;
-
-            if self.vocab.len() as u32 >= self.vocab_size {
-                break;
-            }
+            pairs.merge((a, b), new_id);
         }
 
         self.rebuild_encode_cache();
@@ -141,31 +172,10 @@ impl BpeTokenizer {
 
     /// Encode text to token IDs.
     pub fn encode(&self, text: &str) -> Vec<u32> {
-        let bytes = text.as_bytes();
-        if bytes.is_empty() {
-            return vec![];
+        let mut tokens = Vec::new();
+        for piece in self.pieces(text) {
+            tokens.extend(self.encode_piece(piece.as_bytes()));
         }
-
-        // Start with byte-level tokens
-        let mut tokens: Vec<u32> = bytes.iter().map(|&b| BASE_VOCAB_START + b as u32).collect();
-
-        // Apply merges in order
-        for &(a, b) in &self.merges {
-            let merged_id = self.merge_token_id(a, b);
-            let mut new_tokens = Vec::with_capacity(tokens.len());
-            let mut i = 0;
-            while i < tokens.len() {
-                if i + 1 < tokens.len() && tokens[i] == a && tokens[i + 1] == b {
-                    new_tokens.push(merged_id);
-                    i += 2;
-                } else {
-                    new_tokens.push(tokens[i]);
-                    i += 1;
-                }
-            }
-            tokens = new_tokens;
-        }
-
         tokens
     }
 
@@ -185,17 +195,52 @@ impl BpeTokenizer {
         self.vocab.len() as u32
     }
 
-    /// Get the token ID for a merge pair.
-    fn merge_token_id(&self, a: u32, b: u32) -> u32 {
-        for (i, &(ma, mb)) in self.merges.iter().enumerate() {
-            if ma == a && mb == b {
-                return MERGE_START + i as u32;
+    /// Split text into the units merges are applied within.
+    fn pieces<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        if self.pretokenize {
+            pretokenize(text)
+        } else if text.is_empty() {
+            vec![]
+        } else {
+            vec![text]
+        }
+    }
+
+    /// Encode one piece: repeatedly merge the lowest-ranked adjacent pair.
+    /// Equivalent to applying every merge in learned order, because a pair
+    /// can only appear after the merges that produced its tokens.
+    fn encode_piece(&self, bytes: &[u8]) -> Vec<u32> {
+        let mut tokens: Vec<u32> = bytes.iter().map(|&b| BASE_VOCAB_START + b as u32).collect();
+
+        while tokens.len() > 1 {
+            let best = tokens
+                .windows(2)
+                .filter_map(|w| self.ranks.get(&(w[0], w[1])).copied())
+                .min();
+            let Some(rank) = best else {
+                break;
+            };
+
+            let (a, b) = self.merges[rank as usize];
+            let merged_id = MERGE_START + rank;
+            let mut new_tokens = Vec::with_capacity(tokens.len());
+            let mut i = 0;
+            while i < tokens.len() {
+                if i + 1 < tokens.len() && tokens[i] == a && tokens[i + 1] == b {
+                    new_tokens.push(merged_id);
+                    i += 2;
+                } else {
+                    new_tokens.push(tokens[i]);
+                    i += 1;
+                }
             }
+            tokens = new_tokens;
         }
-        UNK_TOKEN
+
+        tokens
     }
 
-    /// Rebuild the encode cache from vocab.
+    /// Rebuild the encode cache and merge ranks from vocab and merges.
     fn rebuild_encode_cache(&mut self) {
         self.encode_cache.clear();
         for (id, bytes) in self.vocab.iter().enumerate() {
@@ -203,6 +248,12 @@ impl BpeTokenizer {
                 self.encode_cache.insert(bytes.clone(), id as u32);
             }
         }
+        self.ranks.clear();
+        for (rank, &pair) in self.merges.iter().enumerate() {
+            // Older tokenizers may repeat a pair; the first one wins, as it
+            // always did when merges were applied in order.
+            self.ranks.entry(pair).or_insert(rank as u32);
+        }
     }
 
     /// Serialize to JSON.
@@ -217,6 +268,157 @@ impl BpeTokenizer {
         Some(tok)
     }
 
+    /// Export in the Hugging Face `tokenizer.json` format: a byte-level BPE
+    /// model, the special tokens as added tokens, and (when pre-tokenizing)
+    /// a `Split` pre-tokenizer carrying [`PRETOKENIZE_PATTERN`].
+    pub fn to_hf_json(&self) -> Result<String, BpeError> {
+        let table = byte_chars();
+        let mut names: Vec<String> = Vec::with_capacity(self.vocab.len());
+        let mut vocab = serde_json::Map::new();
+        for (id, bytes) in self.vocab.iter().enumerate() {
+            let name = match SPECIAL_NAMES.get(id) {
+                Some(special) => special.to_string(),
+                None => bytes.iter().map(|&b| table[b as usize]).collect(),
+            };
+            if vocab.insert(name.clone(), id.into()).is_some() {
+                return Err(BpeError::Duplicate(name));
+            }
+            names.push(name);
+        }
+
+        let merges: Vec<String> = self
+            .merges
+            .iter()
+            .map(|&(a, b)| format!("{} {}", names[a as usize], names[b as usize]))
+            .collect();
+
+        let added_tokens: Vec<serde_json::Value> = SPECIAL_NAMES
+            .iter()
+            .enumerate()
+            .map(|(id, name)| {
+                serde_json::json!({
+                    "id": id,
+                    "content": name,
+                    "single_word": false,
+                    "lstrip": false,
+                    "rstrip": false,
+                    "normalized": false,
+                    "special": true,
+                })
+            })
+            .collect();
+
+        let byte_level = serde_json::json!({
+            "type": "ByteLevel",
+            "add_prefix_space": false,
+            "trim_offsets": false,
+            "use_regex": false,
+        });
+        let pre_tokenizer = if self.pretokenize {
+            serde_json::json!({
+                "type": "Sequence",
+                "pretokenizers": [
+                    {
+                        "type": "Split",
+                        "pattern": { "Regex": PRETOKENIZE_PATTERN },
+                        "behavior": "Isolated",
+                        "invert": false,
+                    },
+                    byte_level,
+                ],
+            })
+        } else {
+            byte_level.clone()
+        };
+
+        let doc = serde_json::json!({
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": added_tokens,
+            "normalizer": null,
+            "pre_tokenizer": pre_tokenizer,
+            "post_processor": null,
+            "decoder": byte_level,
+            "model": {
+                "type": "BPE",
+                "dropout": null,
+                "unk_token": null,
+                "continuing_subword_prefix": null,
+                "end_of_word_suffix": null,
+                "fuse_unk": false,
+                "byte_fallback": false,
+                "ignore_merges": false,
+                "vocab": vocab,
+                "merges": merges,
+            },
+        });
+        Ok(serde_json::to_string_pretty(&doc)?)
+    }
+
+    /// Import a byte-level BPE `tokenizer.json`. Merge tokens are renumbered in
+    /// merge order after the special and byte tokens, which reproduces the
+    /// original IDs for files written by [`Self::to_hf_json`].
+    pub fn from_hf_json(json: &str) -> Result<Self, BpeError> {
+        let doc: serde_json::Value = serde_json::from_str(json)?;
+        let model = &doc["model"];
+        if model["type"].as_str() != Some("BPE") {
+            return Err(BpeError::Format("model.type is not BPE".into()));
+        }
+        let merges = model["merges"]
+            .as_array()
+            .ok_or_else(|| BpeError::Format("model.merges is not an array".into()))?;
+        let file_vocab = model["vocab"].as_object().map_or(0, |v| v.len());
+
+        let mut inverse: HashMap<char, u8> = HashMap::new();
+        for (b, &c) in byte_chars().iter().enumerate() {
+            inverse.insert(c, b as u8);
+        }
+
+        let mut tok = Self::new(MERGE_START);
+        tok.pretokenize = has_rust_split(&doc["pre_tokenizer"]);
+        let mut ids: HashMap<String, u32> = HashMap::new();
+        for (b, &c) in byte_chars().iter().enumerate() {
+            ids.insert(c.to_string(), BASE_VOCAB_START + b as u32);
+        }
+
+        for merge in merges {
+            let (left, right) = match merge {
+                serde_json::Value::String(s) => s
+                    .split_once(' ')
+                    .map(|(l, r)| (l.to_string(), r.to_string()))
+                    .ok_or_else(|| BpeError::Format(format!("bad merge {s:?}")))?,
+                serde_json::Value::Array(pair) if pair.len() == 2 => {
+                    match (pair[0].as_str(), pair[1].as_str()) {
+                        (Some(l), Some(r)) => (l.to_string(), r.to_string()),
+                        _ => return Err(BpeError::Format(format!("bad merge {merge}"))),
+                    }
+                }
+                _ => return Err(BpeError::Format(format!("bad merge {merge}"))),
+            };
+            let a = *ids
+                .get(&left)
+                .ok_or_else(|| BpeError::UnknownToken(left.clone()))?;
+            let b = *ids
+                .get(&right)
+                .ok_or_else(|| BpeError::UnknownToken(right.clone()))?;
+
+            let name = format!("{left}{right}");
+            let bytes = name
+                .chars()
+                .map(|c| inverse.get(&c).copied())
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| BpeError::NotByteLevel(name.clone()))?;
+            ids.entry(name).or_insert(tok.vocab.len() as u32);
+            tok.vocab.push(bytes);
+            tok.merges.push((a, b));
+        }
+
+        tok.vocab_size = (tok.vocab.len()).max(file_vocab) as u32;
+        tok.rebuild_encode_cache();
+        Ok(tok)
+    }
+
     /// Compression ratio: original bytes / encoded tokens.
     /// Higher = better compression = more efficient tokenization.
     pub fn compression_ratio(&self, text: &str) -> f64 {
@@ -228,6 +430,240 @@ impl BpeTokenizer {
     }
 }
 
+/// Lexical class of a byte for pre-tokenization. Non-ASCII bytes count as
+/// identifier characters, so pieces never split a UTF-8 sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Ident,
+    Number,
+    Punct,
+    Space,
+}
+
+fn class_of(b: u8) -> Class {
+    match b {
+        b'0'..=b'9' => Class::Number,
+        b'A'..=b'Z' | b'a'..=b'z' | b'_' | 0x80..=0xFF => Class::Ident,
+        b' ' | b'\t' | b'\n' | b'\r' | 0x0B | 0x0C => Class::Space,
+        _ => Class::Punct,
+    }
+}
+
+/// Split source text on Rust lexical classes: identifiers/keywords, numeric
+/// literals (`1_000u64`, `1.5e3`, but not the `..` in `0..10`), runs of
+/// punctuation (`::`, `->`, `);`) and whitespace. A single space before a
+/// non-space piece is attached to it, so ` fn` and ` {` are learnable units
+/// while indentation stays its own piece. Concatenating the pieces gives
+/// back the input.
+pub fn pretokenize(text: &str) -> Vec<&str> {
+    let bytes = text.as_bytes();
+    let mut pieces = Vec::new();
+    let mut start = 0;
+
+    while start < bytes.len() {
+        let mut pos = start;
+        if bytes[pos] == b' '
+            && bytes
+                .get(pos + 1)
+                .is_some_and(|&b| class_of(b) != Class::Space)
+        {
+            pos += 1;
+        }
+
+        let class = class_of(bytes[pos]);
+        let mut end = pos + 1;
+        while end < bytes.len() {
+            let next = class_of(bytes[end]);
+            let more = match class {
+                Class::Ident => matches!(next, Class::Ident | Class::Number),
+                Class::Number => {
+                    matches!(next, Class::Ident | Class::Number)
+                        || (bytes[end] == b'.'
+                            && bytes.get(end + 1).is_some_and(|b| b.is_ascii_digit()))
+                }
+                Class::Punct | Class::Space => next == class,
+            };
+            if !more {
+                break;
+            }
+            end += 1;
+        }
+
+        // Leave the last whitespace character for the piece that follows.
+        if class == Class::Space && end < bytes.len() && end - start > 1 {
+            end -= 1;
+        }
+
+        pieces.push(&text[start..end]);
+        start = end;
+    }
+
+    pieces
+}
+
+/// GPT-2 byte-level alphabet: printable bytes map to themselves, the rest
+/// to code points from U+0100 up, so every token is a printable string.
+fn byte_chars() -> [char; 256] {
+    let mut table = ['\0'; 256];
+    let mut shifted = 0;
+    for b in 0..256u32 {
+        let code = if matches!(b, 0x21..=0x7E | 0xA1..=0xAC | 0xAE..=0xFF) {
+            b
+        } else {
+            shifted += 1;
+            255 + shifted
+        };
+        table[b as usize] = char::from_u32(code).unwrap_or('\u{FFFD}');
+    }
+    table
+}
+
+/// Whether a `tokenizer.json` pre-tokenizer contains our Rust `Split`.
+fn has_rust_split(pre: &serde_json::Value) -> bool {
+    match pre["type"].as_str() {
+        Some("Split") => pre["pattern"]["Regex"].as_str() == Some(PRETOKENIZE_PATTERN),
+        Some("Sequence") => pre["pretokenizers"]
+            .as_array()
+            .is_some_and(|list| list.iter().any(has_rust_split)),
+        _ => false,
+    }
+}
+
+/// Marks a symbol slot that has been merged into its left neighbour.
+const MERGED: u32 = u32::MAX;
+/// End of a word in the symbol linked list.
+const NONE: usize = usize::MAX;
+
+/// Incremental pair statistics for training. All words live in one flat
+/// symbol array linked per word; merging a pair walks only the positions
+/// where it occurs and adjusts the counts of the neighbouring pairs.
+struct PairCounts {
+    symbols: Vec<u32>,
+    prev: Vec<usize>,
+    next: Vec<usize>,
+    /// Frequency of the word each symbol belongs to.
+    weight: Vec<u64>,
+    counts: HashMap<(u32, u32), u64>,
+    /// Left positions of each pair. May hold stale entries; checked on use.
+    positions: HashMap<(u32, u32), Vec<usize>>,
+    /// Max-heap by count, ties to the smallest pair. Entries go stale when a
+    /// count changes; a fresh entry is pushed on every change.
+    heap: BinaryHeap<(u64, Reverse<(u32, u32)>)>,
+}
+
+impl PairCounts {
+    fn new(words: impl Iterator<Item = (Vec<u32>, u64)>) -> Self {
+        let mut pc = Self {
+            symbols: Vec::new(),
+            prev: Vec::new(),
+            next: Vec::new(),
+            weight: Vec::new(),
+            counts: HashMap::new(),
+            positions: HashMap::new(),
+            heap: BinaryHeap::new(),
+        };
+
+        for (word, count) in words {
+            let base = pc.symbols.len();
+            for (k, &sym) in word.iter().enumerate() {
+                pc.symbols.push(sym);
+                pc.prev.push(if k == 0 { NONE } else { base + k - 1 });
+                pc.next.push(if k + 1 == word.len() {
+                    NONE
+                } else {
+                    base + k + 1
+                });
+                pc.weight.push(count);
+                if k > 0 {
+                    pc.add((word[k - 1], sym), count, base + k - 1);
+                }
+            }
+        }
+
+        pc.heap = pc
+            .counts
+            .iter()
+            .map(|(&pair, &count)| (count, Reverse(pair)))
+            .collect();
+        pc
+    }
+
+    fn add(&mut self, pair: (u32, u32), w: u64, pos: usize) {
+        *self.counts.entry(pair).or_insert(0) += w;
+        self.positions.entry(pair).or_default().push(pos);
+    }
+
+    fn sub(&mut self, pair: (u32, u32), w: u64) {
+        if let Some(count) = self.counts.get_mut(&pair) {
+            *count = count.saturating_sub(w);
+            if *count == 0 {
+                self.counts.remove(&pair);
+                self.positions.remove(&pair);
+            }
+        }
+    }
+
+    /// The most frequent pair not rejected by `skip`.
+    fn best(&mut self, skip: impl Fn(u32, u32) -> bool) -> Option<((u32, u32), u64)> {
+        while let Some((count, Reverse(pair))) = self.heap.pop() {
+            if self.counts.get(&pair) != Some(&count) || skip(pair.0, pair.1) {
+                continue;
+            }
+            return Some((pair, count));
+        }
+        None
+    }
+
+    /// Replace every occurrence of `pair` (left to right) with `new_id`.
+    fn merge(&mut self, pair: (u32, u32), new_id: u32) {
+        let (a, b) = pair;
+        let Some(mut occurrences) = self.positions.remove(&pair) else {
+            return;
+        };
+        occurrences.sort_unstable();
+        occurrences.dedup();
+
+        let mut touched = Vec::new();
+        for i in occurrences {
+            let j = self.next[i];
+            if self.symbols[i] != a || j == NONE || self.symbols[j] != b {
+                continue; // stale, or consumed by an overlapping merge
+            }
+            let w = self.weight[i];
+
+            let p = self.prev[i];
+            if p != NONE {
+                let left = self.symbols[p];
+                self.sub((left, a), w);
+                self.add((left, new_id), w, p);
+                touched.extend([(left, a), (left, new_id)]);
+            }
+            let n = self.next[j];
+            if n != NONE {
+                let right = self.symbols[n];
+                self.sub((b, right), w);
+                self.add((new_id, right), w, i);
+                touched.extend([(b, right), (new_id, right)]);
+                self.prev[n] = i;
+            }
+            self.sub(pair, w);
+
+            self.symbols[i] = new_id;
+            self.symbols[j] = MERGED;
+            self.next[i] = n;
+        }
+        self.counts.remove(&pair);
+
+        touched.sort_unstable();
+        touched.dedup();
+        for pair in touched {
+            if let Some(&count) = self.counts.get(&pair) {
+                self.heap.push((count, Reverse(pair)));
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -301,4 +737,208 @@ pub fn fibonacci(n: u64) -> u64 {
         assert_eq!(UNK_TOKEN, 3);
         assert_eq!(MERGE_START, 260);
     }
+
+    const SAMPLE: &str = r#"
+use std::collections::HashMap;
+
+/// Count words.
+pub fn word_counts(text: &str) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for word in text.split_whitespace() {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    counts
+}
+
+pub fn fibonacci(n: u64) -> u64 {
+    match n {
+        0 => 0,
+        1 => 1,
+        _ => fibonacci(n - 1) + fibonacci(n - 2),
+    }
+}
+
+fn main() {
+    let total: f64 = (0..10).map(|i| i as f64 * 1.5e3_f64).sum();
+    println!("{total} — {:?}", word_counts("a b a"));
+}
+"#;
+
+    /// The textbook trainer: recount every pair over every piece per merge.
+    fn naive_merges(text: &str, vocab_size: u32) -> Vec<(u32, u32)> {
+        let mut words: Vec<Vec<u32>> = pretokenize(text)
+            .iter()
+            .map(|p| p.bytes().map(|b| BASE_VOCAB_START + b as u32).collect())
+            .collect();
+        let mut vocab: Vec<Vec<u8>> = BpeTokenizer::new(vocab_size).vocab;
+        let mut merges = Vec::new();
+        while (vocab.len() as u32) < vocab_size {
+            let mut counts: HashMap<(u32, u32), u64> = HashMap::new();
+            for word in &words {
+                for w in word.windows(2) {
+                    *counts.entry((w[0], w[1])).or_insert(0) += 1;
+                }
+            }
+            let best = counts
+                .into_iter()
+                .filter(|&((a, b), _)| {
+                    let mut bytes = vocab[a as usize].clone();
+                    bytes.extend_from_slice(&vocab[b as usize]);
+                    !vocab.contains(&bytes)
+                })
+                .max_by_key(|&(pair, count)| (count, Reverse(pair)));
+            let Some(((a, b), count)) = best else { break };
+            if count < 2 {
+                break;
+            }
+            let id = vocab.len() as u32;
+            let mut bytes = vocab[a as usize].clone();
+            bytes.extend_from_slice(&vocab[b as usize]);
+            vocab.push(bytes);
+            merges.push((a, b));
+            for word in &mut words {
+                let mut out = Vec::with_capacity(word.len());
+                let mut i = 0;
+                while i < word.len() {
+                    if i + 1 < word.len() && word[i] == a && word[i + 1] == b {
+                        out.push(id);
+                        i += 2;
+                    } else {
+                        out.push(word[i]);
+                        i += 1;
+                    }
+                }
+                *word = out;
+            }
+        }
+        merges
+    }
+
+    #[test]
+    fn test_incremental_trainer_matches_naive() {
+        let mut tok = BpeTokenizer::new(400);
+        tok.train(SAMPLE);
+        assert!(tok.merges.len() > 20);
+        assert_eq!(tok.merges, naive_merges(SAMPLE, 400));
+
+        // Overlapping runs are the tricky case for in-place updates.
+        let runs = "aaaaaaa aaaa aaaaa bbbbbb";
+        let mut tok = BpeTokenizer::new(300);
+        tok.train(runs);
+        assert_eq!(tok.merges, naive_merges(runs, 300));
+        assert_eq!(tok.decode(&tok.encode(runs)), runs);
+    }
+
+    #[test]
+    fn test_pretokenize_rust_classes() {
+        let code = "fn add(a: u64) -> u64 {\n    a + 1.5e3_f64\n}";
+        assert_eq!(
+            pretokenize(code),
+            vec![
+                "fn",
+                " add",
+                "(",
+                "a",
+                ":",
+                " u64",
+                ")",
+                " ->",
+                " u64",
+                " {",
+                "\n   ",
+                " a",
+                " +",
+                " 1.5e3_f64",
+                "\n",
+                "}",
+            ]
+        );
+        assert_eq!(pretokenize("x.0..10"), vec!["x", ".", "0", "..", "10"]);
+        assert_eq!(pretokenize("\tlet héllo"), vec!["\t", "let", " héllo"]);
+        assert_eq!(pretokenize(SAMPLE).concat(), SAMPLE);
+    }
+
+    #[test]
+    fn test_merges_stay_inside_pieces() {
+        let mut tok = BpeTokenizer::new(1024);
+        tok.train(SAMPLE);
+        for bytes in &tok.vocab[MERGE_START as usize..] {
+            if let Ok(text) = std::str::from_utf8(bytes) {
+                assert_eq!(pretokenize(text), vec![text], "merge {text:?} spans pieces");
+            }
+        }
+    }
+
+    #[test]
+    fn test_training_continues_from_existing_merges() {
+        let mut tok = BpeTokenizer::new(2048);
+        tok.train(SAMPLE);
+        let learned = tok.merges.clone();
+        tok.train("impl Iterator for Fibonacci { type Item = u64; }");
+        assert_eq!(tok.merges[..learned.len()], learned[..]);
+        let mut seen = std::collections::HashSet::new();
+        assert!(tok.vocab[MERGE_START as usize..]
+            .iter()
+            .all(|v| seen.insert(v)));
+    }
+
+    #[test]
+    fn test_legacy_json_keeps_whole_sequence_encoding() {
+        let mut tok = BpeTokenizer::new(512);
+        tok.pretokenize = false;
+        tok.train(SAMPLE);
+        let legacy = tok.to_json().replace(",\"pretokenize\":false", "");
+        assert!(!legacy.contains("pretokenize"));
+
+        let loaded = BpeTokenizer::from_json(&legacy).unwrap();
+        assert!(!loaded.pretokenize);
+        assert_eq!(loaded.encode(SAMPLE), tok.encode(SAMPLE));
+        // Whole-sequence mode is free to merge across a space.
+        assert!(loaded
+            .vocab
+            .iter()
+            .any(|v| v.len() > 1 && v.ends_with(b" ")));
+    }
+
+    #[test]
+    fn test_hf_json_roundtrip() {
+        let mut tok = BpeTokenizer::new(1024);
+        tok.train(SAMPLE);
+        let json = tok.to_hf_json().unwrap();
+
+        let doc: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(doc["model"]["type"], "BPE");
+        assert_eq!(doc["model"]["vocab"]["Ġfn"], tok.encode(" fn")[0]);
+        assert_eq!(doc["added_tokens"][1]["content"], "<|bos|>");
+
+        let back = BpeTokenizer::from_hf_json(&json).unwrap();
+        assert!(back.pretokenize);
+        assert_eq!(back.merges, tok.merges);
+        assert_eq!(back.vocab, tok.vocab);
+        for text in [
+            SAMPLE,
+            "struct Ünïcode<'a> { s: &'a str }\r\n",
+            "",
+            "\u{0}\u{7f}",
+        ] {
+            let ids = tok.encode(text);
+            assert_eq!(back.encode(text), ids);
+            assert_eq!(back.decode(&ids), text);
+        }
+        assert_eq!(back.to_hf_json().unwrap(), json);
+    }
+
+    #[test]
+    fn test_hf_json_rejects_unknown_merge() {
+        let json = r#"{"model":{"type":"BPE","vocab":{},"merges":["a zz"]}}"#;
+        assert!(matches!(
+            BpeTokenizer::from_hf_json(json),
+            Err(BpeError::UnknownToken(t)) if t == "zz"
+        ));
+        let json = r#"{"model":{"type":"WordPiece"}}"#;
+        assert!(matches!(
+            BpeTokenizer::from_hf_json(json),
+            Err(BpeError::Format(_))
+        ));
+    }
 }
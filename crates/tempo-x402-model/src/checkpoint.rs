@@ -0,0 +1,727 @@
+//! Binary checkpoint format — versioned, checksummed, mmap-able.
+//!
+//! JSON checkpoints of the 16M-parameter unified model run to hundreds of MB
+//! of decimal floats and take seconds to parse. A checkpoint is instead a
+//! small JSON header followed by raw little-endian tensor data, in the spirit
+//! of safetensors:
+//!
+//! ```text
+//! ┌──────────┬─────────┬────────────┬─────────────┬─────────┬──────────────────┐
+//! │ X402CKPT │ version │ header_len │ header JSON │ padding │ tensor data ...  │
+//! │ 8 bytes  │ u32 LE  │ u32 LE     │             │ to 64 B │ 64-byte aligned  │
+//! └──────────┴─────────┴────────────┴─────────────┴─────────┴──────────────────┘
+//! ```
+//!
+//! The header names the architecture, its hyperparameters, every tensor's
+//! dtype/shape/offset and a CRC32 of the data section. Loading validates the
+//! header against the current architecture (replacing the per-model
+//! dimension checks of `from_json`) and then reads tensors in place: from an
+//! mmap'd file or a borrowed buffer, aligned `f32` tensors are zero-copy.
+//!
+//! Legacy JSON checkpoints are still accepted by [`Checkpointable::load_bytes`]
+//! and [`Checkpointable::load_file`], so existing state migrates on next save.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// File magic.
+pub const MAGIC: &[u8; 8] = b"X402CKPT";
+/// Current format version.
+pub const FORMAT_VERSION: u32 = 1;
+/// Alignment of the data section and of every tensor within it.
+pub const ALIGN: usize = 64;
+
+const PREAMBLE_LEN: usize = MAGIC.len() + 4 + 4;
+
+/// Errors from reading or validating a checkpoint.
+#[derive(Debug, thiserror::Error)]
+pub enum CheckpointError {
+    #[error("not a checkpoint (bad magic)")]
+    BadMagic,
+
+    #[error("unsupported checkpoint version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("checkpoint truncated")]
+    Truncated,
+
+    #[error("invalid checkpoint header: {0}")]
+    Header(String),
+
+    #[error("checksum mismatch: header {expected:08x}, data {actual:08x}")]
+    Checksum { expected: u32, actual: u32 },
+
+    #[error("architecture mismatch: expected {expected}, found {found}")]
+    Arch { expected: String, found: String },
+
+    #[error("hyperparameter {name}: expected {expected}, found {found:?}")]
+    Param {
+        name: String,
+        expected: u64,
+        found: Option<u64>,
+    },
+
+    #[error("missing tensor {0}")]
+    MissingTensor(String),
+
+    #[error("tensor {name}: expected shape {expected:?}, found {found:?}")]
+    Shape {
+        name: String,
+        expected: Vec<usize>,
+        found: Vec<usize>,
+    },
+
+    #[error("tensor {name}: expected dtype {expected:?}, found {found:?}")]
+    Dtype {
+        name: String,
+        expected: Dtype,
+        found: Dtype,
+    },
+
+    #[error("invalid legacy JSON checkpoint")]
+    LegacyJson,
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Element type of a stored tensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dtype {
+    F32,
+    F16,
+    I8,
+}
+
+impl Dtype {
+    /// Bytes per element.
+    pub fn size(self) -> usize {
+        match self {
+            Dtype::F32 => 4,
+            Dtype::F16 => 2,
+            Dtype::I8 => 1,
+        }
+    }
+}
+
+/// One tensor's entry in the header.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TensorInfo {
+    pub name: String,
+    pub dtype: Dtype,
+    pub shape: Vec<usize>,
+    /// Byte offset from the start of the data section.
+    pub offset: u64,
+}
+
+impl TensorInfo {
+    /// Number of elements, or `None` if the shape overflows `usize`.
+    pub fn numel(&self) -> Option<usize> {
+        self.shape.iter().try_fold(1usize, |n, &d| n.checked_mul(d))
+    }
+
+    /// Stored size in bytes, or `None` on overflow.
+    pub fn byte_len(&self) -> Option<usize> {
+        self.numel()?.checked_mul(self.dtype.size())
+    }
+
+    /// Byte range within the data section, or `None` on overflow.
+    fn span(&self) -> Option<std::ops::Range<usize>> {
+        let start = usize::try_from(self.offset).ok()?;
+        Some(start..start.checked_add(self.byte_len()?)?)
+    }
+}
+
+/// Checkpoint header — everything but the tensor data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointHeader {
+    /// Architecture identifier, e.g. `"unified"`.
+    pub arch: String,
+    /// Architecture hyperparameters (d_model, layer counts, vocab, ...).
+    pub params: BTreeMap<String, u64>,
+    /// Training metadata (train_steps, running_loss, ...).
+    #[serde(default)]
+    pub meta: BTreeMap<String, serde_json::Value>,
+    pub tensors: Vec<TensorInfo>,
+    /// Length of the data section in bytes.
+    pub data_len: u64,
+    /// CRC32 (IEEE) of the data section.
+    pub checksum: u32,
+}
+
+/// Builds a checkpoint in memory.
+pub struct CheckpointWriter {
+    header: CheckpointHeader,
+    data: Vec<u8>,
+}
+
+impl CheckpointWriter {
+    /// Start a checkpoint for the given architecture.
+    pub fn new(arch: &str) -> Self {
+        Self {
+            header: CheckpointHeader {
+                arch: arch.to_string(),
+                params: BTreeMap::new(),
+                meta: BTreeMap::new(),
+                tensors: Vec::new(),
+                data_len: 0,
+                checksum: 0,
+            },
+            data: Vec::new(),
+        }
+    }
+
+    /// Record an architecture hyperparameter.
+    pub fn param(&mut self, name: &str, value: u64) -> &mut Self {
+        self.header.params.insert(name.to_string(), value);
+        self
+    }
+
+    /// Record a metadata value.
+    pub fn meta(&mut self, name: &str, value: impl Into<serde_json::Value>) -> &mut Self {
+        self.header.meta.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Append an `f32` tensor. `data.len()` must equal the product of `shape`.
+    pub fn tensor(&mut self, name: &str, shape: &[usize], data: &[f32]) -> &mut Self {
+        self.begin(name, Dtype::F32, shape, data.len());
+        for v in data {
+            self.data.extend_from_slice(&v.to_le_bytes());
+        }
+        self
+    }
+
+    /// Append an `f16` tensor.
+    pub fn tensor_f16(&mut self, name: &str, shape: &[usize], data: &[half::f16]) -> &mut Self {
+        self.begin(name, Dtype::F16, shape, data.len());
+        for v in data {
+            self.data.extend_from_slice(&v.to_le_bytes());
+        }
+        self
+    }
+
+    /// Append an `i8` tensor.
+    pub fn tensor_i8(&mut self, name: &str, shape: &[usize], data: &[i8]) -> &mut Self {
+        self.begin(name, Dtype::I8, shape, data.len());
+        self.data.extend(data.iter().map(|&v| v as u8));
+        self
+    }
+
+    /// Align the data section and record a tensor entry.
+    fn begin(&mut self, name: &str, dtype: Dtype, shape: &[usize], len: usize) {
+        debug_assert_eq!(shape.iter().product::<usize>(), len, "{name}");
+        self.data.resize(align_up(self.data.len()), 0);
+        self.header.tensors.push(TensorInfo {
+            name: name.to_string(),
+            dtype,
+            shape: shape.to_vec(),
+            offset: self.data.len() as u64,
+        });
+        self.data.reserve(len * dtype.size());
+    }
+
+    /// Serialize header and data into the final byte layout.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.header.data_len = self.data.len() as u64;
+        self.header.checksum = crc32(&self.data);
+        let header = serde_json::to_vec(&self.header).unwrap_or_default();
+        let data_start = align_up(PREAMBLE_LEN + header.len());
+
+        let mut out = Vec::with_capacity(data_start + self.data.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        out.extend_from_slice(&header);
+        out.resize(data_start, 0);
+        out.extend_from_slice(&self.data);
+        out
+    }
+}
+
+/// A parsed checkpoint borrowing its bytes (from a buffer or an mmap).
+#[derive(Debug)]
+pub struct Checkpoint<'a> {
+    pub header: CheckpointHeader,
+    data: &'a [u8],
+}
+
+impl<'a> Checkpoint<'a> {
+    /// Parse and verify a checkpoint: magic, version, header and checksum.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, CheckpointError> {
+        if !is_checkpoint(bytes) {
+            return Err(CheckpointError::BadMagic);
+        }
+        if bytes.len() < PREAMBLE_LEN {
+            return Err(CheckpointError::Truncated);
+        }
+        let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        if version != FORMAT_VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+        let header_len = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+        let header_end = PREAMBLE_LEN
+            .checked_add(header_len)
+            .ok_or(CheckpointError::Truncated)?;
+        let header_bytes = bytes
+            .get(PREAMBLE_LEN..header_end)
+            .ok_or(CheckpointError::Truncated)?;
+        let header: CheckpointHeader = serde_json::from_slice(header_bytes)
+            .map_err(|e| CheckpointError::Header(e.to_string()))?;
+
+        // Sizes and offsets come from the (possibly untrusted) header, so
+        // every computation on them is checked.
+        let data_start = header_end
+            .checked_next_multiple_of(ALIGN)
+            .ok_or(CheckpointError::Truncated)?;
+        let data_end = usize::try_from(header.data_len)
+            .ok()
+            .and_then(|len| data_start.checked_add(len))
+            .ok_or(CheckpointError::Truncated)?;
+        let data = bytes
+            .get(data_start..data_end)
+            .ok_or(CheckpointError::Truncated)?;
+        for t in &header.tensors {
+            if t.span().is_none_or(|span| span.end > data.len()) {
+                return Err(CheckpointError::Header(format!(
+                    "tensor {} extends past the data section",
+                    t.name
+                )));
+            }
+        }
+        let actual = crc32(data);
+        if actual != header.checksum {
+            return Err(CheckpointError::Checksum {
+                expected: header.checksum,
+                actual,
+            });
+        }
+        Ok(Self { header, data })
+    }
+
+    /// Check the architecture name and that every expected hyperparameter matches.
+    pub fn check_arch(&self, arch: &str, params: &[(&str, u64)]) -> Result<(), CheckpointError> {
+        if self.header.arch != arch {
+            return Err(CheckpointError::Arch {
+                expected: arch.to_string(),
+                found: self.header.arch.clone(),
+            });
+        }
+        for &(name, expected) in params {
+            let found = self.header.params.get(name).copied();
+            if found != Some(expected) {
+                return Err(CheckpointError::Param {
+                    name: name.to_string(),
+                    expected,
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// A hyperparameter from the header.
+    pub fn param(&self, name: &str) -> Result<u64, CheckpointError> {
+        self.header
+            .params
+            .get(name)
+            .copied()
+            .ok_or_else(|| CheckpointError::Header(format!("missing hyperparameter {name}")))
+    }
+
+    /// Header entry for a tensor.
+    pub fn info(&self, name: &str) -> Option<&TensorInfo> {
+        self.header.tensors.iter().find(|t| t.name == name)
+    }
+
+    /// Raw bytes of a tensor after checking its shape and dtype.
+    fn raw(&self, name: &str, shape: &[usize], dtype: Dtype) -> Result<&'a [u8], CheckpointError> {
+        let info = self
+            .info(name)
+            .ok_or_else(|| CheckpointError::MissingTensor(name.to_string()))?;
+        if info.shape != shape {
+            return Err(CheckpointError::Shape {
+                name: name.to_string(),
+                expected: shape.to_vec(),
+                found: info.shape.clone(),
+            });
+        }
+        if info.dtype != dtype {
+            return Err(CheckpointError::Dtype {
+                name: name.to_string(),
+                expected: dtype,
+                found: info.dtype,
+            });
+        }
+        info.span()
+            .and_then(|span| self.data.get(span))
+            .ok_or_else(|| {
+                CheckpointError::Header(format!("tensor {name} extends past the data section"))
+            })
+    }
+
+    /// Read an `f32` tensor, checking its shape. Zero-copy when the bytes are
+    /// suitably aligned on a little-endian target.
+    pub fn tensor(&self, name: &str, shape: &[usize]) -> Result<Cow<'a, [f32]>, CheckpointError> {
+        let bytes = self.raw(name, shape, Dtype::F32)?;
+
+        #[cfg(target_endian = "little")]
+        {
+            // SAFETY: every bit pattern is a valid f32; align_to only yields
+            // the middle slice for correctly aligned memory.
+            let (head, floats, tail) = unsafe { bytes.align_to::<f32>() };
+            if head.is_empty() && tail.is_empty() {
+                return Ok(Cow::Borrowed(floats));
+            }
+        }
+        Ok(Cow::Owned(
+            bytes
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect(),
+        ))
+    }
+
+    /// Read an `f16` tensor.
+    pub fn tensor_f16(
+        &self,
+        name: &str,
+        shape: &[usize],
+    ) -> Result<Vec<half::f16>, CheckpointError> {
+        let bytes = self.raw(name, shape, Dtype::F16)?;
+        Ok(bytes
+            .chunks_exact(2)
+            .map(|c| half::f16::from_le_bytes([c[0], c[1]]))
+            .collect())
+    }
+
+    /// Read an `i8` tensor.
+    pub fn tensor_i8(&self, name: &str, shape: &[usize]) -> Result<Vec<i8>, CheckpointError> {
+        let bytes = self.raw(name, shape, Dtype::I8)?;
+        Ok(bytes.iter().map(|&b| b as i8).collect())
+    }
+
+    /// Read an `f32` tensor into an owned vector.
+    pub fn tensor_vec(&self, name: &str, shape: &[usize]) -> Result<Vec<f32>, CheckpointError> {
+        self.tensor(name, shape).map(Cow::into_owned)
+    }
+
+    /// Integer metadata value, or 0 if absent.
+    pub fn meta_u64(&self, name: &str) -> u64 {
+        self.header
+            .meta
+            .get(name)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0)
+    }
+
+    /// String metadata value, if present.
+    pub fn meta_str(&self, name: &str) -> Option<&str> {
+        self.header.meta.get(name).and_then(|v| v.as_str())
+    }
+
+    /// Float metadata value, or 0.0 if absent.
+    pub fn meta_f32(&self, name: &str) -> f32 {
+        self.header
+            .meta
+            .get(name)
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0) as f32
+    }
+}
+
+/// A checkpoint file mapped into memory.
+///
+/// Files are written by rename ([`Checkpointable::save_file`]), so a live
+/// mapping keeps seeing the old contents rather than a half-written file.
+pub struct MappedCheckpoint {
+    mmap: memmap2::Mmap,
+}
+
+impl MappedCheckpoint {
+    /// Map a file read-only.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CheckpointError> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the mapping is read-only and checkpoints are replaced
+        // atomically, never modified in place.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(Self { mmap })
+    }
+
+    /// Raw file bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Parse and verify the mapped checkpoint.
+    pub fn checkpoint(&self) -> Result<Checkpoint<'_>, CheckpointError> {
+        Checkpoint::parse(&self.mmap)
+    }
+}
+
+/// Whether `bytes` starts with the checkpoint magic (as opposed to legacy JSON).
+pub fn is_checkpoint(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// A model with a binary checkpoint representation.
+pub trait Checkpointable: Sized {
+    /// Architecture identifier stored in the header.
+    const ARCH: &'static str;
+
+    /// Hyperparameters of the current architecture. A checkpoint loads only
+    /// if its header carries exactly these values.
+    fn hyperparams() -> Vec<(&'static str, u64)>;
+
+    /// Hyperparameters written to this instance's header. Architectures
+    /// sized at runtime add their shape here and read it back in
+    /// `read_tensors`; only [`Checkpointable::hyperparams`] is enforced.
+    fn header_params(&self) -> Vec<(&'static str, u64)> {
+        Self::hyperparams()
+    }
+
+    /// Append this model's tensors and metadata.
+    fn write_tensors(&self, w: &mut CheckpointWriter);
+
+    /// Rebuild the model from a checkpoint whose header has been validated.
+    fn read_tensors(ckpt: &Checkpoint<'_>) -> Result<Self, CheckpointError>;
+
+    /// Parse a legacy JSON checkpoint.
+    fn from_legacy_json(json: &str) -> Option<Self>;
+
+    /// Serialize to checkpoint bytes.
+    fn to_checkpoint(&self) -> Vec<u8> {
+        let mut w = CheckpointWriter::new(Self::ARCH);
+        for (name, value) in self.header_params() {
+            w.param(name, value);
+        }
+        self.write_tensors(&mut w);
+        w.finish()
+    }
+
+    /// Load from a parsed checkpoint, validating the header first.
+    fn from_checkpoint(ckpt: &Checkpoint<'_>) -> Result<Self, CheckpointError> {
+        ckpt.check_arch(Self::ARCH, &Self::hyperparams())?;
+        Self::read_tensors(ckpt)
+    }
+
+    /// Load from checkpoint bytes, or from legacy JSON if the magic is absent.
+    fn load_bytes(bytes: &[u8]) -> Result<Self, CheckpointError> {
+        if is_checkpoint(bytes) {
+            return Self::from_checkpoint(&Checkpoint::parse(bytes)?);
+        }
+        std::str::from_utf8(bytes)
+            .ok()
+            .and_then(Self::from_legacy_json)
+            .ok_or(CheckpointError::LegacyJson)
+    }
+
+    /// Load from a file, mmap'ing binary checkpoints. Legacy JSON files are
+    /// read and parsed as before.
+    fn load_file(path: impl AsRef<Path>) -> Result<Self, CheckpointError> {
+        let mapped = MappedCheckpoint::open(&path)?;
+        if is_checkpoint(mapped.bytes()) {
+            return Self::from_checkpoint(&mapped.checkpoint()?);
+        }
+        Self::load_bytes(mapped.bytes())
+    }
+
+    /// Write a checkpoint file atomically (temp file + rename).
+    fn save_file(&self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("ckpt.tmp");
+        std::fs::write(&tmp, self.to_checkpoint())?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+fn align_up(n: usize) -> usize {
+    n.div_ceil(ALIGN) * ALIGN
+}
+
+/// CRC32 (IEEE 802.3, reflected) lookup table.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+/// CRC32 (IEEE) of a byte slice.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc = CRC_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        let mut w = CheckpointWriter::new("toy");
+        w.param("d", 3).meta("train_steps", 7u64);
+        w.tensor("a", &[2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        w.tensor("b", &[1], &[-0.5]);
+        w.finish()
+    }
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn roundtrip_and_alignment() {
+        let bytes = sample();
+        let ckpt = Checkpoint::parse(&bytes).unwrap();
+        ckpt.check_arch("toy", &[("d", 3)]).unwrap();
+        assert_eq!(ckpt.meta_u64("train_steps"), 7);
+        assert_eq!(
+            ckpt.tensor_vec("a", &[2, 3]).unwrap(),
+            vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]
+        );
+        assert_eq!(ckpt.tensor_vec("b", &[1]).unwrap(), vec![-0.5]);
+        for t in &ckpt.header.tensors {
+            assert_eq!(t.offset as usize % ALIGN, 0);
+        }
+    }
+
+    #[test]
+    fn header_validation() {
+        let bytes = sample();
+        let ckpt = Checkpoint::parse(&bytes).unwrap();
+        assert!(matches!(
+            ckpt.check_arch("other", &[]),
+            Err(CheckpointError::Arch { .. })
+        ));
+        assert!(matches!(
+            ckpt.check_arch("toy", &[("d", 4)]),
+            Err(CheckpointError::Param { .. })
+        ));
+        assert!(matches!(
+            ckpt.tensor("a", &[3, 2]),
+            Err(CheckpointError::Shape { .. })
+        ));
+        assert!(matches!(
+            ckpt.tensor("missing", &[1]),
+            Err(CheckpointError::MissingTensor(_))
+        ));
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let mut bytes = sample();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        assert!(matches!(
+            Checkpoint::parse(&bytes),
+            Err(CheckpointError::Checksum { .. })
+        ));
+        assert!(matches!(
+            Checkpoint::parse(&bytes[..bytes.len() - 8]),
+            Err(CheckpointError::Truncated)
+        ));
+        assert!(matches!(
+            Checkpoint::parse(b"{\"not\":\"binary\"}"),
+            Err(CheckpointError::BadMagic)
+        ));
+    }
+
+    /// `sample()` with its header rewritten by `edit`; the data is unchanged.
+    fn with_header(edit: impl FnOnce(&mut CheckpointHeader)) -> Vec<u8> {
+        let bytes = sample();
+        let ckpt = Checkpoint::parse(&bytes).unwrap();
+        let mut header = ckpt.header.clone();
+        edit(&mut header);
+        let json = serde_json::to_vec(&header).unwrap();
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        out.extend_from_slice(&json);
+        out.resize(align_up(out.len()), 0);
+        out.extend_from_slice(ckpt.data);
+        out
+    }
+
+    #[test]
+    fn hostile_headers_are_rejected_without_panicking() {
+        let huge_offset = with_header(|h| h.tensors[0].offset = u64::MAX);
+        assert!(matches!(
+            Checkpoint::parse(&huge_offset),
+            Err(CheckpointError::Header(_))
+        ));
+        let huge_shape = with_header(|h| h.tensors[1].shape = vec![usize::MAX, 2]);
+        assert!(matches!(
+            Checkpoint::parse(&huge_shape),
+            Err(CheckpointError::Header(_))
+        ));
+        let wrapping_shape = with_header(|h| h.tensors[1].shape = vec![1 << 62, 4]);
+        assert!(Checkpoint::parse(&wrapping_shape).is_err());
+        let huge_data = with_header(|h| h.data_len = u64::MAX);
+        assert!(matches!(
+            Checkpoint::parse(&huge_data),
+            Err(CheckpointError::Truncated)
+        ));
+        let mut huge_header = sample();
+        huge_header[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            Checkpoint::parse(&huge_header),
+            Err(CheckpointError::Truncated)
+        ));
+
+        // Flip every header byte a few ways; parsing may fail but never panics,
+        // and whatever parses reads its tensors in bounds.
+        let bytes = sample();
+        let header_end =
+            PREAMBLE_LEN + u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+        for i in 8..header_end {
+            for flip in [0x01, 0x10, 0x80, 0xFF] {
+                let mut fuzzed = bytes.clone();
+                fuzzed[i] ^= flip;
+                if let Ok(ckpt) = Checkpoint::parse(&fuzzed) {
+                    for t in &ckpt.header.tensors {
+                        let _ = ckpt.tensor(&t.name, &t.shape);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mmap_is_zero_copy() {
+        let path = std::env::temp_dir().join(format!("x402-ckpt-{}.ckpt", std::process::id()));
+        std::fs::write(&path, sample()).unwrap();
+        let mapped = MappedCheckpoint::open(&path).unwrap();
+        let ckpt = mapped.checkpoint().unwrap();
+        let a = ckpt.tensor("a", &[2, 3]).unwrap();
+        assert!(matches!(a, Cow::Borrowed(_)));
+        assert_eq!(a[5], 6.0);
+        drop(ckpt);
+        drop(mapped);
+        let _ = std::fs::remove_file(&path);
+    }
+}
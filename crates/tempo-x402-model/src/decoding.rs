@@ -0,0 +1,890 @@
+//! Incremental decoding with a KV cache, plus sampling and beam search.
+//!
+//! `decode()` on the encoder-decoder models re-runs the whole decoder over the
+//! target prefix for every new token. [`KvCache`] instead keeps each decoder
+//! layer's self-attention keys/values and the cross-attention projections of
+//! the encoder output, so a step only processes the newest position.
+//!
+//! [`generate`] drives any [`IncrementalDecoder`] — the f32 and quantized
+//! unified/codegen models — with a [`Strategy`] (greedy, top-k, nucleus or
+//! beam search), stop tokens, stop sequences and a wall-clock budget.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::codegen::CodeGenModel;
+use crate::linalg::{self, add_assign, attend};
+use crate::quantize::{
+    Dims, QuantMatrix, QuantizedCodeGenModel, QuantizedUnifiedModel, CODEGEN_DIMS,
+};
+use crate::unified::{layer_norm, UnifiedModel};
+
+/// A model that can extend a decoder prefix one token at a time.
+pub trait IncrementalDecoder: Sync {
+    fn vocab_size(&self) -> usize;
+
+    /// Longest decoder sequence (prompt + generated tokens).
+    fn max_seq(&self) -> usize;
+
+    /// Project the encoder output for cross-attention and return an empty cache.
+    fn start(&self, encoder_output: &[f32], enc_len: usize) -> KvCache;
+
+    /// Feed `token` at position `cache.len()`, extend the cache, and return
+    /// the logits `[vocab_size]` for the next token. Callers must not step
+    /// past `max_seq()`.
+    fn step(&self, cache: &mut KvCache, token: u32) -> Vec<f32>;
+}
+
+/// Cached decoder state for one hypothesis. Cloning is cheap for the
+/// cross-attention part (shared), linear in the prefix for self-attention.
+#[derive(Debug, Clone)]
+pub struct KvCache {
+    layers: Vec<LayerCache>,
+    len: usize,
+    enc_len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct LayerCache {
+    /// Self-attention keys/values, `[len x d]`.
+    k: Vec<f32>,
+    v: Vec<f32>,
+    /// Cross-attention keys/values over the encoder output, `[enc_len x d]`.
+    cross: Option<Arc<CrossKv>>,
+}
+
+#[derive(Debug)]
+struct CrossKv {
+    k: Vec<f32>,
+    v: Vec<f32>,
+}
+
+impl KvCache {
+    /// Number of decoder positions already processed.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Memory held by this cache (shared cross-attention included).
+    pub fn size_bytes(&self) -> usize {
+        self.layers
+            .iter()
+            .map(|l| {
+                let cross = l.cross.as_ref().map_or(0, |c| c.k.len() + c.v.len());
+                (l.k.len() + l.v.len() + cross) * 4
+            })
+            .sum()
+    }
+}
+
+// ── Weight views ─────────────────────────────────────────────────
+
+/// A row-major weight matrix that can multiply a single vector.
+pub(crate) trait Linear: Sync {
+    fn matvec(&self, x: &[f32]) -> Vec<f32>;
+
+    /// Row `r` as f32 (embedding lookup).
+    fn row(&self, r: usize) -> Vec<f32>;
+
+    /// `matvec` over `n` row-major input vectors of length `cols`.
+    fn matvec_batch(&self, xs: &[f32], n: usize) -> Vec<f32> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let cols = xs.len() / n;
+        (0..n)
+            .into_par_iter()
+            .flat_map_iter(|i| self.matvec(&xs[i * cols..(i + 1) * cols]))
+            .collect()
+    }
+}
+
+impl Linear for QuantMatrix {
+    fn matvec(&self, x: &[f32]) -> Vec<f32> {
+        QuantMatrix::matvec(self, x)
+    }
+
+    fn row(&self, r: usize) -> Vec<f32> {
+        QuantMatrix::row(self, r)
+    }
+
+    fn matvec_batch(&self, xs: &[f32], n: usize) -> Vec<f32> {
+        QuantMatrix::matvec_batch(self, xs, n)
+    }
+}
+
+impl<T: Linear + ?Sized> Linear for &T {
+    fn matvec(&self, x: &[f32]) -> Vec<f32> {
+        (**self).matvec(x)
+    }
+
+    fn row(&self, r: usize) -> Vec<f32> {
+        (**self).row(r)
+    }
+
+    fn matvec_batch(&self, xs: &[f32], n: usize) -> Vec<f32> {
+        (**self).matvec_batch(xs, n)
+    }
+}
+
+/// Borrowed f32 matrix `[rows x cols]`.
+pub(crate) struct Dense<'a> {
+    w: &'a [f32],
+    rows: usize,
+    cols: usize,
+}
+
+impl<'a> Dense<'a> {
+    pub(crate) fn new(w: &'a [f32], rows: usize, cols: usize) -> Self {
+        Self { w, rows, cols }
+    }
+}
+
+impl Linear for Dense<'_> {
+    fn matvec(&self, x: &[f32]) -> Vec<f32> {
+        linalg::gemv(self.w, x, self.rows, self.cols)
+    }
+
+    fn row(&self, r: usize) -> Vec<f32> {
+        self.w[r * self.cols..(r + 1) * self.cols].to_vec()
+    }
+
+    fn matvec_batch(&self, xs: &[f32], n: usize) -> Vec<f32> {
+        linalg::matmul_nt(xs, self.w, n, self.cols, self.rows)
+    }
+}
+
+/// One decoder layer's weights, borrowed from either precision.
+pub(crate) struct DecoderView<'a, L> {
+    pub(crate) wq: L,
+    pub(crate) wk: L,
+    pub(crate) wv: L,
+    pub(crate) wo: L,
+    pub(crate) cross_wq: L,
+    pub(crate) cross_wk: L,
+    pub(crate) cross_wv: L,
+    pub(crate) cross_wo: L,
+    pub(crate) ff_w1: L,
+    pub(crate) ff_w2: L,
+    pub(crate) ln1_scale: &'a [f32],
+    pub(crate) ln2_scale: &'a [f32],
+    pub(crate) ln3_scale: &'a [f32],
+}
+
+/// The decoder half of an encoder-decoder model, borrowed for decoding.
+pub(crate) struct DecoderStack<'a, L> {
+    pub(crate) dims: Dims,
+    pub(crate) vocab: usize,
+    pub(crate) max_seq: usize,
+    /// Token embeddings `[vocab x d]`, tied with the output projection.
+    pub(crate) embeddings: L,
+    pub(crate) dec_pos: &'a [f32],
+    pub(crate) output_bias: &'a [f32],
+    pub(crate) layers: Vec<DecoderView<'a, L>>,
+}
+
+impl<L: Linear> DecoderStack<'_, L> {
+    fn start(&self, encoder_output: &[f32], enc_len: usize) -> KvCache {
+        let d = self.dims.d;
+        let enc_len = enc_len.min(encoder_output.len() / d);
+        let layers = self
+            .layers
+            .par_iter()
+            .map(|l| LayerCache {
+                k: Vec::new(),
+                v: Vec::new(),
+                cross: (enc_len > 0).then(|| {
+                    let enc = &encoder_output[..enc_len * d];
+                    Arc::new(CrossKv {
+                        k: l.cross_wk.matvec_batch(enc, enc_len),
+                        v: l.cross_wv.matvec_batch(enc, enc_len),
+                    })
+                }),
+            })
+            .collect();
+        KvCache {
+            layers,
+            len: 0,
+            enc_len,
+        }
+    }
+
+    fn step(&self, cache: &mut KvCache, token: u32) -> Vec<f32> {
+        let d = self.dims.d;
+        let pos = cache.len;
+        assert!(pos < self.max_seq, "decoder step past max_seq ({pos})");
+
+        let mut x = self.embeddings.row(token as usize % self.vocab);
+        add_assign(&mut x, &self.dec_pos[pos * d..(pos + 1) * d]);
+
+        for (layer, lc) in self.layers.iter().zip(&mut cache.layers) {
+            // Causal self-attention: the new position attends to the cache
+            // plus itself, so no mask is needed.
+            let normed = layer_norm(&x, layer.ln1_scale, 1, d);
+            lc.k.extend(layer.wk.matvec(&normed));
+            lc.v.extend(layer.wv.matvec(&normed));
+            let q = layer.wq.matvec(&normed);
+            let attn = attend(&q, 1, &lc.k, &lc.v, pos + 1, self.dims, false);
+            add_assign(&mut x, &layer.wo.matvec(&attn));
+
+            if let Some(cross) = &lc.cross {
+                let normed2 = layer_norm(&x, layer.ln2_scale, 1, d);
+                let q = layer.cross_wq.matvec(&normed2);
+                let attn = attend(&q, 1, &cross.k, &cross.v, cache.enc_len, self.dims, false);
+                add_assign(&mut x, &layer.cross_wo.matvec(&attn));
+            }
+
+            let normed3 = layer_norm(&x, layer.ln3_scale, 1, d);
+            let mut hidden = layer.ff_w1.matvec(&normed3);
+            for h in &mut hidden {
+                *h = h.max(0.0);
+            }
+            add_assign(&mut x, &layer.ff_w2.matvec(&hidden));
+        }
+        cache.len += 1;
+
+        let mut logits = self.embeddings.matvec(&x);
+        add_assign(&mut logits, self.output_bias);
+        logits
+    }
+}
+
+/// Borrow an f32 decoder layer of either model (the field layout is shared).
+macro_rules! dense_decoder_view {
+    ($l:expr, $d:expr, $ff:expr) => {
+        DecoderView {
+            wq: Dense::new(&$l.wq, $d, $d),
+            wk: Dense::new(&$l.wk, $d, $d),
+            wv: Dense::new(&$l.wv, $d, $d),
+            wo: Dense::new(&$l.wo, $d, $d),
+            cross_wq: Dense::new(&$l.cross_wq, $d, $d),
+            cross_wk: Dense::new(&$l.cross_wk, $d, $d),
+            cross_wv: Dense::new(&$l.cross_wv, $d, $d),
+            cross_wo: Dense::new(&$l.cross_wo, $d, $d),
+            ff_w1: Dense::new(&$l.ff_w1, $ff, $d),
+            ff_w2: Dense::new(&$l.ff_w2, $d, $ff),
+            ln1_scale: &$l.ln1_scale,
+            ln2_scale: &$l.ln2_scale,
+            ln3_scale: &$l.ln3_scale,
+        }
+    };
+}
+
+impl CodeGenModel {
+    fn decoder_stack(&self) -> DecoderStack<'_, Dense<'_>> {
+        let Dims { d, ff, .. } = CODEGEN_DIMS;
+        DecoderStack {
+            dims: CODEGEN_DIMS,
+            vocab: self.vocab_size,
+            max_seq: self.max_seq,
+            embeddings: Dense::new(&self.embeddings, self.vocab_size, d),
+            dec_pos: &self.dec_pos,
+            output_bias: &self.output_bias,
+            layers: self
+                .decoder_layers
+                .iter()
+                .map(|l| dense_decoder_view!(l, d, ff))
+                .collect(),
+        }
+    }
+}
+
+impl UnifiedModel {
+    fn decoder_stack(&self) -> DecoderStack<'_, Dense<'_>> {
+        let c = &self.config;
+        let Dims { d, ff, .. } = c.dims();
+        DecoderStack {
+            dims: c.dims(),
+            vocab: c.vocab,
+            max_seq: c.max_seq,
+            embeddings: Dense::new(&self.embeddings, c.vocab, d),
+            dec_pos: &self.dec_pos,
+            output_bias: &self.output_bias,
+            layers: self
+                .decoder_layers
+                .iter()
+                .map(|l| dense_decoder_view!(l, d, ff))
+                .collect(),
+        }
+    }
+}
+
+macro_rules! incremental_decoder {
+    ($ty:ty, $vocab:expr, $max_seq:expr) => {
+        impl IncrementalDecoder for $ty {
+            fn vocab_size(&self) -> usize {
+                $vocab(self)
+            }
+
+            fn max_seq(&self) -> usize {
+                $max_seq(self)
+            }
+
+            fn start(&self, encoder_output: &[f32], enc_len: usize) -> KvCache {
+                self.decoder_stack().start(encoder_output, enc_len)
+            }
+
+            fn step(&self, cache: &mut KvCache, token: u32) -> Vec<f32> {
+                self.decoder_stack().step(cache, token)
+            }
+        }
+    };
+}
+
+incremental_decoder!(
+    CodeGenModel,
+    |m: &CodeGenModel| m.vocab_size,
+    |m: &CodeGenModel| m.max_seq
+);
+incremental_decoder!(
+    UnifiedModel,
+    |m: &UnifiedModel| m.config.vocab,
+    |m: &UnifiedModel| m.config.max_seq
+);
+incremental_decoder!(
+    QuantizedCodeGenModel,
+    |m: &QuantizedCodeGenModel| m.vocab_size,
+    |m: &QuantizedCodeGenModel| m.max_seq
+);
+incremental_decoder!(
+    QuantizedUnifiedModel,
+    |m: &QuantizedUnifiedModel| m.config.vocab,
+    |m: &QuantizedUnifiedModel| m.config.max_seq
+);
+
+// ── Generation ───────────────────────────────────────────────────
+
+/// How the next token is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Strategy {
+    /// Always the most likely token. Produces a single candidate.
+    Greedy,
+    /// Sample from the `k` most likely tokens.
+    TopK { k: usize, temperature: f32 },
+    /// Nucleus sampling: sample from the smallest set of tokens whose
+    /// probability mass reaches `p`.
+    TopP { p: f32, temperature: f32 },
+    /// Beam search keeping `width` hypotheses. Finished hypotheses are ranked
+    /// by `log_prob / len^length_penalty`.
+    Beam { width: usize, length_penalty: f32 },
+}
+
+/// Decoding parameters for [`generate`].
+#[derive(Debug, Clone)]
+pub struct DecodeConfig {
+    pub strategy: Strategy,
+    /// Generated tokens per candidate (prompt excluded).
+    pub max_tokens: usize,
+    /// Tokens that end a candidate; they are not included in the output.
+    pub stop_tokens: Vec<u32>,
+    /// Token sequences that end a candidate; they are kept in the output.
+    pub stop_sequences: Vec<Vec<u32>>,
+    /// Wall-clock budget for the whole call. Unfinished candidates are
+    /// returned as they stand when it runs out.
+    pub max_time: Option<Duration>,
+    /// Candidates to return: independent samples for top-k/top-p, the best
+    /// finished beams for beam search (at most `width`).
+    pub num_candidates: usize,
+    /// Seed for sampling strategies; candidate `i` uses `seed + i`.
+    pub seed: u64,
+}
+
+impl Default for DecodeConfig {
+    fn default() -> Self {
+        Self {
+            strategy: Strategy::Greedy,
+            max_tokens: 256,
+            stop_tokens: Vec::new(),
+            stop_sequences: Vec::new(),
+            max_time: None,
+            num_candidates: 1,
+            seed: 0,
+        }
+    }
+}
+
+/// Why a candidate stopped growing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FinishReason {
+    StopToken,
+    StopSequence,
+    MaxTokens,
+    MaxSeq,
+    TimeBudget,
+}
+
+/// One generated continuation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candidate {
+    /// Generated tokens, prompt excluded.
+    pub tokens: Vec<u32>,
+    /// Sum of the model's (temperature 1) log-probabilities of the chosen
+    /// tokens, including a terminating stop token.
+    pub log_prob: f32,
+    pub finish: FinishReason,
+}
+
+/// Generate continuations of the decoder `prompt` (e.g. `[BOS]`) conditioned
+/// on `encoder_output`. The prompt is prefilled once and its cache shared by
+/// every candidate. Returns candidates best-first for beam search, in seed
+/// order for sampling; empty if the prompt is empty.
+pub fn generate<M: IncrementalDecoder + ?Sized>(
+    model: &M,
+    encoder_output: &[f32],
+    enc_len: usize,
+    prompt: &[u32],
+    cfg: &DecodeConfig,
+) -> Vec<Candidate> {
+    let deadline = cfg.max_time.map(|t| Instant::now() + t);
+    let prompt = &prompt[..prompt.len().min(model.max_seq())];
+    let Some((&last, head)) = prompt.split_last() else {
+        return Vec::new();
+    };
+
+    let mut cache = model.start(encoder_output, enc_len);
+    for &tok in head {
+        model.step(&mut cache, tok);
+    }
+    let logits = model.step(&mut cache, last);
+    let root = Hypothesis {
+        tokens: Vec::new(),
+        log_prob: 0.0,
+        cache,
+        logits,
+    };
+
+    match cfg.strategy {
+        Strategy::Beam {
+            width,
+            length_penalty,
+        } => beam_search(model, root, cfg, width.max(1), length_penalty, deadline),
+        Strategy::Greedy => vec![sample(model, root, cfg, &mut Rng::new(cfg.seed), deadline)],
+        _ => (0..cfg.num_candidates.max(1) as u64)
+            .into_par_iter()
+            .map(|i| {
+                let mut rng = Rng::new(cfg.seed.wrapping_add(i));
+                sample(model, root.clone(), cfg, &mut rng, deadline)
+            })
+            .collect(),
+    }
+}
+
+/// A partial candidate: tokens so far, its cache, and the next-token logits.
+#[derive(Clone)]
+struct Hypothesis {
+    tokens: Vec<u32>,
+    log_prob: f32,
+    cache: KvCache,
+    logits: Vec<f32>,
+}
+
+impl Hypothesis {
+    fn finish(self, finish: FinishReason) -> Candidate {
+        Candidate {
+            tokens: self.tokens,
+            log_prob: self.log_prob,
+            finish,
+        }
+    }
+}
+
+/// Outcome of appending `token` to a hypothesis, before stepping the model.
+fn check_stop<M: IncrementalDecoder + ?Sized>(
+    model: &M,
+    cfg: &DecodeConfig,
+    h: &mut Hypothesis,
+    token: u32,
+) -> Option<FinishReason> {
+    if cfg.stop_tokens.contains(&token) {
+        return Some(FinishReason::StopToken);
+    }
+    h.tokens.push(token);
+    if cfg
+        .stop_sequences
+        .iter()
+        .any(|s| !s.is_empty() && h.tokens.ends_with(s))
+    {
+        Some(FinishReason::StopSequence)
+    } else if h.tokens.len() >= cfg.max_tokens {
+        Some(FinishReason::MaxTokens)
+    } else if h.cache.len() >= model.max_seq() {
+        Some(FinishReason::MaxSeq)
+    } else {
+        None
+    }
+}
+
+fn expired(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|d| Instant::now() >= d)
+}
+
+/// Greedy or sampled decoding of a single candidate.
+fn sample<M: IncrementalDecoder + ?Sized>(
+    model: &M,
+    mut h: Hypothesis,
+    cfg: &DecodeConfig,
+    rng: &mut Rng,
+    deadline: Option<Instant>,
+) -> Candidate {
+    if cfg.max_tokens == 0 {
+        return h.finish(FinishReason::MaxTokens);
+    }
+    loop {
+        if expired(deadline) {
+            return h.finish(FinishReason::TimeBudget);
+        }
+        let token = match cfg.strategy {
+            Strategy::TopK { k, temperature } => {
+                let mut probs = softmax(&h.logits, temperature);
+                keep_top_k(&mut probs, k.max(1));
+                rng.pick(&probs)
+            }
+            Strategy::TopP { p, temperature } => {
+                let mut probs = softmax(&h.logits, temperature);
+                keep_top_p(&mut probs, p);
+                rng.pick(&probs)
+            }
+            Strategy::Greedy | Strategy::Beam { .. } => argmax(&h.logits),
+        };
+        h.log_prob += log_softmax(&h.logits)[token as usize];
+        if let Some(reason) = check_stop(model, cfg, &mut h, token) {
+            return h.finish(reason);
+        }
+        h.logits = model.step(&mut h.cache, token);
+    }
+}
+
+fn beam_search<M: IncrementalDecoder + ?Sized>(
+    model: &M,
+    root: Hypothesis,
+    cfg: &DecodeConfig,
+    width: usize,
+    length_penalty: f32,
+    deadline: Option<Instant>,
+) -> Vec<Candidate> {
+    let mut live = vec![root];
+    let mut finished: Vec<Candidate> = Vec::new();
+
+    if cfg.max_tokens == 0 {
+        finished.extend(live.drain(..).map(|h| h.finish(FinishReason::MaxTokens)));
+    }
+
+    while !live.is_empty() && finished.len() < width {
+        if expired(deadline) {
+            finished.extend(live.drain(..).map(|h| h.finish(FinishReason::TimeBudget)));
+            break;
+        }
+
+        // Top `width` extensions of every live beam, then the best overall.
+        let mut expansions: Vec<(usize, u32, f32)> = Vec::new();
+        for (b, h) in live.iter().enumerate() {
+            let logp = log_softmax(&h.logits);
+            let mut order: Vec<usize> = (0..logp.len()).collect();
+            let k = width.min(order.len());
+            order.select_nth_unstable_by(k - 1, |&a, &c| logp[c].total_cmp(&logp[a]));
+            for &t in &order[..k] {
+                expansions.push((b, t as u32, h.log_prob + logp[t]));
+            }
+        }
+        expansions.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        let mut next: Vec<(Hypothesis, u32)> = Vec::new();
+        for (b, token, log_prob) in expansions {
+            if next.len() >= width {
+                break;
+            }
+            let mut h = live[b].clone();
+            h.log_prob = log_prob;
+            match check_stop(model, cfg, &mut h, token) {
+                Some(reason) => finished.push(h.finish(reason)),
+                None => next.push((h, token)),
+            }
+        }
+
+        live = next
+            .into_par_iter()
+            .map(|(mut h, token)| {
+                h.logits = model.step(&mut h.cache, token);
+                h
+            })
+            .collect();
+    }
+
+    let score = |c: &Candidate| c.log_prob / (c.tokens.len().max(1) as f32).powf(length_penalty);
+    finished.sort_by(|a, b| score(b).total_cmp(&score(a)));
+    finished.truncate(cfg.num_candidates.clamp(1, width));
+    finished
+}
+
+fn argmax(v: &[f32]) -> u32 {
+    v.iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map_or(0, |(i, _)| i as u32)
+}
+
+fn softmax(logits: &[f32], temperature: f32) -> Vec<f32> {
+    let t = temperature.max(1e-4);
+    let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let mut probs: Vec<f32> = logits.iter().map(|l| ((l - max) / t).exp()).collect();
+    let sum: f32 = probs.iter().sum();
+    for p in &mut probs {
+        *p /= sum;
+    }
+    probs
+}
+
+fn log_softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let log_sum = logits.iter().map(|l| (l - max).exp()).sum::<f32>().ln() + max;
+    logits.iter().map(|l| l - log_sum).collect()
+}
+
+/// Zero all but the `k` largest probabilities (unnormalized afterwards).
+fn keep_top_k(probs: &mut [f32], k: usize) {
+    if k >= probs.len() {
+        return;
+    }
+    let mut sorted = probs.to_vec();
+    sorted.select_nth_unstable_by(k - 1, |a, b| b.total_cmp(a));
+    let cutoff = sorted[k - 1];
+    let mut kept = 0;
+    for p in probs.iter_mut() {
+        if *p >= cutoff && kept < k {
+            kept += 1;
+        } else {
+            *p = 0.0;
+        }
+    }
+}
+
+/// Zero everything outside the smallest top set with mass >= `p`.
+fn keep_top_p(probs: &mut [f32], p: f32) {
+    let mut order: Vec<usize> = (0..probs.len()).collect();
+    order.sort_by(|&a, &b| probs[b].total_cmp(&probs[a]));
+    let mut mass = 0.0;
+    let mut keep = order.len();
+    for (i, &t) in order.iter().enumerate() {
+        mass += probs[t];
+        if mass >= p {
+            keep = i + 1;
+            break;
+        }
+    }
+    for &t in &order[keep..] {
+        probs[t] = 0.0;
+    }
+}
+
+/// SplitMix64 — deterministic per-candidate sampling.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Sample an index from unnormalized non-negative weights.
+    fn pick(&mut self, weights: &[f32]) -> u32 {
+        let total: f32 = weights.iter().sum();
+        let mut r = self.next_f32() * total;
+        for (i, &w) in weights.iter().enumerate() {
+            if w > 0.0 && r < w {
+                return i as u32;
+            }
+            r -= w;
+        }
+        argmax(weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantize::QuantMode;
+
+    /// Toy decoder over {0: EOS, 1: A, 2: B, 3: BOS} whose next-token
+    /// distribution depends only on the last token (kept in the cache keys).
+    struct Chain;
+
+    impl IncrementalDecoder for Chain {
+        fn vocab_size(&self) -> usize {
+            4
+        }
+
+        fn max_seq(&self) -> usize {
+            64
+        }
+
+        fn start(&self, _: &[f32], _: usize) -> KvCache {
+            KvCache {
+                layers: vec![LayerCache {
+                    k: Vec::new(),
+                    v: Vec::new(),
+                    cross: None,
+                }],
+                len: 0,
+                enc_len: 0,
+            }
+        }
+
+        fn step(&self, cache: &mut KvCache, token: u32) -> Vec<f32> {
+            cache.layers[0].k.push(token as f32);
+            cache.len += 1;
+            let probs: [f32; 4] = match token {
+                3 => [0.0, 0.6, 0.4, 0.0],
+                1 => [0.4, 0.3, 0.3, 0.0],
+                _ => [0.9, 0.05, 0.05, 0.0],
+            };
+            probs.iter().map(|p| p.max(1e-9).ln()).collect()
+        }
+    }
+
+    fn cfg(strategy: Strategy) -> DecodeConfig {
+        DecodeConfig {
+            strategy,
+            max_tokens: 10,
+            stop_tokens: vec![0],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn greedy_and_beam_search() {
+        let greedy = generate(&Chain, &[], 0, &[3], &cfg(Strategy::Greedy));
+        assert_eq!(greedy.len(), 1);
+        assert_eq!(greedy[0].tokens, vec![1]);
+        assert_eq!(greedy[0].finish, FinishReason::StopToken);
+
+        // Greedy takes A (0.6) then EOS (0.4) = 0.24; beam finds B, EOS = 0.36.
+        let beam = Strategy::Beam {
+            width: 2,
+            length_penalty: 0.0,
+        };
+        let beams = generate(
+            &Chain,
+            &[],
+            0,
+            &[3],
+            &DecodeConfig {
+                num_candidates: 2,
+                ..cfg(beam)
+            },
+        );
+        assert_eq!(beams[0].tokens, vec![2]);
+        assert!((beams[0].log_prob - 0.36f32.ln()).abs() < 1e-4);
+        assert_eq!(beams[1].tokens, vec![1]);
+
+        // k=1 and a tiny nucleus both collapse to greedy.
+        for s in [
+            Strategy::TopK {
+                k: 1,
+                temperature: 1.0,
+            },
+            Strategy::TopP {
+                p: 0.01,
+                temperature: 1.0,
+            },
+        ] {
+            let out = generate(&Chain, &[], 0, &[3], &cfg(s));
+            assert_eq!(out[0].tokens, vec![1], "{s:?}");
+        }
+    }
+
+    #[test]
+    fn stop_conditions() {
+        let no_eos = DecodeConfig {
+            stop_tokens: Vec::new(),
+            ..cfg(Strategy::Greedy)
+        };
+        let out = &generate(&Chain, &[], 0, &[3], &no_eos)[0];
+        assert_eq!(out.tokens, vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(out.finish, FinishReason::MaxTokens);
+
+        let stop_seq = DecodeConfig {
+            stop_sequences: vec![vec![0, 0]],
+            ..no_eos.clone()
+        };
+        let out = &generate(&Chain, &[], 0, &[3], &stop_seq)[0];
+        assert_eq!(out.tokens, vec![1, 0, 0]);
+        assert_eq!(out.finish, FinishReason::StopSequence);
+
+        let timed = DecodeConfig {
+            max_time: Some(Duration::ZERO),
+            ..no_eos
+        };
+        let out = &generate(&Chain, &[], 0, &[3], &timed)[0];
+        assert_eq!(out.finish, FinishReason::TimeBudget);
+    }
+
+    #[test]
+    fn sampling_is_seeded() {
+        let s = DecodeConfig {
+            num_candidates: 8,
+            seed: 7,
+            ..cfg(Strategy::TopP {
+                p: 1.0,
+                temperature: 1.0,
+            })
+        };
+        let a: Vec<_> = generate(&Chain, &[], 0, &[3], &s)
+            .into_iter()
+            .map(|c| c.tokens)
+            .collect();
+        let b: Vec<_> = generate(&Chain, &[], 0, &[3], &s)
+            .into_iter()
+            .map(|c| c.tokens)
+            .collect();
+        assert_eq!(a, b);
+        assert!(a.iter().any(|t| t != &a[0]), "8 samples should differ");
+    }
+
+    #[test]
+    fn cached_steps_match_full_decode() {
+        let model = CodeGenModel::new();
+        let context = [1u32, 40, 41, 42, 2];
+        let target = [1u32, 100, 200, 300];
+        let enc = model.encode(&context);
+        let quant = QuantizedCodeGenModel::from_model(&model, QuantMode::Int8);
+        let qenc = quant.encode(&context);
+
+        let mut cache = model.start(&enc, context.len());
+        let mut qcache = quant.start(&qenc, context.len());
+        for n in 1..=target.len() {
+            let got = model.step(&mut cache, target[n - 1]);
+            let want = model.decode(&target[..n], &enc, context.len());
+            let err = got
+                .iter()
+                .zip(&want)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f32::max);
+            assert!(err < 1e-3, "f32 position {n}: max err {err}");
+
+            let got = quant.step(&mut qcache, target[n - 1]);
+            let want = quant.decode(&target[..n], &qenc, context.len());
+            let err = got
+                .iter()
+                .zip(&want)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f32::max);
+            assert!(err < 1e-3, "int8 position {n}: max err {err}");
+        }
+        assert_eq!(cache.len(), target.len());
+    }
+}
@@ -0,0 +1,738 @@
+//! Byzantine-robust, compressed federated averaging between colony peers.
+//!
+//! Each peer publishes a [`SparseDelta`]: its weight progress since the last
+//! publication, top-k sparsified and quantized to int8, tagged with
+//! [`Provenance`] (peer id, train steps, optional signature). The publisher's
+//! [`DeltaBase`] advances by exactly what was sent, so coordinates dropped by
+//! top-k accumulate into later deltas (error feedback) instead of being lost.
+//!
+//! A receiver collects deltas from all peers and [`merge`]s them:
+//! structurally invalid, unsigned/forged or under-trained deltas are
+//! rejected; each remaining delta is trial-applied and rejected if it raises
+//! a local validation loss; survivors are combined with a coordinate-wise
+//! median or trimmed mean ([`Aggregation`]), so a single poisoned peer cannot
+//! move any coordinate on its own. The combined update is kept only if it
+//! does not degrade validation loss either.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::{Checkpoint, CheckpointError, CheckpointWriter, Checkpointable};
+
+/// A model whose trainable weights can be exchanged with peers.
+///
+/// `tensors` and `tensors_mut` must list the same tensors in the same order;
+/// that order defines the flat coordinate space deltas index into.
+pub trait Federated {
+    /// Architecture tag; deltas only merge into the same architecture.
+    fn arch(&self) -> &'static str;
+    fn train_steps(&self) -> u64;
+    fn tensors(&self) -> Vec<&[f32]>;
+    fn tensors_mut(&mut self) -> Vec<&mut [f32]>;
+
+    /// Number of federated coordinates.
+    fn param_len(&self) -> usize {
+        self.tensors().iter().map(|t| t.len()).sum()
+    }
+
+    /// All federated weights, concatenated.
+    fn flat_weights(&self) -> Vec<f32> {
+        self.tensors().concat()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FederatedError {
+    #[error("not a federated delta (bad magic)")]
+    BadMagic,
+
+    #[error("unsupported delta version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("truncated delta")]
+    Truncated,
+
+    #[error("malformed delta: {0}")]
+    Malformed(String),
+}
+
+/// Who produced a delta, and from what training state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub peer_id: String,
+    pub arch: String,
+    /// Publisher's train steps when its previous delta was cut.
+    pub base_steps: u64,
+    /// Publisher's train steps when this delta was cut.
+    pub train_steps: u64,
+    /// Signature over [`SparseDelta::signing_payload`], scheme chosen by the
+    /// caller.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Top-k sparsified, int8-quantized weight delta.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseDelta {
+    pub provenance: Provenance,
+    /// Dense length of the model's coordinate space.
+    pub len: usize,
+    /// Strictly increasing coordinate indices.
+    pub indices: Vec<u32>,
+    /// Quantized values; the real value is `values[i] as f32 * scale`.
+    pub values: Vec<i8>,
+    pub scale: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeltaHeader {
+    provenance: Provenance,
+    len: usize,
+    nnz: usize,
+    scale: f32,
+}
+
+const MAGIC: &[u8; 4] = b"X4FD";
+const VERSION: u8 = 1;
+
+impl SparseDelta {
+    /// Keep the `top_k` fraction of coordinates with the largest magnitude and
+    /// quantize them symmetrically to int8.
+    pub fn compress(delta: &[f32], top_k: f32, provenance: Provenance) -> Self {
+        let mut idx: Vec<u32> = (0..delta.len() as u32)
+            .filter(|&i| delta[i as usize] != 0.0 && delta[i as usize].is_finite())
+            .collect();
+        let k = ((delta.len() as f32 * top_k).ceil() as usize).max(1);
+        if idx.len() > k {
+            idx.select_nth_unstable_by(k - 1, |&a, &b| {
+                delta[b as usize].abs().total_cmp(&delta[a as usize].abs())
+            });
+            idx.truncate(k);
+        }
+        idx.sort_unstable();
+
+        let max = idx
+            .iter()
+            .map(|&i| delta[i as usize].abs())
+            .fold(0.0f32, f32::max);
+        let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+        let values = idx
+            .iter()
+            .map(|&i| (delta[i as usize] / scale).round().clamp(-127.0, 127.0) as i8)
+            .collect();
+        Self {
+            provenance,
+            len: delta.len(),
+            indices: idx,
+            values,
+            scale,
+        }
+    }
+
+    /// Number of transmitted coordinates.
+    pub fn nnz(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Dequantized `(index, value)` pairs.
+    pub fn entries(&self) -> Vec<(u32, f32)> {
+        self.indices
+            .iter()
+            .zip(&self.values)
+            .map(|(&i, &v)| (i, v as f32 * self.scale))
+            .collect()
+    }
+
+    /// Dense dequantized delta.
+    pub fn densify(&self) -> Vec<f32> {
+        let mut dense = vec![0.0; self.len];
+        for (i, v) in self.entries() {
+            dense[i as usize] = v;
+        }
+        dense
+    }
+
+    /// Bytes covered by the signature: the encoded delta with the signature
+    /// field cleared.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let mut unsigned = self.clone();
+        unsigned.provenance.signature = None;
+        unsigned.to_bytes()
+    }
+
+    /// Wire format: magic, version, JSON header length (u32 LE), JSON header,
+    /// LEB128 index gaps, int8 values.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = serde_json::to_vec(&DeltaHeader {
+            provenance: self.provenance.clone(),
+            len: self.len,
+            nnz: self.nnz(),
+            scale: self.scale,
+        })
+        .unwrap_or_default();
+        let mut out = Vec::with_capacity(9 + header.len() + self.nnz() * 3);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        out.extend_from_slice(&header);
+        let mut prev = 0u32;
+        for (n, &i) in self.indices.iter().enumerate() {
+            let gap = if n == 0 { i } else { i - prev };
+            write_varint(&mut out, gap);
+            prev = i;
+        }
+        out.extend(self.values.iter().map(|&v| v as u8));
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FederatedError> {
+        if bytes.len() < 9 || &bytes[..4] != MAGIC {
+            return Err(FederatedError::BadMagic);
+        }
+        if bytes[4] != VERSION {
+            return Err(FederatedError::UnsupportedVersion(bytes[4]));
+        }
+        let header_len = u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]) as usize;
+        let body = &bytes[9..];
+        if body.len() < header_len {
+            return Err(FederatedError::Truncated);
+        }
+        let header: DeltaHeader = serde_json::from_slice(&body[..header_len])
+            .map_err(|e| FederatedError::Malformed(e.to_string()))?;
+        if !header.scale.is_finite() || header.scale <= 0.0 {
+            return Err(FederatedError::Malformed(format!("scale {}", header.scale)));
+        }
+        // Every entry takes at least two bytes, so this also bounds the
+        // allocations below by the input size.
+        if header.nnz.saturating_mul(2) > body.len() - header_len {
+            return Err(FederatedError::Truncated);
+        }
+        if header.nnz > header.len {
+            return Err(FederatedError::Malformed(format!(
+                "{} entries for {} coordinates",
+                header.nnz, header.len
+            )));
+        }
+
+        let mut pos = header_len;
+        let mut indices = Vec::with_capacity(header.nnz);
+        let mut prev = 0u32;
+        for n in 0..header.nnz {
+            let gap = read_varint(body, &mut pos)?;
+            if n > 0 && gap == 0 {
+                return Err(FederatedError::Malformed("indices not increasing".into()));
+            }
+            let i = prev
+                .checked_add(gap)
+                .filter(|&i| (i as usize) < header.len)
+                .ok_or_else(|| FederatedError::Malformed("index out of range".into()))?;
+            indices.push(i);
+            prev = i;
+        }
+        let values = body
+            .get(pos..pos + header.nnz)
+            .ok_or(FederatedError::Truncated)?
+            .iter()
+            .map(|&b| b as i8)
+            .collect();
+        Ok(Self {
+            provenance: header.provenance,
+            len: header.len,
+            indices,
+            values,
+            scale: header.scale,
+        })
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u32, FederatedError> {
+    let mut v = 0u32;
+    for shift in (0..35).step_by(7) {
+        let b = *bytes.get(*pos).ok_or(FederatedError::Truncated)?;
+        *pos += 1;
+        v |= ((b & 0x7f) as u32)
+            .checked_shl(shift)
+            .ok_or_else(|| FederatedError::Malformed("varint overflow".into()))?;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(FederatedError::Malformed("varint too long".into()))
+}
+
+/// The weights a publisher's next delta is measured from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaBase {
+    pub arch: String,
+    pub train_steps: u64,
+    pub weights: Vec<f32>,
+}
+
+impl DeltaBase {
+    pub fn of<M: Federated + ?Sized>(model: &M) -> Self {
+        Self {
+            arch: model.arch().to_string(),
+            train_steps: model.train_steps(),
+            weights: model.flat_weights(),
+        }
+    }
+
+    /// Cut a delta of `model`'s progress since this base and advance the base
+    /// by what was sent. Returns `None` (after resetting the base) if the
+    /// model no longer matches, or if it has not trained since.
+    pub fn publish<M: Federated + ?Sized>(
+        &mut self,
+        model: &M,
+        peer_id: &str,
+        top_k: f32,
+    ) -> Option<SparseDelta> {
+        let current = model.flat_weights();
+        if self.arch != model.arch()
+            || self.weights.len() != current.len()
+            || model.train_steps() < self.train_steps
+        {
+            *self = Self::of(model);
+            return None;
+        }
+        if model.train_steps() == self.train_steps {
+            return None;
+        }
+        let diff: Vec<f32> = current
+            .iter()
+            .zip(&self.weights)
+            .map(|(c, b)| c - b)
+            .collect();
+        let delta = SparseDelta::compress(
+            &diff,
+            top_k,
+            Provenance {
+                peer_id: peer_id.to_string(),
+                arch: self.arch.clone(),
+                base_steps: self.train_steps,
+                train_steps: model.train_steps(),
+                signature: None,
+            },
+        );
+        for (i, v) in delta.entries() {
+            self.weights[i as usize] += v;
+        }
+        self.train_steps = model.train_steps();
+        Some(delta)
+    }
+}
+
+impl Checkpointable for DeltaBase {
+    const ARCH: &'static str = "federated_base";
+
+    fn hyperparams() -> Vec<(&'static str, u64)> {
+        Vec::new()
+    }
+
+    fn write_tensors(&self, w: &mut CheckpointWriter) {
+        w.meta("model", self.arch.as_str())
+            .meta("train_steps", self.train_steps);
+        w.tensor("weights", &[self.weights.len()], &self.weights);
+    }
+
+    fn read_tensors(c: &Checkpoint<'_>) -> Result<Self, CheckpointError> {
+        let len = c
+            .info("weights")
+            .and_then(|i| i.numel())
+            .ok_or_else(|| CheckpointError::MissingTensor("weights".to_string()))?;
+        Ok(Self {
+            arch: c.meta_str("model").unwrap_or_default().to_string(),
+            train_steps: c.meta_u64("train_steps"),
+            weights: c.tensor_vec("weights", &[len])?,
+        })
+    }
+
+    fn from_legacy_json(_json: &str) -> Option<Self> {
+        None
+    }
+}
+
+/// How accepted peer deltas are combined per coordinate. Peers that did not
+/// send a coordinate count as a zero vote for it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Aggregation {
+    Median,
+    /// Drop the `trim` fraction of the lowest and highest values, average the
+    /// rest.
+    TrimmedMean {
+        trim: f32,
+    },
+}
+
+/// Combine deltas coordinate-wise. Returns sorted `(index, value)` pairs for
+/// every coordinate at least one delta touched.
+pub fn aggregate(deltas: &[&SparseDelta], rule: Aggregation) -> Vec<(u32, f32)> {
+    let n = deltas.len();
+    let mut coords: BTreeMap<u32, Vec<f32>> = BTreeMap::new();
+    for d in deltas {
+        for (i, v) in d.entries() {
+            coords.entry(i).or_default().push(v);
+        }
+    }
+    coords
+        .into_iter()
+        .map(|(i, mut vals)| {
+            vals.resize(n, 0.0);
+            vals.sort_unstable_by(f32::total_cmp);
+            let v = match rule {
+                Aggregation::Median if n % 2 == 1 => vals[n / 2],
+                Aggregation::Median => 0.5 * (vals[n / 2 - 1] + vals[n / 2]),
+                Aggregation::TrimmedMean { trim } => {
+                    let cut =
+                        ((n as f32 * trim.clamp(0.0, 0.49)).floor() as usize).min((n - 1) / 2);
+                    let kept = &vals[cut..n - cut];
+                    kept.iter().sum::<f32>() / kept.len() as f32
+                }
+            };
+            (i, v)
+        })
+        .filter(|&(_, v)| v != 0.0)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FederatedConfig {
+    pub aggregation: Aggregation,
+    /// Fraction of the aggregated delta added to the local weights.
+    pub merge_rate: f32,
+    /// Reject an update that raises validation loss by more than this
+    /// fraction of the baseline.
+    pub max_loss_increase: f32,
+    /// Ignore deltas from peers with fewer train steps than this.
+    pub min_peer_steps: u64,
+}
+
+impl Default for FederatedConfig {
+    fn default() -> Self {
+        Self {
+            aggregation: Aggregation::Median,
+            merge_rate: 0.3,
+            max_loss_increase: 0.02,
+            min_peer_steps: 50,
+        }
+    }
+}
+
+/// Why a peer's delta was not used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum Rejection {
+    ArchMismatch { arch: String },
+    ShapeMismatch { len: usize },
+    Undertrained { train_steps: u64 },
+    BadSignature,
+    Duplicate,
+    DegradesLoss { baseline: f32, loss: f32 },
+}
+
+/// Outcome of a [`merge`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub accepted: Vec<String>,
+    pub rejected: Vec<(String, Rejection)>,
+    pub baseline_loss: f32,
+    /// Validation loss with the aggregated update applied.
+    pub merged_loss: Option<f32>,
+    /// Coordinates the aggregated update touched.
+    pub coordinates: usize,
+    /// Whether the update was kept.
+    pub applied: bool,
+}
+
+/// Validate, aggregate and apply peer deltas to `model`.
+///
+/// `verify` checks a delta's signature; `eval` returns a validation loss
+/// (lower is better) for the model's current weights.
+pub fn merge<M: Federated + ?Sized>(
+    model: &mut M,
+    deltas: &[SparseDelta],
+    config: &FederatedConfig,
+    verify: impl Fn(&SparseDelta) -> bool,
+    eval: impl Fn(&M) -> f32,
+) -> MergeReport {
+    let len = model.param_len();
+    let baseline = eval(model);
+    let tolerance = |loss: f32| {
+        loss.is_finite() && loss <= baseline + baseline.abs() * config.max_loss_increase + 1e-6
+    };
+    let mut report = MergeReport {
+        baseline_loss: baseline,
+        ..Default::default()
+    };
+
+    let mut accepted: Vec<&SparseDelta> = Vec::new();
+    for d in deltas {
+        let p = &d.provenance;
+        let rejection = if p.arch != model.arch() {
+            Some(Rejection::ArchMismatch {
+                arch: p.arch.clone(),
+            })
+        } else if d.len != len || d.indices.iter().any(|&i| i as usize >= len) {
+            Some(Rejection::ShapeMismatch { len: d.len })
+        } else if p.train_steps < config.min_peer_steps {
+            Some(Rejection::Undertrained {
+                train_steps: p.train_steps,
+            })
+        } else if !verify(d) {
+            Some(Rejection::BadSignature)
+        } else if accepted.iter().any(|a| a.provenance.peer_id == p.peer_id) {
+            Some(Rejection::Duplicate)
+        } else {
+            let entries = d.entries();
+            let saved = apply(model, &entries, config.merge_rate);
+            let loss = eval(model);
+            restore(model, &entries, &saved);
+            (!tolerance(loss)).then_some(Rejection::DegradesLoss { baseline, loss })
+        };
+        match rejection {
+            Some(r) => report.rejected.push((p.peer_id.clone(), r)),
+            None => {
+                report.accepted.push(p.peer_id.clone());
+                accepted.push(d);
+            }
+        }
+    }
+    if accepted.is_empty() {
+        return report;
+    }
+
+    let update = aggregate(&accepted, config.aggregation);
+    let saved = apply(model, &update, config.merge_rate);
+    let loss = eval(model);
+    report.merged_loss = Some(loss);
+    report.coordinates = update.len();
+    report.applied = tolerance(loss);
+    if !report.applied {
+        restore(model, &update, &saved);
+    }
+    report
+}
+
+/// Visit the weights at sorted flat `indices`.
+fn for_each_coord<M: Federated + ?Sized>(
+    model: &mut M,
+    indices: impl Iterator<Item = u32>,
+    mut f: impl FnMut(usize, &mut f32),
+) {
+    let mut tensors = model.tensors_mut().into_iter();
+    let mut current = tensors.next();
+    let mut base = 0usize;
+    for (n, i) in indices.enumerate() {
+        let i = i as usize;
+        while let Some(t) = &current {
+            if i < base + t.len() {
+                break;
+            }
+            base += t.len();
+            current = tensors.next();
+        }
+        let Some(t) = current.as_mut() else {
+            return;
+        };
+        f(n, &mut t[i - base]);
+    }
+}
+
+/// Add `rate * value` at each entry; returns the previous weights.
+fn apply<M: Federated + ?Sized>(model: &mut M, entries: &[(u32, f32)], rate: f32) -> Vec<f32> {
+    let mut saved = Vec::with_capacity(entries.len());
+    for_each_coord(model, entries.iter().map(|e| e.0), |n, w| {
+        saved.push(*w);
+        *w += rate * entries[n].1;
+    });
+    saved
+}
+
+fn restore<M: Federated + ?Sized>(model: &mut M, entries: &[(u32, f32)], saved: &[f32]) {
+    for_each_coord(model, entries.iter().map(|e| e.0), |n, w| *w = saved[n]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two-tensor model whose loss is the squared distance to `TARGET`.
+    struct Toy {
+        a: Vec<f32>,
+        b: Vec<f32>,
+        steps: u64,
+    }
+
+    const TARGET: [f32; 6] = [1.0, -1.0, 0.5, 2.0, 0.0, -0.5];
+
+    impl Federated for Toy {
+        fn arch(&self) -> &'static str {
+            "toy"
+        }
+        fn train_steps(&self) -> u64 {
+            self.steps
+        }
+        fn tensors(&self) -> Vec<&[f32]> {
+            vec![&self.a, &self.b]
+        }
+        fn tensors_mut(&mut self) -> Vec<&mut [f32]> {
+            vec![&mut self.a, &mut self.b]
+        }
+    }
+
+    fn toy() -> Toy {
+        Toy {
+            a: vec![0.0; 4],
+            b: vec![0.0; 2],
+            steps: 0,
+        }
+    }
+
+    fn loss(m: &Toy) -> f32 {
+        m.flat_weights()
+            .iter()
+            .zip(TARGET)
+            .map(|(w, t)| (w - t) * (w - t))
+            .sum()
+    }
+
+    fn delta(peer: &str, values: &[f32]) -> SparseDelta {
+        SparseDelta::compress(
+            values,
+            1.0,
+            Provenance {
+                peer_id: peer.to_string(),
+                arch: "toy".to_string(),
+                base_steps: 0,
+                train_steps: 100,
+                signature: None,
+            },
+        )
+    }
+
+    #[test]
+    fn compress_keeps_top_k_and_roundtrips() {
+        let values = [0.01, -3.0, 0.5, 0.0, 2.0, -0.02, 0.7, 0.0];
+        let d = SparseDelta::compress(&values, 0.375, delta("p", &[]).provenance);
+        assert_eq!(d.indices, vec![1, 4, 6]);
+        for (i, v) in d.entries() {
+            assert!((v - values[i as usize]).abs() <= d.scale / 2.0 + 1e-6);
+        }
+
+        let bytes = d.to_bytes();
+        assert_eq!(SparseDelta::from_bytes(&bytes).unwrap(), d);
+        assert!(matches!(
+            SparseDelta::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(FederatedError::Truncated)
+        ));
+        assert!(matches!(
+            SparseDelta::from_bytes(b"nope\x01\0\0\0\0"),
+            Err(FederatedError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn median_and_trimmed_mean_ignore_an_outlier() {
+        let honest = [0.1, -0.1, 0.05, 0.2, 0.0, -0.05];
+        let peers: Vec<SparseDelta> = (0..3)
+            .map(|n| delta(&format!("h{n}"), &honest))
+            .chain(std::iter::once(delta("evil", &[100.0; 6])))
+            .collect();
+        let refs: Vec<&SparseDelta> = peers.iter().collect();
+        for rule in [Aggregation::Median, Aggregation::TrimmedMean { trim: 0.25 }] {
+            for (i, v) in aggregate(&refs, rule) {
+                assert!(
+                    (v - honest[i as usize]).abs() < 0.01,
+                    "{rule:?}: {i} -> {v}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn merge_rejects_poisoned_forged_and_foreign_deltas() {
+        let mut m = toy();
+        let good = TARGET.map(|t| t * 0.5);
+        let mut foreign = delta("other-arch", &good);
+        foreign.provenance.arch = "unified".into();
+        let mut forged = delta("forged", &good);
+        forged.provenance.signature = Some("bad".into());
+        let deltas = vec![
+            delta("a", &good),
+            delta("b", &good),
+            delta("poison", &TARGET.map(|t| -10.0 * t)),
+            foreign,
+            forged,
+        ];
+
+        let config = FederatedConfig {
+            merge_rate: 1.0,
+            ..Default::default()
+        };
+        let before = loss(&m);
+        let report = merge(
+            &mut m,
+            &deltas,
+            &config,
+            |d| d.provenance.signature.is_none(),
+            loss,
+        );
+        assert_eq!(report.accepted, vec!["a", "b"]);
+        let reasons: Vec<_> = report.rejected.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(reasons, vec!["poison", "other-arch", "forged"]);
+        assert!(matches!(
+            report.rejected[0].1,
+            Rejection::DegradesLoss { .. }
+        ));
+        assert!(report.applied);
+        assert!(loss(&m) < before * 0.3, "{} -> {}", before, loss(&m));
+    }
+
+    #[test]
+    fn delta_base_carries_dropped_coordinates_forward() {
+        let mut m = toy();
+        let mut base = DeltaBase::of(&m);
+        let mut received = toy();
+        m.a.copy_from_slice(&TARGET[..4]);
+        m.b.copy_from_slice(&TARGET[4..]);
+        m.steps = 10;
+
+        // One coordinate per delta: progress arrives over several rounds.
+        for round in 0..5 {
+            m.steps += 1;
+            let Some(d) = base.publish(&m, "p", 1.0 / 6.0) else {
+                panic!("round {round} published nothing");
+            };
+            assert!(d.nnz() <= 1);
+            apply(&mut received, &d.entries(), 1.0);
+        }
+        assert!(loss(&received) < 1e-3, "residual {}", loss(&received));
+
+        let restored = DeltaBase::load_bytes(&base.to_checkpoint()).unwrap();
+        assert_eq!(restored, base);
+    }
+
+    #[test]
+    fn model_coordinate_spaces_match_their_weight_lists() {
+        let plan = crate::PlanTransformer::new();
+        assert_eq!(plan.flat_weights(), plan.flatten_weights());
+
+        let mut codegen = crate::codegen::CodeGenModel::new();
+        let lens: Vec<usize> = codegen.tensors().iter().map(|t| t.len()).collect();
+        let named: Vec<usize> = codegen
+            .named_params_mut()
+            .iter()
+            .map(|(_, t)| t.len())
+            .collect();
+        assert_eq!(lens, named);
+        assert_eq!(codegen.arch(), "codegen");
+    }
+}
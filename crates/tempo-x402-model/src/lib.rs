@@ -19,24 +19,49 @@
 //!
 //! ## Design
 //!
-//! - Serializable for federated weight sharing between colony peers
-//! - Online SGD training (no batch jobs, no GPU)
-//! - Dimension validation on deserialization (safe scaling)
+//! - Serializable for federated weight sharing between colony peers; robust
+//!   aggregation of sparse int8 deltas with validation gating (`federated`)
+//! - Online SGD training (no batch jobs, no GPU) on a reverse-mode autodiff
+//!   tape (`autograd`) — no hand-written gradients
+//! - AdamW / SGD-momentum with warmup+cosine schedules, global-norm clipping,
+//!   gradient accumulation and checkpointed optimizer state (`optim`)
+//! - Int8/f16 post-training quantization for inference (`quantize`)
+//! - KV-cached incremental decoding with greedy/top-k/top-p/beam search (`decoding`)
+//! - Binary checkpoints (`checkpoint`): versioned header, CRC32, mmap-able;
+//!   architecture validated on load, legacy JSON still read
+//! - Runtime `ModelConfig` for the unified model, stored in its checkpoint;
+//!   models can be grown (deeper, wider) in place (`unified`)
+//! - Shared cache-blocked, rayon-parallel GEMM/GEMV on 8-lane SIMD (`linalg`),
+//!   used by every model's forward pass and by the autodiff tape
 //! - Xavier initialization via deterministic LCG PRNG
 
+pub mod autograd;
 pub mod bpe;
+pub mod checkpoint;
 pub mod codegen;
+pub mod decoding;
 pub mod diff_features;
+pub mod federated;
 pub mod inference;
+mod linalg;
+pub mod optim;
 pub mod quality;
+pub mod quantize;
 pub mod trainer;
 pub mod transformer;
 pub mod unified;
 pub mod vocab;
 
+pub use checkpoint::{CheckpointError, Checkpointable};
+pub use decoding::{Candidate, DecodeConfig, FinishReason, IncrementalDecoder, KvCache, Strategy};
 pub use diff_features::DiffFeatures;
+pub use federated::{
+    Aggregation, DeltaBase, Federated, FederatedConfig, MergeReport, Provenance, SparseDelta,
+};
 pub use inference::generate_plan;
+pub use optim::{Gradients, Optimizer, OptimizerConfig, OptimizerKind, Schedule, StepStats};
 pub use quality::{CodeQualityModel, QualityExample, QualityPrediction};
+pub use quantize::{QuantMode, QuantReport, QuantizedCodeGenModel, QuantizedUnifiedModel};
 pub use trainer::{train_batch, TrainingExample};
 pub use transformer::{PlanTransformer, TransformerDelta};
 pub use vocab::Vocab;
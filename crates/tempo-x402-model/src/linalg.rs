@@ -0,0 +1,353 @@
+//! Linear-algebra kernels shared by every model in this crate.
+//!
+//! Weights are row-major in one of two layouts:
+//!
+//! - `[out x in]` (code-gen, unified, decoding, autograd `linear`): an output
+//!   is a dot product with a weight row → [`gemv`], [`matmul_nt`].
+//! - `[in x out]` (plan transformer, quality model): an input scales a
+//!   weight row into the output → [`gemv_t`], [`matmul_nn`].
+//!
+//! Inner loops run on 8-lane [`f32x8`] vectors (SSE/AVX on x86, NEON on ARM,
+//! scalar elsewhere). Products are tiled so a block of weight rows stays in
+//! L2 while every input row streams past it, and tiles are spread across
+//! rayon once a product is big enough to pay for the fork.
+
+use rayon::prelude::*;
+use wide::f32x8;
+
+use crate::quantize::Dims;
+
+/// Products with at least this many multiply-adds run in parallel.
+pub(crate) const PAR_THRESHOLD: usize = 1 << 18;
+
+/// Floats of weights per tile — half of a typical 256 KiB L2.
+const TILE_FLOATS: usize = 32 * 1024;
+/// Input rows per tile.
+const ROW_TILE: usize = 16;
+
+#[inline(always)]
+fn load(s: &[f32]) -> f32x8 {
+    f32x8::new(s[..8].try_into().expect("8 lanes"))
+}
+
+/// Dot product of two equal-length vectors.
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len().min(b.len());
+    let (a, b) = (&a[..n], &b[..n]);
+    let mut acc0 = f32x8::ZERO;
+    let mut acc1 = f32x8::ZERO;
+    let mut xs = a.chunks_exact(16);
+    let mut ys = b.chunks_exact(16);
+    for (x, y) in (&mut xs).zip(&mut ys) {
+        acc0 += load(x) * load(y);
+        acc1 += load(&x[8..]) * load(&y[8..]);
+    }
+    let mut sum = (acc0 + acc1).reduce_add();
+    for (x, y) in xs.remainder().iter().zip(ys.remainder()) {
+        sum += x * y;
+    }
+    sum
+}
+
+/// `a` dotted with four rows of `b` (row length `a.len()`), sharing the loads of `a`.
+#[inline]
+fn dot4(a: &[f32], b: [&[f32]; 4]) -> [f32; 4] {
+    let k = a.len();
+    let body = k - k % 8;
+    let mut acc = [f32x8::ZERO; 4];
+    for p in (0..body).step_by(8) {
+        let x = load(&a[p..]);
+        for (acc, row) in acc.iter_mut().zip(&b) {
+            *acc += x * load(&row[p..]);
+        }
+    }
+    let mut out = acc.map(|v| v.reduce_add());
+    for p in body..k {
+        for (o, row) in out.iter_mut().zip(&b) {
+            *o += a[p] * row[p];
+        }
+    }
+    out
+}
+
+/// `y += alpha * x`
+pub(crate) fn axpy(y: &mut [f32], alpha: f32, x: &[f32]) {
+    let n = y.len().min(x.len());
+    let (y, x) = (&mut y[..n], &x[..n]);
+    let a = f32x8::splat(alpha);
+    let mut ys = y.chunks_exact_mut(8);
+    let mut xs = x.chunks_exact(8);
+    for (y, x) in (&mut ys).zip(&mut xs) {
+        let v = load(y) + a * load(x);
+        y.copy_from_slice(v.as_array_ref());
+    }
+    for (y, x) in ys.into_remainder().iter_mut().zip(xs.remainder()) {
+        *y += alpha * x;
+    }
+}
+
+/// `a += b`
+pub(crate) fn add_assign(a: &mut [f32], b: &[f32]) {
+    axpy(a, 1.0, b);
+}
+
+/// `y = W · x`: `w` is `[rows x cols]`, `x` has `cols` elements.
+pub(crate) fn gemv(w: &[f32], x: &[f32], rows: usize, cols: usize) -> Vec<f32> {
+    matmul_nt(x, w, 1, cols, rows)
+}
+
+/// `y = Wᵀ · x`: `w` is `[rows x cols]`, `x` has `rows` elements.
+pub(crate) fn gemv_t(w: &[f32], x: &[f32], rows: usize, cols: usize) -> Vec<f32> {
+    matmul_nn(x, w, 1, rows, cols)
+}
+
+/// `a · bᵀ`: `a` is `[n x k]`, `b` is `[m x k]`; returns `[n x m]`.
+pub(crate) fn matmul_nt(a: &[f32], b: &[f32], n: usize, k: usize, m: usize) -> Vec<f32> {
+    // Each tile is up to ROW_TILE rows of `a` against a block of `b` rows
+    // that fits in L2; the block is read from memory once per tile.
+    let block = (TILE_FLOATS / k.max(1)).clamp(4, 256) / 4 * 4;
+    tiled(n, m, n * k * m, block, |rows, cols, out| {
+        let w = cols.len();
+        for (i, row) in rows.clone().zip(out.chunks_mut(w)) {
+            let ai = &a[i * k..(i + 1) * k];
+            let mut quads = row.chunks_exact_mut(4);
+            let mut j = cols.start;
+            for o in &mut quads {
+                let r = |j: usize| &b[j * k..(j + 1) * k];
+                o.copy_from_slice(&dot4(ai, [r(j), r(j + 1), r(j + 2), r(j + 3)]));
+                j += 4;
+            }
+            for o in quads.into_remainder() {
+                *o = dot(ai, &b[j * k..(j + 1) * k]);
+                j += 1;
+            }
+        }
+    })
+}
+
+/// `a · b`: `a` is `[n x k]`, `b` is `[k x m]`; returns `[n x m]`.
+pub(crate) fn matmul_nn(a: &[f32], b: &[f32], n: usize, k: usize, m: usize) -> Vec<f32> {
+    // Each tile owns a band of output columns, so only that band of every
+    // `b` row is touched; zero activations (post-ReLU) are skipped.
+    let block = (TILE_FLOATS / k.max(1)).clamp(8, 1024) / 8 * 8;
+    tiled(n, m, n * k * m, block, |rows, cols, out| {
+        let w = cols.len();
+        for (i, row) in rows.clone().zip(out.chunks_mut(w)) {
+            for p in 0..k {
+                let a_ip = a[i * k + p];
+                if a_ip != 0.0 {
+                    axpy(row, a_ip, &b[p * m + cols.start..p * m + cols.end]);
+                }
+            }
+        }
+    })
+}
+
+/// `aᵀ · b`: `a` is `[n x k]`, `b` is `[n x m]`; returns `[k x m]`.
+pub(crate) fn matmul_tn(a: &[f32], b: &[f32], n: usize, k: usize, m: usize) -> Vec<f32> {
+    let mut out = vec![0.0f32; k * m];
+    if m == 0 {
+        return out;
+    }
+    let band = |(t, chunk): (usize, &mut [f32])| {
+        for (r, row) in chunk.chunks_mut(m).enumerate() {
+            let p = t * ROW_TILE + r;
+            for i in 0..n {
+                let a_ip = a[i * k + p];
+                if a_ip != 0.0 {
+                    axpy(row, a_ip, &b[i * m..(i + 1) * m]);
+                }
+            }
+        }
+    };
+    if n * k * m >= PAR_THRESHOLD {
+        out.par_chunks_mut(ROW_TILE * m).enumerate().for_each(band);
+    } else {
+        out.chunks_mut(ROW_TILE * m).enumerate().for_each(band);
+    }
+    out
+}
+
+/// Run `kernel` over `[ROW_TILE x block]` tiles of an `[n x m]` output and
+/// assemble the result. `kernel` fills a tile given its row and column range.
+fn tiled<F>(n: usize, m: usize, work: usize, block: usize, kernel: F) -> Vec<f32>
+where
+    F: Fn(std::ops::Range<usize>, std::ops::Range<usize>, &mut [f32]) + Sync,
+{
+    let mut out = vec![0.0f32; n * m];
+    if n == 0 || m == 0 {
+        return out;
+    }
+    let row_tiles = n.div_ceil(ROW_TILE);
+    let col_tiles = m.div_ceil(block);
+    let bounds = |t: usize| {
+        let (ti, tj) = (t / col_tiles, t % col_tiles);
+        (
+            ti * ROW_TILE..((ti + 1) * ROW_TILE).min(n),
+            tj * block..((tj + 1) * block).min(m),
+        )
+    };
+
+    if col_tiles == 1 {
+        // Tiles are whole output rows: write in place.
+        let fill = |(ti, chunk): (usize, &mut [f32])| kernel(bounds(ti).0, 0..m, chunk);
+        if work >= PAR_THRESHOLD {
+            out.par_chunks_mut(ROW_TILE * m).enumerate().for_each(fill);
+        } else {
+            out.chunks_mut(ROW_TILE * m).enumerate().for_each(fill);
+        }
+        return out;
+    }
+
+    let compute = |t: usize| {
+        let (rows, cols) = bounds(t);
+        let mut tile = vec![0.0f32; rows.len() * cols.len()];
+        kernel(rows, cols, &mut tile);
+        tile
+    };
+    let tiles: Vec<Vec<f32>> = if work >= PAR_THRESHOLD {
+        (0..row_tiles * col_tiles).into_par_iter().map(compute).collect()
+    } else {
+        (0..row_tiles * col_tiles).map(compute).collect()
+    };
+    for (t, tile) in tiles.iter().enumerate() {
+        let (rows, cols) = bounds(t);
+        for (i, src) in rows.zip(tile.chunks(cols.len())) {
+            out[i * m + cols.start..i * m + cols.end].copy_from_slice(src);
+        }
+    }
+    out
+}
+
+/// Multi-head scaled dot-product attention over pre-projected Q/K/V.
+/// `q` is `[q_len x d]`, `k`/`v` are `[kv_len x d]`; returns `[q_len x d]`.
+/// With `causal`, query `pos` sees keys `0..=pos`.
+pub(crate) fn attend(
+    q: &[f32],
+    q_len: usize,
+    k: &[f32],
+    v: &[f32],
+    kv_len: usize,
+    dims: Dims,
+    causal: bool,
+) -> Vec<f32> {
+    let Dims {
+        d, n_heads, d_head, ..
+    } = dims;
+    let inv_sqrt = 1.0 / (d_head as f32).sqrt();
+    let row = |pos: usize| {
+        let span = if causal {
+            (pos + 1).min(kv_len)
+        } else {
+            kv_len
+        };
+        let mut out = vec![0.0f32; d];
+        let mut weights = vec![0.0f32; span];
+        for h in 0..n_heads {
+            let off = h * d_head;
+            let qh = &q[pos * d + off..pos * d + off + d_head];
+            for (t, w) in weights.iter_mut().enumerate() {
+                *w = dot(qh, &k[t * d + off..t * d + off + d_head]) * inv_sqrt;
+            }
+            let max_w = weights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            let mut sum = 0.0;
+            for w in &mut weights {
+                *w = (*w - max_w).exp();
+                sum += *w;
+            }
+            let out_h = &mut out[off..off + d_head];
+            for (t, w) in weights.iter().enumerate() {
+                axpy(out_h, w / sum, &v[t * d + off..t * d + off + d_head]);
+            }
+        }
+        out
+    };
+    if q_len * kv_len * d >= PAR_THRESHOLD / 4 {
+        (0..q_len).into_par_iter().flat_map_iter(row).collect()
+    } else {
+        (0..q_len).flat_map(row).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seq(n: usize, seed: u32) -> Vec<f32> {
+        (0..n as u32)
+            .map(|i| ((i.wrapping_mul(2_654_435_761) ^ seed) % 1000) as f32 / 500.0 - 1.0)
+            .collect()
+    }
+
+    fn close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (i, (x, y)) in a.iter().zip(b).enumerate() {
+            assert!((x - y).abs() <= 1e-4 * (1.0 + y.abs()), "[{i}] {x} vs {y}");
+        }
+    }
+
+    #[test]
+    fn products_match_naive_loops() {
+        // Odd sizes exercise the lane tails, the 4-row remainder and
+        // multi-tile assembly; the large case runs the parallel path.
+        for &(n, k, m) in &[(1, 3, 5), (7, 33, 70), (17, 100, 300), (40, 384, 390)] {
+            let a = seq(n * k, 1);
+            let b_nt = seq(m * k, 2);
+            let b_nn = seq(k * m, 3);
+            let b_tn = seq(n * m, 4);
+
+            let nt: Vec<f32> = (0..n * m)
+                .map(|o| (0..k).map(|p| a[o / m * k + p] * b_nt[o % m * k + p]).sum())
+                .collect();
+            let nn: Vec<f32> = (0..n * m)
+                .map(|o| (0..k).map(|p| a[o / m * k + p] * b_nn[p * m + o % m]).sum())
+                .collect();
+            let tn: Vec<f32> = (0..k * m)
+                .map(|o| (0..n).map(|i| a[i * k + o / m] * b_tn[i * m + o % m]).sum())
+                .collect();
+
+            close(&matmul_nt(&a, &b_nt, n, k, m), &nt);
+            close(&matmul_nn(&a, &b_nn, n, k, m), &nn);
+            close(&matmul_tn(&a, &b_tn, n, k, m), &tn);
+        }
+    }
+
+    #[test]
+    fn gemv_layouts() {
+        let (rows, cols) = (37, 19);
+        let w = seq(rows * cols, 5);
+        let x = seq(cols, 6);
+        let xt = seq(rows, 7);
+        let want: Vec<f32> = (0..rows)
+            .map(|r| (0..cols).map(|c| w[r * cols + c] * x[c]).sum())
+            .collect();
+        let want_t: Vec<f32> = (0..cols)
+            .map(|c| (0..rows).map(|r| w[r * cols + c] * xt[r]).sum())
+            .collect();
+        close(&gemv(&w, &x, rows, cols), &want);
+        close(&gemv_t(&w, &xt, rows, cols), &want_t);
+        assert!(gemv(&w, &x, 0, cols).is_empty());
+    }
+
+    #[test]
+    fn causal_attention_ignores_the_future() {
+        let (len, d) = (5, 8);
+        let q = seq(len * d, 8);
+        let k = seq(len * d, 9);
+        let mut v = seq(len * d, 10);
+        let dims = Dims {
+            d,
+            n_heads: 2,
+            d_head: 4,
+            ff: 0,
+        };
+        let before = attend(&q, len, &k, &v, len, dims, true);
+        // Changing the last value row only moves the last position.
+        for x in &mut v[(len - 1) * d..] {
+            *x += 1.0;
+        }
+        let after = attend(&q, len, &k, &v, len, dims, true);
+        close(&after[..(len - 1) * d], &before[..(len - 1) * d]);
+        assert_ne!(after[(len - 1) * d..], before[(len - 1) * d..]);
+    }
+}
@@ -0,0 +1,504 @@
+//! Optimizers for the tape-trained models.
+//!
+//! A training step accumulates per-example gradients into [`Gradients`]
+//! (keyed by checkpoint tensor name), then an [`Optimizer`] applies their
+//! mean: global-norm clipping, a [`Schedule`]d learning rate, and SGD with
+//! momentum or AdamW (decoupled weight decay on matrices only).
+//!
+//! Optimizer moments and the step counter are [`Checkpointable`], so they are
+//! saved next to the model checkpoint and training resumes exactly where it
+//! stopped.
+
+use std::collections::BTreeMap;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::{Checkpoint, CheckpointError, CheckpointWriter, Checkpointable};
+
+/// Summed gradients of a mini-batch, keyed by parameter name.
+#[derive(Debug, Clone, Default)]
+pub struct Gradients {
+    grads: BTreeMap<String, Vec<f32>>,
+    examples: usize,
+    loss_sum: f32,
+}
+
+impl Gradients {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one example's gradient for `name`.
+    pub fn add(&mut self, name: &str, grad: &[f32]) {
+        match self.grads.get_mut(name) {
+            Some(acc) => {
+                for (a, g) in acc.iter_mut().zip(grad) {
+                    *a += g;
+                }
+            }
+            None => {
+                self.grads.insert(name.to_string(), grad.to_vec());
+            }
+        }
+    }
+
+    /// Mark one example as accumulated, with its loss.
+    pub fn record(&mut self, loss: f32) {
+        self.examples += 1;
+        self.loss_sum += loss;
+    }
+
+    /// Examples accumulated since the last `clear`.
+    pub fn examples(&self) -> usize {
+        self.examples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.examples == 0
+    }
+
+    pub fn mean_loss(&self) -> f32 {
+        self.loss_sum / self.examples.max(1) as f32
+    }
+
+    /// Summed gradient for `name`.
+    pub fn get(&self, name: &str) -> Option<&[f32]> {
+        self.grads.get(name).map(Vec::as_slice)
+    }
+
+    /// L2 norm of the mean gradient across all parameters.
+    pub fn global_norm(&self) -> f32 {
+        let sq: f64 = self
+            .grads
+            .values()
+            .map(|g| g.par_iter().map(|&x| (x as f64) * (x as f64)).sum::<f64>())
+            .sum();
+        sq.sqrt() as f32 / self.examples.max(1) as f32
+    }
+
+    pub fn clear(&mut self) {
+        self.grads.clear();
+        self.examples = 0;
+        self.loss_sum = 0.0;
+    }
+
+    /// Legacy update: plain SGD on the mean gradient with each element
+    /// clipped to [-1, 1].
+    pub fn sgd(&self, params: Vec<(String, &mut [f32])>, lr: f32) {
+        let scale = 1.0 / self.examples.max(1) as f32;
+        for (name, p) in params {
+            if let Some(g) = self.grads.get(&name) {
+                p.par_iter_mut()
+                    .zip(g.par_iter())
+                    .for_each(|(p, &g)| *p -= lr * (g * scale).clamp(-1.0, 1.0));
+            }
+        }
+    }
+}
+
+/// Learning rate as a function of the optimizer step.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Schedule {
+    Constant {
+        lr: f32,
+    },
+    /// Linear warmup to `peak_lr`, then cosine decay to `min_lr` over
+    /// `decay_steps`, constant at `min_lr` afterwards.
+    WarmupCosine {
+        peak_lr: f32,
+        min_lr: f32,
+        warmup_steps: u64,
+        decay_steps: u64,
+    },
+}
+
+impl Schedule {
+    pub fn lr(&self, step: u64) -> f32 {
+        match *self {
+            Schedule::Constant { lr } => lr,
+            Schedule::WarmupCosine {
+                peak_lr,
+                min_lr,
+                warmup_steps,
+                decay_steps,
+            } => {
+                if step < warmup_steps {
+                    return peak_lr * (step + 1) as f32 / warmup_steps as f32;
+                }
+                let progress = ((step - warmup_steps) as f32 / decay_steps.max(1) as f32).min(1.0);
+                min_lr + (peak_lr - min_lr) * 0.5 * (1.0 + (std::f32::consts::PI * progress).cos())
+            }
+        }
+    }
+}
+
+/// Update rule.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OptimizerKind {
+    Sgd {
+        momentum: f32,
+    },
+    AdamW {
+        beta1: f32,
+        beta2: f32,
+        eps: f32,
+        weight_decay: f32,
+    },
+}
+
+impl OptimizerKind {
+    /// AdamW with the usual defaults (β = 0.9/0.999, ε = 1e-8, decay 0.01).
+    pub fn adamw() -> Self {
+        OptimizerKind::AdamW {
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-8,
+            weight_decay: 0.01,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OptimizerConfig {
+    pub kind: OptimizerKind,
+    pub schedule: Schedule,
+    /// Rescale the mean gradient so its global L2 norm is at most this.
+    pub clip_norm: Option<f32>,
+}
+
+/// What one optimizer step did.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StepStats {
+    pub step: u64,
+    pub lr: f32,
+    /// Global norm of the mean gradient, before clipping.
+    pub grad_norm: f32,
+    pub clipped: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Moments {
+    m: Vec<f32>,
+    /// Second moment (AdamW only).
+    v: Vec<f32>,
+}
+
+/// Stateful optimizer. Moments are created lazily per parameter name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Optimizer {
+    pub config: OptimizerConfig,
+    step: u64,
+    moments: BTreeMap<String, Moments>,
+}
+
+impl Optimizer {
+    pub fn new(config: OptimizerConfig) -> Self {
+        Self {
+            config,
+            step: 0,
+            moments: BTreeMap::new(),
+        }
+    }
+
+    /// Optimizer steps taken so far.
+    pub fn steps(&self) -> u64 {
+        self.step
+    }
+
+    /// Learning rate the next step will use.
+    pub fn current_lr(&self) -> f32 {
+        self.config.schedule.lr(self.step)
+    }
+
+    /// Apply the mean of `grads` to `params`. Parameters without a gradient
+    /// are left untouched. Returns `None` if no examples were accumulated.
+    pub fn step(
+        &mut self,
+        params: Vec<(String, &mut [f32])>,
+        grads: &Gradients,
+    ) -> Option<StepStats> {
+        if grads.is_empty() {
+            return None;
+        }
+        let grad_norm = grads.global_norm();
+        let clip = match self.config.clip_norm {
+            Some(max) if grad_norm > max && grad_norm > 0.0 => max / grad_norm,
+            _ => 1.0,
+        };
+        let scale = clip / grads.examples() as f32;
+        let lr = self.current_lr();
+        self.step += 1;
+        let t = self.step as i32;
+
+        for (name, p) in params {
+            let Some(g) = grads.get(&name) else {
+                continue;
+            };
+            let adam = matches!(self.config.kind, OptimizerKind::AdamW { .. });
+            let fresh = || Moments {
+                m: vec![0.0; p.len()],
+                v: if adam { vec![0.0; p.len()] } else { Vec::new() },
+            };
+            let mom = self.moments.entry(name.clone()).or_insert_with(fresh);
+            // A tensor that changed size (a grown model) restarts its moments.
+            if mom.m.len() != p.len() {
+                *mom = fresh();
+            }
+            match self.config.kind {
+                OptimizerKind::Sgd { momentum } => {
+                    p.par_iter_mut()
+                        .zip(mom.m.par_iter_mut())
+                        .zip(g.par_iter())
+                        .for_each(|((p, m), &g)| {
+                            *m = momentum * *m + g * scale;
+                            *p -= lr * *m;
+                        });
+                }
+                OptimizerKind::AdamW {
+                    beta1,
+                    beta2,
+                    eps,
+                    weight_decay,
+                } => {
+                    let bc1 = 1.0 - beta1.powi(t);
+                    let bc2 = 1.0 - beta2.powi(t);
+                    let wd = if decays(&name) { weight_decay } else { 0.0 };
+                    p.par_iter_mut()
+                        .zip(mom.m.par_iter_mut())
+                        .zip(mom.v.par_iter_mut())
+                        .zip(g.par_iter())
+                        .for_each(|(((p, m), v), &g)| {
+                            let g = g * scale;
+                            *m = beta1 * *m + (1.0 - beta1) * g;
+                            *v = beta2 * *v + (1.0 - beta2) * g * g;
+                            let update = (*m / bc1) / ((*v / bc2).sqrt() + eps);
+                            *p -= lr * (update + wd * *p);
+                        });
+                }
+            }
+        }
+
+        Some(StepStats {
+            step: self.step,
+            lr,
+            grad_norm,
+            clipped: clip < 1.0,
+        })
+    }
+}
+
+/// Weight decay applies to weight matrices and embeddings, not to norm
+/// scales, biases or positional tables.
+fn decays(name: &str) -> bool {
+    !(name.ends_with("_scale") || name.ends_with("bias") || name.ends_with("_pos"))
+}
+
+impl Checkpointable for Optimizer {
+    const ARCH: &'static str = "optimizer";
+
+    fn hyperparams() -> Vec<(&'static str, u64)> {
+        Vec::new()
+    }
+
+    fn write_tensors(&self, w: &mut CheckpointWriter) {
+        w.meta(
+            "config",
+            serde_json::to_value(self.config).unwrap_or_default(),
+        )
+        .meta("step", self.step);
+        for (name, mom) in &self.moments {
+            w.tensor(&format!("m.{name}"), &[mom.m.len()], &mom.m);
+            if !mom.v.is_empty() {
+                w.tensor(&format!("v.{name}"), &[mom.v.len()], &mom.v);
+            }
+        }
+    }
+
+    fn read_tensors(c: &Checkpoint<'_>) -> Result<Self, CheckpointError> {
+        let config = c
+            .header
+            .meta
+            .get("config")
+            .cloned()
+            .and_then(|v| serde_json::from_value(v).ok())
+            .ok_or_else(|| CheckpointError::Header("missing optimizer config".to_string()))?;
+        let mut moments = BTreeMap::new();
+        for info in &c.header.tensors {
+            let Some(name) = info.name.strip_prefix("m.") else {
+                continue;
+            };
+            let m = c.tensor_vec(&info.name, &info.shape)?;
+            let v_name = format!("v.{name}");
+            let v = match c.info(&v_name) {
+                Some(v) => c.tensor_vec(&v_name, &v.shape)?,
+                None => Vec::new(),
+            };
+            moments.insert(name.to_string(), Moments { m, v });
+        }
+        Ok(Self {
+            config,
+            step: c.meta_u64("step"),
+            moments,
+        })
+    }
+
+    fn from_legacy_json(_json: &str) -> Option<Self> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimise `sum((p - 3)^2)` from zero.
+    fn run(config: OptimizerConfig, steps: usize) -> (Vec<f32>, Optimizer) {
+        let mut p = vec![0.0f32; 4];
+        let mut opt = Optimizer::new(config);
+        for _ in 0..steps {
+            let mut g = Gradients::new();
+            // Two examples with the same gradient: the mean is what counts.
+            for _ in 0..2 {
+                let grad: Vec<f32> = p.iter().map(|x| 2.0 * (x - 3.0)).collect();
+                g.add("w", &grad);
+                g.record(p.iter().map(|x| (x - 3.0).powi(2)).sum());
+            }
+            opt.step(vec![("w".to_string(), &mut p[..])], &g);
+        }
+        (p, opt)
+    }
+
+    #[test]
+    fn sgd_momentum_and_adamw_converge() {
+        let sgd = OptimizerConfig {
+            kind: OptimizerKind::Sgd { momentum: 0.9 },
+            schedule: Schedule::Constant { lr: 0.02 },
+            clip_norm: None,
+        };
+        let (p, _) = run(sgd, 200);
+        assert!(p.iter().all(|x| (x - 3.0).abs() < 1e-2), "{p:?}");
+
+        let adam = OptimizerConfig {
+            kind: OptimizerKind::AdamW {
+                beta1: 0.9,
+                beta2: 0.999,
+                eps: 1e-8,
+                weight_decay: 0.0,
+            },
+            schedule: Schedule::Constant { lr: 0.1 },
+            clip_norm: Some(1.0),
+        };
+        let (p, opt) = run(adam, 300);
+        assert!(p.iter().all(|x| (x - 3.0).abs() < 5e-2), "{p:?}");
+        assert_eq!(opt.steps(), 300);
+    }
+
+    #[test]
+    fn clipping_bounds_the_update() {
+        let config = OptimizerConfig {
+            kind: OptimizerKind::Sgd { momentum: 0.0 },
+            schedule: Schedule::Constant { lr: 1.0 },
+            clip_norm: Some(0.5),
+        };
+        let mut p = [0.0f32; 2];
+        let mut g = Gradients::new();
+        g.add("w", &[30.0, 40.0]);
+        g.record(0.0);
+        let stats = Optimizer::new(config)
+            .step(vec![("w".to_string(), &mut p[..])], &g)
+            .unwrap();
+        assert!(stats.clipped);
+        assert!((stats.grad_norm - 50.0).abs() < 1e-3);
+        let norm = (p[0] * p[0] + p[1] * p[1]).sqrt();
+        assert!((norm - 0.5).abs() < 1e-5, "update norm {norm}");
+    }
+
+    #[test]
+    fn warmup_cosine_schedule() {
+        let s = Schedule::WarmupCosine {
+            peak_lr: 1.0,
+            min_lr: 0.1,
+            warmup_steps: 10,
+            decay_steps: 100,
+        };
+        assert!((s.lr(0) - 0.1).abs() < 1e-6);
+        assert!((s.lr(9) - 1.0).abs() < 1e-6);
+        assert!((s.lr(10) - 1.0).abs() < 1e-6);
+        assert!((s.lr(60) - 0.55).abs() < 1e-4);
+        assert!((s.lr(110) - 0.1).abs() < 1e-6);
+        assert!((s.lr(10_000) - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn resumes_exactly_from_checkpoint() {
+        let config = OptimizerConfig {
+            kind: OptimizerKind::adamw(),
+            schedule: Schedule::WarmupCosine {
+                peak_lr: 0.1,
+                min_lr: 0.01,
+                warmup_steps: 5,
+                decay_steps: 50,
+            },
+            clip_norm: Some(1.0),
+        };
+        let (mut p, opt) = run(config, 10);
+        let restored = Optimizer::load_bytes(&opt.to_checkpoint()).unwrap();
+        assert_eq!(restored, opt);
+
+        // Continuing from the restored state matches continuing in memory.
+        let mut q = p.clone();
+        let mut a = opt;
+        let mut b = restored;
+        for _ in 0..5 {
+            for (params, o) in [(&mut p, &mut a), (&mut q, &mut b)] {
+                let mut g = Gradients::new();
+                let grad: Vec<f32> = params.iter().map(|x| 2.0 * (x - 3.0)).collect();
+                g.add("w", &grad);
+                g.record(0.0);
+                o.step(vec![("w".to_string(), &mut params[..])], &g);
+            }
+        }
+        assert_eq!(p, q);
+    }
+
+    #[test]
+    fn adamw_trains_a_model_on_minibatches() {
+        let pairs = [
+            ([1u32, 40, 41, 42, 2], [1u32, 100, 200, 300, 2]),
+            ([1u32, 50, 51, 2, 2], [1u32, 110, 210, 310, 2]),
+        ];
+        let mut model = crate::codegen::CodeGenModel::new();
+        let mut opt = Optimizer::new(OptimizerConfig {
+            kind: OptimizerKind::adamw(),
+            schedule: Schedule::Constant { lr: 3e-4 },
+            clip_norm: Some(1.0),
+        });
+
+        let mut losses = Vec::new();
+        for _ in 0..4 {
+            let mut grads = Gradients::new();
+            for (context, target) in &pairs {
+                model.enc_dec_gradients(context, target, &mut grads);
+            }
+            losses.push(grads.mean_loss());
+            let stats = model.apply_gradients(&mut opt, &grads).unwrap();
+            assert!(stats.grad_norm.is_finite());
+        }
+        assert!(losses[3] < losses[0], "batch loss {losses:?}");
+        assert_eq!(model.train_steps, 4);
+
+        // Every gradient name is a checkpoint tensor the optimizer can update.
+        let bytes = opt.to_checkpoint();
+        let ckpt = Checkpoint::parse(&bytes).unwrap();
+        let names: Vec<String> = model
+            .named_params_mut()
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        for info in &ckpt.header.tensors {
+            let name = &info.name[2..];
+            assert!(names.iter().any(|n| n == name), "unknown tensor {name}");
+        }
+    }
+}
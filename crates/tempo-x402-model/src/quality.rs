@@ -11,7 +11,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::checkpoint::{Checkpoint, CheckpointError, CheckpointWriter, Checkpointable};
 use crate::diff_features::DIFF_FEATURE_DIM;
+use crate::linalg::{self, axpy, dot};
 
 /// Hidden layer size — scaled to match plan transformer capacity.
 /// Code quality evaluation is at least as hard as plan sequence prediction.
@@ -91,30 +93,15 @@ impl CodeQualityModel {
         );
 
         // Layer 1: input → hidden (ReLU)
-        let mut h1 = vec![0.0f32; HIDDEN_SIZE];
-        for j in 0..HIDDEN_SIZE {
-            let mut sum = self.b1[j];
-            for i in 0..DIFF_FEATURE_DIM {
-                sum += features[i] * self.w1[i * HIDDEN_SIZE + j];
-            }
-            h1[j] = sum.max(0.0); // ReLU
-        }
+        let mut h1 = affine(&self.w1, &self.b1, &features[..DIFF_FEATURE_DIM]);
+        relu(&mut h1);
 
         // Layer 2: hidden → hidden (ReLU)
-        let mut h2 = vec![0.0f32; HIDDEN_SIZE];
-        for j in 0..HIDDEN_SIZE {
-            let mut sum = self.b2[j];
-            for i in 0..HIDDEN_SIZE {
-                sum += h1[i] * self.w2[i * HIDDEN_SIZE + j];
-            }
-            h2[j] = sum.max(0.0); // ReLU
-        }
+        let mut h2 = affine(&self.w2, &self.b2, &h1);
+        relu(&mut h2);
 
         // Layer 3: hidden → output (tanh for -1 to +1 range)
-        let mut output = self.b3;
-        for i in 0..HIDDEN_SIZE {
-            output += h2[i] * self.w3[i];
-        }
+        let output = self.b3 + dot(&h2, &self.w3);
         let score = output.tanh();
 
         // Confidence based on training steps (saturates at 1.0 after 100 examples)
@@ -129,32 +116,15 @@ impl CodeQualityModel {
         let target = example.target.clamp(-1.0, 1.0);
 
         // Forward pass (save activations for backprop)
-        let mut h1 = vec![0.0f32; HIDDEN_SIZE];
-        let mut h1_pre = vec![0.0f32; HIDDEN_SIZE]; // pre-ReLU
-        for j in 0..HIDDEN_SIZE {
-            let mut sum = self.b1[j];
-            for i in 0..DIFF_FEATURE_DIM {
-                sum += features[i] * self.w1[i * HIDDEN_SIZE + j];
-            }
-            h1_pre[j] = sum;
-            h1[j] = sum.max(0.0);
-        }
+        let h1_pre = affine(&self.w1, &self.b1, &features[..DIFF_FEATURE_DIM]); // pre-ReLU
+        let mut h1 = h1_pre.clone();
+        relu(&mut h1);
 
-        let mut h2 = vec![0.0f32; HIDDEN_SIZE];
-        let mut h2_pre = vec![0.0f32; HIDDEN_SIZE];
-        for j in 0..HIDDEN_SIZE {
-            let mut sum = self.b2[j];
-            for i in 0..HIDDEN_SIZE {
-                sum += h1[i] * self.w2[i * HIDDEN_SIZE + j];
-            }
-            h2_pre[j] = sum;
-            h2[j] = sum.max(0.0);
-        }
+        let h2_pre = affine(&self.w2, &self.b2, &h1);
+        let mut h2 = h2_pre.clone();
+        relu(&mut h2);
 
-        let mut output = self.b3;
-        for i in 0..HIDDEN_SIZE {
-            output += h2[i] * self.w3[i];
-        }
+        let output = self.b3 + dot(&h2, &self.w3);
         let prediction = output.tanh();
 
         // Loss: MSE
@@ -180,32 +150,19 @@ impl CodeQualityModel {
                 d_h2[j] = 0.0; // ReLU derivative
             }
         }
-        for i in 0..HIDDEN_SIZE {
-            for j in 0..HIDDEN_SIZE {
-                let grad = d_h2[j] * h1[i] + WEIGHT_DECAY * self.w2[i * HIDDEN_SIZE + j];
-                self.w2[i * HIDDEN_SIZE + j] -= LEARNING_RATE * grad;
-            }
-        }
+        sgd_outer(&mut self.w2, &h1, &d_h2);
         for j in 0..HIDDEN_SIZE {
             self.b2[j] -= LEARNING_RATE * d_h2[j];
         }
 
         // Layer 1 gradients
-        let mut d_h1 = vec![0.0f32; HIDDEN_SIZE];
+        let mut d_h1 = linalg::gemv(&self.w2, &d_h2, HIDDEN_SIZE, HIDDEN_SIZE);
         for i in 0..HIDDEN_SIZE {
-            for j in 0..HIDDEN_SIZE {
-                d_h1[i] += d_h2[j] * self.w2[i * HIDDEN_SIZE + j];
-            }
             if h1_pre[i] <= 0.0 {
                 d_h1[i] = 0.0; // ReLU derivative
             }
         }
-        for i in 0..DIFF_FEATURE_DIM {
-            for j in 0..HIDDEN_SIZE {
-                let grad = d_h1[j] * features[i] + WEIGHT_DECAY * self.w1[i * HIDDEN_SIZE + j];
-                self.w1[i * HIDDEN_SIZE + j] -= LEARNING_RATE * grad;
-            }
-        }
+        sgd_outer(&mut self.w1, &features[..DIFF_FEATURE_DIM], &d_h1);
         for j in 0..HIDDEN_SIZE {
             self.b1[j] -= LEARNING_RATE * d_h1[j];
         }
@@ -247,7 +204,71 @@ impl CodeQualityModel {
     }
 }
 
+impl Checkpointable for CodeQualityModel {
+    const ARCH: &'static str = "code_quality";
+
+    fn hyperparams() -> Vec<(&'static str, u64)> {
+        vec![
+            ("input", DIFF_FEATURE_DIM as u64),
+            ("hidden", HIDDEN_SIZE as u64),
+        ]
+    }
+
+    fn write_tensors(&self, w: &mut CheckpointWriter) {
+        w.meta("train_steps", self.train_steps)
+            .meta("running_loss", self.running_loss);
+        w.tensor("w1", &[DIFF_FEATURE_DIM, HIDDEN_SIZE], &self.w1)
+            .tensor("b1", &[HIDDEN_SIZE], &self.b1)
+            .tensor("w2", &[HIDDEN_SIZE, HIDDEN_SIZE], &self.w2)
+            .tensor("b2", &[HIDDEN_SIZE], &self.b2)
+            .tensor("w3", &[HIDDEN_SIZE, 1], &self.w3)
+            .tensor("b3", &[1], &[self.b3]);
+    }
+
+    fn read_tensors(c: &Checkpoint<'_>) -> Result<Self, CheckpointError> {
+        Ok(Self {
+            w1: c.tensor_vec("w1", &[DIFF_FEATURE_DIM, HIDDEN_SIZE])?,
+            b1: c.tensor_vec("b1", &[HIDDEN_SIZE])?,
+            w2: c.tensor_vec("w2", &[HIDDEN_SIZE, HIDDEN_SIZE])?,
+            b2: c.tensor_vec("b2", &[HIDDEN_SIZE])?,
+            w3: c.tensor_vec("w3", &[HIDDEN_SIZE, 1])?,
+            b3: c.tensor("b3", &[1])?[0],
+            train_steps: c.meta_u64("train_steps"),
+            running_loss: c.meta_f32("running_loss"),
+        })
+    }
+
+    fn from_legacy_json(json: &str) -> Option<Self> {
+        Self::from_json(json)
+    }
+}
+
 /// Xavier initialization for weight matrices.
+/// `b + Wᵀ·x` for a `[in x HIDDEN_SIZE]` weight matrix.
+fn affine(w: &[f32], b: &[f32], x: &[f32]) -> Vec<f32> {
+    let mut out = linalg::gemv_t(w, x, x.len(), HIDDEN_SIZE);
+    linalg::add_assign(&mut out, b);
+    out
+}
+
+fn relu(x: &mut [f32]) {
+    for v in x {
+        *v = v.max(0.0);
+    }
+}
+
+/// SGD step with weight decay on a `[in x out]` matrix whose gradient is the
+/// outer product `input ⊗ d_out`.
+fn sgd_outer(w: &mut [f32], input: &[f32], d_out: &[f32]) {
+    let decay = 1.0 - LEARNING_RATE * WEIGHT_DECAY;
+    for (row, &x) in w.chunks_mut(d_out.len()).zip(input) {
+        for v in row.iter_mut() {
+            *v *= decay;
+        }
+        axpy(row, -LEARNING_RATE * x, d_out);
+    }
+}
+
 fn xavier_init(fan_in: usize, fan_out: usize, seed: &mut u64) -> Vec<f32> {
     let scale = (2.0 / (fan_in + fan_out) as f64).sqrt() as f32;
     (0..fan_in * fan_out)
@@ -307,4 +328,22 @@ mod tests {
         assert_eq!(model.param_count(), restored.param_count());
         assert_eq!(model.train_steps, restored.train_steps);
     }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let mut model = CodeQualityModel::new();
+        model.train(&QualityExample {
+            features: vec![0.5; DIFF_FEATURE_DIM],
+            target: 0.8,
+        });
+        let bytes = model.to_checkpoint();
+        let restored = CodeQualityModel::load_bytes(&bytes).unwrap();
+        assert_eq!(restored.w2, model.w2);
+        assert_eq!(restored.b3, model.b3);
+        assert_eq!(restored.train_steps, 1);
+        // Legacy JSON still loads through the same entry point.
+        let legacy = CodeQualityModel::load_bytes(model.to_json().as_bytes()).unwrap();
+        assert_eq!(legacy.w1, model.w1);
+        assert!(bytes.len() < model.to_json().len() / 2);
+    }
 }
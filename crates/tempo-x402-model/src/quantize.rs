@@ -0,0 +1,1143 @@
+//! Post-training quantization — int8 / f16 inference for the encoder-decoder models.
+//!
+//! Training stays in f32. For shipping and inference, [`QuantizedUnifiedModel`]
+//! and [`QuantizedCodeGenModel`] are built from a trained model:
+//!
+//! - **int8**: symmetric per-output-channel weights (`w ≈ q · scale[row]`),
+//!   activations quantized per vector on the fly, dot products accumulated
+//!   in `i32`. ~4x smaller than f32.
+//! - **f16**: weights stored as IEEE half floats, widened to f32 in the
+//!   dot product. ~2x smaller, near-lossless.
+//!
+//! Positional encodings, layer-norm scales and biases stay f32 — they are a
+//! rounding error next to the weight matrices. Attention runs on the shared
+//! f32 kernel in `linalg`, so both precisions have the same forward shape.
+//!
+//! [`QuantReport`] measures the accuracy delta against the f32 model on a
+//! held-out set.
+
+use half::f16;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::{Checkpoint, CheckpointError, CheckpointWriter, Checkpointable};
+use crate::codegen::{self, CodeGenModel};
+use crate::decoding::{DecoderStack, DecoderView};
+use crate::linalg::{add_assign, attend, PAR_THRESHOLD};
+use crate::unified::{self, layer_norm, ModelConfig, UnifiedModel};
+
+/// Weight precision of a quantized model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuantMode {
+    Int8,
+    F16,
+}
+
+impl QuantMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QuantMode::Int8 => "int8",
+            QuantMode::F16 => "f16",
+        }
+    }
+
+    /// Parse `"int8"`/`"i8"` or `"f16"`/`"fp16"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "int8" | "i8" => Some(QuantMode::Int8),
+            "f16" | "fp16" => Some(QuantMode::F16),
+            _ => None,
+        }
+    }
+}
+
+// ── Quantized matrix ─────────────────────────────────────────────
+
+/// A row-major `[rows x cols]` weight matrix (`rows` = output channels).
+#[derive(Debug, Clone)]
+pub enum QuantMatrix {
+    /// Symmetric per-row int8: `w[r][c] ≈ data[r][c] * scales[r]`.
+    Int8 {
+        rows: usize,
+        cols: usize,
+        data: Vec<i8>,
+        scales: Vec<f32>,
+    },
+    F16 {
+        rows: usize,
+        cols: usize,
+        data: Vec<f16>,
+    },
+}
+
+impl QuantMatrix {
+    /// Quantize a row-major f32 matrix.
+    pub fn quantize(w: &[f32], rows: usize, cols: usize, mode: QuantMode) -> Self {
+        debug_assert_eq!(w.len(), rows * cols);
+        match mode {
+            QuantMode::Int8 => {
+                let mut data = Vec::with_capacity(rows * cols);
+                let mut scales = Vec::with_capacity(rows);
+                for row in w.chunks_exact(cols) {
+                    let max = row.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+                    let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+                    scales.push(scale);
+                    data.extend(
+                        row.iter()
+                            .map(|v| (v / scale).round().clamp(-127.0, 127.0) as i8),
+                    );
+                }
+                QuantMatrix::Int8 {
+                    rows,
+                    cols,
+                    data,
+                    scales,
+                }
+            }
+            QuantMode::F16 => QuantMatrix::F16 {
+                rows,
+                cols,
+                data: w.iter().map(|&v| f16::from_f32(v)).collect(),
+            },
+        }
+    }
+
+    pub fn mode(&self) -> QuantMode {
+        match self {
+            QuantMatrix::Int8 { .. } => QuantMode::Int8,
+            QuantMatrix::F16 { .. } => QuantMode::F16,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        match self {
+            QuantMatrix::Int8 { rows, .. } | QuantMatrix::F16 { rows, .. } => *rows,
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        match self {
+            QuantMatrix::Int8 { cols, .. } | QuantMatrix::F16 { cols, .. } => *cols,
+        }
+    }
+
+    /// Storage size in bytes.
+    pub fn size_bytes(&self) -> usize {
+        match self {
+            QuantMatrix::Int8 { data, scales, .. } => data.len() + scales.len() * 4,
+            QuantMatrix::F16 { data, .. } => data.len() * 2,
+        }
+    }
+
+    /// Dequantize one row (embedding lookup).
+    pub fn row(&self, r: usize) -> Vec<f32> {
+        let cols = self.cols();
+        match self {
+            QuantMatrix::Int8 { data, scales, .. } => data[r * cols..(r + 1) * cols]
+                .iter()
+                .map(|&q| q as f32 * scales[r])
+                .collect(),
+            QuantMatrix::F16 { data, .. } => data[r * cols..(r + 1) * cols]
+                .iter()
+                .map(|v| v.to_f32())
+                .collect(),
+        }
+    }
+
+    /// Dequantize the whole matrix.
+    pub fn dequantize(&self) -> Vec<f32> {
+        (0..self.rows()).flat_map(|r| self.row(r)).collect()
+    }
+
+    /// `y = W · x` for a single input vector of length `cols`.
+    pub fn matvec(&self, x: &[f32]) -> Vec<f32> {
+        let (rows, cols) = (self.rows(), self.cols());
+        let parallel = rows * cols >= PAR_THRESHOLD;
+        match self {
+            QuantMatrix::Int8 { data, scales, .. } => {
+                let (xq, sx) = quantize_activations(x);
+                let row =
+                    |r: usize| dot_i8(&xq, &data[r * cols..(r + 1) * cols]) as f32 * scales[r] * sx;
+                if parallel {
+                    (0..rows).into_par_iter().map(row).collect()
+                } else {
+                    (0..rows).map(row).collect()
+                }
+            }
+            QuantMatrix::F16 { data, .. } => {
+                let row = |r: usize| dot_f16(x, &data[r * cols..(r + 1) * cols]);
+                if parallel {
+                    (0..rows).into_par_iter().map(row).collect()
+                } else {
+                    (0..rows).map(row).collect()
+                }
+            }
+        }
+    }
+
+    /// `matvec` over `n` row-major input vectors, parallel across vectors.
+    /// Returns `[n x rows]`.
+    pub fn matvec_batch(&self, xs: &[f32], n: usize) -> Vec<f32> {
+        let cols = self.cols();
+        (0..n)
+            .into_par_iter()
+            .flat_map_iter(|i| self.matvec(&xs[i * cols..(i + 1) * cols]))
+            .collect()
+    }
+
+    fn write(&self, w: &mut CheckpointWriter, name: &str) {
+        let shape = [self.rows(), self.cols()];
+        match self {
+            QuantMatrix::Int8 { data, scales, .. } => {
+                w.tensor_i8(name, &shape, data).tensor(
+                    &format!("{name}.scale"),
+                    &[shape[0]],
+                    scales,
+                );
+            }
+            QuantMatrix::F16 { data, .. } => {
+                w.tensor_f16(name, &shape, data);
+            }
+        }
+    }
+
+    fn read(
+        c: &Checkpoint<'_>,
+        name: &str,
+        rows: usize,
+        cols: usize,
+        mode: QuantMode,
+    ) -> Result<Self, CheckpointError> {
+        Ok(match mode {
+            QuantMode::Int8 => QuantMatrix::Int8 {
+                rows,
+                cols,
+                data: c.tensor_i8(name, &[rows, cols])?,
+                scales: c.tensor_vec(&format!("{name}.scale"), &[rows])?,
+            },
+            QuantMode::F16 => QuantMatrix::F16 {
+                rows,
+                cols,
+                data: c.tensor_f16(name, &[rows, cols])?,
+            },
+        })
+    }
+}
+
+/// Symmetric per-vector activation quantization: `x ≈ q · scale`.
+fn quantize_activations(x: &[f32]) -> (Vec<i8>, f32) {
+    let max = x.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+    if max == 0.0 {
+        return (vec![0; x.len()], 0.0);
+    }
+    let scale = max / 127.0;
+    let inv = 1.0 / scale;
+    (
+        x.iter()
+            .map(|v| (v * inv).round().clamp(-127.0, 127.0) as i8)
+            .collect(),
+        scale,
+    )
+}
+
+/// Integer dot product, written so the compiler vectorizes the i32 widening.
+#[inline]
+fn dot_i8(a: &[i8], b: &[i8]) -> i32 {
+    a.iter().zip(b).map(|(&x, &y)| x as i32 * y as i32).sum()
+}
+
+#[inline]
+fn dot_f16(x: &[f32], w: &[f16]) -> f32 {
+    x.iter().zip(w).map(|(a, b)| a * b.to_f32()).sum()
+}
+
+// ── Quantized layers ─────────────────────────────────────────────
+
+/// Layer dimensions shared by the encoder and decoder stacks.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Dims {
+    pub(crate) d: usize,
+    pub(crate) n_heads: usize,
+    pub(crate) d_head: usize,
+    pub(crate) ff: usize,
+}
+
+/// Encoder layer with quantized projections.
+#[derive(Debug, Clone)]
+pub struct QuantEncoderLayer {
+    pub wq: QuantMatrix,
+    pub wk: QuantMatrix,
+    pub wv: QuantMatrix,
+    pub wo: QuantMatrix,
+    pub ff_w1: QuantMatrix,
+    pub ff_w2: QuantMatrix,
+    pub ln1_scale: Vec<f32>,
+    pub ln2_scale: Vec<f32>,
+}
+
+/// Decoder layer with quantized projections.
+#[derive(Debug, Clone)]
+pub struct QuantDecoderLayer {
+    pub wq: QuantMatrix,
+    pub wk: QuantMatrix,
+    pub wv: QuantMatrix,
+    pub wo: QuantMatrix,
+    pub cross_wq: QuantMatrix,
+    pub cross_wk: QuantMatrix,
+    pub cross_wv: QuantMatrix,
+    pub cross_wo: QuantMatrix,
+    pub ff_w1: QuantMatrix,
+    pub ff_w2: QuantMatrix,
+    pub ln1_scale: Vec<f32>,
+    pub ln2_scale: Vec<f32>,
+    pub ln3_scale: Vec<f32>,
+}
+
+/// Quantize an encoder layer of either model (the field layout is shared).
+macro_rules! quant_encoder_layer {
+    ($l:expr, $mode:expr, $d:expr, $ff:expr) => {
+        QuantEncoderLayer {
+            wq: QuantMatrix::quantize(&$l.wq, $d, $d, $mode),
+            wk: QuantMatrix::quantize(&$l.wk, $d, $d, $mode),
+            wv: QuantMatrix::quantize(&$l.wv, $d, $d, $mode),
+            wo: QuantMatrix::quantize(&$l.wo, $d, $d, $mode),
+            ff_w1: QuantMatrix::quantize(&$l.ff_w1, $ff, $d, $mode),
+            ff_w2: QuantMatrix::quantize(&$l.ff_w2, $d, $ff, $mode),
+            ln1_scale: $l.ln1_scale.clone(),
+            ln2_scale: $l.ln2_scale.clone(),
+        }
+    };
+}
+
+/// Quantize a decoder layer of either model.
+macro_rules! quant_decoder_layer {
+    ($l:expr, $mode:expr, $d:expr, $ff:expr) => {
+        QuantDecoderLayer {
+            wq: QuantMatrix::quantize(&$l.wq, $d, $d, $mode),
+            wk: QuantMatrix::quantize(&$l.wk, $d, $d, $mode),
+            wv: QuantMatrix::quantize(&$l.wv, $d, $d, $mode),
+            wo: QuantMatrix::quantize(&$l.wo, $d, $d, $mode),
+            cross_wq: QuantMatrix::quantize(&$l.cross_wq, $d, $d, $mode),
+            cross_wk: QuantMatrix::quantize(&$l.cross_wk, $d, $d, $mode),
+            cross_wv: QuantMatrix::quantize(&$l.cross_wv, $d, $d, $mode),
+            cross_wo: QuantMatrix::quantize(&$l.cross_wo, $d, $d, $mode),
+            ff_w1: QuantMatrix::quantize(&$l.ff_w1, $ff, $d, $mode),
+            ff_w2: QuantMatrix::quantize(&$l.ff_w2, $d, $ff, $mode),
+            ln1_scale: $l.ln1_scale.clone(),
+            ln2_scale: $l.ln2_scale.clone(),
+            ln3_scale: $l.ln3_scale.clone(),
+        }
+    };
+}
+
+impl QuantEncoderLayer {
+    fn size_bytes(&self) -> usize {
+        [
+            &self.wq,
+            &self.wk,
+            &self.wv,
+            &self.wo,
+            &self.ff_w1,
+            &self.ff_w2,
+        ]
+        .iter()
+        .map(|m| m.size_bytes())
+        .sum::<usize>()
+            + (self.ln1_scale.len() + self.ln2_scale.len()) * 4
+    }
+
+    fn forward(&self, x: &[f32], n: usize, dims: Dims) -> Vec<f32> {
+        let normed = layer_norm(x, &self.ln1_scale, n, dims.d);
+        let q = self.wq.matvec_batch(&normed, n);
+        let k = self.wk.matvec_batch(&normed, n);
+        let v = self.wv.matvec_batch(&normed, n);
+        let attn = attend(&q, n, &k, &v, n, dims, false);
+        let mut residual = add(x, &self.wo.matvec_batch(&attn, n));
+
+        let normed2 = layer_norm(&residual, &self.ln2_scale, n, dims.d);
+        add_assign(&mut residual, &ffn(&normed2, n, &self.ff_w1, &self.ff_w2));
+        residual
+    }
+
+    fn write(&self, w: &mut CheckpointWriter, p: &str) {
+        self.wq.write(w, &format!("{p}.wq"));
+        self.wk.write(w, &format!("{p}.wk"));
+        self.wv.write(w, &format!("{p}.wv"));
+        self.wo.write(w, &format!("{p}.wo"));
+        self.ff_w1.write(w, &format!("{p}.ff_w1"));
+        self.ff_w2.write(w, &format!("{p}.ff_w2"));
+        w.tensor(
+            &format!("{p}.ln1_scale"),
+            &[self.ln1_scale.len()],
+            &self.ln1_scale,
+        )
+        .tensor(
+            &format!("{p}.ln2_scale"),
+            &[self.ln2_scale.len()],
+            &self.ln2_scale,
+        );
+    }
+
+    fn read(
+        c: &Checkpoint<'_>,
+        p: &str,
+        dims: Dims,
+        mode: QuantMode,
+    ) -> Result<Self, CheckpointError> {
+        let Dims { d, ff, .. } = dims;
+        Ok(Self {
+            wq: QuantMatrix::read(c, &format!("{p}.wq"), d, d, mode)?,
+            wk: QuantMatrix::read(c, &format!("{p}.wk"), d, d, mode)?,
+            wv: QuantMatrix::read(c, &format!("{p}.wv"), d, d, mode)?,
+            wo: QuantMatrix::read(c, &format!("{p}.wo"), d, d, mode)?,
+            ff_w1: QuantMatrix::read(c, &format!("{p}.ff_w1"), ff, d, mode)?,
+            ff_w2: QuantMatrix::read(c, &format!("{p}.ff_w2"), d, ff, mode)?,
+            ln1_scale: c.tensor_vec(&format!("{p}.ln1_scale"), &[d])?,
+            ln2_scale: c.tensor_vec(&format!("{p}.ln2_scale"), &[d])?,
+        })
+    }
+}
+
+impl QuantDecoderLayer {
+    fn size_bytes(&self) -> usize {
+        [
+            &self.wq,
+            &self.wk,
+            &self.wv,
+            &self.wo,
+            &self.cross_wq,
+            &self.cross_wk,
+            &self.cross_wv,
+            &self.cross_wo,
+            &self.ff_w1,
+            &self.ff_w2,
+        ]
+        .iter()
+        .map(|m| m.size_bytes())
+        .sum::<usize>()
+            + (self.ln1_scale.len() + self.ln2_scale.len() + self.ln3_scale.len()) * 4
+    }
+
+    fn forward(&self, x: &[f32], n: usize, enc: &[f32], enc_len: usize, dims: Dims) -> Vec<f32> {
+        // Causal self-attention
+        let normed = layer_norm(x, &self.ln1_scale, n, dims.d);
+        let q = self.wq.matvec_batch(&normed, n);
+        let k = self.wk.matvec_batch(&normed, n);
+        let v = self.wv.matvec_batch(&normed, n);
+        let attn = attend(&q, n, &k, &v, n, dims, true);
+        let mut residual = add(x, &self.wo.matvec_batch(&attn, n));
+
+        // Cross-attention: decoder queries, encoder keys/values
+        let normed2 = layer_norm(&residual, &self.ln2_scale, n, dims.d);
+        if enc_len > 0 && !enc.is_empty() {
+            let q = self.cross_wq.matvec_batch(&normed2, n);
+            let k = self.cross_wk.matvec_batch(enc, enc_len);
+            let v = self.cross_wv.matvec_batch(enc, enc_len);
+            let attn = attend(&q, n, &k, &v, enc_len, dims, false);
+            add_assign(&mut residual, &self.cross_wo.matvec_batch(&attn, n));
+        }
+
+        let normed3 = layer_norm(&residual, &self.ln3_scale, n, dims.d);
+        add_assign(&mut residual, &ffn(&normed3, n, &self.ff_w1, &self.ff_w2));
+        residual
+    }
+
+    fn write(&self, w: &mut CheckpointWriter, p: &str) {
+        self.wq.write(w, &format!("{p}.wq"));
+        self.wk.write(w, &format!("{p}.wk"));
+        self.wv.write(w, &format!("{p}.wv"));
+        self.wo.write(w, &format!("{p}.wo"));
+        self.cross_wq.write(w, &format!("{p}.cross_wq"));
+        self.cross_wk.write(w, &format!("{p}.cross_wk"));
+        self.cross_wv.write(w, &format!("{p}.cross_wv"));
+        self.cross_wo.write(w, &format!("{p}.cross_wo"));
+        self.ff_w1.write(w, &format!("{p}.ff_w1"));
+        self.ff_w2.write(w, &format!("{p}.ff_w2"));
+        w.tensor(
+            &format!("{p}.ln1_scale"),
+            &[self.ln1_scale.len()],
+            &self.ln1_scale,
+        )
+        .tensor(
+            &format!("{p}.ln2_scale"),
+            &[self.ln2_scale.len()],
+            &self.ln2_scale,
+        )
+        .tensor(
+            &format!("{p}.ln3_scale"),
+            &[self.ln3_scale.len()],
+            &self.ln3_scale,
+        );
+    }
+
+    fn read(
+        c: &Checkpoint<'_>,
+        p: &str,
+        dims: Dims,
+        mode: QuantMode,
+    ) -> Result<Self, CheckpointError> {
+        let Dims { d, ff, .. } = dims;
+        Ok(Self {
+            wq: QuantMatrix::read(c, &format!("{p}.wq"), d, d, mode)?,
+            wk: QuantMatrix::read(c, &format!("{p}.wk"), d, d, mode)?,
+            wv: QuantMatrix::read(c, &format!("{p}.wv"), d, d, mode)?,
+            wo: QuantMatrix::read(c, &format!("{p}.wo"), d, d, mode)?,
+            cross_wq: QuantMatrix::read(c, &format!("{p}.cross_wq"), d, d, mode)?,
+            cross_wk: QuantMatrix::read(c, &format!("{p}.cross_wk"), d, d, mode)?,
+            cross_wv: QuantMatrix::read(c, &format!("{p}.cross_wv"), d, d, mode)?,
+            cross_wo: QuantMatrix::read(c, &format!("{p}.cross_wo"), d, d, mode)?,
+            ff_w1: QuantMatrix::read(c, &format!("{p}.ff_w1"), ff, d, mode)?,
+            ff_w2: QuantMatrix::read(c, &format!("{p}.ff_w2"), d, ff, mode)?,
+            ln1_scale: c.tensor_vec(&format!("{p}.ln1_scale"), &[d])?,
+            ln2_scale: c.tensor_vec(&format!("{p}.ln2_scale"), &[d])?,
+            ln3_scale: c.tensor_vec(&format!("{p}.ln3_scale"), &[d])?,
+        })
+    }
+}
+
+/// Position-wise ReLU feed-forward.
+fn ffn(x: &[f32], n: usize, w1: &QuantMatrix, w2: &QuantMatrix) -> Vec<f32> {
+    let mut hidden = w1.matvec_batch(x, n);
+    for h in &mut hidden {
+        *h = h.max(0.0);
+    }
+    w2.matvec_batch(&hidden, n)
+}
+
+fn add(a: &[f32], b: &[f32]) -> Vec<f32> {
+    a.iter().zip(b).map(|(x, y)| x + y).collect()
+}
+
+// ── Shared encoder-decoder core ──────────────────────────────────
+
+/// The quantized encoder-decoder common to both models.
+#[derive(Debug, Clone)]
+struct EncDec {
+    mode: QuantMode,
+    dims: Dims,
+    vocab: usize,
+    max_seq: usize,
+    /// Token embeddings `[vocab x d]`, tied with the output projection.
+    embeddings: QuantMatrix,
+    enc_pos: Vec<f32>,
+    dec_pos: Vec<f32>,
+    encoder_layers: Vec<QuantEncoderLayer>,
+    decoder_layers: Vec<QuantDecoderLayer>,
+    output_bias: Vec<f32>,
+}
+
+impl EncDec {
+    fn embed(&self, tokens: &[u32], pos_table: &[f32]) -> (Vec<f32>, usize) {
+        let d = self.dims.d;
+        let n = tokens.len().min(self.max_seq);
+        let mut hidden = Vec::with_capacity(n * d);
+        for (pos, &tok) in tokens.iter().take(n).enumerate() {
+            let row = self.embeddings.row(tok as usize % self.vocab);
+            hidden.extend(
+                row.iter()
+                    .zip(&pos_table[pos * d..(pos + 1) * d])
+                    .map(|(e, p)| e + p),
+            );
+        }
+        (hidden, n)
+    }
+
+    fn encode(&self, tokens: &[u32]) -> Vec<f32> {
+        let (mut hidden, n) = self.embed(tokens, &self.enc_pos);
+        for layer in &self.encoder_layers {
+            hidden = layer.forward(&hidden, n, self.dims);
+        }
+        hidden
+    }
+
+    fn decode(&self, target: &[u32], encoder_output: &[f32], enc_len: usize) -> Vec<f32> {
+        let d = self.dims.d;
+        let (mut hidden, n) = self.embed(target, &self.dec_pos);
+        if n == 0 {
+            return vec![0.0; self.vocab];
+        }
+        for layer in &self.decoder_layers {
+            hidden = layer.forward(&hidden, n, encoder_output, enc_len, self.dims);
+        }
+        let mut logits = self.embeddings.matvec(&hidden[(n - 1) * d..n * d]);
+        add_assign(&mut logits, &self.output_bias);
+        logits
+    }
+
+    fn decoder_stack(&self) -> DecoderStack<'_, &QuantMatrix> {
+        DecoderStack {
+            dims: self.dims,
+            vocab: self.vocab,
+            max_seq: self.max_seq,
+            embeddings: &self.embeddings,
+            dec_pos: &self.dec_pos,
+            output_bias: &self.output_bias,
+            layers: self
+                .decoder_layers
+                .iter()
+                .map(|l| DecoderView {
+                    wq: &l.wq,
+                    wk: &l.wk,
+                    wv: &l.wv,
+                    wo: &l.wo,
+                    cross_wq: &l.cross_wq,
+                    cross_wk: &l.cross_wk,
+                    cross_wv: &l.cross_wv,
+                    cross_wo: &l.cross_wo,
+                    ff_w1: &l.ff_w1,
+                    ff_w2: &l.ff_w2,
+                    ln1_scale: &l.ln1_scale,
+                    ln2_scale: &l.ln2_scale,
+                    ln3_scale: &l.ln3_scale,
+                })
+                .collect(),
+        }
+    }
+
+    fn size_bytes(&self) -> usize {
+        self.embeddings.size_bytes()
+            + (self.enc_pos.len() + self.dec_pos.len() + self.output_bias.len()) * 4
+            + self
+                .encoder_layers
+                .iter()
+                .map(|l| l.size_bytes())
+                .sum::<usize>()
+            + self
+                .decoder_layers
+                .iter()
+                .map(|l| l.size_bytes())
+                .sum::<usize>()
+    }
+
+    fn write(&self, w: &mut CheckpointWriter) {
+        let d = self.dims.d;
+        w.meta("quant", self.mode.as_str());
+        self.embeddings.write(w, "embeddings");
+        w.tensor("enc_pos", &[self.max_seq, d], &self.enc_pos)
+            .tensor("dec_pos", &[self.max_seq, d], &self.dec_pos)
+            .tensor("output_bias", &[self.vocab], &self.output_bias);
+        for (i, l) in self.encoder_layers.iter().enumerate() {
+            l.write(w, &format!("enc.{i}"));
+        }
+        for (i, l) in self.decoder_layers.iter().enumerate() {
+            l.write(w, &format!("dec.{i}"));
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn read(
+        c: &Checkpoint<'_>,
+        dims: Dims,
+        vocab: usize,
+        max_seq: usize,
+        enc_layers: usize,
+        dec_layers: usize,
+    ) -> Result<Self, CheckpointError> {
+        let mode = c
+            .meta_str("quant")
+            .and_then(QuantMode::parse)
+            .ok_or_else(|| CheckpointError::Header("missing quantization mode".to_string()))?;
+        let d = dims.d;
+        Ok(Self {
+            mode,
+            dims,
+            vocab,
+            max_seq,
+            embeddings: QuantMatrix::read(c, "embeddings", vocab, d, mode)?,
+            enc_pos: c.tensor_vec("enc_pos", &[max_seq, d])?,
+            dec_pos: c.tensor_vec("dec_pos", &[max_seq, d])?,
+            encoder_layers: (0..enc_layers)
+                .map(|i| QuantEncoderLayer::read(c, &format!("enc.{i}"), dims, mode))
+                .collect::<Result<_, _>>()?,
+            decoder_layers: (0..dec_layers)
+                .map(|i| QuantDecoderLayer::read(c, &format!("dec.{i}"), dims, mode))
+                .collect::<Result<_, _>>()?,
+            output_bias: c.tensor_vec("output_bias", &[vocab])?,
+        })
+    }
+}
+
+// ── Quantized unified model ──────────────────────────────────────
+
+/// Quantized [`UnifiedModel`] for inference. Same API as the f32 model's
+/// inference methods: `encode`, `fast_predict`, `decode`, `forward`.
+#[derive(Debug, Clone)]
+pub struct QuantizedUnifiedModel {
+    core: EncDec,
+    fast_w1: QuantMatrix,
+    fast_w2: QuantMatrix,
+    fast_bias: Vec<f32>,
+    pub train_steps: u64,
+    pub config: ModelConfig,
+}
+
+impl QuantizedUnifiedModel {
+    /// Quantize a trained model.
+    pub fn from_model(m: &UnifiedModel, mode: QuantMode) -> Self {
+        let c = m.config;
+        let Dims { d, ff, .. } = c.dims();
+        Self {
+            core: EncDec {
+                mode,
+                dims: c.dims(),
+                vocab: c.vocab,
+                max_seq: c.max_seq,
+                embeddings: QuantMatrix::quantize(&m.embeddings, c.vocab, d, mode),
+                enc_pos: m.enc_pos.clone(),
+                dec_pos: m.dec_pos.clone(),
+                encoder_layers: m
+                    .encoder_layers
+                    .iter()
+                    .map(|l| quant_encoder_layer!(l, mode, d, ff))
+                    .collect(),
+                decoder_layers: m
+                    .decoder_layers
+                    .iter()
+                    .map(|l| quant_decoder_layer!(l, mode, d, ff))
+                    .collect(),
+                output_bias: m.output_bias.clone(),
+            },
+            fast_w1: QuantMatrix::quantize(&m.fast_w1, unified::FAST_HIDDEN, d, mode),
+            fast_w2: QuantMatrix::quantize(
+                &m.fast_w2,
+                unified::FAST_OUTPUT,
+                unified::FAST_HIDDEN,
+                mode,
+            ),
+            fast_bias: m.fast_bias.clone(),
+            train_steps: m.train_steps,
+            config: c,
+        }
+    }
+
+    pub fn mode(&self) -> QuantMode {
+        self.core.mode
+    }
+
+    /// Weight storage in bytes.
+    pub fn size_bytes(&self) -> usize {
+        self.core.size_bytes()
+            + self.fast_w1.size_bytes()
+            + self.fast_w2.size_bytes()
+            + self.fast_bias.len() * 4
+    }
+
+    /// Encode context tokens with bidirectional attention. `[seq_len x d_model]`.
+    pub fn encode(&self, tokens: &[u32]) -> Vec<f32> {
+        self.core.encode(tokens)
+    }
+
+    /// Encode -> mean pool -> fast head. Returns `FAST_OUTPUT` raw values.
+    pub fn fast_predict(&self, tokens: &[u32]) -> Vec<f32> {
+        let d = self.core.dims.d;
+        let n = tokens.len().min(self.core.max_seq);
+        if n == 0 {
+            return vec![0.0; unified::FAST_OUTPUT];
+        }
+        let enc = self.encode(tokens);
+        let mut pooled = vec![0.0f32; d];
+        for pos in 0..n {
+            add_assign(&mut pooled, &enc[pos * d..(pos + 1) * d]);
+        }
+        for p in &mut pooled {
+            *p /= n as f32;
+        }
+        let mut h1 = self.fast_w1.matvec(&pooled);
+        for h in &mut h1 {
+            *h = h.max(0.0);
+        }
+        let mut out = self.fast_w2.matvec(&h1);
+        add_assign(&mut out, &self.fast_bias);
+        out
+    }
+
+    /// Borrowed decoder weights for incremental decoding.
+    pub(crate) fn decoder_stack(&self) -> DecoderStack<'_, &QuantMatrix> {
+        self.core.decoder_stack()
+    }
+
+    /// Logits `[UNIFIED_VOCAB]` for the last target position.
+    pub fn decode(&self, target: &[u32], encoder_output: &[f32], enc_len: usize) -> Vec<f32> {
+        self.core.decode(target, encoder_output, enc_len)
+    }
+
+    /// Encode + decode on the same tokens.
+    pub fn forward(&self, tokens: &[u32]) -> Vec<f32> {
+        let n = tokens.len().min(self.core.max_seq);
+        if n == 0 {
+            return vec![0.0; self.core.vocab];
+        }
+        let enc = self.encode(tokens);
+        self.decode(tokens, &enc, n)
+    }
+
+    /// Compare against the f32 model on held-out token sequences.
+    pub fn accuracy_report(&self, reference: &UnifiedModel, held_out: &[Vec<u32>]) -> QuantReport {
+        let mut report =
+            QuantReport::new(self.mode(), reference.param_count() * 4, self.size_bytes());
+        let mut fast = ErrorStats::default();
+        for tokens in held_out.iter().filter(|t| !t.is_empty()) {
+            let (want, f32_ms) = timed(|| reference.forward(tokens));
+            let (got, q_ms) = timed(|| self.forward(tokens));
+            report.add_logits(&want, &got, f32_ms, q_ms);
+            fast.add(&reference.fast_predict(tokens), &self.fast_predict(tokens));
+        }
+        report.fast_mse = Some(fast.mse());
+        report.fast_max_abs = Some(fast.max_abs);
+        report.finish()
+    }
+}
+
+impl Checkpointable for QuantizedUnifiedModel {
+    const ARCH: &'static str = "unified-quantized";
+
+    fn hyperparams() -> Vec<(&'static str, u64)> {
+        UnifiedModel::hyperparams()
+    }
+
+    fn header_params(&self) -> Vec<(&'static str, u64)> {
+        let mut params = self.config.header_params();
+        params.extend(Self::hyperparams());
+        params
+    }
+
+    fn write_tensors(&self, w: &mut CheckpointWriter) {
+        w.meta("train_steps", self.train_steps);
+        self.core.write(w);
+        self.fast_w1.write(w, "fast_w1");
+        self.fast_w2.write(w, "fast_w2");
+        w.tensor("fast_bias", &[unified::FAST_OUTPUT], &self.fast_bias);
+    }
+
+    fn read_tensors(c: &Checkpoint<'_>) -> Result<Self, CheckpointError> {
+        let config = ModelConfig::from_checkpoint(c)?;
+        let core = EncDec::read(
+            c,
+            config.dims(),
+            config.vocab,
+            config.max_seq,
+            config.enc_layers,
+            config.dec_layers,
+        )?;
+        let mode = core.mode;
+        Ok(Self {
+            fast_w1: QuantMatrix::read(c, "fast_w1", unified::FAST_HIDDEN, config.d_model, mode)?,
+            fast_w2: QuantMatrix::read(
+                c,
+                "fast_w2",
+                unified::FAST_OUTPUT,
+                unified::FAST_HIDDEN,
+                mode,
+            )?,
+            fast_bias: c.tensor_vec("fast_bias", &[unified::FAST_OUTPUT])?,
+            train_steps: c.meta_u64("train_steps"),
+            config,
+            core,
+        })
+    }
+
+    fn from_legacy_json(_json: &str) -> Option<Self> {
+        None
+    }
+}
+
+// ── Quantized codegen model ──────────────────────────────────────
+
+pub(crate) const CODEGEN_DIMS: Dims = Dims {
+    d: codegen::SMALL_D_MODEL,
+    n_heads: codegen::SMALL_N_HEADS,
+    d_head: codegen::SMALL_D_HEAD,
+    ff: codegen::SMALL_D_FF,
+};
+
+/// Quantized [`CodeGenModel`] for inference.
+#[derive(Debug, Clone)]
+pub struct QuantizedCodeGenModel {
+    core: EncDec,
+    pub train_steps: u64,
+    pub max_seq: usize,
+    pub vocab_size: usize,
+}
+
+impl QuantizedCodeGenModel {
+    /// Quantize a trained model.
+    pub fn from_model(m: &CodeGenModel, mode: QuantMode) -> Self {
+        let Dims { d, ff, .. } = CODEGEN_DIMS;
+        Self {
+            core: EncDec {
+                mode,
+                dims: CODEGEN_DIMS,
+                vocab: m.vocab_size,
+                max_seq: m.max_seq,
+                embeddings: QuantMatrix::quantize(&m.embeddings, m.vocab_size, d, mode),
+                enc_pos: m.enc_pos.clone(),
+                dec_pos: m.dec_pos.clone(),
+                encoder_layers: m
+                    .encoder_layers
+                    .iter()
+                    .map(|l| quant_encoder_layer!(l, mode, d, ff))
+                    .collect(),
+                decoder_layers: m
+                    .decoder_layers
+                    .iter()
+                    .map(|l| quant_decoder_layer!(l, mode, d, ff))
+                    .collect(),
+                output_bias: m.output_bias.clone(),
+            },
+            train_steps: m.train_steps,
+            max_seq: m.max_seq,
+            vocab_size: m.vocab_size,
+        }
+    }
+
+    pub fn mode(&self) -> QuantMode {
+        self.core.mode
+    }
+
+    /// Weight storage in bytes.
+    pub fn size_bytes(&self) -> usize {
+        self.core.size_bytes()
+    }
+
+    /// Encode context tokens with bidirectional attention. `[seq_len x d_model]`.
+    pub fn encode(&self, context: &[u32]) -> Vec<f32> {
+        self.core.encode(context)
+    }
+
+    /// Borrowed decoder weights for incremental decoding.
+    pub(crate) fn decoder_stack(&self) -> DecoderStack<'_, &QuantMatrix> {
+        self.core.decoder_stack()
+    }
+
+    /// Logits `[vocab_size]` for the last target position.
+    pub fn decode(&self, target: &[u32], encoder_output: &[f32], enc_len: usize) -> Vec<f32> {
+        self.core.decode(target, encoder_output, enc_len)
+    }
+
+    /// Encode + decode on the same tokens.
+    pub fn forward(&self, tokens: &[u32]) -> Vec<f32> {
+        let n = tokens.len().min(self.max_seq);
+        if n == 0 {
+            return vec![0.0; self.vocab_size];
+        }
+        let enc = self.encode(tokens);
+        self.decode(tokens, &enc, n)
+    }
+
+    /// Compare against the f32 model on held-out token sequences.
+    pub fn accuracy_report(&self, reference: &CodeGenModel, held_out: &[Vec<u32>]) -> QuantReport {
+        let mut report =
+            QuantReport::new(self.mode(), reference.param_count() * 4, self.size_bytes());
+        for tokens in held_out.iter().filter(|t| !t.is_empty()) {
+            let (want, f32_ms) = timed(|| reference.forward(tokens));
+            let (got, q_ms) = timed(|| self.forward(tokens));
+            report.add_logits(&want, &got, f32_ms, q_ms);
+        }
+        report.finish()
+    }
+}
+
+impl Checkpointable for QuantizedCodeGenModel {
+    const ARCH: &'static str = "codegen-quantized";
+
+    fn hyperparams() -> Vec<(&'static str, u64)> {
+        CodeGenModel::hyperparams()
+    }
+
+    fn write_tensors(&self, w: &mut CheckpointWriter) {
+        w.meta("train_steps", self.train_steps);
+        self.core.write(w);
+    }
+
+    fn read_tensors(c: &Checkpoint<'_>) -> Result<Self, CheckpointError> {
+        let core = EncDec::read(
+            c,
+            CODEGEN_DIMS,
+            codegen::SMALL_VOCAB,
+            codegen::SMALL_MAX_SEQ,
+            codegen::ENC_LAYERS,
+            codegen::DEC_LAYERS,
+        )?;
+        Ok(Self {
+            train_steps: c.meta_u64("train_steps"),
+            max_seq: core.max_seq,
+            vocab_size: core.vocab,
+            core,
+        })
+    }
+
+    fn from_legacy_json(_json: &str) -> Option<Self> {
+        None
+    }
+}
+
+// ── Accuracy report ──────────────────────────────────────────────
+
+/// Accuracy delta of a quantized model against its f32 reference.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuantReport {
+    pub mode: QuantMode,
+    /// Held-out sequences evaluated.
+    pub samples: usize,
+    pub f32_bytes: usize,
+    pub quant_bytes: usize,
+    /// Mean squared error of the last-position logits.
+    pub logit_mse: f32,
+    pub logit_max_abs: f32,
+    /// Fraction of samples where the argmax token agrees.
+    pub top1_agreement: f32,
+    /// Fast-head error (unified model only).
+    pub fast_mse: Option<f32>,
+    pub fast_max_abs: Option<f32>,
+    /// Mean forward latency per sample.
+    pub f32_ms: f64,
+    pub quant_ms: f64,
+    #[serde(skip)]
+    logits: ErrorStats,
+    #[serde(skip)]
+    agree: usize,
+}
+
+impl QuantReport {
+    fn new(mode: QuantMode, f32_bytes: usize, quant_bytes: usize) -> Self {
+        Self {
+            mode,
+            samples: 0,
+            f32_bytes,
+            quant_bytes,
+            logit_mse: 0.0,
+            logit_max_abs: 0.0,
+            top1_agreement: 0.0,
+            fast_mse: None,
+            fast_max_abs: None,
+            f32_ms: 0.0,
+            quant_ms: 0.0,
+            logits: ErrorStats::default(),
+            agree: 0,
+        }
+    }
+
+    fn add_logits(&mut self, want: &[f32], got: &[f32], f32_ms: f64, q_ms: f64) {
+        self.samples += 1;
+        self.logits.add(want, got);
+        if argmax(want) == argmax(got) {
+            self.agree += 1;
+        }
+        self.f32_ms += f32_ms;
+        self.quant_ms += q_ms;
+    }
+
+    fn finish(mut self) -> Self {
+        let n = self.samples.max(1);
+        self.logit_mse = self.logits.mse();
+        self.logit_max_abs = self.logits.max_abs;
+        self.top1_agreement = self.agree as f32 / n as f32;
+        self.f32_ms /= n as f64;
+        self.quant_ms /= n as f64;
+        self
+    }
+
+    /// Size reduction factor relative to f32.
+    pub fn compression(&self) -> f32 {
+        self.f32_bytes as f32 / self.quant_bytes.max(1) as f32
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ErrorStats {
+    sum_sq: f64,
+    count: usize,
+    max_abs: f32,
+}
+
+impl ErrorStats {
+    fn add(&mut self, want: &[f32], got: &[f32]) {
+        for (a, b) in want.iter().zip(got) {
+            let e = a - b;
+            self.sum_sq += (e * e) as f64;
+            self.max_abs = self.max_abs.max(e.abs());
+        }
+        self.count += want.len().min(got.len());
+    }
+
+    fn mse(&self) -> f32 {
+        (self.sum_sq / self.count.max(1) as f64) as f32
+    }
+}
+
+fn argmax(v: &[f32]) -> usize {
+    v.iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn timed<T>(f: impl FnOnce() -> T) -> (T, f64) {
+    let start = std::time::Instant::now();
+    let out = f();
+    (out, start.elapsed().as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn int8_matvec_tracks_f32() {
+        let (rows, cols) = (16, 64);
+        let w: Vec<f32> = (0..rows * cols)
+            .map(|i| ((i * 37 % 101) as f32 / 50.0 - 1.0) * (1 + i / cols) as f32)
+            .collect();
+        let x: Vec<f32> = (0..cols).map(|i| (i as f32 * 0.3).sin()).collect();
+        let exact: Vec<f32> = w
+            .chunks_exact(cols)
+            .map(|row| row.iter().zip(&x).map(|(a, b)| a * b).sum())
+            .collect();
+        for mode in [QuantMode::Int8, QuantMode::F16] {
+            let q = QuantMatrix::quantize(&w, rows, cols, mode);
+            for (r, (got, want)) in q.matvec(&x).iter().zip(&exact).enumerate() {
+                // Per-row scales keep the error relative to each row's magnitude.
+                let tol = 0.02 * (1 + r) as f32 * cols as f32 / 8.0;
+                assert!(
+                    (got - want).abs() < tol,
+                    "{mode:?} row {r}: {got} vs {want}"
+                );
+            }
+        }
+        let q = QuantMatrix::quantize(&w, rows, cols, QuantMode::Int8);
+        assert_eq!(q.size_bytes(), rows * cols + rows * 4);
+    }
+
+    #[test]
+    fn quantized_unified_matches_reference() {
+        let model = UnifiedModel::new();
+        let held_out = vec![
+            vec![1, 5, 9, 200, 33, 7],
+            vec![unified::TASK_CODE, 42, 43, 44],
+        ];
+
+        let f16 = QuantizedUnifiedModel::from_model(&model, QuantMode::F16);
+        let report = f16.accuracy_report(&model, &held_out);
+        assert_eq!(report.samples, 2);
+        assert_eq!(report.top1_agreement, 1.0);
+        assert!(report.logit_max_abs < 0.05, "{report:?}");
+        assert!(report.compression() > 1.9);
+
+        let int8 = QuantizedUnifiedModel::from_model(&model, QuantMode::Int8);
+        let report = int8.accuracy_report(&model, &held_out);
+        assert_eq!(report.top1_agreement, 1.0, "{report:?}");
+        assert!(report.logit_max_abs < 0.5, "{report:?}");
+        assert!(report.logit_mse < 0.01, "{report:?}");
+        assert!(report.fast_mse.unwrap() < 0.01, "{report:?}");
+        assert!(report.compression() > 3.5);
+
+        // Ships as a checkpoint and reloads bit-identically.
+        let bytes = int8.to_checkpoint();
+        let restored = QuantizedUnifiedModel::load_bytes(&bytes).unwrap();
+        assert_eq!(restored.mode(), QuantMode::Int8);
+        assert_eq!(restored.forward(&held_out[0]), int8.forward(&held_out[0]));
+        assert!(bytes.len() < model.to_checkpoint().len() / 3);
+    }
+}
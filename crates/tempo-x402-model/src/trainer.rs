@@ -4,7 +4,8 @@
 //! Each training example is a (context, plan_steps, fitness_weight) triple.
 //! Higher-fitness plans have more influence on the model.
 
-use crate::transformer::{PlanTransformer, D_MODEL};
+use crate::linalg::{self, axpy};
+use crate::transformer::{feed_forward, PlanTransformer, D_MODEL};
 use crate::vocab::VOCAB_SIZE;
 use crate::vocab::{self, BOS, EOS};
 
@@ -96,27 +97,24 @@ pub fn train_batch(model: &mut PlanTransformer, examples: &[TrainingExample]) ->
             // output_proj: D_MODEL × VOCAB_SIZE
             let last_pos = input.len() - 1;
             let last_hidden = get_last_hidden(model, input);
-            for v in 0..VOCAB_SIZE {
-                for d in 0..D_MODEL {
-                    let idx = d * VOCAB_SIZE + v;
-                    let grad = grad_logits[v] * last_hidden[d];
-                    model.output_proj[idx] -=
-                        LEARNING_RATE * (grad + WEIGHT_DECAY * model.output_proj[idx]);
+            let decay = 1.0 - LEARNING_RATE * WEIGHT_DECAY;
+            for (d, row) in model.output_proj.chunks_mut(VOCAB_SIZE).enumerate() {
+                for w in row.iter_mut() {
+                    *w *= decay;
                 }
-                model.output_bias[v] -= LEARNING_RATE * grad_logits[v];
+                axpy(row, -LEARNING_RATE * last_hidden[d], &grad_logits);
             }
+            axpy(&mut model.output_bias, -LEARNING_RATE, &grad_logits);
 
             // Update token embedding for the last input token
             let last_token = input[last_pos] as usize;
             if last_token < VOCAB_SIZE {
                 let emb_start = last_token * D_MODEL;
+                // Gradient flows back through output projection
+                let grad = linalg::gemv(&model.output_proj, &grad_logits, D_MODEL, VOCAB_SIZE);
                 for d in 0..D_MODEL {
-                    // Gradient flows back through output projection
-                    let grad: f32 = (0..VOCAB_SIZE)
-                        .map(|v| grad_logits[v] * model.output_proj[d * VOCAB_SIZE + v])
-                        .sum();
                     model.embedding[emb_start + d] -=
-                        LEARNING_RATE * grad.clamp(-GRAD_CLIP, GRAD_CLIP);
+                        LEARNING_RATE * grad[d].clamp(-GRAD_CLIP, GRAD_CLIP);
                 }
             }
 
@@ -137,6 +135,25 @@ pub fn train_batch(model: &mut PlanTransformer, examples: &[TrainingExample]) ->
     (trained, avg_loss)
 }
 
+/// Mean weighted next-step cross-entropy over `examples`, without training.
+/// Used as the validation loss when merging peer weights.
+pub fn evaluate(model: &PlanTransformer, examples: &[TrainingExample]) -> f32 {
+    let mut total_loss = 0.0f32;
+    let mut positions = 0usize;
+    for example in examples {
+        let mut tokens: Vec<u32> = example.context.clone();
+        tokens.push(BOS);
+        tokens.extend_from_slice(&example.steps);
+        for i in example.context.len() + 1..tokens.len() {
+            let probs = PlanTransformer::softmax(&model.forward(&tokens[..i]));
+            let p = probs.get(tokens[i] as usize).copied().unwrap_or(0.0);
+            total_loss += -p.max(1e-10).ln() * example.weight;
+            positions += 1;
+        }
+    }
+    total_loss / positions.max(1) as f32
+}
+
 /// Get the last hidden state from a forward pass (needed for backprop).
 /// This is a simplified version that re-runs forward — not efficient but correct.
 fn get_last_hidden(model: &PlanTransformer, tokens: &[u32]) -> Vec<f32> {
@@ -157,24 +174,8 @@ fn get_last_hidden(model: &PlanTransformer, tokens: &[u32]) -> Vec<f32> {
     // This is an approximation for the backward pass
     for layer in &model.layers {
         // Feed-forward only (skip attention for speed in training)
-        let ff_out: Vec<f32> = (0..D_MODEL)
-            .map(|i| {
-                let h: f32 = (0..crate::transformer::D_FF)
-                    .map(|j| {
-                        let act: f32 = (0..D_MODEL)
-                            .map(|k| hidden[k] * layer.ff.w1[k * crate::transformer::D_FF + j])
-                            .sum::<f32>()
-                            + layer.ff.b1[j];
-                        act.max(0.0) * layer.ff.w2[j * D_MODEL + i]
-                    })
-                    .sum::<f32>()
-                    + layer.ff.b2[i];
-                h
-            })
-            .collect();
-        for (i, v) in hidden.iter_mut().enumerate() {
-            *v += ff_out[i];
-        }
+        let ff_out = feed_forward(&hidden, &layer.ff);
+        linalg::add_assign(&mut hidden, &ff_out);
     }
 
     hidden
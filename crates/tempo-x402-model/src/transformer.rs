@@ -14,6 +14,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::checkpoint::{Checkpoint, CheckpointError, CheckpointWriter, Checkpointable};
+use crate::federated::Federated;
+use crate::linalg::{self, axpy, dot};
 use crate::vocab::{MAX_SEQ_LEN, VOCAB_SIZE};
 
 // ── Architecture Constants ───────────────────────────────────────────
@@ -197,14 +200,8 @@ impl PlanTransformer {
 
         // Step 3: Output projection (last position only)
         let last = &hidden[seq_len - 1];
-        let mut logits = vec![0.0f32; VOCAB_SIZE];
-        for v in 0..VOCAB_SIZE {
-            let mut sum = self.output_bias[v];
-            for d in 0..D_MODEL {
-                sum += last[d] * self.output_proj[d * VOCAB_SIZE + v];
-            }
-            logits[v] = sum;
-        }
+        let mut logits = linalg::gemv_t(&self.output_proj, last, D_MODEL, VOCAB_SIZE);
+        linalg::add_assign(&mut logits, &self.output_bias);
 
         logits
     }
@@ -259,10 +256,9 @@ impl PlanTransformer {
                 scores = exps.iter().map(|&e| e / sum.max(1e-10)).collect();
 
                 // Weighted sum of values
+                let out = &mut concat_heads[i][h * D_HEAD..(h + 1) * D_HEAD];
                 for j in 0..seq_len {
-                    for d in 0..D_HEAD {
-                        concat_heads[i][h * D_HEAD + d] += scores[j] * values[j][d];
-                    }
+                    axpy(out, scores[j], &values[j]);
                 }
             }
         }
@@ -349,7 +345,8 @@ impl PlanTransformer {
         }
     }
 
-    /// Merge a weight delta from a peer (federated averaging).
+    /// Merge a weight delta from a peer (federated averaging). Dense and
+    /// unvalidated; colony sync goes through [`crate::federated::merge`].
     pub fn merge_delta(&mut self, delta: &TransformerDelta, merge_rate: f32) {
         let mut w = self.flatten_weights();
         if w.len() != delta.weights.len() {
@@ -362,6 +359,150 @@ impl PlanTransformer {
     }
 }
 
+impl Checkpointable for PlanTransformer {
+    const ARCH: &'static str = "plan_transformer";
+
+    fn hyperparams() -> Vec<(&'static str, u64)> {
+        vec![
+            ("d_model", D_MODEL as u64),
+            ("n_heads", N_HEADS as u64),
+            ("d_ff", D_FF as u64),
+            ("n_layers", N_LAYERS as u64),
+            ("vocab", VOCAB_SIZE as u64),
+            ("max_seq", MAX_SEQ_LEN as u64),
+        ]
+    }
+
+    fn write_tensors(&self, w: &mut CheckpointWriter) {
+        w.meta("train_steps", self.train_steps)
+            .meta("running_loss", self.running_loss);
+        w.tensor("embedding", &[VOCAB_SIZE, D_MODEL], &self.embedding)
+            .tensor("pos_encoding", &[MAX_SEQ_LEN, D_MODEL], &self.pos_encoding);
+        for (i, layer) in self.layers.iter().enumerate() {
+            for (h, head) in layer.attention.heads.iter().enumerate() {
+                let p = format!("layers.{i}.heads.{h}");
+                w.tensor(&format!("{p}.wq"), &[D_MODEL, D_HEAD], &head.wq)
+                    .tensor(&format!("{p}.wk"), &[D_MODEL, D_HEAD], &head.wk)
+                    .tensor(&format!("{p}.wv"), &[D_MODEL, D_HEAD], &head.wv);
+            }
+            let p = format!("layers.{i}");
+            w.tensor(
+                &format!("{p}.wo"),
+                &[N_HEADS * D_HEAD, D_MODEL],
+                &layer.attention.wo,
+            )
+            .tensor(&format!("{p}.ff.w1"), &[D_MODEL, D_FF], &layer.ff.w1)
+            .tensor(&format!("{p}.ff.b1"), &[D_FF], &layer.ff.b1)
+            .tensor(&format!("{p}.ff.w2"), &[D_FF, D_MODEL], &layer.ff.w2)
+            .tensor(&format!("{p}.ff.b2"), &[D_MODEL], &layer.ff.b2)
+            .tensor(&format!("{p}.ln1_scale"), &[D_MODEL], &layer.ln1_scale)
+            .tensor(&format!("{p}.ln2_scale"), &[D_MODEL], &layer.ln2_scale);
+        }
+        w.tensor("output_proj", &[D_MODEL, VOCAB_SIZE], &self.output_proj)
+            .tensor("output_bias", &[VOCAB_SIZE], &self.output_bias);
+    }
+
+    fn read_tensors(c: &Checkpoint<'_>) -> Result<Self, CheckpointError> {
+        let layers = (0..N_LAYERS)
+            .map(|i| {
+                let heads = (0..N_HEADS)
+                    .map(|h| {
+                        let p = format!("layers.{i}.heads.{h}");
+                        Ok(AttentionHead {
+                            wq: c.tensor_vec(&format!("{p}.wq"), &[D_MODEL, D_HEAD])?,
+                            wk: c.tensor_vec(&format!("{p}.wk"), &[D_MODEL, D_HEAD])?,
+                            wv: c.tensor_vec(&format!("{p}.wv"), &[D_MODEL, D_HEAD])?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, CheckpointError>>()?;
+                let p = format!("layers.{i}");
+                Ok(TransformerLayer {
+                    attention: MultiHeadAttention {
+                        heads,
+                        wo: c.tensor_vec(&format!("{p}.wo"), &[N_HEADS * D_HEAD, D_MODEL])?,
+                    },
+                    ff: FeedForward {
+                        w1: c.tensor_vec(&format!("{p}.ff.w1"), &[D_MODEL, D_FF])?,
+                        b1: c.tensor_vec(&format!("{p}.ff.b1"), &[D_FF])?,
+                        w2: c.tensor_vec(&format!("{p}.ff.w2"), &[D_FF, D_MODEL])?,
+                        b2: c.tensor_vec(&format!("{p}.ff.b2"), &[D_MODEL])?,
+                    },
+                    ln1_scale: c.tensor_vec(&format!("{p}.ln1_scale"), &[D_MODEL])?,
+                    ln2_scale: c.tensor_vec(&format!("{p}.ln2_scale"), &[D_MODEL])?,
+                })
+            })
+            .collect::<Result<Vec<_>, CheckpointError>>()?;
+
+        Ok(Self {
+            embedding: c.tensor_vec("embedding", &[VOCAB_SIZE, D_MODEL])?,
+            pos_encoding: c.tensor_vec("pos_encoding", &[MAX_SEQ_LEN, D_MODEL])?,
+            layers,
+            output_proj: c.tensor_vec("output_proj", &[D_MODEL, VOCAB_SIZE])?,
+            output_bias: c.tensor_vec("output_bias", &[VOCAB_SIZE])?,
+            train_steps: c.meta_u64("train_steps"),
+            running_loss: c.meta_f32("running_loss"),
+        })
+    }
+
+    /// Legacy JSON carries no architecture header, so check the embedding
+    /// size to reject weights from before a model scaling.
+    fn from_legacy_json(json: &str) -> Option<Self> {
+        Self::from_json(json).filter(|m| m.embedding.len() == VOCAB_SIZE * D_MODEL)
+    }
+}
+
+impl Federated for PlanTransformer {
+    fn arch(&self) -> &'static str {
+        <Self as Checkpointable>::ARCH
+    }
+
+    fn train_steps(&self) -> u64 {
+        self.train_steps
+    }
+
+    /// Same order as [`PlanTransformer::flatten_weights`] (positional
+    /// encodings are fixed and not shared).
+    fn tensors(&self) -> Vec<&[f32]> {
+        let mut t: Vec<&[f32]> = vec![&self.embedding];
+        for layer in &self.layers {
+            for head in &layer.attention.heads {
+                t.extend([&head.wq[..], &head.wk, &head.wv]);
+            }
+            t.extend([
+                &layer.attention.wo[..],
+                &layer.ff.w1,
+                &layer.ff.b1,
+                &layer.ff.w2,
+                &layer.ff.b2,
+                &layer.ln1_scale,
+                &layer.ln2_scale,
+            ]);
+        }
+        t.extend([&self.output_proj[..], &self.output_bias]);
+        t
+    }
+
+    fn tensors_mut(&mut self) -> Vec<&mut [f32]> {
+        let mut t: Vec<&mut [f32]> = vec![&mut self.embedding];
+        for layer in &mut self.layers {
+            for head in &mut layer.attention.heads {
+                t.extend([&mut head.wq[..], &mut head.wk, &mut head.wv]);
+            }
+            t.extend([
+                &mut layer.attention.wo[..],
+                &mut layer.ff.w1,
+                &mut layer.ff.b1,
+                &mut layer.ff.w2,
+                &mut layer.ff.b2,
+                &mut layer.ln1_scale,
+                &mut layer.ln2_scale,
+            ]);
+        }
+        t.extend([&mut self.output_proj[..], &mut self.output_bias]);
+        t
+    }
+}
+
 /// Weight delta for federated transformer sharing between colony peers.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransformerDelta {
@@ -372,40 +513,24 @@ pub struct TransformerDelta {
 
 // ── Math Helpers ─────────────────────────────────────────────────────
 
-/// Matrix-vector multiply: (rows × cols) × (cols,) → (rows,)
+/// Matrix-vector multiply: `mat` is `[cols x rows]` (input-major), `vec` has
+/// `cols` elements → `(rows,)`.
 fn mat_vec_mul(mat: &[f32], vec: &[f32], cols: usize, rows: usize) -> Vec<f32> {
-    (0..rows)
-        .map(|r| (0..cols).map(|c| mat[c * rows + r] * vec[c]).sum::<f32>())
-        .collect()
-}
-
-/// Dot product.
-fn dot(a: &[f32], b: &[f32]) -> f32 {
-    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+    linalg::gemv_t(mat, vec, cols, rows)
 }
 
 /// Feed-forward: ReLU(x·W1 + b1)·W2 + b2
-fn feed_forward(x: &[f32], ff: &FeedForward) -> Vec<f32> {
+pub(crate) fn feed_forward(x: &[f32], ff: &FeedForward) -> Vec<f32> {
     // Up projection + ReLU
-    let h: Vec<f32> = (0..D_FF)
-        .map(|i| {
-            let sum: f32 = (0..D_MODEL)
-                .map(|j| x[j] * ff.w1[j * D_FF + i])
-                .sum::<f32>()
-                + ff.b1[i];
-            sum.max(0.0) // ReLU
-        })
-        .collect();
+    let mut h = linalg::gemv_t(&ff.w1, x, D_MODEL, D_FF);
+    for (h, b) in h.iter_mut().zip(&ff.b1) {
+        *h = (*h + b).max(0.0); // ReLU
+    }
 
     // Down projection
-    (0..D_MODEL)
-        .map(|i| {
-            (0..D_FF)
-                .map(|j| h[j] * ff.w2[j * D_MODEL + i])
-                .sum::<f32>()
-                + ff.b2[i]
-        })
-        .collect()
+    let mut out = linalg::gemv_t(&ff.w2, &h, D_FF, D_MODEL);
+    linalg::add_assign(&mut out, &ff.b2);
+    out
 }
 
 /// Simplified layer norm (scale only, no bias).
@@ -491,4 +616,23 @@ mod tests {
         assert_eq!(restored.param_count(), model.param_count());
         assert_eq!(restored.train_steps, 0);
     }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut model = PlanTransformer::new();
+        model.train_steps = 12;
+        let restored = PlanTransformer::load_bytes(&model.to_checkpoint()).unwrap();
+        assert_eq!(restored.flatten_weights(), model.flatten_weights());
+        assert_eq!(restored.pos_encoding, model.pos_encoding);
+        assert_eq!(restored.train_steps, 12);
+
+        // A checkpoint from a differently sized architecture is rejected by
+        // the header check before any tensor is read.
+        let mut w = crate::checkpoint::CheckpointWriter::new(PlanTransformer::ARCH);
+        w.param("d_model", 128);
+        assert!(matches!(
+            PlanTransformer::load_bytes(&w.finish()),
+            Err(CheckpointError::Param { .. })
+        ));
+    }
 }
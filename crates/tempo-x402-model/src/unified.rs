@@ -12,7 +12,15 @@
 //! The shared encoder learns from ALL tasks simultaneously — the key advantage
 //! over separate models.
 
-use rayon::prelude::*;
+use crate::autograd::{
+    collect, named, DecoderGraph, DecoderParams, EncoderGraph, EncoderParams, Tape,
+    DECODER_TENSORS, ENCODER_TENSORS,
+};
+use crate::checkpoint::{Checkpoint, CheckpointError, CheckpointWriter, Checkpointable};
+use crate::federated::Federated;
+use crate::linalg::{self, add_assign};
+use crate::optim::{Gradients, Optimizer, StepStats};
+use crate::quantize::Dims;
 
 // ── Task prefix tokens ────────────────────────────────────────────
 
@@ -22,8 +30,9 @@ pub const TASK_CODE: u32 = 8194; // code generation
 pub const TASK_PLAN: u32 = 8195; // plan generation
 pub const UNIFIED_VOCAB: usize = 8200; // 8192 BPE + 8 task tokens
 
-// ── Architecture constants ────────────────────────────────────────
+// ── Architecture ──────────────────────────────────────────────────
 
+// Default architecture; see [`ModelConfig`] for the runtime shape.
 pub const D_MODEL: usize = 384;
 pub const N_HEADS: usize = 6;
 pub const D_HEAD: usize = D_MODEL / N_HEADS; // 64
@@ -35,18 +44,188 @@ pub const DEC_LAYERS: usize = 3;
 /// Fast head output dimensionality:
 /// 1 success_prob + 11 error categories + 11 capability confidences + 1 quality score
 pub const FAST_OUTPUT: usize = 24;
+/// Fast head hidden width.
+pub const FAST_HIDDEN: usize = 256;
+
+/// Scale of the noise that fills new weights when a model is grown.
+const GROW_NOISE: f32 = 1e-3;
+
+/// Shape of a [`UnifiedModel`]. Stored in every checkpoint header and used
+/// for allocation and every forward/backward pass, so nodes can run
+/// different sizes (e.g. 2 layers at D=256 on a small box) and a trained
+/// model can be grown with [`UnifiedModel::grow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ModelConfig {
+    pub d_model: usize,
+    pub n_heads: usize,
+    pub d_ff: usize,
+    pub max_seq: usize,
+    /// BPE vocabulary plus the task tokens.
+    pub vocab: usize,
+    pub enc_layers: usize,
+    pub dec_layers: usize,
+}
+
+/// Why a [`ModelConfig`] (or a growth step to one) is invalid.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ConfigError {
+    #[error("{0} must be non-zero")]
+    Zero(&'static str),
+
+    #[error("d_model {d_model} is not divisible by n_heads {n_heads}")]
+    Heads { d_model: usize, n_heads: usize },
+
+    #[error("vocab {0} leaves no room for the task tokens")]
+    Vocab(usize),
+
+    #[error("cannot shrink {name} from {from} to {to}")]
+    Shrink {
+        name: &'static str,
+        from: usize,
+        to: usize,
+    },
+
+    #[error("head width must stay {from} when growing (got {to}); scale n_heads with d_model")]
+    HeadWidth { from: usize, to: usize },
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            d_model: D_MODEL,
+            n_heads: N_HEADS,
+            d_ff: D_FF,
+            max_seq: MAX_SEQ,
+            vocab: UNIFIED_VOCAB,
+            enc_layers: ENC_LAYERS,
+            dec_layers: DEC_LAYERS,
+        }
+    }
+}
+
+impl ModelConfig {
+    /// Per-head width.
+    pub fn d_head(&self) -> usize {
+        self.d_model / self.n_heads.max(1)
+    }
+
+    /// Check the shape is usable: non-zero sizes, whole heads, and room for
+    /// the task tokens.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (name, value) in self.sizes() {
+            if value == 0 {
+                return Err(ConfigError::Zero(name));
+            }
+        }
+        if !self.d_model.is_multiple_of(self.n_heads) {
+            return Err(ConfigError::Heads {
+                d_model: self.d_model,
+                n_heads: self.n_heads,
+            });
+        }
+        if self.vocab <= TASK_PLAN as usize {
+            return Err(ConfigError::Vocab(self.vocab));
+        }
+        Ok(())
+    }
+
+    /// Check that a model of this shape can be grown into `target`: nothing
+    /// shrinks and the head width is unchanged, so existing heads keep their
+    /// slice of the residual stream.
+    pub fn check_growth(&self, target: &ModelConfig) -> Result<(), ConfigError> {
+        target.validate()?;
+        for ((name, from), (_, to)) in self.sizes().into_iter().zip(target.sizes()) {
+            if to < from {
+                return Err(ConfigError::Shrink { name, from, to });
+            }
+        }
+        if target.d_head() != self.d_head() {
+            return Err(ConfigError::HeadWidth {
+                from: self.d_head(),
+                to: target.d_head(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Exact parameter count of a model with this shape.
+    pub fn param_count(&self) -> usize {
+        let (d, ff, v, s) = (self.d_model, self.d_ff, self.vocab, self.max_seq);
+        let embed = v * d + 2 * s * d; // shared embeddings + enc_pos + dec_pos
+        let enc_per_layer = 4 * d * d + 2 * d * ff + 2 * d; // attn + ff + 2 ln
+        let dec_per_layer = 8 * d * d + 2 * d * ff + 3 * d; // self-attn + cross-attn + ff + 3 ln
+        let fast_head = d * FAST_HIDDEN + FAST_HIDDEN * FAST_OUTPUT + FAST_OUTPUT;
+        embed + self.enc_layers * enc_per_layer + self.dec_layers * dec_per_layer + v + fast_head
+    }
+
+    /// Short human-readable description, e.g. `"3+3 layers D=384"`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{}+{} layers D={} heads={} ff={}",
+            self.enc_layers, self.dec_layers, self.d_model, self.n_heads, self.d_ff
+        )
+    }
+
+    pub(crate) fn dims(&self) -> Dims {
+        Dims {
+            d: self.d_model,
+            n_heads: self.n_heads,
+            d_head: self.d_head(),
+            ff: self.d_ff,
+        }
+    }
+
+    fn sizes(&self) -> [(&'static str, usize); 7] {
+        [
+            ("d_model", self.d_model),
+            ("n_heads", self.n_heads),
+            ("d_ff", self.d_ff),
+            ("max_seq", self.max_seq),
+            ("vocab", self.vocab),
+            ("enc_layers", self.enc_layers),
+            ("dec_layers", self.dec_layers),
+        ]
+    }
+
+    /// Header hyperparameters, under the names checkpoints have always used.
+    pub(crate) fn header_params(&self) -> Vec<(&'static str, u64)> {
+        self.sizes()
+            .into_iter()
+            .map(|(name, value)| (name, value as u64))
+            .collect()
+    }
+
+    /// Read the shape back from a checkpoint header.
+    pub(crate) fn from_checkpoint(c: &Checkpoint<'_>) -> Result<Self, CheckpointError> {
+        let p = |name| c.param(name).map(|v| v as usize);
+        let config = Self {
+            d_model: p("d_model")?,
+            n_heads: p("n_heads")?,
+            d_ff: p("d_ff")?,
+            max_seq: p("max_seq")?,
+            vocab: p("vocab")?,
+            enc_layers: p("enc_layers")?,
+            dec_layers: p("dec_layers")?,
+        };
+        config
+            .validate()
+            .map_err(|e| CheckpointError::Header(e.to_string()))?;
+        Ok(config)
+    }
+}
 
 // ── Layer types ───────────────────────────────────────────────────
 
 /// Encoder layer — bidirectional self-attention + FFN.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct EncoderLayer {
-    pub wq: Vec<f32>, // D_MODEL x D_MODEL
+    pub wq: Vec<f32>, // d_model x d_model
     pub wk: Vec<f32>,
     pub wv: Vec<f32>,
     pub wo: Vec<f32>,
-    pub ff_w1: Vec<f32>, // D_MODEL x D_FF
-    pub ff_w2: Vec<f32>, // D_FF x D_MODEL
+    pub ff_w1: Vec<f32>, // d_model x d_ff
+    pub ff_w2: Vec<f32>, // d_ff x d_model
     pub ln1_scale: Vec<f32>,
     pub ln2_scale: Vec<f32>,
 }
@@ -69,38 +248,154 @@ pub struct DecoderLayer {
     pub ln3_scale: Vec<f32>,
 }
 
+impl EncoderLayer {
+    fn init(rng: &mut XorShift64, d: usize, ff: usize) -> Self {
+        Self {
+            wq: xavier_init(rng, d * d, d, d),
+            wk: xavier_init(rng, d * d, d, d),
+            wv: xavier_init(rng, d * d, d, d),
+            wo: xavier_init(rng, d * d, d, d),
+            ff_w1: xavier_init(rng, d * ff, d, ff),
+            ff_w2: xavier_init(rng, ff * d, ff, d),
+            ln1_scale: vec![1.0; d],
+            ln2_scale: vec![1.0; d],
+        }
+    }
+}
+
+impl DecoderLayer {
+    fn init(rng: &mut XorShift64, d: usize, ff: usize) -> Self {
+        Self {
+            wq: xavier_init(rng, d * d, d, d),
+            wk: xavier_init(rng, d * d, d, d),
+            wv: xavier_init(rng, d * d, d, d),
+            wo: xavier_init(rng, d * d, d, d),
+            cross_wq: xavier_init(rng, d * d, d, d),
+            cross_wk: xavier_init(rng, d * d, d, d),
+            cross_wv: xavier_init(rng, d * d, d, d),
+            cross_wo: xavier_init(rng, d * d, d, d),
+            ff_w1: xavier_init(rng, d * ff, d, ff),
+            ff_w2: xavier_init(rng, ff * d, ff, d),
+            ln1_scale: vec![1.0; d],
+            ln2_scale: vec![1.0; d],
+            ln3_scale: vec![1.0; d],
+        }
+    }
+}
+
+impl EncoderParams for EncoderLayer {
+    fn tensors(&self) -> [&[f32]; 8] {
+        [
+            &self.wq,
+            &self.wk,
+            &self.wv,
+            &self.wo,
+            &self.ff_w1,
+            &self.ff_w2,
+            &self.ln1_scale,
+            &self.ln2_scale,
+        ]
+    }
+
+    fn tensors_mut(&mut self) -> [&mut [f32]; 8] {
+        [
+            &mut self.wq,
+            &mut self.wk,
+            &mut self.wv,
+            &mut self.wo,
+            &mut self.ff_w1,
+            &mut self.ff_w2,
+            &mut self.ln1_scale,
+            &mut self.ln2_scale,
+        ]
+    }
+}
+
+impl DecoderParams for DecoderLayer {
+    fn tensors(&self) -> [&[f32]; 13] {
+        [
+            &self.wq,
+            &self.wk,
+            &self.wv,
+            &self.wo,
+            &self.cross_wq,
+            &self.cross_wk,
+            &self.cross_wv,
+            &self.cross_wo,
+            &self.ff_w1,
+            &self.ff_w2,
+            &self.ln1_scale,
+            &self.ln2_scale,
+            &self.ln3_scale,
+        ]
+    }
+
+    fn tensors_mut(&mut self) -> [&mut [f32]; 13] {
+        [
+            &mut self.wq,
+            &mut self.wk,
+            &mut self.wv,
+            &mut self.wo,
+            &mut self.cross_wq,
+            &mut self.cross_wk,
+            &mut self.cross_wv,
+            &mut self.cross_wo,
+            &mut self.ff_w1,
+            &mut self.ff_w2,
+            &mut self.ln1_scale,
+            &mut self.ln2_scale,
+            &mut self.ln3_scale,
+        ]
+    }
+}
+
 // ── Unified Model ─────────────────────────────────────────────────
 
 /// Unified Cognitive Model — shared encoder with fast and slow output heads.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct UnifiedModel {
     // Shared encoder (bidirectional)
-    pub embeddings: Vec<f32>,              // UNIFIED_VOCAB x D_MODEL
-    pub enc_pos: Vec<f32>,                 // MAX_SEQ x D_MODEL
-    pub encoder_layers: Vec<EncoderLayer>, // ENC_LAYERS
+    pub embeddings: Vec<f32>,              // vocab x d_model
+    pub enc_pos: Vec<f32>,                 // max_seq x d_model
+    pub encoder_layers: Vec<EncoderLayer>, // enc_layers
 
     // Fast classification head
-    pub fast_w1: Vec<f32>,   // D_MODEL x 256
-    pub fast_w2: Vec<f32>,   // 256 x FAST_OUTPUT
+    pub fast_w1: Vec<f32>,   // d_model x FAST_HIDDEN
+    pub fast_w2: Vec<f32>,   // FAST_HIDDEN x FAST_OUTPUT
     pub fast_bias: Vec<f32>, // FAST_OUTPUT
 
     // Slow decoder head
-    pub dec_pos: Vec<f32>,                 // MAX_SEQ x D_MODEL
-    pub decoder_layers: Vec<DecoderLayer>, // DEC_LAYERS
-    pub output_bias: Vec<f32>,             // UNIFIED_VOCAB
+    pub dec_pos: Vec<f32>,                 // max_seq x d_model
+    pub decoder_layers: Vec<DecoderLayer>, // dec_layers
+    pub output_bias: Vec<f32>,             // vocab
 
     pub train_steps: u64,
     pub running_loss: f32,
-    pub d_model: usize,
+    /// Architecture. Legacy JSON checkpoints predate it and always used the
+    /// default shape.
+    #[serde(default)]
+    pub config: ModelConfig,
 }
 
 impl UnifiedModel {
-    /// Create a new model with Xavier initialization.
+    /// Create a new model of the default shape with Xavier initialization.
     pub fn new() -> Self {
-        let d = D_MODEL;
-        let v = UNIFIED_VOCAB;
-        let s = MAX_SEQ;
-        let ff = D_FF;
+        Self::with_config(ModelConfig::default())
+    }
+
+    /// Create a new model of the given shape with Xavier initialization.
+    ///
+    /// # Panics
+    ///
+    /// If `config` fails [`ModelConfig::validate`].
+    pub fn with_config(config: ModelConfig) -> Self {
+        if let Err(e) = config.validate() {
+            panic!("invalid unified model config: {e}");
+        }
+        let d = config.d_model;
+        let v = config.vocab;
+        let s = config.max_seq;
+        let ff = config.d_ff;
 
         let mut rng = XorShift64(42);
 
@@ -109,40 +404,21 @@ impl UnifiedModel {
         let dec_pos = xavier_init(&mut rng, s * d, s, d);
         let output_bias = vec![0.0; v];
 
-        let encoder_layers = (0..ENC_LAYERS)
-            .map(|_| EncoderLayer {
-                wq: xavier_init(&mut rng, d * d, d, d),
-                wk: xavier_init(&mut rng, d * d, d, d),
-                wv: xavier_init(&mut rng, d * d, d, d),
-                wo: xavier_init(&mut rng, d * d, d, d),
-                ff_w1: xavier_init(&mut rng, d * ff, d, ff),
-                ff_w2: xavier_init(&mut rng, ff * d, ff, d),
-                ln1_scale: vec![1.0; d],
-                ln2_scale: vec![1.0; d],
-            })
+        let encoder_layers = (0..config.enc_layers)
+            .map(|_| EncoderLayer::init(&mut rng, d, ff))
             .collect();
-
-        let decoder_layers = (0..DEC_LAYERS)
-            .map(|_| DecoderLayer {
-                wq: xavier_init(&mut rng, d * d, d, d),
-                wk: xavier_init(&mut rng, d * d, d, d),
-                wv: xavier_init(&mut rng, d * d, d, d),
-                wo: xavier_init(&mut rng, d * d, d, d),
-                cross_wq: xavier_init(&mut rng, d * d, d, d),
-                cross_wk: xavier_init(&mut rng, d * d, d, d),
-                cross_wv: xavier_init(&mut rng, d * d, d, d),
-                cross_wo: xavier_init(&mut rng, d * d, d, d),
-                ff_w1: xavier_init(&mut rng, d * ff, d, ff),
-                ff_w2: xavier_init(&mut rng, ff * d, ff, d),
-                ln1_scale: vec![1.0; d],
-                ln2_scale: vec![1.0; d],
-                ln3_scale: vec![1.0; d],
-            })
+        let decoder_layers = (0..config.dec_layers)
+            .map(|_| DecoderLayer::init(&mut rng, d, ff))
             .collect();
 
-        // Fast head: D_MODEL -> 256 -> FAST_OUTPUT
-        let fast_w1 = xavier_init(&mut rng, d * 256, d, 256);
-        let fast_w2 = xavier_init(&mut rng, 256 * FAST_OUTPUT, 256, FAST_OUTPUT);
+        // Fast head: d_model -> FAST_HIDDEN -> FAST_OUTPUT
+        let fast_w1 = xavier_init(&mut rng, d * FAST_HIDDEN, d, FAST_HIDDEN);
+        let fast_w2 = xavier_init(
+            &mut rng,
+            FAST_HIDDEN * FAST_OUTPUT,
+            FAST_HIDDEN,
+            FAST_OUTPUT,
+        );
         let fast_bias = vec![0.0; FAST_OUTPUT];
 
         Self {
@@ -157,15 +433,97 @@ impl UnifiedModel {
             output_bias,
             train_steps: 0,
             running_loss: 0.0,
-            d_model: d,
+            config,
         }
     }
 
+    /// Grow into a larger shape, keeping everything learned so far.
+    ///
+    /// Existing weights are copied into the top-left corner of the larger
+    /// tensors and new entries start as ±[`GROW_NOISE`] noise. Added layers
+    /// go on top of each stack with zeroed output projections, so through
+    /// the residual path they start as the identity: adding layers (or
+    /// widening `d_ff`) leaves outputs all but unchanged, while widening
+    /// `d_model` is compensated in the layer-norm scales and stays close.
+    pub fn grow(&self, target: ModelConfig) -> Result<Self, ConfigError> {
+        let from = self.config;
+        from.check_growth(&target)?;
+        let (d0, ff0, d, ff) = (from.d_model, from.d_ff, target.d_model, target.d_ff);
+        let mut rng = XorShift64(42 ^ self.train_steps.wrapping_add(1));
+        let rng = &mut rng;
+
+        let mut encoder_layers: Vec<EncoderLayer> = self
+            .encoder_layers
+            .iter()
+            .map(|l| EncoderLayer {
+                wq: pad(&l.wq, (d0, d0), (d, d), rng),
+                wk: pad(&l.wk, (d0, d0), (d, d), rng),
+                wv: pad(&l.wv, (d0, d0), (d, d), rng),
+                wo: pad(&l.wo, (d0, d0), (d, d), rng),
+                ff_w1: pad(&l.ff_w1, (ff0, d0), (ff, d), rng),
+                ff_w2: pad(&l.ff_w2, (d0, ff0), (d, ff), rng),
+                ln1_scale: pad_scale(&l.ln1_scale, d),
+                ln2_scale: pad_scale(&l.ln2_scale, d),
+            })
+            .collect();
+        encoder_layers.resize_with(target.enc_layers, || {
+            let mut l = EncoderLayer::init(rng, d, ff);
+            l.wo.fill(0.0);
+            l.ff_w2.fill(0.0);
+            l
+        });
+
+        let mut decoder_layers: Vec<DecoderLayer> = self
+            .decoder_layers
+            .iter()
+            .map(|l| DecoderLayer {
+                wq: pad(&l.wq, (d0, d0), (d, d), rng),
+                wk: pad(&l.wk, (d0, d0), (d, d), rng),
+                wv: pad(&l.wv, (d0, d0), (d, d), rng),
+                wo: pad(&l.wo, (d0, d0), (d, d), rng),
+                cross_wq: pad(&l.cross_wq, (d0, d0), (d, d), rng),
+                cross_wk: pad(&l.cross_wk, (d0, d0), (d, d), rng),
+                cross_wv: pad(&l.cross_wv, (d0, d0), (d, d), rng),
+                cross_wo: pad(&l.cross_wo, (d0, d0), (d, d), rng),
+                ff_w1: pad(&l.ff_w1, (ff0, d0), (ff, d), rng),
+                ff_w2: pad(&l.ff_w2, (d0, ff0), (d, ff), rng),
+                ln1_scale: pad_scale(&l.ln1_scale, d),
+                ln2_scale: pad_scale(&l.ln2_scale, d),
+                ln3_scale: pad_scale(&l.ln3_scale, d),
+            })
+            .collect();
+        decoder_layers.resize_with(target.dec_layers, || {
+            let mut l = DecoderLayer::init(rng, d, ff);
+            l.wo.fill(0.0);
+            l.cross_wo.fill(0.0);
+            l.ff_w2.fill(0.0);
+            l
+        });
+
+        let mut output_bias = self.output_bias.clone();
+        output_bias.resize(target.vocab, 0.0);
+
+        Ok(Self {
+            embeddings: pad(&self.embeddings, (from.vocab, d0), (target.vocab, d), rng),
+            enc_pos: pad(&self.enc_pos, (from.max_seq, d0), (target.max_seq, d), rng),
+            encoder_layers,
+            fast_w1: pad(&self.fast_w1, (FAST_HIDDEN, d0), (FAST_HIDDEN, d), rng),
+            fast_w2: self.fast_w2.clone(),
+            fast_bias: self.fast_bias.clone(),
+            dec_pos: pad(&self.dec_pos, (from.max_seq, d0), (target.max_seq, d), rng),
+            decoder_layers,
+            output_bias,
+            train_steps: self.train_steps,
+            running_loss: self.running_loss,
+            config: target,
+        })
+    }
+
     /// Encode context tokens with bidirectional attention.
     /// Returns flattened [seq_len x d_model].
     pub fn encode(&self, tokens: &[u32]) -> Vec<f32> {
-        let d = self.d_model;
-        let seq_len = tokens.len().min(MAX_SEQ);
+        let d = self.config.d_model;
+        let seq_len = tokens.len().min(self.config.max_seq);
 
         if seq_len == 0 {
             return vec![];
@@ -174,7 +532,7 @@ impl UnifiedModel {
         // Embed + encoder positional encoding
         let mut hidden = vec![0.0f32; seq_len * d];
         for (pos, &tok) in tokens.iter().take(seq_len).enumerate() {
-            let tok_idx = tok as usize % UNIFIED_VOCAB;
+            let tok_idx = tok as usize % self.config.vocab;
             for j in 0..d {
                 hidden[pos * d + j] = self.embeddings[tok_idx * d + j] + self.enc_pos[pos * d + j];
             }
@@ -192,8 +550,8 @@ impl UnifiedModel {
     /// Returns FAST_OUTPUT floats (raw; caller applies sigmoid/softmax as needed).
     /// Used for brain prediction and quality evaluation.
     pub fn fast_predict(&self, tokens: &[u32]) -> Vec<f32> {
-        let d = self.d_model;
-        let seq_len = tokens.len().min(MAX_SEQ);
+        let d = self.config.d_model;
+        let seq_len = tokens.len().min(self.config.max_seq);
 
         if seq_len == 0 {
             return vec![0.0; FAST_OUTPUT];
@@ -213,40 +571,34 @@ impl UnifiedModel {
             pooled[j] *= inv_len;
         }
 
-        // FFN layer 1: D_MODEL -> 256, ReLU
-        let mut h1 = vec![0.0f32; 256];
-        for j in 0..256 {
-            let w_idx = j * d;
-            let val: f32 = (0..d).map(|k| pooled[k] * self.fast_w1[w_idx + k]).sum();
-            h1[j] = val.max(0.0); // ReLU
+        // FFN layer 1: d_model -> FAST_HIDDEN, ReLU
+        let mut h1 = linalg::gemv(&self.fast_w1, &pooled, FAST_HIDDEN, d);
+        for h in &mut h1 {
+            *h = h.max(0.0); // ReLU
         }
 
-        // FFN layer 2: 256 -> FAST_OUTPUT + bias
-        let mut output = vec![0.0f32; FAST_OUTPUT];
-        for j in 0..FAST_OUTPUT {
-            let w_idx = j * 256;
-            let val: f32 = (0..256).map(|k| h1[k] * self.fast_w2[w_idx + k]).sum();
-            output[j] = val + self.fast_bias[j];
-        }
+        // FFN layer 2: FAST_HIDDEN -> FAST_OUTPUT + bias
+        let mut output = linalg::gemv(&self.fast_w2, &h1, FAST_OUTPUT, FAST_HIDDEN);
+        add_assign(&mut output, &self.fast_bias);
 
         output
     }
 
     /// Slow inference: decode target conditioned on encoder output.
-    /// Returns logits of shape [UNIFIED_VOCAB] for the LAST token position.
+    /// Returns logits of shape [vocab] for the LAST token position.
     /// Used for code generation and plan generation.
     pub fn decode(&self, target: &[u32], encoder_output: &[f32], enc_len: usize) -> Vec<f32> {
-        let d = self.d_model;
-        let seq_len = target.len().min(MAX_SEQ);
+        let d = self.config.d_model;
+        let seq_len = target.len().min(self.config.max_seq);
 
         if seq_len == 0 {
-            return vec![0.0; UNIFIED_VOCAB];
+            return vec![0.0; self.config.vocab];
         }
 
         // Embed + decoder positional encoding
         let mut hidden = vec![0.0f32; seq_len * d];
         for (pos, &tok) in target.iter().take(seq_len).enumerate() {
-            let tok_idx = tok as usize % UNIFIED_VOCAB;
+            let tok_idx = tok as usize % self.config.vocab;
             for j in 0..d {
                 hidden[pos * d + j] = self.embeddings[tok_idx * d + j] + self.dec_pos[pos * d + j];
             }
@@ -259,27 +611,17 @@ impl UnifiedModel {
 
         // Output projection (last position): hidden[last] x embeddings^T + bias
         let last_hidden = &hidden[(seq_len - 1) * d..seq_len * d];
-        let logits: Vec<f32> = (0..UNIFIED_VOCAB)
-            .into_par_iter()
-            .map(|v_idx| {
-                let mut dot = self.output_bias[v_idx];
-                let emb_off = v_idx * d;
-                for j in 0..d {
-                    dot += last_hidden[j] * self.embeddings[emb_off + j];
-                }
-                dot
-            })
-            .collect();
-
+        let mut logits = linalg::gemv(&self.embeddings, last_hidden, self.config.vocab, d);
+        add_assign(&mut logits, &self.output_bias);
         logits
     }
 
     /// Legacy forward compat -- encode+decode on same tokens.
-    /// Returns logits of shape [UNIFIED_VOCAB] for the LAST token position.
+    /// Returns logits of shape [vocab] for the LAST token position.
     pub fn forward(&self, tokens: &[u32]) -> Vec<f32> {
-        let seq_len = tokens.len().min(MAX_SEQ);
+        let seq_len = tokens.len().min(self.config.max_seq);
         if seq_len == 0 {
-            return vec![0.0; UNIFIED_VOCAB];
+            return vec![0.0; self.config.vocab];
         }
         let enc = self.encode(tokens);
         self.decode(tokens, &enc, seq_len)
@@ -289,141 +631,9 @@ impl UnifiedModel {
     /// MSE loss, backprop through fast head + shared encoder.
     /// Returns loss.
     pub fn train_fast(&mut self, tokens: &[u32], targets: &[f32], lr: f32) -> f32 {
-        let d = self.d_model;
-        let seq_len = tokens.len().min(MAX_SEQ);
-
-        if seq_len == 0 || targets.len() != FAST_OUTPUT {
-            return 0.0;
-        }
-
-        // === ENCODER FORWARD -- save activations ===
-
-        let mut enc_hidden = vec![0.0f32; seq_len * d];
-        for (pos, &tok) in tokens.iter().take(seq_len).enumerate() {
-            let tok_idx = tok as usize % UNIFIED_VOCAB;
-            for j in 0..d {
-                enc_hidden[pos * d + j] =
-                    self.embeddings[tok_idx * d + j] + self.enc_pos[pos * d + j];
-            }
-        }
-
-        let mut enc_layer_inputs: Vec<Vec<f32>> = Vec::with_capacity(ENC_LAYERS + 1);
-        enc_layer_inputs.push(enc_hidden.clone());
-
-        for layer in &self.encoder_layers {
-            enc_hidden = self.apply_encoder_layer(layer, &enc_hidden, seq_len);
-            enc_layer_inputs.push(enc_hidden.clone());
-        }
-
-        // === MEAN POOL ===
-
-        let mut pooled = vec![0.0f32; d];
-        for pos in 0..seq_len {
-            for j in 0..d {
-                pooled[j] += enc_hidden[pos * d + j];
-            }
-        }
-        let inv_len = 1.0 / seq_len as f32;
-        for j in 0..d {
-            pooled[j] *= inv_len;
-        }
-
-        // === FAST HEAD FORWARD ===
-
-        // Layer 1: D -> 256, ReLU
-        let mut h1 = vec![0.0f32; 256];
-        for j in 0..256 {
-            let w_idx = j * d;
-            let val: f32 = (0..d).map(|k| pooled[k] * self.fast_w1[w_idx + k]).sum();
-            h1[j] = val.max(0.0);
-        }
-
-        // Layer 2: 256 -> FAST_OUTPUT + bias
-        let mut output = vec![0.0f32; FAST_OUTPUT];
-        for j in 0..FAST_OUTPUT {
-            let w_idx = j * 256;
-            let val: f32 = (0..256).map(|k| h1[k] * self.fast_w2[w_idx + k]).sum();
-            output[j] = val + self.fast_bias[j];
-        }
-
-        // === MSE LOSS ===
-
-        let mut loss = 0.0f32;
-        let mut d_output = vec![0.0f32; FAST_OUTPUT];
-        for j in 0..FAST_OUTPUT {
-            let diff = output[j] - targets[j];
-            loss += diff * diff;
-            d_output[j] = 2.0 * diff / FAST_OUTPUT as f32; // d(MSE)/d(output)
-        }
-        loss /= FAST_OUTPUT as f32;
-
-        // === BACKPROP THROUGH FAST HEAD ===
-
-        // Gradient through layer 2
-        let mut d_h1 = vec![0.0f32; 256];
-        for j in 0..FAST_OUTPUT {
-            let g = d_output[j];
-            if g.abs() < 1e-8 {
-                continue;
-            }
-            // Update bias
-            self.fast_bias[j] -= lr * clip_grad(g);
-            // Update fast_w2
-            let w_idx = j * 256;
-            for k in 0..256 {
-                self.fast_w2[w_idx + k] -= lr * clip_grad(g * h1[k]);
-                d_h1[k] += g * self.fast_w2[w_idx + k];
-            }
-        }
-
-        // Gradient through ReLU
-        for k in 0..256 {
-            if h1[k] <= 0.0 {
-                d_h1[k] = 0.0;
-            }
-        }
-
-        // Gradient through layer 1
-        let mut d_pooled = vec![0.0f32; d];
-        for j in 0..256 {
-            if d_h1[j].abs() < 1e-8 {
-                continue;
-            }
-            let w_idx = j * d;
-            for k in 0..d {
-                self.fast_w1[w_idx + k] -= lr * clip_grad(d_h1[j] * pooled[k]);
-                d_pooled[k] += d_h1[j] * self.fast_w1[w_idx + k];
-            }
-        }
-
-        // === BACKPROP THROUGH MEAN POOL -> ENCODER ===
-
-        // d_pooled distributes equally to all positions
-        let mut d_enc_output = vec![0.0f32; seq_len * d];
-        for pos in 0..seq_len {
-            for j in 0..d {
-                d_enc_output[pos * d + j] = d_pooled[j] * inv_len;
-            }
-        }
-
-        // Backprop through encoder layers in reverse
-        self.backprop_encoder(&enc_layer_inputs, &mut d_enc_output, seq_len, lr);
-
-        // Input embedding gradient
-        for (pos, &tok) in tokens.iter().take(seq_len).enumerate() {
-            let tok_idx = tok as usize % UNIFIED_VOCAB;
-            let emb_off = tok_idx * d;
-            for j in 0..d {
-                let g = d_enc_output[pos * d + j];
-                if g.abs() > 1e-8 {
-                    self.embeddings[emb_off + j] -= lr * 0.1 * clip_grad(g);
-                }
-            }
-        }
-
-        self.train_steps += 1;
-        self.running_loss = 0.95 * self.running_loss + 0.05 * loss;
-
+        let mut grads = Gradients::new();
+        let loss = self.fast_gradients(tokens, targets, &mut grads);
+        self.sgd(&grads, lr);
         loss
     }
 
@@ -431,421 +641,180 @@ impl UnifiedModel {
     /// Cross-entropy loss on last target token.
     /// Returns loss.
     pub fn train_slow(&mut self, context: &[u32], target: &[u32], lr: f32) -> f32 {
-        if target.len() < 2 {
-            return 0.0;
-        }
-
-        let d = self.d_model;
-        let n_heads = N_HEADS;
-        let d_head = D_HEAD;
-        let ff = D_FF;
-        let dec_input = &target[..target.len() - 1];
-        let dec_target = target[target.len() - 1];
-        let enc_len = context.len().min(MAX_SEQ);
-        let dec_len = dec_input.len().min(MAX_SEQ);
-
-        // === ENCODER FORWARD -- save activations ===
-
-        let mut enc_hidden = vec![0.0f32; enc_len * d];
-        for (pos, &tok) in context.iter().take(enc_len).enumerate() {
-            let tok_idx = tok as usize % UNIFIED_VOCAB;
-            for j in 0..d {
-                enc_hidden[pos * d + j] =
-                    self.embeddings[tok_idx * d + j] + self.enc_pos[pos * d + j];
-            }
-        }
-
-        let mut enc_layer_inputs: Vec<Vec<f32>> = Vec::with_capacity(ENC_LAYERS + 1);
-        enc_layer_inputs.push(enc_hidden.clone());
+        let mut grads = Gradients::new();
+        let loss = self.slow_gradients(context, target, &mut grads);
+        self.sgd(&grads, lr);
+        loss
+    }
 
-        for layer in &self.encoder_layers {
-            enc_hidden = self.apply_encoder_layer(layer, &enc_hidden, enc_len);
-            enc_layer_inputs.push(enc_hidden.clone());
-        }
-        let encoder_output = enc_hidden;
+    /// Accumulate fast-head gradients for one example into `grads` without
+    /// touching the weights. Returns the loss (0.0 and nothing recorded for
+    /// an invalid example).
+    pub fn fast_gradients(&self, tokens: &[u32], targets: &[f32], grads: &mut Gradients) -> f32 {
+        self.fast_example(tokens, targets, Some(grads))
+    }
 
-        // === DECODER FORWARD -- save activations ===
+    /// Fast-head loss for one example, forward only.
+    pub fn fast_loss(&self, tokens: &[u32], targets: &[f32]) -> f32 {
+        self.fast_example(tokens, targets, None)
+    }
 
-        let mut dec_hidden = vec![0.0f32; dec_len * d];
-        for (pos, &tok) in dec_input.iter().take(dec_len).enumerate() {
-            let tok_idx = tok as usize % UNIFIED_VOCAB;
-            for j in 0..d {
-                dec_hidden[pos * d + j] =
-                    self.embeddings[tok_idx * d + j] + self.dec_pos[pos * d + j];
-            }
-        }
+    /// Slow-head loss for one (context, target) pair, forward only.
+    pub fn slow_loss(&self, context: &[u32], target: &[u32]) -> f32 {
+        self.slow_example(context, target, None)
+    }
 
-        let mut dec_layer_inputs: Vec<Vec<f32>> = Vec::with_capacity(DEC_LAYERS + 1);
-        dec_layer_inputs.push(dec_hidden.clone());
+    fn fast_example(&self, tokens: &[u32], targets: &[f32], grads: Option<&mut Gradients>) -> f32 {
+        let d = self.config.d_model;
+        let seq_len = tokens.len().min(self.config.max_seq);
 
-        for layer in &self.decoder_layers {
-            dec_hidden =
-                self.apply_decoder_layer(layer, &dec_hidden, dec_len, &encoder_output, enc_len);
-            dec_layer_inputs.push(dec_hidden.clone());
+        if seq_len == 0 || targets.len() != FAST_OUTPUT {
+            return 0.0;
         }
 
-        // === OUTPUT PROJECTION ===
-
-        let last_pos = dec_len - 1;
-        let last_hidden = &dec_hidden[last_pos * d..(last_pos + 1) * d];
-        let logits: Vec<f32> = (0..UNIFIED_VOCAB)
-            .into_par_iter()
-            .map(|v_idx| {
-                let mut dot = self.output_bias[v_idx];
-                let off = v_idx * d;
-                for j in 0..d {
-                    dot += last_hidden[j] * self.embeddings[off + j];
-                }
-                dot
-            })
-            .collect();
-
-        // Loss (cross-entropy)
-        let max_logit = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-        let exp_sum: f32 = logits.iter().map(|l| (l - max_logit).exp()).sum();
-        let log_sum_exp = max_logit + exp_sum.ln();
-        let target_idx = dec_target as usize % UNIFIED_VOCAB;
-        let loss = log_sum_exp - logits[target_idx];
-
-        let softmax: Vec<f32> = logits.iter().map(|l| (l - log_sum_exp).exp()).collect();
-        let mut d_logits = softmax;
-        d_logits[target_idx] -= 1.0;
-
-        // === BACKPROP ===
-
-        // 1. Output bias
-        self.output_bias
-            .par_iter_mut()
-            .zip(d_logits.par_iter())
-            .for_each(|(b, &g)| {
-                *b -= lr * clip_grad(g);
-            });
+        let mut t = Tape::new();
+        let embeddings = t.param(&self.embeddings, self.config.vocab, d);
+        let enc = EncoderGraph::build(
+            &mut t,
+            embeddings,
+            &self.enc_pos,
+            &self.encoder_layers,
+            &token_ids(&tokens[..seq_len], self.config.vocab),
+            self.config.dims(),
+        );
+
+        // Mean pool -> D x FAST_HIDDEN ReLU -> FAST_HIDDEN x FAST_OUTPUT + bias
+        let w1 = t.param(&self.fast_w1, FAST_HIDDEN, d);
+        let w2 = t.param(&self.fast_w2, FAST_OUTPUT, FAST_HIDDEN);
+        let bias = t.param(&self.fast_bias, 1, FAST_OUTPUT);
+        let pooled = t.mean_rows(enc.output);
+        let h1 = t.linear(pooled, w1);
+        let h1 = t.relu(h1);
+        let out = t.linear(h1, w2);
+        let out = t.add_row(out, bias);
+        let loss_var = t.mse(out, targets);
+        let loss = t.scalar(loss_var);
+        let Some(grads) = grads else {
+            return loss;
+        };
+
+        t.backward(loss_var);
+        collect(&t, w1, "fast_w1", grads);
+        collect(&t, w2, "fast_w2", grads);
+        collect(&t, bias, "fast_bias", grads);
+        enc.collect(&t, grads);
+        collect(&t, embeddings, "embeddings", grads);
+        grads.record(loss);
 
-        // 2. Embedding gradient from output projection
-        let d_hidden_last: Vec<f32> = (0..d)
-            .into_par_iter()
-            .map(|j| {
-                let mut sum = 0.0f32;
-                for v_idx in 0..UNIFIED_VOCAB {
-                    if d_logits[v_idx].abs() < 1e-7 {
-                        continue;
-                    }
-                    sum += d_logits[v_idx] * self.embeddings[v_idx * d + j];
-                }
-                sum
-            })
-            .collect();
+        loss
+    }
 
-        // Update embeddings from output projection
-        for v_idx in 0..UNIFIED_VOCAB {
-            if d_logits[v_idx].abs() < 1e-7 {
-                continue;
-            }
-            let g = d_logits[v_idx];
-            let emb_off = v_idx * d;
-            for j in 0..d {
-                self.embeddings[emb_off + j] -= lr * clip_grad(g * last_hidden[j]);
-            }
-        }
+    /// Accumulate slow-head gradients for one (context, target) pair into
+    /// `grads`. Returns the loss.
+    pub fn slow_gradients(&self, context: &[u32], target: &[u32], grads: &mut Gradients) -> f32 {
+        self.slow_example(context, target, Some(grads))
+    }
 
-        let mut d_dec_output = vec![0.0f32; dec_len * d];
-        for j in 0..d {
-            d_dec_output[last_pos * d + j] = d_hidden_last[j];
+    fn slow_example(&self, context: &[u32], target: &[u32], grads: Option<&mut Gradients>) -> f32 {
+        if target.len() < 2 {
+            return 0.0;
         }
 
-        // 3. Backprop through decoder layers in REVERSE order
-        let mut d_encoder = vec![0.0f32; enc_len * d];
-
-        for l_idx in (0..DEC_LAYERS).rev() {
-            let layer_in = &dec_layer_inputs[l_idx];
-            let d_out = &d_dec_output;
-
-            // --- FFN backprop ---
-            let normed3 = layer_norm(layer_in, &self.decoder_layers[l_idx].ln3_scale, dec_len, d);
-
-            let d_residual = d_out.clone();
-
-            for pos in 0..dec_len {
-                let d_norm = &d_out[pos * d..(pos + 1) * d];
-                if d_norm.iter().all(|&x| x.abs() < 1e-8) {
-                    continue;
-                }
-
-                let inp = &normed3[pos * d..(pos + 1) * d];
-                let mut ff_hidden = vec![0.0f32; ff];
-                for k in 0..ff {
-                    let w_idx = k * d;
-                    let val: f32 = (0..d)
-                        .map(|j| inp[j] * self.decoder_layers[l_idx].ff_w1[w_idx + j])
-                        .sum();
-                    ff_hidden[k] = val.max(0.0);
-                }
-
-                let lr_ff = lr * 0.5;
-                let mut d_ff = vec![0.0f32; ff];
-                for j in 0..d {
-                    let g_j = d_norm[j];
-                    if g_j.abs() < 1e-8 {
-                        continue;
-                    }
-                    for k in 0..ff {
-                        let idx = j * ff + k;
-                        self.decoder_layers[l_idx].ff_w2[idx] -=
-                            lr_ff * clip_grad(g_j * ff_hidden[k]);
-                        d_ff[k] += g_j * self.decoder_layers[l_idx].ff_w2[idx];
-                    }
-                }
-
-                for k in 0..ff {
-                    if ff_hidden[k] <= 0.0 {
-                        continue;
-                    }
-                    let w_idx = k * d;
-                    for j in 0..d {
-                        self.decoder_layers[l_idx].ff_w1[w_idx + j] -=
-                            lr_ff * clip_grad(d_ff[k] * inp[j]);
-                    }
-                }
-            }
-
-            // --- Cross-attention backprop ---
-            if enc_len > 0 {
-                let normed2 =
-                    layer_norm(layer_in, &self.decoder_layers[l_idx].ln2_scale, dec_len, d);
-
-                for pos in 0..dec_len {
-                    let d_pos = &d_residual[pos * d..(pos + 1) * d];
-                    if d_pos.iter().all(|&x| x.abs() < 1e-8) {
-                        continue;
-                    }
-
-                    let lr_cross = lr * 0.3;
-
-                    for h in 0..n_heads {
-                        let h_off = h * d_head;
-                        let inp = &normed2[pos * d..(pos + 1) * d];
-
-                        // Recompute cross-attention Q
-                        let mut q = vec![0.0f32; d_head];
-                        for j in 0..d_head {
-                            let w_idx = (h_off + j) * d;
-                            q[j] = (0..d)
-                                .map(|k| inp[k] * self.decoder_layers[l_idx].cross_wq[w_idx + k])
-                                .sum();
-                        }
-
-                        // Cross-attention weights
-                        let mut weights = vec![0.0f32; enc_len];
-                        for enc_pos in 0..enc_len {
-                            let enc_inp = &encoder_output[enc_pos * d..(enc_pos + 1) * d];
-                            let mut score = 0.0f32;
-                            for j in 0..d_head {
-                                let w_idx = (h_off + j) * d;
-                                let k_j: f32 = (0..d)
-                                    .map(|kk| {
-                                        enc_inp[kk]
-                                            * self.decoder_layers[l_idx].cross_wk[w_idx + kk]
-                                    })
-                                    .sum();
-                                score += q[j] * k_j;
-                            }
-                            weights[enc_pos] = score / (d_head as f32).sqrt();
-                        }
-
-                        let max_w = weights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-                        let exp_s: f32 = weights.iter().map(|w| (w - max_w).exp()).sum();
-                        for w in &mut weights {
-                            *w = (*w - max_w).exp() / exp_s;
-                        }
-
-                        // Recompute cross-attention output for this head
-                        let mut cross_head = vec![0.0f32; d_head];
-                        for enc_pos in 0..enc_len {
-                            let enc_inp = &encoder_output[enc_pos * d..(enc_pos + 1) * d];
-                            for j in 0..d_head {
-                                let w_idx = (h_off + j) * d;
-                                let v_j: f32 = (0..d)
-                                    .map(|kk| {
-                                        enc_inp[kk]
-                                            * self.decoder_layers[l_idx].cross_wv[w_idx + kk]
-                                    })
-                                    .sum();
-                                cross_head[j] += weights[enc_pos] * v_j;
-                            }
-                        }
-
-                        // Gradient through cross_wo
-                        let mut d_cross = vec![0.0f32; d_head];
-                        for j in 0..d {
-                            if d_pos[j].abs() < 1e-8 {
-                                continue;
-                            }
-                            for jj in 0..d_head {
-                                let idx = j * d + h_off + jj;
-                                self.decoder_layers[l_idx].cross_wo[idx] -=
-                                    lr_cross * clip_grad(d_pos[j] * cross_head[jj]);
-                                d_cross[jj] += d_pos[j] * self.decoder_layers[l_idx].cross_wo[idx];
-                            }
-                        }
-
-                        // Gradient through cross_wv -> also propagates to encoder output
-                        for enc_pos in 0..enc_len {
-                            if weights[enc_pos] < 1e-6 {
-                                continue;
-                            }
-                            let enc_inp = &encoder_output[enc_pos * d..(enc_pos + 1) * d];
-                            for j in 0..d_head {
-                                let w_idx = (h_off + j) * d;
-                                let g = d_cross[j] * weights[enc_pos];
-                                for kk in 0..d {
-                                    self.decoder_layers[l_idx].cross_wv[w_idx + kk] -=
-                                        lr_cross * clip_grad(g * enc_inp[kk]);
-                                    d_encoder[enc_pos * d + kk] += lr_cross
-                                        * clip_grad(
-                                            g * self.decoder_layers[l_idx].cross_wv[w_idx + kk],
-                                        );
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            // --- Causal self-attention backprop ---
-            let normed1 = layer_norm(layer_in, &self.decoder_layers[l_idx].ln1_scale, dec_len, d);
-
-            for pos in 0..dec_len {
-                let d_pos = &d_residual[pos * d..(pos + 1) * d];
-                if d_pos.iter().all(|&x| x.abs() < 1e-8) {
-                    continue;
-                }
-
-                let lr_attn = lr * 0.3;
-
-                for h in 0..n_heads {
-                    let h_off = h * d_head;
-                    let inp = &normed1[pos * d..(pos + 1) * d];
-
-                    let mut q = vec![0.0f32; d_head];
-                    for j in 0..d_head {
-                        let w_idx = (h_off + j) * d;
-                        q[j] = (0..d)
-                            .map(|k| inp[k] * self.decoder_layers[l_idx].wq[w_idx + k])
-                            .sum();
-                    }
-
-                    let mut weights = vec![0.0f32; pos + 1];
-                    for prev in 0..=pos {
-                        let prev_inp = &normed1[prev * d..(prev + 1) * d];
-                        let mut score = 0.0f32;
-                        for j in 0..d_head {
-                            let w_idx = (h_off + j) * d;
-                            let k_j: f32 = (0..d)
-                                .map(|kk| prev_inp[kk] * self.decoder_layers[l_idx].wk[w_idx + kk])
-                                .sum();
-                            score += q[j] * k_j;
-                        }
-                        weights[prev] = score / (d_head as f32).sqrt();
-                    }
-
-                    let max_w = weights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-                    let exp_s: f32 = weights.iter().map(|w| (w - max_w).exp()).sum();
-                    for w in &mut weights {
-                        *w = (*w - max_w).exp() / exp_s;
-                    }
-
-                    let mut attn_head = vec![0.0f32; d_head];
-                    for prev in 0..=pos {
-                        let prev_inp = &normed1[prev * d..(prev + 1) * d];
-                        for j in 0..d_head {
-                            let w_idx = (h_off + j) * d;
-                            let v_j: f32 = (0..d)
-                                .map(|kk| prev_inp[kk] * self.decoder_layers[l_idx].wv[w_idx + kk])
-                                .sum();
-                            attn_head[j] += weights[prev] * v_j;
-                        }
-                    }
-
-                    let mut d_attn = vec![0.0f32; d_head];
-                    for j in 0..d {
-                        if d_pos[j].abs() < 1e-8 {
-                            continue;
-                        }
-                        for jj in 0..d_head {
-                            let idx = j * d + h_off + jj;
-                            self.decoder_layers[l_idx].wo[idx] -=
-                                lr_attn * clip_grad(d_pos[j] * attn_head[jj]);
-                            d_attn[jj] += d_pos[j] * self.decoder_layers[l_idx].wo[idx];
-                        }
-                    }
-
-                    for prev in 0..=pos {
-                        if weights[prev] < 1e-6 {
-                            continue;
-                        }
-                        let prev_inp = &normed1[prev * d..(prev + 1) * d];
-                        for j in 0..d_head {
-                            let w_idx = (h_off + j) * d;
-                            let g = d_attn[j] * weights[prev];
-                            for kk in 0..d {
-                                self.decoder_layers[l_idx].wv[w_idx + kk] -=
-                                    lr_attn * clip_grad(g * prev_inp[kk]);
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Pass gradient to previous decoder layer
-            d_dec_output = d_residual;
+        let d = self.config.d_model;
+        let dec_input = &target[..target.len() - 1];
+        let dec_target = target[target.len() - 1] as usize % self.config.vocab;
+        let enc_len = context.len().min(self.config.max_seq);
+        let dec_len = dec_input.len().min(self.config.max_seq);
+
+        let mut t = Tape::new();
+        let embeddings = t.param(&self.embeddings, self.config.vocab, d);
+        let enc = (enc_len > 0).then(|| {
+            EncoderGraph::build(
+                &mut t,
+                embeddings,
+                &self.enc_pos,
+                &self.encoder_layers,
+                &token_ids(&context[..enc_len], self.config.vocab),
+                self.config.dims(),
+            )
+        });
+        let dec = DecoderGraph::build(
+            &mut t,
+            embeddings,
+            &self.dec_pos,
+            &self.decoder_layers,
+            &token_ids(&dec_input[..dec_len], self.config.vocab),
+            enc.as_ref().map(|e| e.output),
+            self.config.dims(),
+        );
+
+        // Output projection (last position), tied with the embeddings
+        let output_bias = t.param(&self.output_bias, 1, self.config.vocab);
+        let last = t.rows(dec.output, dec_len - 1, 1);
+        let logits = t.linear(last, embeddings);
+        let logits = t.add_row(logits, output_bias);
+        let loss_var = t.cross_entropy(logits, &[dec_target]);
+        let loss = t.scalar(loss_var);
+        let Some(grads) = grads else {
+            return loss;
+        };
+
+        t.backward(loss_var);
+        collect(&t, output_bias, "output_bias", grads);
+        dec.collect(&t, grads);
+        if let Some(enc) = &enc {
+            enc.collect(&t, grads);
         }
+        collect(&t, embeddings, "embeddings", grads);
+        grads.record(loss);
 
-        // 4. Backprop through encoder layers (via cross-attention gradients)
-        let mut d_enc_output = d_encoder;
-        self.backprop_encoder(&enc_layer_inputs, &mut d_enc_output, enc_len, lr);
+        loss
+    }
 
-        // 5. Input embedding gradient (decoder side)
-        for (pos, &tok) in dec_input.iter().take(dec_len).enumerate() {
-            let tok_idx = tok as usize % UNIFIED_VOCAB;
-            let emb_off = tok_idx * d;
-            for j in 0..d {
-                let g = d_dec_output[pos * d + j];
-                if g.abs() > 1e-8 {
-                    self.embeddings[emb_off + j] -= lr * 0.1 * clip_grad(g);
-                }
-            }
-        }
+    /// Apply accumulated gradients with `opt` as one training step.
+    pub fn apply_gradients(&mut self, opt: &mut Optimizer, grads: &Gradients) -> Option<StepStats> {
+        let stats = opt.step(self.named_params_mut(), grads)?;
+        self.record_step(grads.mean_loss());
+        Some(stats)
+    }
 
-        // 6. Input embedding gradient (encoder side)
-        for (pos, &tok) in context.iter().take(enc_len).enumerate() {
-            let tok_idx = tok as usize % UNIFIED_VOCAB;
-            let emb_off = tok_idx * d;
-            for j in 0..d {
-                let g = d_enc_output[pos * d + j];
-                if g.abs() > 1e-8 {
-                    self.embeddings[emb_off + j] -= lr * 0.1 * clip_grad(g);
-                }
-            }
+    /// Legacy per-element-clipped SGD update.
+    fn sgd(&mut self, grads: &Gradients, lr: f32) {
+        if grads.is_empty() {
+            return;
         }
+        grads.sgd(self.named_params_mut(), lr);
+        self.record_step(grads.mean_loss());
+    }
 
+    fn record_step(&mut self, loss: f32) {
         self.train_steps += 1;
         self.running_loss = 0.95 * self.running_loss + 0.05 * loss;
+    }
 
-        loss
+    /// Every trainable tensor under its checkpoint name.
+    pub fn named_params_mut(&mut self) -> Vec<(String, &mut [f32])> {
+        let mut params: Vec<(String, &mut [f32])> = vec![
+            ("embeddings".into(), &mut self.embeddings),
+            ("enc_pos".into(), &mut self.enc_pos),
+            ("dec_pos".into(), &mut self.dec_pos),
+            ("fast_w1".into(), &mut self.fast_w1),
+            ("fast_w2".into(), &mut self.fast_w2),
+            ("fast_bias".into(), &mut self.fast_bias),
+            ("output_bias".into(), &mut self.output_bias),
+        ];
+        for (i, l) in self.encoder_layers.iter_mut().enumerate() {
+            params.extend(named(&format!("enc.{i}"), l.tensors_mut(), ENCODER_TENSORS));
+        }
+        for (i, l) in self.decoder_layers.iter_mut().enumerate() {
+            params.extend(named(&format!("dec.{i}"), l.tensors_mut(), DECODER_TENSORS));
+        }
+        params
     }
 
-    /// Approximate parameter count.
+    /// Parameter count.
     pub fn param_count(&self) -> usize {
-        let d = self.d_model;
-        let v = UNIFIED_VOCAB;
-        let s = MAX_SEQ;
-        let ff = D_FF;
-
-        let embed = v * d + 2 * s * d; // shared embeddings + enc_pos + dec_pos
-        let enc_per_layer = 4 * d * d + 2 * d * ff + 2 * d; // attn + ff + 2 ln
-        let dec_per_layer = 8 * d * d + 2 * d * ff + 3 * d; // self-attn + cross-attn + ff + 3 ln
-        let fast_head = d * 256 + 256 * FAST_OUTPUT + FAST_OUTPUT; // fast head params
-        embed + ENC_LAYERS * enc_per_layer + DEC_LAYERS * dec_per_layer + v + fast_head
+        self.config.param_count()
     }
 
     /// Serialize to JSON.
@@ -856,145 +825,65 @@ impl UnifiedModel {
     /// Deserialize from JSON with dimension validation.
     pub fn from_json(json: &str) -> Option<Self> {
         let model: Self = serde_json::from_str(json).ok()?;
+        model.shapes_match().then_some(model)
+    }
 
-        // Validate architecture matches
-        if model.d_model != D_MODEL {
-            return None;
-        }
-        if model.encoder_layers.len() != ENC_LAYERS {
-            return None;
-        }
-        if model.decoder_layers.len() != DEC_LAYERS {
-            return None;
-        }
-        if model.embeddings.len() != UNIFIED_VOCAB * D_MODEL {
-            return None;
-        }
-        if model.fast_w1.len() != D_MODEL * 256 {
-            return None;
-        }
-        if model.fast_w2.len() != 256 * FAST_OUTPUT {
-            return None;
-        }
-        if model.fast_bias.len() != FAST_OUTPUT {
-            return None;
-        }
-        if model.output_bias.len() != UNIFIED_VOCAB {
-            return None;
-        }
-
-        Some(model)
+    /// Whether every tensor has the size its config implies.
+    fn shapes_match(&self) -> bool {
+        let c = &self.config;
+        let (d, ff) = (c.d_model, c.d_ff);
+        let sq = d * d;
+        c.validate().is_ok()
+            && self.embeddings.len() == c.vocab * d
+            && self.enc_pos.len() == c.max_seq * d
+            && self.dec_pos.len() == c.max_seq * d
+            && self.encoder_layers.len() == c.enc_layers
+            && self.decoder_layers.len() == c.dec_layers
+            && self.encoder_layers.iter().all(|l| {
+                l.tensors()
+                    .iter()
+                    .zip([sq, sq, sq, sq, ff * d, d * ff, d, d])
+                    .all(|(t, n)| t.len() == n)
+            })
+            && self.decoder_layers.iter().all(|l| {
+                l.tensors()
+                    .iter()
+                    .zip([sq, sq, sq, sq, sq, sq, sq, sq, ff * d, d * ff, d, d, d])
+                    .all(|(t, n)| t.len() == n)
+            })
+            && self.fast_w1.len() == d * FAST_HIDDEN
+            && self.fast_w2.len() == FAST_HIDDEN * FAST_OUTPUT
+            && self.fast_bias.len() == FAST_OUTPUT
+            && self.output_bias.len() == c.vocab
     }
 
     // ── Internal: encoder layer forward ───────────────────────────
 
     /// Apply a single encoder layer (bidirectional self-attention + FFN).
     fn apply_encoder_layer(&self, layer: &EncoderLayer, input: &[f32], seq_len: usize) -> Vec<f32> {
-        let d = self.d_model;
-        let n_heads = N_HEADS;
-        let d_head = D_HEAD;
-        let ff = D_FF;
+        let d = self.config.d_model;
+        let dims = self.config.dims();
 
-        // Layer norm 1
+        // Bidirectional self-attention; Q/K/V are projected once per position
         let normed = layer_norm(input, &layer.ln1_scale, seq_len, d);
-
-        // Multi-head BIDIRECTIONAL attention -- parallel across heads
-        let head_outputs: Vec<Vec<f32>> = (0..n_heads)
-            .into_par_iter()
-            .map(|h| {
-                let mut head_out = vec![0.0f32; seq_len * d_head];
-                for pos in 0..seq_len {
-                    let inp = &normed[pos * d..(pos + 1) * d];
-
-                    let mut q = vec![0.0f32; d_head];
-                    for j in 0..d_head {
-                        let w_idx = (h * d_head + j) * d;
-                        q[j] = (0..d).map(|k| inp[k] * layer.wq[w_idx + k]).sum::<f32>();
-                    }
-
-                    // Bidirectional: attend to ALL positions
-                    let mut weights = vec![0.0f32; seq_len];
-                    for prev in 0..seq_len {
-                        let prev_inp = &normed[prev * d..(prev + 1) * d];
-                        let mut score = 0.0f32;
-                        for j in 0..d_head {
-                            let w_idx = (h * d_head + j) * d;
-                            let k_j: f32 =
-                                (0..d).map(|kk| prev_inp[kk] * layer.wk[w_idx + kk]).sum();
-                            score += q[j] * k_j;
-                        }
-                        weights[prev] = score / (d_head as f32).sqrt();
-                    }
-
-                    let max_w = weights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-                    let exp_sum: f32 = weights.iter().map(|w| (w - max_w).exp()).sum();
-                    for w in &mut weights {
-                        *w = (*w - max_w).exp() / exp_sum;
-                    }
-
-                    for prev in 0..seq_len {
-                        let prev_inp = &normed[prev * d..(prev + 1) * d];
-                        for j in 0..d_head {
-                            let w_idx = (h * d_head + j) * d;
-                            let v_j: f32 =
-                                (0..d).map(|kk| prev_inp[kk] * layer.wv[w_idx + kk]).sum();
-                            head_out[pos * d_head + j] += weights[prev] * v_j;
-                        }
-                    }
-                }
-                head_out
-            })
-            .collect();
-
-        // Merge head outputs into attn_out
-        let mut attn_out = vec![0.0f32; seq_len * d];
-        for (h, head_out) in head_outputs.iter().enumerate() {
-            for pos in 0..seq_len {
-                for j in 0..d_head {
-                    attn_out[pos * d + h * d_head + j] = head_out[pos * d_head + j];
-                }
-            }
-        }
+        let q = linalg::matmul_nt(&normed, &layer.wq, seq_len, d, d);
+        let k = linalg::matmul_nt(&normed, &layer.wk, seq_len, d, d);
+        let v = linalg::matmul_nt(&normed, &layer.wv, seq_len, d, d);
+        let attn = linalg::attend(&q, seq_len, &k, &v, seq_len, dims, false);
 
         // Output projection + residual
         let mut residual = input.to_vec();
-        for pos in 0..seq_len {
-            let a = &attn_out[pos * d..(pos + 1) * d];
-            for j in 0..d {
-                let out_j: f32 = (0..d).map(|k| a[k] * layer.wo[j * d + k]).sum();
-                residual[pos * d + j] += out_j;
-            }
-        }
+        add_assign(
+            &mut residual,
+            &linalg::matmul_nt(&attn, &layer.wo, seq_len, d, d),
+        );
 
-        // Layer norm 2
+        // Feed-forward
         let normed2 = layer_norm(&residual, &layer.ln2_scale, seq_len, d);
-
-        // Feed-forward -- parallel across positions
-        let ff_deltas: Vec<Vec<f32>> = (0..seq_len)
-            .into_par_iter()
-            .map(|pos| {
-                let inp = &normed2[pos * d..(pos + 1) * d];
-                let mut ff_hidden = vec![0.0f32; ff];
-                for j in 0..ff {
-                    let w_idx = j * d;
-                    let val: f32 = (0..d).map(|k| inp[k] * layer.ff_w1[w_idx + k]).sum();
-                    ff_hidden[j] = val.max(0.0);
-                }
-                let mut delta = vec![0.0f32; d];
-                for j in 0..d {
-                    let w_idx = j * ff;
-                    delta[j] = (0..ff).map(|k| ff_hidden[k] * layer.ff_w2[w_idx + k]).sum();
-                }
-                delta
-            })
-            .collect();
-
-        // Add FFN deltas to residual
-        for (pos, delta) in ff_deltas.iter().enumerate() {
-            for j in 0..d {
-                residual[pos * d + j] += delta[j];
-            }
-        }
+        add_assign(
+            &mut residual,
+            &feed_forward(&normed2, seq_len, &layer.ff_w1, &layer.ff_w2, dims),
+        );
 
         residual
     }
@@ -1010,364 +899,231 @@ impl UnifiedModel {
         encoder_output: &[f32],
         enc_len: usize,
     ) -> Vec<f32> {
-        let d = self.d_model;
-        let n_heads = N_HEADS;
-        let d_head = D_HEAD;
-        let ff = D_FF;
+        let d = self.config.d_model;
+        let dims = self.config.dims();
 
         // === 1. Causal self-attention ===
 
         let normed = layer_norm(input, &layer.ln1_scale, seq_len, d);
+        let q = linalg::matmul_nt(&normed, &layer.wq, seq_len, d, d);
+        let k = linalg::matmul_nt(&normed, &layer.wk, seq_len, d, d);
+        let v = linalg::matmul_nt(&normed, &layer.wv, seq_len, d, d);
+        let attn = linalg::attend(&q, seq_len, &k, &v, seq_len, dims, true);
 
-        let head_outputs: Vec<Vec<f32>> = (0..n_heads)
-            .into_par_iter()
-            .map(|h| {
-                let mut head_out = vec![0.0f32; seq_len * d_head];
-                for pos in 0..seq_len {
-                    let inp = &normed[pos * d..(pos + 1) * d];
-
-                    let mut q = vec![0.0f32; d_head];
-                    for j in 0..d_head {
-                        let w_idx = (h * d_head + j) * d;
-                        q[j] = (0..d).map(|k| inp[k] * layer.wq[w_idx + k]).sum::<f32>();
-                    }
-
-                    // Causal: only attend to positions <= pos
-                    let mut weights = vec![0.0f32; pos + 1];
-                    for prev in 0..=pos {
-                        let prev_inp = &normed[prev * d..(prev + 1) * d];
-                        let mut score = 0.0f32;
-                        for j in 0..d_head {
-                            let w_idx = (h * d_head + j) * d;
-                            let k_j: f32 =
-                                (0..d).map(|kk| prev_inp[kk] * layer.wk[w_idx + kk]).sum();
-                            score += q[j] * k_j;
-                        }
-                        weights[prev] = score / (d_head as f32).sqrt();
-                    }
-
-                    let max_w = weights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-                    let exp_sum: f32 = weights.iter().map(|w| (w - max_w).exp()).sum();
-                    for w in &mut weights {
-                        *w = (*w - max_w).exp() / exp_sum;
-                    }
-
-                    for prev in 0..=pos {
-                        let prev_inp = &normed[prev * d..(prev + 1) * d];
-                        for j in 0..d_head {
-                            let w_idx = (h * d_head + j) * d;
-                            let v_j: f32 =
-                                (0..d).map(|kk| prev_inp[kk] * layer.wv[w_idx + kk]).sum();
-                            head_out[pos * d_head + j] += weights[prev] * v_j;
-                        }
-                    }
-                }
-                head_out
-            })
-            .collect();
-
-        // Merge head outputs
-        let mut attn_out = vec![0.0f32; seq_len * d];
-        for (h, head_out) in head_outputs.iter().enumerate() {
-            for pos in 0..seq_len {
-                for j in 0..d_head {
-                    attn_out[pos * d + h * d_head + j] = head_out[pos * d_head + j];
-                }
-            }
-        }
-
-        // Output projection + residual
         let mut residual = input.to_vec();
-        for pos in 0..seq_len {
-            let a = &attn_out[pos * d..(pos + 1) * d];
-            for j in 0..d {
-                let out_j: f32 = (0..d).map(|k| a[k] * layer.wo[j * d + k]).sum();
-                residual[pos * d + j] += out_j;
-            }
-        }
+        add_assign(
+            &mut residual,
+            &linalg::matmul_nt(&attn, &layer.wo, seq_len, d, d),
+        );
 
         // === 2. Cross-attention (decoder Q, encoder K/V) ===
 
         let normed2 = layer_norm(&residual, &layer.ln2_scale, seq_len, d);
-
-        if enc_len > 0 && !encoder_output.is_empty() {
-            let cross_head_outputs: Vec<Vec<f32>> = (0..n_heads)
-                .into_par_iter()
-                .map(|h| {
-                    let mut head_out = vec![0.0f32; seq_len * d_head];
-                    for pos in 0..seq_len {
-                        let inp = &normed2[pos * d..(pos + 1) * d];
-
-                        let mut q = vec![0.0f32; d_head];
-                        for j in 0..d_head {
-                            let w_idx = (h * d_head + j) * d;
-                            q[j] = (0..d)
-                                .map(|k| inp[k] * layer.cross_wq[w_idx + k])
-                                .sum::<f32>();
-                        }
-
-                        // K, V from encoder output -- attend to ALL encoder positions
-                        let mut weights = vec![0.0f32; enc_len];
-                        for enc_pos in 0..enc_len {
-                            let enc_inp = &encoder_output[enc_pos * d..(enc_pos + 1) * d];
-                            let mut score = 0.0f32;
-                            for j in 0..d_head {
-                                let w_idx = (h * d_head + j) * d;
-                                let k_j: f32 = (0..d)
-                                    .map(|kk| enc_inp[kk] * layer.cross_wk[w_idx + kk])
-                                    .sum();
-                                score += q[j] * k_j;
-                            }
-                            weights[enc_pos] = score / (d_head as f32).sqrt();
-                        }
-
-                        let max_w = weights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-                        let exp_sum: f32 = weights.iter().map(|w| (w - max_w).exp()).sum();
-                        for w in &mut weights {
-                            *w = (*w - max_w).exp() / exp_sum;
-                        }
-
-                        for enc_pos in 0..enc_len {
-                            let enc_inp = &encoder_output[enc_pos * d..(enc_pos + 1) * d];
-                            for j in 0..d_head {
-                                let w_idx = (h * d_head + j) * d;
-                                let v_j: f32 = (0..d)
-                                    .map(|kk| enc_inp[kk] * layer.cross_wv[w_idx + kk])
-                                    .sum();
-                                head_out[pos * d_head + j] += weights[enc_pos] * v_j;
-                            }
-                        }
-                    }
-                    head_out
-                })
-                .collect();
-
-            // Merge cross-attention head outputs
-            let mut cross_attn_out = vec![0.0f32; seq_len * d];
-            for (h, head_out) in cross_head_outputs.iter().enumerate() {
-                for pos in 0..seq_len {
-                    for j in 0..d_head {
-                        cross_attn_out[pos * d + h * d_head + j] = head_out[pos * d_head + j];
-                    }
-                }
-            }
-
-            // Cross-attention output projection + residual
-            for pos in 0..seq_len {
-                let a = &cross_attn_out[pos * d..(pos + 1) * d];
-                for j in 0..d {
-                    let out_j: f32 = (0..d).map(|k| a[k] * layer.cross_wo[j * d + k]).sum();
-                    residual[pos * d + j] += out_j;
-                }
-            }
+        let enc_len = enc_len.min(encoder_output.len() / d);
+
+        if enc_len > 0 {
+            let enc = &encoder_output[..enc_len * d];
+            let q = linalg::matmul_nt(&normed2, &layer.cross_wq, seq_len, d, d);
+            let k = linalg::matmul_nt(enc, &layer.cross_wk, enc_len, d, d);
+            let v = linalg::matmul_nt(enc, &layer.cross_wv, enc_len, d, d);
+            let attn = linalg::attend(&q, seq_len, &k, &v, enc_len, dims, false);
+            add_assign(
+                &mut residual,
+                &linalg::matmul_nt(&attn, &layer.cross_wo, seq_len, d, d),
+            );
         }
 
         // === 3. Feed-forward ===
 
         let normed3 = layer_norm(&residual, &layer.ln3_scale, seq_len, d);
-
-        let ff_deltas: Vec<Vec<f32>> = (0..seq_len)
-            .into_par_iter()
-            .map(|pos| {
-                let inp = &normed3[pos * d..(pos + 1) * d];
-                let mut ff_hidden = vec![0.0f32; ff];
-                for j in 0..ff {
-                    let w_idx = j * d;
-                    let val: f32 = (0..d).map(|k| inp[k] * layer.ff_w1[w_idx + k]).sum();
-                    ff_hidden[j] = val.max(0.0);
-                }
-                let mut delta = vec![0.0f32; d];
-                for j in 0..d {
-                    let w_idx = j * ff;
-                    delta[j] = (0..ff).map(|k| ff_hidden[k] * layer.ff_w2[w_idx + k]).sum();
-                }
-                delta
-            })
-            .collect();
-
-        for (pos, delta) in ff_deltas.iter().enumerate() {
-            for j in 0..d {
-                residual[pos * d + j] += delta[j];
-            }
-        }
+        add_assign(
+            &mut residual,
+            &feed_forward(&normed3, seq_len, &layer.ff_w1, &layer.ff_w2, dims),
+        );
 
         residual
     }
+}
+
+impl Default for UnifiedModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    // ── Internal: shared encoder backprop ─────────────────────────
+// ── Checkpoint ────────────────────────────────────────────────────
 
-    /// Backprop through encoder layers in reverse order. Mutates d_enc_output in place.
-    fn backprop_encoder(
-        &mut self,
-        enc_layer_inputs: &[Vec<f32>],
-        d_enc_output: &mut Vec<f32>,
-        enc_len: usize,
-        lr: f32,
-    ) {
-        let d = self.d_model;
-        let n_heads = N_HEADS;
-        let d_head = D_HEAD;
-        let ff = D_FF;
-
-        for l_idx in (0..ENC_LAYERS).rev() {
-            let layer_in = &enc_layer_inputs[l_idx];
-            let d_out = &*d_enc_output;
-
-            // --- FFN backprop ---
-            let normed2 = layer_norm(layer_in, &self.encoder_layers[l_idx].ln2_scale, enc_len, d);
-            let d_residual = d_out.to_vec();
-
-            for pos in 0..enc_len {
-                let d_norm = &d_out[pos * d..(pos + 1) * d];
-                if d_norm.iter().all(|&x| x.abs() < 1e-8) {
-                    continue;
-                }
-
-                let inp = &normed2[pos * d..(pos + 1) * d];
-                let mut ff_hidden = vec![0.0f32; ff];
-                for k in 0..ff {
-                    let w_idx = k * d;
-                    let val: f32 = (0..d)
-                        .map(|j| inp[j] * self.encoder_layers[l_idx].ff_w1[w_idx + j])
-                        .sum();
-                    ff_hidden[k] = val.max(0.0);
-                }
-
-                let lr_ff = lr * 0.5;
-                let mut d_ff = vec![0.0f32; ff];
-                for j in 0..d {
-                    let g_j = d_norm[j];
-                    if g_j.abs() < 1e-8 {
-                        continue;
-                    }
-                    for k in 0..ff {
-                        let idx = j * ff + k;
-                        self.encoder_layers[l_idx].ff_w2[idx] -=
-                            lr_ff * clip_grad(g_j * ff_hidden[k]);
-                        d_ff[k] += g_j * self.encoder_layers[l_idx].ff_w2[idx];
-                    }
-                }
-
-                for k in 0..ff {
-                    if ff_hidden[k] <= 0.0 {
-                        continue;
-                    }
-                    let w_idx = k * d;
-                    for j in 0..d {
-                        self.encoder_layers[l_idx].ff_w1[w_idx + j] -=
-                            lr_ff * clip_grad(d_ff[k] * inp[j]);
-                    }
-                }
-            }
+impl Checkpointable for UnifiedModel {
+    const ARCH: &'static str = "unified";
 
-            // --- Bidirectional attention backprop ---
-            let normed1 = layer_norm(layer_in, &self.encoder_layers[l_idx].ln1_scale, enc_len, d);
-
-            for pos in 0..enc_len {
-                let d_pos = &d_residual[pos * d..(pos + 1) * d];
-                if d_pos.iter().all(|&x| x.abs() < 1e-8) {
-                    continue;
-                }
-
-                let lr_attn = lr * 0.3;
-
-                for h in 0..n_heads {
-                    let h_off = h * d_head;
-                    let inp = &normed1[pos * d..(pos + 1) * d];
-
-                    let mut q = vec![0.0f32; d_head];
-                    for j in 0..d_head {
-                        let w_idx = (h_off + j) * d;
-                        q[j] = (0..d)
-                            .map(|k| inp[k] * self.encoder_layers[l_idx].wq[w_idx + k])
-                            .sum();
-                    }
-
-                    // Bidirectional: attend to ALL positions
-                    let mut weights = vec![0.0f32; enc_len];
-                    for prev in 0..enc_len {
-                        let prev_inp = &normed1[prev * d..(prev + 1) * d];
-                        let mut score = 0.0f32;
-                        for j in 0..d_head {
-                            let w_idx = (h_off + j) * d;
-                            let k_j: f32 = (0..d)
-                                .map(|kk| prev_inp[kk] * self.encoder_layers[l_idx].wk[w_idx + kk])
-                                .sum();
-                            score += q[j] * k_j;
-                        }
-                        weights[prev] = score / (d_head as f32).sqrt();
-                    }
-
-                    let max_w = weights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-                    let exp_s: f32 = weights.iter().map(|w| (w - max_w).exp()).sum();
-                    for w in &mut weights {
-                        *w = (*w - max_w).exp() / exp_s;
-                    }
-
-                    let mut attn_head = vec![0.0f32; d_head];
-                    for prev in 0..enc_len {
-                        let prev_inp = &normed1[prev * d..(prev + 1) * d];
-                        for j in 0..d_head {
-                            let w_idx = (h_off + j) * d;
-                            let v_j: f32 = (0..d)
-                                .map(|kk| prev_inp[kk] * self.encoder_layers[l_idx].wv[w_idx + kk])
-                                .sum();
-                            attn_head[j] += weights[prev] * v_j;
-                        }
-                    }
-
-                    let mut d_attn = vec![0.0f32; d_head];
-                    for j in 0..d {
-                        if d_pos[j].abs() < 1e-8 {
-                            continue;
-                        }
-                        for jj in 0..d_head {
-                            let idx = j * d + h_off + jj;
-                            self.encoder_layers[l_idx].wo[idx] -=
-                                lr_attn * clip_grad(d_pos[j] * attn_head[jj]);
-                            d_attn[jj] += d_pos[j] * self.encoder_layers[l_idx].wo[idx];
-                        }
-                    }
-
-                    for prev in 0..enc_len {
-                        if weights[prev] < 1e-6 {
-                            continue;
-                        }
-                        let prev_inp = &normed1[prev * d..(prev + 1) * d];
-                        for j in 0..d_head {
-                            let w_idx = (h_off + j) * d;
-                            let g = d_attn[j] * weights[prev];
-                            for kk in 0..d {
-                                self.encoder_layers[l_idx].wv[w_idx + kk] -=
-                                    lr_attn * clip_grad(g * prev_inp[kk]);
-                            }
-                        }
-                    }
-                }
-            }
+    /// Only the fast head is fixed; the rest of the shape comes from the
+    /// checkpoint's [`ModelConfig`].
+    fn hyperparams() -> Vec<(&'static str, u64)> {
+        vec![
+            ("fast_hidden", FAST_HIDDEN as u64),
+            ("fast_output", FAST_OUTPUT as u64),
+        ]
+    }
 
-            // Pass gradient to previous encoder layer
-            *d_enc_output = d_residual;
+    fn header_params(&self) -> Vec<(&'static str, u64)> {
+        let mut params = self.config.header_params();
+        params.extend(Self::hyperparams());
+        params
+    }
+
+    fn write_tensors(&self, w: &mut CheckpointWriter) {
+        let c = &self.config;
+        let (d, ff) = (c.d_model, c.d_ff);
+        w.meta("train_steps", self.train_steps)
+            .meta("running_loss", self.running_loss);
+        w.tensor("embeddings", &[c.vocab, d], &self.embeddings)
+            .tensor("enc_pos", &[c.max_seq, d], &self.enc_pos)
+            .tensor("dec_pos", &[c.max_seq, d], &self.dec_pos);
+        for (i, l) in self.encoder_layers.iter().enumerate() {
+            let p = format!("enc.{i}");
+            w.tensor(&format!("{p}.wq"), &[d, d], &l.wq)
+                .tensor(&format!("{p}.wk"), &[d, d], &l.wk)
+                .tensor(&format!("{p}.wv"), &[d, d], &l.wv)
+                .tensor(&format!("{p}.wo"), &[d, d], &l.wo)
+                .tensor(&format!("{p}.ff_w1"), &[ff, d], &l.ff_w1)
+                .tensor(&format!("{p}.ff_w2"), &[d, ff], &l.ff_w2)
+                .tensor(&format!("{p}.ln1_scale"), &[d], &l.ln1_scale)
+                .tensor(&format!("{p}.ln2_scale"), &[d], &l.ln2_scale);
+        }
+        for (i, l) in self.decoder_layers.iter().enumerate() {
+            let p = format!("dec.{i}");
+            w.tensor(&format!("{p}.wq"), &[d, d], &l.wq)
+                .tensor(&format!("{p}.wk"), &[d, d], &l.wk)
+                .tensor(&format!("{p}.wv"), &[d, d], &l.wv)
+                .tensor(&format!("{p}.wo"), &[d, d], &l.wo)
+                .tensor(&format!("{p}.cross_wq"), &[d, d], &l.cross_wq)
+                .tensor(&format!("{p}.cross_wk"), &[d, d], &l.cross_wk)
+                .tensor(&format!("{p}.cross_wv"), &[d, d], &l.cross_wv)
+                .tensor(&format!("{p}.cross_wo"), &[d, d], &l.cross_wo)
+                .tensor(&format!("{p}.ff_w1"), &[ff, d], &l.ff_w1)
+                .tensor(&format!("{p}.ff_w2"), &[d, ff], &l.ff_w2)
+                .tensor(&format!("{p}.ln1_scale"), &[d], &l.ln1_scale)
+                .tensor(&format!("{p}.ln2_scale"), &[d], &l.ln2_scale)
+                .tensor(&format!("{p}.ln3_scale"), &[d], &l.ln3_scale);
         }
+        w.tensor("fast_w1", &[FAST_HIDDEN, d], &self.fast_w1)
+            .tensor("fast_w2", &[FAST_OUTPUT, FAST_HIDDEN], &self.fast_w2)
+            .tensor("fast_bias", &[FAST_OUTPUT], &self.fast_bias)
+            .tensor("output_bias", &[c.vocab], &self.output_bias);
+    }
+
+    fn read_tensors(c: &Checkpoint<'_>) -> Result<Self, CheckpointError> {
+        let config = ModelConfig::from_checkpoint(c)?;
+        let (d, ff) = (config.d_model, config.d_ff);
+        let encoder_layers = (0..config.enc_layers)
+            .map(|i| {
+                let p = format!("enc.{i}");
+                Ok(EncoderLayer {
+                    wq: c.tensor_vec(&format!("{p}.wq"), &[d, d])?,
+                    wk: c.tensor_vec(&format!("{p}.wk"), &[d, d])?,
+                    wv: c.tensor_vec(&format!("{p}.wv"), &[d, d])?,
+                    wo: c.tensor_vec(&format!("{p}.wo"), &[d, d])?,
+                    ff_w1: c.tensor_vec(&format!("{p}.ff_w1"), &[ff, d])?,
+                    ff_w2: c.tensor_vec(&format!("{p}.ff_w2"), &[d, ff])?,
+                    ln1_scale: c.tensor_vec(&format!("{p}.ln1_scale"), &[d])?,
+                    ln2_scale: c.tensor_vec(&format!("{p}.ln2_scale"), &[d])?,
+                })
+            })
+            .collect::<Result<Vec<_>, CheckpointError>>()?;
+        let decoder_layers = (0..config.dec_layers)
+            .map(|i| {
+                let p = format!("dec.{i}");
+                Ok(DecoderLayer {
+                    wq: c.tensor_vec(&format!("{p}.wq"), &[d, d])?,
+                    wk: c.tensor_vec(&format!("{p}.wk"), &[d, d])?,
+                    wv: c.tensor_vec(&format!("{p}.wv"), &[d, d])?,
+                    wo: c.tensor_vec(&format!("{p}.wo"), &[d, d])?,
+                    cross_wq: c.tensor_vec(&format!("{p}.cross_wq"), &[d, d])?,
+                    cross_wk: c.tensor_vec(&format!("{p}.cross_wk"), &[d, d])?,
+                    cross_wv: c.tensor_vec(&format!("{p}.cross_wv"), &[d, d])?,
+                    cross_wo: c.tensor_vec(&format!("{p}.cross_wo"), &[d, d])?,
+                    ff_w1: c.tensor_vec(&format!("{p}.ff_w1"), &[ff, d])?,
+                    ff_w2: c.tensor_vec(&format!("{p}.ff_w2"), &[d, ff])?,
+                    ln1_scale: c.tensor_vec(&format!("{p}.ln1_scale"), &[d])?,
+                    ln2_scale: c.tensor_vec(&format!("{p}.ln2_scale"), &[d])?,
+                    ln3_scale: c.tensor_vec(&format!("{p}.ln3_scale"), &[d])?,
+                })
+            })
+            .collect::<Result<Vec<_>, CheckpointError>>()?;
+
+        Ok(Self {
+            embeddings: c.tensor_vec("embeddings", &[config.vocab, d])?,
+            enc_pos: c.tensor_vec("enc_pos", &[config.max_seq, d])?,
+            encoder_layers,
+            fast_w1: c.tensor_vec("fast_w1", &[FAST_HIDDEN, d])?,
+            fast_w2: c.tensor_vec("fast_w2", &[FAST_OUTPUT, FAST_HIDDEN])?,
+            fast_bias: c.tensor_vec("fast_bias", &[FAST_OUTPUT])?,
+            dec_pos: c.tensor_vec("dec_pos", &[config.max_seq, d])?,
+            decoder_layers,
+            output_bias: c.tensor_vec("output_bias", &[config.vocab])?,
+            train_steps: c.meta_u64("train_steps"),
+            running_loss: c.meta_f32("running_loss"),
+            config,
+        })
+    }
+
+    fn from_legacy_json(json: &str) -> Option<Self> {
+        Self::from_json(json)
     }
 }
 
-impl Default for UnifiedModel {
-    fn default() -> Self {
-        Self::new()
+impl Federated for UnifiedModel {
+    fn arch(&self) -> &'static str {
+        <Self as Checkpointable>::ARCH
+    }
+
+    fn train_steps(&self) -> u64 {
+        self.train_steps
+    }
+
+    /// Same order as [`UnifiedModel::named_params_mut`].
+    fn tensors(&self) -> Vec<&[f32]> {
+        let mut t: Vec<&[f32]> = vec![
+            &self.embeddings,
+            &self.enc_pos,
+            &self.dec_pos,
+            &self.fast_w1,
+            &self.fast_w2,
+            &self.fast_bias,
+            &self.output_bias,
+        ];
+        t.extend(self.encoder_layers.iter().flat_map(|l| l.tensors()));
+        t.extend(self.decoder_layers.iter().flat_map(|l| l.tensors()));
+        t
+    }
+
+    fn tensors_mut(&mut self) -> Vec<&mut [f32]> {
+        self.named_params_mut()
+            .into_iter()
+            .map(|(_, t)| t)
+            .collect()
     }
 }
 
 // ── Utilities (duplicated from codegen.rs to avoid circular deps) ─
 
-/// Clip gradient to [-0.5, 0.5] to prevent explosions.
-#[inline]
-fn clip_grad(g: f32) -> f32 {
-    g.clamp(-0.5, 0.5)
+/// Token ids folded into the vocabulary, as tape `gather` indices.
+fn token_ids(tokens: &[u32], vocab: usize) -> Vec<usize> {
+    tokens.iter().map(|&t| t as usize % vocab).collect()
+}
+
+/// Position-wise ReLU feed-forward over `[n x d]` rows.
+fn feed_forward(x: &[f32], n: usize, w1: &[f32], w2: &[f32], dims: Dims) -> Vec<f32> {
+    let Dims { d, ff, .. } = dims;
+    let mut hidden = linalg::matmul_nt(x, w1, n, d, ff);
+    for h in &mut hidden {
+        *h = h.max(0.0);
+    }
+    linalg::matmul_nt(&hidden, w2, n, ff, d)
 }
 
 /// Simple layer normalization (mean=0, var=1, then scale).
-fn layer_norm(input: &[f32], scale: &[f32], seq_len: usize, d: usize) -> Vec<f32> {
+pub(crate) fn layer_norm(input: &[f32], scale: &[f32], seq_len: usize, d: usize) -> Vec<f32> {
     let mut output = input.to_vec();
     for pos in 0..seq_len {
         let slice = &input[pos * d..(pos + 1) * d];
@@ -1381,6 +1137,38 @@ fn layer_norm(input: &[f32], scale: &[f32], seq_len: usize, d: usize) -> Vec<f32
     output
 }
 
+/// Copy a row-major `old` matrix into the top-left corner of a larger one,
+/// filling the new entries with small noise.
+fn pad(
+    old: &[f32],
+    (rows, cols): (usize, usize),
+    (new_rows, new_cols): (usize, usize),
+    rng: &mut XorShift64,
+) -> Vec<f32> {
+    let mut out = Vec::with_capacity(new_rows * new_cols);
+    for r in 0..new_rows {
+        for c in 0..new_cols {
+            out.push(if r < rows && c < cols {
+                old[r * cols + c]
+            } else {
+                rng.next() * GROW_NOISE
+            });
+        }
+    }
+    out
+}
+
+/// Extend a layer-norm scale to `d` dimensions. The residual stream's new
+/// dimensions are near zero, which shrinks the normalizing deviation by about
+/// `sqrt(old/d)`; the old scales absorb that factor, and new dimensions start
+/// at 0 so they add nothing until trained.
+fn pad_scale(old: &[f32], d: usize) -> Vec<f32> {
+    let gain = (old.len() as f32 / d as f32).sqrt();
+    let mut out: Vec<f32> = old.iter().map(|s| s * gain).collect();
+    out.resize(d, 0.0);
+    out
+}
+
 /// XorShift64 PRNG for deterministic initialization.
 struct XorShift64(u64);
 
@@ -1398,3 +1186,117 @@ fn xavier_init(rng: &mut XorShift64, size: usize, fan_in: usize, fan_out: usize)
     let limit = (6.0 / (fan_in + fan_out) as f32).sqrt();
     (0..size).map(|_| rng.next() * limit).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small() -> ModelConfig {
+        ModelConfig {
+            d_model: 32,
+            n_heads: 2,
+            d_ff: 64,
+            max_seq: 16,
+            vocab: UNIFIED_VOCAB,
+            enc_layers: 1,
+            dec_layers: 1,
+        }
+    }
+
+    #[test]
+    fn config_is_validated() {
+        assert_eq!(ModelConfig::default().validate(), Ok(()));
+        let bad = ModelConfig {
+            n_heads: 3,
+            ..small()
+        };
+        assert!(matches!(bad.validate(), Err(ConfigError::Heads { .. })));
+        let bad = ModelConfig {
+            vocab: 100,
+            ..small()
+        };
+        assert_eq!(bad.validate(), Err(ConfigError::Vocab(100)));
+        let bad = ModelConfig {
+            dec_layers: 0,
+            ..small()
+        };
+        assert_eq!(bad.validate(), Err(ConfigError::Zero("dec_layers")));
+    }
+
+    #[test]
+    fn runtime_shape_roundtrips_through_checkpoint() {
+        let config = small();
+        let model = UnifiedModel::with_config(config);
+        assert_eq!(
+            model.param_count(),
+            model.tensors().iter().map(|t| t.len()).sum::<usize>()
+        );
+
+        let tokens = [TASK_CODE, 5, 9, 200];
+        let loss = model.slow_loss(&tokens, &[1, 7, 8]);
+        assert!(loss.is_finite() && loss > 0.0);
+
+        let restored = UnifiedModel::load_bytes(&model.to_checkpoint()).unwrap();
+        assert_eq!(restored.config, config);
+        assert_eq!(restored.forward(&tokens), model.forward(&tokens));
+
+        let quantized = crate::QuantizedUnifiedModel::from_model(&model, crate::QuantMode::F16);
+        let restored = crate::QuantizedUnifiedModel::load_bytes(&quantized.to_checkpoint());
+        assert_eq!(restored.unwrap().config, config);
+    }
+
+    #[test]
+    fn grow_keeps_what_was_learned() {
+        let model = UnifiedModel::with_config(small());
+        let tokens = [TASK_PREDICT, 5, 9, 200];
+
+        // Extra layers start as the identity.
+        let deeper = model
+            .grow(ModelConfig {
+                enc_layers: 3,
+                dec_layers: 2,
+                ..small()
+            })
+            .unwrap();
+        assert_eq!(deeper.encoder_layers.len(), 3);
+        assert_eq!(deeper.forward(&tokens), model.forward(&tokens));
+        assert_eq!(deeper.fast_predict(&tokens), model.fast_predict(&tokens));
+
+        // Widening (same head width) stays close and trains.
+        let wider_config = ModelConfig {
+            d_model: 64,
+            n_heads: 4,
+            d_ff: 128,
+            max_seq: 32,
+            ..deeper.config
+        };
+        let wider = deeper.grow(wider_config).unwrap();
+        assert!(wider.shapes_match());
+        let (a, b) = (model.fast_predict(&tokens), wider.fast_predict(&tokens));
+        let drift = a
+            .iter()
+            .zip(&b)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0.0, f32::max);
+        assert!(drift < 0.1, "fast head drifted by {drift}");
+        assert!(wider.slow_loss(&tokens, &[1, 7, 8]).is_finite());
+        let restored = UnifiedModel::load_bytes(&wider.to_checkpoint()).unwrap();
+        assert_eq!(restored.config, wider_config);
+
+        assert!(matches!(
+            wider.grow(small()),
+            Err(ConfigError::Shrink {
+                name: "d_model",
+                ..
+            })
+        ));
+        let new_head_width = ModelConfig {
+            d_model: 128,
+            ..wider_config
+        };
+        assert!(matches!(
+            wider.grow(new_head_width),
+            Err(ConfigError::HeadWidth { from: 16, to: 32 })
+        ));
+    }
+}
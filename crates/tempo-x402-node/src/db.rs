@@ -552,6 +552,18 @@ const CARTRIDGES_SCHEMA: &str = r#"
         updated_at INTEGER NOT NULL,
         PRIMARY KEY (slug, key)
     );
+
+    CREATE TABLE IF NOT EXISTS cartridge_trigger_runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        slug TEXT NOT NULL,
+        trigger TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        status INTEGER,
+        fuel_consumed INTEGER NOT NULL DEFAULT 0,
+        error TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_trigger_runs_slug ON cartridge_trigger_runs(slug, started_at);
 "#;
 
 /// Initialize the cartridges tables on an existing gateway database.
@@ -569,6 +581,28 @@ pub fn init_cartridges_schema(db: &Database) -> Result<(), GatewayError> {
             )
             .map_err(|e| GatewayError::Internal(format!("migration: {e}")))?;
         }
+        // Migration: add manifest limits + metered pricing columns (JSON) if missing
+        let has_limits = conn
+            .prepare("SELECT limits FROM cartridges LIMIT 0")
+            .is_ok();
+        if !has_limits {
+            conn.execute_batch(
+                "ALTER TABLE cartridges ADD COLUMN limits TEXT NOT NULL DEFAULT '{}'; \
+                 ALTER TABLE cartridges ADD COLUMN pricing TEXT;",
+            )
+            .map_err(|e| GatewayError::Internal(format!("migration: {e}")))?;
+        }
+        // Migration: add manifest triggers column (JSON) if missing
+        let has_triggers = conn
+            .prepare("SELECT triggers FROM cartridges LIMIT 0")
+            .is_ok();
+        if !has_triggers {
+            conn.execute(
+                "ALTER TABLE cartridges ADD COLUMN triggers TEXT NOT NULL DEFAULT '[]'",
+                [],
+            )
+            .map_err(|e| GatewayError::Internal(format!("migration: {e}")))?;
+        }
         Ok(())
     })
 }
@@ -592,6 +626,15 @@ pub struct CartridgeRecord {
     /// "backend", "interactive", or "frontend"
     #[serde(default = "default_cartridge_type")]
     pub cartridge_type: String,
+    /// Manifest-declared resource limits (capped by node policy at execution).
+    #[serde(default)]
+    pub limits: x402_cartridge::ResourceLimits,
+    /// Metered pricing — when set, billing follows actual fuel/memory usage.
+    #[serde(default)]
+    pub pricing: Option<x402_cartridge::MeteredPricing>,
+    /// Cron and event triggers run by the node scheduler.
+    #[serde(default)]
+    pub triggers: Vec<x402_cartridge::Trigger>,
 }
 
 fn default_cartridge_type() -> String {
@@ -603,11 +646,13 @@ pub fn upsert_cartridge(db: &Database, record: &CartridgeRecord) -> Result<(), G
     db.with_connection(|conn| {
         conn.execute(
             "INSERT INTO cartridges (slug, name, description, version, price_usd, price_amount, \
-             owner_address, source_repo, wasm_path, wasm_hash, active, created_at, updated_at, cartridge_type) \
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14) \
+             owner_address, source_repo, wasm_path, wasm_hash, active, created_at, updated_at, cartridge_type, \
+             limits, pricing, triggers) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17) \
              ON CONFLICT(slug) DO UPDATE SET \
              name=?2, description=?3, version=?4, price_usd=?5, price_amount=?6, \
-             wasm_path=?9, wasm_hash=?10, active=?11, updated_at=?13, cartridge_type=?14",
+             wasm_path=?9, wasm_hash=?10, active=?11, updated_at=?13, cartridge_type=?14, \
+             limits=?15, pricing=?16, triggers=?17",
             params![
                 record.slug,
                 record.name,
@@ -623,6 +668,12 @@ pub fn upsert_cartridge(db: &Database, record: &CartridgeRecord) -> Result<(), G
                 record.created_at,
                 record.updated_at,
                 record.cartridge_type,
+                serde_json::to_string(&record.limits).unwrap_or_else(|_| "{}".to_string()),
+                record
+                    .pricing
+                    .as_ref()
+                    .and_then(|p| serde_json::to_string(p).ok()),
+                serde_json::to_string(&record.triggers).unwrap_or_else(|_| "[]".to_string()),
             ],
         )
         .map_err(|e| GatewayError::Internal(format!("upsert cartridge: {e}")))?;
@@ -636,7 +687,7 @@ pub fn get_cartridge(db: &Database, slug: &str) -> Result<Option<CartridgeRecord
         conn.query_row(
             "SELECT slug, name, description, version, price_usd, price_amount, \
              owner_address, source_repo, wasm_path, wasm_hash, active, created_at, updated_at, \
-             COALESCE(cartridge_type, 'backend') \
+             COALESCE(cartridge_type, 'backend'), limits, pricing, triggers \
              FROM cartridges WHERE slug = ?1 AND active = 1",
             params![slug],
             |row| {
@@ -657,6 +708,17 @@ pub fn get_cartridge(db: &Database, slug: &str) -> Result<Option<CartridgeRecord
                     cartridge_type: row
                         .get::<_, String>(13)
                         .unwrap_or_else(|_| "backend".to_string()),
+                    limits: row
+                        .get::<_, Option<String>>(14)?
+                        .and_then(|s| serde_json::from_str(&s).ok())
+                        .unwrap_or_default(),
+                    pricing: row
+                        .get::<_, Option<String>>(15)?
+                        .and_then(|s| serde_json::from_str(&s).ok()),
+                    triggers: row
+                        .get::<_, Option<String>>(16)?
+                        .and_then(|s| serde_json::from_str(&s).ok())
+                        .unwrap_or_default(),
                 })
             },
         )
@@ -672,7 +734,7 @@ pub fn list_cartridges(db: &Database) -> Result<Vec<CartridgeRecord>, GatewayErr
             .prepare(
                 "SELECT slug, name, description, version, price_usd, price_amount, \
                  owner_address, source_repo, wasm_path, wasm_hash, active, created_at, updated_at, \
-                 COALESCE(cartridge_type, 'backend') \
+                 COALESCE(cartridge_type, 'backend'), limits, pricing, triggers \
                  FROM cartridges WHERE active = 1 ORDER BY created_at DESC",
             )
             .map_err(|e| GatewayError::Internal(format!("list cartridges: {e}")))?;
@@ -696,6 +758,17 @@ pub fn list_cartridges(db: &Database) -> Result<Vec<CartridgeRecord>, GatewayErr
                     cartridge_type: row
                         .get::<_, String>(13)
                         .unwrap_or_else(|_| "backend".to_string()),
+                    limits: row
+                        .get::<_, Option<String>>(14)?
+                        .and_then(|s| serde_json::from_str(&s).ok())
+                        .unwrap_or_default(),
+                    pricing: row
+                        .get::<_, Option<String>>(15)?
+                        .and_then(|s| serde_json::from_str(&s).ok()),
+                    triggers: row
+                        .get::<_, Option<String>>(16)?
+                        .and_then(|s| serde_json::from_str(&s).ok())
+                        .unwrap_or_default(),
                 })
             })
             .map_err(|e| GatewayError::Internal(format!("list cartridges query: {e}")))?;
@@ -775,3 +848,80 @@ pub fn cartridge_kv_delete(db: &Database, slug: &str) -> Result<(), GatewayError
         Ok(())
     })
 }
+
+// ── Cartridge trigger runs ──────────────────────────────────────────
+
+/// How many runs to keep per cartridge. Older rows are pruned on insert.
+const MAX_TRIGGER_RUNS_PER_CARTRIDGE: i64 = 200;
+
+/// One scheduled or event-triggered cartridge execution.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TriggerRun {
+    pub slug: String,
+    /// Trigger label, e.g. `cron:*/5 * * * *` or `event:payment.settled`.
+    pub trigger: String,
+    pub started_at: i64,
+    pub duration_ms: u64,
+    /// HTTP status returned by the cartridge (None if execution failed).
+    pub status: Option<u16>,
+    pub fuel_consumed: u64,
+    pub error: Option<String>,
+}
+
+/// Record a trigger run and prune old history for the cartridge.
+pub fn record_trigger_run(db: &Database, run: &TriggerRun) -> Result<(), GatewayError> {
+    db.with_connection(|conn| {
+        conn.execute(
+            "INSERT INTO cartridge_trigger_runs \
+             (slug, trigger, started_at, duration_ms, status, fuel_consumed, error) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                run.slug,
+                run.trigger,
+                run.started_at,
+                run.duration_ms as i64,
+                run.status.map(|s| s as i64),
+                run.fuel_consumed as i64,
+                run.error,
+            ],
+        )
+        .map_err(|e| GatewayError::Internal(format!("record trigger run: {e}")))?;
+        conn.execute(
+            "DELETE FROM cartridge_trigger_runs WHERE slug = ?1 AND id NOT IN \
+             (SELECT id FROM cartridge_trigger_runs WHERE slug = ?1 ORDER BY id DESC LIMIT ?2)",
+            params![run.slug, MAX_TRIGGER_RUNS_PER_CARTRIDGE],
+        )
+        .map_err(|e| GatewayError::Internal(format!("prune trigger runs: {e}")))?;
+        Ok(())
+    })
+}
+
+/// Most recent trigger runs for a cartridge, newest first.
+pub fn list_trigger_runs(
+    db: &Database,
+    slug: &str,
+    limit: u32,
+) -> Result<Vec<TriggerRun>, GatewayError> {
+    db.with_connection(|conn| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT slug, trigger, started_at, duration_ms, status, fuel_consumed, error \
+                 FROM cartridge_trigger_runs WHERE slug = ?1 ORDER BY id DESC LIMIT ?2",
+            )
+            .map_err(|e| GatewayError::Internal(format!("list trigger runs: {e}")))?;
+        let rows = stmt
+            .query_map(params![slug, limit], |row| {
+                Ok(TriggerRun {
+                    slug: row.get(0)?,
+                    trigger: row.get(1)?,
+                    started_at: row.get(2)?,
+                    duration_ms: row.get::<_, i64>(3)? as u64,
+                    status: row.get::<_, Option<i64>>(4)?.map(|s| s as u16),
+                    fuel_consumed: row.get::<_, i64>(5)? as u64,
+                    error: row.get(6)?,
+                })
+            })
+            .map_err(|e| GatewayError::Internal(format!("list trigger runs query: {e}")))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    })
+}
@@ -37,6 +37,7 @@ mod routes;
 #[cfg(feature = "soul")]
 mod soul_observer;
 mod state;
+mod triggers;
 
 use state::NodeState;
 
@@ -227,6 +228,14 @@ async fn main() -> std::io::Result<()> {
         );
         // Propagate INSTANCE_ID to env so soul config picks it up
         std::env::set_var("INSTANCE_ID", &id.instance_id);
+        // Sign outgoing peer messages with the instance key
+        #[cfg(feature = "soul")]
+        match x402::WalletSigner::new(&id.private_key) {
+            Ok(signer) => {
+                x402_soul::envelope::configure_signer(signer, id.instance_id.clone());
+            }
+            Err(e) => tracing::warn!(error = %e, "Instance key unusable — peer messages can't be signed"),
+        }
         Some(id)
     } else {
         tracing::info!("AUTO_BOOTSTRAP not set — running without identity");
@@ -809,7 +818,8 @@ async fn main() -> std::io::Result<()> {
         },
         cartridge_engine: {
             let cartridge_dir = "/data/cartridges";
-            match x402_cartridge::CartridgeEngine::new(cartridge_dir) {
+            let policy = x402_cartridge::LimitPolicy::from_env();
+            match x402_cartridge::CartridgeEngine::with_policy(cartridge_dir, policy) {
                 Ok(engine) => {
                     // Auto-load any existing compiled cartridges
                     if let Ok(entries) = std::fs::read_dir(cartridge_dir) {
@@ -835,6 +845,33 @@ async fn main() -> std::io::Result<()> {
                             }
                         }
                     }
+                    // Cartridge builds and benchmark validation run in the
+                    // namespace sandbox unless SANDBOX_ALLOW_UNCONFINED opts
+                    // into the degraded mode.
+                    match (
+                        x402_cartridge::sandbox::check(),
+                        x402_cartridge::sandbox::mode(),
+                    ) {
+                        (Ok(()), _) => {}
+                        (Err(e), Ok(_)) => {
+                            tracing::error!(
+                                error = %e,
+                                "Build sandbox unavailable: SANDBOX_ALLOW_UNCONFINED is set, so \
+                                 cartridge compiles and benchmark runs execute untrusted code \
+                                 WITHOUT filesystem, network or process isolation"
+                            );
+                        }
+                        (Err(e), Err(_)) => {
+                            tracing::error!(
+                                error = %e,
+                                "Build sandbox unavailable: cartridge compiles and benchmark runs \
+                                 will fail. Allow unprivileged user namespaces (sysctl \
+                                 kernel.unprivileged_userns_clone=1, or under Docker \
+                                 --security-opt seccomp=unconfined), or set \
+                                 SANDBOX_ALLOW_UNCONFINED=1 to run them unisolated"
+                            );
+                        }
+                    }
                     let loaded = engine.loaded_slugs();
                     if !loaded.is_empty() {
                         tracing::info!(count = loaded.len(), slugs = ?loaded, "Cartridge engine initialized");
@@ -872,6 +909,9 @@ async fn main() -> std::io::Result<()> {
                                     created_at: now,
                                     updated_at: now,
                                     cartridge_type: cart_type,
+                                    limits: Default::default(),
+                                    pricing: None,
+                                    triggers: Vec::new(),
                                 };
                                 if let Err(e) = db::upsert_cartridge(&cartridge_db, &record) {
                                     tracing::warn!(slug = %slug, error = %e, "Failed to auto-register cartridge in DB");
@@ -881,6 +921,12 @@ async fn main() -> std::io::Result<()> {
                             }
                         }
                     }
+                    // Apply manifest-declared limits so nested x402_call targets honor them too
+                    if let Ok(records) = db::list_cartridges(&cartridge_db) {
+                        for record in records {
+                            engine.set_limits(&record.slug, record.limits);
+                        }
+                    }
                     Some(std::sync::Arc::new(engine))
                 }
                 Err(e) => {
@@ -889,8 +935,13 @@ async fn main() -> std::io::Result<()> {
                 }
             }
         },
+        cartridge_events: triggers::event_channel(),
+        metered_nonces: routes::cartridges::PendingNonces::default(),
     };
 
+    // ── Cartridge triggers ────────────────────────────────────────────
+    triggers::spawn(node_state.clone());
+
     let node_data = web::Data::new(node_state.clone());
     let gateway_data = web::Data::new(node_state.gateway.clone());
     let facilitator_data = facilitator_state.map(web::Data::from);
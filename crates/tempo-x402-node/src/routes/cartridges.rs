@@ -3,10 +3,20 @@
 //! Mirrors the script endpoint pattern (`/x/{slug}`) but executes
 //! precompiled WASM modules via wasmtime instead of bash scripts.
 
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
 use actix_web::{web, HttpRequest, HttpResponse};
-use alloy::primitives::Address;
+use alloy::primitives::{Address, FixedBytes};
 use serde::Deserialize;
-use x402_gateway::middleware::{endpoint_requirements, require_payment};
+use x402::payment::{PaymentPayload, PaymentRequirements};
+use x402::scheme::SchemeFacilitator;
+use x402_cartridge::triggers::{CUSTOM_EVENT_PREFIX, EVENT_PAYMENT_SETTLED};
+use x402_cartridge::{EffectiveLimits, ExecutionFailure, MeteredPricing, StreamEvent, Trigger};
+use x402_gateway::middleware::{
+    endpoint_requirements, extract_payment_header, payment_required_response, require_payment,
+    verify_and_settle,
+};
 
 use crate::db;
 use crate::state::NodeState;
@@ -40,6 +50,9 @@ pub async fn list_cartridges(state: web::Data<NodeState>) -> HttpResponse {
                         created_at: now,
                         updated_at: now,
                         cartridge_type: "frontend".to_string(),
+                        limits: Default::default(),
+                        pricing: None,
+                        triggers: Vec::new(),
                     };
                     let _ = db::upsert_cartridge(&state.gateway.db, &record);
                 }
@@ -84,6 +97,9 @@ pub async fn list_cartridges(state: web::Data<NodeState>) -> HttpResponse {
                     created_at: now,
                     updated_at: now,
                     cartridge_type: cartridge_type.to_string(),
+                    limits: Default::default(),
+                    pricing: None,
+                    triggers: Vec::new(),
                 };
                 let _ = db::upsert_cartridge(&state.gateway.db, &record);
             }
@@ -151,9 +167,32 @@ pub async fn handle_cartridge(
         }
     };
 
+    let engine = match state.cartridge_engine.clone() {
+        Some(e) => e,
+        None => {
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "cartridge engine not initialized"
+            }));
+        }
+    };
+    // Manifest limits are registered with the engine when the record is
+    // stored (and at startup), so nested x402_call invocations see them too.
+    let limits = engine.limits_for(&slug);
+
     // ── x402 payment gate ──
+    let mut metered: Option<MeteredAuthorization> = None;
     let owner_address = &cartridge.owner_address;
     if !owner_address.is_empty() {
+        if let Ok(owner) = owner_address.parse::<Address>() {
+            if let Some(ref pricing) = cartridge.pricing {
+                match authorize_metered(&req, &state, &slug, owner, pricing, &limits).await {
+                    Ok(auth) => metered = Some(auth),
+                    Err(http_response) => return http_response,
+                }
+            }
+        }
+    }
+    if !owner_address.is_empty() && metered.is_none() {
         if let Ok(owner) = owner_address.parse::<Address>() {
             let requirements = endpoint_requirements(
                 owner,
@@ -189,6 +228,14 @@ pub async fn handle_cartridge(
                 {
                     tracing::warn!(slug = %slug, error = %e, "Failed to record cartridge payment");
                 }
+                crate::triggers::emit(
+                    &state,
+                    EVENT_PAYMENT_SETTLED,
+                    serde_json::json!({
+                        "endpoint": format!("cartridge-{slug}"),
+                        "amount": cartridge.price_amount,
+                    }),
+                );
 
                 #[cfg(feature = "erc8004")]
                 if let Some(ref tx) = state.reputation_tx {
@@ -202,15 +249,6 @@ pub async fn handle_cartridge(
     }
 
     // ── Execute cartridge ──
-    let engine = match state.cartridge_engine.clone() {
-        Some(e) => e,
-        None => {
-            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
-                "error": "cartridge engine not initialized"
-            }));
-        }
-    };
-
     // Build request
     let method = req.method().to_string();
     let req_path = req.match_info().get("path").unwrap_or("/").to_string();
@@ -225,60 +263,377 @@ pub async fn handle_cartridge(
     let cartridge_request = x402_cartridge::CartridgeRequest {
         method,
         path: req_path,
+        query: req.query_string().to_string(),
         body: body_str,
         headers,
         payment: None, // TODO: populate from settle result
+        raw_body: Some(body.to_vec()),
     };
 
     // Load KV store for this cartridge
     let kv = db::cartridge_kv_load(&state.gateway.db, &slug).unwrap_or_default();
 
-    // Execute in blocking thread pool (wasmtime is synchronous)
+    // Execute in blocking thread pool (wasmtime is synchronous).
+    // Unmetered cartridges may stream via x402_write. Metered ones are buffered
+    // because the cost must be settled before the response is committed.
     let slug_clone = slug.clone();
-    let result = web::block(move || engine.execute_with_composition(&slug_clone, &cartridge_request, kv, 30))
-        .await
-        .unwrap_or_else(|e| {
-            Err(x402_cartridge::CartridgeError::ExecutionFailed(format!(
-                "block: {e}"
-            )))
+    let fuel_budget = metered.as_ref().map(|m| m.fuel_budget);
+    let (sink, mut events) = tokio::sync::mpsc::channel::<StreamEvent>(16);
+    let task = tokio::task::spawn_blocking(move || match fuel_budget {
+        Some(budget) => {
+            drop(sink);
+            engine.execute_metered(&slug_clone, &cartridge_request, kv, 30, budget)
+        }
+        None => engine
+            .execute_streaming(&slug_clone, &cartridge_request, kv, 30, sink)
+            .map_err(ExecutionFailure::from),
+    });
+
+    // The first event is the head if the cartridge streams; the channel closes
+    // without events if it finishes without calling x402_write.
+    if let Some(StreamEvent::Head {
+        status,
+        content_type,
+        headers,
+        usage,
+    }) = events.recv().await
+    {
+        let db = state.gateway.db.clone();
+        let slug_bg = slug.clone();
+        actix_web::rt::spawn(async move {
+            match task.await {
+                Ok(Ok((r, kv_out))) => {
+                    tracing::info!(
+                        slug = %slug_bg,
+                        status = r.status,
+                        duration_ms = r.duration_ms,
+                        fuel = r.usage.fuel_consumed,
+                        "Cartridge stream finished"
+                    );
+                    if !kv_out.is_empty() {
+                        if let Err(e) = db::cartridge_kv_save(&db, &slug_bg, &kv_out) {
+                            tracing::warn!(slug = %slug_bg, error = %e, "Failed to persist cartridge KV");
+                        }
+                    }
+                }
+                Ok(Err(e)) => {
+                    tracing::warn!(slug = %slug_bg, error = %e, "Cartridge stream failed")
+                }
+                Err(e) => tracing::warn!(slug = %slug_bg, error = %e, "Cartridge task panicked"),
+            }
         });
 
+        let mut response = HttpResponse::build(
+            actix_web::http::StatusCode::from_u16(status)
+                .unwrap_or(actix_web::http::StatusCode::OK),
+        );
+        // Totals are only known once the stream ends; the head carries what
+        // was consumed before the first x402_write.
+        response
+            .content_type(content_type)
+            .append_header(("X-Cartridge-Fuel", usage.fuel_consumed.to_string()))
+            .append_header((
+                "X-Cartridge-Peak-Memory",
+                usage.peak_memory_bytes.to_string(),
+            ));
+        for (name, value) in headers {
+            response.append_header((name, value));
+        }
+        let chunks = tokio_stream::StreamExt::filter_map(
+            tokio_stream::wrappers::ReceiverStream::new(events),
+            |event| match event {
+                StreamEvent::Chunk(bytes) => {
+                    Some(Ok::<_, actix_web::Error>(web::Bytes::from(bytes)))
+                }
+                StreamEvent::Head { .. } => None,
+            },
+        );
+        return response.streaming(chunks);
+    }
+
+    let result = task.await.unwrap_or_else(|e| {
+        Err(x402_cartridge::CartridgeError::ExecutionFailed(format!("block: {e}")).into())
+    });
+
     match result {
         Ok((r, kv_out)) => {
             tracing::info!(
                 slug = %slug,
                 status = r.status,
                 duration_ms = r.duration_ms,
+                fuel = r.usage.fuel_consumed,
+                peak_memory = r.usage.peak_memory_bytes,
                 "Cartridge executed"
             );
+
+            // Metered: settle the measured cost against the pre-authorized maximum
+            let mut cost = None;
+            if let (Some(auth), Some(pricing)) = (metered, cartridge.pricing.as_ref()) {
+                let amount = pricing.cost(&r.usage);
+                if let Err(http_response) = settle_metered(&state, &slug, auth, amount).await {
+                    return http_response;
+                }
+                cost = Some(amount);
+            }
+
             // Persist modified KV store back to DB
             if !kv_out.is_empty() {
                 if let Err(e) = db::cartridge_kv_save(&state.gateway.db, &slug, &kv_out) {
                     tracing::warn!(slug = %slug, error = %e, "Failed to persist cartridge KV");
                 }
             }
-            HttpResponse::build(
+            let mut response = HttpResponse::build(
                 actix_web::http::StatusCode::from_u16(r.status)
                     .unwrap_or(actix_web::http::StatusCode::OK),
-            )
-            .content_type(r.content_type)
-            .body(r.body)
+            );
+            response
+                .content_type(r.content_type)
+                .append_header(("X-Cartridge-Fuel", r.usage.fuel_consumed.to_string()))
+                .append_header((
+                    "X-Cartridge-Peak-Memory",
+                    r.usage.peak_memory_bytes.to_string(),
+                ));
+            if let Some(amount) = cost {
+                response.append_header(("X-Cartridge-Cost", amount.to_string()));
+            }
+            for (name, value) in r.headers {
+                response.append_header((name, value));
+            }
+            response.body(r.body)
         }
-        Err(e) => {
-            tracing::warn!(slug = %slug, error = %e, "Cartridge execution failed");
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("{e}"),
+        Err(failure) => {
+            tracing::warn!(
+                slug = %slug,
+                error = %failure,
+                fuel = failure.usage.fuel_consumed,
+                "Cartridge execution failed"
+            );
+
+            // Metered: a failed run still consumed resources. Running out of
+            // fuel consumes the whole budget, so it's charged in full.
+            let mut cost = None;
+            if let (Some(auth), Some(pricing)) = (metered, cartridge.pricing.as_ref()) {
+                let amount = pricing.cost(&failure.usage);
+                if let Err(http_response) = settle_metered(&state, &slug, auth, amount).await {
+                    return http_response;
+                }
+                cost = Some(amount);
+            }
+
+            let mut response = HttpResponse::InternalServerError();
+            response.append_header(("X-Cartridge-Fuel", failure.usage.fuel_consumed.to_string()));
+            if let Some(amount) = cost {
+                response.append_header(("X-Cartridge-Cost", amount.to_string()));
+            }
+            response.json(serde_json::json!({
+                "error": format!("{failure}"),
             }))
         }
     }
 }
 
+/// Payment nonces of metered calls that are between pre-authorization and
+/// settlement. `verify` only checks that a nonce is unused; the facilitator
+/// claims it at settlement, after the cartridge has run. Reserving it first
+/// keeps concurrent requests from running on one signed payment.
+#[derive(Clone, Default)]
+pub struct PendingNonces(Arc<Mutex<HashSet<FixedBytes<32>>>>);
+
+impl PendingNonces {
+    /// Reserve `nonce`; `None` while another request holds it.
+    fn reserve(&self, nonce: FixedBytes<32>) -> Option<NonceReservation> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(nonce)
+            .then(|| NonceReservation {
+                pending: self.clone(),
+                nonce,
+            })
+    }
+}
+
+/// Releases its nonce when dropped: after settlement, where the facilitator
+/// has claimed it for good, or when the call fails before that.
+struct NonceReservation {
+    pending: PendingNonces,
+    nonce: FixedBytes<32>,
+}
+
+impl Drop for NonceReservation {
+    fn drop(&mut self) {
+        self.pending.0.lock().unwrap().remove(&self.nonce);
+    }
+}
+
+/// A verified pre-authorization for a metered cartridge call.
+struct MeteredAuthorization {
+    payload: PaymentPayload,
+    requirements: PaymentRequirements,
+    fuel_budget: u64,
+    _reservation: NonceReservation,
+}
+
+/// Reserve the payment's nonce, verify the payer's pre-authorized maximum
+/// (the signed `value`) and derive a fuel budget that keeps the cost within
+/// it. Nothing is settled yet.
+async fn authorize_metered(
+    req: &HttpRequest,
+    state: &NodeState,
+    slug: &str,
+    owner: Address,
+    pricing: &MeteredPricing,
+    limits: &EffectiveLimits,
+) -> Result<MeteredAuthorization, HttpResponse> {
+    let mut requirements = endpoint_requirements(
+        owner,
+        &metered_price_label(pricing),
+        &pricing.max_cost(limits).to_string(),
+        Some(&format!(
+            "Metered WASM cartridge: /c/{slug} (base {} + {} per megafuel + {} per MB)",
+            pricing.base_amount, pricing.per_megafuel_amount, pricing.per_mb_amount
+        )),
+        state
+            .gateway
+            .facilitator
+            .as_ref()
+            .map(|f| f.facilitator.facilitator_address()),
+    );
+    requirements.metered = true;
+
+    let payload = match extract_payment_header(req) {
+        Some(p) => p,
+        None => return Err(payment_required_response(requirements)),
+    };
+
+    // Pre-authorization needs an in-process verify before running the cartridge
+    let facilitator = match state.gateway.facilitator.as_deref() {
+        Some(f) => f,
+        None => {
+            return Err(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "metered cartridges require an embedded facilitator"
+            })));
+        }
+    };
+
+    let max_amount = payload.payload.value.parse::<u64>().unwrap_or(0);
+    let fuel_budget = match pricing.fuel_budget(max_amount, limits) {
+        Some(b) if b > 0 => b,
+        _ => {
+            return Err(HttpResponse::PaymentRequired().json(serde_json::json!({
+                "error": "payment_failed",
+                "message": "pre-authorized amount does not cover base and memory charges",
+                "accepts": [requirements],
+            })));
+        }
+    };
+
+    // Reserve before verifying: once the reservation is held, a nonce that
+    // verify reports unused can't be settled by anyone else first.
+    let Some(reservation) = state.metered_nonces.reserve(payload.payload.nonce) else {
+        return Err(HttpResponse::PaymentRequired().json(serde_json::json!({
+            "error": "payment_failed",
+            "message": "payment authorization is already in use by another request",
+            "accepts": [requirements],
+        })));
+    };
+
+    let mut preauth = requirements.clone();
+    preauth.amount = max_amount.to_string();
+    match facilitator.facilitator.verify(&payload, &preauth).await {
+        Ok(v) if v.is_valid => {}
+        Ok(v) => {
+            return Err(HttpResponse::PaymentRequired().json(serde_json::json!({
+                "error": "payment_failed",
+                "message": v.invalid_reason.unwrap_or_else(|| "invalid payment".to_string()),
+                "accepts": [requirements],
+            })));
+        }
+        Err(e) => {
+            return Err(HttpResponse::PaymentRequired().json(serde_json::json!({
+                "error": "payment_failed",
+                "message": e.to_string(),
+                "accepts": [requirements],
+            })));
+        }
+    }
+
+    Ok(MeteredAuthorization {
+        payload,
+        requirements,
+        fuel_budget,
+        _reservation: reservation,
+    })
+}
+
+/// Human-readable price for a metered 402: the base charge in USD.
+/// Token amounts use 6 decimals (1000 = $0.001).
+fn metered_price_label(pricing: &MeteredPricing) -> String {
+    format!("${:.6}", pricing.base_amount as f64 / 1_000_000.0)
+}
+
+/// Settle `amount` against a verified pre-authorization and record the payment.
+async fn settle_metered(
+    state: &NodeState,
+    slug: &str,
+    auth: MeteredAuthorization,
+    amount: u64,
+) -> Result<(), HttpResponse> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let mut requirements = auth.requirements;
+    requirements.amount = amount.to_string();
+    if let Err(e) = verify_and_settle(
+        &state.gateway.http_client,
+        &state.gateway.config.facilitator_url,
+        state.gateway.config.hmac_secret.as_deref(),
+        state.gateway.facilitator.as_deref(),
+        &auth.payload,
+        &requirements,
+    )
+    .await
+    {
+        tracing::warn!(slug = %slug, error = %e, "Metered cartridge settlement failed");
+        return Err(HttpResponse::PaymentRequired().json(serde_json::json!({
+            "error": "payment_failed",
+            "message": e.to_string(),
+        })));
+    }
+
+    if let Err(e) = state
+        .gateway
+        .db
+        .record_payment(&format!("cartridge-{slug}"), &requirements.amount)
+    {
+        tracing::warn!(slug = %slug, error = %e, "Failed to record cartridge payment");
+    }
+    crate::triggers::emit(
+        state,
+        EVENT_PAYMENT_SETTLED,
+        serde_json::json!({
+            "endpoint": format!("cartridge-{slug}"),
+            "amount": requirements.amount,
+        }),
+    );
+    Ok(())
+}
+
 #[derive(Deserialize)]
 pub struct UploadCartridge {
     pub slug: String,
     pub name: Option<String>,
     pub description: Option<String>,
     pub source_code: Option<String>,
+    /// Requested resource limits (capped by node policy).
+    #[serde(default)]
+    pub limits: x402_cartridge::ResourceLimits,
+    /// Metered pricing (base + per-megafuel + per-MB).
+    #[serde(default)]
+    pub pricing: Option<MeteredPricing>,
+    /// Cron schedules and node events that invoke the cartridge.
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
 }
 
 /// `POST /admin/cartridges` — register and optionally compile a new cartridge.
@@ -288,6 +643,13 @@ pub async fn upload_cartridge(
 ) -> HttpResponse {
     let slug = &body.slug;
     let name = body.name.as_deref().unwrap_or(slug);
+    for trigger in &body.triggers {
+        if let Err(e) = trigger.validate() {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("{e}")
+            }));
+        }
+    }
     let cartridge_dir = format!("/data/cartridges/{slug}");
     let src_dir = format!("{cartridge_dir}/src");
     let bin_dir = format!("{cartridge_dir}/bin");
@@ -345,12 +707,18 @@ pub async fn upload_cartridge(
         created_at: now,
         updated_at: now,
         cartridge_type: "backend".to_string(),
+        limits: body.limits.clone(),
+        pricing: body.pricing.clone(),
+        triggers: body.triggers.clone(),
     };
     if let Err(e) = db::upsert_cartridge(&state.gateway.db, &record) {
         return HttpResponse::InternalServerError().json(serde_json::json!({
             "error": format!("failed to register: {e}")
         }));
     }
+    if let Some(ref engine) = state.cartridge_engine {
+        engine.set_limits(&record.slug, record.limits.clone());
+    }
 
     HttpResponse::Ok().json(serde_json::json!({
         "status": "created",
@@ -560,6 +928,9 @@ pub async fn get_cartridge_manifest(
             "version": c.version,
             "cartridge_type": c.cartridge_type,
             "price": c.price_usd,
+            "limits": c.limits,
+            "pricing": c.pricing,
+            "triggers": c.triggers,
         })),
         Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
             "error": format!("cartridge '{slug}' not found")
@@ -570,12 +941,70 @@ pub async fn get_cartridge_manifest(
     }
 }
 
+#[derive(Deserialize)]
+pub struct RunsQuery {
+    pub limit: Option<u32>,
+}
+
+/// `GET /c/{slug}/runs` — recent scheduled and event-triggered executions.
+pub async fn list_trigger_runs(
+    state: web::Data<NodeState>,
+    path: web::Path<String>,
+    query: web::Query<RunsQuery>,
+) -> HttpResponse {
+    let slug = path.into_inner();
+    let limit = query.limit.unwrap_or(50).min(200);
+    match db::list_trigger_runs(&state.gateway.db, &slug, limit) {
+        Ok(runs) => {
+            let failures = runs.iter().filter(|r| r.error.is_some()).count();
+            HttpResponse::Ok().json(serde_json::json!({
+                "slug": slug,
+                "runs": runs,
+                "count": runs.len(),
+                "failures": failures,
+            }))
+        }
+        Err(e) => {
+            tracing::warn!(slug = %slug, error = %e, "Failed to list trigger runs");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "failed to list trigger runs"
+            }))
+        }
+    }
+}
+
+/// `POST /admin/cartridges/events/{name}` — emit an internal event.
+///
+/// `name` is the part after `custom.`; the JSON body is passed through as the
+/// event payload to every cartridge with a matching event trigger.
+pub async fn emit_custom_event(
+    state: web::Data<NodeState>,
+    path: web::Path<String>,
+    body: Option<web::Json<serde_json::Value>>,
+) -> HttpResponse {
+    let name = format!("{CUSTOM_EVENT_PREFIX}{}", path.into_inner());
+    let trigger = Trigger::Event {
+        event: name.clone(),
+    };
+    if let Err(e) = trigger.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("{e}")
+        }));
+    }
+    let payload = body.map(|b| b.into_inner()).unwrap_or_default();
+    crate::triggers::emit(&state, &name, payload);
+    HttpResponse::Accepted().json(serde_json::json!({
+        "emitted": name,
+    }))
+}
+
 /// Configure cartridge routes.
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.route("/c", web::get().to(list_cartridges))
         .route("/c/{slug}/wasm", web::get().to(serve_wasm_binary))
         .route("/c/{slug}/manifest", web::get().to(get_cartridge_manifest))
         .route("/c/{slug}/pkg/{file}", web::get().to(serve_frontend_pkg))
+        .route("/c/{slug}/runs", web::get().to(list_trigger_runs))
         .route("/c/{slug}", web::get().to(handle_cartridge))
         .route("/c/{slug}", web::post().to(handle_cartridge))
         .route("/c/{slug}", web::delete().to(delete_cartridge_handler))
@@ -589,5 +1018,40 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         .route(
             "/admin/cartridges/{slug}/compile",
             web::post().to(compile_cartridge),
+        )
+        .route(
+            "/admin/cartridges/events/{name}",
+            web::post().to(emit_custom_event),
         );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_requests_cannot_share_a_payment() {
+        let pending = PendingNonces::default();
+        let nonce = FixedBytes::<32>::repeat_byte(7);
+        let barrier = std::sync::Barrier::new(2);
+        let reserved: Vec<bool> = std::thread::scope(|s| {
+            let requests: Vec<_> = (0..2)
+                .map(|_| {
+                    s.spawn(|| {
+                        barrier.wait();
+                        let reservation = pending.reserve(nonce);
+                        // Hold the reservation until both requests have tried.
+                        barrier.wait();
+                        reservation.is_some()
+                    })
+                })
+                .collect();
+            requests.into_iter().map(|r| r.join().unwrap()).collect()
+        });
+        assert_eq!(reserved.iter().filter(|r| **r).count(), 1);
+
+        // Settled or failed: the nonce is free for the facilitator to judge.
+        assert!(pending.reserve(nonce).is_some());
+        assert!(pending.reserve(FixedBytes::repeat_byte(8)).is_some());
+    }
+}
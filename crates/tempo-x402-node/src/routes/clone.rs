@@ -68,6 +68,7 @@ pub async fn clone_instance(
         description: Some("Clone instance fee".to_string()),
         mime_type: Some("application/json".to_string()),
         facilitator_address: None,
+        metered: false,
     };
 
     // Early 402 if no payment header
@@ -122,6 +122,14 @@ pub async fn handle_script(
                 {
                     tracing::warn!(slug = %slug, error = %e, "Failed to record script payment");
                 }
+                crate::triggers::emit(
+                    &state,
+                    x402_cartridge::triggers::EVENT_PAYMENT_SETTLED,
+                    serde_json::json!({
+                        "endpoint": format!("script-{slug}"),
+                        "amount": price_amount,
+                    }),
+                );
 
                 // Send settlement event for reputation tracking
                 #[cfg(feature = "erc8004")]
@@ -316,9 +324,11 @@ pub async fn handle_app(
                 let request = x402_cartridge::CartridgeRequest {
                     method,
                     path: "/".to_string(),
+                    query: req.query_string().to_string(),
                     body: body_str,
                     headers: std::collections::HashMap::new(),
                     payment: None,
+                    raw_body: Some(body.to_vec()),
                 };
                 match tokio::task::block_in_place(|| {
                     engine.execute(&slug, &request, Default::default(), 30)
@@ -258,3 +258,12 @@ pub(super) async fn trigger_benchmark(state: web::Data<NodeState>) -> HttpRespon
         "problems_attempted": current.as_ref().map(|s| s.problems_attempted).unwrap_or(0),
     }))
 }
+
+/// DELETE /soul/benchmark — cancel the running benchmark session.
+/// Running sandboxes are killed and the session records no score.
+pub(super) async fn cancel_benchmark() -> HttpResponse {
+    let cancelled = x402_soul::benchmark::cancel_benchmark_session();
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": if cancelled { "cancelled" } else { "idle" },
+    }))
+}
@@ -15,36 +15,39 @@ pub(super) async fn get_brain_weights(state: web::Data<NodeState>) -> HttpRespon
     }))
 }
 
-/// POST /soul/brain/merge — merge weight delta from a peer.
-#[derive(Deserialize)]
-pub(crate) struct MergeDeltaRequest {
-    delta: String,
-    merge_rate: Option<f32>,
-}
-
-pub(super) async fn merge_brain_delta(
+/// GET /soul/federated/{model}/delta — this node's compressed weight delta
+/// for `brain`, `plan`, `unified` or `codegen` (binary, see
+/// `x402_model::federated`). 404 until the model has trained since its
+/// first publication.
+pub(super) async fn get_federated_delta(
     state: web::Data<NodeState>,
-    body: web::Json<MergeDeltaRequest>,
+    path: web::Path<String>,
 ) -> HttpResponse {
-    let Some(soul_db) = &state.soul_db else {
+    let Some(soul_db) = state.soul_db.clone() else {
         return HttpResponse::ServiceUnavailable().json(serde_json::json!({"error": "no soul"}));
     };
-    let delta: x402_soul::brain::WeightDelta = match serde_json::from_str(&body.delta) {
-        Ok(d) => d,
-        Err(e) => {
-            return HttpResponse::BadRequest()
-                .json(serde_json::json!({"error": format!("invalid delta: {e}")}));
+    let Some(model) = x402_soul::federation::FedModel::parse(&path.into_inner()) else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "unknown model"}));
+    };
+    // Loading the larger models and cutting a delta is CPU-bound.
+    match web::block(move || x402_soul::federation::published_delta(&soul_db, model)).await {
+        Ok(Some(bytes)) => HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .body(bytes),
+        Ok(None) => {
+            HttpResponse::NotFound().json(serde_json::json!({"error": "no delta published yet"}))
         }
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": format!("delta failed: {e}")})),
+    }
+}
+
+/// GET /soul/federated — last federated merge report per model.
+pub(super) async fn get_federation_status(state: web::Data<NodeState>) -> HttpResponse {
+    let Some(soul_db) = &state.soul_db else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({"error": "no soul"}));
     };
-    let merge_rate = body.merge_rate.unwrap_or(0.5);
-    let mut brain = x402_soul::brain::load_brain(soul_db);
-    brain.merge_delta(&delta, merge_rate);
-    x402_soul::brain::save_brain(soul_db, &brain);
-    HttpResponse::Ok().json(serde_json::json!({
-        "merged": true,
-        "train_steps": brain.train_steps,
-        "source": delta.source_id,
-    }))
+    HttpResponse::Ok().json(x402_soul::federation::status(soul_db))
 }
 
 // ── Experience sharing endpoints ──
@@ -206,17 +209,8 @@ pub(super) async fn get_transformer_weights(state: web::Data<NodeState>) -> Http
     }))
 }
 
-/// POST /soul/model/transformer/merge — merge transformer weight delta from a peer.
-#[derive(Deserialize)]
-pub(crate) struct TransformerMergeRequest {
-    delta: String,
-    merge_rate: Option<f32>,
-}
-
-pub(super) async fn merge_transformer_delta(
-    state: web::Data<NodeState>,
-    body: web::Json<TransformerMergeRequest>,
-) -> HttpResponse {
+/// GET /soul/model/transformer/checkpoint — transformer weights as a binary checkpoint.
+pub(super) async fn get_transformer_checkpoint(state: web::Data<NodeState>) -> HttpResponse {
     let soul_db = match state.soul_db.as_ref() {
         Some(db) => db,
         None => {
@@ -224,19 +218,7 @@ pub(super) async fn merge_transformer_delta(
                 .json(serde_json::json!({"error": "soul not active"}))
         }
     };
-    let delta: x402_soul::model::TransformerDelta = match serde_json::from_str(&body.delta) {
-        Ok(d) => d,
-        Err(e) => {
-            return HttpResponse::BadRequest()
-                .json(serde_json::json!({"error": format!("invalid delta: {e}")}));
-        }
-    };
-    let merge_rate = body.merge_rate.unwrap_or(0.5);
-    x402_soul::model::merge_peer_delta(soul_db, &delta, merge_rate);
-    let status = x402_soul::model::status(soul_db);
-    HttpResponse::Ok().json(serde_json::json!({
-        "merged": true,
-        "train_steps": status.train_steps,
-        "source": delta.source_id,
-    }))
+    HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .body(x402_soul::model::export_checkpoint(soul_db))
 }
@@ -1,15 +1,20 @@
 //! Colony coordination endpoints — worker registration, benchmark distribution, work assignment.
 //!
 //! These endpoints are served by the QUEEN node. Workers call them to participate
-//! in the collective consciousness.
+//! in the collective consciousness. POST bodies are signed peer envelopes
+//! (see [`super::peer_auth`]); the worker id is the envelope's signed sender,
+//! and results and training data are only accepted from a worker registered
+//! under that id with the signing address.
 
+use super::peer_auth::{self, PeerMessage};
 use super::*;
+use x402_soul::envelope::PeerEnvelope;
 
 /// POST /soul/colony/register — Worker registers or heartbeats.
 /// Body: { "instance_id": "...", "url": "..." }
 pub(super) async fn colony_register(
     state: web::Data<NodeState>,
-    body: web::Json<serde_json::Value>,
+    envelope: web::Json<PeerEnvelope>,
 ) -> HttpResponse {
     let soul_db = match &state.soul_db {
         Some(db) => db,
@@ -19,18 +24,47 @@ pub(super) async fn colony_register(
         }
     };
 
-    let instance_id = body
-        .get("instance_id")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
+    let PeerMessage {
+        sender,
+        address,
+        body,
+    } = match peer_auth::open::<serde_json::Value>(&state, "/soul/colony/register", &envelope)
+        .await
+    {
+        Ok(message) => message,
+        Err(response) => return response,
+    };
+    let instance_id = sender.as_str();
     let url = body.get("url").and_then(|v| v.as_str()).unwrap_or("");
 
     if instance_id.is_empty() || url.is_empty() {
         return HttpResponse::BadRequest()
             .json(serde_json::json!({"error": "instance_id and url required"}));
     }
+    if body
+        .get("instance_id")
+        .and_then(|v| v.as_str())
+        .is_some_and(|id| id != instance_id)
+    {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "instance_id does not match signer"}));
+    }
 
-    x402_soul::collective::register_worker(soul_db, instance_id, url);
+    let is_new = !x402_soul::collective::get_live_workers(soul_db)
+        .iter()
+        .any(|w| w.instance_id == instance_id);
+    if let Err(e) =
+        x402_soul::collective::register_worker(soul_db, instance_id, url, &address.to_string())
+    {
+        return HttpResponse::Forbidden().json(serde_json::json!({"error": e}));
+    }
+    if is_new {
+        crate::triggers::emit(
+            &state,
+            x402_cartridge::triggers::EVENT_PEER_JOINED,
+            serde_json::json!({ "instance_id": instance_id, "url": url }),
+        );
+    }
 
     let workers = x402_soul::collective::get_live_workers(soul_db);
     HttpResponse::Ok().json(serde_json::json!({
@@ -121,7 +155,7 @@ pub(super) async fn colony_benchmark_assignment(
 /// Body: { "session_id": "...", "worker_id": "...", "results": [...] }
 pub(super) async fn colony_benchmark_result(
     state: web::Data<NodeState>,
-    body: web::Json<serde_json::Value>,
+    envelope: web::Json<PeerEnvelope>,
 ) -> HttpResponse {
     let soul_db = match &state.soul_db {
         Some(db) => db,
@@ -131,7 +165,25 @@ pub(super) async fn colony_benchmark_result(
         }
     };
 
-    let worker_id = body.get("worker_id").and_then(|v| v.as_str()).unwrap_or("");
+    let PeerMessage {
+        sender,
+        address,
+        body,
+    } = match peer_auth::open::<serde_json::Value>(
+        &state,
+        "/soul/colony/benchmark/result",
+        &envelope,
+    )
+    .await
+    {
+        Ok(message) => message,
+        Err(response) => return response,
+    };
+    if let Err(e) = x402_soul::collective::registered_worker(soul_db, &sender, &address.to_string())
+    {
+        return HttpResponse::Forbidden().json(serde_json::json!({"error": e}));
+    }
+    let worker_id = sender.as_str();
     let results = body
         .get("results")
         .and_then(|v| v.as_array())
@@ -145,6 +197,9 @@ pub(super) async fn colony_benchmark_result(
         if let Ok(result) =
             serde_json::from_value::<x402_soul::collective::BenchmarkResult>(result_val.clone())
         {
+            if !x402_soul::problem_pack::valid_slug(&result.slug) {
+                continue;
+            }
             attempted += 1;
             if result.passed {
                 passed += 1;
@@ -165,6 +220,7 @@ pub(super) async fn colony_benchmark_result(
                 starter_code: String::new(),
                 difficulty: String::new(),
                 cargo_toml: String::new(),
+                pack: String::new(),
             };
             let run = x402_soul::benchmark::BenchmarkRun {
                 id: uuid::Uuid::new_v4().to_string(),
@@ -175,6 +231,8 @@ pub(super) async fn colony_benchmark_result(
                 error_output: result.error_output.chars().take(500).collect(),
                 total_ms: result.total_ms,
                 created_at: chrono::Utc::now().timestamp(),
+                pack: result.pack,
+                report: result.report,
             };
             if let Err(e) = soul_db.insert_benchmark_run(&run) {
                 tracing::warn!(error = %e, "Failed to record worker benchmark run");
@@ -200,7 +258,7 @@ pub(super) async fn colony_benchmark_result(
 /// Body: { "examples": [...] }
 pub(super) async fn colony_train(
     state: web::Data<NodeState>,
-    body: web::Json<serde_json::Value>,
+    envelope: web::Json<PeerEnvelope>,
 ) -> HttpResponse {
     let soul_db = match &state.soul_db {
         Some(db) => db,
@@ -210,6 +268,20 @@ pub(super) async fn colony_train(
         }
     };
 
+    let PeerMessage {
+        sender,
+        address,
+        body,
+    } = match peer_auth::open::<serde_json::Value>(&state, "/soul/colony/train", &envelope)
+        .await
+    {
+        Ok(message) => message,
+        Err(response) => return response,
+    };
+    if let Err(e) = x402_soul::collective::registered_worker(soul_db, &sender, &address.to_string())
+    {
+        return HttpResponse::Forbidden().json(serde_json::json!({"error": e}));
+    }
     let examples = body
         .get("examples")
         .and_then(|v| v.as_array())
@@ -244,7 +316,7 @@ pub(super) async fn colony_train(
 /// Body: { "worker_id": "..." }
 pub(super) async fn colony_work(
     state: web::Data<NodeState>,
-    body: web::Json<serde_json::Value>,
+    envelope: web::Json<PeerEnvelope>,
 ) -> HttpResponse {
     let _soul_db = match &state.soul_db {
         Some(db) => db,
@@ -254,7 +326,13 @@ pub(super) async fn colony_work(
         }
     };
 
-    let _worker_id = body.get("worker_id").and_then(|v| v.as_str()).unwrap_or("");
+    let _worker_id =
+        match peer_auth::open::<serde_json::Value>(&state, "/soul/colony/work", &envelope)
+            .await
+        {
+            Ok(message) => message.sender,
+            Err(response) => return response,
+        };
 
     // For now, return 204 No Content — work distribution will be added
     // as the plan execution pipeline matures. The distributed benchmark
@@ -265,7 +343,7 @@ pub(super) async fn colony_work(
 /// POST /soul/colony/report — Worker reports task completion.
 pub(super) async fn colony_report(
     state: web::Data<NodeState>,
-    body: web::Json<serde_json::Value>,
+    envelope: web::Json<PeerEnvelope>,
 ) -> HttpResponse {
     let _soul_db = match &state.soul_db {
         Some(db) => db,
@@ -275,6 +353,14 @@ pub(super) async fn colony_report(
         }
     };
 
+    let body =
+        match peer_auth::open::<serde_json::Value>(&state, "/soul/colony/report", &envelope)
+            .await
+        {
+            Ok(message) => message.body,
+            Err(response) => return response,
+        };
+
     // Accept and log — plan step distribution will be implemented
     // after distributed benchmark proves the coordination protocol works.
     tracing::info!(body = %body, "Work report received from worker");
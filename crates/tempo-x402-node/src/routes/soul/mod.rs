@@ -9,6 +9,7 @@ mod colony_routes;
 mod diagnostics;
 mod lifecycle;
 mod nudges;
+mod peer_auth;
 mod plans;
 mod status;
 
@@ -85,6 +86,31 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         .route("/soul/plan/approve", web::post().to(plans::plan_approve))
         .route("/soul/plan/reject", web::post().to(plans::plan_reject))
         .route("/soul/plan/pending", web::get().to(plans::plan_pending))
+        .route(
+            "/soul/colony/register",
+            web::post().to(colony_routes::colony_register),
+        )
+        .route(
+            "/soul/colony/peers",
+            web::get().to(colony_routes::colony_peers),
+        )
+        .route(
+            "/soul/colony/benchmark/assignment",
+            web::get().to(colony_routes::colony_benchmark_assignment),
+        )
+        .route(
+            "/soul/colony/benchmark/result",
+            web::post().to(colony_routes::colony_benchmark_result),
+        )
+        .route(
+            "/soul/colony/train",
+            web::post().to(colony_routes::colony_train),
+        )
+        .route("/soul/colony/work", web::post().to(colony_routes::colony_work))
+        .route(
+            "/soul/colony/report",
+            web::post().to(colony_routes::colony_report),
+        )
         .route("/soul/nudge", web::post().to(nudges::soul_nudge))
         .route("/soul/nudges", web::get().to(nudges::soul_nudges))
         .route(
@@ -110,8 +136,24 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             web::get().to(brain::get_brain_weights),
         )
         .route(
-            "/soul/brain/merge",
-            web::post().to(brain::merge_brain_delta),
+            "/soul/model/transformer",
+            web::get().to(brain::get_transformer_status),
+        )
+        .route(
+            "/soul/model/transformer/weights",
+            web::get().to(brain::get_transformer_weights),
+        )
+        .route(
+            "/soul/model/transformer/checkpoint",
+            web::get().to(brain::get_transformer_checkpoint),
+        )
+        .route(
+            "/soul/federated",
+            web::get().to(brain::get_federation_status),
+        )
+        .route(
+            "/soul/federated/{model}/delta",
+            web::get().to(brain::get_federated_delta),
         )
         .route("/soul/lessons", web::get().to(brain::get_lessons))
         .route("/soul/diagnostics", web::get().to(diagnostics::diagnostics))
@@ -119,6 +161,10 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         .route(
             "/soul/benchmark",
             web::post().to(benchmark::trigger_benchmark),
+        )
+        .route(
+            "/soul/benchmark",
+            web::delete().to(benchmark::cancel_benchmark),
         );
 }
 
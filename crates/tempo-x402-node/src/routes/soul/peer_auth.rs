@@ -0,0 +1,198 @@
+//! Verification of signed peer messages for colony and weight-merge endpoints.
+//!
+//! Peers POST a [`PeerEnvelope`] (see `x402_soul::envelope`). [`open`] checks
+//! its signature, recipient, path and timestamp, that the signer is a known
+//! peer, and that the nonce hasn't been used. Known peers are: addresses
+//! listed in `PEER_ADDRESSES`, our parent, our children and linked peers, and
+//! (with `erc8004`) owners of an agent identity on the registry.
+//!
+//! The recipient must be one of this node's configured public hosts
+//! (`NODE_PUBLIC_HOSTS`, comma-separated, plus `RAILWAY_PUBLIC_DOMAIN` and
+//! `GATEWAY_URL`). The request's Host header is never used: the client
+//! chooses it, so it would let an envelope sealed for one node open on any
+//! other. With no host configured, every envelope is rejected.
+
+use alloy::primitives::Address;
+use serde::de::DeserializeOwned;
+use x402_soul::envelope::{EnvelopeError, NonceCache, PeerEnvelope};
+
+use super::*;
+
+/// A verified peer message.
+pub(super) struct PeerMessage<T> {
+    /// Sender's instance id, as signed.
+    pub sender: String,
+    pub address: Address,
+    pub body: T,
+}
+
+/// Verify `envelope` for `path` and parse its body. On failure, returns the
+/// response to send: 401 for bad or stale signatures, 403 for unknown
+/// signers, 409 for replays, 400 for bodies that don't parse, and 503 when
+/// this node has no public host configured.
+pub(super) async fn open<T: DeserializeOwned>(
+    state: &NodeState,
+    path: &str,
+    envelope: &PeerEnvelope,
+) -> Result<PeerMessage<T>, HttpResponse> {
+    let hosts = own_hosts(|var| std::env::var(var).ok());
+    if hosts.is_empty() {
+        tracing::error!(path, "Rejected peer message: set NODE_PUBLIC_HOSTS to accept peer messages");
+        return Err(HttpResponse::ServiceUnavailable()
+            .json(serde_json::json!({ "error": "node has no public host configured" })));
+    }
+    let now = chrono::Utc::now().timestamp();
+    let verified = async {
+        let address = envelope.open(&hosts, path, now)?;
+        if !is_known_peer(state, address).await {
+            return Err(EnvelopeError::UnknownPeer(address));
+        }
+        NonceCache::global().check(address, &envelope.nonce, envelope.timestamp, now)?;
+        Ok(PeerMessage {
+            sender: envelope.sender.clone(),
+            address,
+            body: envelope.body()?,
+        })
+    }
+    .await;
+
+    verified.map_err(|e| {
+        tracing::warn!(path, sender = %envelope.sender, error = %e, "Rejected peer message");
+        let mut response = match e {
+            EnvelopeError::UnknownPeer(_) => HttpResponse::Forbidden(),
+            EnvelopeError::Replayed => HttpResponse::Conflict(),
+            EnvelopeError::Malformed(_) => HttpResponse::BadRequest(),
+            _ => HttpResponse::Unauthorized(),
+        };
+        response.json(serde_json::json!({ "error": e.to_string() }))
+    })
+}
+
+/// Hosts envelopes must be addressed to, read through `var`. Empty when none
+/// are configured.
+fn own_hosts(var: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    ["NODE_PUBLIC_HOSTS", "RAILWAY_PUBLIC_DOMAIN", "GATEWAY_URL"]
+        .into_iter()
+        .filter_map(var)
+        .flat_map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|host| !host.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Whether `address` belongs to a peer we accept messages from.
+async fn is_known_peer(state: &NodeState, address: Address) -> bool {
+    let is = |candidate: &str| {
+        candidate
+            .trim()
+            .parse::<Address>()
+            .is_ok_and(|a| a == address)
+    };
+
+    let allowlist = std::env::var("PEER_ADDRESSES").unwrap_or_default();
+    if allowlist.split(',').any(is) {
+        return true;
+    }
+    if state.identity.as_ref().and_then(|id| id.parent_address) == Some(address) {
+        return true;
+    }
+    let children = crate::db::list_children_active(&state.gateway.db).unwrap_or_default();
+    if children.iter().any(|c| is(&c.address)) {
+        return true;
+    }
+
+    #[cfg(feature = "erc8004")]
+    {
+        if erc8004::has_agent_identity(&state.gateway.config.rpc_url, address).await {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(feature = "erc8004")]
+mod erc8004 {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::{Duration, Instant};
+
+    use alloy::primitives::Address;
+
+    /// How long a registry lookup is trusted.
+    const CACHE_TTL: Duration = Duration::from_secs(600);
+
+    static CACHE: OnceLock<Mutex<HashMap<Address, (bool, Instant)>>> = OnceLock::new();
+
+    /// Whether `address` owns an agent identity NFT. Lookups are cached;
+    /// RPC failures are not, and count as no.
+    pub(super) async fn has_agent_identity(rpc_url: &str, address: Address) -> bool {
+        let registry = x402_identity::identity_registry();
+        if registry == Address::ZERO {
+            return false;
+        }
+        let cache = CACHE.get_or_init(Default::default);
+        if let Some(&(owns, at)) = cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&address)
+        {
+            if at.elapsed() < CACHE_TTL {
+                return owns;
+            }
+        }
+
+        let Ok(rpc) = rpc_url.parse::<reqwest::Url>() else {
+            return false;
+        };
+        let provider = alloy::providers::ProviderBuilder::new().connect_http(rpc);
+        let owns = match x402_identity::onchain::balance_of(&provider, registry, address).await {
+            Ok(balance) => !balance.is_zero(),
+            Err(e) => {
+                tracing::warn!(%address, error = %e, "ERC-8004 identity lookup failed");
+                return false;
+            }
+        };
+        cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(address, (owns, Instant::now()));
+        owns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x402::WalletSigner;
+
+    const PATH: &str = "/soul/colony/train";
+
+    #[test]
+    fn spoofed_host_does_not_open_envelopes_for_other_nodes() {
+        // Replayed to this worker with `Host: queen.example.com`; only the
+        // configured hosts count.
+        let envelope =
+            PeerEnvelope::seal(&WalletSigner::random(), "worker-1", "queen.example.com", PATH, &1)
+                .unwrap();
+        let now = envelope.timestamp;
+
+        let configured = own_hosts(|var| {
+            (var == "NODE_PUBLIC_HOSTS").then(|| "worker.example.com, worker.internal:8080".to_string())
+        });
+        assert_eq!(configured, ["worker.example.com", "worker.internal:8080"]);
+        assert!(matches!(
+            envelope.open(&configured, PATH, now),
+            Err(EnvelopeError::WrongRecipient(_))
+        ));
+
+        // Nothing configured: nothing opens.
+        let unconfigured = own_hosts(|_| None);
+        assert!(unconfigured.is_empty());
+        assert!(envelope.open(&unconfigured, PATH, now).is_err());
+    }
+}
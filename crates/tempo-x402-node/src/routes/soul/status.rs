@@ -263,6 +263,14 @@ pub(super) async fn soul_status(state: web::Data<NodeState>) -> HttpResponse {
                 x402_soul::benchmark::collective_score(soul_db);
 
             let active_score = opus_score.clone();
+            let split = x402_soul::contamination::load(soul_db);
+            let split_score = |side: &str| {
+                active_score.as_ref().and_then(|s| s.get(side)).cloned().map(|mut v| {
+                    let pass = v.get("pass_at_1").and_then(|p| p.as_f64()).unwrap_or(0.0);
+                    v["iq"] = serde_json::json!(x402_soul::opus_bench::weighted_score_to_iq(pass));
+                    v
+                })
+            };
 
             Some(serde_json::json!({
                 "mode": "Opus",
@@ -271,6 +279,14 @@ pub(super) async fn soul_status(state: web::Data<NodeState>) -> HttpResponse {
                 "problems_attempted": active_score.as_ref()
                     .and_then(|s| s.get("problems_attempted")).and_then(|v| v.as_u64()).unwrap_or(0),
                 "opus_iq": opus_iq,
+                "seen": split_score("seen"),
+                "unseen": split_score("unseen"),
+                "held_out": split.map(|split| serde_json::json!({
+                    "epoch": split.epoch,
+                    "problems": split.held_out.len(),
+                    "contaminated": split.contaminated,
+                    "rotates_at": split.rotates_at(),
+                })),
                 "opus": opus_score,
                 "elo_rating": elo,
                 "elo_display": x402_soul::elo::rating_display(soul_db),
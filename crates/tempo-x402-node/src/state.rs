@@ -68,4 +68,8 @@ pub struct NodeState {
     pub soul_observer: Option<()>,
     /// WASM cartridge engine (None if not initialized)
     pub cartridge_engine: Option<Arc<x402_cartridge::CartridgeEngine>>,
+    /// Node event bus feeding cartridge event triggers (see `triggers`).
+    pub cartridge_events: crate::triggers::EventSender,
+    /// Payment nonces reserved by metered cartridge calls until settlement.
+    pub metered_nonces: crate::routes::cartridges::PendingNonces,
 }
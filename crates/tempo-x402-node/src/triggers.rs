@@ -0,0 +1,374 @@
+//! Cartridge trigger scheduler — runs cartridges on cron schedules and node events.
+//!
+//! Cartridges declare triggers in their manifest (see `x402_cartridge::triggers`).
+//! Route handlers publish events with [`emit`]; the scheduler task wakes at the
+//! start of every minute for cron triggers and on every event for event triggers,
+//! then invokes `x402_handle` with a synthetic request within the cartridge's
+//! resource limits. Every run is recorded in `cartridge_trigger_runs`.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::broadcast;
+use x402_cartridge::triggers::trigger_request;
+use x402_cartridge::{CronSchedule, Trigger, TriggerEvent};
+
+use crate::db;
+use crate::state::NodeState;
+
+/// Buffered events per subscriber before the scheduler starts dropping them.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Sender half of the node event bus.
+pub type EventSender = broadcast::Sender<TriggerEvent>;
+
+/// Create the node event bus.
+pub fn event_channel() -> EventSender {
+    broadcast::channel(EVENT_CHANNEL_CAPACITY).0
+}
+
+/// Publish a node event. No-op if the scheduler isn't running.
+pub fn emit(state: &NodeState, name: &str, payload: serde_json::Value) {
+    let _ = state
+        .cartridge_events
+        .send(TriggerEvent::new(name, payload));
+}
+
+/// Spawn the scheduler. Does nothing if the cartridge engine isn't available.
+pub fn spawn(state: NodeState) {
+    if state.cartridge_engine.is_none() {
+        return;
+    }
+    let mut events = state.cartridge_events.subscribe();
+    let running = Running::default();
+
+    tokio::spawn(async move {
+        tracing::info!("Cartridge trigger scheduler started");
+        let mut last_goal_check = chrono::Utc::now().timestamp();
+        loop {
+            let now = chrono::Utc::now();
+            let next_minute = 60 - (now.timestamp() % 60) as u64;
+            tokio::select! {
+                _ = tokio::time::sleep(std::time::Duration::from_secs(next_minute)) => {
+                    let fired_at = chrono::Utc::now();
+                    poll_completed_goals(&state, &mut last_goal_check);
+                    dispatch(&state, &running, None, fired_at);
+                }
+                event = events.recv() => match event {
+                    Ok(event) => dispatch(&state, &running, Some(event), chrono::Utc::now()),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!(dropped = n, "Cartridge trigger scheduler lagged; events dropped");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+    });
+}
+
+/// Slug + trigger labels currently executing, so a slow run isn't overlapped.
+#[derive(Clone, Default)]
+struct Running(Arc<Mutex<HashSet<String>>>);
+
+impl Running {
+    /// Claim `key` for one run; `None` while an earlier run still holds it.
+    fn claim(&self, key: String) -> Option<Claim> {
+        self.0.lock().unwrap().insert(key.clone()).then(|| Claim {
+            running: self.clone(),
+            key,
+        })
+    }
+}
+
+/// Releases its key when dropped, even if the run panicked.
+struct Claim {
+    running: Running,
+    key: String,
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        self.running.0.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// The backend triggers that fire on this cron tick (`event` is `None`) or
+/// for this event.
+fn due<'a>(
+    records: &'a [db::CartridgeRecord],
+    event: Option<&TriggerEvent>,
+    fired_at: &chrono::DateTime<chrono::Utc>,
+) -> Vec<(&'a db::CartridgeRecord, &'a Trigger)> {
+    records
+        .iter()
+        .filter(|record| record.cartridge_type == "backend")
+        .flat_map(|record| record.triggers.iter().map(move |t| (record, t)))
+        .filter(|(_, trigger)| match (event, trigger) {
+            (None, Trigger::Cron { schedule }) => CronSchedule::parse(schedule)
+                .map(|s| s.matches(fired_at))
+                .unwrap_or(false),
+            (Some(ev), t) => t.listens_for(&ev.name),
+            _ => false,
+        })
+        .collect()
+}
+
+/// Find triggers matching this tick (cron) or event and run them.
+fn dispatch(
+    state: &NodeState,
+    running: &Running,
+    event: Option<TriggerEvent>,
+    fired_at: chrono::DateTime<chrono::Utc>,
+) {
+    let records = match db::list_cartridges(&state.gateway.db) {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::warn!(error = %e, "Trigger scheduler failed to list cartridges");
+            return;
+        }
+    };
+
+    for (record, trigger) in due(&records, event.as_ref(), &fired_at) {
+        let key = format!("{}:{}", record.slug, trigger.label());
+        let Some(claim) = running.claim(key) else {
+            tracing::debug!(slug = %record.slug, trigger = %trigger.label(), "Trigger still running, skipped");
+            continue;
+        };
+        let state = state.clone();
+        let record = record.clone();
+        let trigger = trigger.clone();
+        let event = event.clone();
+        tokio::spawn(async move {
+            run_trigger(&state, &record, &trigger, event.as_ref(), fired_at).await;
+            drop(claim);
+        });
+    }
+}
+
+/// What a run's history may say about a failure. `GET /c/{slug}/runs` is
+/// public, so traps, host paths and database errors stay in the node log.
+fn run_error(e: &x402_cartridge::CartridgeError) -> String {
+    use x402_cartridge::CartridgeError;
+    match e {
+        CartridgeError::Timeout(secs) => format!("timed out after {secs}s"),
+        CartridgeError::ResourceLimit(_) => "resource limit exceeded".to_string(),
+        CartridgeError::NotFound(_) => "cartridge not loaded".to_string(),
+        _ => "execution failed".to_string(),
+    }
+}
+
+/// Execute one trigger, persist KV and record the run.
+async fn run_trigger(
+    state: &NodeState,
+    record: &db::CartridgeRecord,
+    trigger: &Trigger,
+    event: Option<&TriggerEvent>,
+    fired_at: chrono::DateTime<chrono::Utc>,
+) {
+    let Some(engine) = state.cartridge_engine.clone() else {
+        return;
+    };
+    let slug = record.slug.clone();
+    let timeout = engine.limits_for(&slug).timeout_secs;
+    let request = trigger_request(trigger, event, fired_at);
+    let kv = db::cartridge_kv_load(&state.gateway.db, &slug).unwrap_or_default();
+
+    let started = std::time::Instant::now();
+    let slug_clone = slug.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        engine.execute_with_composition(&slug_clone, &request, kv, timeout)
+    })
+    .await
+    .unwrap_or_else(|e| {
+        Err(x402_cartridge::CartridgeError::ExecutionFailed(format!(
+            "block: {e}"
+        )))
+    });
+
+    let mut run = db::TriggerRun {
+        slug: slug.clone(),
+        trigger: trigger.label(),
+        started_at: fired_at.timestamp(),
+        duration_ms: started.elapsed().as_millis() as u64,
+        status: None,
+        fuel_consumed: 0,
+        error: None,
+    };
+    match result {
+        Ok((r, kv_out)) => {
+            tracing::info!(
+                slug = %slug,
+                trigger = %run.trigger,
+                status = r.status,
+                duration_ms = r.duration_ms,
+                "Cartridge trigger executed"
+            );
+            if !kv_out.is_empty() {
+                if let Err(e) = db::cartridge_kv_save(&state.gateway.db, &slug, &kv_out) {
+                    tracing::warn!(slug = %slug, error = %e, "Failed to persist cartridge KV");
+                }
+            }
+            run.duration_ms = r.duration_ms;
+            run.status = Some(r.status);
+            run.fuel_consumed = r.usage.fuel_consumed;
+        }
+        Err(e) => {
+            tracing::warn!(slug = %slug, trigger = %run.trigger, error = %e, "Cartridge trigger failed");
+            run.error = Some(run_error(&e));
+        }
+    }
+    if let Err(e) = db::record_trigger_run(&state.gateway.db, &run) {
+        tracing::warn!(slug = %slug, error = %e, "Failed to record trigger run");
+    }
+}
+
+/// Emit `goal.completed` for soul goals completed since the last check.
+#[cfg(feature = "soul")]
+fn poll_completed_goals(state: &NodeState, last_check: &mut i64) {
+    let Some(ref soul_db) = state.soul_db else {
+        return;
+    };
+    let Ok(goals) = soul_db.recent_finished_goals(20) else {
+        return;
+    };
+    let mut newest = *last_check;
+    for goal in goals {
+        let Some(completed_at) = goal.completed_at else {
+            continue;
+        };
+        if goal.status != x402_soul::world_model::GoalStatus::Completed
+            || completed_at <= *last_check
+        {
+            continue;
+        }
+        newest = newest.max(completed_at);
+        emit(
+            state,
+            x402_cartridge::triggers::EVENT_GOAL_COMPLETED,
+            serde_json::json!({
+                "goal_id": goal.id,
+                "description": goal.description,
+                "completed_at": completed_at,
+            }),
+        );
+    }
+    *last_check = newest;
+}
+
+#[cfg(not(feature = "soul"))]
+fn poll_completed_goals(_state: &NodeState, _last_check: &mut i64) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(
+        slug: &str,
+        cartridge_type: &str,
+        triggers: serde_json::Value,
+    ) -> db::CartridgeRecord {
+        serde_json::from_value(serde_json::json!({
+            "slug": slug,
+            "name": slug,
+            "description": null,
+            "version": "0.1.0",
+            "price_usd": "$0.001",
+            "price_amount": "1000",
+            "owner_address": "",
+            "source_repo": null,
+            "wasm_path": "",
+            "wasm_hash": "",
+            "active": true,
+            "created_at": 0,
+            "updated_at": 0,
+            "cartridge_type": cartridge_type,
+            "triggers": triggers,
+        }))
+        .unwrap()
+    }
+
+    fn labels(due: &[(&db::CartridgeRecord, &Trigger)]) -> Vec<String> {
+        due.iter()
+            .map(|(r, t)| format!("{}:{}", r.slug, t.label()))
+            .collect()
+    }
+
+    #[test]
+    fn cron_triggers_fire_when_their_schedule_matches() {
+        let records = vec![
+            record(
+                "ticker",
+                "backend",
+                serde_json::json!([
+                    {"type": "cron", "schedule": "*/5 * * * *"},
+                    {"type": "cron", "schedule": "0 12 * * *"},
+                    {"type": "event", "event": "payment.settled"},
+                ]),
+            ),
+            record("quiet", "backend", serde_json::json!([])),
+        ];
+        let at = |h, m| chrono::Utc.with_ymd_and_hms(2026, 3, 4, h, m, 0).unwrap();
+        assert_eq!(
+            labels(&due(&records, None, &at(9, 10))),
+            ["ticker:cron:*/5 * * * *"]
+        );
+        assert_eq!(
+            labels(&due(&records, None, &at(12, 0))),
+            ["ticker:cron:*/5 * * * *", "ticker:cron:0 12 * * *"]
+        );
+        assert!(due(&records, None, &at(9, 11)).is_empty());
+    }
+
+    #[test]
+    fn events_only_fire_listening_backend_triggers() {
+        let triggers = serde_json::json!([
+            {"type": "cron", "schedule": "* * * * *"},
+            {"type": "event", "event": "payment.settled"},
+        ]);
+        let records = vec![
+            record("backend", "backend", triggers.clone()),
+            record("game", "interactive", triggers),
+            record(
+                "broken",
+                "backend",
+                serde_json::json!([{"type": "cron", "schedule": "nonsense"}]),
+            ),
+        ];
+        let now = chrono::Utc::now();
+        let settled = TriggerEvent::new("payment.settled", serde_json::json!({}));
+        assert_eq!(
+            labels(&due(&records, Some(&settled), &now)),
+            ["backend:event:payment.settled"]
+        );
+        let other = TriggerEvent::new("custom.other", serde_json::json!({}));
+        assert!(due(&records, Some(&other), &now).is_empty());
+        assert_eq!(
+            labels(&due(&records, None, &now)),
+            ["backend:cron:* * * * *"]
+        );
+    }
+
+    #[test]
+    fn overlapping_runs_are_skipped_until_the_first_finishes() {
+        let running = Running::default();
+        let first = running.claim("ticker:cron:* * * * *".to_string()).unwrap();
+        assert!(running.claim("ticker:cron:* * * * *".to_string()).is_none());
+        let other = running.claim("ticker:event:payment.settled".to_string());
+        assert!(other.is_some());
+        drop(first);
+        assert!(running.claim("ticker:cron:* * * * *".to_string()).is_some());
+    }
+
+    #[test]
+    fn run_history_hides_internal_errors() {
+        use x402_cartridge::CartridgeError;
+        let trap =
+            CartridgeError::ExecutionFailed("wasm trap at /data/cartridges/x/bin/x.wasm".into());
+        assert_eq!(run_error(&trap), "execution failed");
+        assert_eq!(
+            run_error(&CartridgeError::Timeout(30)),
+            "timed out after 30s"
+        );
+    }
+}
@@ -1,5 +1,6 @@
 //! Claude API backend for benchmarking.
 
+use crate::cache::CacheRequest;
 use crate::runner::CodeGenerator;
 use x402_soul::benchmark::BenchmarkProblem;
 
@@ -97,6 +98,15 @@ impl CodeGenerator for ClaudeGenerator {
     fn name(&self) -> &str {
         &self.model
     }
+
+    fn cache_request(&self, problem: &BenchmarkProblem) -> Option<CacheRequest> {
+        Some(CacheRequest {
+            backend: "anthropic".to_string(),
+            model: self.model.clone(),
+            prompt: Self::build_prompt(problem),
+            params: serde_json::json!({ "max_tokens": 4096 }),
+        })
+    }
 }
 
 /// Strip ```rust ... ``` fences from LLM output.
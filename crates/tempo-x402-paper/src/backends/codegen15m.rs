@@ -4,26 +4,41 @@
 //! This serves as the ablation study — same training data, same verification,
 //! but trained from random initialization instead of a pretrained base.
 
-use crate::runner::CodeGenerator;
+use std::path::Path;
+
+use x402_model::Checkpointable;
 use x402_soul::benchmark::BenchmarkProblem;
 
+use crate::finetune::{self, Trainable, MODEL_FILE, TOKENIZER_FILE};
+use crate::runner::CodeGenerator;
+use crate::selfplay::{build_instruction, TrainingExample};
+
 pub struct CodeGen15MGenerator {
     name: String,
     model: x402_model::codegen::CodeGenModel,
     tokenizer: x402_model::bpe::BpeTokenizer,
+    /// Created on the first fine-tune and kept across iterations.
+    optimizer: Option<x402_model::Optimizer>,
 }
 
 impl CodeGen15MGenerator {
-    /// Load from a JSON weights file, or create a fresh model.
+    /// Load from a checkpoint (binary or legacy JSON weights), a checkpoint
+    /// directory written by [`Trainable::save_checkpoint`], or create a
+    /// fresh model.
     pub fn new(weights_path: Option<&str>) -> Self {
+        let mut tokenizer = None;
         let model = if let Some(path) = weights_path {
-            std::fs::read_to_string(path)
-                .ok()
-                .and_then(|json| x402_model::codegen::CodeGenModel::from_json(&json))
-                .unwrap_or_else(|| {
-                    tracing::warn!("Failed to load weights from {path}, using fresh model");
-                    x402_model::codegen::CodeGenModel::new()
-                })
+            let mut file = Path::new(path).to_path_buf();
+            if file.is_dir() {
+                tokenizer = std::fs::read_to_string(file.join(TOKENIZER_FILE))
+                    .ok()
+                    .and_then(|json| x402_model::bpe::BpeTokenizer::from_json(&json));
+                file = file.join(MODEL_FILE);
+            }
+            x402_model::codegen::CodeGenModel::load_file(&file).unwrap_or_else(|e| {
+                tracing::warn!("Failed to load weights from {path} ({e}), using fresh model");
+                x402_model::codegen::CodeGenModel::new()
+            })
         } else {
             x402_model::codegen::CodeGenModel::new()
         };
@@ -36,7 +51,8 @@ impl CodeGen15MGenerator {
         Self {
             name,
             model,
-            tokenizer: x402_model::bpe::BpeTokenizer::new(8192),
+            tokenizer: tokenizer.unwrap_or_else(|| x402_model::bpe::BpeTokenizer::new(8192)),
+            optimizer: None,
         }
     }
 
@@ -49,29 +65,32 @@ impl CodeGen15MGenerator {
         }
         self
     }
+}
 
-    /// Train the model on a set of training examples (from self-play data).
-    pub fn train_on_examples(&mut self, examples: &[crate::selfplay::TrainingExample]) {
-        for ex in examples {
-            let mut target_tokens = vec![x402_model::bpe::BOS_TOKEN];
-            target_tokens.extend(self.tokenizer.encode(&ex.output));
-            target_tokens.push(x402_model::bpe::EOS_TOKEN);
-            if target_tokens.len() > x402_model::codegen::SMALL_MAX_SEQ {
-                target_tokens.truncate(x402_model::codegen::SMALL_MAX_SEQ);
-            }
-
-            let mut context_tokens = vec![x402_model::bpe::BOS_TOKEN];
-            context_tokens.extend(self.tokenizer.encode(&ex.instruction));
-            context_tokens.push(x402_model::bpe::EOS_TOKEN);
-            if context_tokens.len() > x402_model::codegen::SMALL_MAX_SEQ {
-                context_tokens.truncate(x402_model::codegen::SMALL_MAX_SEQ);
-            }
+impl Trainable for CodeGen15MGenerator {
+    fn fine_tune(
+        &mut self,
+        examples: &[TrainingExample],
+        learning_rate: f32,
+        epochs: u32,
+    ) -> (Vec<f32>, u64) {
+        finetune::ensure_tokenizer(&mut self.tokenizer, examples);
+        finetune::train(
+            &mut self.model,
+            &mut self.optimizer,
+            &self.tokenizer,
+            &[x402_model::bpe::BOS_TOKEN],
+            examples,
+            learning_rate,
+            epochs,
+        )
+    }
 
-            if target_tokens.len() >= 3 && context_tokens.len() >= 3 {
-                let lr = 0.001;
-                self.model.train_enc_dec(&context_tokens, &target_tokens, lr);
-            }
-        }
+    fn save_checkpoint(&self, dir: &Path) -> Result<(), String> {
+        self.model
+            .save_file(dir.join(MODEL_FILE))
+            .map_err(|e| e.to_string())?;
+        std::fs::write(dir.join(TOKENIZER_FILE), self.tokenizer.to_json()).map_err(|e| e.to_string())
     }
 }
 
@@ -88,20 +107,14 @@ impl CodeGenerator for CodeGen15MGenerator {
             ));
         }
 
-        // Build context from problem
-        let context = format!(
-            "{}\n\nTests:\n{}\n\nStarter:\n{}",
-            problem.instructions, problem.test_code, problem.starter_code
+        // Encode the context exactly as fine-tuning does
+        let context_tokens = finetune::context_tokens(
+            &self.tokenizer,
+            &[x402_model::bpe::BOS_TOKEN],
+            &build_instruction(problem),
+            self.model.max_seq,
         );
 
-        // Encode context
-        let mut context_tokens = vec![x402_model::bpe::BOS_TOKEN];
-        context_tokens.extend(self.tokenizer.encode(&context));
-        context_tokens.push(x402_model::bpe::EOS_TOKEN);
-        if context_tokens.len() > self.model.max_seq {
-            context_tokens.truncate(self.model.max_seq);
-        }
-
         let encoder_output = self.model.encode(&context_tokens);
         let enc_len = context_tokens.len().min(self.model.max_seq);
 
@@ -158,4 +171,8 @@ impl CodeGenerator for CodeGen15MGenerator {
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn trainable(&mut self) -> Option<&mut dyn Trainable> {
+        Some(self)
+    }
 }
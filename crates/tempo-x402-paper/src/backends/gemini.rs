@@ -1,5 +1,6 @@
 //! Gemini API backend for benchmarking.
 
+use crate::cache::CacheRequest;
 use crate::runner::CodeGenerator;
 use x402_soul::benchmark::BenchmarkProblem;
 
@@ -101,6 +102,15 @@ impl CodeGenerator for GeminiGenerator {
     fn name(&self) -> &str {
         &self.model
     }
+
+    fn cache_request(&self, problem: &BenchmarkProblem) -> Option<CacheRequest> {
+        Some(CacheRequest {
+            backend: "gemini".to_string(),
+            model: self.model.clone(),
+            prompt: Self::build_prompt(problem),
+            params: serde_json::json!({ "temperature": 0.2, "maxOutputTokens": 4096 }),
+        })
+    }
 }
 
 fn strip_code_fences(text: &str) -> &str {
@@ -6,6 +6,7 @@
 //! NOTE: Requires `llama-cpp` feature to be enabled. When disabled,
 //! this module provides a stub that returns an error.
 
+use crate::cache::CacheRequest;
 use crate::runner::CodeGenerator;
 use x402_soul::benchmark::BenchmarkProblem;
 
@@ -120,6 +121,29 @@ impl CodeGenerator for LocalModelGenerator {
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn cache_request(&self, problem: &BenchmarkProblem) -> Option<CacheRequest> {
+        Some(CacheRequest {
+            backend: "llama-cli".to_string(),
+            model: self.model_path.clone(),
+            prompt: Self::build_prompt(problem),
+            params: serde_json::json!({
+                "max_tokens": self.max_tokens,
+                "temperature": self.temperature,
+                "n_ctx": self.n_ctx,
+            }),
+        })
+    }
+
+    /// Expects the trainer to leave a merged `model.gguf` in `dir`.
+    fn load_weights(&mut self, dir: &std::path::Path) -> Result<(), String> {
+        let path = dir.join("model.gguf");
+        if !path.exists() {
+            return Err(format!("{} not written by the training command", path.display()));
+        }
+        self.model_path = path.display().to_string();
+        Ok(())
+    }
 }
 
 fn strip_code_fences(text: &str) -> &str {
@@ -2,3 +2,5 @@ pub mod claude;
 pub mod codegen15m;
 pub mod gemini;
 pub mod local;
+pub mod openai;
+pub mod unified;
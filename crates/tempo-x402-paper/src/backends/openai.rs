@@ -0,0 +1,166 @@
+//! OpenAI-compatible chat completions backend.
+//!
+//! Speaks `POST {base_url}/chat/completions`, which vLLM, llama-server,
+//! TGI (Messages API), Ollama (`/v1`) and hosted OpenAI-style APIs all
+//! serve, so any of them can be benchmarked by pointing `--base-url` at it.
+
+use crate::cache::CacheRequest;
+use crate::runner::CodeGenerator;
+use x402_soul::benchmark::BenchmarkProblem;
+
+/// Sampling parameters sent with every request.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Sampling {
+    pub temperature: f32,
+    pub top_p: f32,
+    pub max_tokens: u32,
+    /// Server-side seed, for backends that support reproducible sampling.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            temperature: 0.2,
+            top_p: 1.0,
+            max_tokens: 4096,
+            seed: None,
+            stop: Vec::new(),
+        }
+    }
+}
+
+pub struct OpenAiGenerator {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    /// Name in results; defaults to the model.
+    name: String,
+    sampling: Sampling,
+}
+
+impl OpenAiGenerator {
+    pub fn new(base_url: String, api_key: Option<String>, model: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|k| !k.is_empty()),
+            name: model.clone(),
+            model,
+            sampling: Sampling::default(),
+        }
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    fn build_prompt(problem: &BenchmarkProblem) -> String {
+        format!(
+            "You are an expert Rust programmer. Solve this problem by writing a complete Rust \
+             library (src/lib.rs) that passes the provided tests.\n\n\
+             ## Problem: {}\n\n\
+             ## Instructions\n{}\n\n\
+             ## Test Code (must pass)\n```rust\n{}\n```\n\n\
+             ## Starter Code\n```rust\n{}\n```\n\n\
+             {}\
+             IMPORTANT: Output ONLY the complete src/lib.rs code. No explanations, no markdown \
+             fences, no commentary. Just the Rust code that will be written to src/lib.rs.",
+            problem.slug,
+            problem.instructions,
+            problem.test_code,
+            problem.starter_code,
+            if !problem.cargo_toml.is_empty() {
+                format!(
+                    "## Available Dependencies (Cargo.toml)\n```toml\n{}\n```\n\n",
+                    problem.cargo_toml
+                )
+            } else {
+                String::new()
+            }
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl CodeGenerator for OpenAiGenerator {
+    async fn generate(&self, problem: &BenchmarkProblem) -> Result<String, String> {
+        let prompt = Self::build_prompt(problem);
+
+        let mut body = serde_json::to_value(&self.sampling).map_err(|e| format!("params: {e}"))?;
+        body["model"] = self.model.clone().into();
+        body["messages"] = serde_json::json!([{ "role": "user", "content": prompt }]);
+
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| format!("request failed: {e}"))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("API error {status}: {text}"));
+        }
+
+        let json: serde_json::Value = resp.json().await.map_err(|e| format!("parse: {e}"))?;
+
+        let text = json
+            .get("choices")
+            .and_then(|c| c.as_array())
+            .and_then(|arr| arr.first())
+            .and_then(|c| c.get("message"))
+            .and_then(|m| m.get("content"))
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| "no text in response".to_string())?;
+
+        let code = strip_code_fences(text);
+        Ok(code.to_string())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn cache_request(&self, problem: &BenchmarkProblem) -> Option<CacheRequest> {
+        Some(CacheRequest {
+            backend: "openai".to_string(),
+            model: self.model.clone(),
+            prompt: Self::build_prompt(problem),
+            params: serde_json::to_value(&self.sampling).ok()?,
+        })
+    }
+}
+
+fn strip_code_fences(text: &str) -> &str {
+    let trimmed = text.trim();
+    if let Some(after) = trimmed.strip_prefix("```rust") {
+        if let Some(code) = after.strip_suffix("```") {
+            return code.trim();
+        }
+        return after.trim();
+    }
+    if let Some(after) = trimmed.strip_prefix("```") {
+        if let Some(code) = after.strip_suffix("```") {
+            return code.trim();
+        }
+        return after.trim();
+    }
+    trimmed
+}
@@ -0,0 +1,145 @@
+//! The soul's unified encoder-decoder as a benchmark backend.
+//!
+//! Loads a `UnifiedModel` checkpoint (the node writes one to
+//! `/tmp/unified_model.ckpt`) or starts from random weights, generates with
+//! the slow head, and fine-tunes in process during self-play.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use x402_model::bpe::{BpeTokenizer, BOS_TOKEN, EOS_TOKEN};
+use x402_model::unified::{UnifiedModel, TASK_CODE};
+use x402_model::{Checkpointable, Optimizer};
+use x402_soul::benchmark::BenchmarkProblem;
+
+use crate::finetune::{self, Trainable, MODEL_FILE, TOKENIZER_FILE};
+use crate::runner::CodeGenerator;
+use crate::selfplay::{build_instruction, TrainingExample};
+
+/// Tokens generated per solution.
+const MAX_TOKENS: usize = 512;
+
+pub struct UnifiedGenerator {
+    name: String,
+    model: UnifiedModel,
+    tokenizer: BpeTokenizer,
+    /// Created on the first fine-tune and kept across iterations.
+    optimizer: Option<Optimizer>,
+    /// Generations so far; seeds the next one's sampling.
+    generations: AtomicU64,
+}
+
+impl UnifiedGenerator {
+    pub fn from_model(model: UnifiedModel, tokenizer: BpeTokenizer) -> Self {
+        Self {
+            name: format!("unified-{}", model.config.describe()),
+            model,
+            tokenizer,
+            optimizer: None,
+            generations: AtomicU64::new(0),
+        }
+    }
+
+    /// Load a checkpoint file, or a directory written by
+    /// [`Trainable::save_checkpoint`] (model and tokenizer).
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let (model_path, tokenizer) = if path.is_dir() {
+            let tok_path = path.join(TOKENIZER_FILE);
+            let tokenizer = std::fs::read_to_string(&tok_path)
+                .ok()
+                .and_then(|json| BpeTokenizer::from_json(&json));
+            (path.join(MODEL_FILE), tokenizer)
+        } else {
+            (path.to_path_buf(), None)
+        };
+        let model = UnifiedModel::load_file(&model_path)
+            .map_err(|e| format!("{}: {e}", model_path.display()))?;
+        let tokenizer = tokenizer.unwrap_or_else(|| BpeTokenizer::new(8192));
+        Ok(Self::from_model(model, tokenizer))
+    }
+
+    /// Use a trained BPE tokenizer from a JSON file.
+    pub fn with_tokenizer(mut self, path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        self.tokenizer =
+            BpeTokenizer::from_json(&json).ok_or_else(|| format!("{path}: not a BPE tokenizer"))?;
+        Ok(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl CodeGenerator for UnifiedGenerator {
+    async fn generate(&self, problem: &BenchmarkProblem) -> Result<String, String> {
+        if self.tokenizer.merges.is_empty() {
+            return Err("BPE tokenizer not trained (0 merges)".to_string());
+        }
+        let context = finetune::context_tokens(
+            &self.tokenizer,
+            &[TASK_CODE],
+            &build_instruction(problem),
+            self.model.config.max_seq,
+        );
+        let encoder_output = self.model.encode(&context);
+        let config = x402_model::DecodeConfig {
+            strategy: x402_model::Strategy::TopP {
+                p: 0.95,
+                temperature: 0.8,
+            },
+            max_tokens: MAX_TOKENS,
+            stop_tokens: vec![EOS_TOKEN],
+            seed: self.generations.fetch_add(1, Ordering::Relaxed),
+            ..Default::default()
+        };
+        let candidates = x402_model::decoding::generate(
+            &self.model,
+            &encoder_output,
+            context.len(),
+            &[BOS_TOKEN],
+            &config,
+        );
+        let code = candidates
+            .first()
+            .map(|c| self.tokenizer.decode(&c.tokens))
+            .unwrap_or_default();
+        if code.trim().is_empty() {
+            return Err("Model generated empty output".to_string());
+        }
+        Ok(code)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn trainable(&mut self) -> Option<&mut dyn Trainable> {
+        Some(self)
+    }
+}
+
+impl Trainable for UnifiedGenerator {
+    fn fine_tune(
+        &mut self,
+        examples: &[TrainingExample],
+        learning_rate: f32,
+        epochs: u32,
+    ) -> (Vec<f32>, u64) {
+        finetune::ensure_tokenizer(&mut self.tokenizer, examples);
+        finetune::train(
+            &mut self.model,
+            &mut self.optimizer,
+            &self.tokenizer,
+            &[TASK_CODE],
+            examples,
+            learning_rate,
+            epochs,
+        )
+    }
+
+    fn save_checkpoint(&self, dir: &Path) -> Result<(), String> {
+        self.model
+            .save_file(dir.join(MODEL_FILE))
+            .map_err(|e| e.to_string())?;
+        std::fs::write(dir.join(TOKENIZER_FILE), self.tokenizer.to_json())
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,330 @@
+//! Content-addressed cache of model generations.
+//!
+//! A generation is keyed by the SHA-256 of its backend, model, prompt,
+//! sampling parameters and sample index, so reruns and ablations that only
+//! change validation or scoring replay the exact same solutions instead of
+//! paying for new ones. Entries live at `{dir}/{key[..2]}/{key}.json` and
+//! keep the request next to the response for auditing.
+//!
+//! In replay-only mode a missing entry is an error rather than a request,
+//! which guarantees a run used nothing but cached generations. Backends that
+//! can't be cached are an error there too.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use sha2::{Digest, Sha256};
+use x402_soul::benchmark::BenchmarkProblem;
+
+use crate::runner::CodeGenerator;
+
+/// Default cache directory, relative to the working directory.
+pub const DEFAULT_DIR: &str = ".cache/generations";
+
+static GLOBAL: OnceLock<ResponseCache> = OnceLock::new();
+
+/// What determines a generation. Backends that can't describe one (e.g.
+/// models whose weights change between calls) aren't cached.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CacheRequest {
+    /// Backend family, e.g. `"openai"` or `"anthropic"`.
+    pub backend: String,
+    pub model: String,
+    pub prompt: String,
+    /// Sampling parameters as sent to the backend.
+    pub params: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Replay cached generations, generate and store the rest.
+    ReadWrite,
+    /// Replay cached generations; a miss is an error.
+    ReplayOnly,
+    /// Always generate, store nothing.
+    Off,
+}
+
+#[derive(Debug)]
+pub enum CacheError {
+    /// Replay-only mode and the generation isn't cached.
+    Missing { slug: String, key: String },
+    /// Replay-only mode and the backend's generations can't be cached.
+    Uncacheable { slug: String, backend: String },
+    /// The backend failed.
+    Generate(String),
+}
+
+impl std::fmt::Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { slug, key } => {
+                write!(f, "{slug}: generation {key} not in cache (replay-only)")
+            }
+            Self::Uncacheable { slug, backend } => {
+                write!(f, "{slug}: {backend} generations aren't cached (replay-only)")
+            }
+            Self::Generate(e) => f.write_str(e),
+        }
+    }
+}
+
+/// A cached generation.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    #[serde(flatten)]
+    request: CacheRequest,
+    sample: usize,
+    response: String,
+    created_at: String,
+}
+
+pub struct ResponseCache {
+    dir: PathBuf,
+    mode: CacheMode,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>, mode: CacheMode) -> Self {
+        Self {
+            dir: dir.into(),
+            mode,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// The process-wide cache, read-write at [`DEFAULT_DIR`] unless
+    /// [`configure_global`](Self::configure_global) ran first.
+    pub fn global() -> &'static ResponseCache {
+        GLOBAL.get_or_init(|| Self::new(DEFAULT_DIR, CacheMode::ReadWrite))
+    }
+
+    /// Set up the global cache. Only works before its first use; returns
+    /// `false` if it already exists.
+    pub fn configure_global(dir: impl Into<PathBuf>, mode: CacheMode) -> bool {
+        GLOBAL.set(Self::new(dir, mode)).is_ok()
+    }
+
+    /// Generations replayed and generated (or missed) so far.
+    pub fn stats(&self) -> (usize, usize) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Sample `sample` of `generator`'s solution to `problem`, from the cache
+    /// when possible.
+    pub async fn generate(
+        &self,
+        generator: &dyn CodeGenerator,
+        problem: &BenchmarkProblem,
+        sample: usize,
+    ) -> Result<String, CacheError> {
+        let request = match (self.mode, generator.cache_request(problem)) {
+            (CacheMode::ReplayOnly, None) => {
+                return Err(CacheError::Uncacheable {
+                    slug: problem.slug.clone(),
+                    backend: generator.name().to_string(),
+                })
+            }
+            (CacheMode::Off, _) | (_, None) => {
+                return generator
+                    .generate(problem)
+                    .await
+                    .map_err(CacheError::Generate)
+            }
+            (_, Some(request)) => request,
+        };
+        let key = key(&request, sample);
+        let path = self.path(&key);
+        if let Some(response) = read(&path) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(response);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        if self.mode == CacheMode::ReplayOnly {
+            return Err(CacheError::Missing {
+                slug: problem.slug.clone(),
+                key,
+            });
+        }
+
+        let response = generator
+            .generate(problem)
+            .await
+            .map_err(CacheError::Generate)?;
+        let entry = Entry {
+            request,
+            sample,
+            response,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        if let Err(e) = write(&path, &entry) {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to cache generation");
+        }
+        Ok(entry.response)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{key}.json"))
+    }
+}
+
+/// SHA-256 over the canonical JSON of the request and sample index.
+fn key(request: &CacheRequest, sample: usize) -> String {
+    let canonical = sorted(serde_json::json!({
+        "backend": request.backend,
+        "model": request.model,
+        "prompt": request.prompt,
+        "params": request.params,
+        "sample": sample,
+    }));
+    Sha256::digest(canonical.to_string().as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// `value` with object keys in sorted order, so equal params hash equally
+/// whatever order the backend built them in.
+fn sorted(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            serde_json::Value::Object(entries.into_iter().map(|(k, v)| (k, sorted(v))).collect())
+        }
+        serde_json::Value::Array(items) => items.into_iter().map(sorted).collect(),
+        other => other,
+    }
+}
+
+fn read(path: &Path) -> Option<String> {
+    let json = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str::<Entry>(&json)
+        .map(|entry| entry.response)
+        .ok()
+}
+
+/// Write through a temporary file so concurrent runs never see half an entry.
+fn write(path: &Path, entry: &Entry) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(entry)?;
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    struct Counter {
+        calls: AtomicU32,
+        temperature: f64,
+    }
+
+    #[async_trait::async_trait]
+    impl CodeGenerator for Counter {
+        async fn generate(&self, _problem: &BenchmarkProblem) -> Result<String, String> {
+            let n = self.calls.fetch_add(1, Ordering::Relaxed);
+            Ok(format!("pub fn answer() -> u32 {{ {n} }}"))
+        }
+
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn cache_request(&self, problem: &BenchmarkProblem) -> Option<CacheRequest> {
+            Some(CacheRequest {
+                backend: "test".to_string(),
+                model: "counter".to_string(),
+                prompt: problem.instructions.clone(),
+                params: serde_json::json!({ "temperature": self.temperature }),
+            })
+        }
+    }
+
+    fn problem() -> BenchmarkProblem {
+        BenchmarkProblem {
+            slug: "cached".to_string(),
+            instructions: "Return 42.".to_string(),
+            test_code: String::new(),
+            starter_code: String::new(),
+            difficulty: "tier1".to_string(),
+            cargo_toml: String::new(),
+            pack: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_by_request_and_sample() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResponseCache::new(dir.path(), CacheMode::ReadWrite);
+        let counter = Counter {
+            calls: AtomicU32::new(0),
+            temperature: 0.2,
+        };
+        let p = problem();
+
+        let first = cache.generate(&counter, &p, 0).await.unwrap();
+        assert_eq!(cache.generate(&counter, &p, 0).await.unwrap(), first);
+        let other_sample = cache.generate(&counter, &p, 1).await.unwrap();
+        assert_ne!(other_sample, first);
+        assert_eq!(counter.calls.load(Ordering::Relaxed), 2);
+        assert_eq!(cache.stats(), (1, 2));
+
+        // Different sampling parameters are a different generation.
+        let hotter = Counter {
+            calls: AtomicU32::new(10),
+            temperature: 0.8,
+        };
+        assert_ne!(cache.generate(&hotter, &p, 0).await.unwrap(), first);
+
+        let replay = ResponseCache::new(dir.path(), CacheMode::ReplayOnly);
+        assert_eq!(replay.generate(&counter, &p, 1).await.unwrap(), other_sample);
+        let missing = replay.generate(&counter, &p, 2).await;
+        assert!(matches!(missing, Err(CacheError::Missing { .. })));
+        assert_eq!(counter.calls.load(Ordering::Relaxed), 2);
+    }
+
+    /// A backend whose generations can't be described (e.g. a local model
+    /// whose weights change between calls).
+    struct Uncacheable(AtomicU32);
+
+    #[async_trait::async_trait]
+    impl CodeGenerator for Uncacheable {
+        async fn generate(&self, _problem: &BenchmarkProblem) -> Result<String, String> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(String::new())
+        }
+
+        fn name(&self) -> &str {
+            "uncacheable"
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_only_never_runs_uncacheable_backends() {
+        let dir = tempfile::tempdir().unwrap();
+        let generator = Uncacheable(AtomicU32::new(0));
+        let p = problem();
+
+        let replay = ResponseCache::new(dir.path(), CacheMode::ReplayOnly);
+        let result = replay.generate(&generator, &p, 0).await;
+        assert!(matches!(result, Err(CacheError::Uncacheable { .. })));
+        assert_eq!(generator.0.load(Ordering::Relaxed), 0);
+
+        let live = ResponseCache::new(dir.path(), CacheMode::ReadWrite);
+        live.generate(&generator, &p, 0).await.unwrap();
+        assert_eq!(generator.0.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,471 @@
+//! Fine-tuning between self-play iterations.
+//!
+//! The from-scratch models (`CodeGenModel`, `UnifiedModel`) run in process
+//! and implement [`Trainable`]: after each iteration they train on every
+//! verified example so far, a checkpoint is saved, and the next iteration
+//! evaluates it. Any other model (a GGUF file, an API) is trained by an
+//! external command — typically a LoRA script — that reads the dataset
+//! exported as JSONL chat records and writes new weights for the generator
+//! to load.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use x402_model::bpe::{BpeTokenizer, BOS_TOKEN, EOS_TOKEN};
+use x402_model::{Gradients, Optimizer, StepStats};
+
+use crate::runner::CodeGenerator;
+use crate::selfplay::{SelfPlayConfig, TrainingExample};
+use crate::stats::Rng;
+
+/// Model weights inside a checkpoint directory.
+pub(crate) const MODEL_FILE: &str = "model.ckpt";
+/// Tokenizer inside a checkpoint directory.
+pub(crate) const TOKENIZER_FILE: &str = "tokenizer.json";
+
+/// Examples accumulated per optimizer step.
+const BATCH_SIZE: usize = 4;
+
+/// Vocabulary of a tokenizer learned from self-play data.
+const TOKENIZER_VOCAB: u32 = 8192;
+
+/// System message of exported chat records.
+const SYSTEM_PROMPT: &str =
+    "You are an expert Rust programmer. Reply with the complete src/lib.rs and nothing else.";
+
+/// What one fine-tuning step did.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FineTuneStats {
+    /// `"in-process"` or `"command"`.
+    pub trainer: String,
+    pub examples: usize,
+    pub epochs: u32,
+    /// Optimizer steps taken (in-process only).
+    pub steps: u64,
+    /// Mean training loss of each epoch (in-process only).
+    pub epoch_loss: Vec<f32>,
+    /// Checkpoint directory.
+    pub checkpoint: String,
+    pub elapsed_ms: u64,
+}
+
+/// A model that can be fine-tuned in process.
+pub trait Trainable: Send + Sync {
+    /// Train `epochs` passes over `examples`. Returns the mean loss of each
+    /// epoch and the optimizer steps taken.
+    fn fine_tune(
+        &mut self,
+        examples: &[TrainingExample],
+        learning_rate: f32,
+        epochs: u32,
+    ) -> (Vec<f32>, u64);
+
+    /// Write the model and its tokenizer into `dir`.
+    fn save_checkpoint(&self, dir: &Path) -> Result<(), String>;
+}
+
+/// Fine-tune `generator` on `examples` and checkpoint it into `checkpoint`.
+///
+/// In-process models train directly; otherwise `config.train_command` runs
+/// with the chat export at `data` and the generator loads whatever weights
+/// it leaves in `checkpoint`. Returns `None` when there is nothing to train
+/// on, no way to train, or training failed.
+pub async fn step(
+    generator: &mut dyn CodeGenerator,
+    examples: &[TrainingExample],
+    data: &Path,
+    checkpoint: &Path,
+    config: &SelfPlayConfig,
+) -> Option<FineTuneStats> {
+    if examples.is_empty() {
+        return None;
+    }
+    let start = std::time::Instant::now();
+    if let Err(e) = std::fs::create_dir_all(checkpoint) {
+        tracing::warn!(path = %checkpoint.display(), error = %e, "Failed to create checkpoint dir");
+        return None;
+    }
+
+    let (trainer, epoch_loss, steps) = if let Some(model) = generator.trainable() {
+        let lr = config.learning_rate as f32;
+        let (epoch_loss, steps) =
+            tokio::task::block_in_place(|| model.fine_tune(examples, lr, config.epochs));
+        if let Err(e) = model.save_checkpoint(checkpoint) {
+            tracing::warn!(error = %e, "Failed to save checkpoint");
+        }
+        ("in-process", epoch_loss, steps)
+    } else if let Some(template) = &config.train_command {
+        let vars = [
+            ("data", data.display().to_string()),
+            ("output", checkpoint.display().to_string()),
+            ("model", generator.name().to_string()),
+            ("rank", config.lora_rank.to_string()),
+            ("lr", config.learning_rate.to_string()),
+            ("epochs", config.epochs.to_string()),
+        ];
+        if let Err(e) = run_command(template, &vars).await {
+            tracing::error!(error = %e, "Training command failed");
+            return None;
+        }
+        if let Err(e) = generator.load_weights(checkpoint) {
+            tracing::error!(error = %e, "Failed to load fine-tuned weights");
+            return None;
+        }
+        ("command", Vec::new(), 0)
+    } else {
+        tracing::info!(
+            "Training data saved to {} ({} examples). {} can't train in process; \
+             pass --train-command to fine-tune it between iterations.",
+            data.display(),
+            examples.len(),
+            generator.name()
+        );
+        return None;
+    };
+
+    let stats = FineTuneStats {
+        trainer: trainer.to_string(),
+        examples: examples.len(),
+        epochs: config.epochs,
+        steps,
+        epoch_loss,
+        checkpoint: checkpoint.display().to_string(),
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    };
+    tracing::info!(
+        trainer,
+        examples = stats.examples,
+        steps,
+        loss = ?stats.epoch_loss.last(),
+        checkpoint = %stats.checkpoint,
+        "Fine-tuned"
+    );
+    Some(stats)
+}
+
+/// Run `template` through `sh -c` with `{name}` placeholders replaced by
+/// `vars`, e.g. `python lora.py --data {data} --out {output} --r {rank}`.
+async fn run_command(template: &str, vars: &[(&str, String)]) -> Result<(), String> {
+    let command = vars
+        .iter()
+        .fold(template.to_string(), |cmd, (name, value)| {
+            cmd.replace(&format!("{{{name}}}"), value)
+        });
+    tracing::info!(%command, "Running training command");
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .status()
+        .await
+        .map_err(|e| format!("spawn: {e}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("`{command}` exited with {status}"))
+    }
+}
+
+// ── In-process training ───────────────────────────────────────────
+
+/// The encoder-decoder operations fine-tuning needs, common to both
+/// from-scratch architectures.
+pub(crate) trait Seq2Seq {
+    fn max_seq(&self) -> usize;
+    fn train_steps(&self) -> u64;
+    fn gradients(&self, context: &[u32], target: &[u32], grads: &mut Gradients) -> f32;
+    fn apply(&mut self, opt: &mut Optimizer, grads: &Gradients) -> Option<StepStats>;
+}
+
+impl Seq2Seq for x402_model::codegen::CodeGenModel {
+    fn max_seq(&self) -> usize {
+        self.max_seq
+    }
+
+    fn train_steps(&self) -> u64 {
+        self.train_steps
+    }
+
+    fn gradients(&self, context: &[u32], target: &[u32], grads: &mut Gradients) -> f32 {
+        self.enc_dec_gradients(context, target, grads)
+    }
+
+    fn apply(&mut self, opt: &mut Optimizer, grads: &Gradients) -> Option<StepStats> {
+        self.apply_gradients(opt, grads)
+    }
+}
+
+impl Seq2Seq for x402_model::unified::UnifiedModel {
+    fn max_seq(&self) -> usize {
+        self.config.max_seq
+    }
+
+    fn train_steps(&self) -> u64 {
+        self.train_steps
+    }
+
+    fn gradients(&self, context: &[u32], target: &[u32], grads: &mut Gradients) -> f32 {
+        self.slow_gradients(context, target, grads)
+    }
+
+    fn apply(&mut self, opt: &mut Optimizer, grads: &Gradients) -> Option<StepStats> {
+        self.apply_gradients(opt, grads)
+    }
+}
+
+/// Train `model` for `epochs` shuffled passes over `examples`, encoded with
+/// `tok` and the task `prefix`, using AdamW at a constant `learning_rate`
+/// with gradients clipped to norm 1. `opt` carries the optimizer state from
+/// one call to the next. Returns the mean loss of each epoch and the
+/// optimizer steps taken.
+pub(crate) fn train<M: Seq2Seq>(
+    model: &mut M,
+    opt: &mut Option<Optimizer>,
+    tok: &BpeTokenizer,
+    prefix: &[u32],
+    examples: &[TrainingExample],
+    learning_rate: f32,
+    epochs: u32,
+) -> (Vec<f32>, u64) {
+    let schedule = x402_model::Schedule::Constant { lr: learning_rate };
+    let opt = opt.get_or_insert_with(|| {
+        Optimizer::new(x402_model::OptimizerConfig {
+            kind: x402_model::OptimizerKind::adamw(),
+            schedule,
+            clip_norm: Some(1.0),
+        })
+    });
+    opt.config.schedule = schedule;
+
+    let max_seq = model.max_seq();
+    let pairs: Vec<(Vec<u32>, Vec<u32>)> = examples
+        .iter()
+        .map(|ex| {
+            (
+                context_tokens(tok, prefix, &ex.instruction, max_seq),
+                target_tokens(tok, &ex.output, max_seq),
+            )
+        })
+        .filter(|(context, target)| context.len() >= 3 && target.len() >= 3)
+        .collect();
+    if pairs.is_empty() {
+        return (Vec::new(), 0);
+    }
+
+    let mut rng = Rng::new(model.train_steps());
+    let mut order: Vec<usize> = (0..pairs.len()).collect();
+    let mut grads = Gradients::new();
+    let mut steps = 0;
+    let mut epoch_loss = Vec::with_capacity(epochs as usize);
+    for _ in 0..epochs {
+        for i in (1..order.len()).rev() {
+            order.swap(i, rng.below(i + 1));
+        }
+        let mut total = 0.0;
+        for &i in &order {
+            let (context, target) = &pairs[i];
+            total += model.gradients(context, target, &mut grads);
+            if grads.examples() >= BATCH_SIZE {
+                steps += model.apply(opt, &grads).is_some() as u64;
+                grads.clear();
+            }
+        }
+        steps += model.apply(opt, &grads).is_some() as u64;
+        grads.clear();
+        epoch_loss.push(total / pairs.len() as f32);
+    }
+    (epoch_loss, steps)
+}
+
+/// Learn BPE merges from `examples` if `tok` has none yet, so a model
+/// starting from nothing gets a usable vocabulary on its first fine-tune.
+pub(crate) fn ensure_tokenizer(tok: &mut BpeTokenizer, examples: &[TrainingExample]) {
+    if !tok.merges.is_empty() {
+        return;
+    }
+    let corpus: String = examples
+        .iter()
+        .flat_map(|ex| [ex.instruction.as_str(), ex.output.as_str()])
+        .collect::<Vec<_>>()
+        .join("\n");
+    *tok = BpeTokenizer::new(TOKENIZER_VOCAB);
+    tok.train(&corpus);
+    tracing::info!(
+        merges = tok.merges.len(),
+        "Trained BPE tokenizer on self-play data"
+    );
+}
+
+/// Encoder input: `prefix`, then the instruction, cut to `max_seq`.
+pub(crate) fn context_tokens(
+    tok: &BpeTokenizer,
+    prefix: &[u32],
+    instruction: &str,
+    max_seq: usize,
+) -> Vec<u32> {
+    let mut tokens = prefix.to_vec();
+    tokens.extend(tok.encode(instruction));
+    tokens.truncate(max_seq);
+    tokens
+}
+
+/// Decoder target: BOS, the solution, EOS, cut to `max_seq`.
+fn target_tokens(tok: &BpeTokenizer, output: &str, max_seq: usize) -> Vec<u32> {
+    let mut tokens = vec![BOS_TOKEN];
+    tokens.extend(tok.encode(output));
+    tokens.push(EOS_TOKEN);
+    tokens.truncate(max_seq);
+    tokens
+}
+
+// ── Dataset export ────────────────────────────────────────────────
+
+/// JSONL layouts external trainers consume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetFormat {
+    /// `{"messages": [system, user, assistant]}` (OpenAI / TRL chat).
+    Chat,
+    /// `{"instruction", "input", "output"}` (Alpaca).
+    Instruction,
+}
+
+impl FromStr for DatasetFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chat" => Ok(Self::Chat),
+            "instruction" | "alpaca" => Ok(Self::Instruction),
+            other => Err(format!(
+                "unknown format {other:?} (expected chat or instruction)"
+            )),
+        }
+    }
+}
+
+/// One example as a record of `format`.
+pub fn record(example: &TrainingExample, format: DatasetFormat) -> serde_json::Value {
+    match format {
+        DatasetFormat::Chat => serde_json::json!({
+            "messages": [
+                { "role": "system", "content": SYSTEM_PROMPT },
+                { "role": "user", "content": example.instruction },
+                { "role": "assistant", "content": example.output },
+            ]
+        }),
+        DatasetFormat::Instruction => serde_json::json!({
+            "instruction": example.instruction,
+            "input": "",
+            "output": example.output,
+        }),
+    }
+}
+
+/// Write `examples` to `path` as JSONL records of `format`.
+pub fn export(
+    examples: &[TrainingExample],
+    format: DatasetFormat,
+    path: &Path,
+) -> std::io::Result<()> {
+    let mut output = String::new();
+    for ex in examples {
+        output.push_str(&record(ex, format).to_string());
+        output.push('\n');
+    }
+    std::fs::write(path, output)
+}
+
+/// Read training examples from a self-play `train.jsonl`.
+pub fn load_examples(path: &Path) -> Result<Vec<TrainingExample>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| format!("{}:{}: {e}", path.display(), i + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::unified::UnifiedGenerator;
+    use x402_model::unified::{ModelConfig, UnifiedModel, UNIFIED_VOCAB};
+    use x402_model::Checkpointable;
+    use x402_soul::benchmark::BenchmarkProblem;
+
+    fn example(slug: &str, output: &str) -> TrainingExample {
+        TrainingExample {
+            instruction: format!("Problem: {slug}\n\nReturn the answer."),
+            output: output.to_string(),
+            slug: slug.to_string(),
+            tier: "tier1".to_string(),
+            iteration: 0,
+            was_retry: false,
+            from_mutant: false,
+        }
+    }
+
+    fn problem() -> BenchmarkProblem {
+        BenchmarkProblem {
+            slug: "answer".to_string(),
+            instructions: "Return the answer.".to_string(),
+            test_code: String::new(),
+            starter_code: String::new(),
+            difficulty: "tier1".to_string(),
+            cargo_toml: String::new(),
+            pack: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn unified_fine_tune_lowers_loss_and_checkpoints() {
+        let model = UnifiedModel::with_config(ModelConfig {
+            d_model: 32,
+            n_heads: 2,
+            d_ff: 64,
+            max_seq: 48,
+            vocab: UNIFIED_VOCAB,
+            enc_layers: 1,
+            dec_layers: 1,
+        });
+        let mut generator = UnifiedGenerator::from_model(model, BpeTokenizer::new(TOKENIZER_VOCAB));
+        let examples = [
+            example("answer", "pub fn answer() -> u32 { 42 }"),
+            example("double", "pub fn double(x: u32) -> u32 { x * 2 }"),
+        ];
+
+        let (epoch_loss, steps) = generator.fine_tune(&examples, 3e-3, 8);
+        assert_eq!(epoch_loss.len(), 8);
+        assert_eq!(steps, 8);
+        assert!(epoch_loss[7] < epoch_loss[0], "{epoch_loss:?}");
+
+        let dir = tempfile::tempdir().unwrap();
+        generator.save_checkpoint(dir.path()).unwrap();
+        let restored = UnifiedModel::load_file(dir.path().join(MODEL_FILE)).unwrap();
+        assert_eq!(restored.train_steps, 8);
+        // The learned tokenizer comes back with the model.
+        let reloaded = UnifiedGenerator::load(dir.path()).unwrap();
+        if let Err(e) = reloaded.generate(&problem()).await {
+            assert!(!e.contains("tokenizer"), "{e}");
+        }
+    }
+
+    #[test]
+    fn exports_chat_and_instruction_records() {
+        let ex = example("answer", "pub fn answer() -> u32 { 42 }");
+        let chat = record(&ex, DatasetFormat::Chat);
+        let roles: Vec<&str> = chat["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["system", "user", "assistant"]);
+        assert_eq!(chat["messages"][2]["content"], ex.output);
+
+        let alpaca = record(&ex, "alpaca".parse().unwrap());
+        assert_eq!(alpaca["instruction"], ex.instruction);
+        assert_eq!(alpaca["output"], ex.output);
+    }
+}
@@ -91,6 +91,7 @@ fn parse_multipl_e_entry(entry: &serde_json::Value) -> Option<BenchmarkProblem>
         starter_code: prompt.to_string(),
         difficulty: "humaneval".to_string(),
         cargo_toml: String::new(),
+        pack: String::new(),
     })
 }
 
@@ -119,6 +120,7 @@ mod tests {
             starter_code: "pub fn has_close_elements(numbers: Vec<f64>, threshold: f64) -> bool {\n    todo!()\n}\n".to_string(),
             difficulty: "humaneval".to_string(),
             cargo_toml: String::new(),
+            pack: String::new(),
         },
         BenchmarkProblem {
             slug: "humaneval-separate-paren-groups".to_string(),
@@ -139,6 +141,7 @@ mod tests {
             starter_code: "pub fn separate_paren_groups(paren_string: String) -> Vec<String> {\n    todo!()\n}\n".to_string(),
             difficulty: "humaneval".to_string(),
             cargo_toml: String::new(),
+            pack: String::new(),
         },
         BenchmarkProblem {
             slug: "humaneval-truncate-number".to_string(),
@@ -158,6 +161,7 @@ mod tests {
             starter_code: "pub fn truncate_number(number: f64) -> f64 {\n    todo!()\n}\n".to_string(),
             difficulty: "humaneval".to_string(),
             cargo_toml: String::new(),
+            pack: String::new(),
         },
     ]
 }
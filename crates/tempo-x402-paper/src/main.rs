@@ -7,20 +7,59 @@
 //!   paper-bench score-claude --problems 5          # smoke test Claude
 //!   paper-bench score-gemini --problems 5          # smoke test Gemini
 //!   paper-bench score-local --model models/qwen.gguf --problems 5
+//!   paper-bench --jobs 8 score-gemini                # validate 8 solutions at once
 //!   paper-bench selfplay --model models/qwen.gguf --iterations 10
-//!   paper-bench summary                             # compare all results
+//!   paper-bench selfplay --scratch unified --seed-data runs/claude/training_data/train.jsonl
+//!   paper-bench selfplay --model q.gguf --train-command "python lora.py {data} {output}"
+//!   paper-bench export-dataset runs/x/training_data/train.jsonl --format instruction --output a.jsonl
+//!   paper-bench --samples 10 score-local --model m.gguf  # pass@k over 10 samples
+//!   paper-bench score-openai --base-url http://localhost:8000/v1 --model qwen2.5-coder
+//!   paper-bench --replay-only score-openai --model qwen2.5-coder  # cached generations only
+//!   paper-bench summary --k 1,10                    # all results, with error bars
+//!   paper-bench compare results/base.json results/iter3.json  # paired test + diff
+//!   paper-bench eval-split --db /data/soul.db       # freeze the held-out split
+//!   paper-bench eval-model --checkpoint /tmp/unified_model.ckpt
+//!   paper-bench --pack packs/acme.tar.gz score-gemini  # score a problem pack
+//!   paper-bench pack-export --output packs/opus        # embedded set as a pack
+//!   paper-bench pack-validate packs/acme               # reference solutions pass
+//!   paper-bench mutate --db /data/soul.db --output packs/bugs  # debugging problems
 
 mod backends;
+mod cache;
+mod finetune;
 mod humaneval;
 mod results;
 mod runner;
 mod selfplay;
+mod stats;
 
+use cache::CacheMode;
 use clap::{Parser, Subcommand};
+use x402_soul::problem_pack::{PackInfo, ProblemPack, ReferenceCheck};
+use x402_soul::validation_pool::{CancelToken, ValidationPool};
 
 #[derive(Parser)]
 #[command(name = "paper-bench", about = "Benchmark code models for self-play fine-tuning research")]
 struct Cli {
+    /// Solutions validated concurrently (default: half the CPUs)
+    #[arg(long, global = true, env = "SOUL_BENCH_WORKERS")]
+    jobs: Option<usize>,
+    /// Problem packs to use instead of the embedded Opus set ("opus" is the
+    /// embedded pack). Repeatable or comma-separated.
+    #[arg(long = "pack", global = true, value_delimiter = ',')]
+    packs: Vec<String>,
+    /// Solutions generated per problem when scoring, for pass@k
+    #[arg(long, global = true, default_value = "1")]
+    samples: usize,
+    /// Directory of cached generations
+    #[arg(long, global = true, env = "PAPER_BENCH_CACHE", default_value = cache::DEFAULT_DIR)]
+    cache_dir: String,
+    /// Always call the backend and cache nothing
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// Only replay cached generations; fail on any that is missing
+    #[arg(long, global = true, conflicts_with = "no_cache")]
+    replay_only: bool,
     #[command(subcommand)]
     command: Command,
 }
@@ -70,6 +109,38 @@ enum Command {
         #[arg(long)]
         humaneval: bool,
     },
+    /// Score a model behind an OpenAI-compatible chat completions API
+    /// (vLLM, llama-server, TGI, Ollama, ...)
+    ScoreOpenai {
+        #[arg(long, env = "OPENAI_BASE_URL", default_value = "http://localhost:8000/v1")]
+        base_url: String,
+        #[arg(long, env = "OPENAI_API_KEY")]
+        api_key: Option<String>,
+        /// Model name as the server knows it
+        #[arg(long)]
+        model: String,
+        /// Name for results (default: the model)
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long, default_value = "0.2")]
+        temperature: f32,
+        #[arg(long, default_value = "1.0")]
+        top_p: f32,
+        #[arg(long, default_value = "4096")]
+        max_tokens: u32,
+        /// Sampling seed, for servers that support it
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Stop sequences (repeatable)
+        #[arg(long)]
+        stop: Vec<String>,
+        #[arg(long, default_value = "0")]
+        problems: usize,
+        #[arg(long, default_value = "results/openai-model.json")]
+        output: String,
+        #[arg(long)]
+        humaneval: bool,
+    },
     /// Run self-play fine-tuning loop
     Selfplay {
         /// Path to GGUF model file (or API backend)
@@ -81,6 +152,40 @@ enum Command {
         /// Use Gemini API instead of local model
         #[arg(long, env = "GEMINI_API_KEY")]
         gemini_key: Option<String>,
+        /// Use a model behind an OpenAI-compatible API at this base URL
+        #[arg(long, requires = "openai_model")]
+        openai_url: Option<String>,
+        /// Model name for --openai-url
+        #[arg(long)]
+        openai_model: Option<String>,
+        /// Train a from-scratch model in process: "codegen" or "unified"
+        #[arg(long)]
+        scratch: Option<String>,
+        /// Weights for --scratch: a checkpoint file or a checkpoint directory
+        /// from an earlier run (fresh weights if omitted)
+        #[arg(long, requires = "scratch")]
+        checkpoint: Option<String>,
+        /// Trained BPE tokenizer (JSON) for --scratch; learned from the
+        /// training data if neither this nor the checkpoint has one
+        #[arg(long, requires = "scratch")]
+        tokenizer: Option<String>,
+        /// Command that fine-tunes the model between iterations, e.g. a LoRA
+        /// script; {data}, {output}, {model}, {rank}, {lr} and {epochs} are
+        /// substituted. It must leave model.gguf in {output}
+        #[arg(long)]
+        train_command: Option<String>,
+        /// Verified examples (train.jsonl) to fine-tune on before the first
+        /// iteration
+        #[arg(long)]
+        seed_data: Option<String>,
+        /// Fine-tuning epochs per iteration
+        #[arg(long, default_value = "3")]
+        epochs: u32,
+        #[arg(long, default_value = "2e-4")]
+        learning_rate: f64,
+        /// LoRA rank passed to --train-command
+        #[arg(long, default_value = "16")]
+        lora_rank: u32,
         /// Number of self-play iterations
         #[arg(long, default_value = "10")]
         iterations: usize,
@@ -90,13 +195,111 @@ enum Command {
         /// Output directory
         #[arg(long, default_value = "selfplay_runs")]
         output_dir: String,
+        /// Debugging problems mutated from each newly solved problem (0 = off)
+        #[arg(long, default_value = "0")]
+        mutants: usize,
+    },
+    /// Freeze the held-out evaluation split from a soul database
+    EvalSplit {
+        #[arg(long, env = "SOUL_DB_PATH", default_value = "/data/soul.db")]
+        db: String,
+        #[arg(long, default_value = "eval/heldout.json")]
+        output: String,
+    },
+    /// Score a unified model checkpoint on the frozen held-out split
+    EvalModel {
+        #[arg(long, default_value = "/tmp/unified_model.ckpt")]
+        checkpoint: String,
+        #[arg(long, default_value = "eval/heldout.json")]
+        heldout: String,
+        /// Opus problems for pass@k (0 = all)
+        #[arg(long, default_value = "0")]
+        problems: usize,
+        /// Samples per problem (0 = skip pass@k)
+        #[arg(long, default_value = "5")]
+        samples: usize,
+        #[arg(long, default_value = "5")]
+        k: usize,
+        #[arg(long, default_value = "results/eval-model.json")]
+        output: String,
+    },
+    /// Convert self-play training data (train.jsonl) for external trainers
+    ExportDataset {
+        input: String,
+        /// "chat" (messages) or "instruction" (Alpaca)
+        #[arg(long, default_value = "chat")]
+        format: finetune::DatasetFormat,
+        /// Leave out the fixes of generated debugging problems
+        #[arg(long)]
+        no_mutants: bool,
+        #[arg(long)]
+        output: String,
     },
     /// Fetch HumanEval-Rust from HuggingFace
     FetchHumaneval,
+    /// Write a built-in problem set as a problem pack directory
+    PackExport {
+        /// "opus" or "humaneval"
+        #[arg(long, default_value = "opus")]
+        source: String,
+        #[arg(long)]
+        output: String,
+    },
+    /// Check a problem pack: hashes, files, and that every reference
+    /// solution passes its tests
+    PackValidate {
+        /// Pack directory or .tar/.tar.gz
+        path: String,
+        /// Recompute the manifest hashes (directories only) instead of
+        /// rejecting edited problems
+        #[arg(long)]
+        rehash: bool,
+    },
+    /// Generate debugging problems by mutating passing solutions, written
+    /// as a pack whose reference solutions are the original code
+    Mutate {
+        /// Mutate the passing benchmark solutions in this soul database
+        /// instead of the reference solutions of the loaded packs
+        #[arg(long)]
+        db: Option<String>,
+        /// Debugging problems kept per solution
+        #[arg(long, default_value = "3")]
+        per_solution: usize,
+        /// Seed for choosing which mutants to try
+        #[arg(long, default_value = "0")]
+        seed: u64,
+        #[arg(long, default_value = "packs/debugging")]
+        output: String,
+    },
     /// Show summary of all results
     Summary {
         #[arg(long, default_value = "results")]
         dir: String,
+        /// pass@k columns to show
+        #[arg(long, value_delimiter = ',', default_value = "1")]
+        k: Vec<usize>,
+        /// Bootstrap resamples per interval
+        #[arg(long, default_value = "1000")]
+        resamples: usize,
+    },
+    /// Paired comparison of two result files: score difference with a
+    /// bootstrap interval, permutation p-value and per-problem diff
+    Compare {
+        baseline: String,
+        candidate: String,
+        #[arg(long, default_value = "1")]
+        k: usize,
+        /// Weight problems by tier
+        #[arg(long)]
+        weighted: bool,
+        /// Bootstrap and permutation resamples
+        #[arg(long, default_value = "10000")]
+        resamples: usize,
+        #[arg(long, default_value = "0")]
+        seed: u64,
+        /// Also write the comparison as JSON
+        #[arg(long)]
+        output: Option<String>,
     },
 }
 
@@ -110,6 +313,22 @@ async fn main() {
         .init();
 
     let cli = Cli::parse();
+    if let Some(jobs) = cli.jobs {
+        x402_soul::validation_pool::ValidationPool::configure_global(jobs);
+    }
+
+    let cache_mode = if cli.replay_only {
+        CacheMode::ReplayOnly
+    } else if cli.no_cache {
+        CacheMode::Off
+    } else {
+        CacheMode::ReadWrite
+    };
+    cache::ResponseCache::configure_global(&cli.cache_dir, cache_mode);
+
+    let samples = cli.samples;
+    let packs = load_packs(&cli.packs);
+    let bench_problems = x402_soul::problem_pack::merge(&packs);
 
     match cli.command {
         Command::ScoreClaude {
@@ -120,13 +339,7 @@ async fn main() {
             humaneval,
         } => {
             let generator = backends::claude::ClaudeGenerator::new(api_key, model);
-            let limit = if problems == 0 { None } else { Some(problems) };
-            runner::run_benchmark(&generator, limit, &output).await;
-            if humaneval {
-                let he_problems = humaneval::load_humaneval_problems();
-                let he_output = output.replace(".json", "-humaneval.json");
-                runner::run_benchmark_on(&generator, &he_problems, limit, &he_output).await;
-            }
+            score(&generator, &bench_problems, problems, samples, &output, humaneval).await;
         }
         Command::ScoreGemini {
             api_key,
@@ -136,13 +349,7 @@ async fn main() {
             humaneval,
         } => {
             let generator = backends::gemini::GeminiGenerator::new(api_key, model);
-            let limit = if problems == 0 { None } else { Some(problems) };
-            runner::run_benchmark(&generator, limit, &output).await;
-            if humaneval {
-                let he_problems = humaneval::load_humaneval_problems();
-                let he_output = output.replace(".json", "-humaneval.json");
-                runner::run_benchmark_on(&generator, &he_problems, limit, &he_output).await;
-            }
+            score(&generator, &bench_problems, problems, samples, &output, humaneval).await;
         }
         Command::ScoreLocal {
             model,
@@ -152,23 +359,63 @@ async fn main() {
             humaneval,
         } => {
             let generator = backends::local::LocalModelGenerator::new(name, model);
-            let limit = if problems == 0 { None } else { Some(problems) };
-            runner::run_benchmark(&generator, limit, &output).await;
-            if humaneval {
-                let he_problems = humaneval::load_humaneval_problems();
-                let he_output = output.replace(".json", "-humaneval.json");
-                runner::run_benchmark_on(&generator, &he_problems, limit, &he_output).await;
+            score(&generator, &bench_problems, problems, samples, &output, humaneval).await;
+        }
+        Command::ScoreOpenai {
+            base_url,
+            api_key,
+            model,
+            name,
+            temperature,
+            top_p,
+            max_tokens,
+            seed,
+            stop,
+            problems,
+            output,
+            humaneval,
+        } => {
+            let mut generator = backends::openai::OpenAiGenerator::new(base_url, api_key, model)
+                .with_sampling(backends::openai::Sampling {
+                    temperature,
+                    top_p,
+                    max_tokens,
+                    seed,
+                    stop,
+                });
+            if let Some(name) = name {
+                generator = generator.with_name(name);
             }
+            score(&generator, &bench_problems, problems, samples, &output, humaneval).await;
         }
         Command::Selfplay {
             model,
             claude_key,
             gemini_key,
+            openai_url,
+            openai_model,
+            scratch,
+            checkpoint,
+            tokenizer,
+            train_command,
+            seed_data,
+            epochs,
+            learning_rate,
+            lora_rank,
             iterations,
             problems,
             output_dir,
+            mutants,
         } => {
-            let generator: Box<dyn runner::CodeGenerator> = if let Some(path) = model {
+            let mut generator: Box<dyn runner::CodeGenerator> = if let Some(kind) = scratch {
+                match scratch_model(&kind, checkpoint.as_deref(), tokenizer.as_deref()) {
+                    Ok(generator) => generator,
+                    Err(e) => {
+                        eprintln!("Error: {e}");
+                        std::process::exit(1);
+                    }
+                }
+            } else if let Some(path) = model {
                 Box::new(backends::local::LocalModelGenerator::new(
                     "qwen-selfplay".to_string(),
                     path,
@@ -178,32 +425,396 @@ async fn main() {
                     key,
                     "claude-opus-4-6-20260411".to_string(),
                 ))
+            } else if let (Some(url), Some(model)) = (openai_url, openai_model) {
+                Box::new(backends::openai::OpenAiGenerator::new(
+                    url,
+                    std::env::var("OPENAI_API_KEY").ok(),
+                    model,
+                ))
             } else if let Some(key) = gemini_key {
                 Box::new(backends::gemini::GeminiGenerator::new(
                     key,
                     "gemini-2.5-flash-lite-preview-06-17".to_string(),
                 ))
             } else {
-                eprintln!("Error: specify --model (GGUF path), --claude-key, or --gemini-key");
+                eprintln!(
+                    "Error: specify --model (GGUF path), --scratch, --claude-key, --gemini-key, \
+                     or --openai-url"
+                );
                 std::process::exit(1);
             };
 
-            let all_problems = x402_soul::opus_bench::load_embedded_problems();
+            let all_problems = bench_problems;
             let config = selfplay::SelfPlayConfig {
                 iterations,
                 max_problems: problems,
                 output_dir,
+                mutants_per_solution: mutants,
+                train_command,
+                seed_data,
+                epochs,
+                learning_rate,
+                lora_rank,
                 ..Default::default()
             };
 
-            selfplay::run_selfplay(generator.as_ref(), &all_problems, &config).await;
+            selfplay::run_selfplay(generator.as_mut(), &all_problems, &config).await;
+        }
+        Command::EvalSplit { db, output } => {
+            let db = match x402_soul::db::SoulDatabase::new(&db) {
+                Ok(db) => db,
+                Err(e) => {
+                    eprintln!("Error: open {db}: {e}");
+                    std::process::exit(1);
+                }
+            };
+            let set = x402_soul::model_eval::HeldOutSet::build(&db);
+            match set.save(&output) {
+                Ok(()) => println!(
+                    "Froze {} fast-head and {} decoder examples to {output}",
+                    set.fast.len(),
+                    set.decode.len()
+                ),
+                Err(e) => eprintln!("Error: {e}"),
+            }
+        }
+        Command::EvalModel {
+            checkpoint,
+            heldout,
+            problems,
+            samples,
+            k,
+            output,
+        } => {
+            let mut all_problems = bench_problems;
+            if problems > 0 {
+                all_problems.truncate(problems);
+            }
+            let report =
+                x402_soul::model_eval::evaluate(&checkpoint, &heldout, &all_problems, samples, k)
+                    .await;
+            match report.and_then(|r| r.save(&output).map(|()| r)) {
+                Ok(r) => {
+                    println!(
+                        "{checkpoint} ({} steps): brier {:.4}, accuracy {:.3}, perplexity {:.2}",
+                        r.train_steps, r.fast.brier, r.fast.accuracy, r.decoder.perplexity
+                    );
+                    if let Some(p) = &r.pass_at_k {
+                        println!("pass@1 {:.3}, pass@{} {:.3}", p.pass_at_1, p.k, p.pass_at_k);
+                    }
+                    println!("Report saved to {output}");
+                }
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::ExportDataset {
+            input,
+            format,
+            no_mutants,
+            output,
+        } => {
+            let mut examples = match finetune::load_examples(std::path::Path::new(&input)) {
+                Ok(examples) => examples,
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    std::process::exit(1);
+                }
+            };
+            if no_mutants {
+                examples.retain(|ex| !ex.from_mutant);
+            }
+            if let Err(e) = finetune::export(&examples, format, std::path::Path::new(&output)) {
+                eprintln!("Error: write {output}: {e}");
+                std::process::exit(1);
+            }
+            println!("Wrote {} examples to {output}", examples.len());
         }
         Command::FetchHumaneval => match humaneval::fetch_and_cache().await {
             Ok(count) => println!("Fetched {count} HumanEval-Rust problems"),
             Err(e) => eprintln!("Error: {e}"),
         },
-        Command::Summary { dir } => {
-            results::print_summary(&dir);
+        Command::PackExport { source, output } => {
+            let pack = match source.as_str() {
+                "opus" => x402_soul::problem_pack::embedded().clone(),
+                "humaneval" => ProblemPack::from_problems(
+                    PackInfo {
+                        name: "humaneval-rust".to_string(),
+                        version: "1.0.0".to_string(),
+                        description: "HumanEval translated to Rust (MultiPL-E)".to_string(),
+                    },
+                    humaneval::load_humaneval_problems()
+                        .into_iter()
+                        .map(|p| (p, None)),
+                ),
+                other => {
+                    eprintln!("Error: unknown source {other:?} (expected opus or humaneval)");
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = pack.write(std::path::Path::new(&output)) {
+                eprintln!("Error: write {output}: {e}");
+                std::process::exit(1);
+            }
+            println!("Wrote {} ({} problems) to {output}", pack.id(), pack.problems.len());
+        }
+        Command::PackValidate { path, rehash } => {
+            if !validate_pack(std::path::Path::new(&path), rehash).await {
+                std::process::exit(1);
+            }
+        }
+        Command::Mutate {
+            db,
+            per_solution,
+            seed,
+            output,
+        } => {
+            let sources = match db {
+                Some(path) => match x402_soul::db::SoulDatabase::new(&path) {
+                    Ok(db) => x402_soul::mutation::solved_problems(&db, &bench_problems),
+                    Err(e) => {
+                        eprintln!("Error: open {path}: {e}");
+                        std::process::exit(1);
+                    }
+                },
+                None => packs
+                    .iter()
+                    .flat_map(|pack| &pack.problems)
+                    .filter_map(|p| Some((p.problem.clone(), p.solution.clone()?)))
+                    .collect(),
+            };
+            if sources.is_empty() {
+                eprintln!("Error: no passing solutions to mutate (packs without solution.rs? try --db)");
+                std::process::exit(1);
+            }
+            let pack = mutate(&sources, per_solution, seed).await;
+            if let Err(e) = pack.write(std::path::Path::new(&output)) {
+                eprintln!("Error: write {output}: {e}");
+                std::process::exit(1);
+            }
+            println!(
+                "Wrote {} ({} debugging problems from {} solutions) to {output}",
+                pack.id(),
+                pack.problems.len(),
+                sources.len()
+            );
+        }
+        Command::Summary { dir, k, resamples } => {
+            results::print_summary(&dir, &k, resamples);
+        }
+        Command::Compare {
+            baseline,
+            candidate,
+            k,
+            weighted,
+            resamples,
+            seed,
+            output,
+        } => {
+            let load = |path: &str| {
+                results::load(path).unwrap_or_else(|e| {
+                    eprintln!("Error: {path}: {e}");
+                    std::process::exit(1);
+                })
+            };
+            let weighting = if weighted {
+                stats::Weighting::Tier
+            } else {
+                stats::Weighting::Raw
+            };
+            let Some(comparison) = stats::compare(
+                &load(&baseline),
+                &load(&candidate),
+                k,
+                weighting,
+                resamples,
+                seed,
+            ) else {
+                eprintln!("Error: no shared problems with at least {k} samples");
+                std::process::exit(1);
+            };
+            results::print_comparison(&baseline, &candidate, &comparison);
+            if let Some(path) = output {
+                let json = serde_json::to_string_pretty(&comparison).expect("comparison serializes");
+                if let Err(e) = std::fs::write(&path, json) {
+                    eprintln!("Error: write {path}: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+/// Score `generator` on up to `problems` benchmark problems (0 = all), and
+/// on HumanEval-Rust next to `output` when asked. Exits on failure.
+async fn score(
+    generator: &dyn runner::CodeGenerator,
+    bench_problems: &[x402_soul::benchmark::BenchmarkProblem],
+    problems: usize,
+    samples: usize,
+    output: &str,
+    humaneval: bool,
+) {
+    let limit = if problems == 0 { None } else { Some(problems) };
+    let mut runs = vec![(bench_problems.to_vec(), output.to_string())];
+    if humaneval {
+        runs.push((
+            humaneval::load_humaneval_problems(),
+            output.replace(".json", "-humaneval.json"),
+        ));
+    }
+    for (problems, output) in runs {
+        if let Err(e) = runner::run_benchmark_on(generator, &problems, limit, samples, &output).await
+        {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// An in-process model for `selfplay --scratch`.
+fn scratch_model(
+    kind: &str,
+    checkpoint: Option<&str>,
+    tokenizer: Option<&str>,
+) -> Result<Box<dyn runner::CodeGenerator>, String> {
+    match kind {
+        "codegen" => {
+            let generator = backends::codegen15m::CodeGen15MGenerator::new(checkpoint);
+            Ok(Box::new(match tokenizer {
+                Some(path) => generator.with_tokenizer(path),
+                None => generator,
+            }))
+        }
+        "unified" => {
+            let generator = match checkpoint {
+                Some(path) => backends::unified::UnifiedGenerator::load(std::path::Path::new(path))?,
+                None => backends::unified::UnifiedGenerator::from_model(
+                    x402_model::unified::UnifiedModel::new(),
+                    x402_model::bpe::BpeTokenizer::new(8192),
+                ),
+            };
+            Ok(Box::new(match tokenizer {
+                Some(path) => generator.with_tokenizer(path)?,
+                None => generator,
+            }))
+        }
+        other => Err(format!("unknown --scratch model {other:?} (expected codegen or unified)")),
+    }
+}
+
+/// Load `--pack` arguments, or the embedded pack when there are none.
+/// Exits on the first pack that fails to load.
+fn load_packs(paths: &[String]) -> Vec<ProblemPack> {
+    if paths.is_empty() {
+        return vec![x402_soul::problem_pack::embedded().clone()];
+    }
+    paths
+        .iter()
+        .map(|path| {
+            if path == "opus" {
+                return x402_soul::problem_pack::embedded().clone();
+            }
+            match ProblemPack::load(std::path::Path::new(path)) {
+                Ok(pack) => {
+                    tracing::info!(pack = %pack.id(), problems = pack.problems.len(), "Loaded problem pack");
+                    pack
+                }
+                Err(e) => {
+                    eprintln!("Error: pack {path}: {e}");
+                    std::process::exit(1);
+                }
+            }
+        })
+        .collect()
+}
+
+/// Debugging problems from every solution in `sources`, as a pack.
+async fn mutate(
+    sources: &[(x402_soul::benchmark::BenchmarkProblem, String)],
+    per_solution: usize,
+    seed: u64,
+) -> ProblemPack {
+    let pool = ValidationPool::global();
+    let cancel = CancelToken::new();
+    let mut generated = Vec::new();
+    for (i, (problem, solution)) in sources.iter().enumerate() {
+        tracing::info!("[{}/{}] mutating {}", i + 1, sources.len(), problem.slug);
+        match x402_soul::mutation::debugging_problems(
+            problem,
+            solution,
+            per_solution,
+            seed,
+            pool,
+            &cancel,
+        )
+        .await
+        {
+            Ok(kept) => generated.extend(kept.into_iter().map(|d| (d.problem, Some(d.fix)))),
+            Err(e) => tracing::warn!(slug = %problem.slug, error = %e, "Mutation stopped"),
+        }
+    }
+    ProblemPack::from_problems(
+        PackInfo {
+            name: "debugging".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            description: format!("Mutants of {} passing solutions (seed {seed})", sources.len()),
+        },
+        generated,
+    )
+}
+
+/// Load a pack and run its reference solutions. Returns whether every
+/// reference passed.
+async fn validate_pack(path: &std::path::Path, rehash: bool) -> bool {
+    let loaded = if rehash {
+        ProblemPack::load_unverified(path)
+    } else {
+        ProblemPack::load(path)
+    };
+    let pack = match loaded {
+        Ok(pack) => pack,
+        Err(e) => {
+            eprintln!("Error: {}: {e}", path.display());
+            return false;
+        }
+    };
+    if rehash {
+        if !path.is_dir() {
+            eprintln!("Error: --rehash needs a pack directory");
+            return false;
+        }
+        if let Err(e) = pack.write(path) {
+            eprintln!("Error: rewrite {}: {e}", path.display());
+            return false;
+        }
+        println!("Rehashed {}", pack.id());
+    }
+
+    let pool = ValidationPool::global();
+    let cancel = CancelToken::new();
+    let Ok(checks) = x402_soul::problem_pack::check_references(&pack, pool, &cancel).await else {
+        return false;
+    };
+    let mut failed = 0;
+    let mut missing = 0;
+    for (slug, check) in &checks {
+        match check {
+            ReferenceCheck::Passed => {}
+            ReferenceCheck::Missing => missing += 1,
+            ReferenceCheck::Failed(error) => {
+                failed += 1;
+                println!("FAIL {slug}\n{error}\n");
+            }
         }
     }
+    println!(
+        "{}: {} problems, {} references passed, {failed} failed, {missing} without a reference",
+        pack.id(),
+        checks.len(),
+        checks.len() - failed - missing
+    );
+    failed == 0
 }
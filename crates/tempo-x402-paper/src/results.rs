@@ -1,18 +1,41 @@
 //! Results storage — JSON files for benchmark outputs.
 
+use std::collections::BTreeMap;
+
 use crate::runner::ProblemResult;
+use crate::stats::{self, Comparison, Weighting};
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct BenchmarkOutput {
     pub model: String,
     pub timestamp: String,
+    /// Distinct problems scored.
     pub total_problems: usize,
+    /// Problems with at least one passing sample.
     pub passed: usize,
+    /// pass@1 in percent.
     pub raw_pass_rate: f64,
+    /// Tier-weighted pass@1 in percent.
     pub weighted_pass_rate: f64,
+    /// Weighted share of individual tests passed.
+    #[serde(default)]
+    pub partial_credit: f64,
+    /// Attempts per problem; `results` has one entry per attempt.
+    #[serde(default = "one")]
+    pub samples_per_problem: usize,
+    /// Unbiased pass@k in percent, for each k up to `samples_per_problem`.
+    #[serde(default)]
+    pub pass_at_k: BTreeMap<usize, f64>,
+    /// Ids of the problem packs scored.
+    #[serde(default)]
+    pub packs: Vec<String>,
     pub results: Vec<ProblemResult>,
 }
 
+fn one() -> usize {
+    1
+}
+
 /// Save benchmark output to a JSON file.
 pub fn save(output: &BenchmarkOutput, path: &str) -> Result<(), String> {
     if let Some(parent) = std::path::Path::new(path).parent() {
@@ -29,8 +52,9 @@ pub fn load(path: &str) -> Result<BenchmarkOutput, String> {
     serde_json::from_str(&json).map_err(|e| format!("parse: {e}"))
 }
 
-/// Print a summary table of all results in a directory.
-pub fn print_summary(dir: &str) {
+/// Print a summary table of all results in a directory: pass@k for each
+/// of `ks` and tier-weighted pass@1, with bootstrap intervals.
+pub fn print_summary(dir: &str, ks: &[usize], resamples: usize) {
     let entries = match std::fs::read_dir(dir) {
         Ok(e) => e,
         Err(_) => {
@@ -54,14 +78,34 @@ pub fn print_summary(dir: &str) {
         return;
     }
 
-    println!("\n{:<30} {:>8} {:>8} {:>10} {:>10}", "Model", "Passed", "Total", "Raw %", "Weighted %");
-    println!("{}", "-".repeat(70));
+    let mut header = format!("\n{:<30} {:>8} {:>8} {:>4}", "Model", "Passed", "Total", "n");
+    for k in ks {
+        header.push_str(&format!(" {:>20}", format!("pass@{k} %")));
+    }
+    header.push_str(&format!(" {:>20} {:>10}", "Weighted %", "Partial %"));
+    println!("{header}");
+    println!("{}", "-".repeat(header.len() - 1));
     for o in &outputs {
-        println!(
-            "{:<30} {:>8} {:>8} {:>9.1}% {:>9.1}%",
-            o.model, o.passed, o.total_problems, o.raw_pass_rate, o.weighted_pass_rate
+        let problems = stats::problems(&o.results);
+        let interval = |k: usize, weighting: Weighting| {
+            stats::bootstrap(&problems, k, weighting, resamples, 0)
+                .map_or_else(|| "-".to_string(), |e| e.to_string())
+        };
+        let mut row = format!(
+            "{:<30} {:>8} {:>8} {:>4}",
+            o.model, o.passed, o.total_problems, o.samples_per_problem
         );
+        for &k in ks {
+            row.push_str(&format!(" {:>20}", interval(k, Weighting::Raw)));
+        }
+        row.push_str(&format!(
+            " {:>20} {:>9.1}%",
+            interval(1, Weighting::Tier),
+            o.partial_credit
+        ));
+        println!("{row}");
     }
+    println!("Intervals are 95% bootstrap over problems ({resamples} resamples).");
 
     // Per-tier breakdown
     println!("\nPer-tier breakdown:");
@@ -87,3 +131,41 @@ pub fn print_summary(dir: &str) {
     }
     println!();
 }
+
+/// Print a paired comparison and its per-problem diff.
+pub fn print_comparison(baseline: &str, candidate: &str, c: &Comparison) {
+    let weighted = match c.weighting {
+        Weighting::Raw => "",
+        Weighting::Tier => " (tier-weighted)",
+    };
+    println!("\npass@{}{weighted} on {} shared problems:", c.k, c.shared);
+    println!("  baseline   {baseline}: {}", c.baseline);
+    println!("  candidate  {candidate}: {}", c.candidate);
+    println!("  difference {}, p = {:.4}", c.difference, c.p_value);
+    println!("  (95% paired bootstrap interval; sign-flip permutation p-value)");
+
+    for (title, diffs) in [("Regressed", &c.regressed), ("Improved", &c.improved)] {
+        println!("\n{title} ({}):", diffs.len());
+        for d in diffs {
+            println!(
+                "  {:<40} {:<8} {:>3}/{:<3} -> {:>3}/{:<3} {:>+7.1}",
+                d.slug, d.tier, d.baseline.0, d.baseline.1, d.candidate.0, d.candidate.1, d.delta
+            );
+        }
+    }
+    if !c.only_baseline.is_empty() {
+        println!(
+            "\nOnly in baseline ({}): {}",
+            c.only_baseline.len(),
+            c.only_baseline.join(", ")
+        );
+    }
+    if !c.only_candidate.is_empty() {
+        println!(
+            "\nOnly in candidate ({}): {}",
+            c.only_candidate.len(),
+            c.only_candidate.join(", ")
+        );
+    }
+    println!();
+}
@@ -1,7 +1,16 @@
 //! Benchmark runner — runs a CodeGenerator against the Opus-201 benchmark.
 
-use x402_soul::benchmark::{validate_solution, BenchmarkProblem};
-use x402_soul::opus_bench;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use futures::StreamExt;
+use x402_soul::benchmark::BenchmarkProblem;
+use x402_soul::test_report::TestReport;
+use x402_soul::validation_pool::{CancelToken, Cancelled, ValidationPool};
+
+use crate::cache::{CacheError, CacheRequest, ResponseCache};
+use crate::finetune::Trainable;
+use crate::stats::{self, Weighting};
 
 /// Trait for any code generation backend (Claude, Gemini, Qwen, local model).
 #[async_trait::async_trait]
@@ -10,6 +19,19 @@ pub trait CodeGenerator: Send + Sync {
     async fn generate(&self, problem: &BenchmarkProblem) -> Result<String, String>;
     /// Human-readable name for results (e.g. "claude-opus-4", "qwen-0.5b-base").
     fn name(&self) -> &str;
+    /// Backend, model, prompt and sampling parameters of a generation, for
+    /// the [`ResponseCache`]. `None` (the default) disables caching.
+    fn cache_request(&self, _problem: &BenchmarkProblem) -> Option<CacheRequest> {
+        None
+    }
+    /// The model itself, when it can be fine-tuned in process.
+    fn trainable(&mut self) -> Option<&mut dyn Trainable> {
+        None
+    }
+    /// Switch to weights an external training command wrote into `dir`.
+    fn load_weights(&mut self, _dir: &Path) -> Result<(), String> {
+        Err(format!("{} can't load fine-tuned weights", self.name()))
+    }
 }
 
 /// Result of a single problem attempt.
@@ -21,102 +43,107 @@ pub struct ProblemResult {
     pub time_ms: u64,
     pub error: String,
     pub solution: String,
+    /// Index of this attempt among the problem's samples.
+    #[serde(default)]
+    pub sample: usize,
+    /// Per-test outcomes and compile errors, when validation ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<TestReport>,
 }
 
-/// Run the Opus-201 benchmark with a given generator.
-pub async fn run_benchmark(
-    generator: &dyn CodeGenerator,
-    limit: Option<usize>,
-    output_path: &str,
-) {
-    let problems = opus_bench::load_embedded_problems();
-    run_benchmark_on(generator, &problems, limit, output_path).await;
-}
-
-/// Run a benchmark on an arbitrary set of problems.
+/// Run a benchmark on an arbitrary set of problems, `samples` attempts each.
+///
+/// Problems are generated (through the global [`ResponseCache`]) and
+/// validated concurrently through the global [`ValidationPool`]. Ctrl-C stops
+/// the run and saves what has finished so far. In replay-only mode a
+/// generation missing from the cache stops the run with an error and saves
+/// nothing.
 pub async fn run_benchmark_on(
     generator: &dyn CodeGenerator,
     problems: &[BenchmarkProblem],
     limit: Option<usize>,
+    samples: usize,
     output_path: &str,
-) {
+) -> Result<(), String> {
+    let samples = samples.max(1);
     let total = match limit {
         Some(n) if n > 0 => n.min(problems.len()),
         _ => problems.len(),
     };
+    let pool = ValidationPool::global();
+    let mut packs: Vec<String> = problems[..total].iter().map(|p| p.pack.clone()).collect();
+    packs.sort();
+    packs.dedup();
+    packs.retain(|p| !p.is_empty());
 
     tracing::info!(
         model = generator.name(),
         total_problems = total,
+        samples,
+        workers = pool.workers(),
         "Starting Opus-201 benchmark"
     );
 
-    let mut results = Vec::new();
-    let mut passed = 0u32;
-    let mut total_weight = 0.0f64;
-    let mut earned_weight = 0.0f64;
-
-    for (i, problem) in problems.iter().take(total).enumerate() {
-        let weight = opus_bench::opus_difficulty_weight(&problem.difficulty);
-        total_weight += weight;
+    let cancel = CancelToken::new();
+    let on_ctrl_c = cancel.clone();
+    let ctrl_c = tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            tracing::warn!("Interrupted — saving finished results");
+            on_ctrl_c.cancel();
+        }
+    });
 
-        tracing::info!(
-            "[{}/{}] {} ({})",
-            i + 1,
-            total,
-            problem.slug,
-            problem.difficulty
-        );
+    let attempts: Vec<_> = problems
+        .iter()
+        .take(total)
+        .flat_map(|problem| (0..samples).map(move |sample| (problem, sample)))
+        .map(|(problem, sample)| attempt(generator, problem, sample, pool, &cancel))
+        .collect();
+    let mut attempts = futures::stream::iter(attempts).buffered(pool.workers());
 
-        let start = std::time::Instant::now();
-        let solution = match generator.generate(problem).await {
-            Ok(s) => s,
-            Err(e) => {
-                tracing::warn!(slug = %problem.slug, error = %e, "Generation failed");
-                results.push(ProblemResult {
-                    slug: problem.slug.clone(),
-                    tier: problem.difficulty.clone(),
-                    passed: false,
-                    time_ms: start.elapsed().as_millis() as u64,
-                    error: e,
-                    solution: String::new(),
-                });
-                continue;
+    let mut results = Vec::new();
+    let mut missing = None;
+    while let Some(attempt) = attempts.next().await {
+        let result = match attempt {
+            Ok(result) => result,
+            Err(Stop::Missing(e)) => {
+                missing = Some(e);
+                break;
             }
+            Err(Stop::Cancelled) => break,
         };
-
-        // Validate via cargo test
-        let (pass, error_output) = validate_solution(problem, &solution, "/tmp").await;
-        let time_ms = start.elapsed().as_millis() as u64;
-
-        if pass {
-            passed += 1;
-            earned_weight += weight;
-            tracing::info!(slug = %problem.slug, time_ms, "PASS");
-        } else {
-            tracing::info!(slug = %problem.slug, time_ms, "FAIL");
-        }
-
-        results.push(ProblemResult {
-            slug: problem.slug.clone(),
-            tier: problem.difficulty.clone(),
-            passed: pass,
-            time_ms,
-            error: error_output,
-            solution,
-        });
+        tracing::info!(
+            "[{}/{}] {} ({}) {} in {}ms",
+            results.len() + 1,
+            total * samples,
+            result.slug,
+            result.tier,
+            if result.passed { "PASS" } else { "FAIL" },
+            result.time_ms
+        );
+        results.push(result);
+    }
+    drop(attempts);
+    ctrl_c.abort();
+    if let Some(e) = missing {
+        return Err(e.to_string());
+    }
+    let (hits, misses) = ResponseCache::global().stats();
+    if hits > 0 {
+        tracing::info!(replayed = hits, generated = misses, "Used cached generations");
     }
 
-    let raw_pct = if total > 0 {
-        passed as f64 / total as f64 * 100.0
-    } else {
-        0.0
-    };
-    let weighted_pct = if total_weight > 0.0 {
-        earned_weight / total_weight * 100.0
-    } else {
-        0.0
-    };
+    let scored = stats::problems(&results);
+    let total = scored.len();
+    let passed = scored.iter().filter(|p| p.c > 0).count();
+    let raw_pct = stats::score(&scored, 1, Weighting::Raw).unwrap_or(0.0);
+    let weighted_pct = stats::score(&scored, 1, Weighting::Tier).unwrap_or(0.0);
+    let partial_pct = stats::partial_credit(&scored, Weighting::Tier).unwrap_or(0.0);
+    let pass_at_k: BTreeMap<usize, f64> = stats::REPORTED_K
+        .iter()
+        .filter(|&&k| k <= samples)
+        .filter_map(|&k| Some((k, stats::score(&scored, k, Weighting::Raw)?)))
+        .collect();
 
     tracing::info!(
         model = generator.name(),
@@ -124,6 +151,7 @@ pub async fn run_benchmark_on(
         total,
         raw = format!("{raw_pct:.1}%"),
         weighted = format!("{weighted_pct:.1}%"),
+        partial = format!("{partial_pct:.1}%"),
         "Benchmark complete"
     );
 
@@ -132,15 +160,79 @@ pub async fn run_benchmark_on(
         model: generator.name().to_string(),
         timestamp: chrono::Utc::now().to_rfc3339(),
         total_problems: total,
-        passed: passed as usize,
+        passed,
         raw_pass_rate: raw_pct,
         weighted_pass_rate: weighted_pct,
+        partial_credit: partial_pct,
+        samples_per_problem: samples,
+        pass_at_k,
+        packs,
         results,
     };
 
-    if let Err(e) = crate::results::save(&output, output_path) {
-        tracing::error!(error = %e, "Failed to save results");
-    } else {
-        tracing::info!(path = output_path, "Results saved");
+    crate::results::save(&output, output_path)
+        .map_err(|e| format!("save {output_path}: {e}"))?;
+    tracing::info!(path = output_path, "Results saved");
+    Ok(())
+}
+
+/// Why an attempt produced no result.
+enum Stop {
+    Cancelled,
+    /// Replay-only mode hit an uncached or uncacheable generation.
+    Missing(CacheError),
+}
+
+impl From<Cancelled> for Stop {
+    fn from(_: Cancelled) -> Self {
+        Self::Cancelled
     }
 }
+
+/// Generate and validate one problem.
+async fn attempt(
+    generator: &dyn CodeGenerator,
+    problem: &BenchmarkProblem,
+    sample: usize,
+    pool: &ValidationPool,
+    cancel: &CancelToken,
+) -> Result<ProblemResult, Stop> {
+    let start = std::time::Instant::now();
+    let solution = tokio::select! {
+        generated = ResponseCache::global().generate(generator, problem, sample) => generated,
+        _ = cancel.cancelled() => return Err(Stop::Cancelled),
+    };
+    let solution = match solution {
+        Ok(s) => s,
+        Err(e @ (CacheError::Missing { .. } | CacheError::Uncacheable { .. })) => {
+            cancel.cancel();
+            return Err(Stop::Missing(e));
+        }
+        Err(CacheError::Generate(e)) => {
+            tracing::warn!(slug = %problem.slug, error = %e, "Generation failed");
+            return Ok(ProblemResult {
+                slug: problem.slug.clone(),
+                tier: problem.difficulty.clone(),
+                passed: false,
+                time_ms: start.elapsed().as_millis() as u64,
+                error: e,
+                solution: String::new(),
+                sample,
+                report: None,
+            });
+        }
+    };
+
+    // Validate via cargo test
+    let validation = pool.validate(problem, &solution, cancel).await?;
+    Ok(ProblemResult {
+        slug: problem.slug.clone(),
+        tier: problem.difficulty.clone(),
+        passed: validation.passed,
+        time_ms: start.elapsed().as_millis() as u64,
+        error: validation.error_output,
+        solution,
+        sample,
+        report: Some(validation.report),
+    })
+}
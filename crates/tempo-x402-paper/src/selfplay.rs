@@ -5,11 +5,29 @@
 //! 2. Validate each via cargo test (ground truth oracle)
 //! 3. Passing solutions accumulate into the training set
 //! 4. Failed solutions are retried with error context (self-play retry)
-//! 5. Fine-tune the model on accumulated verified solutions (LoRA)
-//! 6. Evaluate, checkpoint, repeat
+//! 5. Fine-tune the model on accumulated verified solutions — in process
+//!    for the from-scratch models, through an external (LoRA) command for
+//!    everything else (see `finetune`)
+//! 6. Checkpoint, and evaluate the fine-tuned model in the next iteration
+//!
+//! With `mutants_per_solution` set, each newly solved problem's solution is
+//! also mutated into debugging problems (see `x402_soul::mutation`). Their
+//! known fixes go straight into the training set and the problems join the
+//! benchmark from the next iteration on, so every solve yields more work.
+
+use std::path::Path;
 
+use crate::cache::{CacheError, ResponseCache};
+use crate::finetune::{self, DatasetFormat, FineTuneStats};
 use crate::runner::{CodeGenerator, ProblemResult};
 use x402_soul::benchmark::{validate_solution, BenchmarkProblem};
+use x402_soul::problem_pack::{PackInfo, ProblemPack};
+use x402_soul::validation_pool::{CancelToken, ValidationPool};
+
+/// Verified examples, one `TrainingExample` per line.
+const TRAIN_FILE: &str = "train.jsonl";
+/// The same examples as chat records.
+const CHAT_FILE: &str = "chat.jsonl";
 
 /// Configuration for a self-play run.
 #[derive(Debug, Clone, serde::Serialize)]
@@ -20,7 +38,7 @@ pub struct SelfPlayConfig {
     pub max_problems: usize,
     /// Whether to retry failed problems with error context
     pub retry_with_context: bool,
-    /// LoRA rank for fine-tuning
+    /// LoRA rank, passed to the training command
     pub lora_rank: u32,
     /// Learning rate for fine-tuning
     pub learning_rate: f64,
@@ -28,6 +46,15 @@ pub struct SelfPlayConfig {
     pub epochs: u32,
     /// Directory for checkpoints and training data
     pub output_dir: String,
+    /// Debugging problems generated from each newly solved problem (0 = off)
+    pub mutants_per_solution: usize,
+    /// Shell command that fine-tunes models which can't train in process,
+    /// with `{data}`, `{output}`, `{model}`, `{rank}`, `{lr}` and `{epochs}`
+    /// placeholders
+    pub train_command: Option<String>,
+    /// Verified examples (a previous run's `train.jsonl`) to fine-tune on
+    /// before the first iteration
+    pub seed_data: Option<String>,
 }
 
 impl Default for SelfPlayConfig {
@@ -40,6 +67,9 @@ impl Default for SelfPlayConfig {
             learning_rate: 2e-4,
             epochs: 3,
             output_dir: "selfplay_runs".to_string(),
+            mutants_per_solution: 0,
+            train_command: None,
+            seed_data: None,
         }
     }
 }
@@ -53,6 +83,9 @@ pub struct TrainingExample {
     pub tier: String,
     pub iteration: usize,
     pub was_retry: bool,
+    /// The known fix of a generated debugging problem, not a model solution
+    #[serde(default)]
+    pub from_mutant: bool,
 }
 
 /// Result of a single self-play iteration.
@@ -64,13 +97,17 @@ pub struct IterationResult {
     pub problems_passed_on_retry: usize,
     pub new_training_examples: usize,
     pub total_training_examples: usize,
+    pub new_debugging_problems: usize,
     pub pass_rate: f64,
+    /// The fine-tuning step after this iteration, if one ran
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fine_tune: Option<FineTuneStats>,
     pub problem_results: Vec<ProblemResult>,
 }
 
 /// Run the full self-play loop.
 pub async fn run_selfplay(
-    generator: &dyn CodeGenerator,
+    generator: &mut dyn CodeGenerator,
     problems: &[BenchmarkProblem],
     config: &SelfPlayConfig,
 ) -> Vec<IterationResult> {
@@ -93,15 +130,45 @@ pub async fn run_selfplay(
 
     let mut all_training_examples: Vec<TrainingExample> = Vec::new();
     let mut all_results: Vec<IterationResult> = Vec::new();
+    let data_dir = format!("{dir}/training_data");
+
+    // Start from another run's verified examples
+    if let Some(path) = &config.seed_data {
+        match finetune::load_examples(Path::new(path)) {
+            Ok(examples) => {
+                tracing::info!(path, examples = examples.len(), "Loaded seed training data");
+                all_training_examples = examples;
+                save_training_data(&all_training_examples, &data_dir);
+                finetune::step(
+                    generator,
+                    &all_training_examples,
+                    &Path::new(&data_dir).join(CHAT_FILE),
+                    &Path::new(dir).join("checkpoints/seed"),
+                    config,
+                )
+                .await;
+            }
+            Err(e) => tracing::error!(error = %e, "Failed to load seed data"),
+        }
+    }
 
     // Track which problems have been solved (permanent set)
     let mut solved_slugs: std::collections::HashSet<String> = std::collections::HashSet::new();
 
+    // Debugging problems mutated from passing solutions, with their fixes
+    let mut debugging: Vec<(BenchmarkProblem, String)> = Vec::new();
+
     for iteration in 0..config.iterations {
+        let iteration_problems: Vec<&BenchmarkProblem> = problems
+            .iter()
+            .take(total_problems)
+            .chain(debugging.iter().map(|(p, _)| p))
+            .collect();
+        let attempted = iteration_problems.len();
         tracing::info!(
             iteration,
             model = generator.name(),
-            problems = total_problems,
+            problems = attempted,
             solved = solved_slugs.len(),
             training_examples = all_training_examples.len(),
             "Starting self-play iteration"
@@ -109,24 +176,30 @@ pub async fn run_selfplay(
 
         let mut iter_results = Vec::new();
         let mut new_examples = Vec::new();
+        let mut new_debugging = Vec::new();
         let mut passed = 0;
         let retry_passed = 0;
 
-        for (i, problem) in problems.iter().take(total_problems).enumerate() {
+        for (i, problem) in iteration_problems.iter().copied().enumerate() {
             tracing::info!(
                 "[iter {iteration}] [{}/{}] {} ({})",
                 i + 1,
-                total_problems,
+                attempted,
                 problem.slug,
                 problem.difficulty
             );
 
             let start = std::time::Instant::now();
 
-            // Generate solution
-            let solution = match generator.generate(problem).await {
+            // Generate solution; each iteration is a fresh sample in the cache
+            let cache = ResponseCache::global();
+            let solution = match cache.generate(&*generator, problem, iteration).await {
                 Ok(s) => s,
-                Err(e) => {
+                Err(e @ (CacheError::Missing { .. } | CacheError::Uncacheable { .. })) => {
+                    tracing::error!(error = %e, "Stopping self-play");
+                    return all_results;
+                }
+                Err(CacheError::Generate(e)) => {
                     tracing::warn!(slug = %problem.slug, error = %e, "Generation failed");
                     iter_results.push(ProblemResult {
                         slug: problem.slug.clone(),
@@ -135,6 +208,8 @@ pub async fn run_selfplay(
                         time_ms: start.elapsed().as_millis() as u64,
                         error: e,
                         solution: String::new(),
+                        sample: iteration,
+                        report: None,
                     });
                     continue;
                 }
@@ -145,7 +220,7 @@ pub async fn run_selfplay(
 
             if pass {
                 passed += 1;
-                solved_slugs.insert(problem.slug.clone());
+                let first_solve = solved_slugs.insert(problem.slug.clone());
                 new_examples.push(TrainingExample {
                     instruction: build_instruction(problem),
                     output: solution.clone(),
@@ -153,8 +228,24 @@ pub async fn run_selfplay(
                     tier: problem.difficulty.clone(),
                     iteration,
                     was_retry: false,
+                    from_mutant: false,
                 });
                 tracing::info!(slug = %problem.slug, "PASS");
+
+                if first_solve && !x402_soul::mutation::is_generated(&problem.slug) {
+                    for debug in mutate(problem, &solution, iteration, config).await {
+                        new_examples.push(TrainingExample {
+                            instruction: build_instruction(&debug.problem),
+                            output: debug.fix.clone(),
+                            slug: debug.problem.slug.clone(),
+                            tier: debug.problem.difficulty.clone(),
+                            iteration,
+                            was_retry: false,
+                            from_mutant: true,
+                        });
+                        new_debugging.push((debug.problem, debug.fix));
+                    }
+                }
             } else if config.retry_with_context && !error_output.is_empty() {
                 // Self-play retry: feed the error back and try again
                 tracing::info!(slug = %problem.slug, "FAIL — retrying with error context");
@@ -170,63 +261,67 @@ pub async fn run_selfplay(
                 time_ms: start.elapsed().as_millis() as u64,
                 error: error_output,
                 solution,
+                sample: iteration,
+                report: None,
             });
         }
 
         // Accumulate training data
         all_training_examples.extend(new_examples.iter().cloned());
+        let new_debugging_problems = new_debugging.len();
+        debugging.extend(new_debugging);
 
-        let result = IterationResult {
+        let mut result = IterationResult {
             iteration,
-            problems_attempted: total_problems,
+            problems_attempted: attempted,
             problems_passed: passed,
             problems_passed_on_retry: retry_passed,
             new_training_examples: new_examples.len(),
             total_training_examples: all_training_examples.len(),
-            pass_rate: if total_problems > 0 {
-                passed as f64 / total_problems as f64 * 100.0
+            new_debugging_problems,
+            pass_rate: if attempted > 0 {
+                passed as f64 / attempted as f64 * 100.0
             } else {
                 0.0
             },
+            fine_tune: None,
             problem_results: iter_results,
         };
 
-        // Save iteration results
-        if let Ok(json) = serde_json::to_string_pretty(&result) {
-            let _ = std::fs::write(
-                format!("{dir}/results/iteration_{iteration}.json"),
-                json,
-            );
-        }
-
-        // Save accumulated training data as JSONL (for fine-tuning)
-        save_training_data(&all_training_examples, &format!("{dir}/training_data/train.jsonl"));
-
         tracing::info!(
             iteration,
             passed,
-            total = total_problems,
+            total = attempted,
             rate = format!("{:.1}%", result.pass_rate),
             new_examples = new_examples.len(),
             total_examples = all_training_examples.len(),
             total_solved = solved_slugs.len(),
+            new_debugging_problems,
             "Iteration complete"
         );
 
-        // TODO: Fine-tune step here
-        // For now, the training data is saved as JSONL.
-        // The user runs fine-tuning externally:
-        //   python finetune.py --data selfplay_runs/training_data/train.jsonl \
-        //     --base-model Qwen/Qwen2.5-Coder-0.5B-Instruct \
-        //     --output selfplay_runs/checkpoints/iter_{iteration}
-        //
-        // Then reload the model for the next iteration:
-        //   paper-bench selfplay --model selfplay_runs/checkpoints/iter_{iteration}/model.gguf
-        tracing::info!(
-            "Training data saved to {dir}/training_data/train.jsonl ({} examples). \
-             Run fine-tuning, then restart with the new model for the next iteration.",
-            all_training_examples.len()
-        );
+        // Save accumulated training data as JSONL, then fine-tune on it;
+        // the next iteration evaluates the new checkpoint
+        save_training_data(&all_training_examples, &data_dir);
+        if new_debugging_problems > 0 {
+            save_debugging_pack(&debugging, &format!("{dir}/debugging"));
+        }
+        result.fine_tune = finetune::step(
+            generator,
+            &all_training_examples,
+            &Path::new(&data_dir).join(CHAT_FILE),
+            &Path::new(dir).join(format!("checkpoints/iter_{iteration}")),
+            config,
+        )
+        .await;
+
+        // Save iteration results
+        if let Ok(json) = serde_json::to_string_pretty(&result) {
+            let _ = std::fs::write(
+                format!("{dir}/results/iteration_{iteration}.json"),
+                json,
+            );
+        }
 
         all_results.push(result);
     }
@@ -238,12 +333,21 @@ pub async fn run_selfplay(
 
     // Print convergence curve
     println!("\n=== Self-Play Convergence ===");
-    println!("{:<12} {:>8} {:>10} {:>10} {:>12}", "Iteration", "Passed", "Rate", "New Ex.", "Total Ex.");
-    println!("{}", "-".repeat(55));
+    println!(
+        "{:<12} {:>8} {:>10} {:>10} {:>12} {:>10}",
+        "Iteration", "Passed", "Rate", "New Ex.", "Total Ex.", "Loss"
+    );
+    println!("{}", "-".repeat(66));
     for r in &all_results {
+        let loss = r
+            .fine_tune
+            .as_ref()
+            .and_then(|f| f.epoch_loss.last())
+            .map(|l| format!("{l:.4}"))
+            .unwrap_or_else(|| "-".to_string());
         println!(
-            "{:<12} {:>8} {:>9.1}% {:>10} {:>12}",
-            r.iteration, r.problems_passed, r.pass_rate, r.new_training_examples, r.total_training_examples
+            "{:<12} {:>8} {:>9.1}% {:>10} {:>12} {:>10}",
+            r.iteration, r.problems_passed, r.pass_rate, r.new_training_examples, r.total_training_examples, loss
         );
     }
     println!();
@@ -251,8 +355,49 @@ pub async fn run_selfplay(
     all_results
 }
 
+/// Debugging problems from a newly passing solution, per
+/// `config.mutants_per_solution`. The iteration seeds the mutant choice.
+async fn mutate(
+    problem: &BenchmarkProblem,
+    solution: &str,
+    iteration: usize,
+    config: &SelfPlayConfig,
+) -> Vec<x402_soul::mutation::DebugProblem> {
+    if config.mutants_per_solution == 0 {
+        return Vec::new();
+    }
+    x402_soul::mutation::debugging_problems(
+        problem,
+        solution,
+        config.mutants_per_solution,
+        iteration as u64,
+        ValidationPool::global(),
+        &CancelToken::new(),
+    )
+    .await
+    .unwrap_or_default()
+}
+
+/// Write the generated debugging problems as a pack, fixes as reference
+/// solutions, so they can be rerun with `--pack`.
+fn save_debugging_pack(debugging: &[(BenchmarkProblem, String)], dir: &str) {
+    let pack = ProblemPack::from_problems(
+        PackInfo {
+            name: "selfplay-debugging".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            description: "Mutants of solutions found during self-play".to_string(),
+        },
+        debugging
+            .iter()
+            .map(|(problem, fix)| (problem.clone(), Some(fix.clone()))),
+    );
+    if let Err(e) = pack.write(std::path::Path::new(dir)) {
+        tracing::warn!(error = %e, "Failed to save debugging pack");
+    }
+}
+
 /// Build the instruction string for a training example.
-fn build_instruction(problem: &BenchmarkProblem) -> String {
+pub(crate) fn build_instruction(problem: &BenchmarkProblem) -> String {
     format!(
         "Write a complete Rust library (src/lib.rs) that passes these tests.\n\n\
          Problem: {}\n\n{}\n\nTests:\n```rust\n{}\n```\n\nStarter:\n```rust\n{}\n```",
@@ -263,17 +408,20 @@ fn build_instruction(problem: &BenchmarkProblem) -> String {
     )
 }
 
-/// Save training examples as JSONL (one JSON object per line).
-fn save_training_data(examples: &[TrainingExample], path: &str) {
+/// Save training examples into `dir`: as raw `TrainingExample` JSONL, and
+/// as chat records for external trainers.
+fn save_training_data(examples: &[TrainingExample], dir: &str) {
     let mut output = String::new();
     for ex in examples {
-        // Deduplicate: keep latest per slug
         if let Ok(json) = serde_json::to_string(ex) {
             output.push_str(&json);
             output.push('\n');
         }
     }
-    if let Err(e) = std::fs::write(path, &output) {
+    let dir = Path::new(dir);
+    let saved = std::fs::write(dir.join(TRAIN_FILE), &output)
+        .and_then(|()| finetune::export(examples, DatasetFormat::Chat, &dir.join(CHAT_FILE)));
+    if let Err(e) = saved {
         tracing::warn!(error = %e, "Failed to save training data");
     }
 }
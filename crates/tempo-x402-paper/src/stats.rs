@@ -0,0 +1,456 @@
+//! Statistics for scoring and comparing benchmark runs.
+//!
+//! A result file holds one [`ProblemResult`] per sample, so each problem has
+//! `n` samples of which `c` passed. pass@k is the unbiased estimator
+//! `1 - C(n-c, k) / C(n, k)` averaged over problems with at least `k`
+//! samples, optionally weighted by `opus_difficulty_weight`. Error bars are
+//! 95% percentile bootstraps over problems.
+//!
+//! Two runs are compared on the problems they share: a paired bootstrap
+//! gives the interval of the score difference, and a sign-flip permutation
+//! test on the per-problem differences gives its p-value.
+
+use std::collections::BTreeMap;
+
+use x402_soul::model_eval::pass_at_k_estimate;
+use x402_soul::opus_bench::opus_difficulty_weight;
+
+use crate::results::BenchmarkOutput;
+use crate::runner::ProblemResult;
+
+/// Two-sided confidence level of every interval.
+const CONFIDENCE: f64 = 0.95;
+
+/// pass@k values stored in result files, when enough samples ran.
+pub const REPORTED_K: &[usize] = &[1, 5, 10, 25, 100];
+
+/// Samples of one problem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProblemSamples {
+    pub slug: String,
+    pub tier: String,
+    /// Samples run.
+    pub n: usize,
+    /// Samples that passed.
+    pub c: usize,
+    /// Mean share of tests passed per sample.
+    pub partial: f64,
+}
+
+impl ProblemSamples {
+    fn pass_at(&self, k: usize) -> f64 {
+        pass_at_k_estimate(self.n, self.c, k)
+    }
+
+    fn weight(&self, weighting: Weighting) -> f64 {
+        match weighting {
+            Weighting::Raw => 1.0,
+            Weighting::Tier => opus_difficulty_weight(&self.tier),
+        }
+    }
+}
+
+/// How problems count towards a score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weighting {
+    /// Every problem counts once.
+    Raw,
+    /// Problems count by `opus_difficulty_weight` of their tier.
+    Tier,
+}
+
+/// A score in percent with its confidence interval.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Estimate {
+    pub value: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl std::fmt::Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1} [{:.1}, {:.1}]", self.value, self.low, self.high)
+    }
+}
+
+/// Group per-sample results by problem, in slug order.
+pub fn problems(results: &[ProblemResult]) -> Vec<ProblemSamples> {
+    let mut by_slug: BTreeMap<&str, ProblemSamples> = BTreeMap::new();
+    for r in results {
+        let p = by_slug.entry(&r.slug).or_insert_with(|| ProblemSamples {
+            slug: r.slug.clone(),
+            tier: r.tier.clone(),
+            n: 0,
+            c: 0,
+            partial: 0.0,
+        });
+        p.n += 1;
+        let partial = if r.passed {
+            p.c += 1;
+            1.0
+        } else {
+            r.report
+                .as_ref()
+                .map_or(0.0, |report| report.partial_credit())
+        };
+        p.partial += (partial - p.partial) / p.n as f64;
+    }
+    by_slug.into_values().collect()
+}
+
+/// pass@k in percent over problems with at least `k` samples; `None` when
+/// there are none.
+pub fn score(problems: &[ProblemSamples], k: usize, weighting: Weighting) -> Option<f64> {
+    let eligible: Vec<&ProblemSamples> = problems.iter().filter(|p| p.n >= k).collect();
+    weighted_mean(&eligible, weighting, |p| p.pass_at(k))
+}
+
+/// Weighted mean share of tests passed, in percent.
+pub fn partial_credit(problems: &[ProblemSamples], weighting: Weighting) -> Option<f64> {
+    let all: Vec<&ProblemSamples> = problems.iter().collect();
+    weighted_mean(&all, weighting, |p| p.partial)
+}
+
+/// [`score`] with a bootstrap interval from `resamples` resamplings of the
+/// problems.
+pub fn bootstrap(
+    problems: &[ProblemSamples],
+    k: usize,
+    weighting: Weighting,
+    resamples: usize,
+    seed: u64,
+) -> Option<Estimate> {
+    let points: Vec<(f64, f64)> = problems
+        .iter()
+        .filter(|p| p.n >= k)
+        .map(|p| (p.weight(weighting), p.pass_at(k)))
+        .collect();
+    let value = mean_of(&points, 0..points.len())?;
+    let mut rng = Rng::new(seed);
+    let mut stats: Vec<f64> = (0..resamples)
+        .filter_map(|_| {
+            let draw: Vec<usize> = (0..points.len()).map(|_| rng.below(points.len())).collect();
+            mean_of(&points, draw.into_iter())
+        })
+        .collect();
+    let (low, high) = interval(&mut stats).unwrap_or((value, value));
+    Some(Estimate { value, low, high })
+}
+
+/// One problem's change between two runs.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ProblemDiff {
+    pub slug: String,
+    pub tier: String,
+    /// `(passed, samples)` in the baseline.
+    pub baseline: (usize, usize),
+    /// `(passed, samples)` in the candidate.
+    pub candidate: (usize, usize),
+    /// Candidate minus baseline pass@k, in percentage points.
+    pub delta: f64,
+}
+
+/// Paired comparison of two runs on their shared problems.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Comparison {
+    pub k: usize,
+    pub weighting: Weighting,
+    /// Problems scored in both runs with at least `k` samples.
+    pub shared: usize,
+    pub baseline: Estimate,
+    pub candidate: Estimate,
+    /// Candidate minus baseline, with a paired bootstrap interval.
+    pub difference: Estimate,
+    /// Two-sided sign-flip permutation p-value of the difference.
+    pub p_value: f64,
+    /// Problems whose pass@k went up, largest change first.
+    pub improved: Vec<ProblemDiff>,
+    /// Problems whose pass@k went down, largest change first.
+    pub regressed: Vec<ProblemDiff>,
+    /// Problems only one of the runs scored (or scored with fewer than `k`
+    /// samples).
+    pub only_baseline: Vec<String>,
+    pub only_candidate: Vec<String>,
+}
+
+/// Compare `candidate` against `baseline`. `None` when they share no
+/// problem with at least `k` samples.
+pub fn compare(
+    baseline: &BenchmarkOutput,
+    candidate: &BenchmarkOutput,
+    k: usize,
+    weighting: Weighting,
+    resamples: usize,
+    seed: u64,
+) -> Option<Comparison> {
+    let eligible = |output: &BenchmarkOutput| -> BTreeMap<String, ProblemSamples> {
+        problems(&output.results)
+            .into_iter()
+            .filter(|p| p.n >= k)
+            .map(|p| (p.slug.clone(), p))
+            .collect()
+    };
+    let base = eligible(baseline);
+    let cand = eligible(candidate);
+    let pairs: Vec<(&ProblemSamples, &ProblemSamples)> = base
+        .values()
+        .filter_map(|b| Some((b, cand.get(&b.slug)?)))
+        .collect();
+    if pairs.is_empty() {
+        return None;
+    }
+
+    // (weight, baseline pass@k, candidate pass@k) per shared problem.
+    let points: Vec<(f64, f64, f64)> = pairs
+        .iter()
+        .map(|(b, c)| (b.weight(weighting), b.pass_at(k), c.pass_at(k)))
+        .collect();
+    let total_weight: f64 = points.iter().map(|p| p.0).sum();
+    let means = |draw: &[usize]| -> (f64, f64) {
+        let weight: f64 = draw.iter().map(|&i| points[i].0).sum();
+        let (b, c) = draw.iter().fold((0.0, 0.0), |(b, c), &i| {
+            let (w, pb, pc) = points[i];
+            (b + w * pb, c + w * pc)
+        });
+        (b / weight * 100.0, c / weight * 100.0)
+    };
+    let all: Vec<usize> = (0..points.len()).collect();
+    let (base_value, cand_value) = means(&all);
+
+    let mut rng = Rng::new(seed);
+    let mut base_stats = Vec::with_capacity(resamples);
+    let mut cand_stats = Vec::with_capacity(resamples);
+    let mut diff_stats = Vec::with_capacity(resamples);
+    for _ in 0..resamples {
+        let draw: Vec<usize> = (0..points.len()).map(|_| rng.below(points.len())).collect();
+        let (b, c) = means(&draw);
+        base_stats.push(b);
+        cand_stats.push(c);
+        diff_stats.push(c - b);
+    }
+    let estimate = |value: f64, stats: &mut Vec<f64>| {
+        let (low, high) = interval(stats).unwrap_or((value, value));
+        Estimate { value, low, high }
+    };
+
+    // Under the null the sign of each problem's difference is arbitrary.
+    let observed = (cand_value - base_value).abs();
+    let extreme = (0..resamples)
+        .filter(|_| {
+            let flipped: f64 = points
+                .iter()
+                .map(|&(w, b, c)| if rng.coin() { w * (c - b) } else { w * (b - c) })
+                .sum();
+            (flipped / total_weight * 100.0).abs() >= observed - 1e-9
+        })
+        .count();
+    let p_value = (extreme + 1) as f64 / (resamples + 1) as f64;
+
+    let mut improved = Vec::new();
+    let mut regressed = Vec::new();
+    for (b, c) in &pairs {
+        let delta = (c.pass_at(k) - b.pass_at(k)) * 100.0;
+        let diff = ProblemDiff {
+            slug: b.slug.clone(),
+            tier: b.tier.clone(),
+            baseline: (b.c, b.n),
+            candidate: (c.c, c.n),
+            delta,
+        };
+        if delta > 0.0 {
+            improved.push(diff);
+        } else if delta < 0.0 {
+            regressed.push(diff);
+        }
+    }
+    improved.sort_by(|a, b| b.delta.total_cmp(&a.delta));
+    regressed.sort_by(|a, b| a.delta.total_cmp(&b.delta));
+
+    Some(Comparison {
+        k,
+        weighting,
+        shared: pairs.len(),
+        baseline: estimate(base_value, &mut base_stats),
+        candidate: estimate(cand_value, &mut cand_stats),
+        difference: estimate(cand_value - base_value, &mut diff_stats),
+        p_value,
+        improved,
+        regressed,
+        only_baseline: base
+            .keys()
+            .filter(|s| !cand.contains_key(*s))
+            .cloned()
+            .collect(),
+        only_candidate: cand
+            .keys()
+            .filter(|s| !base.contains_key(*s))
+            .cloned()
+            .collect(),
+    })
+}
+
+fn weighted_mean(
+    problems: &[&ProblemSamples],
+    weighting: Weighting,
+    value: impl Fn(&ProblemSamples) -> f64,
+) -> Option<f64> {
+    let points: Vec<(f64, f64)> = problems
+        .iter()
+        .map(|p| (p.weight(weighting), value(p)))
+        .collect();
+    mean_of(&points, 0..points.len())
+}
+
+/// Weighted mean of the drawn `(weight, value)` points, in percent.
+fn mean_of(points: &[(f64, f64)], draw: impl Iterator<Item = usize>) -> Option<f64> {
+    let (weight, sum) = draw.fold((0.0, 0.0), |(w, s), i| {
+        let (wi, vi) = points[i];
+        (w + wi, s + wi * vi)
+    });
+    (weight > 0.0).then(|| sum / weight * 100.0)
+}
+
+/// Central [`CONFIDENCE`] percentile interval of bootstrap statistics.
+fn interval(stats: &mut [f64]) -> Option<(f64, f64)> {
+    if stats.is_empty() {
+        return None;
+    }
+    stats.sort_by(f64::total_cmp);
+    let tail = (1.0 - CONFIDENCE) / 2.0;
+    let at = |q: f64| stats[((stats.len() - 1) as f64 * q).round() as usize];
+    Some((at(tail), at(1.0 - tail)))
+}
+
+/// SplitMix64, so intervals and p-values are reproducible for a seed.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform index below `n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * n as f64) as usize
+    }
+
+    fn coin(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(slug: &str, tier: &str, passed: bool) -> ProblemResult {
+        ProblemResult {
+            slug: slug.to_string(),
+            tier: tier.to_string(),
+            passed,
+            time_ms: 0,
+            error: String::new(),
+            solution: String::new(),
+            sample: 0,
+            report: None,
+        }
+    }
+
+    fn output(results: Vec<ProblemResult>) -> BenchmarkOutput {
+        BenchmarkOutput {
+            model: "m".to_string(),
+            timestamp: String::new(),
+            total_problems: 0,
+            passed: 0,
+            raw_pass_rate: 0.0,
+            weighted_pass_rate: 0.0,
+            partial_credit: 0.0,
+            samples_per_problem: 1,
+            pass_at_k: BTreeMap::new(),
+            packs: Vec::new(),
+            results,
+        }
+    }
+
+    #[test]
+    fn pass_at_k_scores_use_every_sample() {
+        // a: 1 of 4 samples pass, b: 4 of 4, c: 0 of 2.
+        let mut results = vec![result("a", "tier1", true), result("c", "tier6", false)];
+        results.extend((0..3).map(|_| result("a", "tier1", false)));
+        results.extend((0..4).map(|_| result("b", "tier2", true)));
+        results.push(result("c", "tier6", false));
+        let problems = problems(&results);
+        assert_eq!(
+            problems.iter().map(|p| (p.n, p.c)).collect::<Vec<_>>(),
+            [(4, 1), (4, 4), (2, 0)]
+        );
+
+        let pass1 = score(&problems, 1, Weighting::Raw).unwrap();
+        assert!((pass1 - (0.25 + 1.0 + 0.0) / 3.0 * 100.0).abs() < 1e-9);
+        // pass@2 of a is 1 - C(3,2)/C(4,2) = 0.5; c has too few samples for k = 4.
+        let pass2 = score(&problems, 2, Weighting::Raw).unwrap();
+        assert!((pass2 - (0.5 + 1.0 + 0.0) / 3.0 * 100.0).abs() < 1e-9);
+        let pass4 = score(&problems, 4, Weighting::Raw).unwrap();
+        assert!((pass4 - 100.0).abs() < 1e-9);
+        let weighted = score(&problems, 1, Weighting::Tier).unwrap();
+        assert!((weighted - (0.25 + 2.0) / 11.0 * 100.0).abs() < 1e-9);
+
+        let estimate = bootstrap(&problems, 1, Weighting::Raw, 500, 1).unwrap();
+        assert_eq!(estimate.value, pass1);
+        assert!(estimate.low <= pass1 && pass1 <= estimate.high);
+        assert!(estimate.low < estimate.high);
+        assert_eq!(
+            Some(estimate),
+            bootstrap(&problems, 1, Weighting::Raw, 500, 1)
+        );
+    }
+
+    #[test]
+    fn paired_comparison_reports_per_problem_changes() {
+        let slugs: Vec<String> = (0..40).map(|i| format!("p{i:02}")).collect();
+        let base = output(
+            slugs
+                .iter()
+                .map(|s| result(s, "tier1", s.as_str() < "p10"))
+                .chain([result("gone", "tier1", true)])
+                .collect(),
+        );
+        let cand = output(
+            slugs
+                .iter()
+                .map(|s| result(s, "tier1", s.as_str() != "p00" && s.as_str() < "p30"))
+                .chain([result("new", "tier1", false)])
+                .collect(),
+        );
+
+        let c = compare(&base, &cand, 1, Weighting::Raw, 2000, 7).unwrap();
+        assert_eq!(c.shared, 40);
+        assert_eq!((c.baseline.value, c.candidate.value), (25.0, 72.5));
+        assert!(c.difference.low > 0.0, "{}", c.difference);
+        assert!(c.p_value < 0.01, "{}", c.p_value);
+        assert_eq!(c.improved.len(), 20);
+        assert_eq!(c.regressed.len(), 1);
+        assert_eq!(c.regressed[0].slug, "p00");
+        assert_eq!(
+            (c.regressed[0].baseline, c.regressed[0].candidate),
+            ((1, 1), (0, 1))
+        );
+        assert_eq!(c.only_baseline, ["gone"]);
+        assert_eq!(c.only_candidate, ["new"]);
+
+        // A run compared with itself shows no difference.
+        let same = compare(&base, &base, 1, Weighting::Tier, 500, 7).unwrap();
+        assert_eq!(same.difference.value, 0.0);
+        assert_eq!(same.p_value, 1.0);
+        assert!(same.improved.is_empty() && same.regressed.is_empty());
+    }
+}
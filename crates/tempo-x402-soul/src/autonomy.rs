@@ -598,40 +598,8 @@ pub async fn sync_cognitive_systems(
         _ => {}
     }
 
-    // ── Fetch peer's brain weights ──
-    // Brain was previously missing from sync — the primary predictor never shared knowledge.
-    match http_client
-        .get(format!("{peer_url}/soul/brain/weights"))
-        .timeout(std::time::Duration::from_secs(10))
-        .send()
-        .await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            if let Ok(data) = resp.json::<serde_json::Value>().await {
-                if let Some(weights_json) = data.get("weights").and_then(|v| v.as_str()) {
-                    if let Some(peer_brain) = crate::brain::Brain::from_json(weights_json) {
-                        let peer_steps = peer_brain.train_steps;
-                        // Only merge if peer has meaningful training
-                        if peer_steps >= 100 {
-                            let mut local_brain = crate::brain::load_brain(db);
-                            let delta = peer_brain.compute_delta(&local_brain, peer_id);
-                            local_brain.merge_delta(&delta, effective_rate);
-                            crate::brain::save_brain(db, &local_brain);
-                            tracing::info!(
-                                peer = %peer_id,
-                                peer_steps,
-                                rate = format!("{:.3}", effective_rate),
-                                "Merged peer BRAIN weights (fitness-weighted)"
-                            );
-                        }
-                    }
-                }
-            }
-        }
-        _ => {
-            tracing::debug!(peer = %peer_id, "Failed to fetch peer brain weights");
-        }
-    }
+    // Brain, plan transformer, unified and codegen weights are merged across
+    // all peers at once by `federation::federated_round`.
 
     // ── Fetch peer's benchmark solutions for codegen training ──
     // Import peer solutions so our codegen model trains on colony-wide data.
@@ -693,65 +661,6 @@ pub async fn sync_cognitive_systems(
                     }
                 }
 
-                // ── Fetch peer's transformer weights ──
-                match http_client
-                    .get(format!("{peer_url}/soul/model/transformer"))
-                    .timeout(std::time::Duration::from_secs(10))
-                    .send()
-                    .await
-                {
-                    Ok(resp) if resp.status().is_success() => {
-                        if let Ok(data) = resp.json::<serde_json::Value>().await {
-                            let peer_steps = data
-                                .get("train_steps")
-                                .and_then(|v| v.as_u64())
-                                .unwrap_or(0);
-                            // Only merge if the peer has done meaningful training
-                            if peer_steps >= 50 {
-                                // Fetch full weights for delta computation
-                                if let Ok(weights_resp) = http_client
-                                    .get(format!("{peer_url}/soul/model/transformer/weights"))
-                                    .timeout(std::time::Duration::from_secs(15))
-                                    .send()
-                                    .await
-                                {
-                                    if weights_resp.status().is_success() {
-                                        if let Ok(weights_data) =
-                                            weights_resp.json::<serde_json::Value>().await
-                                        {
-                                            if let Some(weights_json) =
-                                                weights_data.get("weights").and_then(|v| v.as_str())
-                                            {
-                                                if let Some(peer_model) =
-                                                    x402_model::PlanTransformer::from_json(
-                                                        weights_json,
-                                                    )
-                                                {
-                                                    let local = crate::model::load_model(db);
-                                                    let delta =
-                                                        peer_model.compute_delta(&local, peer_id);
-                                                    crate::model::merge_peer_delta(
-                                                        db,
-                                                        &delta,
-                                                        effective_rate,
-                                                    );
-                                                    tracing::debug!(
-                                                        peer = %peer_id,
-                                                        peer_steps,
-                                                        rate = format!("{:.3}", effective_rate),
-                                                        "Merged peer transformer weights"
-                                                    );
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-
                 // Update peer activity in hivemind
                 if let Some(activities) = data.get("peer_activities") {
                     if let Ok(acts) = serde_json::from_value::<Vec<crate::hivemind::PeerActivity>>(
@@ -12,10 +12,15 @@
 //! - Tier 5 (5x): Exploit known LLM failure modes (adversarial)
 //! - Tier 6 (8x): Multi-step algorithms, precision-critical (brutal)
 
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use x402_cartridge::sandbox;
 
+use crate::contamination::{ProblemSplit, SplitScore, SplitScores};
 use crate::db::SoulDatabase;
 use crate::llm::LlmClient;
+use crate::test_report::{TestOutcome, TestReport};
+use crate::validation_pool::{CancelToken, Cancelled, Validation, ValidationPool};
 
 /// A benchmark problem (embedded from opus_bench).
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +38,10 @@ pub struct BenchmarkProblem {
     /// Cargo.toml content (std-only for Opus problems, may have deps for others).
     #[serde(default)]
     pub cargo_toml: String,
+    /// Id of the [problem pack](crate::problem_pack) this came from, e.g.
+    /// `opus@9.3.0+1a2b3c4d5e6f`. Empty for ad-hoc problems.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub pack: String,
 }
 
 /// Result of running a single benchmark problem.
@@ -49,6 +58,13 @@ pub struct BenchmarkRun {
     /// Time to generate + validate in ms.
     pub total_ms: u64,
     pub created_at: i64,
+    /// Id of the problem pack the problem came from (empty for older runs).
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub pack: String,
+    /// Per-test outcomes and compile errors (absent for older runs and
+    /// attempts that never reached `cargo test`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<TestReport>,
 }
 
 
@@ -59,10 +75,20 @@ pub struct BenchmarkReport {
     pub total_failures: usize,
     pub failure_rates_by_tier: std::collections::HashMap<String, f64>,
     pub common_errors: Vec<String>,
+    /// Pass rates on problems the models may have trained on and on unseen
+    /// held-out problems (latest run per problem).
+    #[serde(default)]
+    pub seen: SplitScore,
+    #[serde(default)]
+    pub unseen: SplitScore,
 }
 
 /// Aggregates benchmark runs into a report.
-pub fn benchmark_report(runs: &[BenchmarkRun], problems: &[BenchmarkProblem]) -> BenchmarkReport {
+pub fn benchmark_report(
+    runs: &[BenchmarkRun],
+    problems: &[BenchmarkProblem],
+    split: &ProblemSplit,
+) -> BenchmarkReport {
     let mut total_failures = 0;
     let mut failures_by_tier: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
     let mut total_by_tier: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
@@ -77,7 +103,10 @@ pub fn benchmark_report(runs: &[BenchmarkRun], problems: &[BenchmarkProblem]) ->
                 *failures_by_tier.entry(problem.difficulty.clone()).or_insert(0) += 1;
                 
                 // Track simple common error patterns
-                let error_summary = run.error_output.lines().take(3).collect::<Vec<_>>().join(" ");
+                let error_summary = match &run.report {
+                    Some(report) if !report.is_empty() => report.headline(),
+                    _ => run.error_output.lines().take(3).collect::<Vec<_>>().join(" "),
+                };
                 *common_errors.entry(error_summary).or_insert(0) += 1;
             }
         }
@@ -94,12 +123,15 @@ pub fn benchmark_report(runs: &[BenchmarkRun], problems: &[BenchmarkProblem]) ->
     let mut sorted_errors: Vec<_> = common_errors.into_iter().collect();
     sorted_errors.sort_by(|a, b| b.1.cmp(&a.1));
     let common_errors = sorted_errors.into_iter().take(5).map(|(e, _)| e).collect();
+    let SplitScores { seen, unseen } = crate::contamination::split_scores(runs, problems, split);
 
     BenchmarkReport {
         total_attempts: runs.len(),
         total_failures,
         failure_rates_by_tier,
         common_errors,
+        seen,
+        unseen,
     }
 }
 
@@ -115,10 +147,21 @@ pub struct BenchmarkScore {
     pub problems_attempted: u32,
     /// Total problems passed in this scoring window.
     pub problems_passed: u32,
+    /// Weighted share of individual tests passed, so a solution that passes
+    /// 9 of 10 tests earns more than one that doesn't compile.
+    #[serde(default)]
+    pub partial_credit: f64,
     /// When this score was computed.
     pub measured_at: i64,
     /// Historical scores for trend tracking.
     pub history: Vec<HistoricalScore>,
+    /// Latest result per problem this rotation on problems the models may
+    /// have trained on (see [`crate::contamination`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seen: Option<SplitScore>,
+    /// Same, on held-out problems absent from the training data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unseen: Option<SplitScore>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -129,7 +172,7 @@ pub struct HistoricalScore {
 }
 
 /// Difficulty weight for scoring.
-fn difficulty_weight(difficulty: &str) -> f64 {
+pub(crate) fn difficulty_weight(difficulty: &str) -> f64 {
     match difficulty {
         "tier1" => 1.0,
         "tier2" => 2.0,
@@ -150,30 +193,31 @@ pub const REFERENCE_SCORES: &[(&str, f64)] = &[
     ("Gemini 3 Pro", 80.0),
 ];
 
-/// Shared target directory for all benchmark compilations.
-/// Deps compile once, then each exercise only recompiles its own lib + tests.
-const BENCHMARK_TARGET_DIR: &str = "/tmp/bench_target";
+/// Local codegen candidates sampled per problem before falling back to the LLM.
+const LOCAL_CANDIDATES: usize = 4;
 
 /// Generate a solution for a benchmark problem using the LLM.
 /// If `peer_failures` is provided, they're injected as negative context —
 /// the LLM sees what was tried before and why it failed, making it more
 /// likely to find a different, working approach. This is the core mechanism
 /// for proving collective intelligence (2 agents > 1 agent).
+///
+/// Local candidates are tested through `pool`; `Err` if `cancel` fires
+/// while they decode or run.
 pub async fn generate_solution(
     llm: &LlmClient,
     db: &SoulDatabase,
     problem: &BenchmarkProblem,
     peer_failures: &[SharedFailure],
-) -> Result<String, String> {
-    // Phase 3 weaning: DISABLED during benchmark.
-    // The encoder-decoder generate() does 512 autoregressive forward passes
-    // through the 15M model. Each pass processes the full context via cross-
-    // attention. On CPU this takes 5-10 minutes PER PROBLEM, completely
-    // blocking the benchmark session. Enable once loss drops below 4.0
-    // and generation is fast enough (<10s per problem).
+    pool: &ValidationPool,
+    cancel: &CancelToken,
+) -> Result<Result<String, String>, Cancelled> {
+    // Phase 3 weaning: local-first once the codegen model is good enough.
+    // Decoding is incremental (KV cache), so several sampled candidates fit
+    // in a fixed time budget. Each is run against the tests; the first that
+    // passes is used, otherwise fall through to the LLM.
     //
-    // The codegen model still TRAINS on benchmark solutions — it just
-    // doesn't try to generate during the benchmark session itself.
+    // The codegen model still TRAINS on benchmark solutions either way.
     {
         let codegen_loss: f32 = db
             .get_state("codegen_loss_display")
@@ -188,14 +232,46 @@ pub async fn generate_solution(
                 problem.instructions.chars().take(500).collect::<String>(),
                 problem.test_code.chars().take(1000).collect::<String>(),
             );
-            // Only try with max 64 tokens (fast) — don't burn 512 tokens at ~1s each
-            match crate::codegen::generate(db, &codegen_prompt, 64) {
-                Some(local_code) if local_code.len() > 30 => {
-                    let _ = db.set_state("codegen_last_used", "1");
-                    tracing::info!(slug = %problem.slug, chars = local_code.len(), "Codegen: attempting local solution");
-                    return Ok(local_code);
+            let config = x402_model::DecodeConfig {
+                strategy: x402_model::Strategy::TopP {
+                    p: 0.95,
+                    temperature: 0.8,
+                },
+                max_tokens: 256,
+                max_time: Some(std::time::Duration::from_secs(60)),
+                num_candidates: LOCAL_CANDIDATES,
+                seed: chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0) as u64,
+                ..Default::default()
+            };
+            // Decoding takes up to a minute of CPU: keep it off the runtime.
+            let candidates = match crate::codegen::load_decoder(db) {
+                Some(decoder) => {
+                    let decoding = tokio::task::spawn_blocking(move || {
+                        decoder.candidates(&codegen_prompt, &config)
+                    });
+                    tokio::select! {
+                        decoded = decoding => decoded.unwrap_or_default(),
+                        _ = cancel.cancelled() => return Err(Cancelled),
+                    }
+                }
+                None => Vec::new(),
+            };
+            for (i, local_code) in candidates.iter().enumerate() {
+                if local_code.len() <= 30 {
+                    continue;
+                }
+                let passed = pool.validate(problem, local_code, cancel).await?.passed;
+                tracing::info!(
+                    slug = %problem.slug,
+                    candidate = i,
+                    chars = local_code.len(),
+                    passed,
+                    "Codegen: local candidate tested"
+                );
+                if passed {
+                    let _ = db.set_state(&format!("codegen_last_used_{}", problem.slug), "1");
+                    return Ok(Ok(local_code.clone()));
                 }
-                _ => {}
             }
         }
     }
@@ -295,13 +371,24 @@ pub async fn generate_solution(
         prompt.push_str("## FAILED PREVIOUS ATTEMPTS — study these carefully\n\n");
         for (i, failure) in relevant_failures.iter().enumerate().take(2) {
             let sol_preview: String = failure.failed_solution.chars().take(1500).collect();
-            // Parse test output to extract specific failing tests and assertions
-            let focused_errors = parse_test_failures(&failure.error_output);
-            let err_section = if focused_errors.is_empty() {
-                let raw: String = failure.error_output.chars().take(500).collect();
-                format!("Raw error:\n```\n{raw}\n```")
-            } else {
-                format!("Failing tests:\n{focused_errors}")
+            // Prefer the structured report; older peers only send text
+            let err_section = match &failure.report {
+                Some(report) if !report.compiled() => {
+                    format!("Compile errors:\n```\n{}\n```", report.summary())
+                }
+                Some(report) if report.failed() > 0 => {
+                    format!("Failing tests:\n{}", report.summary())
+                }
+                _ => {
+                    // Parse test output to extract specific failing tests and assertions
+                    let focused_errors = parse_test_failures(&failure.error_output);
+                    if focused_errors.is_empty() {
+                        let raw: String = failure.error_output.chars().take(500).collect();
+                        format!("Raw error:\n```\n{raw}\n```")
+                    } else {
+                        format!("Failing tests:\n{focused_errors}")
+                    }
+                }
             };
             prompt.push_str(&format!(
                 "### Attempt {} (by {})\n```rust\n{}\n```\n{}\n\n",
@@ -324,13 +411,13 @@ pub async fn generate_solution(
          Output ONLY the complete src/lib.rs implementation. No markdown fences."
     ));
 
-    let response = llm
-        .think(&system, &prompt)
-        .await
-        .map_err(|e| format!("LLM generation failed: {e}"))?;
-
-    let cleaned = strip_code_blocks(&response);
-    Ok(cleaned)
+    let response = tokio::select! {
+        response = llm.think(&system, &prompt) => response,
+        _ = cancel.cancelled() => return Err(Cancelled),
+    };
+    Ok(response
+        .map(|response| strip_code_blocks(&response))
+        .map_err(|e| format!("LLM generation failed: {e}")))
 }
 
 /// Request for peer review of a benchmark solution.
@@ -442,142 +529,25 @@ pub async fn request_peer_review(peer_url: &str, req: &ReviewRequest) -> Option<
     resp.json::<ReviewResponse>().await.ok()
 }
 
-/// Validate a solution by creating a temp Cargo project and running `cargo test`.
+/// Validate a solution by running `cargo test` on it in the sandbox.
 /// Returns (passed, error_output).
+///
+/// The solution, tests and manifest are LLM- or peer-supplied, so each
+/// attempt runs in its own sandbox: no network, a read-only toolchain, a
+/// private tmpfs workspace and CPU, memory and process limits. Attempts go
+/// through the shared [`ValidationPool`], which bounds concurrency and reuses
+/// precompiled dependencies.
 pub async fn validate_solution(
     problem: &BenchmarkProblem,
     solution: &str,
     _workspace_root: &str,
 ) -> (bool, String) {
-    let test_dir = format!("/tmp/bench_{}", problem.slug);
-
-    // Create temp Cargo project
-    let setup = async {
-        // Clean up any previous run of THIS exercise (not the shared target)
-        let _ = tokio::fs::remove_dir_all(&test_dir).await;
-        tokio::fs::create_dir_all(format!("{test_dir}/src")).await?;
-        tokio::fs::create_dir_all(format!("{test_dir}/tests")).await?;
-
-        // Write Cargo.toml — use the provided one if available, fall back to minimal
-        let cargo_toml = if !problem.cargo_toml.is_empty() {
-            problem.cargo_toml.clone()
-        } else {
-            // Use the exercise slug as the crate name — tests import `use {slug}::*`
-            format!(
-                "[package]\n\
-                 name = \"{slug}\"\n\
-                 version = \"0.1.0\"\n\
-                 edition = \"2021\"\n",
-                slug = problem.slug
-            )
-        };
-        tokio::fs::write(format!("{test_dir}/Cargo.toml"), &cargo_toml).await?;
-
-        // Write solution as src/lib.rs
-        tokio::fs::write(format!("{test_dir}/src/lib.rs"), solution).await?;
-
-        // Write test file — remove #[ignore] attributes so all tests run
-        let test_code = problem.test_code.replace("#[ignore]", "");
-        let test_slug = problem.slug.replace('-', "_");
-        tokio::fs::write(format!("{test_dir}/tests/{test_slug}.rs"), &test_code).await?;
-
-        Ok::<(), std::io::Error>(())
-    };
-
-    if let Err(e) = setup.await {
-        return (false, format!("Setup failed: {e}"));
-    }
-
-    // Run cargo test with shared target dir (deps compile once across all exercises)
-    let output = tokio::time::timeout(
-        // Use spawn_blocking with std::process::Command + explicit kill.
-        // tokio::process::Command + tokio::time::timeout does NOT reliably
-        // kill cargo test — the timeout fires but the process keeps running,
-        // blocking the entire thinking loop for 60+ minutes.
-        std::time::Duration::from_secs(120),
-        tokio::task::spawn_blocking({
-            let test_dir = test_dir.clone();
-            move || {
-                let child = std::process::Command::new("cargo")
-                    .arg("test")
-                    .arg("--manifest-path")
-                    .arg(format!("{test_dir}/Cargo.toml"))
-                    .env("CARGO_TARGET_DIR", BENCHMARK_TARGET_DIR)
-                    .stdout(std::process::Stdio::piped())
-                    .stderr(std::process::Stdio::piped())
-                    .spawn()?;
-
-                // Wait with timeout — KILL the process if it takes too long.
-                // std::process::Child::wait() blocks, so use a watchdog thread.
-                let pid = child.id();
-                let deadline = std::time::Instant::now() + std::time::Duration::from_secs(90);
-                let killed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-                let killed2 = killed.clone();
-
-                // Watchdog: kill the process after 90s
-                std::thread::spawn(move || {
-                    let remaining = deadline.saturating_duration_since(std::time::Instant::now());
-                    std::thread::sleep(remaining);
-                    // Send SIGKILL via kill command (works on Linux)
-                    let _ = std::process::Command::new("kill")
-                        .args(["-9", &pid.to_string()])
-                        .output();
-                    killed2.store(true, std::sync::atomic::Ordering::Relaxed);
-                });
-
-                let output = child.wait_with_output();
-                if killed.load(std::sync::atomic::Ordering::Relaxed) {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::TimedOut,
-                        "cargo test killed after 90s",
-                    ))
-                } else {
-                    output
-                }
-            }
-        }),
-    )
-    .await;
-
-    // Clean up exercise dir (keep shared target for next exercise)
-    let _ = tokio::fs::remove_dir_all(&test_dir).await;
-
-    match output {
-        Ok(Ok(Ok(out))) => {
-            let stdout = String::from_utf8_lossy(&out.stdout).to_string();
-            let stderr = String::from_utf8_lossy(&out.stderr).to_string();
-
-            if out.status.success() {
-                (true, String::new())
-            } else {
-                let error = if stderr.contains("error[E") {
-                    stderr
-                        .lines()
-                        .filter(|l| l.contains("error") || l.contains("-->"))
-                        .take(10)
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                } else if stdout.contains("FAILED") {
-                    stdout.chars().take(500).collect()
-                } else {
-                    format!(
-                        "stderr: {}\nstdout: {}",
-                        stderr.chars().take(300).collect::<String>(),
-                        stdout.chars().take(200).collect::<String>()
-                    )
-                };
-                (false, error)
-            }
-        }
-        Ok(Ok(Err(e))) => {
-            if e.kind() == std::io::ErrorKind::TimedOut {
-                (false, "cargo test killed after 90s".into())
-            } else {
-                (false, format!("exec error: {e}"))
-            }
-        }
-        Ok(Err(_)) => (false, "spawn_blocking panicked".into()),
-        Err(_) => (false, "outer timeout (120s)".into()),
+    match ValidationPool::global()
+        .validate(problem, solution, &CancelToken::new())
+        .await
+    {
+        Ok(v) => (v.passed, v.error_output),
+        Err(cancelled) => (false, cancelled.to_string()),
     }
 }
 
@@ -631,12 +601,14 @@ pub fn get_peer_url(db: &SoulDatabase) -> Option<String> {
 }
 
 /// Record a single benchmark run.
+#[allow(clippy::too_many_arguments)]
 fn record_run(
     db: &SoulDatabase,
     problem: &BenchmarkProblem,
     passed: bool,
     solution: &str,
     error: &str,
+    report: Option<TestReport>,
     total_ms: u64,
     task_id: &str,
 ) {
@@ -649,6 +621,8 @@ fn record_run(
         error_output: error.chars().take(500).collect(),
         total_ms,
         created_at: chrono::Utc::now().timestamp(),
+        pack: problem.pack.clone(),
+        report,
     };
 
     if let Err(e) = db.insert_benchmark_run(&run) {
@@ -661,6 +635,7 @@ fn update_score(
     db: &SoulDatabase,
     weighted_score: f64,
     raw_rate: f64,
+    partial_credit: f64,
     attempted: u32,
     passed: u32,
     measured_at: i64,
@@ -670,8 +645,11 @@ fn update_score(
         raw_pass_rate: 0.0,
         problems_attempted: 0,
         problems_passed: 0,
+        partial_credit: 0.0,
         measured_at: 0,
         history: Vec::new(),
+        seen: None,
+        unseen: None,
     });
 
     // Push previous score to history (if it had data)
@@ -689,6 +667,7 @@ fn update_score(
 
     score.pass_at_1 = weighted_score;
     score.raw_pass_rate = raw_rate;
+    score.partial_credit = partial_credit;
     score.problems_attempted = attempted;
     score.problems_passed = passed;
     score.measured_at = measured_at;
@@ -731,6 +710,14 @@ pub fn load_score(db: &SoulDatabase) -> Option<BenchmarkScore> {
         .and_then(|s| serde_json::from_str(&s).ok())
 }
 
+/// Load the current Opus benchmark score.
+pub fn load_opus_score(db: &SoulDatabase) -> Option<BenchmarkScore> {
+    db.get_state("opus_benchmark_score")
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str(&s).ok())
+}
+
 /// Strip markdown code blocks from LLM output.
 fn strip_code_blocks(s: &str) -> String {
     let s = s.trim();
@@ -814,12 +801,155 @@ pub const DEFAULT_BENCHMARK_INTERVAL: u64 = 5;
 /// 10 × 70s = ~700s = ~12 min. Tight but usually completes within timeout.
 pub const DEFAULT_SAMPLE_SIZE: usize = 10;
 
+/// Cancels the running Opus session, if any.
+static SESSION_CANCEL: std::sync::Mutex<Option<CancelToken>> = std::sync::Mutex::new(None);
+
+/// Cancel the running benchmark session: queued validations are dropped,
+/// running sandboxes are killed and the session records nothing. Returns
+/// whether a session was running.
+pub fn cancel_benchmark_session() -> bool {
+    match SESSION_CANCEL.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Registers a session's token in [`SESSION_CANCEL`] until dropped, so a
+/// session that times out or panics doesn't leave a stale token behind.
+struct ActiveSession(CancelToken);
+
+impl ActiveSession {
+    fn start() -> Self {
+        let cancel = CancelToken::new();
+        *SESSION_CANCEL.lock().unwrap_or_else(|e| e.into_inner()) = Some(cancel.clone());
+        Self(cancel)
+    }
+}
+
+impl Drop for ActiveSession {
+    fn drop(&mut self) {
+        SESSION_CANCEL
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+}
+
+/// Outcome of solving and validating one problem, before it is recorded.
+struct Attempt {
+    success: bool,
+    error_output: String,
+    /// Report of the last validation.
+    report: TestReport,
+    last_solution: String,
+    retry_count: u32,
+    elapsed_ms: u64,
+}
+
+/// Generate, validate and (once) retry one problem. `Ok(Err(_))` is a
+/// generation failure.
+async fn attempt_problem(
+    llm: &LlmClient,
+    db: &SoulDatabase,
+    problem: &BenchmarkProblem,
+    peer_failures: &[SharedFailure],
+    pool: &ValidationPool,
+    cancel: &CancelToken,
+) -> Result<Result<Attempt, String>, Cancelled> {
+    let task_id = format!("opus/{}", problem.slug);
+
+    // Load own past failures for this problem
+    let own_failures: Vec<SharedFailure> = db
+        .get_all_benchmark_runs()
+        .unwrap_or_default()
+        .iter()
+        .filter(|r| !r.passed && r.task_id == task_id && !r.generated_solution.is_empty())
+        .take(2)
+        .map(|r| SharedFailure {
+            task_id: r.task_id.clone(),
+            entry_point: r.entry_point.clone(),
+            failed_solution: r.generated_solution.clone(),
+            error_output: r.error_output.clone(),
+            attempted_by: "self (previous attempt)".to_string(),
+            report: r.report.clone(),
+        })
+        .collect();
+
+    let mut all_failures = own_failures;
+    all_failures.extend(
+        peer_failures
+            .iter()
+            .filter(|f| f.task_id == task_id)
+            .cloned(),
+    );
+
+    // Generate solution
+    let solution = match generate_solution(llm, db, problem, &all_failures, pool, cancel).await? {
+        Ok(s) => s,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    // Validate via cargo test with self-play retries
+    let start = std::time::Instant::now();
+    let Validation {
+        passed: mut success,
+        mut error_output,
+        mut report,
+    } = pool.validate(problem, &solution, cancel).await?;
+
+    // Only 1 retry for Opus — 3 retries made tiers 1-5 trivially easy.
+    // One retry catches compilation typos; more than that inflates scores.
+    let max_retries = 1;
+    let mut retry_count = 0;
+    let mut last_solution = solution.clone();
+    let mut retry_context = all_failures.clone();
+    while !success && !error_output.is_empty() && retry_count < max_retries {
+        retry_count += 1;
+        tracing::info!(slug = %problem.slug, retry = retry_count, "Opus: retrying");
+        retry_context.push(SharedFailure {
+            task_id: task_id.clone(),
+            entry_point: problem.slug.clone(),
+            failed_solution: last_solution.clone(),
+            error_output: error_output.clone(),
+            attempted_by: format!("self (retry {})", retry_count),
+            report: Some(report.clone()),
+        });
+        if let Ok(retry_solution) =
+            generate_solution(llm, db, problem, &retry_context, pool, cancel).await?
+        {
+            let retry = pool.validate(problem, &retry_solution, cancel).await?;
+            report = retry.report;
+            if retry.passed {
+                success = true;
+                error_output = String::new();
+            } else {
+                error_output = retry.error_output;
+                last_solution = retry_solution;
+            }
+        } else {
+            break;
+        }
+    }
+
+    Ok(Ok(Attempt {
+        success,
+        error_output,
+        report,
+        last_solution,
+        retry_count,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    }))
+}
+
 /// Run a benchmark session using Opus IQ problems (embedded, no network).
 /// Solve via LLM, validate via cargo test, record, train brain.
 pub async fn run_opus_benchmark_session(
     llm: &LlmClient,
     db: &SoulDatabase,
-    workspace_root: &str,
+    _workspace_root: &str,
     sample_size: usize,
 ) -> Result<f64, String> {
     tracing::info!(
@@ -827,35 +957,21 @@ pub async fn run_opus_benchmark_session(
         "Starting Opus IQ benchmark session"
     );
 
-    // Pre-flight: check disk space — cargo test needs ~500MB for compilation
-    // Clean any stale benchmark artifacts first
-    let _ = tokio::fs::remove_dir_all(BENCHMARK_TARGET_DIR).await;
-    let disk_ok = tokio::process::Command::new("df")
-        .args(["--output=pcent", "/tmp"])
-        .output()
-        .await
-        .map(|o| {
-            let s = String::from_utf8_lossy(&o.stdout);
-            let pct: u64 = s
-                .lines()
-                .last()
-                .unwrap_or("0")
-                .trim()
-                .trim_end_matches('%')
-                .parse()
-                .unwrap_or(0);
-            pct < 90
-        })
-        .unwrap_or(true); // if df fails, try anyway
-    if !disk_ok {
-        return Err("Disk usage above 90% — skipping benchmark to avoid hang".into());
-    }
+    // Pre-flight: every solution is tested in the sandbox. Without one,
+    // every run would fail and drag the score down for no reason.
+    sandbox::mode().map_err(|e| e.to_string())?;
 
-    let problems = crate::opus_bench::load_embedded_problems();
+    // Embedded Opus problems plus any packs in SOUL_PROBLEM_PACKS
+    let problems = crate::problem_pack::load_problems();
     if problems.is_empty() {
         return Err("No Opus benchmark problems loaded".into());
     }
 
+    crate::problem_pack::migrate_renamed_slugs(db, &problems);
+
+    // Rotate the held-out split if due and re-check it for contamination
+    let split = crate::contamination::refresh(db, &problems);
+
     // Build set of already-solved problems to prioritize unsolved
     let all_runs = db.get_all_benchmark_runs().unwrap_or_default();
     let solved_slugs: std::collections::HashSet<String> = all_runs
@@ -932,6 +1048,30 @@ pub async fn run_opus_benchmark_session(
             }
         }
 
+        // Phase 1b: reserve a quarter of the sample for unseen held-out
+        // problems, those not yet measured this rotation first, so the
+        // unseen score covers the whole held-out set before it rotates.
+        let measured: std::collections::HashSet<&str> = all_runs
+            .iter()
+            .filter(|r| r.created_at >= split.created_at)
+            .map(|r| r.entry_point.as_str())
+            .collect();
+        let unseen_selected = selected.iter().filter(|p| split.is_unseen(&p.slug)).count();
+        let mut unseen: Vec<&BenchmarkProblem> = problems
+            .iter()
+            .filter(|p| split.is_unseen(&p.slug) && !selected.iter().any(|s| s.slug == p.slug))
+            .collect();
+        unseen.sort_by_key(|p| {
+            (
+                measured.contains(p.slug.as_str()),
+                crate::model_eval::fnv1a(format!("{seed}:{}", p.slug).as_bytes()),
+            )
+        });
+        let unseen_slots = (sample_size / 4).max(1).saturating_sub(unseen_selected);
+        for p in unseen.into_iter().take(unseen_slots) {
+            selected.push(p.clone());
+        }
+
         // Phase 2: fill remaining slots from unsolved NON-STUCK problems, easier first
         let selected_slugs: std::collections::HashSet<String> =
             selected.iter().map(|p| p.slug.clone()).collect();
@@ -981,6 +1121,7 @@ pub async fn run_opus_benchmark_session(
 
     let mut total_weight = 0.0f64;
     let mut earned_weight = 0.0f64;
+    let mut earned_partial = 0.0f64;
     let mut passed = 0u32;
     let mut attempted = 0u32;
     let now = chrono::Utc::now().timestamp();
@@ -999,39 +1140,49 @@ pub async fn run_opus_benchmark_session(
     // Load peer failures for collaborative solving
     let peer_failures = load_peer_failures(db);
 
-    for problem in &sample {
+    // Solve and validate up to `workers` problems at once, then record the
+    // outcomes in sample order. Cancelling the session drops queued
+    // attempts and kills running sandboxes.
+    let pool = ValidationPool::global();
+    let session = ActiveSession::start();
+    let cancel = session.0.clone();
+    let outcomes = async {
+        let warmed = pool.warm(&sample, &cancel).await?;
+        if warmed > 0 {
+            tracing::info!(warmed, "Opus: dependency caches ready");
+        }
+        let attempts: Vec<_> = sample
+            .iter()
+            .map(|problem| attempt_problem(llm, db, problem, &peer_failures, pool, &cancel))
+            .collect();
+        let attempts = futures::stream::iter(attempts)
+            .buffered(pool.workers())
+        .collect::<Vec<_>>()
+        .await;
+        attempts.into_iter().collect::<Result<Vec<_>, Cancelled>>()
+    }
+    .await;
+    drop(session);
+    let outcomes = outcomes.map_err(|_| {
+        tracing::warn!("Opus: benchmark session cancelled — nothing recorded");
+        "Benchmark session cancelled".to_string()
+    })?;
+
+    for (problem, outcome) in sample.iter().zip(outcomes) {
         attempted += 1;
         let weight = difficulty_weight(&problem.difficulty);
         total_weight += weight;
         let task_id = format!("opus/{}", problem.slug);
 
-        // Load own past failures for this problem
-        let own_failures: Vec<SharedFailure> = db
-            .get_all_benchmark_runs()
-            .unwrap_or_default()
-            .iter()
-            .filter(|r| !r.passed && r.task_id == task_id && !r.generated_solution.is_empty())
-            .take(2)
-            .map(|r| SharedFailure {
-                task_id: r.task_id.clone(),
-                entry_point: r.entry_point.clone(),
-                failed_solution: r.generated_solution.clone(),
-                error_output: r.error_output.clone(),
-                attempted_by: "self (previous attempt)".to_string(),
-            })
-            .collect();
-
-        let mut all_failures = own_failures;
-        all_failures.extend(
-            peer_failures
-                .iter()
-                .filter(|f| f.task_id == task_id)
-                .cloned(),
-        );
-
-        // Generate solution
-        let solution = match generate_solution(llm, db, problem, &all_failures).await {
-            Ok(s) => s,
+        let Attempt {
+            success,
+            error_output,
+            report,
+            last_solution,
+            retry_count,
+            elapsed_ms,
+        } = match outcome {
+            Ok(attempt) => attempt,
             Err(e) => {
                 let is_api_error = e.contains("429")
                     || e.contains("quota")
@@ -1045,59 +1196,23 @@ pub async fn run_opus_benchmark_session(
                     tracing::warn!(slug = %problem.slug, error = %e, "Opus: LLM API error — NOT counting");
                 } else {
                     tracing::warn!(slug = %problem.slug, tier = %problem.difficulty, error = %e, "Opus: gen failed");
-                    record_run(db, problem, false, "", &e, 0, &task_id);
+                    record_run(db, problem, false, "", &e, None, 0, &task_id);
                 }
                 continue;
             }
         };
 
-        // Validate via cargo test with self-play retries
-        let start = std::time::Instant::now();
-        let (mut success, mut error_output) =
-            validate_solution(problem, &solution, workspace_root).await;
-
-        // Only 1 retry for Opus — 3 retries made tiers 1-5 trivially easy.
-        // One retry catches compilation typos; more than that inflates scores.
-        let max_retries = 1;
-        let mut retry_count = 0;
-        let mut last_solution = solution.clone();
-        let mut retry_context = all_failures.clone();
-        while !success && !error_output.is_empty() && retry_count < max_retries {
-            retry_count += 1;
-            tracing::info!(slug = %problem.slug, retry = retry_count, "Opus: retrying");
-            retry_context.push(SharedFailure {
-                task_id: task_id.clone(),
-                entry_point: problem.slug.clone(),
-                failed_solution: last_solution.clone(),
-                error_output: error_output.clone(),
-                attempted_by: format!("self (retry {})", retry_count),
-            });
-            if let Ok(retry_solution) = generate_solution(llm, db, problem, &retry_context).await {
-                let (retry_ok, retry_err) =
-                    validate_solution(problem, &retry_solution, workspace_root).await;
-                if retry_ok {
-                    success = true;
-                    error_output = String::new();
-                } else {
-                    error_output = retry_err;
-                    last_solution = retry_solution;
-                }
-            } else {
-                break;
-            }
-        }
-
-        let elapsed_ms = start.elapsed().as_millis() as u64;
-
         // Check if codegen was used for this solution
+        let codegen_key = format!("codegen_last_used_{}", problem.slug);
         let codegen_used = db
-            .get_state("codegen_last_used")
+            .get_state(&codegen_key)
             .ok()
             .flatten()
             .map(|v| v == "1")
             .unwrap_or(false);
-        let _ = db.set_state("codegen_last_used", "0"); // Reset flag
+        let _ = db.set_state(&codegen_key, "0"); // Reset flag
 
+        earned_partial += weight * if success { 1.0 } else { report.partial_credit() };
         if success {
             passed += 1;
             earned_weight += weight;
@@ -1147,7 +1262,11 @@ pub async fn run_opus_benchmark_session(
         // Save per-problem failure context for next attempt (persistent across sessions)
         if !success && !error_output.is_empty() {
             let key = format!("problem_context_{}", problem.slug);
-            let failures = parse_test_failures(&error_output);
+            let failures = if report.is_empty() {
+                parse_test_failures(&error_output)
+            } else {
+                report.summary()
+            };
             let ctx = if failures.is_empty() {
                 format!(
                     "Last error ({}): {}",
@@ -1172,7 +1291,9 @@ pub async fn run_opus_benchmark_session(
             retry_number: 0,
             had_peer_context: !peer_failures.is_empty(),
             had_peer_review: false,
-            compiled: success || !error_output.contains("error[E"),
+            compiled: success || (report.compiled() && !error_output.contains("error[E")),
+            test_pass_rate: if success { 1.0 } else { report.partial_credit() as f32 },
+            error_codes: report.errors_by_code().into_keys().collect(),
             elo_rating: current_elo,
             pass_at_1: current_pass_at_1,
             peer_count: 0,
@@ -1185,13 +1306,10 @@ pub async fn run_opus_benchmark_session(
             success,
             &last_solution,
             &error_output,
+            Some(report),
             elapsed_ms,
             &task_id,
         );
-
-        // DON'T clean shared target dir between problems — deps compile once,
-        // then each exercise only recompiles its own lib + tests (~5s vs ~5min).
-        // The target dir is cleaned at session start and end only.
     }
 
     // Guard: if no problems were actually attempted (all API errors), skip scoring.
@@ -1214,15 +1332,29 @@ pub async fn run_opus_benchmark_session(
         0.0
     };
 
+    let partial_credit = if total_weight > 0.0 {
+        earned_partial / total_weight * 100.0
+    } else {
+        0.0
+    };
+
     // Store Opus score (also as the primary benchmark score)
-    update_opus_score(db, weighted_score, raw_rate, attempted, passed, now);
-    update_score(db, weighted_score, raw_rate, attempted, passed, now);
+    update_opus_score(db, weighted_score, raw_rate, partial_credit, attempted, passed, now);
+    update_score(db, weighted_score, raw_rate, partial_credit, attempted, passed, now);
+
+    // Seen vs unseen over this rotation's runs (the latest per problem)
+    let rotation_runs: Vec<BenchmarkRun> = db
+        .get_all_benchmark_runs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|r| r.created_at >= split.created_at)
+        .collect();
+    let split_scores = crate::contamination::split_scores(&rotation_runs, &problems, &split);
+    store_split_scores(db, split_scores);
 
     // Compute IQ
     let iq = crate::opus_bench::weighted_score_to_iq(weighted_score);
 
-    let _ = tokio::fs::remove_dir_all(BENCHMARK_TARGET_DIR).await;
-
     // Codegen vs Gemini stats — THE metric for real learning
     let codegen_attempts: u64 = db
         .get_state("codegen_benchmark_attempts")
@@ -1245,6 +1377,9 @@ pub async fn run_opus_benchmark_session(
     tracing::info!(
         weighted = format!("{:.1}%", weighted_score),
         raw = format!("{:.1}%", raw_rate),
+        seen = format!("{:.1}%", split_scores.seen.pass_at_1),
+        unseen = format!("{:.1}%", split_scores.unseen.pass_at_1),
+        unseen_attempted = split_scores.unseen.attempted,
         iq = format!("{:.0}", iq),
         passed = passed,
         attempted = attempted,
@@ -1272,6 +1407,7 @@ fn update_opus_score(
     db: &SoulDatabase,
     weighted_score: f64,
     raw_rate: f64,
+    partial_credit: f64,
     attempted: u32,
     passed: u32,
     measured_at: i64,
@@ -1286,8 +1422,11 @@ fn update_opus_score(
             raw_pass_rate: 0.0,
             problems_attempted: 0,
             problems_passed: 0,
+            partial_credit: 0.0,
             measured_at: 0,
             history: Vec::new(),
+            seen: None,
+            unseen: None,
         });
 
     if score.problems_attempted > 0 {
@@ -1303,6 +1442,7 @@ fn update_opus_score(
 
     score.pass_at_1 = weighted_score;
     score.raw_pass_rate = raw_rate;
+    score.partial_credit = partial_credit;
     score.problems_attempted = attempted;
     score.problems_passed = passed;
     score.measured_at = measured_at;
@@ -1312,6 +1452,18 @@ fn update_opus_score(
     }
 }
 
+/// Attach seen/unseen scores to the stored Opus score.
+fn store_split_scores(db: &SoulDatabase, scores: SplitScores) {
+    let Some(mut score) = load_opus_score(db) else {
+        return;
+    };
+    score.seen = Some(scores.seen);
+    score.unseen = Some(scores.unseen);
+    if let Ok(json) = serde_json::to_string(&score) {
+        let _ = db.set_state("opus_benchmark_score", &json);
+    }
+}
+
 /// Format Opus IQ benchmark for prompt injection.
 pub fn opus_summary_for_prompt(db: &SoulDatabase) -> String {
     let score = match db
@@ -1327,8 +1479,13 @@ pub fn opus_summary_for_prompt(db: &SoulDatabase) -> String {
     let iq = crate::opus_bench::weighted_score_to_iq(score.pass_at_1);
 
     let mut lines = vec![format!(
-        "# Opus IQ Benchmark: {:.1}% weighted ({:.1}% raw, {}/{} problems) — IQ: {:.0}",
-        score.pass_at_1, score.raw_pass_rate, score.problems_passed, score.problems_attempted, iq
+        "# Opus IQ Benchmark: {:.1}% weighted ({:.1}% raw, {}/{} problems, {:.1}% of tests) — IQ: {:.0}",
+        score.pass_at_1,
+        score.raw_pass_rate,
+        score.problems_passed,
+        score.problems_attempted,
+        score.partial_credit,
+        iq
     )];
 
     lines.push(
@@ -1380,6 +1537,9 @@ pub struct SharedFailure {
     pub error_output: String,
     /// Who attempted it.
     pub attempted_by: String,
+    /// Failing tests and compile errors, when the attempt reached `cargo test`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<TestReport>,
 }
 
 /// Export failed attempts for peer sharing (collaborative solving).
@@ -1403,11 +1563,13 @@ pub fn export_failures(db: &SoulDatabase) -> Vec<SharedFailure> {
                     failed_solution: r.generated_solution.clone(),
                     error_output: r.error_output.clone(),
                     attempted_by: instance_id.clone(),
+                    report: r.report.clone(),
                 });
             // Update if this is a more recent failure
             if r.created_at > 0 {
                 entry.failed_solution = r.generated_solution.clone();
                 entry.error_output = r.error_output.clone();
+                entry.report = r.report.clone();
             }
         }
     }
@@ -1527,8 +1689,8 @@ pub async fn import_solutions(
     let already_imported: std::collections::HashSet<String> =
         existing.iter().map(|s| s.task_id.clone()).collect();
 
-    // Load embedded problems for test validation
-    let problems = crate::opus_bench::load_embedded_problems();
+    // Load problems for test validation
+    let problems = crate::problem_pack::load_problems();
     let problem_map: std::collections::HashMap<String, &BenchmarkProblem> = problems
         .iter()
         .map(|p| (format!("opus/{}", p.slug), p))
@@ -1576,10 +1738,10 @@ pub async fn import_solutions(
 }
 
 /// Compute the collective score: our solutions + verified peer solutions.
-/// Uses the total problem count from the embedded problem set.
+/// Uses the total problem count from the loaded problem packs.
 pub fn collective_score(db: &SoulDatabase) -> (f64, u32, u32) {
     let all_solutions = export_solutions(db);
-    let total_problems = crate::opus_bench::load_embedded_problems().len() as u32;
+    let total_problems = crate::problem_pack::load_problems().len() as u32;
 
     let unique_solved: std::collections::HashSet<&str> =
         all_solutions.iter().map(|s| s.task_id.as_str()).collect();
@@ -1646,6 +1808,83 @@ fn load_benchmark_hints(db: &SoulDatabase) -> String {
     String::new()
 }
 
+const HINT_STRING_STR: &str =
+    "String/&str type mismatch — check test expectations for owned vs borrowed strings";
+const HINT_MISSING_ITEM: &str = "Missing imports or pub exports — ensure all types/functions used by tests are publicly accessible";
+const HINT_MISSING_TRAIT: &str = "Missing trait implementation — check what traits the tests expect (Display, From, Iterator, etc.)";
+const HINT_TYPE_MISMATCH: &str =
+    "Type mismatch — carefully read the function signature the tests expect";
+const HINT_OVERFLOW: &str =
+    "Integer overflow/underflow — use checked arithmetic or handle edge cases";
+const HINT_BORROW: &str = "Borrow checker issue — prefer owned types (String, Vec) in return positions unless tests require references";
+const HINT_LOGIC: &str = "Logic error — the code compiled but produced wrong output. Trace through the test cases manually";
+
+/// Hints for a failure with a [`TestReport`]: by rustc error code, or by
+/// the failing tests' panic messages.
+fn report_hints(report: &TestReport) -> Vec<&'static str> {
+    let mut hints = Vec::new();
+    for error in &report.compile_errors {
+        let hint = match error.code.as_str() {
+            "E0308"
+                if error.rendered.contains("`&str`") && error.rendered.contains("`String`") =>
+            {
+                HINT_STRING_STR
+            }
+            "E0308" => HINT_TYPE_MISMATCH,
+            "E0412" | "E0425" | "E0432" | "E0433" | "E0603" => HINT_MISSING_ITEM,
+            "E0277" => HINT_MISSING_TRAIT,
+            "E0106" | "E0382" | "E0499" | "E0502" | "E0505" | "E0506" | "E0515" | "E0597"
+            | "E0716" => HINT_BORROW,
+            _ => continue,
+        };
+        hints.push(hint);
+    }
+    for test in report
+        .tests
+        .iter()
+        .filter(|t| t.outcome == TestOutcome::Failed)
+    {
+        if test.message.contains("overflow") || test.message.contains("attempt to") {
+            hints.push(HINT_OVERFLOW);
+        } else {
+            hints.push(HINT_LOGIC);
+        }
+    }
+    hints.sort_unstable();
+    hints.dedup();
+    hints
+}
+
+/// Hints from raw error text (runs recorded before test reports existed).
+fn text_hints(err: &str) -> Vec<&'static str> {
+    let mut hints = Vec::new();
+    // Categorize common Rust errors
+    if err.contains("expected `&str`, found `String`")
+        || err.contains("expected `String`, found `&str`")
+    {
+        hints.push(HINT_STRING_STR);
+    }
+    if err.contains("not found in this scope") || err.contains("cannot find") {
+        hints.push(HINT_MISSING_ITEM);
+    }
+    if err.contains("trait bound") && err.contains("not satisfied") {
+        hints.push(HINT_MISSING_TRAIT);
+    }
+    if err.contains("mismatched types") {
+        hints.push(HINT_TYPE_MISMATCH);
+    }
+    if err.contains("overflow") || err.contains("attempt to") {
+        hints.push(HINT_OVERFLOW);
+    }
+    if err.contains("borrow") || err.contains("lifetime") {
+        hints.push(HINT_BORROW);
+    }
+    if err.contains("thread 'main' panicked") || err.contains("assertion") {
+        hints.push(HINT_LOGIC);
+    }
+    hints
+}
+
 /// Analyze recent benchmark failures and extract common error patterns.
 /// Called after each benchmark session to update the hint cache.
 pub fn update_benchmark_hints(db: &SoulDatabase) {
@@ -1662,34 +1901,12 @@ pub fn update_benchmark_hints(db: &SoulDatabase) {
     let mut patterns: std::collections::HashMap<&str, u32> = std::collections::HashMap::new();
 
     for f in &failures {
-        let err = &f.error_output;
-        // Categorize common Rust errors
-        if err.contains("expected `&str`, found `String`")
-            || err.contains("expected `String`, found `&str`")
-        {
-            *patterns.entry("String/&str type mismatch — check test expectations for owned vs borrowed strings").or_default() += 1;
-        }
-        if err.contains("not found in this scope") || err.contains("cannot find") {
-            *patterns.entry("Missing imports or pub exports — ensure all types/functions used by tests are publicly accessible").or_default() += 1;
-        }
-        if err.contains("trait bound") && err.contains("not satisfied") {
-            *patterns.entry("Missing trait implementation — check what traits the tests expect (Display, From, Iterator, etc.)").or_default() += 1;
-        }
-        if err.contains("mismatched types") {
-            *patterns
-                .entry("Type mismatch — carefully read the function signature the tests expect")
-                .or_default() += 1;
-        }
-        if err.contains("overflow") || err.contains("attempt to") {
-            *patterns
-                .entry("Integer overflow/underflow — use checked arithmetic or handle edge cases")
-                .or_default() += 1;
-        }
-        if err.contains("borrow") || err.contains("lifetime") {
-            *patterns.entry("Borrow checker issue — prefer owned types (String, Vec) in return positions unless tests require references").or_default() += 1;
-        }
-        if err.contains("thread 'main' panicked") || err.contains("assertion") {
-            *patterns.entry("Logic error — the code compiled but produced wrong output. Trace through the test cases manually").or_default() += 1;
+        let hints = match &f.report {
+            Some(report) if !report.is_empty() => report_hints(report),
+            _ => text_hints(&f.error_output),
+        };
+        for hint in hints {
+            *patterns.entry(hint).or_default() += 1;
         }
     }
 
@@ -204,40 +204,12 @@ impl Brain {
         let h2 = relu(&z2);
         let output = add_bias(&matmul(&h2, &self.w3, HIDDEN_SIZE, OUTPUT_SIZE), &self.b3);
 
-        // ── Compute targets ──
-        let mut target = [0.0f32; OUTPUT_SIZE];
-        // Success target
-        target[0] = if example.success { 1.0 } else { 0.0 };
-        // Error category target (one-hot at index 1..12)
-        if let Some(ref cat) = example.error_category {
-            target[1 + error_category_to_idx(cat)] = 1.0;
-        } else {
-            target[1 + 10] = 1.0; // Unknown
-        }
-        // Capability target (1.0 if success for that capability)
-        let cap_idx = capability_to_idx(&example.capability);
-        target[12 + cap_idx] = if example.success { 1.0 } else { 0.0 };
-
-        // ── Compute loss ──
+        // ── Compute targets and loss ──
+        let target = example_targets(example);
+        let total_loss = output_loss(&output, &target);
         let pred_success = sigmoid(output[0]);
-        let success_loss = binary_cross_entropy(pred_success, target[0]);
-
         let error_probs = softmax(&output[1..12]);
-        let error_loss: f32 = error_probs
-            .iter()
-            .zip(target[1..12].iter())
-            .map(|(p, t)| -t * (p + 1e-8).ln())
-            .sum();
-
         let cap_preds: Vec<f32> = output[12..23].iter().map(|&x| sigmoid(x)).collect();
-        let cap_loss: f32 = cap_preds
-            .iter()
-            .zip(target[12..23].iter())
-            .map(|(p, t)| binary_cross_entropy(*p, *t))
-            .sum::<f32>()
-            / 11.0;
-
-        let total_loss = success_loss + error_loss + cap_loss;
 
         // ── Backward pass ──
         // Output gradients
@@ -293,6 +265,22 @@ impl Brain {
         total_loss
     }
 
+    /// Loss on an example without training (same objective as `train`).
+    pub fn loss(&self, example: &TrainingExample) -> f32 {
+        let input = &example.features;
+        assert_eq!(input.len(), INPUT_SIZE);
+        let h1 = relu(&add_bias(
+            &matmul(input, &self.w1, INPUT_SIZE, HIDDEN_SIZE),
+            &self.b1,
+        ));
+        let h2 = relu(&add_bias(
+            &matmul(&h1, &self.w2, HIDDEN_SIZE, HIDDEN_SIZE),
+            &self.b2,
+        ));
+        let output = add_bias(&matmul(&h2, &self.w3, HIDDEN_SIZE, OUTPUT_SIZE), &self.b3);
+        output_loss(&output, &example_targets(example))
+    }
+
     /// Train on a batch of examples.
     pub fn train_batch(&mut self, examples: &[TrainingExample]) -> f32 {
         let mut total_loss = 0.0;
@@ -363,6 +351,31 @@ impl Default for Brain {
     }
 }
 
+impl x402_model::Federated for Brain {
+    fn arch(&self) -> &'static str {
+        "brain"
+    }
+
+    fn train_steps(&self) -> u64 {
+        self.train_steps
+    }
+
+    fn tensors(&self) -> Vec<&[f32]> {
+        vec![&self.w1, &self.b1, &self.w2, &self.b2, &self.w3, &self.b3]
+    }
+
+    fn tensors_mut(&mut self) -> Vec<&mut [f32]> {
+        vec![
+            &mut self.w1,
+            &mut self.b1,
+            &mut self.w2,
+            &mut self.b2,
+            &mut self.w3,
+            &mut self.b3,
+        ]
+    }
+}
+
 // ── Feature encoding ─────────────────────────────────────────────────
 
 /// Encode a plan step into a feature vector for the brain.
@@ -646,6 +659,10 @@ pub struct BenchmarkAttemptContext {
     pub had_peer_review: bool,
     /// Did the solution compile? (false = compile error, true = either passed or logic error)
     pub compiled: bool,
+    /// Fraction of the problem's tests that passed (partial credit).
+    pub test_pass_rate: f32,
+    /// Distinct rustc error codes hit, e.g. `["E0308", "E0425"]`.
+    pub error_codes: Vec<String>,
     /// Current ELO rating
     pub elo_rating: f32,
     /// Current pass@1
@@ -732,6 +749,11 @@ pub fn benchmark_attempts_to_examples(
         // Feature 42: compiled successfully
         features[42] = if attempt.compiled { 1.0 } else { 0.0 };
 
+        // Feature 53: share of tests passed — separates near-misses from wrong approaches
+        features[53] = attempt.test_pass_rate.clamp(0.0, 1.0);
+        // Feature 54: distinct rustc error codes (normalized)
+        features[54] = (attempt.error_codes.len() as f32 / 5.0).min(1.0);
+
         // Features 49-52: problem slug hash (4 features from deterministic hash)
         // Gives the brain a unique per-problem fingerprint
         if !attempt.problem_slug.is_empty() {
@@ -1194,6 +1216,46 @@ fn step_to_capability(step: &PlanStep) -> Capability {
     }
 }
 
+// ── Loss ─────────────────────────────────────────────────────────────
+
+/// Output targets: success, one-hot error category (1..12), and the
+/// exercised capability's outcome (12..23).
+fn example_targets(example: &TrainingExample) -> [f32; OUTPUT_SIZE] {
+    let mut target = [0.0f32; OUTPUT_SIZE];
+    // Success target
+    target[0] = if example.success { 1.0 } else { 0.0 };
+    // Error category target (one-hot at index 1..12)
+    if let Some(ref cat) = example.error_category {
+        target[1 + error_category_to_idx(cat)] = 1.0;
+    } else {
+        target[1 + 10] = 1.0; // Unknown
+    }
+    // Capability target (1.0 if success for that capability)
+    let cap_idx = capability_to_idx(&example.capability);
+    target[12 + cap_idx] = if example.success { 1.0 } else { 0.0 };
+    target
+}
+
+/// Success BCE + error-category CE + mean capability BCE.
+fn output_loss(output: &[f32], target: &[f32; OUTPUT_SIZE]) -> f32 {
+    let success_loss = binary_cross_entropy(sigmoid(output[0]), target[0]);
+
+    let error_loss: f32 = softmax(&output[1..12])
+        .iter()
+        .zip(target[1..12].iter())
+        .map(|(p, t)| -t * (p + 1e-8).ln())
+        .sum();
+
+    let cap_loss: f32 = output[12..23]
+        .iter()
+        .zip(target[12..23].iter())
+        .map(|(&x, t)| binary_cross_entropy(sigmoid(x), *t))
+        .sum::<f32>()
+        / 11.0;
+
+    success_loss + error_loss + cap_loss
+}
+
 // ── Math utilities ───────────────────────────────────────────────────
 
 /// Xavier weight initialization.
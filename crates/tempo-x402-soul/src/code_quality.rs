@@ -10,20 +10,22 @@
 use crate::db::SoulDatabase;
 
 /// Load the code quality model from soul_state.
-/// If dimensions mismatch (architecture changed), reinitialize fresh.
+/// Accepts binary checkpoints and legacy JSON; if the architecture changed,
+/// reinitialize fresh.
 pub fn load_model(db: &SoulDatabase) -> x402_model::CodeQualityModel {
-    match db.get_state("code_quality_model").ok().flatten() {
-        Some(json) if !json.is_empty() => {
-            x402_model::CodeQualityModel::from_json(&json).unwrap_or_default()
+    use x402_model::Checkpointable;
+    match db.get_state_bytes("code_quality_model").ok().flatten() {
+        Some(bytes) if !bytes.is_empty() => {
+            x402_model::CodeQualityModel::load_bytes(&bytes).unwrap_or_default()
         }
         _ => x402_model::CodeQualityModel::new(),
     }
 }
 
-/// Save the code quality model to soul_state.
+/// Save the code quality model to soul_state as a binary checkpoint.
 pub fn save_model(db: &SoulDatabase, model: &x402_model::CodeQualityModel) {
-    let json = model.to_json();
-    if let Err(e) = db.set_state("code_quality_model", &json) {
+    use x402_model::Checkpointable;
+    if let Err(e) = db.set_state_bytes("code_quality_model", &model.to_checkpoint()) {
         tracing::warn!(error = %e, "Failed to save code quality model");
     }
 }
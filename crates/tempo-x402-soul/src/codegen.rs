@@ -50,6 +50,8 @@ fn walk_rs_files(dir: &std::path::Path, corpus: &mut String, depth: u32, max_byt
             || name_str == "tests"
             || name_str == "benches"
             || name_str == "examples"
+            // Benchmark problems: their tests must not leak into training
+            || name_str == "opus_bench"
         {
             continue;
         }
@@ -165,8 +167,10 @@ pub fn train_tokenizer(db: &SoulDatabase) {
 
     let mut corpus = String::new();
 
-    // Source 1: benchmark solutions (highest quality — verified by cargo test)
-    for sol in &solutions {
+    // Source 1: benchmark solutions (highest quality — verified by cargo test),
+    // except those to held-out problems
+    let split = crate::contamination::current(db);
+    for sol in solutions.iter().filter(|s| !split.excludes(s)) {
         if let Some(code) = sol.get("code").and_then(|v| v.as_str()) {
             corpus.push_str(code);
             corpus.push('\n');
@@ -208,22 +212,24 @@ pub fn train_tokenizer(db: &SoulDatabase) {
     );
 }
 
-/// Path for binary model weights (much faster than JSON in sled).
-/// At 55M params, JSON = 660MB, binary = 220MB. Binary also loads 10x faster.
+/// Path for the binary model checkpoint (much faster than JSON in sled).
+/// At 15M params, JSON ≈ 180MB, checkpoint = 60MB, and loading is a memcpy
+/// out of an mmap instead of a float parse.
 fn model_weights_path() -> std::path::PathBuf {
     let dir = std::env::var("SOUL_WORKSPACE_ROOT").unwrap_or_else(|_| "/tmp".to_string());
     std::path::Path::new(&dir).join("codegen_model.bin")
 }
 
-/// Load the code gen model — try binary file first, fall back to sled JSON.
+/// Load the code gen model — try the checkpoint file first, fall back to sled JSON.
+/// `codegen_model.bin` held JSON before checkpoints existed; `load_file`
+/// still accepts that, and the next save rewrites it as binary.
 pub fn load_model(db: &SoulDatabase) -> x402_model::codegen::CodeGenModel {
-    // Try binary file first (fast path for large models)
+    use x402_model::Checkpointable;
     let bin_path = model_weights_path();
     if bin_path.exists() {
-        if let Ok(json) = std::fs::read_to_string(&bin_path) {
-            if let Some(model) = x402_model::codegen::CodeGenModel::from_json(&json) {
-                return model;
-            }
+        match x402_model::codegen::CodeGenModel::load_file(&bin_path) {
+            Ok(model) => return model,
+            Err(e) => tracing::warn!(error = %e, "codegen: checkpoint rejected"),
         }
     }
 
@@ -236,19 +242,16 @@ pub fn load_model(db: &SoulDatabase) -> x402_model::codegen::CodeGenModel {
     }
 }
 
-/// Save the code gen model to a binary file (fast) + lightweight marker in sled.
+/// Save the code gen model to a checkpoint file (fast) + lightweight marker in sled.
 pub fn save_model(db: &SoulDatabase, model: &x402_model::codegen::CodeGenModel) {
-    let json = model.to_json();
+    use x402_model::Checkpointable;
     let bin_path = model_weights_path();
 
     // Save to file (fast, no sled size pressure)
-    if let Some(parent) = bin_path.parent() {
-        let _ = std::fs::create_dir_all(parent);
-    }
-    if let Err(e) = std::fs::write(&bin_path, &json) {
+    if let Err(e) = model.save_file(&bin_path) {
         tracing::warn!(error = %e, "Failed to save codegen model to file");
         // Fall back to sled
-        if let Err(e2) = db.set_state("codegen_model", &json) {
+        if let Err(e2) = db.set_state("codegen_model", &model.to_json()) {
             tracing::warn!(error = %e2, "Failed to save codegen model to sled");
         }
         return;
@@ -265,6 +268,135 @@ pub fn save_model(db: &SoulDatabase, model: &x402_model::codegen::CodeGenModel)
     let _ = db.set_state("codegen_model", &marker);
 }
 
+/// Weight precision for shipped/inference codegen weights: `SOUL_CODEGEN_QUANT`
+/// = `int8` (default), `f16`, or `off` to run inference on the f32 weights.
+fn quant_mode() -> Option<x402_model::QuantMode> {
+    match std::env::var("SOUL_CODEGEN_QUANT") {
+        Ok(v) => x402_model::QuantMode::parse(&v),
+        Err(_) => Some(x402_model::QuantMode::Int8),
+    }
+}
+
+/// Path for the quantized inference checkpoint.
+fn quantized_weights_path(mode: x402_model::QuantMode) -> std::path::PathBuf {
+    model_weights_path().with_extension(format!("{}.ckpt", mode.as_str()))
+}
+
+/// Held-out sequences for the quantization accuracy report. Failed benchmark
+/// attempts are never trained on, so they are real unseen Rust code.
+fn quant_held_out(db: &SoulDatabase, tok: &x402_model::bpe::BpeTokenizer) -> Vec<Vec<u32>> {
+    const SAMPLES: usize = 3;
+    const SAMPLE_TOKENS: usize = 32;
+    let solutions: Vec<serde_json::Value> = db
+        .get_state("codegen_solutions")
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let code =
+        |sol: &serde_json::Value| sol.get("code").and_then(|v| v.as_str()).map(str::to_string);
+    let failed: Vec<String> = solutions
+        .iter()
+        .filter(|s| s.get("passed").and_then(|v| v.as_bool()) == Some(false))
+        .filter_map(code)
+        .collect();
+    let pool = if failed.is_empty() {
+        solutions.iter().rev().filter_map(code).collect()
+    } else {
+        failed
+    };
+    pool.iter()
+        .take(SAMPLES)
+        .map(|code| {
+            let mut tokens = vec![x402_model::bpe::BOS_TOKEN];
+            tokens.extend(tok.encode(code));
+            tokens.truncate(SAMPLE_TOKENS);
+            tokens
+        })
+        .collect()
+}
+
+/// Quantize the freshly trained model for inference and record its accuracy
+/// delta against the f32 weights.
+fn ship_quantized(
+    db: &SoulDatabase,
+    model: &x402_model::codegen::CodeGenModel,
+    tok: &x402_model::bpe::BpeTokenizer,
+) {
+    use x402_model::Checkpointable;
+    let Some(mode) = quant_mode() else {
+        return;
+    };
+    let quantized = x402_model::QuantizedCodeGenModel::from_model(model, mode);
+    if let Err(e) = quantized.save_file(quantized_weights_path(mode)) {
+        tracing::warn!(error = %e, "codegen: failed to save quantized model");
+        return;
+    }
+    let report = quantized.accuracy_report(model, &quant_held_out(db, tok));
+    tracing::info!(
+        mode = mode.as_str(),
+        samples = report.samples,
+        compression = format!("{:.1}x", report.compression()),
+        top1_agreement = format!("{:.2}", report.top1_agreement),
+        logit_mse = format!("{:.6}", report.logit_mse),
+        "codegen: quantized model shipped"
+    );
+    if let Ok(json) = serde_json::to_string(&report) {
+        let _ = db.set_state("codegen_quant_report", &json);
+    }
+}
+
+/// Codegen weights used for generation: the quantized variant when one has
+/// been shipped, otherwise the f32 training weights.
+enum InferenceModel {
+    F32(x402_model::codegen::CodeGenModel),
+    Quantized(x402_model::QuantizedCodeGenModel),
+}
+
+impl InferenceModel {
+    fn load(db: &SoulDatabase) -> Self {
+        use x402_model::Checkpointable;
+        if let Some(mode) = quant_mode() {
+            let path = quantized_weights_path(mode);
+            if path.exists() {
+                match x402_model::QuantizedCodeGenModel::load_file(&path) {
+                    Ok(model) => return Self::Quantized(model),
+                    Err(e) => tracing::warn!(error = %e, "codegen: quantized checkpoint rejected"),
+                }
+            }
+        }
+        Self::F32(load_model(db))
+    }
+
+    fn train_steps(&self) -> u64 {
+        match self {
+            Self::F32(m) => m.train_steps,
+            Self::Quantized(m) => m.train_steps,
+        }
+    }
+
+    fn max_seq(&self) -> usize {
+        match self {
+            Self::F32(m) => m.max_seq,
+            Self::Quantized(m) => m.max_seq,
+        }
+    }
+
+    fn encode(&self, context: &[u32]) -> Vec<f32> {
+        match self {
+            Self::F32(m) => m.encode(context),
+            Self::Quantized(m) => m.encode(context),
+        }
+    }
+
+    fn decoder(&self) -> &dyn x402_model::IncrementalDecoder {
+        match self {
+            Self::F32(m) => m,
+            Self::Quantized(m) => m,
+        }
+    }
+}
+
 /// Train the code generation model on ALL available Rust code:
 /// 1. Cartridge training corpus (filesystem — loaded once)
 /// 2. Benchmark solutions (verified, high quality — weighted 3x)
@@ -284,15 +416,17 @@ pub fn train_model(db: &SoulDatabase) {
     // Examples WITHOUT context use the old train_step (backward compat).
     let mut examples: Vec<(Option<String>, String, u32)> = Vec::new();
 
-    // Source 1: benchmark solutions (verified — train 3x more on these)
+    // Source 1: benchmark solutions (verified — train 3x more on these).
+    // Solutions to held-out problems are kept for scoring, never trained on.
     let solutions: Vec<serde_json::Value> = db
         .get_state("codegen_solutions")
         .ok()
         .flatten()
         .and_then(|s| serde_json::from_str(&s).ok())
         .unwrap_or_default();
+    let split = crate::contamination::current(db);
 
-    for sol in &solutions {
+    for sol in solutions.iter().filter(|s| !split.excludes(s)) {
         if sol.get("passed").and_then(|v| v.as_bool()).unwrap_or(true) {
             if let Some(code) = sol.get("code").and_then(|v| v.as_str()) {
                 if code.len() >= 50 {
@@ -426,6 +560,7 @@ pub fn train_model(db: &SoulDatabase) {
 
     if trained > 0 {
         save_model(db, &model);
+        ship_quantized(db, &model, &tok);
         tracing::info!(
             trained,
             loss = format!("{:.4}", total_loss / trained as f32),
@@ -457,102 +592,110 @@ pub fn train_model(db: &SoulDatabase) {
 }
 
 /// Generate code given a prompt (test code context). Returns None if model not ready.
-/// Uses the encoder-decoder: encodes prompt (tests) once, then decodes solution tokens.
+/// Nucleus sampling at temperature 0.8 — explores diverse outputs instead of
+/// repeating the same greedy argmax every time.
 pub fn generate(db: &SoulDatabase, prompt: &str, max_tokens: usize) -> Option<String> {
+    let config = x402_model::DecodeConfig {
+        strategy: x402_model::Strategy::TopP {
+            p: 0.95,
+            temperature: 0.8,
+        },
+        max_tokens,
+        seed: chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0) as u64,
+        ..Default::default()
+    };
+    generate_candidates(db, prompt, &config).into_iter().next()
+}
+
+/// Generate up to `config.num_candidates` distinct solutions for a prompt.
+/// Empty if the model is not ready. Decoding is CPU-bound; async callers
+/// should [`load_decoder`] and run [`Decoder::candidates`] on a blocking
+/// thread.
+pub fn generate_candidates(
+    db: &SoulDatabase,
+    prompt: &str,
+    config: &x402_model::DecodeConfig,
+) -> Vec<String> {
+    load_decoder(db)
+        .map(|decoder| decoder.candidates(prompt, config))
+        .unwrap_or_default()
+}
+
+/// The tokenizer and model candidates are decoded with.
+pub struct Decoder {
+    tok: x402_model::bpe::BpeTokenizer,
+    model: InferenceModel,
+}
+
+/// Load the [`Decoder`], or `None` if the model is not ready.
+pub fn load_decoder(db: &SoulDatabase) -> Option<Decoder> {
     let tok = load_tokenizer(db);
     if tok.merges.is_empty() {
         tracing::debug!("codegen: generate skip — BPE not trained");
         return None;
     }
 
-    let model = load_model(db);
-    if model.train_steps < 10 {
+    let model = InferenceModel::load(db);
+    if model.train_steps() < 10 {
         tracing::debug!(
-            steps = model.train_steps,
+            steps = model.train_steps(),
             "codegen: generate skip — need >=10 training steps (have {})",
-            model.train_steps
+            model.train_steps()
         );
         return None;
     }
+    Some(Decoder { tok, model })
+}
 
-    // Tokenize context (test code) for the encoder
-    let mut context_tokens = vec![x402_model::bpe::BOS_TOKEN];
-    context_tokens.extend(tok.encode(prompt));
-    context_tokens.push(x402_model::bpe::EOS_TOKEN);
-    if context_tokens.len() > model.max_seq {
-        context_tokens.truncate(model.max_seq);
-    }
-
-    // Encode the context ONCE (bidirectional attention over test code)
-    let encoder_output = model.encode(&context_tokens);
-    let enc_len = context_tokens.len().min(model.max_seq);
-
-    // Start decoder with BOS token
-    let mut tokens = vec![x402_model::bpe::BOS_TOKEN];
-
-    // Generate token by token, conditioned on encoded context
-    for _ in 0..max_tokens {
-        if tokens.len() >= model.max_seq {
-            break;
+impl Decoder {
+    /// Uses the encoder-decoder: encodes prompt (tests) once, then decodes
+    /// solution tokens incrementally from a shared KV cache. EOS always stops
+    /// a candidate.
+    pub fn candidates(&self, prompt: &str, config: &x402_model::DecodeConfig) -> Vec<String> {
+        let Self { tok, model } = self;
+
+        // Tokenize context (test code) for the encoder
+        let mut context_tokens = vec![x402_model::bpe::BOS_TOKEN];
+        context_tokens.extend(tok.encode(prompt));
+        context_tokens.push(x402_model::bpe::EOS_TOKEN);
+        context_tokens.truncate(model.max_seq());
+
+        // Encode the context ONCE (bidirectional attention over test code)
+        let encoder_output = model.encode(&context_tokens);
+
+        let mut config = config.clone();
+        if !config.stop_tokens.contains(&x402_model::bpe::EOS_TOKEN) {
+            config.stop_tokens.push(x402_model::bpe::EOS_TOKEN);
         }
 
-        let logits = model.decode(&tokens, &encoder_output, enc_len);
-
-        // Temperature sampling — explore diverse outputs instead of repeating
-        // the same greedy argmax every time. Temperature 0.8 balances quality
-        // and diversity. Without this, codegen produces identical output forever.
-        let temperature: f32 = 0.8;
-        let next_token = {
-            // Apply temperature
-            let scaled: Vec<f32> = logits.iter().map(|l| l / temperature).collect();
-            // Softmax
-            let max_logit = scaled.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-            let exps: Vec<f32> = scaled.iter().map(|l| (l - max_logit).exp()).collect();
-            let sum: f32 = exps.iter().sum();
-            let probs: Vec<f32> = exps.iter().map(|e| e / sum).collect();
-            // Sample from distribution using a simple LCG PRNG seeded from token position
-            let seed = (tokens.len() as u64)
-                .wrapping_mul(6364136223846793005)
-                .wrapping_add(chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0) as u64);
-            let r = ((seed >> 16) as f32) / (u32::MAX as f32);
-            let mut cumulative = 0.0f32;
-            let mut chosen = 0u32;
-            for (i, p) in probs.iter().enumerate() {
-                cumulative += p;
-                if cumulative >= r {
-                    chosen = i as u32;
-                    break;
-                }
-            }
-            chosen
-        };
+        // Decoder starts from BOS — decoder tokens are pure solution, no prompt mixed in
+        let started = std::time::Instant::now();
+        let candidates = x402_model::decoding::generate(
+            model.decoder(),
+            &encoder_output,
+            context_tokens.len(),
+            &[x402_model::bpe::BOS_TOKEN],
+            &config,
+        );
 
-        if next_token == x402_model::bpe::EOS_TOKEN {
-            break;
+        let mut outputs: Vec<String> = Vec::new();
+        for candidate in &candidates {
+            let generated = tok.decode(&candidate.tokens);
+            if generated.trim().is_empty() || outputs.contains(&generated) {
+                continue;
+            }
+            outputs.push(generated);
         }
 
-        tokens.push(next_token);
-    }
-
-    // Decode (skip BOS — decoder tokens are pure solution, no prompt mixed in)
-    let prompt_len = 1; // just BOS
-    if tokens.len() <= prompt_len {
-        tracing::debug!("codegen: generate produced no tokens");
-        return None;
-    }
-
-    let generated = tok.decode(&tokens[prompt_len..]);
-    if generated.trim().is_empty() {
-        tracing::debug!("codegen: generate produced empty output");
-        return None;
+        tracing::info!(
+            strategy = ?config.strategy,
+            candidates = outputs.len(),
+            generated_tokens = candidates.iter().map(|c| c.tokens.len()).sum::<usize>(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "codegen: local model generated code"
+        );
+        outputs
     }
-
-    tracing::info!(
-        prompt_tokens = prompt_len,
-        generated_tokens = tokens.len() - prompt_len,
-        "codegen: local model generated code"
-    );
-    Some(generated)
 }
 
 /// Record a successful code diff as training data for the codegen model.
@@ -729,6 +872,12 @@ pub fn status(db: &SoulDatabase) -> serde_json::Value {
         "model_steps": model.train_steps,
         "model_loss": format!("{:.4}", model.running_loss),
         "can_generate": can_generate,
+        "quantization": quant_mode().map(|m| m.as_str()).unwrap_or("off"),
+        "quant_report": db
+            .get_state("codegen_quant_report")
+            .ok()
+            .flatten()
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok()),
         "target_params": x402_model::codegen::CODEGEN_PARAMS,
         "target_vocab": x402_model::codegen::CODEGEN_VOCAB_SIZE,
     })
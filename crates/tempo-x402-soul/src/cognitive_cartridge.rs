@@ -78,13 +78,15 @@ impl CognitiveOrchestrator {
         let cart_request = x402_cartridge::CartridgeRequest {
             method: "POST".to_string(),
             path: "/".to_string(),
+            query: String::new(),
             body,
             headers: std::collections::HashMap::new(),
             payment: None,
+            raw_body: None,
         };
 
         match engine.execute(&slug, &cart_request, std::collections::HashMap::new(), 30) {
-            Ok((result, _kv)) if result.status == 200 => serde_json::from_str(&result.body).ok(),
+            Ok((result, _kv)) if result.status == 200 => serde_json::from_slice(&result.body).ok(),
             Ok((result, _kv)) => {
                 tracing::debug!(
                     system,
@@ -11,6 +11,9 @@
 //!   6. Workers submit training data, queen trains brain on all
 //!   7. Workers fetch updated weights from queen
 //!
+//! Worker → queen messages are wallet-signed envelopes
+//! ([`crate::envelope`]); the queen rejects unsigned and replayed ones.
+//!
 //! Failure modes:
 //!   - Worker can't reach queen → falls back to standalone
 //!   - Worker dies → queen prunes after 10 min, reassigns work
@@ -51,6 +54,9 @@ impl ColonyRole {
 pub struct ColonyWorker {
     pub instance_id: String,
     pub url: String,
+    /// Wallet address the worker signs its messages with.
+    #[serde(default)]
+    pub address: String,
     pub registered_at: i64,
     pub last_heartbeat: i64,
     pub fitness: f64,
@@ -73,6 +79,12 @@ pub struct BenchmarkResult {
     pub solution: String,
     pub error_output: String,
     pub total_ms: u64,
+    /// Problem pack id, from workers that send it.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub pack: String,
+    /// Per-test outcomes and compile errors, from workers that send them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<crate::test_report::TestReport>,
 }
 
 /// Work assignment for a worker (plan step execution).
@@ -99,26 +111,69 @@ pub struct WorkResult {
 
 const HEARTBEAT_TIMEOUT_SECS: i64 = 600; // 10 minutes
 
-/// Register or update a worker. Called by POST /soul/colony/register.
-pub fn register_worker(db: &SoulDatabase, instance_id: &str, url: &str) {
+/// Register or update a worker signing as `address`. Called by POST
+/// /soul/colony/register. Fails if the instance id is registered to another
+/// address.
+pub fn register_worker(
+    db: &SoulDatabase,
+    instance_id: &str,
+    url: &str,
+    address: &str,
+) -> Result<(), String> {
+    if !valid_instance_id(instance_id) {
+        return Err(format!("invalid instance id {instance_id:?}"));
+    }
     let now = chrono::Utc::now().timestamp();
     let mut workers = load_workers(db);
 
     if let Some(w) = workers.iter_mut().find(|w| w.instance_id == instance_id) {
+        if !w.address.is_empty() && !w.address.eq_ignore_ascii_case(address) {
+            return Err(format!("{instance_id} is registered to {}", w.address));
+        }
         w.url = url.to_string();
+        w.address = address.to_string();
         w.last_heartbeat = now;
     } else {
         workers.push(ColonyWorker {
             instance_id: instance_id.to_string(),
             url: url.to_string(),
+            address: address.to_string(),
             registered_at: now,
             last_heartbeat: now,
             fitness: 0.0,
         });
-        tracing::info!(instance_id, url, "New worker registered in colony");
+        tracing::info!(instance_id, url, address, "New worker registered in colony");
     }
 
     save_workers(db, &workers);
+    Ok(())
+}
+
+/// The registered worker `instance_id`, if it signs as `address`. Results
+/// and training data are only accepted from registered workers, under the
+/// id they registered with.
+pub fn registered_worker(
+    db: &SoulDatabase,
+    instance_id: &str,
+    address: &str,
+) -> Result<ColonyWorker, String> {
+    if !valid_instance_id(instance_id) {
+        return Err(format!("invalid instance id {instance_id:?}"));
+    }
+    load_workers(db)
+        .into_iter()
+        .find(|w| w.instance_id == instance_id && w.address.eq_ignore_ascii_case(address))
+        .ok_or_else(|| format!("{address} is not registered as worker {instance_id}"))
+}
+
+/// Instance ids are embedded in training sources (`worker/{id}/{slug}`), so
+/// they're limited to alphanumerics, `-` and `_`.
+fn valid_instance_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
 }
 
 /// Get live workers (prune dead ones). Called by GET /soul/colony/peers.
@@ -264,18 +319,11 @@ pub async fn register_with_queen(queen: &str, instance_id: &str, self_url: &str)
         .build()
         .unwrap_or_default();
 
-    match client
-        .post(format!(
-            "{}/soul/colony/register",
-            queen.trim_end_matches('/')
-        ))
-        .json(&serde_json::json!({
-            "instance_id": instance_id,
-            "url": self_url,
-        }))
-        .send()
-        .await
-    {
+    let body = serde_json::json!({
+        "instance_id": instance_id,
+        "url": self_url,
+    });
+    match crate::envelope::post_signed(&client, queen, "/soul/colony/register", &body).await {
         Ok(resp) if resp.status().is_success() => {
             tracing::info!(queen, "Registered with queen");
             true
@@ -330,18 +378,12 @@ pub async fn report_benchmark_results(
         .build()
         .unwrap_or_default();
 
-    match client
-        .post(format!(
-            "{}/soul/colony/benchmark/result",
-            queen.trim_end_matches('/')
-        ))
-        .json(&serde_json::json!({
-            "session_id": session_id,
-            "worker_id": instance_id,
-            "results": results,
-        }))
-        .send()
-        .await
+    let body = serde_json::json!({
+        "session_id": session_id,
+        "worker_id": instance_id,
+        "results": results,
+    });
+    match crate::envelope::post_signed(&client, queen, "/soul/colony/benchmark/result", &body).await
     {
         Ok(resp) if resp.status().is_success() => {
             tracing::info!(
@@ -368,15 +410,11 @@ pub async fn submit_training_data(queen: &str, examples: &[serde_json::Value]) -
         .build()
         .unwrap_or_default();
 
-    match client
-        .post(format!("{}/soul/colony/train", queen.trim_end_matches('/')))
-        .json(&serde_json::json!({ "examples": examples }))
-        .send()
-        .await
-    {
-        Ok(resp) if resp.status().is_success() => true,
-        _ => false,
-    }
+    let body = serde_json::json!({ "examples": examples });
+    matches!(
+        crate::envelope::post_signed(&client, queen, "/soul/colony/train", &body).await,
+        Ok(resp) if resp.status().is_success()
+    )
 }
 
 /// Fetch work assignment from queen. Returns None if no work available.
@@ -386,10 +424,8 @@ pub async fn fetch_work(queen: &str, instance_id: &str) -> Option<WorkAssignment
         .build()
         .ok()?;
 
-    let resp = client
-        .post(format!("{}/soul/colony/work", queen.trim_end_matches('/')))
-        .json(&serde_json::json!({ "worker_id": instance_id }))
-        .send()
+    let body = serde_json::json!({ "worker_id": instance_id });
+    let resp = crate::envelope::post_signed(&client, queen, "/soul/colony/work", &body)
         .await
         .ok()?;
 
@@ -410,18 +446,10 @@ pub async fn report_work(queen: &str, result: &WorkResult) -> bool {
         .build()
         .unwrap_or_default();
 
-    match client
-        .post(format!(
-            "{}/soul/colony/report",
-            queen.trim_end_matches('/')
-        ))
-        .json(result)
-        .send()
-        .await
-    {
-        Ok(resp) if resp.status().is_success() => true,
-        _ => false,
-    }
+    matches!(
+        crate::envelope::post_signed(&client, queen, "/soul/colony/report", result).await,
+        Ok(resp) if resp.status().is_success()
+    )
 }
 
 /// Fetch latest brain weights from queen.
@@ -465,3 +493,27 @@ fn save_workers(db: &SoulDatabase, workers: &[ColonyWorker]) {
         let _ = db.set_state("colony_workers", &json);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workers_are_bound_to_their_signing_address() {
+        let path = format!("/tmp/test_colony_{}", uuid::Uuid::new_v4());
+        let db = SoulDatabase::new(&path).unwrap();
+        let (alice, mallory) = (
+            "0x1111111111111111111111111111111111111111",
+            "0x2222222222222222222222222222222222222222",
+        );
+        register_worker(&db, "worker-a", "https://a.example.com", alice).unwrap();
+        assert!(register_worker(&db, "worker-a", "https://m.example.com", mallory).is_err());
+        assert!(register_worker(&db, "x/opus", "https://m.example.com", mallory).is_err());
+
+        assert!(registered_worker(&db, "worker-a", alice).is_ok());
+        assert!(registered_worker(&db, "worker-a", mallory).is_err());
+        assert!(registered_worker(&db, "worker-b", alice).is_err());
+        assert!(registered_worker(&db, "worker-a/x", alice).is_err());
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
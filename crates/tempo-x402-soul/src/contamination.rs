@@ -0,0 +1,459 @@
+//! Train/held-out split of benchmark problems, and contamination checks.
+//!
+//! Passing benchmark solutions become training data for the codegen and
+//! unified models, so a score on problems the models have trained on
+//! measures recall, not skill. This module keeps part of the benchmark
+//! unseen:
+//!
+//! 1. **Split**: [`ProblemSplit::build`] holds out [`HELD_OUT_PERCENT`] of
+//!    each tier, chosen by a stable hash of the slug and the rotation epoch.
+//!    Every node with the same problems and rotation period gets the same
+//!    split, so merged peer weights don't leak held-out problems either.
+//!    Solutions to held-out problems are still recorded but never trained on
+//!    ([`ProblemSplit::excludes_source`]).
+//! 2. **Rotate**: the held-out set changes every [`rotation_secs`]; problems
+//!    that were held out become training data and others take their place.
+//! 3. **Detect**: a held-out problem whose tests share enough word n-grams
+//!    with the training corpus (recorded before it was held out, or copied
+//!    under another name) is *contaminated* and counts as seen.
+//! 4. **Score**: [`split_scores`] reports seen and unseen problems
+//!    separately; the ELO rating follows the unseen score ([`rating_score`]).
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::benchmark::{difficulty_weight, BenchmarkProblem, BenchmarkRun};
+use crate::db::SoulDatabase;
+use crate::model_eval::fnv1a;
+use crate::mutation::SLUG_INFIX;
+
+/// Share of each tier's problems (in percent) held out from training.
+pub const HELD_OUT_PERCENT: usize = 20;
+/// Days between held-out rotations, unless `SOUL_HELDOUT_ROTATION_DAYS` is set.
+pub const DEFAULT_ROTATION_DAYS: u64 = 7;
+/// Share of a held-out problem's n-grams found in the training corpus at
+/// which it counts as seen.
+pub const CONTAMINATION_THRESHOLD: f64 = 0.3;
+/// Unseen problems measured before the rating follows the unseen score.
+pub const MIN_UNSEEN_ATTEMPTS: u32 = 3;
+/// Words per n-gram.
+const NGRAM: usize = 10;
+const STATE_KEY: &str = "benchmark_split";
+
+/// Which benchmark problems are held out from training this epoch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProblemSplit {
+    /// Rotation epoch (`timestamp / rotation_secs()`).
+    pub epoch: u64,
+    /// When this split was built. Training examples recorded earlier may
+    /// have been trained on whatever their problem.
+    pub created_at: i64,
+    /// Slugs held out from training.
+    pub held_out: BTreeSet<String>,
+    /// Held-out slugs found in the training corpus, with their n-gram
+    /// overlap. These count as seen.
+    #[serde(default)]
+    pub contaminated: BTreeMap<String, f64>,
+    /// When contamination was last checked.
+    #[serde(default)]
+    pub checked_at: i64,
+}
+
+impl ProblemSplit {
+    /// Hold out [`HELD_OUT_PERCENT`] of each tier (at least one problem in
+    /// tiers with two or more). Generated debugging problems follow the
+    /// problem they were derived from and are never picked themselves.
+    pub fn build(problems: &[BenchmarkProblem], epoch: u64, created_at: i64) -> Self {
+        let mut by_tier: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for p in problems.iter().filter(|p| !p.slug.contains(SLUG_INFIX)) {
+            by_tier.entry(&p.difficulty).or_default().push(&p.slug);
+        }
+        let mut held_out = BTreeSet::new();
+        for slugs in by_tier.values_mut() {
+            if slugs.len() < 2 {
+                continue;
+            }
+            let count = (slugs.len() * HELD_OUT_PERCENT).div_ceil(100);
+            slugs.sort_by_key(|slug| (fnv1a(format!("{epoch}:{slug}").as_bytes()), *slug));
+            held_out.extend(slugs.iter().take(count).map(|s| s.to_string()));
+        }
+        Self {
+            epoch,
+            created_at,
+            held_out,
+            contaminated: BTreeMap::new(),
+            checked_at: 0,
+        }
+    }
+
+    /// Whether `slug` (or the problem it was generated from) is held out.
+    pub fn is_held_out(&self, slug: &str) -> bool {
+        self.held_out.contains(base_slug(slug))
+    }
+
+    /// Held out and not found in the training corpus.
+    pub fn is_unseen(&self, slug: &str) -> bool {
+        self.is_held_out(slug) && !self.contaminated.contains_key(base_slug(slug))
+    }
+
+    /// Whether training must skip an example recorded under `source`
+    /// (`opus/{slug}` for benchmark solutions, `worker/{id}/{slug}` for
+    /// those reported by colony workers).
+    pub fn excludes_source(&self, source: &str) -> bool {
+        let slug = source.strip_prefix("opus/").or_else(|| {
+            source
+                .strip_prefix("worker/")
+                .and_then(|rest| rest.split_once('/'))
+                .map(|(_, slug)| slug)
+        });
+        slug.is_some_and(|slug| self.is_held_out(slug))
+    }
+
+    /// Whether a `codegen_solutions` entry must be left out of training.
+    pub fn excludes(&self, solution: &serde_json::Value) -> bool {
+        solution
+            .get("source")
+            .and_then(|v| v.as_str())
+            .is_some_and(|source| self.excludes_source(source))
+    }
+
+    /// When the next rotation is due.
+    pub fn rotates_at(&self) -> i64 {
+        ((self.epoch + 1) * rotation_secs()) as i64
+    }
+}
+
+/// The slug a generated debugging problem (`{slug}-bug-{hash}`) came from.
+fn base_slug(slug: &str) -> &str {
+    slug.split(SLUG_INFIX).next().unwrap_or(slug)
+}
+
+/// Seconds between held-out rotations (`SOUL_HELDOUT_ROTATION_DAYS`).
+pub fn rotation_secs() -> u64 {
+    let days = std::env::var("SOUL_HELDOUT_ROTATION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|&d| d > 0)
+        .unwrap_or(DEFAULT_ROTATION_DAYS);
+    days * 86_400
+}
+
+/// Rotation epoch at unix time `ts`.
+pub fn epoch_at(ts: i64) -> u64 {
+    ts.max(0) as u64 / rotation_secs()
+}
+
+/// The stored split, whatever its epoch.
+pub fn load(db: &SoulDatabase) -> Option<ProblemSplit> {
+    db.get_state(STATE_KEY)
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str(&s).ok())
+}
+
+fn save(db: &SoulDatabase, split: &ProblemSplit) {
+    if let Ok(json) = serde_json::to_string(split) {
+        let _ = db.set_state(STATE_KEY, &json);
+    }
+}
+
+/// The split for training: the stored one while its epoch lasts, otherwise
+/// a freshly rotated one.
+pub fn current(db: &SoulDatabase) -> ProblemSplit {
+    match load(db) {
+        Some(split) if split.epoch == epoch_at(chrono::Utc::now().timestamp()) => split,
+        _ => refresh(db, &crate::problem_pack::load_problems()),
+    }
+}
+
+/// Rotate the split if its epoch has passed, re-check contamination against
+/// the current training corpus, and store it. Called at the start of each
+/// benchmark session.
+pub fn refresh(db: &SoulDatabase, problems: &[BenchmarkProblem]) -> ProblemSplit {
+    let now = chrono::Utc::now().timestamp();
+    let epoch = epoch_at(now);
+    let mut split = match load(db) {
+        Some(split) if split.epoch == epoch => split,
+        previous => {
+            let split = ProblemSplit::build(problems, epoch, now);
+            tracing::info!(
+                epoch,
+                previous_epoch = previous.map(|s| s.epoch),
+                held_out = split.held_out.len(),
+                "Benchmark: rotated held-out problems"
+            );
+            split
+        }
+    };
+
+    let solutions: Vec<serde_json::Value> = db
+        .get_state("codegen_solutions")
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let corpus = training_corpus(&solutions, &split);
+    split.contaminated = contaminated(&corpus, problems, &split);
+    split.checked_at = now;
+    if !split.contaminated.is_empty() {
+        tracing::info!(
+            contaminated = split.contaminated.len(),
+            slugs = %split.contaminated.keys().cloned().collect::<Vec<_>>().join(", "),
+            "Benchmark: held-out problems overlap training data — counted as seen"
+        );
+    }
+    save(db, &split);
+    split
+}
+
+// ── Contamination ───────────────────────────────────────────────────
+
+/// N-gram hashes of every `codegen_solutions` entry the models may have
+/// trained on: all entries recorded before the split, and later ones that
+/// training doesn't exclude.
+pub fn training_corpus(solutions: &[serde_json::Value], split: &ProblemSplit) -> HashSet<u64> {
+    let mut corpus = HashSet::new();
+    for sol in solutions {
+        let ts = sol.get("ts").and_then(|v| v.as_i64()).unwrap_or(0);
+        if ts >= split.created_at && split.excludes(sol) {
+            continue;
+        }
+        for field in ["code", "context"] {
+            if let Some(text) = sol.get(field).and_then(|v| v.as_str()) {
+                corpus.extend(ngrams(text));
+            }
+        }
+    }
+    corpus
+}
+
+/// Held-out problems whose tests and starter code overlap `corpus` by at
+/// least [`CONTAMINATION_THRESHOLD`].
+pub fn contaminated(
+    corpus: &HashSet<u64>,
+    problems: &[BenchmarkProblem],
+    split: &ProblemSplit,
+) -> BTreeMap<String, f64> {
+    problems
+        .iter()
+        .filter(|p| split.held_out.contains(&p.slug))
+        .filter_map(|p| {
+            let share = overlap(&format!("{}\n{}", p.test_code, p.starter_code), corpus);
+            (share >= CONTAMINATION_THRESHOLD).then(|| (p.slug.clone(), share))
+        })
+        .collect()
+}
+
+/// Share of `text`'s n-grams present in `corpus` (0 for text too short to
+/// have any).
+pub fn overlap(text: &str, corpus: &HashSet<u64>) -> f64 {
+    let grams = ngrams(text);
+    if grams.is_empty() {
+        return 0.0;
+    }
+    grams.iter().filter(|g| corpus.contains(g)).count() as f64 / grams.len() as f64
+}
+
+/// Hashes of the [`NGRAM`]-word windows of `text`. Words are identifier and
+/// number runs, so formatting and punctuation don't matter.
+fn ngrams(text: &str) -> HashSet<u64> {
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| !w.is_empty())
+        .collect();
+    words
+        .windows(NGRAM)
+        .map(|w| fnv1a(w.join(" ").as_bytes()))
+        .collect()
+}
+
+// ── Scores ──────────────────────────────────────────────────────────
+
+/// Difficulty-weighted pass rate over one side of the split.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SplitScore {
+    /// Weighted pass rate in percent.
+    pub pass_at_1: f64,
+    pub attempted: u32,
+    pub passed: u32,
+}
+
+/// Scores on problems the models may have trained on, and on the rest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SplitScores {
+    pub seen: SplitScore,
+    pub unseen: SplitScore,
+}
+
+/// Score the latest run of each problem in `runs`, split by whether the
+/// problem is unseen.
+pub fn split_scores(
+    runs: &[BenchmarkRun],
+    problems: &[BenchmarkProblem],
+    split: &ProblemSplit,
+) -> SplitScores {
+    let mut latest: HashMap<&str, &BenchmarkRun> = HashMap::new();
+    for run in runs {
+        let entry = latest.entry(run.entry_point.as_str()).or_insert(run);
+        if run.created_at > entry.created_at {
+            *entry = run;
+        }
+    }
+
+    // (earned weight, total weight, attempted, passed) for seen, unseen
+    let mut totals = [(0.0f64, 0.0f64, 0u32, 0u32); 2];
+    for (slug, run) in latest {
+        let Some(problem) = problems.iter().find(|p| p.slug == slug) else {
+            continue;
+        };
+        let weight = difficulty_weight(&problem.difficulty);
+        let side = &mut totals[split.is_unseen(slug) as usize];
+        side.1 += weight;
+        side.2 += 1;
+        if run.passed {
+            side.0 += weight;
+            side.3 += 1;
+        }
+    }
+    let score = |(earned, total, attempted, passed): (f64, f64, u32, u32)| SplitScore {
+        pass_at_1: if total > 0.0 {
+            earned / total * 100.0
+        } else {
+            0.0
+        },
+        attempted,
+        passed,
+    };
+    SplitScores {
+        seen: score(totals[0]),
+        unseen: score(totals[1]),
+    }
+}
+
+/// The score the ELO rating should follow: the unseen score once
+/// [`MIN_UNSEEN_ATTEMPTS`] unseen problems have been measured this epoch,
+/// otherwise `session_score`.
+pub fn rating_score(db: &SoulDatabase, session_score: f64) -> f64 {
+    crate::benchmark::load_opus_score(db)
+        .and_then(|s| s.unseen)
+        .filter(|u| u.attempted >= MIN_UNSEEN_ATTEMPTS)
+        .map(|u| u.pass_at_1)
+        .unwrap_or(session_score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problem(slug: &str, tier: &str, test_code: &str) -> BenchmarkProblem {
+        BenchmarkProblem {
+            slug: slug.to_string(),
+            instructions: String::new(),
+            test_code: test_code.to_string(),
+            starter_code: String::new(),
+            difficulty: tier.to_string(),
+            cargo_toml: String::new(),
+            pack: String::new(),
+        }
+    }
+
+    fn run(slug: &str, passed: bool, created_at: i64) -> BenchmarkRun {
+        BenchmarkRun {
+            id: format!("{slug}-{created_at}"),
+            task_id: format!("opus/{slug}"),
+            entry_point: slug.to_string(),
+            passed,
+            generated_solution: String::new(),
+            error_output: String::new(),
+            total_ms: 0,
+            created_at,
+            pack: String::new(),
+            report: None,
+        }
+    }
+
+    #[test]
+    fn split_is_stratified_stable_and_rotates() {
+        let problems: Vec<_> = (0..20)
+            .map(|i| problem(&format!("t1-{i}"), "tier1", ""))
+            .chain((0..5).map(|i| problem(&format!("t4-{i}"), "tier4", "")))
+            .chain([problem("t6-0", "tier6", "")])
+            .collect();
+        let split = ProblemSplit::build(&problems, 3, 0);
+        let in_tier = |prefix: &str| {
+            split
+                .held_out
+                .iter()
+                .filter(|s| s.starts_with(prefix))
+                .count()
+        };
+        assert_eq!(in_tier("t1-"), 4);
+        assert_eq!(in_tier("t4-"), 1);
+        assert_eq!(in_tier("t6-"), 0);
+        assert_eq!(
+            ProblemSplit::build(&problems, 3, 0).held_out,
+            split.held_out
+        );
+        assert!((4..10)
+            .any(|epoch| ProblemSplit::build(&problems, epoch, 0).held_out != split.held_out));
+
+        let held = split.held_out.iter().next().unwrap().clone();
+        assert!(split.is_held_out(&format!("{held}{SLUG_INFIX}0a1b")));
+        assert!(split.excludes_source(&format!("opus/{held}")));
+        assert!(split.excludes_source(&format!("worker/w-1/{held}")));
+        assert!(!split.excludes_source(&format!("read:{held}")));
+    }
+
+    #[test]
+    fn detects_held_out_tests_in_training_data() {
+        let tests = "#[test]\nfn pushes_and_pops() {\n    let mut s = Stack::new();\n    s.push(1);\n    s.push(2);\n    assert_eq!(s.pop(), Some(2));\n    assert_eq!(s.pop(), Some(1));\n    assert_eq!(s.pop(), None);\n}\n";
+        let problems = vec![
+            problem("stack", "tier1", tests),
+            problem("queue", "tier1", ""),
+        ];
+        let mut split = ProblemSplit::build(&problems, 0, 1_000);
+        split.held_out = BTreeSet::from(["stack".to_string()]);
+
+        // Recorded while held out: excluded from training, so not contamination.
+        let later = serde_json::json!({"code": "pub struct Stack;", "source": "opus/stack", "context": tests, "ts": 2_000});
+        assert!(contaminated(&training_corpus(&[later], &split), &problems, &split).is_empty());
+
+        // Recorded before the split (or under another name): contaminated.
+        let earlier = serde_json::json!({"code": "pub struct Stack;", "source": "opus/stack", "context": tests, "ts": 500});
+        let found = contaminated(&training_corpus(&[earlier], &split), &problems, &split);
+        assert!(found["stack"] > 0.9);
+        split.contaminated = found;
+        assert!(split.is_held_out("stack") && !split.is_unseen("stack"));
+    }
+
+    #[test]
+    fn scores_latest_run_per_side() {
+        let problems = vec![
+            problem("seen", "tier1", ""),
+            problem("fresh", "tier4", ""),
+            problem("dirty", "tier2", ""),
+        ];
+        let mut split = ProblemSplit::build(&problems, 0, 0);
+        split.held_out = BTreeSet::from(["fresh".to_string(), "dirty".to_string()]);
+        split.contaminated = BTreeMap::from([("dirty".to_string(), 0.8)]);
+
+        let runs = vec![
+            run("seen", true, 1),
+            run("dirty", false, 1),
+            run("fresh", false, 1),
+            run("fresh", true, 2),
+        ];
+        let scores = split_scores(&runs, &problems, &split);
+        assert_eq!(
+            scores.unseen,
+            SplitScore {
+                pass_at_1: 100.0,
+                attempted: 1,
+                passed: 1
+            }
+        );
+        assert_eq!(scores.seen.attempted, 2);
+        assert!((scores.seen.pass_at_1 - 100.0 / 3.0).abs() < 1e-9);
+    }
+}
@@ -30,4 +30,15 @@ impl SoulDatabase {
         self.state.insert(key.as_bytes(), value.as_bytes())?;
         Ok(())
     }
+
+    /// Get a raw soul state value (binary blobs such as model checkpoints).
+    pub fn get_state_bytes(&self, key: &str) -> Result<Option<Vec<u8>>, SoulError> {
+        Ok(self.state.get(key.as_bytes())?.map(|v| v.to_vec()))
+    }
+
+    /// Set a raw soul state value (upsert).
+    pub fn set_state_bytes(&self, key: &str, value: &[u8]) -> Result<(), SoulError> {
+        self.state.insert(key.as_bytes(), value)?;
+        Ok(())
+    }
 }
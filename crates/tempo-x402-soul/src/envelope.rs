@@ -0,0 +1,326 @@
+//! Wallet-signed envelopes for peer-to-peer messages.
+//!
+//! Colony endpoints act on what peers send them: registering workers,
+//! queueing training data, recording benchmark runs. Every such message
+//! travels in a [`PeerEnvelope`] signed with the sender's instance key
+//! (EIP-191, [`WalletSigner::sign_message`]) over the recipient's host, the
+//! request path, sender, timestamp, nonce and a SHA-256 of the body.
+//!
+//! The receiver [opens](PeerEnvelope::open) it — recovers the signer, checks
+//! that the message is for one of its own hosts and this path, and that the
+//! timestamp is within [`MAX_SKEW_SECS`] — then rejects nonces it has
+//! already seen ([`NonceCache`]). Binding the recipient means a node that
+//! receives an envelope can't replay it to other nodes that trust the same
+//! sender. Whether the signer is a peer worth listening to is the
+//! receiver's call (the node checks its peer registry and ERC-8004
+//! identities).
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use alloy::primitives::Address;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x402::{recover_message_signer, WalletSigner};
+
+/// How far an envelope's timestamp may be from the receiver's clock.
+pub const MAX_SKEW_SECS: i64 = 300;
+
+static SIGNER: OnceLock<(WalletSigner, String)> = OnceLock::new();
+static NONCES: OnceLock<NonceCache> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("no instance key configured for signing")]
+    NoSigner,
+    #[error("signing failed: {0}")]
+    Sign(String),
+    #[error("malformed envelope: {0}")]
+    Malformed(String),
+    #[error("envelope is for {got}, not {expected}")]
+    WrongPath { expected: String, got: String },
+    #[error("envelope is addressed to {0}")]
+    WrongRecipient(String),
+    #[error("envelope timestamp is {age}s off")]
+    Stale { age: i64 },
+    #[error("invalid signature: {0}")]
+    BadSignature(String),
+    #[error("signed by {recovered}, not {claimed}")]
+    SignerMismatch { claimed: String, recovered: Address },
+    #[error("nonce already used")]
+    Replayed,
+    #[error("{0} is not a known peer")]
+    UnknownPeer(Address),
+}
+
+/// A peer message and the sender's signature over it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerEnvelope {
+    /// Sender's instance id.
+    pub sender: String,
+    /// Sender's wallet address; must match the signature.
+    pub address: String,
+    /// Host (and port, if any) of the node the message is for, as in
+    /// [`host_of`].
+    pub recipient: String,
+    /// Unix seconds at signing.
+    pub timestamp: i64,
+    /// Unique per message, so a captured envelope can't be replayed.
+    pub nonce: String,
+    /// Endpoint the message is for, e.g. `/soul/colony/train`.
+    pub path: String,
+    /// The message, as JSON.
+    pub body: String,
+    /// 0x-prefixed EIP-191 signature over [`signing_message`](Self::signing_message).
+    pub signature: String,
+}
+
+impl PeerEnvelope {
+    /// Sign `body` for `path` on `recipient` (a host) as `sender`.
+    pub fn seal(
+        signer: &WalletSigner,
+        sender: &str,
+        recipient: &str,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<Self, EnvelopeError> {
+        let mut envelope = Self {
+            sender: sender.to_string(),
+            address: signer.address_string(),
+            recipient: host_of(recipient),
+            timestamp: chrono::Utc::now().timestamp(),
+            nonce: uuid::Uuid::new_v4().simple().to_string(),
+            path: path.to_string(),
+            body: serde_json::to_string(body).map_err(|e| EnvelopeError::Sign(e.to_string()))?,
+            signature: String::new(),
+        };
+        envelope.signature = signer
+            .sign_message(&envelope.signing_message())
+            .map_err(EnvelopeError::Sign)?;
+        Ok(envelope)
+    }
+
+    /// The bytes that are signed.
+    pub fn signing_message(&self) -> Vec<u8> {
+        let body_hash: String = Sha256::digest(self.body.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        format!(
+            "x402 peer message\nrecipient: {}\npath: {}\nsender: {}\naddress: {}\ntimestamp: {}\nnonce: {}\nbody-sha256: {}",
+            self.recipient, self.path, self.sender, self.address, self.timestamp, self.nonce, body_hash
+        )
+        .into_bytes()
+    }
+
+    /// Check the envelope was signed by its claimed address, for one of
+    /// `hosts` (the receiver's own) and `path`, within [`MAX_SKEW_SECS`] of
+    /// `now`. Returns the signer. Does not check the nonce (see
+    /// [`NonceCache`]) or who the signer is.
+    pub fn open(&self, hosts: &[String], path: &str, now: i64) -> Result<Address, EnvelopeError> {
+        if !hosts.iter().any(|h| host_of(h) == self.recipient) {
+            return Err(EnvelopeError::WrongRecipient(self.recipient.clone()));
+        }
+        if self.path != path {
+            return Err(EnvelopeError::WrongPath {
+                expected: path.to_string(),
+                got: self.path.clone(),
+            });
+        }
+        let age = now - self.timestamp;
+        if age.abs() > MAX_SKEW_SECS {
+            return Err(EnvelopeError::Stale { age });
+        }
+        if self.nonce.is_empty() {
+            return Err(EnvelopeError::Malformed("empty nonce".to_string()));
+        }
+        let signature = alloy::hex::decode(self.signature.trim_start_matches("0x"))
+            .map_err(|e| EnvelopeError::BadSignature(e.to_string()))?;
+        let recovered = recover_message_signer(&self.signing_message(), &signature)
+            .map_err(EnvelopeError::BadSignature)?;
+        let claimed: Address = self
+            .address
+            .parse()
+            .map_err(|_| EnvelopeError::Malformed(format!("bad address {}", self.address)))?;
+        if recovered != claimed {
+            return Err(EnvelopeError::SignerMismatch {
+                claimed: self.address.clone(),
+                recovered,
+            });
+        }
+        Ok(recovered)
+    }
+
+    /// Parse the body.
+    pub fn body<T: DeserializeOwned>(&self) -> Result<T, EnvelopeError> {
+        serde_json::from_str(&self.body).map_err(|e| EnvelopeError::Malformed(e.to_string()))
+    }
+}
+
+/// Nonces seen within the timestamp window, per signer.
+#[derive(Default)]
+pub struct NonceCache {
+    seen: Mutex<HashMap<(Address, String), i64>>,
+}
+
+impl NonceCache {
+    /// The process-wide cache.
+    pub fn global() -> &'static NonceCache {
+        NONCES.get_or_init(NonceCache::default)
+    }
+
+    /// Record `nonce` from `signer`; `Err(Replayed)` if it was already used.
+    /// Entries older than twice the timestamp window are dropped, since their
+    /// envelopes would be rejected as stale anyway.
+    pub fn check(
+        &self,
+        signer: Address,
+        nonce: &str,
+        timestamp: i64,
+        now: i64,
+    ) -> Result<(), EnvelopeError> {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, ts| now - *ts <= 2 * MAX_SKEW_SECS);
+        if seen
+            .insert((signer, nonce.to_string()), timestamp)
+            .is_some()
+        {
+            return Err(EnvelopeError::Replayed);
+        }
+        Ok(())
+    }
+}
+
+/// The host (and port) part of a URL or bare host, lowercased:
+/// `https://Queen.example.com:8443/soul` → `queen.example.com:8443`.
+pub fn host_of(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Set the instance key and id that outgoing peer messages are signed
+/// with. Only works once; returns `false` if already configured.
+pub fn configure_signer(signer: WalletSigner, instance_id: String) -> bool {
+    SIGNER.set((signer, instance_id)).is_ok()
+}
+
+/// The configured instance key, if any.
+pub(crate) fn signer() -> Option<&'static WalletSigner> {
+    SIGNER.get().map(|(signer, _)| signer)
+}
+
+/// Seal `body` for `path` on `recipient` with the configured instance key.
+pub fn seal(
+    recipient: &str,
+    path: &str,
+    body: &impl Serialize,
+) -> Result<PeerEnvelope, EnvelopeError> {
+    let (signer, instance_id) = SIGNER.get().ok_or(EnvelopeError::NoSigner)?;
+    PeerEnvelope::seal(signer, instance_id, recipient, path, body)
+}
+
+/// POST `body` to `{base_url}{path}` in a signed envelope.
+pub async fn post_signed(
+    client: &reqwest::Client,
+    base_url: &str,
+    path: &str,
+    body: &impl Serialize,
+) -> Result<reqwest::Response, String> {
+    let envelope = seal(base_url, path, body).map_err(|e| e.to_string())?;
+    client
+        .post(format!("{}{path}", base_url.trim_end_matches('/')))
+        .json(&envelope)
+        .send()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "/soul/colony/train";
+
+    fn hosts() -> Vec<String> {
+        vec!["queen.example.com".to_string()]
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let signer = WalletSigner::random();
+        let body = serde_json::json!({ "examples": [1, 2, 3] });
+        let envelope = PeerEnvelope::seal(
+            &signer,
+            "worker-1",
+            "https://Queen.example.com/",
+            PATH,
+            &body,
+        )
+        .unwrap();
+        let now = envelope.timestamp;
+
+        assert_eq!(
+            envelope.open(&hosts(), PATH, now).unwrap(),
+            signer.address()
+        );
+        assert_eq!(envelope.body::<serde_json::Value>().unwrap(), body);
+
+        assert!(matches!(
+            envelope.open(&hosts(), "/soul/colony/report", now),
+            Err(EnvelopeError::WrongPath { .. })
+        ));
+        assert!(matches!(
+            envelope.open(&hosts(), PATH, now + MAX_SKEW_SECS + 1),
+            Err(EnvelopeError::Stale { .. })
+        ));
+
+        let mut tampered = envelope.clone();
+        tampered.body = r#"{"examples":[]}"#.to_string();
+        assert!(matches!(
+            tampered.open(&hosts(), PATH, now),
+            Err(EnvelopeError::SignerMismatch { .. })
+        ));
+
+        let mut impostor = envelope.clone();
+        impostor.address = WalletSigner::random().address_string();
+        assert!(impostor.open(&hosts(), PATH, now).is_err());
+    }
+
+    #[test]
+    fn envelopes_cannot_be_forwarded_to_another_node() {
+        let signer = WalletSigner::random();
+        let envelope =
+            PeerEnvelope::seal(&signer, "worker-1", "queen.example.com", PATH, &1).unwrap();
+        let now = envelope.timestamp;
+        let elsewhere = vec!["other.example.com".to_string()];
+        assert!(matches!(
+            envelope.open(&elsewhere, PATH, now),
+            Err(EnvelopeError::WrongRecipient(_))
+        ));
+
+        // Re-addressing it breaks the signature.
+        let mut forwarded = envelope.clone();
+        forwarded.recipient = "other.example.com".to_string();
+        assert!(matches!(
+            forwarded.open(&elsewhere, PATH, now),
+            Err(EnvelopeError::SignerMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn nonces_are_single_use() {
+        let cache = NonceCache::default();
+        let peer = WalletSigner::random().address();
+        cache.check(peer, "n1", 1_000, 1_000).unwrap();
+        assert!(matches!(
+            cache.check(peer, "n1", 1_000, 1_001),
+            Err(EnvelopeError::Replayed)
+        ));
+        cache.check(peer, "n2", 1_000, 1_001).unwrap();
+        cache
+            .check(WalletSigner::random().address(), "n1", 1_000, 1_001)
+            .unwrap();
+    }
+}
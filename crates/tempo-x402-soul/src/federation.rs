@@ -0,0 +1,492 @@
+//! Federated weight sharing across the colony.
+//!
+//! Every neural model (brain, plan transformer, unified, codegen) publishes a
+//! compressed delta of its recent training progress at
+//! `GET /soul/federated/{model}/delta`. A sync round fetches the deltas of
+//! all peers and merges them with `x402_model::federated::merge`: signature
+//! and shape checks, per-peer validation against a local held-out loss, then
+//! a coordinate-wise median so no single peer can poison the model.
+//!
+//! Deltas are signed with the publisher's instance key (EIP-191, as in
+//! [`envelope`](crate::envelope)). A sync round asks each peer for its
+//! wallet address at `/instance/info` and only accepts a delta whose
+//! provenance names that peer and whose signature recovers to that address,
+//! so one peer can't publish as another. Unsigned deltas are rejected
+//! unless the operator sets `FEDERATION_ALLOW_UNSIGNED=1`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use alloy::primitives::Address;
+use x402_model::{Checkpointable, DeltaBase, Federated, FederatedConfig, MergeReport, SparseDelta};
+
+use crate::db::SoulDatabase;
+
+/// Where publish bases and the last cut delta live.
+const FED_DIR: &str = "/tmp/federated";
+/// Fraction of coordinates sent per delta.
+const TOP_K: f32 = 0.01;
+/// Re-cut the published delta after this many local train steps.
+const REPUBLISH_STEPS: u64 = 20;
+/// Held-out examples used to validate peer deltas.
+const VALIDATION_EXAMPLES: usize = 8;
+
+/// A federated model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FedModel {
+    Brain,
+    Plan,
+    Unified,
+    Codegen,
+}
+
+impl FedModel {
+    pub const ALL: [FedModel; 4] = [
+        FedModel::Brain,
+        FedModel::Plan,
+        FedModel::Unified,
+        FedModel::Codegen,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FedModel::Brain => "brain",
+            FedModel::Plan => "plan",
+            FedModel::Unified => "unified",
+            FedModel::Codegen => "codegen",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.as_str() == s)
+    }
+
+    fn config(self) -> FederatedConfig {
+        FederatedConfig {
+            min_peer_steps: match self {
+                FedModel::Brain => 100,
+                _ => 50,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+fn base_path(model: FedModel) -> PathBuf {
+    PathBuf::from(FED_DIR).join(format!("{}.base.ckpt", model.as_str()))
+}
+
+fn delta_path(model: FedModel) -> PathBuf {
+    PathBuf::from(FED_DIR).join(format!("{}.delta", model.as_str()))
+}
+
+fn instance_id() -> String {
+    std::env::var("INSTANCE_ID").unwrap_or_else(|_| "self".into())
+}
+
+/// Whether the operator opted in to merging deltas that aren't signed by
+/// the peer they came from.
+fn allow_unsigned() -> bool {
+    std::env::var("FEDERATION_ALLOW_UNSIGNED").is_ok_and(|v| v == "1" || v == "true")
+}
+
+fn sign(delta: &mut SparseDelta) {
+    let Some(signer) = crate::envelope::signer() else {
+        return;
+    };
+    match signer.sign_message(&delta.signing_payload()) {
+        Ok(signature) => delta.provenance.signature = Some(signature),
+        Err(e) => tracing::warn!(error = %e, "Failed to sign federated delta"),
+    }
+}
+
+/// Whether `delta` is signed by the address of the peer its provenance
+/// names, per `identities` (peer id → wallet address).
+fn verify(identities: &HashMap<String, Address>, delta: &SparseDelta) -> bool {
+    let Some(&expected) = identities.get(&delta.provenance.peer_id) else {
+        return false;
+    };
+    delta
+        .provenance
+        .signature
+        .as_deref()
+        .and_then(|sig| alloy::hex::decode(sig.trim_start_matches("0x")).ok())
+        .and_then(|sig| x402::recover_message_signer(&delta.signing_payload(), &sig).ok())
+        .is_some_and(|signer| signer == expected)
+}
+
+/// The wallet address `peer_url` reports at `/instance/info`.
+async fn peer_address(http_client: &reqwest::Client, peer_url: &str) -> Option<Address> {
+    let info: serde_json::Value = http_client
+        .get(format!("{}/instance/info", peer_url.trim_end_matches('/')))
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+    info.get("identity")
+        .and_then(|i| i.get("address"))
+        .or_else(|| info.get("address"))?
+        .as_str()?
+        .parse()
+        .ok()
+}
+
+// ── Publishing ───────────────────────────────────────────────────────
+
+/// The encoded delta this node currently publishes for `model`, cutting a
+/// new one when the last is [`REPUBLISH_STEPS`] old. `None` until the model
+/// has trained since its first publication base was taken.
+pub fn published_delta(db: &SoulDatabase, model: FedModel) -> Option<Vec<u8>> {
+    match model {
+        FedModel::Brain => publish(model, &crate::brain::load_brain(db)),
+        FedModel::Plan => publish(model, &crate::model::load_model(db)),
+        FedModel::Unified => publish(model, &crate::unified_training::load_model(db)),
+        FedModel::Codegen => publish(model, &crate::codegen::load_model(db)),
+    }
+}
+
+fn publish<M: Federated>(kind: FedModel, model: &M) -> Option<Vec<u8>> {
+    let steps = model.train_steps();
+    let cached = std::fs::read(delta_path(kind))
+        .ok()
+        .and_then(|b| SparseDelta::from_bytes(&b).ok().map(|d| (b, d)))
+        .filter(|(_, d)| d.provenance.arch == model.arch() && d.provenance.train_steps <= steps);
+    if let Some((bytes, d)) = &cached {
+        if steps < d.provenance.train_steps + REPUBLISH_STEPS {
+            return Some(bytes.clone());
+        }
+    }
+
+    let mut base = DeltaBase::load_file(base_path(kind)).unwrap_or_else(|_| DeltaBase::of(model));
+    let delta = base.publish(model, &instance_id(), TOP_K);
+    let _ = std::fs::create_dir_all(FED_DIR);
+    if let Err(e) = base.save_file(base_path(kind)) {
+        tracing::warn!(model = kind.as_str(), error = %e, "Failed to save federation base");
+    }
+    let Some(mut delta) = delta else {
+        return cached.map(|(bytes, _)| bytes);
+    };
+    sign(&mut delta);
+    let bytes = delta.to_bytes();
+    let _ = std::fs::write(delta_path(kind), &bytes);
+    tracing::debug!(
+        model = kind.as_str(),
+        nnz = delta.nnz(),
+        bytes = bytes.len(),
+        steps,
+        "Published federated delta"
+    );
+    Some(bytes)
+}
+
+// ── Merging ──────────────────────────────────────────────────────────
+
+/// Fetch every peer's delta for each model and merge them robustly.
+/// Merging runs on a blocking thread (validation runs model forwards).
+pub async fn federated_round(
+    db: &Arc<SoulDatabase>,
+    peers: &[(String, String)],
+    http_client: &reqwest::Client,
+) {
+    let mut identities = HashMap::new();
+    for (peer_id, peer_url) in peers {
+        let Some(address) = peer_address(http_client, peer_url).await else {
+            continue;
+        };
+        // Two peers claiming one id: trust neither.
+        if identities.insert(peer_id.clone(), address).is_some_and(|a| a != address) {
+            tracing::warn!(peer = %peer_id, "Conflicting federation identities");
+            identities.insert(peer_id.clone(), Address::ZERO);
+        }
+    }
+    let identities = Arc::new(identities);
+
+    for model in FedModel::ALL {
+        let mut deltas = Vec::new();
+        for (peer_id, peer_url) in peers {
+            let url = format!(
+                "{}/soul/federated/{}/delta",
+                peer_url.trim_end_matches('/'),
+                model.as_str()
+            );
+            let resp = match http_client
+                .get(&url)
+                .timeout(std::time::Duration::from_secs(30))
+                .send()
+                .await
+            {
+                Ok(resp) if resp.status().is_success() => resp,
+                _ => continue,
+            };
+            let Ok(bytes) = resp.bytes().await else {
+                continue;
+            };
+            match SparseDelta::from_bytes(&bytes) {
+                Ok(d) if d.provenance.peer_id == *peer_id => deltas.push(d),
+                Ok(d) => tracing::warn!(
+                    peer = %peer_id,
+                    claimed = %d.provenance.peer_id,
+                    model = model.as_str(),
+                    "Federated delta names another peer"
+                ),
+                Err(e) => {
+                    tracing::debug!(peer = %peer_id, model = model.as_str(), error = %e, "Bad federated delta")
+                }
+            }
+        }
+        if deltas.is_empty() {
+            continue;
+        }
+
+        let db = db.clone();
+        let identities = identities.clone();
+        let merged =
+            tokio::task::spawn_blocking(move || merge_deltas(&db, model, &deltas, &identities))
+                .await;
+        if let Ok(Some(report)) = merged {
+            tracing::info!(
+                model = model.as_str(),
+                accepted = report.accepted.len(),
+                rejected = report.rejected.len(),
+                applied = report.applied,
+                coordinates = report.coordinates,
+                baseline_loss = format!("{:.4}", report.baseline_loss),
+                merged_loss = format!("{:.4}", report.merged_loss.unwrap_or(f32::NAN)),
+                "Federated merge"
+            );
+        }
+    }
+}
+
+/// Validate, aggregate and apply peer deltas to the local `model`, saving it
+/// if the merge was kept. Each delta must be signed by its peer's address in
+/// `identities` unless unsigned merging is allowed. `None` when there is no
+/// local validation data to judge peers by.
+pub fn merge_deltas(
+    db: &SoulDatabase,
+    model: FedModel,
+    deltas: &[SparseDelta],
+    identities: &HashMap<String, Address>,
+) -> Option<MergeReport> {
+    let config = model.config();
+    let unsigned_ok = allow_unsigned();
+    let accept = |d: &SparseDelta| unsigned_ok || verify(identities, d);
+    let report = match model {
+        FedModel::Brain => {
+            let mut examples = crate::brain::outcomes_to_examples(db);
+            examples.extend(crate::brain::events_to_examples(db));
+            examples.truncate(VALIDATION_EXAMPLES * 4);
+            if examples.is_empty() {
+                return None;
+            }
+            let mut brain = crate::brain::load_brain(db);
+            let report = x402_model::federated::merge(&mut brain, deltas, &config, accept, |b| {
+                mean(examples.iter().map(|e| b.loss(e)))
+            });
+            if report.applied {
+                crate::brain::save_brain(db, &brain);
+            }
+            report
+        }
+        FedModel::Plan => {
+            let outcomes = db.get_recent_plan_outcomes(20).unwrap_or_default();
+            let mut vocab = crate::model::load_vocab(db);
+            let mut examples = crate::model::outcome_examples(&outcomes, &mut vocab);
+            examples.truncate(VALIDATION_EXAMPLES);
+            if examples.is_empty() {
+                return None;
+            }
+            let mut plan = crate::model::load_model(db);
+            let report = x402_model::federated::merge(&mut plan, deltas, &config, accept, |m| {
+                x402_model::trainer::evaluate(m, &examples)
+            });
+            if report.applied {
+                crate::model::save_model(db, &plan);
+            }
+            report
+        }
+        FedModel::Unified => {
+            let mut unified = crate::unified_training::load_model(db);
+            let max_seq = unified.config.max_seq;
+            let pairs = solution_pairs(db, |tok, ctx| {
+                let mut t = vec![x402_model::unified::TASK_CODE];
+                t.extend(tok.encode(&format!("[CODE] {ctx}")));
+                t.truncate(max_seq);
+                t
+            });
+            if pairs.is_empty() {
+                return None;
+            }
+            let report = x402_model::federated::merge(&mut unified, deltas, &config, accept, |m| {
+                mean(pairs.iter().map(|(c, t)| m.slow_loss(c, t)))
+            });
+            if report.applied {
+                crate::unified_training::save_model(db, &unified);
+            }
+            report
+        }
+        FedModel::Codegen => {
+            let pairs = solution_pairs(db, |tok, ctx| {
+                let mut t = vec![x402_model::bpe::BOS_TOKEN];
+                t.extend(tok.encode(ctx));
+                t.push(x402_model::bpe::EOS_TOKEN);
+                t.truncate(x402_model::codegen::SMALL_MAX_SEQ);
+                t
+            });
+            if pairs.is_empty() {
+                return None;
+            }
+            let mut codegen = crate::codegen::load_model(db);
+            let report = x402_model::federated::merge(&mut codegen, deltas, &config, accept, |m| {
+                mean(pairs.iter().map(|(c, t)| m.enc_dec_loss(c, t)))
+            });
+            if report.applied {
+                crate::codegen::save_model(db, &codegen);
+            }
+            report
+        }
+    };
+    let _ = db.set_state(
+        &format!("federation_{}", model.as_str()),
+        &serde_json::to_string(&report).unwrap_or_default(),
+    );
+    Some(report)
+}
+
+/// The most recent verified benchmark solutions with test context, as
+/// (encoder context, 64-token decoder target) pairs.
+fn solution_pairs(
+    db: &SoulDatabase,
+    encode_context: impl Fn(&x402_model::bpe::BpeTokenizer, &str) -> Vec<u32>,
+) -> Vec<(Vec<u32>, Vec<u32>)> {
+    let tok = crate::codegen::load_tokenizer(db);
+    if tok.merges.is_empty() {
+        return Vec::new();
+    }
+    let solutions: Vec<serde_json::Value> = db
+        .get_state("codegen_solutions")
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    solutions
+        .iter()
+        .rev()
+        .filter(|s| s.get("passed").and_then(|v| v.as_bool()).unwrap_or(true))
+        .filter_map(|s| {
+            let code = s.get("code")?.as_str()?;
+            let ctx = s.get("context")?.as_str()?;
+            let mut target = vec![x402_model::bpe::BOS_TOKEN];
+            target.extend(tok.encode(code));
+            target.push(x402_model::bpe::EOS_TOKEN);
+            target.truncate(64);
+            (target.len() >= 3).then(|| (encode_context(&tok, ctx), target))
+        })
+        .take(VALIDATION_EXAMPLES / 2)
+        .collect()
+}
+
+fn mean(losses: impl Iterator<Item = f32>) -> f32 {
+    let (sum, n) = losses.fold((0.0f32, 0usize), |(s, n), l| (s + l, n + 1));
+    sum / n.max(1) as f32
+}
+
+/// Status for /soul/status API: last merge report per model.
+pub fn status(db: &SoulDatabase) -> serde_json::Value {
+    let mut out = serde_json::Map::new();
+    for model in FedModel::ALL {
+        let report = db
+            .get_state(&format!("federation_{}", model.as_str()))
+            .ok()
+            .flatten()
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok());
+        out.insert(model.as_str().to_string(), report.unwrap_or_default());
+    }
+    serde_json::json!({
+        "top_k": TOP_K,
+        "signed": crate::envelope::signer().is_some(),
+        "allow_unsigned": allow_unsigned(),
+        "models": out,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_names_roundtrip() {
+        for model in FedModel::ALL {
+            assert_eq!(FedModel::parse(model.as_str()), Some(model));
+        }
+        assert_eq!(FedModel::parse("cortex"), None);
+    }
+
+    #[test]
+    fn deltas_must_be_signed_by_the_named_peer() {
+        let mut trained = crate::brain::Brain::new();
+        let mut base = DeltaBase::of(&trained);
+        let example = crate::brain::TrainingExample {
+            features: vec![0.5; 128],
+            success: true,
+            error_category: None,
+            capability: crate::capability::Capability::CodeCompile,
+        };
+        for _ in 0..10 {
+            trained.train(&example);
+        }
+        let unsigned = base.publish(&trained, "peer-a", 0.05).unwrap();
+        let peer_a = x402::WalletSigner::random();
+        let signed_by = |signer: &x402::WalletSigner| {
+            let mut d = unsigned.clone();
+            d.provenance.signature = Some(signer.sign_message(&d.signing_payload()).unwrap());
+            d
+        };
+        let identities = HashMap::from([("peer-a".to_string(), peer_a.address())]);
+
+        assert!(verify(&identities, &signed_by(&peer_a)));
+        assert!(!verify(&identities, &unsigned));
+        // Another peer signing as peer-a.
+        assert!(!verify(&identities, &signed_by(&x402::WalletSigner::random())));
+        // Signed, then re-attributed.
+        let mut renamed = signed_by(&peer_a);
+        renamed.provenance.peer_id = "peer-b".to_string();
+        assert!(!verify(&identities, &renamed));
+        assert!(!verify(&HashMap::new(), &signed_by(&peer_a)));
+    }
+
+    #[test]
+    fn brain_delta_merges_into_a_peer() {
+        let mut trained = crate::brain::Brain::new();
+        let mut base = DeltaBase::of(&trained);
+        assert_eq!(trained.param_len(), trained.param_count());
+
+        let example = crate::brain::TrainingExample {
+            features: vec![0.5; 128],
+            success: true,
+            error_category: None,
+            capability: crate::capability::Capability::CodeCompile,
+        };
+        let mut peer = trained.clone();
+        for _ in 0..200 {
+            trained.train(&example);
+        }
+        let delta = base.publish(&trained, "peer-a", 0.05).unwrap();
+        let delta = SparseDelta::from_bytes(&delta.to_bytes()).unwrap();
+
+        let before = peer.loss(&example);
+        let report = x402_model::federated::merge(
+            &mut peer,
+            &[delta],
+            &FedModel::Brain.config(),
+            |_| true,
+            |b| b.loss(&example),
+        );
+        assert!(report.applied, "{report:?}");
+        assert!(peer.loss(&example) < before);
+    }
+}
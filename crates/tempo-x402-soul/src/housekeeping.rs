@@ -318,11 +318,12 @@ fn prune_soul_state(db: &Arc<SoulDatabase>) {
         ("hivemind_state", 5_000_000),    // 5MB — pheromone trails
         ("synthesis_state", 3_000_000),   // 3MB — metacognitive state
         ("codegen_solutions", 5_000_000), // 5MB — benchmark solutions
-        ("plan_transformer", 15_000_000), // 15MB — transformer weights
+        ("plan_transformer", 15_000_000), // 15MB — transformer checkpoint (~9MB binary)
         ("plan_transformer_vocab", 5_000_000), // 5MB — vocab
     ];
     for &(key, max_bytes) in large_blob_caps {
-        if let Ok(Some(val)) = db.get_state(key) {
+        // Raw bytes: model weights are stored as binary checkpoints.
+        if let Ok(Some(val)) = db.get_state_bytes(key) {
             if val.len() > max_bytes {
                 tracing::warn!(
                     key = key,
@@ -67,12 +67,15 @@ pub mod collective;
 pub mod colony;
 pub mod computer_use;
 pub mod config;
+pub mod contamination;
 pub mod cortex;
 pub mod db;
 pub mod elo;
+pub mod envelope;
 pub mod error;
 pub mod evaluation;
 pub mod events;
+pub mod federation;
 pub mod feedback;
 pub mod fitness;
 pub mod free_energy;
@@ -86,17 +89,21 @@ pub mod llm;
 pub mod memory;
 pub mod mode;
 pub mod model;
+pub mod model_eval;
 pub mod moe;
+pub mod mutation;
 pub mod neuroplastic;
 pub mod normalize;
 pub mod observer;
 pub mod opus_bench;
 pub mod persistent_memory;
 pub mod plan;
+pub mod problem_pack;
 pub mod prompts;
 pub mod robust_db;
 pub mod synthesis;
 pub mod temporal;
+pub mod test_report;
 pub mod thinking;
 pub mod tool_decl;
 pub mod tool_registry;
@@ -104,6 +111,7 @@ pub mod tools;
 pub mod toon;
 pub mod unified_training;
 pub mod validation;
+pub mod validation_pool;
 pub mod world_model;
 
 pub use chat::{handle_chat, handle_chat_stream, ChatReply};
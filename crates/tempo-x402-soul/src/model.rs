@@ -5,6 +5,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use x402_model::Checkpointable;
+
 use crate::db::SoulDatabase;
 
 // Re-export for node routes
@@ -23,40 +25,32 @@ pub struct ModelStatus {
 }
 
 /// Load the plan transformer from soul_state.
-/// If the saved model has different dimensions than the current architecture
-/// (e.g., after a model scaling), discard it and start fresh.
+/// Accepts binary checkpoints and legacy JSON. If the saved model has a
+/// different architecture than the current one (e.g., after a model scaling),
+/// discard it and start fresh.
 pub fn load_model(db: &SoulDatabase) -> x402_model::PlanTransformer {
-    match db.get_state("plan_transformer").ok().flatten() {
-        Some(json) if !json.is_empty() => {
-            match x402_model::PlanTransformer::from_json(&json) {
-                Some(model) => {
-                    // Validate dimensions match current architecture
-                    let expected_embedding =
-                        x402_model::vocab::VOCAB_SIZE * x402_model::transformer::D_MODEL;
-                    if model.embedding.len() != expected_embedding {
-                        tracing::warn!(
-                            saved_embedding = model.embedding.len(),
-                            expected = expected_embedding,
-                            "Transformer dimensions mismatch — reinitializing (architecture was scaled)"
-                        );
-                        // Clear stale weights from DB
-                        let _ = db.set_state("plan_transformer", "");
-                        x402_model::PlanTransformer::new()
-                    } else {
-                        model
-                    }
+    match db.get_state_bytes("plan_transformer").ok().flatten() {
+        Some(bytes) if !bytes.is_empty() => {
+            match x402_model::PlanTransformer::load_bytes(&bytes) {
+                Ok(model) => model,
+                Err(e) => {
+                    tracing::warn!(
+                        error = %e,
+                        "Transformer checkpoint rejected — reinitializing (architecture was scaled)"
+                    );
+                    // Clear stale weights from DB
+                    let _ = db.set_state("plan_transformer", "");
+                    x402_model::PlanTransformer::new()
                 }
-                None => x402_model::PlanTransformer::new(),
             }
         }
         _ => x402_model::PlanTransformer::new(),
     }
 }
 
-/// Save the plan transformer to soul_state.
+/// Save the plan transformer to soul_state as a binary checkpoint.
 pub fn save_model(db: &SoulDatabase, model: &x402_model::PlanTransformer) {
-    let json = model.to_json();
-    if let Err(e) = db.set_state("plan_transformer", &json) {
+    if let Err(e) = db.set_state_bytes("plan_transformer", &model.to_checkpoint()) {
         tracing::warn!(error = %e, "Failed to save plan transformer");
     }
 }
@@ -76,21 +70,14 @@ pub fn save_vocab(db: &SoulDatabase, vocab: &x402_model::Vocab) {
     }
 }
 
-/// Train the model on recent successful plan outcomes.
-/// Called every N cycles from the thinking loop.
-/// Returns (examples_trained, loss).
-pub fn train_from_outcomes(db: &SoulDatabase) -> (usize, f32) {
-    let outcomes = match db.get_recent_plan_outcomes(20) {
-        Ok(o) => o,
-        Err(_) => return (0, 0.0),
-    };
-
-    let mut model = load_model(db);
-    let mut vocab = load_vocab(db);
-
+/// Completed plan outcomes as (goal keywords → step tokens) examples.
+pub(crate) fn outcome_examples(
+    outcomes: &[crate::feedback::PlanOutcome],
+    vocab: &mut x402_model::Vocab,
+) -> Vec<x402_model::TrainingExample> {
     let mut examples: Vec<x402_model::TrainingExample> = Vec::new();
 
-    for outcome in &outcomes {
+    for outcome in outcomes {
         // Only train on completed (not trivial) plans
         if outcome.status != "completed" {
             continue;
@@ -123,6 +110,21 @@ pub fn train_from_outcomes(db: &SoulDatabase) -> (usize, f32) {
             source: "local".to_string(),
         });
     }
+    examples
+}
+
+/// Train the model on recent successful plan outcomes.
+/// Called every N cycles from the thinking loop.
+/// Returns (examples_trained, loss).
+pub fn train_from_outcomes(db: &SoulDatabase) -> (usize, f32) {
+    let outcomes = match db.get_recent_plan_outcomes(20) {
+        Ok(o) => o,
+        Err(_) => return (0, 0.0),
+    };
+
+    let mut model = load_model(db);
+    let mut vocab = load_vocab(db);
+    let examples = outcome_examples(&outcomes, &mut vocab);
 
     if examples.is_empty() {
         return (0, 0.0);
@@ -240,6 +242,50 @@ pub fn export_weights(db: &SoulDatabase) -> String {
     model.to_json()
 }
 
+/// Export transformer weights as a binary checkpoint for peer sharing.
+pub fn export_checkpoint(db: &SoulDatabase) -> Vec<u8> {
+    load_model(db).to_checkpoint()
+}
+
+/// Fetch a peer's plan transformer. Prefers the binary checkpoint endpoint
+/// (validated against the local architecture) and falls back to the JSON
+/// weights endpoint for peers that predate it.
+pub async fn fetch_peer_model(
+    http_client: &reqwest::Client,
+    peer_url: &str,
+) -> Option<x402_model::PlanTransformer> {
+    if let Ok(resp) = http_client
+        .get(format!("{peer_url}/soul/model/transformer/checkpoint"))
+        .timeout(std::time::Duration::from_secs(15))
+        .send()
+        .await
+    {
+        if resp.status().is_success() {
+            let bytes = resp.bytes().await.ok()?;
+            return match x402_model::PlanTransformer::load_bytes(&bytes) {
+                Ok(model) => Some(model),
+                Err(e) => {
+                    tracing::debug!(peer = %peer_url, error = %e, "Peer transformer checkpoint rejected");
+                    None
+                }
+            };
+        }
+    }
+
+    let resp = http_client
+        .get(format!("{peer_url}/soul/model/transformer/weights"))
+        .timeout(std::time::Duration::from_secs(15))
+        .send()
+        .await
+        .ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let data = resp.json::<serde_json::Value>().await.ok()?;
+    let weights_json = data.get("weights").and_then(|v| v.as_str())?;
+    x402_model::PlanTransformer::from_legacy_json(weights_json)
+}
+
 /// Merge a transformer weight delta from a peer.
 pub fn merge_peer_delta(db: &SoulDatabase, delta: &x402_model::TransformerDelta, merge_rate: f32) {
     let mut model = load_model(db);
@@ -0,0 +1,533 @@
+//! Offline evaluation of the unified model on a frozen held-out split.
+//!
+//! Training consumes data online, so two checkpoints were never scored on
+//! the same examples. This module fixes that:
+//!
+//! 1. **Split**: every example is assigned to train or held-out by a stable
+//!    hash of its identity ([`is_held_out`]); online training skips the
+//!    held-out share.
+//! 2. **Freeze**: [`HeldOutSet::build`] snapshots the held-out plan outcomes
+//!    and code examples (benchmark solutions, commit diffs, ...) together
+//!    with the tokenizer, so later runs see exactly the same tokens.
+//! 3. **Score**: fast-head calibration (Brier, accuracy per error category),
+//!    teacher-forced decoder perplexity, and pass@1/pass@k on Opus problems
+//!    through the `cargo test` validator.
+//!
+//! Reports are JSON so successive checkpoints can be diffed.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use x402_model::bpe::BpeTokenizer;
+use x402_model::unified::{ModelConfig, UnifiedModel};
+use x402_model::IncrementalDecoder;
+
+use crate::benchmark::{validate_solution, BenchmarkProblem};
+use crate::db::SoulDatabase;
+use crate::unified_training::{
+    code_context_tokens, code_target_tokens, fast_targets, predict_prompt, predict_tokens,
+    FAST_CATEGORIES,
+};
+
+/// Share of examples (in percent) held out from training.
+pub const HELD_OUT_PERCENT: u64 = 10;
+/// Decoder target length scored for perplexity (BOS and EOS included).
+const EVAL_TARGET_TOKENS: usize = 256;
+/// Generated tokens per pass@k sample.
+const SAMPLE_TOKENS: usize = 256;
+/// Plan outcomes scanned when building the held-out set.
+const MAX_OUTCOMES: u32 = 5000;
+
+// ── Split ───────────────────────────────────────────────────────────
+
+/// Whether the example identified by `key` belongs to the held-out split.
+/// Stable across processes and releases (FNV-1a), so an example never
+/// migrates between splits.
+pub fn is_held_out(key: &str) -> bool {
+    fnv1a(key.as_bytes()) % 100 < HELD_OUT_PERCENT
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// ── Frozen held-out set ─────────────────────────────────────────────
+
+/// A held-out plan outcome for the fast head.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FastExample {
+    pub key: String,
+    pub prompt: String,
+    pub success: bool,
+    /// Error category of a failed outcome (`ErrorCategory::as_str`).
+    pub category: Option<String>,
+}
+
+/// A held-out (context, code) pair for the decoder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecodeExample {
+    /// Where the code came from: `"benchmark"`, `"commit"`, ...
+    pub source: String,
+    /// Test code the solution answers, when known.
+    pub context: Option<String>,
+    pub code: String,
+}
+
+/// The frozen evaluation set: held-out examples plus the tokenizer they
+/// are encoded with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldOutSet {
+    pub created_at: String,
+    /// BPE tokenizer JSON at freeze time.
+    pub tokenizer: String,
+    pub fast: Vec<FastExample>,
+    pub decode: Vec<DecodeExample>,
+}
+
+impl HeldOutSet {
+    /// Collect the held-out share of the database's training data.
+    pub fn build(db: &SoulDatabase) -> Self {
+        let fast = db
+            .get_recent_plan_outcomes(MAX_OUTCOMES)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|o| is_held_out(&o.id) && matches!(o.status.as_str(), "completed" | "failed"))
+            .map(|o| FastExample {
+                prompt: predict_prompt(&o),
+                success: o.status == "completed",
+                category: o.error_category.as_ref().map(|c| c.as_str().to_string()),
+                key: o.id,
+            })
+            .collect();
+
+        let solutions: Vec<serde_json::Value> = db
+            .get_state("codegen_solutions")
+            .ok()
+            .flatten()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        let decode = solutions
+            .iter()
+            .filter_map(|sol| {
+                let code = sol.get("code")?.as_str()?;
+                (code.len() >= 50 && is_held_out(code)).then(|| DecodeExample {
+                    source: sol
+                        .get("source")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("unknown")
+                        .to_string(),
+                    context: sol
+                        .get("context")
+                        .and_then(|v| v.as_str())
+                        .map(String::from),
+                    code: code.to_string(),
+                })
+            })
+            .collect();
+
+        Self {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            tokenizer: crate::codegen::load_tokenizer(db).to_json(),
+            fast,
+            decode,
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        write_json(self, path)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("read {path}: {e}"))?;
+        serde_json::from_str(&json).map_err(|e| format!("parse {path}: {e}"))
+    }
+
+    pub fn tokenizer(&self) -> Result<BpeTokenizer, String> {
+        BpeTokenizer::from_json(&self.tokenizer)
+            .filter(|t| !t.merges.is_empty())
+            .ok_or_else(|| "held-out set has no trained tokenizer".to_string())
+    }
+}
+
+// ── Report ──────────────────────────────────────────────────────────
+
+/// Evaluation of one checkpoint against a frozen held-out set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub checkpoint: String,
+    pub heldout: String,
+    pub timestamp: String,
+    pub config: ModelConfig,
+    pub train_steps: u64,
+    pub fast: FastMetrics,
+    pub decoder: DecoderMetrics,
+    pub pass_at_k: Option<PassAtK>,
+}
+
+/// Fast-head calibration on held-out plan outcomes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FastMetrics {
+    pub examples: usize,
+    /// Mean squared error of the success probability.
+    pub brier: f64,
+    /// Success/failure accuracy at a 0.5 threshold.
+    pub accuracy: f64,
+    /// Accuracy of the predicted label (`"success"` or an error category)
+    /// per true label.
+    pub per_category: BTreeMap<String, CategoryMetrics>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CategoryMetrics {
+    pub examples: usize,
+    pub accuracy: f64,
+}
+
+/// Teacher-forced decoder likelihood on held-out code.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DecoderMetrics {
+    pub examples: usize,
+    /// Predicted tokens scored.
+    pub tokens: usize,
+    /// Mean negative log-likelihood per token (nats).
+    pub mean_nll: f64,
+    pub perplexity: f64,
+    /// Mean NLL per example source.
+    pub per_source: BTreeMap<String, f64>,
+}
+
+/// Functional correctness on benchmark problems.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PassAtK {
+    pub problems: usize,
+    pub samples_per_problem: usize,
+    pub k: usize,
+    pub pass_at_1: f64,
+    pub pass_at_k: f64,
+    pub per_problem: Vec<ProblemPasses>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProblemPasses {
+    pub slug: String,
+    pub tier: String,
+    pub samples: usize,
+    pub passed: usize,
+}
+
+// ── Metrics ─────────────────────────────────────────────────────────
+
+/// Label the fast head predicts: `"success"` or its most likely category.
+fn predicted_label(out: &[f32]) -> &'static str {
+    if out[0] >= 0.5 {
+        return "success";
+    }
+    let idx = (0..FAST_CATEGORIES.len())
+        .max_by(|&a, &b| out[1 + a].total_cmp(&out[1 + b]))
+        .unwrap_or(FAST_CATEGORIES.len() - 1);
+    FAST_CATEGORIES[idx]
+}
+
+/// Brier score and accuracies of the fast head.
+pub fn evaluate_fast(
+    model: &UnifiedModel,
+    tok: &BpeTokenizer,
+    examples: &[FastExample],
+) -> FastMetrics {
+    let mut m = FastMetrics::default();
+    let mut correct = 0usize;
+    let mut brier = 0.0f64;
+    let mut per_category: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for ex in examples {
+        let out = model.fast_predict(&predict_tokens(tok, &ex.prompt));
+        let p = out[0].clamp(0.0, 1.0) as f64;
+        let y = if ex.success { 1.0 } else { 0.0 };
+        brier += (p - y).powi(2);
+        correct += usize::from((p >= 0.5) == ex.success);
+
+        // Same folding of unknown categories as the training targets.
+        let targets = fast_targets(ex.success, ex.category.as_deref());
+        let truth = predicted_label(&targets);
+        let entry = per_category.entry(truth.to_string()).or_default();
+        entry.0 += 1;
+        entry.1 += usize::from(predicted_label(&out) == truth);
+        m.examples += 1;
+    }
+    if m.examples > 0 {
+        m.brier = brier / m.examples as f64;
+        m.accuracy = correct as f64 / m.examples as f64;
+    }
+    m.per_category = per_category
+        .into_iter()
+        .map(|(label, (n, ok))| {
+            let metrics = CategoryMetrics {
+                examples: n,
+                accuracy: ok as f64 / n as f64,
+            };
+            (label, metrics)
+        })
+        .collect();
+    m
+}
+
+/// Sum of `-ln p(next token)` over `target` given the encoded context.
+fn sequence_nll(model: &UnifiedModel, context: &[u32], target: &[u32]) -> (f64, usize) {
+    let enc = model.encode(context);
+    let mut cache = model.start(&enc, context.len().min(model.config.max_seq));
+    let target = &target[..target.len().min(model.max_seq())];
+    let mut nll = 0.0f64;
+    for pair in target.windows(2) {
+        let logits = model.step(&mut cache, pair[0]);
+        let next = pair[1] as usize % logits.len();
+        let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let log_z = max as f64
+            + logits
+                .iter()
+                .map(|&l| ((l - max) as f64).exp())
+                .sum::<f64>()
+                .ln();
+        nll += log_z - logits[next] as f64;
+    }
+    (nll, target.len().saturating_sub(1))
+}
+
+/// Per-token perplexity of the decoder on held-out code.
+pub fn evaluate_decoder(
+    model: &UnifiedModel,
+    tok: &BpeTokenizer,
+    examples: &[DecodeExample],
+) -> DecoderMetrics {
+    let mut m = DecoderMetrics::default();
+    let mut total = 0.0f64;
+    let mut per_source: BTreeMap<String, (f64, usize)> = BTreeMap::new();
+    for ex in examples {
+        let context =
+            code_context_tokens(tok, ex.context.as_deref(), &ex.code, model.config.max_seq);
+        let target = code_target_tokens(tok, &ex.code, EVAL_TARGET_TOKENS);
+        let (nll, tokens) = sequence_nll(model, &context, &target);
+        if tokens == 0 {
+            continue;
+        }
+        total += nll;
+        m.tokens += tokens;
+        m.examples += 1;
+        let entry = per_source.entry(ex.source.clone()).or_default();
+        entry.0 += nll;
+        entry.1 += tokens;
+    }
+    if m.tokens > 0 {
+        m.mean_nll = total / m.tokens as f64;
+        m.perplexity = m.mean_nll.exp();
+    }
+    m.per_source = per_source
+        .into_iter()
+        .map(|(source, (nll, n))| (source, nll / n as f64))
+        .collect();
+    m
+}
+
+/// Unbiased pass@k estimate from `n` samples of which `c` passed:
+/// `1 - C(n-c, k) / C(n, k)`.
+pub fn pass_at_k_estimate(n: usize, c: usize, k: usize) -> f64 {
+    if k == 0 || n == 0 {
+        return 0.0;
+    }
+    if n - c < k {
+        return 1.0;
+    }
+    let fail_all = ((n - c + 1)..=n).fold(1.0f64, |acc, i| acc * (1.0 - k as f64 / i as f64));
+    1.0 - fail_all
+}
+
+/// Sample `samples` solutions per problem and validate each with `cargo test`.
+pub async fn evaluate_pass_at_k(
+    model: &UnifiedModel,
+    tok: &BpeTokenizer,
+    problems: &[BenchmarkProblem],
+    samples: usize,
+    k: usize,
+    seed: u64,
+) -> PassAtK {
+    let k = k.clamp(1, samples.max(1));
+    let mut per_problem = Vec::with_capacity(problems.len());
+    for (i, problem) in problems.iter().enumerate() {
+        let context = code_context_tokens(
+            tok,
+            Some(&problem.test_code),
+            &problem.starter_code,
+            model.config.max_seq,
+        );
+        let enc = model.encode(&context);
+        let config = x402_model::DecodeConfig {
+            strategy: x402_model::Strategy::TopP {
+                p: 0.95,
+                temperature: 0.8,
+            },
+            max_tokens: SAMPLE_TOKENS,
+            stop_tokens: vec![x402_model::bpe::EOS_TOKEN],
+            num_candidates: samples,
+            seed: seed.wrapping_add((i * samples) as u64),
+            ..Default::default()
+        };
+        let candidates = x402_model::decoding::generate(
+            model,
+            &enc,
+            context.len(),
+            &[x402_model::bpe::BOS_TOKEN],
+            &config,
+        );
+        let mut passed = 0;
+        for candidate in &candidates {
+            let code = tok.decode(&candidate.tokens);
+            if !code.trim().is_empty() && validate_solution(problem, &code, "/tmp").await.0 {
+                passed += 1;
+            }
+        }
+        tracing::info!(slug = %problem.slug, samples, passed, "pass@k problem scored");
+        per_problem.push(ProblemPasses {
+            slug: problem.slug.clone(),
+            tier: problem.difficulty.clone(),
+            samples,
+            passed,
+        });
+    }
+
+    let mean = |f: &dyn Fn(&ProblemPasses) -> f64| {
+        per_problem.iter().map(f).sum::<f64>() / per_problem.len().max(1) as f64
+    };
+    PassAtK {
+        problems: per_problem.len(),
+        samples_per_problem: samples,
+        k,
+        pass_at_1: mean(&|p| pass_at_k_estimate(p.samples, p.passed, 1)),
+        pass_at_k: mean(&|p| pass_at_k_estimate(p.samples, p.passed, k)),
+        per_problem,
+    }
+}
+
+/// Score a checkpoint on a frozen held-out set. `samples == 0` skips pass@k.
+pub async fn evaluate(
+    checkpoint: &str,
+    heldout_path: &str,
+    problems: &[BenchmarkProblem],
+    samples: usize,
+    k: usize,
+) -> Result<EvalReport, String> {
+    use x402_model::Checkpointable;
+    let model = UnifiedModel::load_file(checkpoint).map_err(|e| format!("{checkpoint}: {e}"))?;
+    let set = HeldOutSet::load(heldout_path)?;
+    let tok = set.tokenizer()?;
+
+    let fast = evaluate_fast(&model, &tok, &set.fast);
+    let decoder = evaluate_decoder(&model, &tok, &set.decode);
+    let pass_at_k = if samples > 0 && !problems.is_empty() {
+        Some(evaluate_pass_at_k(&model, &tok, problems, samples, k, 0).await)
+    } else {
+        None
+    };
+    Ok(EvalReport {
+        checkpoint: checkpoint.to_string(),
+        heldout: heldout_path.to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        config: model.config,
+        train_steps: model.train_steps,
+        fast,
+        decoder,
+        pass_at_k,
+    })
+}
+
+impl EvalReport {
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        write_json(self, path)
+    }
+}
+
+fn write_json(value: &impl Serialize, path: &str) -> Result<(), String> {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("mkdir: {e}"))?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|e| format!("serialize: {e}"))?;
+    std::fs::write(path, json).map_err(|e| format!("write {path}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_is_stable_and_roughly_ten_percent() {
+        assert_eq!(is_held_out("plan-42"), is_held_out("plan-42"));
+        let held = (0..10_000)
+            .filter(|i| is_held_out(&format!("outcome-{i}")))
+            .count();
+        assert!((800..1200).contains(&held), "{held}");
+    }
+
+    #[test]
+    fn pass_at_k_estimator() {
+        assert_eq!(pass_at_k_estimate(5, 0, 1), 0.0);
+        assert_eq!(pass_at_k_estimate(5, 5, 1), 1.0);
+        assert!((pass_at_k_estimate(10, 3, 1) - 0.3).abs() < 1e-12);
+        // 1 - C(8,2)/C(10,2) = 1 - 28/45
+        assert!((pass_at_k_estimate(10, 2, 2) - 17.0 / 45.0).abs() < 1e-12);
+        assert_eq!(pass_at_k_estimate(4, 1, 4), 1.0);
+    }
+
+    #[test]
+    fn scores_a_small_model() {
+        let config = ModelConfig {
+            d_model: 32,
+            n_heads: 2,
+            d_ff: 64,
+            max_seq: 64,
+            enc_layers: 1,
+            dec_layers: 1,
+            ..Default::default()
+        };
+        let model = UnifiedModel::with_config(config);
+        let mut tok = BpeTokenizer::new(300);
+        tok.train("fn add(a: i32, b: i32) -> i32 { a + b } fn main() { add(1, 2); }");
+
+        let fast = evaluate_fast(
+            &model,
+            &tok,
+            &[
+                FastExample {
+                    key: "a".into(),
+                    prompt: "[PREDICT] goal=fix build".into(),
+                    success: true,
+                    category: None,
+                },
+                FastExample {
+                    key: "b".into(),
+                    prompt: "[PREDICT] goal=add tests".into(),
+                    success: false,
+                    category: Some("rate_limit".into()),
+                },
+            ],
+        );
+        assert_eq!(fast.examples, 2);
+        assert!((0.0..=1.0).contains(&fast.brier));
+        assert_eq!(fast.per_category["success"].examples, 1);
+        assert_eq!(fast.per_category["other"].examples, 1);
+
+        let decoder = evaluate_decoder(
+            &model,
+            &tok,
+            &[DecodeExample {
+                source: "commit".into(),
+                context: None,
+                code: "fn add(a: i32, b: i32) -> i32 { a + b }".repeat(2),
+            }],
+        );
+        assert_eq!(decoder.examples, 1);
+        assert!(decoder.tokens > 2);
+        // An untrained model is close to uniform over the vocabulary.
+        let uniform = (config.vocab as f64).ln();
+        assert!((decoder.mean_nll - uniform).abs() < 1.0, "{decoder:?}");
+        assert!(decoder.per_source.contains_key("commit"));
+    }
+}
@@ -0,0 +1,908 @@
+//! Debugging problems generated by mutating passing solutions.
+//!
+//! Tier-2 problems hand the model buggy code plus the tests that catch the
+//! bug. Instead of writing them by hand, [`mutants`] applies one small,
+//! Rust-aware change to a solution that passes: an off-by-one, a flipped
+//! comparison or boolean operator, swapped call arguments, or a removed
+//! branch. [`debugging_problems`] runs the mutants through the validation
+//! pool and keeps those that still compile but fail at least one test. Each
+//! kept mutant becomes a problem whose known fix is the original solution,
+//! which makes it a verified (buggy code → fix) training pair.
+//!
+//! Mutations work on tokens rather than a full parse: comments and literals
+//! are never touched, and operators are only mutated when written with
+//! spaces on both sides (rustfmt style), which tells `a < b` from `Vec<T>`.
+//! Mutants that don't compile are filtered out by validation anyway.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::benchmark::BenchmarkProblem;
+use crate::db::SoulDatabase;
+use crate::validation_pool::{CancelToken, Cancelled, ValidationPool};
+
+/// Mutants validated per debugging problem requested, before giving up on
+/// a solution whose mutants mostly don't compile or aren't caught.
+const ATTEMPTS_PER_KEPT: usize = 4;
+
+/// Slug infix of generated problems: `{slug}-bug-{hash}`.
+pub const SLUG_INFIX: &str = "-bug-";
+
+/// Kind of change applied to a solution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MutationKind {
+    /// Integer literal ±1, `<` ↔ `<=`, `>` ↔ `>=`, `..` ↔ `..=`.
+    OffByOne,
+    /// `<` ↔ `>`, `<=` ↔ `>=`, `==` ↔ `!=`.
+    FlipComparison,
+    /// `&&` ↔ `||`.
+    FlipLogic,
+    /// Two adjacent call arguments exchanged.
+    SwapArguments,
+    /// An `else` block or a statement-level `if` deleted.
+    RemoveBranch,
+}
+
+/// A solution with one mutation applied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mutant {
+    pub kind: MutationKind,
+    /// 1-based line of the mutation in the original solution.
+    pub line: usize,
+    /// What changed, e.g. ``"`<` → `<=`"``.
+    pub description: String,
+    pub source: String,
+}
+
+/// A generated debugging problem and the original solution that fixes it.
+#[derive(Debug, Clone)]
+pub struct DebugProblem {
+    pub problem: BenchmarkProblem,
+    pub fix: String,
+    pub mutant: Mutant,
+    /// Tests the mutant fails.
+    pub failing_tests: Vec<String>,
+}
+
+/// Every single-mutation variant of `source`, in source order, without
+/// duplicates or no-op changes.
+pub fn mutants(source: &str) -> Vec<Mutant> {
+    let tokens = tokenize(source);
+    let mut edits = Vec::new();
+    for i in 0..tokens.len() {
+        operator_edits(source, &tokens, i, &mut edits);
+        literal_edits(source, &tokens, i, &mut edits);
+        swap_edits(source, &tokens, i, &mut edits);
+        branch_edits(source, &tokens, i, &mut edits);
+    }
+
+    let mut seen = HashSet::new();
+    edits
+        .into_iter()
+        .filter_map(|edit| {
+            let mut mutated = String::with_capacity(source.len());
+            mutated.push_str(&source[..edit.start]);
+            mutated.push_str(&edit.replacement);
+            mutated.push_str(&source[edit.end..]);
+            if mutated == source || !seen.insert(mutated.clone()) {
+                return None;
+            }
+            Some(Mutant {
+                kind: edit.kind,
+                line: source[..edit.start].matches('\n').count() + 1,
+                description: edit.description,
+                source: mutated,
+            })
+        })
+        .collect()
+}
+
+/// `mutants` reordered for sampling: shuffled by `seed` within each kind,
+/// then interleaved so every kind is tried early.
+pub fn sample_order(mutants: Vec<Mutant>, seed: u64) -> Vec<Mutant> {
+    let mut by_kind: BTreeMap<MutationKind, Vec<([u8; 8], Mutant)>> = BTreeMap::new();
+    for mutant in mutants {
+        let digest = Sha256::new()
+            .chain_update(seed.to_le_bytes())
+            .chain_update(&mutant.source)
+            .finalize();
+        let mut key = [0u8; 8];
+        key.copy_from_slice(&digest[..8]);
+        by_kind.entry(mutant.kind).or_default().push((key, mutant));
+    }
+    let mut queues: Vec<VecDeque<Mutant>> = by_kind
+        .into_values()
+        .map(|mut group| {
+            group.sort_by_key(|(key, _)| *key);
+            group.into_iter().map(|(_, m)| m).collect()
+        })
+        .collect();
+
+    let mut ordered = Vec::new();
+    while queues.iter().any(|q| !q.is_empty()) {
+        for queue in &mut queues {
+            ordered.extend(queue.pop_front());
+        }
+    }
+    ordered
+}
+
+/// Up to `max` debugging problems derived from `solution`. The solution is
+/// first checked against `problem`'s tests; one that doesn't pass yields
+/// nothing. Mutants are tried in [`sample_order`] and kept when they compile
+/// and fail at least one test.
+pub async fn debugging_problems(
+    problem: &BenchmarkProblem,
+    solution: &str,
+    max: usize,
+    seed: u64,
+    pool: &ValidationPool,
+    cancel: &CancelToken,
+) -> Result<Vec<DebugProblem>, Cancelled> {
+    if max == 0 {
+        return Ok(Vec::new());
+    }
+    if !pool.validate(problem, solution, cancel).await?.passed {
+        tracing::debug!(slug = %problem.slug, "Solution fails its own tests — not mutating");
+        return Ok(Vec::new());
+    }
+
+    let candidates: Vec<(Mutant, BenchmarkProblem)> = sample_order(mutants(solution), seed)
+        .into_iter()
+        .take(max * ATTEMPTS_PER_KEPT)
+        .map(|mutant| {
+            let debug = mutant_problem(problem, &mutant);
+            (mutant, debug)
+        })
+        .collect();
+    let checks: Vec<_> = candidates
+        .iter()
+        .map(|(mutant, debug)| pool.validate(debug, &mutant.source, cancel))
+        .collect();
+    let mut results = futures::stream::iter(checks).buffered(pool.workers());
+
+    let mut kept = Vec::new();
+    let mut tried = 0;
+    while let Some(validation) = results.next().await {
+        let (mutant, debug) = &candidates[tried];
+        tried += 1;
+        let validation = validation?;
+        let report = &validation.report;
+        if validation.passed || !report.compiled() || report.failed() == 0 {
+            continue;
+        }
+        let failing_tests: Vec<String> = report
+            .tests
+            .iter()
+            .filter(|t| t.outcome == crate::test_report::TestOutcome::Failed)
+            .map(|t| t.name.clone())
+            .collect();
+        let mut debug = debug.clone();
+        debug.instructions = debug_instructions(problem, &mutant.source, &failing_tests);
+        kept.push(DebugProblem {
+            problem: debug,
+            fix: solution.to_string(),
+            mutant: mutant.clone(),
+            failing_tests,
+        });
+        if kept.len() == max {
+            break;
+        }
+    }
+    tracing::info!(
+        slug = %problem.slug,
+        tried,
+        kept = kept.len(),
+        "Generated debugging problems from mutants"
+    );
+    Ok(kept)
+}
+
+/// Passing solutions from [`export_solutions`](crate::benchmark::export_solutions)
+/// (ours and imported), paired with their problems. One per slug; solutions
+/// to problems not in `problems` are skipped.
+pub fn solved_problems(
+    db: &SoulDatabase,
+    problems: &[BenchmarkProblem],
+) -> Vec<(BenchmarkProblem, String)> {
+    let mut seen = HashSet::new();
+    crate::benchmark::export_solutions(db)
+        .into_iter()
+        .filter_map(|s| {
+            let problem = problems.iter().find(|p| p.slug == s.entry_point)?;
+            seen.insert(problem.slug.clone())
+                .then(|| (problem.clone(), s.solution))
+        })
+        .collect()
+}
+
+/// Whether `slug` names a generated debugging problem.
+pub fn is_generated(slug: &str) -> bool {
+    slug.contains(SLUG_INFIX)
+}
+
+/// `problem` renamed for a mutant: same tests and manifest, with the starter
+/// set to the buggy code. Instructions are filled in once the failing tests
+/// are known.
+fn mutant_problem(problem: &BenchmarkProblem, mutant: &Mutant) -> BenchmarkProblem {
+    let hash = Sha256::digest(mutant.source.as_bytes());
+    let suffix: String = hash[..4].iter().map(|b| format!("{b:02x}")).collect();
+    let slug = format!("{}{SLUG_INFIX}{suffix}", problem.slug);
+    // Without a custom manifest the crate is named after the slug, so the
+    // tests' `use {crate}::` paths follow the rename.
+    let test_code = if problem.cargo_toml.is_empty() {
+        problem.test_code.replace(
+            &format!("{}::", problem.slug.replace('-', "_")),
+            &format!("{}::", slug.replace('-', "_")),
+        )
+    } else {
+        problem.test_code.clone()
+    };
+    BenchmarkProblem {
+        slug,
+        instructions: String::new(),
+        test_code,
+        starter_code: mutant.source.clone(),
+        difficulty: "tier2".to_string(),
+        cargo_toml: problem.cargo_toml.clone(),
+        pack: String::new(),
+    }
+}
+
+fn debug_instructions(problem: &BenchmarkProblem, buggy: &str, failing_tests: &[String]) -> String {
+    format!(
+        "The following solution has a bug. Fix it so all tests pass.\n\n\
+         Original task ({}):\n{}\n\n```rust\n{}\n```\n\nFailing tests: {}",
+        problem.slug,
+        problem.instructions.trim(),
+        buggy.trim_end(),
+        failing_tests.join(", ")
+    )
+}
+
+// ── Tokens ──────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Ident,
+    /// Decimal integer literal, optionally suffixed (`3`, `10_000usize`).
+    Int,
+    /// String, char, float and other literals.
+    Literal,
+    Lifetime,
+    Punct,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.start..self.end]
+    }
+}
+
+/// Multi-character punctuation, longest first.
+const PUNCTS: &[&str] = &[
+    "<<=", ">>=", "...", "..=", "::", "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "^=", "&=", "|=", "<<", ">>", "..",
+];
+
+const KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "else", "enum", "fn", "for", "if", "impl", "in", "let",
+    "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static", "struct", "trait",
+    "type", "unsafe", "use", "where", "while",
+];
+
+const INT_SUFFIXES: &[&str] = &[
+    "", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+/// Split `source` into tokens, skipping whitespace and comments.
+fn tokenize(source: &str) -> Vec<Token> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let b = bytes[i];
+        if b.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if bytes[i..].starts_with(b"//") {
+            i = bytes[i..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(bytes.len(), |n| i + n);
+            continue;
+        }
+        if bytes[i..].starts_with(b"/*") {
+            i = block_comment_end(bytes, i);
+            continue;
+        }
+        let kind = if let Some(end) = prefixed_literal(bytes, i) {
+            i = end;
+            TokenKind::Literal
+        } else if b == b'"' {
+            i = quoted_end(bytes, i + 1, b'"');
+            TokenKind::Literal
+        } else if b == b'\'' {
+            let (end, kind) = quote_or_lifetime(source, i);
+            i = end;
+            kind
+        } else if b.is_ascii_digit() {
+            i = number_end(bytes, i);
+            if INT_SUFFIXES.contains(&split_int(&source[start..i]).1) {
+                TokenKind::Int
+            } else {
+                TokenKind::Literal
+            }
+        } else if b == b'_' || b.is_ascii_alphabetic() || !b.is_ascii() {
+            i += source[i..]
+                .char_indices()
+                .find(|&(_, c)| !(c == '_' || c.is_alphanumeric()))
+                .map_or(source.len() - i, |(n, _)| n);
+            if i == start {
+                // A non-ASCII character that isn't part of an identifier.
+                i += source[i..].chars().next().map_or(1, char::len_utf8);
+                TokenKind::Punct
+            } else {
+                TokenKind::Ident
+            }
+        } else {
+            i += PUNCTS
+                .iter()
+                .find(|p| bytes[i..].starts_with(p.as_bytes()))
+                .map_or(1, |p| p.len());
+            TokenKind::Punct
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: i,
+        });
+    }
+    tokens
+}
+
+fn block_comment_end(bytes: &[u8], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else if bytes[i..].starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+/// End of a quoted literal whose body starts at `i`, honouring escapes.
+fn quoted_end(bytes: &[u8], mut i: usize, quote: u8) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// End of a `b"…"`, `b'…'`, `c"…"` or raw (`r#"…"#`, `br"…"`) literal
+/// starting at `i`, if there is one.
+fn prefixed_literal(bytes: &[u8], i: usize) -> Option<usize> {
+    let mut j = i;
+    let byte = matches!(bytes[j], b'b' | b'c');
+    if byte {
+        j += 1;
+    }
+    let raw = bytes.get(j) == Some(&b'r');
+    if raw {
+        j += 1;
+    }
+    if j == i {
+        return None;
+    }
+    if !raw {
+        return match bytes.get(j) {
+            Some(b'"') => Some(quoted_end(bytes, j + 1, b'"')),
+            Some(b'\'') if bytes[i] == b'b' => Some(quoted_end(bytes, j + 1, b'\'')),
+            _ => None,
+        };
+    }
+    let hashes = bytes[j..].iter().take_while(|&&b| b == b'#').count();
+    j += hashes;
+    if bytes.get(j) != Some(&b'"') {
+        return None;
+    }
+    let closing: Vec<u8> = std::iter::once(b'"')
+        .chain(std::iter::repeat_n(b'#', hashes))
+        .collect();
+    Some(
+        bytes[j + 1..]
+            .windows(closing.len())
+            .position(|w| w == closing.as_slice())
+            .map_or(bytes.len(), |n| j + 1 + n + closing.len()),
+    )
+}
+
+/// A char literal (`'a'`, `'\n'`) or a lifetime (`'a`) starting at `i`.
+fn quote_or_lifetime(source: &str, i: usize) -> (usize, TokenKind) {
+    let bytes = source.as_bytes();
+    if bytes.get(i + 1) == Some(&b'\\') {
+        return (quoted_end(bytes, i + 1, b'\''), TokenKind::Literal);
+    }
+    let Some(c) = source[i + 1..].chars().next() else {
+        return (bytes.len(), TokenKind::Punct);
+    };
+    let after = i + 1 + c.len_utf8();
+    if bytes.get(after) == Some(&b'\'') {
+        return (after + 1, TokenKind::Literal);
+    }
+    let end = source[i + 1..]
+        .char_indices()
+        .find(|&(_, c)| !(c == '_' || c.is_alphanumeric()))
+        .map_or(source.len(), |(n, _)| i + 1 + n);
+    (end, TokenKind::Lifetime)
+}
+
+/// End of a numeric literal; a `.` is only part of it when a digit follows,
+/// so `0..n` stays a range.
+fn number_end(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        let b = bytes[i];
+        let fraction = b == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+        if b.is_ascii_alphanumeric() || b == b'_' || fraction {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+/// Leading digits (with `_` separators) and the rest, e.g. `usize`.
+fn split_int(text: &str) -> (&str, &str) {
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '_'))
+        .unwrap_or(text.len());
+    text.split_at(split)
+}
+
+// ── Mutation operators ──────────────────────────────────────────────────
+
+struct Edit {
+    kind: MutationKind,
+    start: usize,
+    end: usize,
+    replacement: String,
+    description: String,
+}
+
+impl Edit {
+    fn replace(kind: MutationKind, source: &str, token: &Token, replacement: &str) -> Self {
+        Self {
+            kind,
+            start: token.start,
+            end: token.end,
+            replacement: replacement.to_string(),
+            description: format!("`{}` → `{replacement}`", token.text(source)),
+        }
+    }
+}
+
+/// Whether `token` has whitespace on both sides, i.e. is a binary operator
+/// as rustfmt writes it rather than part of a generic or closure.
+fn spaced(source: &str, token: &Token) -> bool {
+    let bytes = source.as_bytes();
+    token.start > 0
+        && bytes[token.start - 1].is_ascii_whitespace()
+        && bytes.get(token.end).is_some_and(u8::is_ascii_whitespace)
+}
+
+fn operator_edits(source: &str, tokens: &[Token], i: usize, edits: &mut Vec<Edit>) {
+    use MutationKind::*;
+    let token = &tokens[i];
+    if token.kind != TokenKind::Punct {
+        return;
+    }
+    let replacements: &[(MutationKind, &str)] = match token.text(source) {
+        "<" => &[(OffByOne, "<="), (FlipComparison, ">")],
+        "<=" => &[(OffByOne, "<"), (FlipComparison, ">=")],
+        ">" => &[(OffByOne, ">="), (FlipComparison, "<")],
+        ">=" => &[(OffByOne, ">"), (FlipComparison, "<=")],
+        "==" => &[(FlipComparison, "!=")],
+        "!=" => &[(FlipComparison, "==")],
+        "&&" => &[(FlipLogic, "||")],
+        // `move || …` is a closure, not a boolean or.
+        "||" if i > 0 && tokens[i - 1].text(source) != "move" => &[(FlipLogic, "&&")],
+        ".." | "..=" => return range_edits(source, tokens, i, edits),
+        _ => return,
+    };
+    if !spaced(source, token) {
+        return;
+    }
+    for &(kind, replacement) in replacements {
+        edits.push(Edit::replace(kind, source, token, replacement));
+    }
+}
+
+/// `a..b` ↔ `a..=b`, for ranges with both bounds.
+fn range_edits(source: &str, tokens: &[Token], i: usize, edits: &mut Vec<Edit>) {
+    let bounded = |t: Option<&Token>| {
+        t.is_some_and(|t| {
+            matches!(t.kind, TokenKind::Ident | TokenKind::Int)
+                || matches!(t.text(source), "(" | ")")
+        })
+    };
+    if i == 0 || !bounded(tokens.get(i - 1)) || !bounded(tokens.get(i + 1)) {
+        return;
+    }
+    let token = &tokens[i];
+    let replacement = if token.text(source) == ".." {
+        "..="
+    } else {
+        ".."
+    };
+    edits.push(Edit::replace(
+        MutationKind::OffByOne,
+        source,
+        token,
+        replacement,
+    ));
+}
+
+/// Integer literal ±1, keeping its suffix. Tuple fields (`.0`) are skipped.
+fn literal_edits(source: &str, tokens: &[Token], i: usize, edits: &mut Vec<Edit>) {
+    let token = &tokens[i];
+    if token.kind != TokenKind::Int || (i > 0 && tokens[i - 1].text(source) == ".") {
+        return;
+    }
+    let (digits, suffix) = split_int(token.text(source));
+    let Ok(value) = digits.replace('_', "").parse::<u128>() else {
+        return;
+    };
+    for changed in [value.checked_add(1), value.checked_sub(1)]
+        .into_iter()
+        .flatten()
+    {
+        edits.push(Edit::replace(
+            MutationKind::OffByOne,
+            source,
+            token,
+            &format!("{changed}{suffix}"),
+        ));
+    }
+}
+
+/// Index of the bracket closing the one at `open`.
+fn matching(source: &str, tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (j, token) in tokens.iter().enumerate().skip(open) {
+        match token.text(source) {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(j);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Swap each pair of adjacent arguments of a call `name(a, b, …)`.
+fn swap_edits(source: &str, tokens: &[Token], i: usize, edits: &mut Vec<Edit>) {
+    if i == 0 || tokens[i].text(source) != "(" {
+        return;
+    }
+    let callee = &tokens[i - 1];
+    if callee.kind != TokenKind::Ident
+        || KEYWORDS.contains(&callee.text(source))
+        || (i >= 2 && tokens[i - 2].text(source) == "fn")
+    {
+        return;
+    }
+    let Some(close) = matching(source, tokens, i) else {
+        return;
+    };
+
+    // Top-level arguments as token index ranges.
+    let mut args = Vec::new();
+    let mut depth = 0usize;
+    let mut arg_start = i + 1;
+    for (j, token) in tokens.iter().enumerate().take(close).skip(i + 1) {
+        match token.text(source) {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth = depth.saturating_sub(1),
+            // Closure parameters would split on the wrong commas.
+            "|" | "||" if depth == 0 => return,
+            "," if depth == 0 => {
+                args.push((arg_start, j));
+                arg_start = j + 1;
+            }
+            _ => {}
+        }
+    }
+    if arg_start < close {
+        args.push((arg_start, close));
+    }
+
+    for pair in args.windows(2) {
+        let [(a_first, a_end), (b_first, b_end)] = [pair[0], pair[1]];
+        if a_first >= a_end || b_first >= b_end {
+            continue;
+        }
+        let (a_start, a_stop) = (tokens[a_first].start, tokens[a_end - 1].end);
+        let (b_start, b_stop) = (tokens[b_first].start, tokens[b_end - 1].end);
+        let (a, b) = (&source[a_start..a_stop], &source[b_start..b_stop]);
+        if a == b {
+            continue;
+        }
+        edits.push(Edit {
+            kind: MutationKind::SwapArguments,
+            start: a_start,
+            end: b_stop,
+            replacement: format!("{b}{}{a}", &source[a_stop..b_start]),
+            description: format!(
+                "swapped `{}` and `{}` in `{}(…)`",
+                short(a),
+                short(b),
+                callee.text(source)
+            ),
+        });
+    }
+}
+
+/// Remove `else { … }`, or a whole `if … { … }` statement without an else.
+fn branch_edits(source: &str, tokens: &[Token], i: usize, edits: &mut Vec<Edit>) {
+    match tokens[i].text(source) {
+        "else" if i > 0 && tokens.get(i + 1).is_some_and(|t| t.text(source) == "{") => {
+            let Some(close) = matching(source, tokens, i + 1) else {
+                return;
+            };
+            edits.push(Edit {
+                kind: MutationKind::RemoveBranch,
+                start: tokens[i - 1].end,
+                end: tokens[close].end,
+                replacement: String::new(),
+                description: "removed `else` branch".to_string(),
+            });
+        }
+        "if" if i == 0 || matches!(tokens[i - 1].text(source), ";" | "{" | "}") => {
+            // The body is the first `{` outside parentheses and brackets.
+            let mut depth = 0usize;
+            let mut body = None;
+            for (j, token) in tokens.iter().enumerate().skip(i + 1) {
+                match token.text(source) {
+                    "(" | "[" => depth += 1,
+                    ")" | "]" => depth = depth.saturating_sub(1),
+                    "{" if depth == 0 => {
+                        body = Some(j);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            let Some(open) = body else {
+                return;
+            };
+            let Some(close) = matching(source, tokens, open) else {
+                return;
+            };
+            if tokens
+                .get(close + 1)
+                .is_some_and(|t| t.text(source) == "else")
+            {
+                return;
+            }
+            let condition = &source[tokens[i].end..tokens[open].start];
+            edits.push(Edit {
+                kind: MutationKind::RemoveBranch,
+                start: tokens[i].start,
+                end: tokens[close].end,
+                replacement: String::new(),
+                description: format!("removed `if {} {{ … }}`", short(condition.trim())),
+            });
+        }
+        _ => {}
+    }
+}
+
+/// First line of `text`, cut to 40 characters for descriptions.
+fn short(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    if line.chars().count() > 40 || line.len() < text.len() {
+        format!("{}…", line.chars().take(40).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOLUTION: &str = r#"/// Index of `target` in sorted `arr`.
+pub fn search(arr: &[i64], target: i64) -> Option<usize> {
+    let (mut lo, mut hi) = (0, arr.len());
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if arr[mid] == target {
+            return Some(mid);
+        } else if arr[mid] < target {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    None
+}
+
+pub fn clamp_all(values: &mut Vec<i64>, lo: i64, hi: i64) {
+    let label = "a < b // not code";
+    for v in values.iter_mut() {
+        *v = (*v).clamp(lo, hi);
+    }
+    if label.is_empty() && values.len() > 1 {
+        values.sort();
+    }
+}
+"#;
+
+    fn find<'a>(mutants: &'a [Mutant], kind: MutationKind, description: &str) -> &'a Mutant {
+        mutants
+            .iter()
+            .find(|m| m.kind == kind && m.description == description)
+            .unwrap_or_else(|| panic!("no {kind:?} mutant {description:?}"))
+    }
+
+    #[test]
+    fn applies_each_operator_outside_literals_and_generics() {
+        let all = mutants(SOLUTION);
+
+        let m = find(&all, MutationKind::OffByOne, "`<` → `<=`");
+        assert_eq!(m.line, 4);
+        assert!(m.source.contains("while lo <= hi {"));
+        assert!(m.source.contains("-> Option<usize>"));
+
+        let m = find(&all, MutationKind::FlipComparison, "`==` → `!=`");
+        assert!(m.source.contains("if arr[mid] != target {"));
+        find(&all, MutationKind::OffByOne, "`1` → `2`");
+        find(&all, MutationKind::FlipLogic, "`&&` → `||`");
+
+        let m = find(
+            &all,
+            MutationKind::SwapArguments,
+            "swapped `lo` and `hi` in `clamp(…)`",
+        );
+        assert!(m.source.contains("(*v).clamp(hi, lo);"));
+
+        let m = find(&all, MutationKind::RemoveBranch, "removed `else` branch");
+        assert!(m.source.contains("lo = mid + 1;\n        }\n    }"));
+        let m = find(
+            &all,
+            MutationKind::RemoveBranch,
+            "removed `if label.is_empty() && values.len() > 1 { … }`",
+        );
+        assert!(!m.source.contains("values.sort()"));
+
+        for m in &all {
+            assert!(
+                m.source.contains("\"a < b // not code\""),
+                "{}",
+                m.description
+            );
+            assert!(
+                m.source.contains("/// Index of `target`"),
+                "{}",
+                m.description
+            );
+            assert_ne!(m.source, SOLUTION);
+        }
+        // `fn search(arr, target)` is a definition, not a call.
+        assert!(!all.iter().any(|m| m.description.contains("in `search(…)`")));
+        // Tuple fields and `Option<usize>` are left alone.
+        assert!(!all.iter().any(|m| m.source.contains("Option>usize")));
+    }
+
+    #[test]
+    fn sample_order_interleaves_kinds_deterministically() {
+        let order = sample_order(mutants(SOLUTION), 7);
+        let kinds: HashSet<MutationKind> = order.iter().take(5).map(|m| m.kind).collect();
+        assert_eq!(kinds.len(), 5);
+        assert_eq!(order, sample_order(mutants(SOLUTION), 7));
+        assert_ne!(order, sample_order(mutants(SOLUTION), 8));
+    }
+
+    #[test]
+    fn mutant_problem_renames_the_crate_in_tests() {
+        let problem = BenchmarkProblem {
+            slug: "opus-search".to_string(),
+            instructions: "Binary search.".to_string(),
+            test_code: "use opus_search::*;\n#[test]\nfn t() {}\n".to_string(),
+            starter_code: String::new(),
+            difficulty: "tier1".to_string(),
+            cargo_toml: String::new(),
+            pack: "opus@1".to_string(),
+        };
+        let mutant = &mutants(SOLUTION)[0];
+        let debug = mutant_problem(&problem, mutant);
+        assert!(debug.slug.starts_with("opus-search-bug-"));
+        assert!(is_generated(&debug.slug));
+        let crate_name = debug.slug.replace('-', "_");
+        assert!(debug
+            .test_code
+            .starts_with(&format!("use {crate_name}::*;")));
+        assert_eq!(debug.starter_code, mutant.source);
+        assert_eq!(debug.difficulty, "tier2");
+        assert!(debug.pack.is_empty());
+    }
+
+    #[tokio::test]
+    async fn keeps_only_compiling_mutants_that_tests_catch() {
+        if let Err(e) = x402_cartridge::sandbox::check() {
+            eprintln!("skipping: {e}");
+            return;
+        }
+        let problem = BenchmarkProblem {
+            slug: "mutate-max".to_string(),
+            instructions: "Return the larger value.".to_string(),
+            test_code: "use mutate_max::*;\n\
+                #[test]\nfn first() { assert_eq!(larger(3, 1), 3); }\n\
+                #[test]\nfn second() { assert_eq!(larger(1, 3), 3); }\n"
+                .to_string(),
+            starter_code: String::new(),
+            difficulty: "tier1".to_string(),
+            cargo_toml: String::new(),
+            pack: String::new(),
+        };
+        let solution = "pub fn larger(a: u32, b: u32) -> u32 {\n    if a > b {\n        a\n    } else {\n        b\n    }\n}\n";
+        let pool = ValidationPool::new(2, std::env::temp_dir());
+        let cancel = CancelToken::new();
+
+        let kept = debugging_problems(&problem, solution, 2, 0, &pool, &cancel)
+            .await
+            .unwrap();
+        // `>=` survives the tests and removing the else doesn't compile, so
+        // only the flipped comparison is kept.
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].mutant.description, "`>` → `<`");
+        for debug in &kept {
+            assert_eq!(debug.fix, solution);
+            assert!(!debug.failing_tests.is_empty());
+            assert!(debug.problem.instructions.contains(&debug.mutant.source));
+            // The known fix passes the renamed problem.
+            let fixed = pool
+                .validate(&debug.problem, &debug.fix, &cancel)
+                .await
+                .unwrap();
+            assert!(fixed.passed, "{}", fixed.error_output);
+        }
+
+        let broken = "pub fn larger(a: u32, b: u32) -> u32 { a }\n";
+        let none = debugging_problems(&problem, broken, 2, 0, &pool, &cancel)
+            .await
+            .unwrap();
+        assert!(none.is_empty());
+    }
+}
@@ -97,5 +97,6 @@ fn problem(
         starter_code: starter.to_string(),
         difficulty: difficulty.to_string(),
         cargo_toml: String::new(), // std-only, no external deps
+        pack: String::new(),
     }
 }
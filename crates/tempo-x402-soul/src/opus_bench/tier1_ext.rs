@@ -150,7 +150,7 @@ fn to_f64() { assert!((Fraction::new(1, 3).to_f64() - 0.3333333).abs() < 0.001);
 fn zero() { assert_eq!(Fraction::new(0, 5), Fraction::new(0, 1)); }"#,
         ),
         problem(
-            "opus-trie",
+            "opus-trie-2",
             "tier1",
             "Implement a trie (prefix tree) for string storage with insert, search, and starts_with.",
             r#"pub struct Trie { /* your fields */ }
@@ -162,7 +162,7 @@ impl Trie {
     pub fn starts_with(&self, prefix: &str) -> bool { todo!() }
     pub fn words_with_prefix(&self, prefix: &str) -> Vec<String> { todo!() }
 }"#,
-            r#"use opus_trie::*;
+            r#"use opus_trie_2::*;
 
 #[test]
 fn insert_and_search() {
@@ -206,7 +206,7 @@ fn empty_prefix() {
 }"#,
         ),
         problem(
-            "opus-lru-cache",
+            "opus-lru-cache-2",
             "tier1",
             "Implement an LRU (Least Recently Used) cache with get and put operations. \
              When the cache is full, evict the least recently used entry.",
@@ -218,7 +218,7 @@ impl<V: Clone> LruCache<V> {
     pub fn put(&mut self, key: String, value: V) { todo!() }
     pub fn len(&self) -> usize { todo!() }
 }"#,
-            r#"use opus_lru_cache::*;
+            r#"use opus_lru_cache_2::*;
 
 #[test]
 fn basic_put_get() {
@@ -569,7 +569,7 @@ fn large() {
 }"#,
         ),
         problem(
-            "opus-json-value",
+            "opus-json-value-2",
             "tier1",
             "Implement a simple JSON value type that can represent null, bool, number, string, array, and object. \
              Implement Display to produce valid JSON output.",
@@ -588,7 +588,7 @@ pub enum JsonValue {
 impl std::fmt::Display for JsonValue {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { todo!() }
 }"#,
-            r#"use opus_json_value::*;
+            r#"use opus_json_value_2::*;
 use std::collections::BTreeMap;
 
 #[test]
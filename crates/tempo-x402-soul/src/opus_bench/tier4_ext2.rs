@@ -3,10 +3,10 @@ use crate::benchmark::BenchmarkProblem;
 
 pub(super) fn tier4_ext2() -> Vec<BenchmarkProblem> {
     vec![
-        problem("opus-n-queens", "tier4",
+        problem("opus-n-queens-2", "tier4",
             "Place N queens on an NxN chessboard so no two attack each other. Return all solutions.",
             r#"pub fn solve_n_queens(n: usize) -> Vec<Vec<usize>> { todo!() }"#,
-            r#"use opus_n_queens::*;
+            r#"use opus_n_queens_2::*;
 #[test] fn one() { assert_eq!(solve_n_queens(1), vec![vec![0]]); }
 #[test] fn four() { let s = solve_n_queens(4); assert_eq!(s.len(), 2); }
 #[test] fn eight() { assert_eq!(solve_n_queens(8).len(), 92); }
@@ -729,7 +729,7 @@ fn edit_distance(ops: &[DiffOp]) -> usize {
 
         // ── 6.7: Huffman Coding ────────────────────────────────────────
         problem(
-            "opus-huffman",
+            "opus-huffman-2",
             "tier6",
             "Implement Huffman coding. \
              `encode(data: &[u8]) -> (Vec<bool>, HuffmanTree)` compresses data to bits + tree. \
@@ -751,7 +751,7 @@ pub fn encode(data: &[u8]) -> (Vec<bool>, HuffmanTree) {
 pub fn decode(bits: &[bool], tree: &HuffmanTree) -> Vec<u8> {
     todo!()
 }"#,
-            r#"use opus_huffman::*;
+            r#"use opus_huffman_2::*;
 
 #[test] fn empty_encode() {
     let (bits, _) = encode(b"");
@@ -0,0 +1,707 @@
+//! Loadable benchmark problem packs.
+//!
+//! A pack is a directory (or a `.tar` / `.tar.gz` of one) with a manifest
+//! and one directory per problem:
+//!
+//! ```text
+//! pack.toml
+//! problems/{slug}/instructions.md   required
+//! problems/{slug}/tests.rs          required
+//! problems/{slug}/starter.rs        optional
+//! problems/{slug}/Cargo.toml        optional (std-only when absent)
+//! problems/{slug}/solution.rs       optional reference solution
+//! ```
+//!
+//! `pack.toml` names and versions the pack and lists every problem with its
+//! difficulty and the SHA-256 of its files, so an edited problem is rejected
+//! rather than silently scored against old results:
+//!
+//! ```toml
+//! [pack]
+//! name = "acme-private"
+//! version = "1.0.0"
+//!
+//! [[problems]]
+//! slug = "acme-ringbuf"
+//! difficulty = "tier2"
+//! hash = "9f2c…"
+//! ```
+//!
+//! The embedded Opus problems are the built-in [`embedded`] pack. Extra packs
+//! listed in `SOUL_PROBLEM_PACKS` (comma-separated paths) are added on top by
+//! [`load_problems`], so private problem sets need no rebuild. Every problem
+//! carries its pack's [`id`](ProblemPack::id), which is recorded in each
+//! `BenchmarkRun`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::benchmark::BenchmarkProblem;
+use crate::validation_pool::{CancelToken, Cancelled, ValidationPool};
+
+/// Manifest file at the pack root.
+pub const MANIFEST: &str = "pack.toml";
+
+const INSTRUCTIONS: &str = "instructions.md";
+const TESTS: &str = "tests.rs";
+const STARTER: &str = "starter.rs";
+const CARGO_TOML: &str = "Cargo.toml";
+const SOLUTION: &str = "solution.rs";
+
+/// Upper bound on the files read from one pack, so a hostile tarball can't
+/// exhaust memory.
+const MAX_PACK_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PackError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid {MANIFEST}: {0}")]
+    Manifest(String),
+
+    #[error("problem {slug}: missing {file}")]
+    Missing { slug: String, file: &'static str },
+
+    #[error("problem {slug}: content hash {actual} does not match manifest {expected}")]
+    HashMismatch {
+        slug: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("duplicate problem slug {0}")]
+    Duplicate(String),
+
+    #[error("invalid problem slug {0:?} (use lowercase letters, digits, '-' and '_')")]
+    InvalidSlug(String),
+
+    #[error("pack is larger than {MAX_PACK_BYTES} bytes")]
+    TooLarge,
+}
+
+/// `[pack]` section of the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackInfo {
+    pub name: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+}
+
+/// One `[[problems]]` entry of the manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PackEntry {
+    slug: String,
+    difficulty: String,
+    #[serde(default)]
+    hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    pack: PackInfo,
+    #[serde(default)]
+    problems: Vec<PackEntry>,
+}
+
+/// A problem and its optional reference solution.
+#[derive(Debug, Clone)]
+pub struct PackProblem {
+    pub problem: BenchmarkProblem,
+    pub solution: Option<String>,
+    /// SHA-256 of the problem's files, hex.
+    pub hash: String,
+}
+
+/// A loaded, hash-checked problem pack.
+#[derive(Debug, Clone)]
+pub struct ProblemPack {
+    pub info: PackInfo,
+    /// SHA-256 over the name, version and every problem hash, hex.
+    pub content_hash: String,
+    pub problems: Vec<PackProblem>,
+}
+
+impl ProblemPack {
+    /// Build a pack from in-memory problems, computing every hash.
+    pub fn from_problems(
+        info: PackInfo,
+        problems: impl IntoIterator<Item = (BenchmarkProblem, Option<String>)>,
+    ) -> Self {
+        let problems: Vec<PackProblem> = problems
+            .into_iter()
+            .map(|(problem, solution)| PackProblem {
+                hash: problem_hash(&problem, solution.as_deref()),
+                problem,
+                solution,
+            })
+            .collect();
+        let mut pack = Self {
+            content_hash: content_hash(&info, &problems),
+            info,
+            problems,
+        };
+        let id = pack.id();
+        for p in &mut pack.problems {
+            p.problem.pack = id.clone();
+        }
+        pack
+    }
+
+    /// Load a pack directory or `.tar` / `.tar.gz` archive, checking every
+    /// problem against the manifest hash.
+    pub fn load(path: &Path) -> Result<Self, PackError> {
+        Self::load_with(path, true)
+    }
+
+    /// Load without checking hashes, e.g. to recompute them after editing.
+    pub fn load_unverified(path: &Path) -> Result<Self, PackError> {
+        Self::load_with(path, false)
+    }
+
+    fn load_with(path: &Path, verify: bool) -> Result<Self, PackError> {
+        if path.is_dir() {
+            let mut budget = MAX_PACK_BYTES;
+            Self::parse(
+                |rel| {
+                    let file = match std::fs::File::open(path.join(rel)) {
+                        Ok(file) => file,
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                        Err(e) => return Err(e.into()),
+                    };
+                    read_limited(file, &mut budget).map(Some)
+                },
+                verify,
+            )
+        } else {
+            let files = read_archive(std::fs::File::open(path)?)?;
+            Self::parse(|rel| Ok(files.get(rel).cloned()), verify)
+        }
+    }
+
+    fn parse(
+        mut read: impl FnMut(&str) -> Result<Option<String>, PackError>,
+        verify: bool,
+    ) -> Result<Self, PackError> {
+        let manifest = read(MANIFEST)?
+            .ok_or_else(|| PackError::Manifest("not found".to_string()))?;
+        let manifest: Manifest =
+            toml::from_str(&manifest).map_err(|e| PackError::Manifest(e.to_string()))?;
+
+        let mut seen = HashSet::new();
+        let mut problems = Vec::with_capacity(manifest.problems.len());
+        for entry in manifest.problems {
+            if !valid_slug(&entry.slug) {
+                return Err(PackError::InvalidSlug(entry.slug));
+            }
+            if !seen.insert(entry.slug.clone()) {
+                return Err(PackError::Duplicate(entry.slug));
+            }
+            let dir = format!("problems/{}", entry.slug);
+            let mut file = |name: &str| read(&format!("{dir}/{name}"));
+            let required = |content: Option<String>, file: &'static str| {
+                content.ok_or_else(|| PackError::Missing {
+                    slug: entry.slug.clone(),
+                    file,
+                })
+            };
+            let problem = BenchmarkProblem {
+                slug: entry.slug.clone(),
+                instructions: required(file(INSTRUCTIONS)?, INSTRUCTIONS)?,
+                test_code: required(file(TESTS)?, TESTS)?,
+                starter_code: file(STARTER)?.unwrap_or_default(),
+                difficulty: entry.difficulty,
+                cargo_toml: file(CARGO_TOML)?.unwrap_or_default(),
+                pack: String::new(),
+            };
+            let solution = file(SOLUTION)?;
+            let hash = problem_hash(&problem, solution.as_deref());
+            if verify && hash != entry.hash {
+                return Err(PackError::HashMismatch {
+                    slug: entry.slug,
+                    expected: entry.hash,
+                    actual: hash,
+                });
+            }
+            problems.push((problem, solution));
+        }
+        Ok(Self::from_problems(manifest.pack, problems))
+    }
+
+    /// `name@version+hash`, with the first 12 hex digits of the content hash.
+    pub fn id(&self) -> String {
+        format!(
+            "{}@{}+{}",
+            self.info.name,
+            self.info.version,
+            &self.content_hash[..12]
+        )
+    }
+
+    /// The problems, each tagged with this pack's id.
+    pub fn benchmark_problems(&self) -> Vec<BenchmarkProblem> {
+        self.problems.iter().map(|p| p.problem.clone()).collect()
+    }
+
+    /// Write the pack as a directory, manifest hashes included.
+    pub fn write(&self, dir: &Path) -> std::io::Result<()> {
+        let manifest = Manifest {
+            pack: self.info.clone(),
+            problems: self
+                .problems
+                .iter()
+                .map(|p| PackEntry {
+                    slug: p.problem.slug.clone(),
+                    difficulty: p.problem.difficulty.clone(),
+                    hash: p.hash.clone(),
+                })
+                .collect(),
+        };
+        std::fs::create_dir_all(dir)?;
+        let toml = toml::to_string_pretty(&manifest)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(dir.join(MANIFEST), toml)?;
+        for p in &self.problems {
+            let problem_dir = dir.join("problems").join(&p.problem.slug);
+            std::fs::create_dir_all(&problem_dir)?;
+            for (name, content) in problem_files(&p.problem, p.solution.as_deref()) {
+                std::fs::write(problem_dir.join(name), content)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Result of running a problem's reference solution.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReferenceCheck {
+    Passed,
+    /// The reference failed its own tests; holds the error output.
+    Failed(String),
+    /// The problem ships no `solution.rs`.
+    Missing,
+}
+
+/// Run every reference solution in `pack` against its tests through `pool`.
+/// Results are in problem order.
+pub async fn check_references(
+    pack: &ProblemPack,
+    pool: &ValidationPool,
+    cancel: &CancelToken,
+) -> Result<Vec<(String, ReferenceCheck)>, Cancelled> {
+    let checks: Vec<_> = pack
+        .problems
+        .iter()
+        .map(|p| async move {
+            let check = match &p.solution {
+                None => ReferenceCheck::Missing,
+                Some(solution) => {
+                    let v = pool.validate(&p.problem, solution, cancel).await?;
+                    if v.passed {
+                        ReferenceCheck::Passed
+                    } else {
+                        ReferenceCheck::Failed(v.error_output)
+                    }
+                }
+            };
+            Ok((p.problem.slug.clone(), check))
+        })
+        .collect();
+    futures::stream::iter(checks)
+        .buffered(pool.workers())
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// The Opus problems compiled into this binary, as a pack.
+pub fn embedded() -> &'static ProblemPack {
+    static EMBEDDED: OnceLock<ProblemPack> = OnceLock::new();
+    EMBEDDED.get_or_init(|| {
+        ProblemPack::from_problems(
+            PackInfo {
+                name: "opus".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                description: "Embedded Opus IQ benchmark".to_string(),
+            },
+            crate::opus_bench::load_embedded_problems()
+                .into_iter()
+                .map(|p| (p, None)),
+        )
+    })
+}
+
+/// Packs listed in `SOUL_PROBLEM_PACKS`. Packs that fail to load are
+/// skipped with a warning.
+pub fn configured_packs() -> Vec<ProblemPack> {
+    let Ok(paths) = std::env::var("SOUL_PROBLEM_PACKS") else {
+        return Vec::new();
+    };
+    paths
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .filter_map(|path| match ProblemPack::load(Path::new(path)) {
+            Ok(pack) => {
+                tracing::info!(path, pack = %pack.id(), problems = pack.problems.len(), "Loaded problem pack");
+                Some(pack)
+            }
+            Err(e) => {
+                tracing::warn!(path, error = %e, "Skipping problem pack");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Every benchmark problem: the embedded pack, then configured packs.
+/// A slug already taken by an earlier pack is skipped.
+pub fn load_problems() -> Vec<BenchmarkProblem> {
+    let extra = configured_packs();
+    merge(std::iter::once(embedded()).chain(&extra))
+}
+
+/// Concatenate packs' problems, keeping the first problem for each slug.
+pub fn merge<'a>(packs: impl IntoIterator<Item = &'a ProblemPack>) -> Vec<BenchmarkProblem> {
+    let mut seen = HashSet::new();
+    let mut problems = Vec::new();
+    for pack in packs {
+        for p in &pack.problems {
+            if seen.insert(p.problem.slug.clone()) {
+                problems.push(p.problem.clone());
+            } else {
+                tracing::warn!(slug = %p.problem.slug, pack = %pack.id(), "Duplicate problem slug — skipped");
+            }
+        }
+    }
+    problems
+}
+
+/// Embedded problems renamed because they shared a slug with an unrelated
+/// earlier problem, as `(new slug, slug both used to share)`. Runs recorded
+/// before the rename carry the shared slug; see [`migrate_renamed_slugs`].
+pub const RENAMED_SLUGS: &[(&str, &str)] = &[
+    ("opus-trie-2", "opus-trie"),
+    ("opus-lru-cache-2", "opus-lru-cache"),
+    ("opus-json-value-2", "opus-json-value"),
+    ("opus-n-queens-2", "opus-n-queens"),
+    ("opus-huffman-2", "opus-huffman"),
+];
+
+/// `BenchmarkRun`s older than problem packs (no pack id) recorded under a
+/// shared slug could be either problem. Each is reassigned to the renamed
+/// problem when its solution defines more of that problem's tested API, and
+/// left alone otherwise. Runs once per database.
+pub fn migrate_renamed_slugs(db: &crate::db::SoulDatabase, problems: &[BenchmarkProblem]) {
+    const DONE: &str = "renamed_slugs_migrated";
+    if db.get_state(DONE).ok().flatten().is_some() {
+        return;
+    }
+    let Ok(runs) = db.get_all_benchmark_runs() else {
+        return;
+    };
+    let find = |slug: &str| problems.iter().find(|p| p.slug == slug);
+    let mut moved = 0;
+    for mut run in runs {
+        if !run.pack.is_empty() {
+            continue;
+        }
+        let Some(&(renamed, _)) = RENAMED_SLUGS.iter().find(|(_, old)| *old == run.entry_point)
+        else {
+            continue;
+        };
+        let (Some(old), Some(new)) = (find(&run.entry_point), find(renamed)) else {
+            continue;
+        };
+        if attempted_renamed(&run.generated_solution, old, new) {
+            run.task_id = run.task_id.replace(&run.entry_point, renamed);
+            run.entry_point = renamed.to_string();
+            if db.insert_benchmark_run(&run).is_ok() {
+                moved += 1;
+            }
+        }
+    }
+    tracing::info!(moved, "Reassigned pre-rename benchmark runs to renamed problems");
+    let _ = db.set_state(DONE, "1");
+}
+
+/// True if `solution` leaves fewer of `new`'s tested items undefined than
+/// of `old`'s.
+fn attempted_renamed(solution: &str, old: &BenchmarkProblem, new: &BenchmarkProblem) -> bool {
+    let defined = defined_items(solution);
+    let missing = |p: &BenchmarkProblem| {
+        let api = defined_items(&p.starter_code);
+        identifiers(&p.test_code)
+            .filter(|id| api.is_empty() || api.contains(id))
+            .filter(|id| !defined.contains(id))
+            .collect::<HashSet<_>>()
+            .len()
+    };
+    missing(new) < missing(old)
+}
+
+/// Names of the `fn`s, types and traits `code` defines.
+fn defined_items(code: &str) -> HashSet<&str> {
+    let mut items = HashSet::new();
+    let mut words = identifiers(code);
+    while let Some(word) = words.next() {
+        if matches!(word, "fn" | "struct" | "enum" | "trait" | "type") {
+            if let Some(name) = words.next() {
+                items.insert(name);
+            }
+        }
+    }
+    items
+}
+
+fn identifiers(code: &str) -> impl Iterator<Item = &str> {
+    code.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| w.starts_with(|c: char| c.is_alphabetic() || c == '_'))
+}
+
+/// Files of a problem directory, in hashing order. Optional files that are
+/// empty are left out.
+fn problem_files<'a>(
+    problem: &'a BenchmarkProblem,
+    solution: Option<&'a str>,
+) -> Vec<(&'static str, &'a str)> {
+    [
+        (INSTRUCTIONS, Some(problem.instructions.as_str())),
+        (TESTS, Some(problem.test_code.as_str())),
+        (STARTER, Some(problem.starter_code.as_str()).filter(|s| !s.is_empty())),
+        (CARGO_TOML, Some(problem.cargo_toml.as_str()).filter(|s| !s.is_empty())),
+        (SOLUTION, solution),
+    ]
+    .into_iter()
+    .filter_map(|(name, content)| Some((name, content?)))
+    .collect()
+}
+
+/// SHA-256 over the slug, difficulty and each present file, length-prefixed.
+fn problem_hash(problem: &BenchmarkProblem, solution: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    for field in [&problem.slug, &problem.difficulty] {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field);
+    }
+    for (name, content) in problem_files(problem, solution) {
+        hasher.update(name);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(content);
+    }
+    hex(&hasher.finalize())
+}
+
+fn content_hash(info: &PackInfo, problems: &[PackProblem]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}\0{}\0", info.name, info.version));
+    for p in problems {
+        hasher.update(format!("{}\0{}\0", p.problem.slug, p.hash));
+    }
+    hex(&hasher.finalize())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Slugs are lowercase alphanumerics, `-` and `_`; they become directory
+/// names and training-source labels.
+pub fn valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+}
+
+fn read_limited(reader: impl Read, budget: &mut u64) -> Result<String, PackError> {
+    let mut content = String::new();
+    reader.take(*budget + 1).read_to_string(&mut content)?;
+    let len = content.len() as u64;
+    if len > *budget {
+        return Err(PackError::TooLarge);
+    }
+    *budget -= len;
+    Ok(content)
+}
+
+/// Read a tarball (gzipped or not) into memory, keyed by path relative to
+/// the directory holding `pack.toml`.
+fn read_archive(mut file: std::fs::File) -> Result<BTreeMap<String, String>, PackError> {
+    let mut magic = [0u8; 2];
+    let gzipped = file.read(&mut magic)? == 2 && magic == [0x1f, 0x8b];
+    let file = std::io::Read::chain(&magic[..], file);
+    let reader: Box<dyn Read> = if gzipped {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+
+    let mut budget = MAX_PACK_BYTES;
+    let mut files = BTreeMap::new();
+    for entry in tar::Archive::new(reader).entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().trim_start_matches("./").to_string();
+        let content = read_limited(entry, &mut budget)?;
+        files.insert(path, content);
+    }
+
+    // Archives often wrap the pack in one top-level directory.
+    let root = files
+        .keys()
+        .filter_map(|p| p.strip_suffix(MANIFEST))
+        .filter(|prefix| prefix.is_empty() || prefix.ends_with('/'))
+        .min_by_key(|prefix| prefix.len())
+        .map(str::to_string)
+        .ok_or_else(|| PackError::Manifest("not found in archive".to_string()))?;
+    Ok(files
+        .into_iter()
+        .filter_map(|(path, content)| Some((path.strip_prefix(&root)?.to_string(), content)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack() -> ProblemPack {
+        let problem = |slug: &str, cargo_toml: &str| BenchmarkProblem {
+            slug: slug.to_string(),
+            instructions: format!("Implement `{slug}`."),
+            test_code: "#[test]\nfn t() {}\n".to_string(),
+            starter_code: String::new(),
+            difficulty: "tier2".to_string(),
+            cargo_toml: cargo_toml.to_string(),
+            pack: String::new(),
+        };
+        ProblemPack::from_problems(
+            PackInfo {
+                name: "acme".to_string(),
+                version: "1.0.0".to_string(),
+                description: String::new(),
+            },
+            [
+                (problem("acme-one", ""), Some("pub fn one() {}\n".to_string())),
+                (problem("acme-two", "[package]\nname = \"acme-two\"\n"), None),
+            ],
+        )
+    }
+
+    #[test]
+    fn directory_round_trip_keeps_hashes_and_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = pack();
+        original.write(dir.path()).unwrap();
+
+        let loaded = ProblemPack::load(dir.path()).unwrap();
+        assert_eq!(loaded.id(), original.id());
+        assert!(loaded.id().starts_with("acme@1.0.0+"));
+        assert_eq!(loaded.problems[0].solution.as_deref(), Some("pub fn one() {}\n"));
+        assert!(loaded.problems[1].solution.is_none());
+        assert_eq!(loaded.problems[1].problem.cargo_toml, original.problems[1].problem.cargo_toml);
+        assert!(loaded.benchmark_problems().iter().all(|p| p.pack == loaded.id()));
+    }
+
+    #[test]
+    fn edited_problems_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        pack().write(dir.path()).unwrap();
+        std::fs::write(dir.path().join("problems/acme-one/tests.rs"), "// changed").unwrap();
+
+        let err = ProblemPack::load(dir.path()).unwrap_err();
+        assert!(matches!(err, PackError::HashMismatch { ref slug, .. } if slug == "acme-one"));
+        let rehashed = ProblemPack::load_unverified(dir.path()).unwrap();
+        assert_ne!(rehashed.id(), pack().id());
+    }
+
+    #[test]
+    fn gzipped_tarball_with_top_level_dir_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = pack();
+        original.write(&dir.path().join("acme")).unwrap();
+
+        let archive = dir.path().join("acme.tar.gz");
+        let gz = flate2::write::GzEncoder::new(
+            std::fs::File::create(&archive).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        tar.append_dir_all("acme", dir.path().join("acme")).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        assert_eq!(ProblemPack::load(&archive).unwrap().id(), original.id());
+    }
+
+    #[test]
+    fn embedded_pack_slugs_are_unique_and_valid() {
+        let pack = embedded();
+        assert!(pack.id().starts_with("opus@"));
+        let problems = merge([pack, pack]);
+        assert_eq!(problems.len(), pack.problems.len());
+        assert!(problems.iter().all(|p| valid_slug(&p.slug)));
+        // A problem without recognizable tests could never be passed.
+        for p in &problems {
+            assert!(!crate::test_report::declared_tests(&p.test_code).is_empty(), "{}", p.slug);
+        }
+    }
+
+    #[test]
+    fn renamed_slugs_are_embedded() {
+        let problems = merge([embedded()]);
+        for (new, old) in RENAMED_SLUGS {
+            let find = |slug: &str| problems.iter().find(|p| p.slug == slug).unwrap();
+            let (old, new) = (find(old), find(new));
+            // A run is attributed by which problem's API its solution defines
+            assert!(attempted_renamed(&new.starter_code, old, new), "{}", new.slug);
+            assert!(!attempted_renamed(&old.starter_code, old, new), "{}", old.slug);
+        }
+    }
+
+    #[test]
+    fn pre_rename_runs_move_to_the_problem_they_attempted() {
+        let dir = std::env::temp_dir().join(format!("x402-rename-{}", uuid::Uuid::new_v4()));
+        let db = crate::db::SoulDatabase::new(dir.to_str().unwrap()).unwrap();
+        let problems = merge([embedded()]);
+        let starter = |slug: &str| {
+            problems.iter().find(|p| p.slug == slug).unwrap().starter_code.clone()
+        };
+        let run = |id: &str, solution: String, pack: &str| crate::benchmark::BenchmarkRun {
+            id: id.to_string(),
+            task_id: "opus/opus-trie".to_string(),
+            entry_point: "opus-trie".to_string(),
+            passed: true,
+            generated_solution: solution,
+            error_output: String::new(),
+            total_ms: 0,
+            created_at: 0,
+            pack: pack.to_string(),
+            report: None,
+        };
+        db.insert_benchmark_run(&run("old", starter("opus-trie"), "")).unwrap();
+        db.insert_benchmark_run(&run("new", starter("opus-trie-2"), "")).unwrap();
+        db.insert_benchmark_run(&run("packed", starter("opus-trie-2"), "opus@1")).unwrap();
+
+        migrate_renamed_slugs(&db, &problems);
+        let slug_of = |id: &str| {
+            let runs = db.get_all_benchmark_runs().unwrap();
+            let run = runs.into_iter().find(|r| r.id == id).unwrap();
+            (run.entry_point, run.task_id)
+        };
+        assert_eq!(slug_of("old").0, "opus-trie");
+        assert_eq!(slug_of("new"), ("opus-trie-2".into(), "opus/opus-trie-2".into()));
+        assert_eq!(slug_of("packed").0, "opus-trie");
+        drop(db);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
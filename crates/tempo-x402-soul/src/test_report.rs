@@ -0,0 +1,673 @@
+//! Structured `cargo test` results.
+//!
+//! Validation runs `cargo test --message-format=json`. Its stdout holds
+//! cargo's build messages as JSON objects, one per line, carrying rustc
+//! diagnostics, followed by libtest's text output: a `test {name} ... ok`
+//! line per test, then the captured output of each failure.
+//! [`TestReport::parse`] keeps the compile errors and test outcomes. They feed
+//! partial credit, retry prompts, brain features and shared failures, which
+//! previously only saw truncated text.
+//!
+//! The code under test shares stdout with libtest and can print lines that
+//! look like test results. Validation therefore wraps the test file in a
+//! module with a random name ([`namespace_tests`]), so real results are
+//! named `{namespace}::test`, and [`TestReport::restrict_to`] keeps only
+//! those, for tests declared in the file, one result each. Tests that never
+//! reported count as not passed, and a file with no recognizable tests
+//! never passes.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Longest panic message or rendered diagnostic kept per entry.
+const MAX_DETAIL_CHARS: usize = 600;
+
+/// Most compile errors kept per report; later ones are usually fallout.
+const MAX_COMPILE_ERRORS: usize = 10;
+
+/// Per-test outcomes and compile errors of one `cargo test`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestReport {
+    #[serde(default)]
+    pub tests: Vec<TestCase>,
+    #[serde(default)]
+    pub compile_errors: Vec<CompileError>,
+    /// Tests declared in the test file; zero if none were recognized or the
+    /// report wasn't [restricted](Self::restrict_to).
+    #[serde(default)]
+    pub declared: usize,
+}
+
+/// One libtest test case.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestCase {
+    /// Name as libtest reports it, e.g. `push_then_pop`.
+    pub name: String,
+    pub outcome: TestOutcome,
+    /// Panic message of a failed test, without the backtrace.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// One rustc error.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompileError {
+    /// Error code such as `E0308`; empty for errors without one.
+    #[serde(default)]
+    pub code: String,
+    pub message: String,
+    /// `file:line` of the primary span.
+    #[serde(default)]
+    pub location: String,
+    /// rustc's rendered diagnostic with notes and help, truncated.
+    #[serde(default)]
+    pub rendered: String,
+}
+
+impl TestReport {
+    /// Parse the stdout of `cargo test --message-format=json`. Lines that
+    /// aren't compile errors, test results or a failed test's output are
+    /// skipped.
+    pub fn parse(stdout: &str) -> Self {
+        let mut report = Self::default();
+        // The failed test whose `---- name stdout ----` section is open, and
+        // the section so far.
+        let mut section: Option<(&str, Vec<&str>)> = None;
+        let mut outputs: Vec<(&str, Vec<&str>)> = Vec::new();
+        for line in stdout.lines() {
+            let trimmed = line.trim();
+            if let Some(name) = section_header(trimmed) {
+                outputs.extend(section.replace((name, Vec::new())));
+            } else if trimmed == "failures:" || trimmed.starts_with("test result:") {
+                outputs.extend(section.take());
+            } else if let Some((_, lines)) = &mut section {
+                lines.push(line);
+            } else if trimmed.starts_with('{') {
+                report.add_cargo_message(trimmed);
+            } else if let Some(case) = test_case(trimmed) {
+                report.tests.push(case);
+            }
+        }
+        outputs.extend(section);
+        for (name, lines) in outputs {
+            let failed = report.tests.iter_mut().find(|t| {
+                t.name == name && t.outcome == TestOutcome::Failed && t.message.is_empty()
+            });
+            if let Some(case) = failed {
+                case.message = panic_message(&lines.join("\n"));
+            }
+        }
+        report
+    }
+
+    /// Keep the compile error in one of cargo's JSON messages.
+    fn add_cargo_message(&mut self, line: &str) {
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(line) else {
+            return;
+        };
+        if message["reason"] != "compiler-message" {
+            return;
+        }
+        if let Some(error) = compile_error(&message["message"]) {
+            if self.compile_errors.len() < MAX_COMPILE_ERRORS
+                && !self.compile_errors.contains(&error)
+            {
+                self.compile_errors.push(error);
+            }
+        }
+    }
+
+    /// Keep the results of tests in `declared` that libtest reported under
+    /// `namespace` (see [`namespace_tests`]), with the namespace stripped
+    /// from their names, and drop the rest. A test reported more than once
+    /// counts as failed. Declared tests without a result count against
+    /// [`partial_credit`](Self::partial_credit).
+    pub fn restrict_to(&mut self, declared: &[String], namespace: &str) {
+        let prefix = format!("{namespace}::");
+        let mut kept: Vec<TestCase> = Vec::new();
+        for mut case in std::mem::take(&mut self.tests) {
+            let Some(path) = case.name.strip_prefix(&prefix) else {
+                continue;
+            };
+            let name = path.rsplit("::").next().unwrap_or(path);
+            if !declared.iter().any(|d| d == name) {
+                continue;
+            }
+            case.name = path.to_string();
+            match kept.iter_mut().find(|k| k.name == case.name) {
+                Some(existing) => {
+                    existing.outcome = TestOutcome::Failed;
+                    existing.message = "reported more than once".to_string();
+                }
+                None => kept.push(case),
+            }
+        }
+        self.tests = kept;
+        self.declared = declared.len();
+    }
+
+    /// Whether tests were declared and every one reported a pass.
+    pub fn all_declared_passed(&self) -> bool {
+        self.declared > 0 && self.failed() == 0 && self.passed() >= self.declared
+    }
+
+    /// Whether nothing was parsed (e.g. the build was killed or never started).
+    pub fn is_empty(&self) -> bool {
+        self.tests.is_empty() && self.compile_errors.is_empty()
+    }
+
+    pub fn compiled(&self) -> bool {
+        self.compile_errors.is_empty()
+    }
+
+    pub fn passed(&self) -> usize {
+        self.count(TestOutcome::Passed)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(TestOutcome::Failed)
+    }
+
+    /// Tests that ran (ignored ones don't count).
+    pub fn total(&self) -> usize {
+        self.passed() + self.failed()
+    }
+
+    /// Fraction of tests passed, in `0.0..=1.0`, out of the declared tests
+    /// when known. Zero when the crate didn't compile or no test ran.
+    pub fn partial_credit(&self) -> f64 {
+        let total = self.expected();
+        if !self.compiled() || total == 0 {
+            0.0
+        } else {
+            self.passed() as f64 / total as f64
+        }
+    }
+
+    /// Tests that should have run: the declared ones, or those that did.
+    fn expected(&self) -> usize {
+        self.declared.max(self.total())
+    }
+
+    /// Compile error counts keyed by rustc code (`"other"` when uncoded).
+    pub fn errors_by_code(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for error in &self.compile_errors {
+            let code = if error.code.is_empty() {
+                "other".to_string()
+            } else {
+                error.code.clone()
+            };
+            *counts.entry(code).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// One line for reports: the first compile error, or which tests failed.
+    pub fn headline(&self) -> String {
+        if let Some(error) = self.compile_errors.first() {
+            return error.header();
+        }
+        let failed: Vec<&str> = self
+            .failing()
+            .map(|t| t.name.as_str())
+            .take(3)
+            .collect();
+        format!(
+            "{}/{} tests failed: {}",
+            self.failed(),
+            self.total(),
+            failed.join(", ")
+        )
+    }
+
+    /// Prompt-ready feedback: rendered compile errors, or each failing test
+    /// with its panic message.
+    pub fn summary(&self) -> String {
+        if !self.compiled() {
+            let errors: Vec<String> = self
+                .compile_errors
+                .iter()
+                .take(5)
+                .map(|e| {
+                    if e.rendered.is_empty() {
+                        format!("{} at {}", e.header(), e.location)
+                    } else {
+                        e.rendered.trim_end().to_string()
+                    }
+                })
+                .collect();
+            return errors.join("\n\n");
+        }
+        let mut lines: Vec<String> = self
+            .failing()
+            .map(|t| {
+                if t.message.is_empty() {
+                    format!("- test `{}` FAILED", t.name)
+                } else {
+                    format!("- test `{}` FAILED: {}", t.name, t.message)
+                }
+            })
+            .collect();
+        lines.push(format!("Passed {}/{} tests.", self.passed(), self.expected()));
+        lines.join("\n")
+    }
+
+    fn failing(&self) -> impl Iterator<Item = &TestCase> {
+        self.tests
+            .iter()
+            .filter(|t| t.outcome == TestOutcome::Failed)
+    }
+
+    fn count(&self, outcome: TestOutcome) -> usize {
+        self.tests.iter().filter(|t| t.outcome == outcome).count()
+    }
+}
+
+impl CompileError {
+    /// `error[E0308]: mismatched types`, as rustc prints it.
+    pub fn header(&self) -> String {
+        if self.code.is_empty() {
+            format!("error: {}", self.message)
+        } else {
+            format!("error[{}]: {}", self.code, self.message)
+        }
+    }
+}
+
+/// `test_code` inside `mod {namespace}`, so libtest reports its tests as
+/// `{namespace}::name`. With a fresh random namespace per run, the code
+/// under test can't easily print events that pass for real ones.
+pub fn namespace_tests(test_code: &str, namespace: &str) -> String {
+    format!("mod {namespace} {{\n{test_code}\n}}\n")
+}
+
+/// Names of the test functions in `test_code`: those with a `#[test]` or
+/// `#[…::test]` (e.g. `#[tokio::test]`) attribute, among any others.
+pub fn declared_tests(test_code: &str) -> Vec<String> {
+    let code = strip_comments(test_code);
+    let mut names = Vec::new();
+    let mut rest = code.as_str();
+    while let Some(at) = rest.find("#[") {
+        // A run of attributes, then the item they apply to.
+        let mut item = &rest[at..];
+        let mut is_test = false;
+        while let Some(attribute) = item.strip_prefix("#[") {
+            let Some(end) = closing_bracket(attribute) else {
+                return names;
+            };
+            is_test |= is_test_attribute(&attribute[..end]);
+            item = attribute[end + 1..].trim_start();
+        }
+        if is_test {
+            if let Some(name) = fn_name(item) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        rest = item;
+    }
+    names
+}
+
+/// `code` with comments replaced by spaces; string and char literals are
+/// kept as they are.
+fn strip_comments(code: &str) -> String {
+    let mut out = String::with_capacity(code.len());
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut depth = 1;
+                while depth > 0 {
+                    match (chars.next(), chars.peek()) {
+                        (Some('*'), Some('/')) => {
+                            chars.next();
+                            depth -= 1;
+                        }
+                        (Some('/'), Some('*')) => {
+                            chars.next();
+                            depth += 1;
+                        }
+                        (Some(_), _) => {}
+                        (None, _) => break,
+                    }
+                }
+                out.push(' ');
+            }
+            ('"', _) => {
+                out.push(c);
+                while let Some(c) = chars.next() {
+                    out.push(c);
+                    match c {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            // A char literal such as '"' (not a lifetime).
+            ('\'', Some(&next)) => {
+                out.push(c);
+                let mut ahead = chars.clone();
+                ahead.next();
+                if next == '\\' || ahead.peek() == Some(&'\'') {
+                    while let Some(c) = chars.next() {
+                        out.push(c);
+                        match c {
+                            '\\' => out.extend(chars.next()),
+                            '\'' => break,
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Index of the `]` closing an attribute whose `#[` precedes `s`, skipping
+/// brackets in string literals.
+fn closing_bracket(s: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            '[' => depth += 1,
+            ']' if depth == 0 => return Some(i),
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether an attribute's contents (between `#[` and `]`) mark a test.
+fn is_test_attribute(attribute: &str) -> bool {
+    let path: String = attribute
+        .split(['(', '='])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    path == "test" || path.ends_with("::test")
+}
+
+/// The name of the function `item` declares, past its visibility and
+/// qualifiers; `None` if `item` isn't a function.
+fn fn_name(item: &str) -> Option<String> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut rest = item;
+    loop {
+        rest = rest.trim_start();
+        let word = &rest[..rest.find(|c| !is_ident(c)).unwrap_or(rest.len())];
+        rest = rest[word.len()..].trim_start();
+        match word {
+            "pub" => {
+                if let Some(scope) = rest.strip_prefix('(') {
+                    rest = &scope[scope.find(')')? + 1..];
+                }
+            }
+            "async" | "unsafe" | "const" => {}
+            "extern" => {
+                if let Some(abi) = rest.strip_prefix('"') {
+                    rest = &abi[abi.find('"')? + 1..];
+                }
+            }
+            "fn" => {
+                let name: String = rest.chars().take_while(|&c| is_ident(c)).collect();
+                return (!name.is_empty()).then_some(name);
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// A rustc diagnostic of level `error`, minus the "aborting due to" trailer.
+fn compile_error(message: &serde_json::Value) -> Option<CompileError> {
+    if message["level"] != "error" {
+        return None;
+    }
+    let spans = message["spans"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let text = message["message"].as_str().unwrap_or_default();
+    if spans.is_empty() && text.starts_with("aborting due to") {
+        return None;
+    }
+    let location = spans
+        .iter()
+        .find(|s| s["is_primary"] == true)
+        .map(|s| {
+            format!(
+                "{}:{}",
+                s["file_name"].as_str().unwrap_or_default(),
+                s["line_start"]
+            )
+        })
+        .unwrap_or_default();
+    Some(CompileError {
+        code: message["code"]["code"].as_str().unwrap_or_default().to_string(),
+        message: text.to_string(),
+        location,
+        rendered: truncate(message["rendered"].as_str().unwrap_or_default()),
+    })
+}
+
+/// A libtest result line: `test {name} ... ok`, `FAILED` or `ignored`
+/// (optionally with a reason).
+fn test_case(line: &str) -> Option<TestCase> {
+    let (name, result) = line.strip_prefix("test ")?.split_once(" ... ")?;
+    let outcome = match result {
+        "ok" => TestOutcome::Passed,
+        "FAILED" => TestOutcome::Failed,
+        r if r == "ignored" || r.starts_with("ignored, ") => TestOutcome::Ignored,
+        _ => return None,
+    };
+    let name = name.strip_suffix(" - should panic").unwrap_or(name);
+    Some(TestCase {
+        name: name.to_string(),
+        outcome,
+        message: String::new(),
+    })
+}
+
+/// The test named by a `---- {name} stdout ----` header, which libtest
+/// prints before a failed test's captured output.
+fn section_header(line: &str) -> Option<&str> {
+    line.strip_prefix("---- ")?.strip_suffix(" stdout ----")
+}
+
+/// The panic payload from a failed test's captured output: the lines after
+/// `panicked at ...:`, stopping at the backtrace.
+fn panic_message(stdout: &str) -> String {
+    let mut lines = stdout.lines().skip_while(|l| !l.contains("panicked at"));
+    if lines.next().is_none() {
+        return truncate(stdout.trim());
+    }
+    let message: Vec<&str> = lines
+        .take_while(|l| !l.starts_with("stack backtrace:") && !l.starts_with("note: "))
+        .collect();
+    truncate(message.join("\n").trim())
+}
+
+fn truncate(text: &str) -> String {
+    text.chars().take(MAX_DETAIL_CHARS).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPILE_FAILURE: &str = r#"{"reason":"compiler-artifact","target":{"name":"demo"}}
+{"reason":"compiler-message","message":{"level":"error","message":"mismatched types","code":{"code":"E0308","explanation":"..."},"spans":[{"file_name":"src/lib.rs","line_start":3,"is_primary":true}],"rendered":"error[E0308]: mismatched types\n --> src/lib.rs:3:5\n"}}
+{"reason":"compiler-message","message":{"level":"error","message":"mismatched types","code":{"code":"E0308","explanation":"..."},"spans":[{"file_name":"src/lib.rs","line_start":3,"is_primary":true}],"rendered":"error[E0308]: mismatched types\n --> src/lib.rs:3:5\n"}}
+{"reason":"compiler-message","message":{"level":"error","message":"cannot find value `x` in this scope","code":{"code":"E0425","explanation":"..."},"spans":[{"file_name":"src/lib.rs","line_start":9,"is_primary":true}],"rendered":""}}
+{"reason":"compiler-message","message":{"level":"warning","message":"unused variable","code":null,"spans":[],"rendered":""}}
+{"reason":"compiler-message","message":{"level":"error","message":"aborting due to 2 previous errors","code":null,"spans":[],"rendered":""}}
+{"reason":"build-finished","success":false}"#;
+
+    /// A run of `bad`, `good` and `ign`, with test names under `prefix`.
+    fn test_failure(prefix: &str) -> String {
+        format!(
+            r#"{{"reason":"build-finished","success":true}}
+
+running 3 tests
+test {prefix}bad ... FAILED
+test {prefix}good ... ok
+test {prefix}ign ... ignored, slow
+
+failures:
+
+---- {prefix}bad stdout ----
+printed by the test
+
+thread '{prefix}bad' (41) panicked at tests/t.rs:3:20:
+assertion `left == right` failed
+  left: 1
+ right: 2
+stack backtrace:
+   0: rust_begin_unwind
+
+failures:
+    {prefix}bad
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.02s
+"#
+        )
+    }
+
+    #[test]
+    fn compile_errors_are_grouped_by_code() {
+        let report = TestReport::parse(COMPILE_FAILURE);
+        assert!(!report.compiled());
+        assert_eq!(report.compile_errors.len(), 2, "duplicates and the trailer are dropped");
+        assert_eq!(report.compile_errors[0].location, "src/lib.rs:3");
+        assert_eq!(
+            report.errors_by_code(),
+            BTreeMap::from([("E0308".to_string(), 1), ("E0425".to_string(), 1)])
+        );
+        assert_eq!(report.partial_credit(), 0.0);
+        assert_eq!(report.headline(), "error[E0308]: mismatched types");
+        assert!(report
+            .summary()
+            .contains("error[E0425]: cannot find value `x` in this scope at src/lib.rs:9"));
+    }
+
+    #[test]
+    fn test_events_give_partial_credit() {
+        let report = TestReport::parse(&test_failure(""));
+        assert!(report.compiled());
+        assert_eq!((report.passed(), report.failed(), report.total()), (1, 1, 2));
+        assert_eq!(report.partial_credit(), 0.5);
+
+        let bad = &report.tests[0];
+        assert_eq!(bad.outcome, TestOutcome::Failed);
+        assert_eq!(bad.message, "assertion `left == right` failed\n  left: 1\n right: 2");
+        assert_eq!(report.tests[2].outcome, TestOutcome::Ignored);
+
+        assert_eq!(report.headline(), "1/2 tests failed: bad");
+        assert!(report.summary().ends_with("Passed 1/2 tests."));
+    }
+
+    #[test]
+    fn test_functions_are_found_through_attributes() {
+        let test_code = r##"
+use demo::*;
+#[test]
+fn plain() {}
+#[test] pub fn public() {}
+#[tokio::test]
+async fn with_runtime() {}
+#[test]
+#[should_panic(expected = "]")]
+#[ignore]
+fn should_panic() {}
+#[allow(unused)] #[test] pub(crate) fn attribute_first() {}
+// #[test]
+// fn commented_out() {}
+/* #[test] fn in_block_comment() {} */
+#[cfg(test)]
+fn helper() { let _ = ("#[", '"'); }
+#[derive(Debug)]
+struct NotATest;
+"##;
+        assert_eq!(
+            declared_tests(test_code),
+            ["plain", "public", "with_runtime", "should_panic", "attribute_first"]
+        );
+    }
+
+    #[test]
+    fn forged_and_missing_results_do_not_earn_credit() {
+        let test_code = "use demo::*;\n#[test]\nfn good() {}\n#[test]\n#[should_panic]\nfn bad() {}\n\
+            #[test] fn ign() {}\n#[test] fn never_ran() {}\nfn helper() {}\n";
+        let declared = declared_tests(test_code);
+        assert_eq!(declared, ["good", "bad", "ign", "never_ran"]);
+        assert!(namespace_tests(test_code, "t_1f2e").starts_with("mod t_1f2e {\nuse demo::*;"));
+
+        // The solution printed passes for `bad`, `never_ran` and a test that
+        // doesn't exist, without knowing the namespace.
+        let forged = format!(
+            "{}test bad - should panic ... ok\ntest never_ran ... ok\ntest t_1f2e::extra ... ok\n",
+            test_failure("t_1f2e::")
+        );
+        let mut report = TestReport::parse(&forged);
+        assert_eq!(report.passed(), 4);
+        report.restrict_to(&declared, "t_1f2e");
+        assert_eq!((report.passed(), report.failed()), (1, 1));
+        assert_eq!(report.tests[0].name, "bad");
+        assert_eq!(report.partial_credit(), 0.25);
+        assert!(!report.all_declared_passed());
+        assert!(report.summary().ends_with("Passed 1/4 tests."));
+    }
+
+    #[test]
+    fn a_test_reported_twice_fails() {
+        // Even a forger who learned the namespace can't add a second result.
+        let mut report = TestReport::parse("test t_9a::good ... ok\ntest t_9a::good ... ok\n");
+        report.restrict_to(&["good".to_string()], "t_9a");
+        assert_eq!((report.passed(), report.failed()), (0, 1));
+        assert_eq!(report.tests[0].message, "reported more than once");
+        assert!(!report.all_declared_passed());
+
+        let mut once = TestReport::parse("test t_9a::good ... ok\n");
+        once.restrict_to(&["good".to_string()], "t_9a");
+        assert!(once.all_declared_passed());
+    }
+
+    #[test]
+    fn no_declared_tests_never_passes() {
+        let mut report = TestReport::parse("test t_9a::anything ... ok\n");
+        report.restrict_to(&declared_tests("fn not_a_test() {}"), "t_9a");
+        assert!(report.tests.is_empty());
+        assert!(!report.all_declared_passed());
+        assert_eq!(report.partial_credit(), 0.0);
+    }
+}
@@ -441,7 +441,13 @@ impl ThinkingLoop {
                                     let iq =
                                         crate::opus_bench::weighted_score_to_iq(weighted_score);
                                     // Compute worker's own ELO from its local results
-                                    crate::elo::update_rating(&self.db, weighted_score);
+                                    crate::elo::update_rating(
+                                        &self.db,
+                                        crate::contamination::rating_score(
+                                            &self.db,
+                                            weighted_score,
+                                        ),
+                                    );
                                     tracing::info!(
                                         iq = format!("{:.0}", iq),
                                         score = format!("{:.1}%", weighted_score),
@@ -465,6 +471,8 @@ impl ThinkingLoop {
                                             solution: r.generated_solution.clone(),
                                             error_output: r.error_output.clone(),
                                             total_ms: r.total_ms,
+                                            pack: r.pack.clone(),
+                                            report: r.report.clone(),
                                         })
                                         .collect();
                                     crate::collective::report_benchmark_results(
@@ -504,7 +512,7 @@ impl ThinkingLoop {
                 {
                     // If queen: distribute problems to workers before running own portion
                     if self.config.colony_role == crate::collective::ColonyRole::Queen {
-                        let problems = crate::opus_bench::load_embedded_problems();
+                        let problems = crate::problem_pack::load_problems();
                         let slugs: Vec<String> = problems.iter().map(|p| p.slug.clone()).collect();
                         let sample_size = crate::benchmark::DEFAULT_SAMPLE_SIZE;
                         let (_, worker_assignments) =
@@ -557,7 +565,11 @@ impl ThinkingLoop {
                         Ok(bench_result) => match bench_result {
                             Ok(weighted_score) => {
                                 let iq = crate::opus_bench::weighted_score_to_iq(weighted_score);
-                                crate::elo::update_rating(&self.db, weighted_score);
+                                // Rate on unseen problems once enough are measured
+                                crate::elo::update_rating(
+                                    &self.db,
+                                    crate::contamination::rating_score(&self.db, weighted_score),
+                                );
                                 // Store score for commit gate delta comparison
                                 let _ = self.db.set_state(
                                     "last_benchmark_score",
@@ -847,6 +859,13 @@ impl ThinkingLoop {
                                         .await;
                                         synced += 1;
                                     }
+                                    // Neural weights: robust aggregation across all peers at once.
+                                    crate::federation::federated_round(
+                                        &self.db,
+                                        &peer_urls,
+                                        &http_client,
+                                    )
+                                    .await;
                                     if synced > 0 {
                                         // Update MoE router with peer expertise data
                                         for (peer_id, peer_url) in &peer_urls {
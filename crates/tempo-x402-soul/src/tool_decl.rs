@@ -643,7 +643,9 @@ pub fn available_tools_with_git(coding_enabled: bool) -> Vec<FunctionDeclaration
                          you can SEE what the cartridge looks like. Use after compile_cartridge to verify \
                          the cartridge renders correctly and is functional. \
                          If the screenshot shows errors, broken layouts, or wrong behavior, \
-                         fix with edit_file and recompile, then visual_test again."
+                         fix with edit_file and recompile, then visual_test again. \
+                         Interactive (x402_tick) cartridges run headless: they are ticked for \
+                         `frames` frames replaying `inputs`, and the last frame is returned."
                 .to_string(),
             parameters: serde_json::json!({
                 "type": "object",
@@ -655,6 +657,23 @@ pub fn available_tools_with_git(coding_enabled: bool) -> Vec<FunctionDeclaration
                     "expected_behavior": {
                         "type": "string",
                         "description": "What the cartridge should look like or do (helps analyze the screenshot)"
+                    },
+                    "frames": {
+                        "type": "integer",
+                        "description": "Interactive cartridges: frames to tick before the screenshot (default 120)"
+                    },
+                    "inputs": {
+                        "type": "array",
+                        "description": "Interactive cartridges: scripted key events, e.g. [{\"frame\": 10, \"action\": \"down\", \"code\": 39}]. Codes are browser keyCodes (37-40 arrows, 32 space).",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "frame": { "type": "integer" },
+                                "action": { "type": "string", "enum": ["down", "up"] },
+                                "code": { "type": "integer" }
+                            },
+                            "required": ["frame", "action", "code"]
+                        }
                     }
                 },
                 "required": ["slug"]
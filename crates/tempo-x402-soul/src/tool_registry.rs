@@ -451,9 +451,11 @@ impl ToolRegistry {
         let request = x402_cartridge::CartridgeRequest {
             method: "POST".to_string(),
             path: "/".to_string(),
+            query: String::new(),
             body: serde_json::to_string(args).unwrap_or_else(|_| "{}".to_string()),
             headers: std::collections::HashMap::new(),
             payment: None,
+            raw_body: None,
         };
 
         // Execute in blocking context (wasmtime is synchronous)
@@ -474,7 +476,7 @@ impl ToolRegistry {
                     "Cartridge tool executed"
                 );
                 Ok(ToolResult {
-                    stdout: r.body,
+                    stdout: r.body_text(),
                     stderr: String::new(),
                     exit_code: if r.status < 400 { 0 } else { 1 },
                     duration_ms,
@@ -268,35 +268,16 @@ impl ToolExecutor {
         path: &str,
         body: &str,
     ) -> Result<ToolResult, String> {
-        let bin_dir = format!("/data/cartridges/{slug}/bin");
-
-        // Find the .wasm file
-        let wasm_path = std::fs::read_dir(&bin_dir)
-            .map_err(|e| format!("no bin dir: {e}"))?
-            .filter_map(|e| e.ok())
-            .find(|e| {
-                e.path()
-                    .extension()
-                    .map(|ext| ext == "wasm")
-                    .unwrap_or(false)
-            })
-            .map(|e| e.path())
-            .ok_or_else(|| format!("no .wasm binary found for '{slug}' — compile it first"))?;
-
-        // Create a temporary engine and load the module
-        let engine = x402_cartridge::CartridgeEngine::new("/data/cartridges")
-            .map_err(|e| format!("engine init failed: {e}"))?;
-
-        engine
-            .load_module(slug, &wasm_path)
-            .map_err(|e| format!("module load failed: {e}"))?;
+        let engine = load_temp_engine(slug)?;
 
         let request = x402_cartridge::CartridgeRequest {
             method: method.to_string(),
             path: path.to_string(),
+            query: String::new(),
             body: body.to_string(),
             headers: std::collections::HashMap::new(),
             payment: None,
+            raw_body: None,
         };
 
         let start = std::time::Instant::now();
@@ -304,7 +285,10 @@ impl ToolExecutor {
             Ok((result, _kv)) => Ok(ToolResult {
                 stdout: format!(
                     "Status: {}\nContent-Type: {}\nDuration: {}ms\n\nBody:\n{}",
-                    result.status, result.content_type, result.duration_ms, result.body
+                    result.status,
+                    result.content_type,
+                    result.duration_ms,
+                    result.body_text()
                 ),
                 stderr: String::new(),
                 exit_code: 0,
@@ -581,11 +565,40 @@ impl ToolExecutor {
 
     /// Visually test a cartridge by opening it in a browser and taking a screenshot.
     /// Returns the screenshot as base64 PNG (injected into LLM conversation as image).
+    ///
+    /// Interactive cartridges are run headless instead: ticked for `frames`
+    /// frames with the scripted `inputs`, no display or browser needed.
     pub(super) async fn visual_test_cartridge(
         &self,
         slug: &str,
         expected_behavior: Option<&str>,
+        frames: Option<u32>,
+        inputs: Vec<x402_cartridge::InputEvent>,
     ) -> Result<ToolResult, String> {
+        // Module compilation and up to 600 ticks are synchronous wasmtime
+        // work; keep them off the async runtime.
+        let headless = {
+            let slug = slug.to_string();
+            let expected_behavior = expected_behavior.map(str::to_string);
+            tokio::task::spawn_blocking(move || {
+                let engine = load_temp_engine(&slug).ok()?;
+                engine.is_interactive(&slug).then(|| {
+                    headless_visual_test(
+                        &engine,
+                        &slug,
+                        expected_behavior.as_deref(),
+                        frames,
+                        inputs,
+                    )
+                })
+            })
+            .await
+            .map_err(|e| format!("headless run panicked: {e}"))?
+        };
+        if let Some(result) = headless {
+            return result;
+        }
+
         let base_url = self
             .gateway_url
             .as_deref()
@@ -671,3 +684,109 @@ impl ToolExecutor {
         })
     }
 }
+
+/// Load a cartridge's compiled .wasm into a throwaway engine.
+fn load_temp_engine(slug: &str) -> Result<x402_cartridge::CartridgeEngine, String> {
+    let bin_dir = format!("/data/cartridges/{slug}/bin");
+
+    // Find the .wasm file
+    let wasm_path = std::fs::read_dir(&bin_dir)
+        .map_err(|e| format!("no bin dir: {e}"))?
+        .filter_map(|e| e.ok())
+        .find(|e| {
+            e.path()
+                .extension()
+                .map(|ext| ext == "wasm")
+                .unwrap_or(false)
+        })
+        .map(|e| e.path())
+        .ok_or_else(|| format!("no .wasm binary found for '{slug}' — compile it first"))?;
+
+    // Create a temporary engine and load the module
+    let engine = x402_cartridge::CartridgeEngine::new("/data/cartridges")
+        .map_err(|e| format!("engine init failed: {e}"))?;
+
+    engine
+        .load_module(slug, &wasm_path)
+        .map_err(|e| format!("module load failed: {e}"))?;
+    Ok(engine)
+}
+
+/// Tick an interactive cartridge headless and report what it rendered.
+/// The final frame is attached as a screenshot for the vision input.
+fn headless_visual_test(
+    engine: &x402_cartridge::CartridgeEngine,
+    slug: &str,
+    expected_behavior: Option<&str>,
+    frames: Option<u32>,
+    inputs: Vec<x402_cartridge::InputEvent>,
+) -> Result<ToolResult, String> {
+    use base64::Engine;
+    use x402_cartridge::headless::hash_distance;
+
+    let frames = frames.unwrap_or(120).clamp(1, 600);
+    let opts = x402_cartridge::HeadlessOptions {
+        frames,
+        inputs,
+        capture: x402_cartridge::Capture::Every((frames / 4).max(1)),
+        ..Default::default()
+    };
+    let run = match engine.run_headless(slug, &opts) {
+        Ok(run) => run,
+        Err(e) => {
+            return Ok(ToolResult {
+                stdout: String::new(),
+                stderr: format!("Headless run of /c/{slug} failed: {e}"),
+                exit_code: 1,
+                duration_ms: 0,
+            })
+        }
+    };
+    let Some(last) = run.captured.last() else {
+        return Err("headless run captured no frames".to_string());
+    };
+
+    let mut lines = Vec::new();
+    let mut changed = false;
+    let mut prev: Option<u64> = None;
+    for frame in &run.captured {
+        let hash = frame.phash();
+        let note = if frame.is_uniform() { " (blank)" } else { "" };
+        lines.push(format!(
+            "  frame {:>4}: phash {hash:016x}{note}",
+            frame.index()
+        ));
+        changed |= prev.is_some_and(|p| hash_distance(p, hash) > 0);
+        prev = Some(hash);
+    }
+    let verdict = if last.is_uniform() {
+        "WARNING: final frame is a single solid color — nothing is being drawn."
+    } else if !changed {
+        "Note: captured frames are identical — no animation or the game is waiting for input."
+    } else {
+        "Frames change over time — the cartridge is animating."
+    };
+    let png = last.to_png().map_err(|e| e.to_string())?;
+    let screenshot_base64 = base64::engine::general_purpose::STANDARD.encode(&png);
+    let expected_note = expected_behavior
+        .map(|b| format!("\nExpected behavior: {b}"))
+        .unwrap_or_default();
+
+    Ok(ToolResult {
+        stdout: format!(
+            "Headless visual test of /c/{slug}: {} frames at {}x{}, {} fuel, {}ms\n\
+             {}\n{verdict}{expected_note}\n\
+             The screenshot is the final frame. Does the cartridge look correct?\n\
+             SCREENSHOT_BASE64:{screenshot_base64}",
+            run.frames_run,
+            run.width,
+            run.height,
+            run.fuel_consumed,
+            run.duration_ms,
+            lines.join("\n"),
+        ),
+        stderr: String::new(),
+        exit_code: 0,
+        duration_ms: run.duration_ms,
+    })
+}
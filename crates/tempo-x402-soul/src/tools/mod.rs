@@ -545,7 +545,16 @@ impl ToolExecutor {
                     .and_then(|v| v.as_str())
                     .ok_or_else(|| "missing 'slug' argument".to_string())?;
                 let expected = args.get("expected_behavior").and_then(|v| v.as_str());
-                self.visual_test_cartridge(slug, expected).await
+                let frames = args
+                    .get("frames")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as u32);
+                let inputs = args
+                    .get("inputs")
+                    .and_then(|v| serde_json::from_value(v.clone()).ok())
+                    .unwrap_or_default();
+                self.visual_test_cartridge(slug, expected, frames, inputs)
+                    .await
             }
             "create_cognitive_cartridge" => {
                 let system = args
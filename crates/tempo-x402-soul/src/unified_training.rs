@@ -12,41 +12,233 @@ use crate::db::SoulDatabase;
 
 const MAX_TRAIN_SECS: u64 = 30;
 
-/// Load the unified model from file or sled.
-fn load_model(db: &SoulDatabase) -> x402_model::unified::UnifiedModel {
-    // Try file first
-    let path = std::path::Path::new("/tmp/unified_model.json");
-    if path.exists() {
-        if let Ok(json) = std::fs::read_to_string(path) {
-            if let Some(model) = x402_model::unified::UnifiedModel::from_json(&json) {
-                return model;
-            }
+/// Binary checkpoint path for the unified model.
+const MODEL_PATH: &str = "/tmp/unified_model.ckpt";
+/// Pre-checkpoint JSON path, migrated on first load.
+const LEGACY_JSON_PATH: &str = "/tmp/unified_model.json";
+/// Optimizer moments + step, saved next to the model so training resumes
+/// exactly after a restart.
+const OPTIMIZER_PATH: &str = "/tmp/unified_model.opt.ckpt";
+/// Examples (fast and slow head mixed) accumulated per optimizer step.
+const BATCH_SIZE: usize = 4;
+
+/// AdamW, 100-step warmup to 3e-4 then cosine decay to 3e-5 over 10k steps.
+fn optimizer_config() -> x402_model::OptimizerConfig {
+    x402_model::OptimizerConfig {
+        kind: x402_model::OptimizerKind::adamw(),
+        schedule: x402_model::Schedule::WarmupCosine {
+            peak_lr: 3e-4,
+            min_lr: 3e-5,
+            warmup_steps: 100,
+            decay_steps: 10_000,
+        },
+        clip_norm: Some(1.0),
+    }
+}
+
+/// Load saved optimizer state, starting fresh if there is none, its config
+/// changed, or it belongs to a model that has since been reinitialized.
+fn load_optimizer(model: &x402_model::unified::UnifiedModel) -> x402_model::Optimizer {
+    use x402_model::Checkpointable;
+    let config = optimizer_config();
+    match x402_model::Optimizer::load_file(OPTIMIZER_PATH) {
+        Ok(opt) if opt.config == config && opt.steps() <= model.train_steps => opt,
+        Ok(_) => {
+            tracing::info!("Unified optimizer state stale — starting fresh");
+            x402_model::Optimizer::new(config)
         }
+        Err(x402_model::CheckpointError::Io(_)) => x402_model::Optimizer::new(config),
+        Err(e) => {
+            tracing::warn!(error = %e, "Unified optimizer checkpoint rejected — starting fresh");
+            x402_model::Optimizer::new(config)
+        }
+    }
+}
+
+fn save_optimizer(opt: &x402_model::Optimizer) {
+    use x402_model::Checkpointable;
+    if let Err(e) = opt.save_file(OPTIMIZER_PATH) {
+        tracing::warn!(error = %e, "Failed to save unified optimizer state");
+    }
+}
+
+/// Apply the accumulated batch (if any) and reset it.
+fn flush(
+    model: &mut x402_model::unified::UnifiedModel,
+    opt: &mut x402_model::Optimizer,
+    grads: &mut x402_model::Gradients,
+    last: &mut Option<x402_model::StepStats>,
+) {
+    if let Some(stats) = model.apply_gradients(opt, grads) {
+        *last = Some(stats);
+    }
+    grads.clear();
+}
+
+/// Architecture this node runs: `SOUL_UNIFIED_CONFIG` as JSON, unset fields
+/// taking the defaults — e.g. `{"d_model":256,"n_heads":4,"enc_layers":2,
+/// "dec_layers":2}` for a small node.
+fn target_config() -> x402_model::unified::ModelConfig {
+    let Some(raw) = std::env::var("SOUL_UNIFIED_CONFIG")
+        .ok()
+        .filter(|s| !s.is_empty())
+    else {
+        return Default::default();
+    };
+    let config = serde_json::from_str::<x402_model::unified::ModelConfig>(&raw)
+        .map_err(|e| e.to_string())
+        .and_then(|c| c.validate().map(|()| c).map_err(|e| e.to_string()));
+    config.unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "Invalid SOUL_UNIFIED_CONFIG — using the default architecture");
+        Default::default()
+    })
+}
+
+/// Load the unified model from its checkpoint file (mmap'd), migrating a
+/// legacy JSON file if that is all there is. A checkpoint smaller than the
+/// configured architecture is grown into it; a larger one is kept as is,
+/// since shrinking would throw training away.
+pub(crate) fn load_model(db: &SoulDatabase) -> x402_model::unified::UnifiedModel {
+    let target = target_config();
+    let Some(model) = load_checkpoint(db) else {
+        return x402_model::unified::UnifiedModel::with_config(target);
+    };
+    if model.config == target {
+        return model;
     }
-    // Fall back to sled
-    match db.get_state("unified_model_meta").ok().flatten() {
-        Some(meta) if meta.contains("steps") => {
-            // Model exists but stored in file — if file missing, reinit
-            x402_model::unified::UnifiedModel::new()
+    match model.grow(target) {
+        Ok(grown) => {
+            tracing::info!(
+                from = %model.config.describe(),
+                to = %target.describe(),
+                "Grew unified model to the configured architecture"
+            );
+            save_model(db, &grown);
+            grown
+        }
+        Err(e) => {
+            tracing::warn!(
+                error = %e,
+                keeping = %model.config.describe(),
+                "Unified checkpoint does not fit SOUL_UNIFIED_CONFIG"
+            );
+            model
         }
-        _ => x402_model::unified::UnifiedModel::new(),
     }
 }
 
-/// Save the unified model to file + lightweight marker in sled.
-fn save_model(db: &SoulDatabase, model: &x402_model::unified::UnifiedModel) {
-    let json = model.to_json();
-    let path = std::path::Path::new("/tmp/unified_model.json");
-    if let Err(e) = std::fs::write(path, &json) {
+fn load_checkpoint(db: &SoulDatabase) -> Option<x402_model::unified::UnifiedModel> {
+    use x402_model::Checkpointable;
+    match x402_model::unified::UnifiedModel::load_file(MODEL_PATH) {
+        Ok(model) => return Some(model),
+        Err(x402_model::CheckpointError::Io(_)) => {}
+        Err(e) => tracing::warn!(error = %e, "Unified model checkpoint rejected — reinitializing"),
+    }
+    let model = x402_model::unified::UnifiedModel::load_file(LEGACY_JSON_PATH).ok()?;
+    save_model(db, &model);
+    let _ = std::fs::remove_file(LEGACY_JSON_PATH);
+    tracing::info!("Migrated unified model from JSON to binary checkpoint");
+    Some(model)
+}
+
+/// Save the unified model to its checkpoint file + lightweight marker in sled.
+pub(crate) fn save_model(db: &SoulDatabase, model: &x402_model::unified::UnifiedModel) {
+    use x402_model::Checkpointable;
+    if let Err(e) = model.save_file(MODEL_PATH) {
         tracing::warn!(error = %e, "Failed to save unified model");
     }
-    let marker = format!(
-        r#"{{"steps":{},"loss":{:.4},"params":{}}}"#,
-        model.train_steps,
-        model.running_loss,
-        model.param_count(),
-    );
-    let _ = db.set_state("unified_model_meta", &marker);
+    let marker = serde_json::json!({
+        "steps": model.train_steps,
+        "loss": model.running_loss,
+        "params": model.param_count(),
+        "config": model.config,
+    });
+    let _ = db.set_state("unified_model_meta", &marker.to_string());
+}
+
+// ── Example encoding (shared with `model_eval`) ───────────────────
+
+/// Error categories the fast head predicts, at output indices 1..=8; the
+/// last slot collects every other category.
+pub(crate) const FAST_CATEGORIES: [&str; 8] = [
+    "compile_error",
+    "test_failure",
+    "shell_error",
+    "network_error",
+    "protected_file",
+    "git_error",
+    "llm_parse_error",
+    "other",
+];
+
+/// Decoder target length used in training (BOS and EOS included).
+const TRAIN_TARGET_TOKENS: usize = 64;
+
+/// Fast-head input text for a plan outcome. The outcome's status is the
+/// label, so it is left out.
+pub(crate) fn predict_prompt(outcome: &crate::feedback::PlanOutcome) -> String {
+    format!(
+        "[PREDICT] goal={} steps={} replan={}",
+        &outcome
+            .goal_description
+            .chars()
+            .take(100)
+            .collect::<String>(),
+        outcome.steps_completed,
+        outcome.replan_count,
+    )
+}
+
+pub(crate) fn predict_tokens(tok: &x402_model::bpe::BpeTokenizer, prompt: &str) -> Vec<u32> {
+    let mut tokens = vec![x402_model::unified::TASK_PREDICT];
+    tokens.extend(tok.encode(prompt));
+    tokens.truncate(128);
+    tokens
+}
+
+/// Fast-head targets: [success_prob, 8 error categories (one-hot), ...].
+pub(crate) fn fast_targets(success: bool, category: Option<&str>) -> Vec<f32> {
+    let mut targets = vec![0.0f32; x402_model::unified::FAST_OUTPUT];
+    targets[0] = if success { 1.0 } else { 0.0 };
+    if let Some(cat) = category.filter(|_| !success) {
+        let idx = FAST_CATEGORIES
+            .iter()
+            .position(|&c| c == cat)
+            .unwrap_or(FAST_CATEGORIES.len() - 1);
+        targets[1 + idx] = 1.0;
+    }
+    targets
+}
+
+/// Encoder input for a code example: the test code it answers when known,
+/// otherwise the start of the code itself.
+pub(crate) fn code_context_tokens(
+    tok: &x402_model::bpe::BpeTokenizer,
+    context: Option<&str>,
+    code: &str,
+    max_seq: usize,
+) -> Vec<u32> {
+    let context_text = match context {
+        Some(ctx) => format!("[CODE] {}", &ctx.chars().take(1000).collect::<String>()),
+        None => format!("[CODE] {}", &code.chars().take(200).collect::<String>()),
+    };
+    let mut tokens = vec![x402_model::unified::TASK_CODE];
+    tokens.extend(tok.encode(&context_text));
+    tokens.truncate(max_seq);
+    tokens
+}
+
+/// Decoder target for a code example: BOS, the code, EOS, cut to `max_len`.
+pub(crate) fn code_target_tokens(
+    tok: &x402_model::bpe::BpeTokenizer,
+    code: &str,
+    max_len: usize,
+) -> Vec<u32> {
+    let mut tokens = vec![x402_model::bpe::BOS_TOKEN];
+    tokens.extend(tok.encode(code));
+    tokens.push(x402_model::bpe::EOS_TOKEN);
+    tokens.truncate(max_len);
+    tokens
 }
 
 /// Train the unified model on all available cognitive data.
@@ -54,6 +246,9 @@ fn save_model(db: &SoulDatabase, model: &x402_model::unified::UnifiedModel) {
 pub fn train_cycle(db: &SoulDatabase) {
     let start = std::time::Instant::now();
     let mut model = load_model(db);
+    let mut opt = load_optimizer(&model);
+    let mut grads = x402_model::Gradients::new();
+    let mut last_step = None;
     let tok = crate::codegen::load_tokenizer(db);
     if tok.merges.is_empty() {
         return; // BPE not ready
@@ -67,62 +262,27 @@ pub fn train_cycle(db: &SoulDatabase) {
     // ── FAST HEAD: Brain prediction data ──
     // Collect recent step outcomes and train the fast head to predict success
     let plan_outcomes = db.get_recent_plan_outcomes(20).unwrap_or_default();
-    for outcome in plan_outcomes.iter().take(5) {
+    let plan_outcomes = plan_outcomes
+        .iter()
+        .filter(|o| !crate::model_eval::is_held_out(&o.id));
+    for outcome in plan_outcomes.take(5) {
         if start.elapsed().as_secs() >= MAX_TRAIN_SECS {
             break;
         }
-
-        // Encode the outcome as a text prompt for the shared encoder
-        let input_text = format!(
-            "[PREDICT] goal={} status={} steps={} replan={}",
-            &outcome
-                .goal_description
-                .chars()
-                .take(100)
-                .collect::<String>(),
-            outcome.status,
-            outcome.steps_completed,
-            outcome.replan_count,
-        );
-
-        let mut tokens = vec![x402_model::unified::TASK_PREDICT];
-        tokens.extend(tok.encode(&input_text));
-        if tokens.len() > 128 {
-            tokens.truncate(128);
-        }
+        let category = outcome.error_category.as_ref().map(|c| c.as_str());
+        let tokens = predict_tokens(&tok, &predict_prompt(outcome));
         if tokens.len() < 3 {
             continue;
         }
+        let targets = fast_targets(outcome.status == "completed", category);
 
-        // Target: [success_prob, 11 error cats (one-hot), 11 capabilities, quality]
-        let mut targets = vec![0.0f32; x402_model::unified::FAST_OUTPUT];
-        targets[0] = if outcome.status == "completed" {
-            1.0
-        } else {
-            0.0
-        };
-        // Error category (if failed)
-        if let Some(ref cat) = outcome.error_category {
-            let cat_idx = match cat.as_str() {
-                "compilation" => 1,
-                "test_failure" => 2,
-                "runtime_error" => 3,
-                "timeout" => 4,
-                "protected_file" => 5,
-                "empty_output" => 6,
-                "api_error" => 7,
-                _ => 8,
-            };
-            if cat_idx < 12 {
-                targets[cat_idx] = 1.0;
-            }
-        }
-
-        let lr = learning_rate(model.train_steps);
-        let loss = model.train_fast(&tokens, &targets, lr);
+        let loss = model.fast_gradients(&tokens, &targets, &mut grads);
         total_loss += loss;
         trained += 1;
         fast_trained += 1;
+        if grads.examples() >= BATCH_SIZE {
+            flush(&mut model, &mut opt, &mut grads, &mut last_step);
+        }
     }
 
     // ── SLOW HEAD: Codegen data (test → solution pairs) ──
@@ -132,6 +292,14 @@ pub fn train_cycle(db: &SoulDatabase) {
         .flatten()
         .and_then(|s| serde_json::from_str(&s).ok())
         .unwrap_or_default();
+    let split = crate::contamination::current(db);
+    let solutions: Vec<&serde_json::Value> = solutions
+        .iter()
+        .filter(|sol| {
+            let code = sol.get("code").and_then(|v| v.as_str()).unwrap_or("");
+            !crate::model_eval::is_held_out(code) && !split.excludes(sol)
+        })
+        .collect();
 
     let offset = (model.train_steps as usize) % solutions.len().max(1);
     for sol in solutions.iter().cycle().skip(offset).take(3) {
@@ -145,39 +313,26 @@ pub fn train_cycle(db: &SoulDatabase) {
         if code.len() < 50 {
             continue;
         }
-
-        // Context: test code (if available) with [CODE] prefix
-        let context_text = if let Some(ctx) = sol.get("context").and_then(|v| v.as_str()) {
-            format!("[CODE] {}", &ctx.chars().take(1000).collect::<String>())
-        } else {
-            format!("[CODE] {}", &code.chars().take(200).collect::<String>())
-        };
-
-        let mut context_tokens = vec![x402_model::unified::TASK_CODE];
-        context_tokens.extend(tok.encode(&context_text));
-        if context_tokens.len() > x402_model::unified::MAX_SEQ {
-            context_tokens.truncate(x402_model::unified::MAX_SEQ);
-        }
-
-        let mut target_tokens = vec![x402_model::bpe::BOS_TOKEN];
-        target_tokens.extend(tok.encode(code));
-        target_tokens.push(x402_model::bpe::EOS_TOKEN);
-        if target_tokens.len() > 64 {
-            target_tokens.truncate(64);
-        }
+        let context = sol.get("context").and_then(|v| v.as_str());
+        let context_tokens = code_context_tokens(&tok, context, code, model.config.max_seq);
+        let target_tokens = code_target_tokens(&tok, code, TRAIN_TARGET_TOKENS);
         if target_tokens.len() < 3 {
             continue;
         }
 
-        let lr = learning_rate(model.train_steps);
-        let loss = model.train_slow(&context_tokens, &target_tokens, lr);
+        let loss = model.slow_gradients(&context_tokens, &target_tokens, &mut grads);
         total_loss += loss;
         trained += 1;
         slow_trained += 1;
+        if grads.examples() >= BATCH_SIZE {
+            flush(&mut model, &mut opt, &mut grads, &mut last_step);
+        }
     }
+    flush(&mut model, &mut opt, &mut grads, &mut last_step);
 
     if trained > 0 {
         save_model(db, &model);
+        save_optimizer(&opt);
         tracing::info!(
             trained,
             fast = fast_trained,
@@ -185,6 +340,7 @@ pub fn train_cycle(db: &SoulDatabase) {
             loss = format!("{:.4}", total_loss / trained as f32),
             running_loss = format!("{:.4}", model.running_loss),
             steps = model.train_steps,
+            lr = opt.current_lr(),
             params = model.param_count(),
             elapsed_secs = start.elapsed().as_secs(),
             "Unified model trained (all tasks)"
@@ -193,17 +349,18 @@ pub fn train_cycle(db: &SoulDatabase) {
         // Store loss for status API
         let _ = db.set_state("unified_model_loss", &format!("{:.4}", model.running_loss));
         let _ = db.set_state("unified_model_steps", &model.train_steps.to_string());
-    }
-}
-
-/// Learning rate with warmup and cosine decay.
-fn learning_rate(step: u64) -> f32 {
-    let s = step as f32;
-    if s < 200.0 {
-        0.0005 + (0.002 - 0.0005) * (s / 200.0)
-    } else {
-        let decay = ((s - 200.0) * std::f32::consts::PI / 10000.0).cos();
-        0.0005 + (0.002 - 0.0005) * 0.5 * (1.0 + decay)
+        if let Some(stats) = last_step {
+            let _ = db.set_state(
+                "unified_optimizer",
+                &serde_json::json!({
+                    "kind": "adamw",
+                    "batch_size": BATCH_SIZE,
+                    "last_step": stats,
+                    "next_lr": opt.current_lr(),
+                })
+                .to_string(),
+            );
+        }
     }
 }
 
@@ -219,10 +376,28 @@ pub fn status(db: &SoulDatabase) -> serde_json::Value {
         .ok()
         .flatten()
         .unwrap_or_else(|| "0".to_string());
+    let optimizer = db
+        .get_state("unified_optimizer")
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok());
+    // Shape of the last saved model, or the configured one before the first save.
+    let config = db
+        .get_state("unified_model_meta")
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+        .and_then(|meta| serde_json::from_value(meta["config"].clone()).ok())
+        .unwrap_or_else(target_config);
     serde_json::json!({
         "loss": loss,
         "steps": steps,
-        "params": x402_model::unified::UnifiedModel::new().param_count(),
-        "architecture": "shared encoder (3 layers D=384) + fast head + slow decoder",
+        "optimizer": optimizer,
+        "params": config.param_count(),
+        "config": config,
+        "architecture": format!(
+            "shared encoder + fast head + slow decoder ({})",
+            config.describe()
+        ),
     })
 }
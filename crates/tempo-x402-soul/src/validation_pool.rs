@@ -0,0 +1,623 @@
+//! Bounded worker pool for benchmark validation.
+//!
+//! Each attempt is a `cargo test` in its own [`SandboxCommand`], so every
+//! workspace is a private tmpfs. Two sessions, or a queen and a worker on the
+//! same host, can validate the same problem at once without sharing files.
+//! The pool caps how many sandboxes run at a time.
+//!
+//! ## Dependency caches
+//!
+//! A problem with dependencies would otherwise rebuild them on every attempt.
+//! Instead, they are compiled once per locked dependency set and toolchain,
+//! into `{cache_dir}/{key}`. Each attempt then mounts that directory as an
+//! overlay on its `CARGO_TARGET_DIR`. Attempts never write to a cache; their
+//! changes stay in their own tmpfs, so there is no shared cargo build lock to
+//! wait on. A cache is built in a scratch directory and published by atomic
+//! rename, so readers never see a half-built one. Concurrent builders, in
+//! this process or another, can only race to publish identical results.
+//!
+//! ## Cancellation
+//!
+//! Every call takes a [`CancelToken`]. Cancelling it makes queued attempts
+//! return [`Cancelled`] without running, and kills sandboxes that are running.
+//!
+//! ## Test output
+//!
+//! Attempts print cargo's build messages as JSON and libtest's usual text
+//! results, both stable, and [`TestReport`] parses the two.
+//!
+//! The test file gets a random name, so the solution can't `include_str!`
+//! it while compiling, and a runner script deletes it before the test
+//! binary starts, so the solution can't read it while the tests run. Either
+//! would reveal the namespace that makes test results unforgeable (see
+//! `test_report`).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use sha2::{Digest, Sha256};
+use tokio::sync::{watch, Semaphore, SemaphorePermit};
+use x402_cartridge::sandbox::{self, SandboxCommand, SandboxError, SandboxLimits};
+
+use crate::benchmark::BenchmarkProblem;
+use crate::test_report::{declared_tests, namespace_tests, TestReport};
+
+/// Default root for precompiled dependency caches.
+const DEFAULT_CACHE_DIR: &str = "/tmp/bench_deps";
+
+/// Wall-clock limit for one sandboxed `cargo test`.
+const VALIDATE_TIMEOUT_SECS: u64 = 90;
+
+/// Building a cache compiles every dependency from scratch.
+const CACHE_BUILD_TIMEOUT_SECS: u64 = 600;
+
+/// `CARGO_TARGET_DIR` inside the sandbox.
+const TARGET_DIR: &str = "/work/target";
+
+/// Environment shared by attempts and cache builds, so cached artifacts
+/// match. Each attempt builds once from scratch, so incremental compilation
+/// would only cost time and tmpfs space.
+const CARGO_ENV: &[(&str, &str)] = &[
+    ("CARGO_TARGET_DIR", TARGET_DIR),
+    ("CARGO_INCREMENTAL", "0"),
+];
+
+/// Cargo's `runner` for the test binary, relative to the crate root.
+const RUNNER: &str = "x402-runner.sh";
+
+/// Deletes the test source, then runs the test binary.
+const RUNNER_SCRIPT: &str = "rm -rf tests\nexec \"$@\"\n";
+
+/// Outcome of one validation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validation {
+    pub passed: bool,
+    /// Prompt-ready failure text: the report's summary, or the setup/cargo
+    /// error when there is no report.
+    pub error_output: String,
+    pub report: TestReport,
+}
+
+/// The validation was cancelled before it finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("validation cancelled")]
+pub struct Cancelled;
+
+/// Cancels a group of validations. Clones share one flag.
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    flag: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self {
+            flag: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.flag.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.flag.borrow()
+    }
+
+    /// Resolves once [`cancel`](Self::cancel) has been called.
+    pub async fn cancelled(&self) {
+        let mut rx = self.flag.subscribe();
+        // The sender lives in `self`, so `wait_for` cannot see it dropped.
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// A bounded set of validation slots plus the dependency caches they share.
+#[derive(Debug)]
+pub struct ValidationPool {
+    permits: Semaphore,
+    workers: usize,
+    cache_dir: PathBuf,
+    /// One build at a time per cache key within this process.
+    building: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+static GLOBAL: OnceLock<ValidationPool> = OnceLock::new();
+
+impl ValidationPool {
+    pub fn new(workers: usize, cache_dir: impl Into<PathBuf>) -> Self {
+        let workers = workers.max(1);
+        Self {
+            permits: Semaphore::new(workers),
+            workers,
+            cache_dir: cache_dir.into(),
+            building: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The process-wide pool behind [`validate_solution`](crate::benchmark::validate_solution).
+    /// Sized by `SOUL_BENCH_WORKERS`, defaulting to half the available CPUs.
+    pub fn global() -> &'static ValidationPool {
+        GLOBAL.get_or_init(|| Self::new(default_workers(), DEFAULT_CACHE_DIR))
+    }
+
+    /// Size the global pool. Only works before its first use; returns
+    /// `false` if it already exists.
+    pub fn configure_global(workers: usize) -> bool {
+        GLOBAL
+            .set(Self::new(workers, DEFAULT_CACHE_DIR))
+            .is_ok()
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    async fn acquire(&self, cancel: &CancelToken) -> Result<SemaphorePermit<'_>, Cancelled> {
+        if cancel.is_cancelled() {
+            return Err(Cancelled);
+        }
+        tokio::select! {
+            permit = self.permits.acquire() => Ok(permit.expect("pool semaphore is never closed")),
+            _ = cancel.cancelled() => Err(Cancelled),
+        }
+    }
+
+    /// Run the problem's tests against `solution` once a slot is free.
+    pub async fn validate(
+        &self,
+        problem: &BenchmarkProblem,
+        solution: &str,
+        cancel: &CancelToken,
+    ) -> Result<Validation, Cancelled> {
+        let _permit = self.acquire(cancel).await?;
+
+        let manifest = manifest(problem);
+        // Remove #[ignore] attributes so all tests run
+        let test_code = problem.test_code.replace("#[ignore]", "");
+        let declared = declared_tests(&test_code);
+        // Fresh per run, so the solution can't print results that pass for
+        // real ones (see `test_report`).
+        let namespace = format!("t_{}", random_id());
+        let test_code = namespace_tests(&test_code, &namespace);
+        // Not the slug, so the solution can't guess the path to include it.
+        let test_target = format!("tests_{}", random_id());
+        let mut command = cargo()
+            .args(["test", "--offline", "--no-fail-fast", "--message-format=json"])
+            .args(["--test", &test_target])
+            .arg("--config")
+            .arg(format!("target.'cfg(all())'.runner = ['sh', '{RUNNER}']"))
+            .file("Cargo.toml", manifest.as_str())
+            .file("src/lib.rs", solution)
+            .file(format!("tests/{test_target}.rs"), test_code)
+            .file(RUNNER, RUNNER_SCRIPT)
+            .limits(SandboxLimits {
+                timeout_secs: VALIDATE_TIMEOUT_SECS,
+                ..SandboxLimits::default()
+            });
+
+        // Only a custom manifest can have dependencies.
+        if !problem.cargo_toml.is_empty() {
+            let lock = match sandbox::fetch_manifest(&manifest).await {
+                Ok(lock) => lock,
+                Err(e) => {
+                    return Ok(Validation {
+                        passed: false,
+                        error_output: format!("Setup failed: {e}"),
+                        report: TestReport::default(),
+                    })
+                }
+            };
+            if let Some(cache) = self.dependency_cache(&manifest, &lock).await {
+                command = command.overlay(cache, TARGET_DIR);
+            }
+            command = command.file("Cargo.lock", lock);
+        }
+
+        let output = tokio::select! {
+            output = command.output() => output,
+            _ = cancel.cancelled() => return Err(Cancelled),
+        };
+        Ok(interpret(output, &declared, &namespace))
+    }
+
+    /// Precompile dependency caches for `problems` ahead of a run, using all
+    /// workers. Returns how many distinct manifests have a cache ready.
+    pub async fn warm(
+        &self,
+        problems: &[BenchmarkProblem],
+        cancel: &CancelToken,
+    ) -> Result<usize, Cancelled> {
+        let mut manifests: Vec<String> = problems
+            .iter()
+            .filter(|p| !p.cargo_toml.is_empty())
+            .map(manifest)
+            .collect();
+        manifests.sort();
+        manifests.dedup();
+
+        let builds = manifests.iter().map(|manifest| async move {
+            let _permit = self.acquire(cancel).await?;
+            let ready = match sandbox::fetch_manifest(manifest).await {
+                Ok(lock) => {
+                    tokio::select! {
+                        cache = self.dependency_cache(manifest, &lock) => cache.is_some(),
+                        _ = cancel.cancelled() => return Err(Cancelled),
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "Validation pool: dependency fetch failed");
+                    false
+                }
+            };
+            Ok::<_, Cancelled>(ready)
+        });
+        let mut ready = 0;
+        for built in futures::future::join_all(builds).await {
+            ready += built? as usize;
+        }
+        Ok(ready)
+    }
+
+    /// The precompiled target dir for this dependency set, building it if
+    /// needed. `None` means no dependencies, or the build failed (the attempt
+    /// then compiles them itself).
+    async fn dependency_cache(&self, manifest: &str, lock: &str) -> Option<PathBuf> {
+        let key = cache_key(manifest, lock)?;
+        let dir = self.cache_dir.join(&key);
+        if dir.is_dir() {
+            return Some(dir);
+        }
+        let flight = self
+            .building
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let _guard = flight.lock().await;
+        if dir.is_dir() {
+            return Some(dir);
+        }
+
+        let started = std::time::Instant::now();
+        match build_cache(manifest, lock, &self.cache_dir, &key).await {
+            Ok(()) => {
+                tracing::info!(
+                    key = %key,
+                    secs = started.elapsed().as_secs(),
+                    "Validation pool: dependency cache built"
+                );
+                Some(dir)
+            }
+            Err(e) => {
+                tracing::warn!(key = %key, error = %e, "Validation pool: dependency cache failed");
+                None
+            }
+        }
+    }
+}
+
+fn default_workers() -> usize {
+    std::env::var("SOUL_BENCH_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get() / 2)
+                .unwrap_or(1)
+        })
+        .max(1)
+}
+
+/// Sandboxed cargo with [`CARGO_ENV`].
+fn cargo() -> SandboxCommand {
+    let mut command = SandboxCommand::new("cargo");
+    for (key, value) in CARGO_ENV {
+        command = command.env(*key, *value);
+    }
+    command
+}
+
+/// 12 random hex digits.
+fn random_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..12].to_string()
+}
+
+/// The problem's Cargo.toml, or a minimal one named after the slug.
+fn manifest(problem: &BenchmarkProblem) -> String {
+    if !problem.cargo_toml.is_empty() {
+        problem.cargo_toml.clone()
+    } else {
+        // Use the exercise slug as the crate name — tests import `use {slug}::*`
+        format!(
+            "[package]\n\
+             name = \"{slug}\"\n\
+             version = \"0.1.0\"\n\
+             edition = \"2021\"\n",
+            slug = problem.slug
+        )
+    }
+}
+
+/// Identifies a dependency build: the locked registry/git packages, the
+/// manifest outside `[package]` (features, profiles), the toolchain and
+/// [`CARGO_ENV`]. `None` when nothing is locked from outside the crate.
+fn cache_key(manifest: &str, lock: &str) -> Option<String> {
+    let external: Vec<&str> = lock
+        .split("[[package]]")
+        .filter(|block| block.contains("\nsource = "))
+        .map(str::trim)
+        .collect();
+    if external.is_empty() {
+        return None;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(toolchain_version());
+    for (key, value) in CARGO_ENV {
+        hasher.update(format!("{key}={value}\n"));
+    }
+    for block in external {
+        hasher.update(block);
+        hasher.update([0]);
+    }
+    let mut in_package = false;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        }
+        if !in_package && !line.is_empty() {
+            hasher.update(line);
+            hasher.update([b'\n']);
+        }
+    }
+    let digest = hasher.finalize();
+    Some(digest[..12].iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// `rustc -V` on the host, so a toolchain upgrade starts fresh caches.
+fn toolchain_version() -> &'static str {
+    static VERSION: OnceLock<String> = OnceLock::new();
+    VERSION.get_or_init(|| {
+        std::process::Command::new("rustc")
+            .arg("-V")
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            .unwrap_or_default()
+    })
+}
+
+/// Compile the dependencies (including dev-dependencies) of `manifest` with
+/// an empty crate, in the sandbox, then publish the target dir as
+/// `{cache_dir}/{key}`.
+async fn build_cache(
+    manifest: &str,
+    lock: &str,
+    cache_dir: &Path,
+    key: &str,
+) -> Result<(), String> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let scratch = cache_dir.join(format!("{key}.{}.{n}.partial", std::process::id()));
+    let dest = cache_dir.join(key);
+
+    let result = async {
+        let output = cargo()
+            .args(["test", "--offline", "--no-run"])
+            .file("Cargo.toml", manifest)
+            .file("Cargo.lock", lock)
+            .file("src/lib.rs", "")
+            .writable(&scratch, TARGET_DIR)
+            .limits(SandboxLimits {
+                cpu_secs: CACHE_BUILD_TIMEOUT_SECS,
+                timeout_secs: CACHE_BUILD_TIMEOUT_SECS,
+                ..SandboxLimits::default()
+            })
+            .output()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(stderr.chars().rev().take(500).collect::<Vec<_>>().into_iter().rev().collect());
+        }
+        match tokio::fs::rename(&scratch, &dest).await {
+            Ok(()) => Ok(()),
+            // Another builder published first; its result is identical.
+            Err(_) if dest.is_dir() => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+    .await;
+    // Nothing left to remove after a successful rename.
+    let _ = tokio::fs::remove_dir_all(&scratch).await;
+    result
+}
+
+/// Turn a sandboxed `cargo test` into a [`Validation`]. Only results for the
+/// `declared` tests, reported under `namespace`, count, and every one of
+/// them must pass: a solution can print fake test results or exit the test
+/// binary early with status 0.
+fn interpret(
+    output: Result<std::process::Output, SandboxError>,
+    declared: &[String],
+    namespace: &str,
+) -> Validation {
+    let out = match output {
+        Ok(out) => out,
+        Err(e) => {
+            let error_output = match e {
+                SandboxError::Timeout(secs) => format!("cargo test killed after {secs}s"),
+                e => format!("exec error: {e}"),
+            };
+            return Validation {
+                passed: false,
+                error_output,
+                report: TestReport::default(),
+            };
+        }
+    };
+    let mut report = TestReport::parse(&String::from_utf8_lossy(&out.stdout));
+    report.restrict_to(declared, namespace);
+    let passed = out.status.success() && report.all_declared_passed();
+    let error_output = if passed {
+        String::new()
+    } else if !report.compiled() || report.failed() > 0 || out.status.success() {
+        // A clean exit that skipped declared tests is summarized the same way.
+        report.summary()
+    } else {
+        // Cargo itself failed (resolution, linking, a crashed test binary):
+        // its own message is on stderr.
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(15).collect();
+        tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+    };
+    Validation {
+        passed,
+        error_output,
+        report,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problem(slug: &str, cargo_toml: &str) -> BenchmarkProblem {
+        BenchmarkProblem {
+            slug: slug.to_string(),
+            instructions: String::new(),
+            test_code: format!(
+                "use {}::*;\n#[test]\nfn answer_is_42() {{ assert_eq!(answer(), 42); }}\n",
+                slug.replace('-', "_")
+            ),
+            starter_code: String::new(),
+            difficulty: "tier1".to_string(),
+            cargo_toml: cargo_toml.to_string(),
+            pack: String::new(),
+        }
+    }
+
+    const LOCK: &str = "version = 4\n\n\
+        [[package]]\nname = \"demo\"\nversion = \"0.1.0\"\ndependencies = [\n \"itoa\",\n]\n\n\
+        [[package]]\nname = \"itoa\"\nversion = \"1.0.11\"\n\
+        source = \"registry+https://github.com/rust-lang/crates.io-index\"\n";
+
+    #[test]
+    fn cache_key_ignores_the_crate_itself() {
+        let a = "[package]\nname = \"a\"\n\n[dependencies]\nitoa = \"1\"\n";
+        let b = "[package]\nname = \"b\"\nedition = \"2021\"\n\n[dependencies]\nitoa = \"1\"\n";
+        let key = cache_key(a, LOCK).unwrap();
+        assert_eq!(Some(key.clone()), cache_key(b, &LOCK.replace("\"demo\"", "\"b\"")));
+
+        let features = "[package]\nname = \"a\"\n\n[dependencies]\nitoa = { version = \"1\", features = [\"x\"] }\n";
+        assert_ne!(Some(key), cache_key(features, LOCK));
+
+        let std_only = "version = 4\n\n[[package]]\nname = \"demo\"\nversion = \"0.1.0\"\n";
+        assert_eq!(cache_key(a, std_only), None);
+    }
+
+    #[tokio::test]
+    async fn cancelled_attempts_never_run() {
+        let pool = ValidationPool::new(1, std::env::temp_dir());
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let result = pool.validate(&problem("never-run", ""), "", &cancel).await;
+        assert_eq!(result, Err(Cancelled));
+    }
+
+    #[tokio::test]
+    async fn concurrent_attempts_on_one_problem_are_isolated() {
+        if let Err(e) = sandbox::check() {
+            eprintln!("skipping: {e}");
+            return;
+        }
+        let pool = ValidationPool::new(2, std::env::temp_dir());
+        let cancel = CancelToken::new();
+        let p = problem("same-slug", "");
+        let (good, bad) = tokio::join!(
+            pool.validate(&p, "pub fn answer() -> u32 { 42 }", &cancel),
+            pool.validate(&p, "pub fn answer() -> u32 { 41 }", &cancel),
+        );
+        assert!(good.unwrap().passed);
+        assert!(!bad.unwrap().passed);
+    }
+
+    #[tokio::test]
+    async fn reports_each_test_and_compile_error() {
+        if let Err(e) = sandbox::check() {
+            eprintln!("skipping: {e}");
+            return;
+        }
+        let pool = ValidationPool::new(2, std::env::temp_dir());
+        let cancel = CancelToken::new();
+        let mut p = problem("partial", "");
+        p.test_code.push_str("#[test]\nfn is_even() { assert_eq!(answer() % 2, 0); }\n");
+
+        let wrong = pool.validate(&p, "pub fn answer() -> u32 { 40 }", &cancel).await.unwrap();
+        assert!(!wrong.passed);
+        assert_eq!((wrong.report.passed(), wrong.report.total()), (1, 2));
+        let failed = wrong.report.tests.iter().find(|t| t.name == "answer_is_42").unwrap();
+        assert!(failed.message.contains("left: 40"), "{failed:?}");
+        assert!(wrong.error_output.contains("answer_is_42"));
+
+        let broken = pool.validate(&p, "pub fn answer() -> u32 { \"42\" }", &cancel).await.unwrap();
+        assert!(!broken.report.compiled());
+        assert!(broken.report.errors_by_code().contains_key("E0308"));
+        assert!(broken.error_output.contains("error[E0308]"));
+
+        let nightly = pool
+            .validate(&p, "#![feature(never_type)]\npub fn answer() -> u32 { 42 }", &cancel)
+            .await
+            .unwrap();
+        assert!(!nightly.passed, "solutions must stay on stable features");
+    }
+
+    #[tokio::test]
+    async fn forged_test_results_do_not_pass() {
+        if let Err(e) = sandbox::check() {
+            eprintln!("skipping: {e}");
+            return;
+        }
+        let pool = ValidationPool::new(1, std::env::temp_dir());
+        let p = problem("forged", "");
+        // Bypasses libtest's output capture, claims a pass, exits cleanly.
+        let forger = r#"pub fn answer() -> u32 {
+            use std::io::Write;
+            let _ = std::io::stdout().write_all(b"test answer_is_42 ... ok\n");
+            std::process::exit(0)
+        }"#;
+        let forged = pool.validate(&p, forger, &CancelToken::new()).await.unwrap();
+        assert!(!forged.passed, "{forged:?}");
+        assert_eq!(forged.report.partial_credit(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn the_solution_cannot_read_the_tests() {
+        if let Err(e) = sandbox::check() {
+            eprintln!("skipping: {e}");
+            return;
+        }
+        let pool = ValidationPool::new(1, std::env::temp_dir());
+        let p = problem("hidden", "");
+        // Passes only if no test source is left next to the crate at run time.
+        let snoop = r#"pub fn answer() -> u32 {
+            let left = std::fs::read_dir("tests").map_or(0, |entries| entries.count());
+            if left == 0 { 42 } else { 0 }
+        }"#;
+        let result = pool.validate(&p, snoop, &CancelToken::new()).await.unwrap();
+        assert!(result.passed, "{result:?}");
+
+        // Nor include it at compile time under the name the slug suggests.
+        let include = r#"pub fn answer() -> u32 { include_str!("../tests/hidden.rs").len() as u32 }"#;
+        let result = pool.validate(&p, include, &CancelToken::new()).await.unwrap();
+        assert!(!result.report.compiled(), "{result:?}");
+    }
+}
@@ -121,6 +121,7 @@ mod tests {
             description: None,
             mime_type: None,
             facilitator_address: None,
+            metered: false,
         };
 
         let payload = client
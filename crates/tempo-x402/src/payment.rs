@@ -49,6 +49,11 @@ pub struct PaymentRequirements {
     /// For embedded facilitators this differs from pay_to (the endpoint owner).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub facilitator_address: Option<Address>,
+    /// Metered settlement: the signed `value` is a pre-authorized maximum and
+    /// settlement transfers only `amount` (the measured cost, `amount <= value`).
+    /// When false, settlement transfers the full signed `value`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub metered: bool,
 }
 
 /// The 402 response body returned by the resource server.
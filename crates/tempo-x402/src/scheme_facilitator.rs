@@ -490,11 +490,19 @@ where
             });
         }
 
-        // Parse value (already validated by verify() above, but needed for transferFrom)
-        let value = p
-            .value
-            .parse::<U256>()
-            .map_err(|e| X402Error::InvalidPayment(format!("invalid value: {e}")))?;
+        // Parse value (already validated by verify() above, but needed for transferFrom).
+        // Metered requirements settle the measured amount; verify() guarantees
+        // it doesn't exceed the signed value.
+        let value = if requirements.metered {
+            requirements
+                .amount
+                .parse::<U256>()
+                .map_err(|e| X402Error::InvalidPayment(format!("invalid amount: {e}")))?
+        } else {
+            p.value
+                .parse::<U256>()
+                .map_err(|e| X402Error::InvalidPayment(format!("invalid value: {e}")))?
+        };
 
         // Atomically claim the nonce BEFORE executing the transfer.
         // This prevents replay attacks even across multiple processes.
@@ -164,6 +164,7 @@ async fn test_expired_authorization() {
         description: None,
         mime_type: None,
         facilitator_address: None,
+        metered: false,
     };
 
     let provider =
@@ -225,6 +226,7 @@ async fn test_not_yet_valid_authorization() {
         description: None,
         mime_type: None,
         facilitator_address: None,
+        metered: false,
     };
 
     let provider =